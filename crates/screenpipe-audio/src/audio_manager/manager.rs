@@ -392,6 +392,14 @@ impl AudioManager {
             .collect::<Vec<AudioDevice>>()
     }
 
+    /// Audio chunks waiting for transcription and transcriptions waiting to be stored.
+    pub fn queue_depths(&self) -> (usize, usize) {
+        (
+            self.recording_receiver.len(),
+            self.transcription_receiver.len(),
+        )
+    }
+
     pub async fn enabled_devices(&self) -> HashSet<String> {
         self.options.read().await.enabled_devices.clone()
     }
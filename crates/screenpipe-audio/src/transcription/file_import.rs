@@ -0,0 +1,145 @@
+//! Transcription of existing recordings (`screenpipe add`), through the same VAD,
+//! diarization and STT steps as live capture.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use screenpipe_core::Language;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+use whisper_rs::WhisperContext;
+
+use crate::{
+    core::{device::AudioDevice, engine::AudioTranscriptionEngine},
+    pcm_decode, resample,
+    segmentation::segmentation_manager::SegmentationManager,
+    speaker::prepare_segments,
+    transcription::{
+        stt::{run_stt, SAMPLE_RATE},
+        whisper::model::{create_whisper_context_parameters, download_whisper_model},
+    },
+    vad::{silero::SileroVad, webrtc::WebRtcVad, VadEngine, VadEngineEnum},
+    TranscriptionResult,
+};
+
+/// Seconds of audio segmented at once. `prepare_segments` buffers the segments of a whole
+/// window, so windows stay close to live chunks. Speakers are matched across windows by
+/// their embeddings.
+const WINDOW_SECS: usize = 60;
+
+pub struct AudioFileTranscriber {
+    vad_engine: Arc<Mutex<Box<dyn VadEngine + Send>>>,
+    segmentation_manager: SegmentationManager,
+    whisper_context: Arc<WhisperContext>,
+    transcription_engine: Arc<AudioTranscriptionEngine>,
+    deepgram_api_key: Option<String>,
+    languages: Vec<Language>,
+}
+
+impl AudioFileTranscriber {
+    pub async fn new(
+        transcription_engine: Arc<AudioTranscriptionEngine>,
+        vad_engine: VadEngineEnum,
+        deepgram_api_key: Option<String>,
+        languages: Vec<Language>,
+    ) -> Result<Self> {
+        let vad_engine: Arc<Mutex<Box<dyn VadEngine + Send>>> = match vad_engine {
+            VadEngineEnum::Silero => Arc::new(Mutex::new(Box::new(SileroVad::new().await?))),
+            VadEngineEnum::WebRtc => Arc::new(Mutex::new(Box::new(WebRtcVad::new()))),
+        };
+        let segmentation_manager = SegmentationManager::new().await?;
+
+        let model_path = download_whisper_model(transcription_engine.clone())?;
+        let context_param = create_whisper_context_parameters(transcription_engine.clone())?;
+        whisper_rs::install_logging_hooks();
+        let whisper_context = Arc::new(WhisperContext::new_with_params(
+            &model_path.to_string_lossy(),
+            context_param,
+        )?);
+
+        Ok(Self {
+            vad_engine,
+            segmentation_manager,
+            whisper_context,
+            transcription_engine,
+            deepgram_api_key,
+            languages,
+        })
+    }
+
+    /// Transcribe the first audio track of `path` and send one result per speech segment.
+    ///
+    /// Results are attributed to `device`, refer to `chunk_path` (the file as stored in
+    /// the data dir) and are stamped `recorded_at` plus their offset in the file. Returns
+    /// the number of segments sent.
+    pub async fn transcribe_file(
+        &self,
+        path: &Path,
+        chunk_path: &str,
+        device: Arc<AudioDevice>,
+        recorded_at: DateTime<Utc>,
+        sender: &crossbeam::channel::Sender<TranscriptionResult>,
+    ) -> Result<usize> {
+        let path: PathBuf = path.to_path_buf();
+        let audio = tokio::task::spawn_blocking(move || -> Result<Vec<f32>> {
+            let (data, sample_rate) = pcm_decode(&path)?;
+            if sample_rate == SAMPLE_RATE {
+                Ok(data)
+            } else {
+                resample(&data, sample_rate, SAMPLE_RATE)
+            }
+        })
+        .await??;
+        info!(
+            "transcribing {:.0}s of audio from {}",
+            audio.len() as f64 / SAMPLE_RATE as f64,
+            chunk_path
+        );
+
+        let mut sent = 0;
+        for (window, samples) in audio.chunks(WINDOW_SECS * SAMPLE_RATE as usize).enumerate() {
+            let window_offset = (window * WINDOW_SECS) as f64;
+            let (mut segments, speech_ratio_ok) = prepare_segments(
+                samples,
+                self.vad_engine.clone(),
+                &self.segmentation_manager.segmentation_model_path,
+                self.segmentation_manager.embedding_manager.clone(),
+                self.segmentation_manager.embedding_extractor.clone(),
+                &device.to_string(),
+            )
+            .await?;
+            if !speech_ratio_ok {
+                debug!("no speech in window {} of {}", window, chunk_path);
+                continue;
+            }
+
+            while let Some(mut segment) = segments.recv().await {
+                segment.start += window_offset;
+                segment.end += window_offset;
+                let segment_time =
+                    recorded_at + Duration::milliseconds((segment.start * 1000.0) as i64);
+
+                let mut result = run_stt(
+                    segment,
+                    device.clone(),
+                    self.transcription_engine.clone(),
+                    self.deepgram_api_key.clone(),
+                    self.languages.clone(),
+                    chunk_path.to_string(),
+                    segment_time.timestamp().max(0) as u64,
+                    self.whisper_context.clone(),
+                )
+                .await?;
+                result.recorded_at = Some(segment_time);
+
+                if sender.send(result).is_err() {
+                    return Ok(sent);
+                }
+                sent += 1;
+            }
+        }
+
+        Ok(sent)
+    }
+}
@@ -94,6 +94,7 @@ pub async fn handle_new_transcript(
         }
 
         TRANSCRIPTS_TOTAL.fetch_add(1, Ordering::SeqCst);
+        metrics::counter!("screenpipe_audio_transcripts_total").increment(1);
 
         info!(
             "device {} received transcription ({} chars)",
@@ -112,6 +113,7 @@ pub async fn handle_new_transcript(
             // If current is empty after cleanup, the entire transcript was a duplicate - skip it
             if current.is_empty() {
                 TRANSCRIPTS_DUPLICATE_BLOCKED.fetch_add(1, Ordering::SeqCst);
+                metrics::counter!("screenpipe_audio_duplicates_blocked_total").increment(1);
                 info!(
                     "device {} skipping duplicate transcript (entire content overlaps with previous)",
                     transcription.input.device
@@ -129,6 +131,7 @@ pub async fn handle_new_transcript(
                 current_transcript = Some(current);
                 was_trimmed = true;
                 TRANSCRIPTS_OVERLAP_TRIMMED.fetch_add(1, Ordering::SeqCst);
+                metrics::counter!("screenpipe_audio_overlaps_trimmed_total").increment(1);
             }
         }
 
@@ -162,7 +165,9 @@ pub async fn handle_new_transcript(
             Ok(id) => {
                 previous_transcript_id = id;
                 TRANSCRIPTS_INSERTED.fetch_add(1, Ordering::SeqCst);
+                metrics::counter!("screenpipe_audio_transcripts_inserted_total").increment(1);
                 TRANSCRIPTS_WORD_COUNT_TOTAL.fetch_add(word_count as u64, Ordering::SeqCst);
+                metrics::counter!("screenpipe_audio_words_total").increment(word_count as u64);
 
                 if was_trimmed {
                     info!(
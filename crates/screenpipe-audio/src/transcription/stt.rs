@@ -229,6 +229,7 @@ pub async fn run_stt(
             speaker_embedding: segment.embedding.clone(),
             start_time: segment.start,
             end_time: segment.end,
+            recorded_at: None,
         }),
         Err(e) => {
             error!("STT error for input {}: {:?}", device, e);
@@ -246,6 +247,7 @@ pub async fn run_stt(
                 speaker_embedding: Vec::new(),
                 start_time: segment.start,
                 end_time: segment.end,
+                recorded_at: None,
             })
         }
     }
@@ -1,5 +1,7 @@
 use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
+
 use screenpipe_core::pii_removal::remove_pii;
 use screenpipe_db::{DatabaseManager, Speaker};
 use tracing::{debug, error, info};
@@ -18,6 +20,9 @@ pub struct TranscriptionResult {
     pub error: Option<String>,
     pub start_time: f64,
     pub end_time: f64,
+    /// When the segment was spoken, for imported recordings. Live results are stamped
+    /// when inserted.
+    pub recorded_at: Option<DateTime<Utc>>,
 }
 
 impl TranscriptionResult {
@@ -109,29 +114,43 @@ pub async fn process_transcription_result(
                 return Ok(Some(audio_chunk_id));
             }
 
-            if let Err(e) = db
-                .insert_audio_transcription(
-                    audio_chunk_id,
-                    &transcription,
-                    0,
-                    &transcription_engine,
-                    &screenpipe_db::AudioDevice {
-                        name: result.input.device.name.clone(),
-                        device_type: match result.input.device.device_type {
-                            crate::core::device::DeviceType::Input => {
-                                screenpipe_db::DeviceType::Input
-                            }
-                            crate::core::device::DeviceType::Output => {
-                                screenpipe_db::DeviceType::Output
-                            }
-                        },
-                    },
-                    Some(speaker.id),
-                    Some(result.start_time),
-                    Some(result.end_time),
-                )
-                .await
-            {
+            let device = screenpipe_db::AudioDevice {
+                name: result.input.device.name.clone(),
+                device_type: match result.input.device.device_type {
+                    crate::core::device::DeviceType::Input => screenpipe_db::DeviceType::Input,
+                    crate::core::device::DeviceType::Output => screenpipe_db::DeviceType::Output,
+                },
+            };
+            let inserted = match result.recorded_at {
+                Some(recorded_at) => {
+                    db.insert_audio_transcription_at(
+                        audio_chunk_id,
+                        &transcription,
+                        0,
+                        &transcription_engine,
+                        &device,
+                        Some(speaker.id),
+                        Some(result.start_time),
+                        Some(result.end_time),
+                        recorded_at,
+                    )
+                    .await
+                }
+                None => {
+                    db.insert_audio_transcription(
+                        audio_chunk_id,
+                        &transcription,
+                        0,
+                        &transcription_engine,
+                        &device,
+                        Some(speaker.id),
+                        Some(result.start_time),
+                        Some(result.end_time),
+                    )
+                    .await
+                }
+            };
+            if let Err(e) = inserted {
                 error!(
                     "Failed to insert audio transcription for device {}: {}",
                     result.input.device, e
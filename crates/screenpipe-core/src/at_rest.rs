@@ -0,0 +1,729 @@
+//! Encryption at rest for the local database and media files.
+//!
+//! Opt-in: when enabled, `db.sqlite` is opened with a SQLCipher key and finished
+//! video/audio chunks are sealed in place with chunked ChaCha20-Poly1305. Readers go
+//! through [`readable_path`], which hands back a decrypted temporary copy of sealed
+//! files and the file itself otherwise.
+//!
+//! Decrypted copies live in `data/.decrypted/<process>` (see [`init_decrypted_dir`]) and
+//! are deleted as soon as the last reader lets go of them. The directory is removed on
+//! shutdown, and copies left behind by a crash on the next start.
+//!
+//! ## Key Hierarchy
+//!
+//! ```text
+//! Passphrase + Salt → Argon2id → Passphrase Key           OS keyring
+//!                                      ↓                       ↓
+//!                       Decrypts Encrypted Master Key  (or)  Master Key
+//!                                      ↓
+//!                                 Master Key
+//!                     ┌────────────────┴────────────────┐
+//!                     ↓                                 ↓
+//!               Database Key                        Media Key
+//!                (SQLCipher)               (chunked ChaCha20-Poly1305)
+//! ```
+//!
+//! The wrapped master key lives in `at-rest-key.json` next to the database, so the
+//! passphrase can't be changed without re-encrypting but losing it means losing the data.
+//!
+//! ## Sealed file format
+//!
+//! ```text
+//! magic (8) | nonce prefix (7) | chunk 0 | chunk 1 | ... | last chunk
+//! ```
+//!
+//! Every chunk holds up to 1 MiB of plaintext plus a 16 byte tag. Its nonce is the prefix,
+//! the chunk counter (u32, big endian) and a last-chunk flag, and the header is bound as
+//! associated data, so chunks can't be reordered, dropped or truncated unnoticed.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::SystemTime;
+use tempfile::TempPath;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::sync::crypto::{
+    decrypt, derive_key_from_password, encrypt, generate_key, generate_nonce, generate_salt,
+    KEY_SIZE, NONCE_SIZE, SALT_SIZE,
+};
+use crate::sync::error::{SyncError, SyncResult};
+
+/// Domain separation constants for key derivation
+const DATABASE_KEY_DOMAIN: &[u8] = b"screenpipe-at-rest-database-key-v1";
+const MEDIA_KEY_DOMAIN: &[u8] = b"screenpipe-at-rest-media-key-v1";
+const KEY_CHECK_DOMAIN: &[u8] = b"screenpipe-at-rest-key-check-v1";
+
+/// File holding the wrapped master key, in the screenpipe data directory
+pub const KEY_FILE_NAME: &str = "at-rest-key.json";
+
+/// OS keyring entry holding the master key when no passphrase is used
+const KEYRING_SERVICE: &str = "screenpipe";
+const KEYRING_USER: &str = "at-rest-master-key";
+
+/// First bytes of every sealed media file
+pub const MAGIC: &[u8; 8] = b"SPENC\x00\x00\x01";
+const NONCE_PREFIX_SIZE: usize = 7;
+const HEADER_SIZE: usize = MAGIC.len() + NONCE_PREFIX_SIZE;
+const TAG_SIZE: usize = 16;
+
+/// Plaintext bytes per sealed chunk
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Directory of the decrypted media copies, in the data directory
+const DECRYPTED_DIR_NAME: &str = ".decrypted";
+
+/// Held locked by the process owning a directory of decrypted copies
+const DECRYPTED_LOCK_NAME: &str = ".lock";
+
+/// Where the master key comes from.
+#[derive(Clone)]
+pub enum KeySource {
+    /// Wrapped with a key derived from this passphrase
+    Passphrase(Zeroizing<String>),
+    /// Stored in the OS keyring (macOS Keychain, Windows Credential Manager, Secret Service)
+    Keyring,
+}
+
+impl KeySource {
+    pub fn passphrase(passphrase: impl Into<String>) -> Self {
+        KeySource::Passphrase(Zeroizing::new(passphrase.into()))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            KeySource::Passphrase(_) => "passphrase",
+            KeySource::Keyring => "keyring",
+        }
+    }
+}
+
+/// On-disk description of the master key.
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyFile {
+    version: u32,
+    /// "passphrase" or "keyring"
+    source: String,
+    /// Passphrase mode only (base64)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encrypted_master_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    master_key_nonce: Option<String>,
+    /// HMAC of a constant with the master key, to detect a wrong key before touching data
+    key_check: String,
+}
+
+/// The keys used for encryption at rest. They are zeroized when dropped.
+#[derive(Zeroize)]
+#[zeroize(drop)]
+pub struct AtRestKeys {
+    database_key: [u8; KEY_SIZE],
+    media_key: [u8; KEY_SIZE],
+}
+
+fn hmac_domain(key: &[u8; KEY_SIZE], domain: &[u8]) -> [u8; KEY_SIZE] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap();
+    mac.update(domain);
+    let mut out = [0u8; KEY_SIZE];
+    out.copy_from_slice(&mac.finalize().into_bytes());
+    out
+}
+
+fn decode_base64(field: &str, value: Option<&String>) -> SyncResult<Vec<u8>> {
+    let value = value.ok_or_else(|| SyncError::Key(format!("key file is missing {}", field)))?;
+    BASE64
+        .decode(value)
+        .map_err(|e| SyncError::Key(format!("invalid {} in key file: {}", field, e)))
+}
+
+impl AtRestKeys {
+    /// Derive the database and media keys from the master key.
+    pub fn from_master_key(master_key: &[u8; KEY_SIZE]) -> Self {
+        Self {
+            database_key: hmac_domain(master_key, DATABASE_KEY_DOMAIN),
+            media_key: hmac_domain(master_key, MEDIA_KEY_DOMAIN),
+        }
+    }
+
+    /// Load the master key of `data_dir`, creating it on first use.
+    pub fn load_or_create(data_dir: &Path, source: &KeySource) -> SyncResult<Self> {
+        let path = data_dir.join(KEY_FILE_NAME);
+        if !path.exists() {
+            return Self::create(&path, source);
+        }
+
+        let key_file: KeyFile = serde_json::from_slice(&std::fs::read(&path)?)
+            .map_err(|e| SyncError::Serialization(format!("invalid key file: {}", e)))?;
+        if key_file.source != source.name() {
+            return Err(SyncError::Key(format!(
+                "data was encrypted with a {} key, not a {} key",
+                key_file.source,
+                source.name()
+            )));
+        }
+
+        let master_key = unwrap_master_key(&key_file, source)?;
+        if hex::encode(hmac_domain(&master_key, KEY_CHECK_DOMAIN)) != key_file.key_check {
+            return Err(SyncError::Auth(
+                "encryption key does not match this data directory".to_string(),
+            ));
+        }
+        Ok(Self::from_master_key(&master_key))
+    }
+
+    fn create(path: &Path, source: &KeySource) -> SyncResult<Self> {
+        let master_key = generate_key();
+        let mut key_file = KeyFile {
+            version: 1,
+            source: source.name().to_string(),
+            salt: None,
+            encrypted_master_key: None,
+            master_key_nonce: None,
+            key_check: hex::encode(hmac_domain(&master_key, KEY_CHECK_DOMAIN)),
+        };
+
+        match source {
+            KeySource::Passphrase(passphrase) => {
+                let salt = generate_salt();
+                let nonce = generate_nonce();
+                let passphrase_key = derive_key_from_password(passphrase, &salt)?;
+                let encrypted = encrypt(&master_key[..], &passphrase_key, &nonce)?;
+                key_file.salt = Some(BASE64.encode(salt));
+                key_file.encrypted_master_key = Some(BASE64.encode(encrypted));
+                key_file.master_key_nonce = Some(BASE64.encode(nonce));
+            }
+            KeySource::Keyring => {
+                let encoded = Zeroizing::new(BASE64.encode(&master_key[..]));
+                keyring_entry()?
+                    .set_password(&encoded)
+                    .map_err(|e| SyncError::Key(format!("failed to write keyring: {}", e)))?;
+            }
+        }
+
+        let json = serde_json::to_vec_pretty(&key_file)
+            .map_err(|e| SyncError::Serialization(e.to_string()))?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(Self::from_master_key(&master_key))
+    }
+
+    /// SQLCipher raw key, as the value of `PRAGMA key`.
+    pub fn database_key_pragma(&self) -> Zeroizing<String> {
+        Zeroizing::new(format!("\"x'{}'\"", hex::encode(self.database_key)))
+    }
+
+    /// Get the media encryption key.
+    pub fn media_key(&self) -> &[u8; KEY_SIZE] {
+        &self.media_key
+    }
+}
+
+fn to_key(bytes: &[u8]) -> SyncResult<[u8; KEY_SIZE]> {
+    bytes.try_into().map_err(|_| {
+        SyncError::Key(format!(
+            "invalid master key length: expected {}, got {}",
+            KEY_SIZE,
+            bytes.len()
+        ))
+    })
+}
+
+/// Recover the master key described by `key_file` from `source`.
+fn unwrap_master_key(
+    key_file: &KeyFile,
+    source: &KeySource,
+) -> SyncResult<Zeroizing<[u8; KEY_SIZE]>> {
+    let master_key = match source {
+        KeySource::Passphrase(passphrase) => {
+            let salt: [u8; SALT_SIZE] =
+                decode_base64("salt", key_file.salt.as_ref())?
+                    .try_into()
+                    .map_err(|_| SyncError::Key("invalid salt length".to_string()))?;
+            let nonce: [u8; NONCE_SIZE] =
+                decode_base64("master_key_nonce", key_file.master_key_nonce.as_ref())?
+                    .try_into()
+                    .map_err(|_| SyncError::Key("invalid nonce length".to_string()))?;
+            let encrypted = decode_base64(
+                "encrypted_master_key",
+                key_file.encrypted_master_key.as_ref(),
+            )?;
+            let passphrase_key = derive_key_from_password(passphrase, &salt)?;
+            decrypt(&encrypted, &passphrase_key, &nonce)
+                .map_err(|_| SyncError::Auth("wrong encryption passphrase".to_string()))?
+        }
+        KeySource::Keyring => {
+            let encoded = Zeroizing::new(
+                keyring_entry()?
+                    .get_password()
+                    .map_err(|e| SyncError::Key(format!("failed to read keyring: {}", e)))?,
+            );
+            BASE64
+                .decode(encoded.as_bytes())
+                .map_err(|e| SyncError::Key(format!("invalid key in keyring: {}", e)))?
+        }
+    };
+    let master_key = Zeroizing::new(master_key);
+    Ok(Zeroizing::new(to_key(&master_key)?))
+}
+
+fn keyring_entry() -> SyncResult<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .map_err(|e| SyncError::Key(format!("failed to open keyring: {}", e)))
+}
+
+// ============================================================================
+// Media files
+// ============================================================================
+
+static MEDIA_KEY: OnceLock<Zeroizing<[u8; KEY_SIZE]>> = OnceLock::new();
+
+/// Use `keys` to read sealed media for the rest of the process.
+pub fn install_media_key(keys: &AtRestKeys) {
+    let _ = MEDIA_KEY.set(Zeroizing::new(keys.media_key));
+}
+
+/// The installed media key, if encryption at rest is enabled.
+pub fn media_key() -> Option<&'static [u8; KEY_SIZE]> {
+    MEDIA_KEY.get().map(|key| &**key)
+}
+
+fn chunk_nonce(prefix: &[u8], counter: u32, last: bool) -> [u8; NONCE_SIZE] {
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce[..NONCE_PREFIX_SIZE].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_SIZE..NONCE_SIZE - 1].copy_from_slice(&counter.to_be_bytes());
+    nonce[NONCE_SIZE - 1] = last as u8;
+    nonce
+}
+
+/// Read until `buf` is full or the reader is exhausted.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Run `f` on every `size` bytes of `reader`, telling it which piece is the last one.
+fn for_each_chunk(
+    reader: &mut impl Read,
+    size: usize,
+    mut f: impl FnMut(&[u8], u32, bool) -> SyncResult<()>,
+) -> SyncResult<()> {
+    let mut current = vec![0u8; size];
+    let mut next = vec![0u8; size];
+    let mut current_len = read_full(reader, &mut current)?;
+    let mut counter: u32 = 0;
+    loop {
+        let next_len = if current_len == size {
+            read_full(reader, &mut next)?
+        } else {
+            0
+        };
+        let last = next_len == 0;
+        f(&current[..current_len], counter, last)?;
+        if last {
+            return Ok(());
+        }
+        counter = counter
+            .checked_add(1)
+            .ok_or_else(|| SyncError::Crypto("file too large to seal".to_string()))?;
+        std::mem::swap(&mut current, &mut next);
+        current_len = next_len;
+    }
+}
+
+/// Encrypt `reader` into `writer` in the sealed file format.
+pub fn encrypt_stream(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    key: &[u8; KEY_SIZE],
+) -> SyncResult<()> {
+    let cipher = ChaCha20Poly1305::new_from_slice(key)
+        .map_err(|e| SyncError::Crypto(format!("invalid key: {}", e)))?;
+    let mut header = [0u8; HEADER_SIZE];
+    header[..MAGIC.len()].copy_from_slice(MAGIC);
+    rand::thread_rng().fill_bytes(&mut header[MAGIC.len()..]);
+    writer.write_all(&header)?;
+
+    let prefix = &header[MAGIC.len()..];
+    for_each_chunk(reader, CHUNK_SIZE, |chunk, counter, last| {
+        let nonce = chunk_nonce(prefix, counter, last);
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: chunk,
+                    aad: &header,
+                },
+            )
+            .map_err(|e| SyncError::Crypto(format!("encryption failed: {}", e)))?;
+        writer.write_all(&ciphertext)?;
+        Ok(())
+    })
+}
+
+/// Decrypt a sealed `reader` into `writer`.
+pub fn decrypt_stream(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    key: &[u8; KEY_SIZE],
+) -> SyncResult<()> {
+    let cipher = ChaCha20Poly1305::new_from_slice(key)
+        .map_err(|e| SyncError::Crypto(format!("invalid key: {}", e)))?;
+    let mut header = [0u8; HEADER_SIZE];
+    if read_full(reader, &mut header)? != HEADER_SIZE || &header[..MAGIC.len()] != MAGIC {
+        return Err(SyncError::DataCorruption(
+            "not a sealed media file".to_string(),
+        ));
+    }
+
+    let prefix = &header[MAGIC.len()..];
+    for_each_chunk(reader, CHUNK_SIZE + TAG_SIZE, |chunk, counter, last| {
+        let nonce = chunk_nonce(prefix, counter, last);
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: chunk,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| {
+                SyncError::Crypto(format!(
+                    "decryption failed at chunk {} (wrong key or corrupted file)",
+                    counter
+                ))
+            })?;
+        writer.write_all(&plaintext)?;
+        Ok(())
+    })
+}
+
+/// Whether `path` is a sealed media file.
+pub fn is_sealed(path: &Path) -> std::io::Result<bool> {
+    let mut magic = [0u8; MAGIC.len()];
+    let n = read_full(&mut File::open(path)?, &mut magic)?;
+    Ok(n == MAGIC.len() && &magic == MAGIC)
+}
+
+/// Encrypt `path` in place. Returns false if it was already sealed.
+///
+/// The sealed copy is written next to the file and renamed over it, so readers see
+/// either the plaintext or the sealed file, never a partial one.
+pub fn seal_file(path: &Path, key: &[u8; KEY_SIZE]) -> SyncResult<bool> {
+    if is_sealed(path)? {
+        return Ok(false);
+    }
+
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".sealing");
+    let tmp = PathBuf::from(tmp_name);
+    let result = (|| -> SyncResult<bool> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        encrypt_stream(&mut reader, &mut writer, key)?;
+        let file = writer
+            .into_inner()
+            .map_err(|e| SyncError::Io(e.into_error()))?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(true)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// A media file that can be handed to ffmpeg or read directly.
+///
+/// For sealed files this is a decrypted copy that is deleted once the last
+/// `ReadablePath` of it is dropped.
+#[derive(Clone)]
+pub struct ReadablePath {
+    path: PathBuf,
+    _decrypted: Option<Arc<TempPath>>,
+}
+
+impl ReadablePath {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.path.to_str().unwrap_or_default()
+    }
+}
+
+/// Decrypted copy shared by the readers of a sealed file, while there are some.
+struct DecryptedEntry {
+    source: PathBuf,
+    modified: Option<SystemTime>,
+    decrypted: Weak<TempPath>,
+}
+
+static DECRYPTED: Mutex<Vec<DecryptedEntry>> = Mutex::new(Vec::new());
+
+struct DecryptedDir {
+    path: PathBuf,
+    _lock: File,
+}
+
+static DECRYPTED_DIR: OnceLock<DecryptedDir> = OnceLock::new();
+
+/// Keep the decrypted copies of this process in a directory of `data_dir/.decrypted`,
+/// removing the directories of processes that are no longer running (their copies
+/// outlived a crash). Call before reading sealed media.
+pub fn init_decrypted_dir(data_dir: &Path) -> SyncResult<()> {
+    if DECRYPTED_DIR.get().is_some() {
+        return Ok(());
+    }
+    let root = data_dir.join(DECRYPTED_DIR_NAME);
+    std::fs::create_dir_all(&root)?;
+    // one process at a time, so a directory being set up isn't taken for abandoned
+    let root_lock = File::create(root.join(DECRYPTED_LOCK_NAME))?;
+    root_lock.lock()?;
+    for entry in std::fs::read_dir(&root)?.flatten() {
+        if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+            continue;
+        }
+        let dir = entry.path();
+        // the lock of a running process can't be taken
+        let abandoned = match File::open(dir.join(DECRYPTED_LOCK_NAME)) {
+            Ok(lock) => lock.try_lock().is_ok(),
+            Err(_) => true,
+        };
+        if abandoned {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+    }
+
+    // tempdir_in() creates the directory readable by the current user only
+    let path = tempfile::Builder::new().tempdir_in(&root)?.into_path();
+    let lock = File::create(path.join(DECRYPTED_LOCK_NAME))?;
+    lock.try_lock().map_err(std::io::Error::from)?;
+    let _ = DECRYPTED_DIR.set(DecryptedDir { path, _lock: lock });
+    Ok(())
+}
+
+/// Remove the decrypted copies of this process, on shutdown.
+pub fn remove_decrypted_dir() -> std::io::Result<()> {
+    match DECRYPTED_DIR.get() {
+        Some(dir) => {
+            DECRYPTED.lock().unwrap().clear();
+            std::fs::remove_dir_all(&dir.path)
+        }
+        None => Ok(()),
+    }
+}
+
+fn decrypted_dir() -> SyncResult<&'static Path> {
+    DECRYPTED_DIR
+        .get()
+        .map(|dir| dir.path.as_path())
+        .ok_or_else(|| SyncError::Key("decrypted media directory not initialized".to_string()))
+}
+
+fn decrypt_to_temp(path: &Path, key: &[u8; KEY_SIZE], dir: &Path) -> SyncResult<TempPath> {
+    let suffix = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let tmp = tempfile::Builder::new().suffix(&suffix).tempfile_in(dir)?;
+    let (file, tmp_path) = tmp.into_parts();
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut writer = BufWriter::new(file);
+    decrypt_stream(&mut reader, &mut writer, key)?;
+    writer.flush()?;
+    Ok(tmp_path)
+}
+
+/// Resolve a media file for reading, decrypting it when it is sealed.
+pub async fn readable_path(path: impl AsRef<Path>) -> SyncResult<ReadablePath> {
+    let path = path.as_ref().to_path_buf();
+    let sealed = match is_sealed(&path) {
+        Ok(sealed) => sealed,
+        // let the caller report missing files the way it always has
+        Err(_) => false,
+    };
+    if !sealed {
+        return Ok(ReadablePath {
+            path,
+            _decrypted: None,
+        });
+    }
+
+    let key = media_key().ok_or_else(|| {
+        SyncError::Key(format!(
+            "{} is encrypted at rest but no key is configured",
+            path.display()
+        ))
+    })?;
+    let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+
+    let shared = DECRYPTED
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|e| e.source == path && e.modified == modified)
+        .find_map(|e| e.decrypted.upgrade());
+    if let Some(decrypted) = shared {
+        return Ok(ReadablePath {
+            path: decrypted.to_path_buf(),
+            _decrypted: Some(decrypted),
+        });
+    }
+
+    let source = path.clone();
+    let dir = decrypted_dir()?;
+    let decrypted = tokio::task::spawn_blocking(move || decrypt_to_temp(&source, key, dir))
+        .await
+        .map_err(|e| SyncError::Crypto(format!("decryption task failed: {}", e)))??;
+    let decrypted = Arc::new(decrypted);
+
+    let mut cache = DECRYPTED.lock().unwrap();
+    cache.retain(|e| e.source != path && e.decrypted.strong_count() > 0);
+    cache.push(DecryptedEntry {
+        source: path,
+        modified,
+        decrypted: Arc::downgrade(&decrypted),
+    });
+    Ok(ReadablePath {
+        path: decrypted.to_path_buf(),
+        _decrypted: Some(decrypted),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(len: usize) {
+        let key = generate_key();
+        let plaintext: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+
+        let mut sealed = Vec::new();
+        encrypt_stream(&mut plaintext.as_slice(), &mut sealed, &key).unwrap();
+        assert!(sealed.starts_with(MAGIC));
+
+        let mut decrypted = Vec::new();
+        decrypt_stream(&mut sealed.as_slice(), &mut decrypted, &key).unwrap();
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn test_stream_roundtrip() {
+        roundtrip(0);
+        roundtrip(10);
+        roundtrip(CHUNK_SIZE);
+        roundtrip(2 * CHUNK_SIZE + 7);
+    }
+
+    #[test]
+    fn test_stream_rejects_tampering() {
+        let key = generate_key();
+        let plaintext = vec![7u8; 2 * CHUNK_SIZE + 100];
+        let mut sealed = Vec::new();
+        encrypt_stream(&mut plaintext.as_slice(), &mut sealed, &key).unwrap();
+
+        // wrong key
+        let other = generate_key();
+        assert!(decrypt_stream(&mut sealed.as_slice(), &mut Vec::new(), &other).is_err());
+
+        // flipped byte
+        let mut flipped = sealed.clone();
+        flipped[HEADER_SIZE + 10] ^= 1;
+        assert!(decrypt_stream(&mut flipped.as_slice(), &mut Vec::new(), &key).is_err());
+
+        // truncated at a chunk boundary
+        let truncated = &sealed[..HEADER_SIZE + 2 * (CHUNK_SIZE + TAG_SIZE)];
+        assert!(decrypt_stream(&mut &truncated[..], &mut Vec::new(), &key).is_err());
+    }
+
+    #[test]
+    fn test_seal_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("monitor_1.mp4");
+        std::fs::write(&path, b"not really a video").unwrap();
+        let key = generate_key();
+
+        assert!(!is_sealed(&path).unwrap());
+        assert!(seal_file(&path, &key).unwrap());
+        assert!(is_sealed(&path).unwrap());
+        assert!(!seal_file(&path, &key).unwrap());
+
+        let decrypted = decrypt_to_temp(&path, &key, dir.path()).unwrap();
+        assert_eq!(std::fs::read(&decrypted).unwrap(), b"not really a video");
+    }
+
+    #[tokio::test]
+    async fn test_decrypted_copies() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let abandoned = data_dir.path().join(DECRYPTED_DIR_NAME).join("crashed");
+        std::fs::create_dir_all(&abandoned).unwrap();
+        std::fs::write(abandoned.join("chunk.mp4"), b"plaintext").unwrap();
+
+        init_decrypted_dir(data_dir.path()).unwrap();
+        assert!(!abandoned.exists());
+
+        let keys = AtRestKeys::from_master_key(&generate_key());
+        install_media_key(&keys);
+        let path = data_dir.path().join("monitor_1.mp4");
+        std::fs::write(&path, b"not really a video").unwrap();
+        seal_file(&path, media_key().unwrap()).unwrap();
+
+        let first = readable_path(&path).await.unwrap();
+        let second = readable_path(&path).await.unwrap();
+        assert_eq!(first.path(), second.path());
+        assert!(first.path().starts_with(decrypted_dir().unwrap()));
+        assert_eq!(std::fs::read(first.path()).unwrap(), b"not really a video");
+
+        // deleted with the last reader
+        let decrypted = first.path().to_path_buf();
+        drop(first);
+        assert!(decrypted.exists());
+        drop(second);
+        assert!(!decrypted.exists());
+
+        remove_decrypted_dir().unwrap();
+        assert!(!decrypted_dir().unwrap().exists());
+    }
+
+    #[test]
+    fn test_key_file_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = KeySource::passphrase("correct horse");
+
+        let created = AtRestKeys::load_or_create(dir.path(), &source).unwrap();
+        let loaded = AtRestKeys::load_or_create(dir.path(), &source).unwrap();
+        assert_eq!(created.media_key(), loaded.media_key());
+        assert_eq!(
+            *created.database_key_pragma(),
+            *loaded.database_key_pragma()
+        );
+        assert_ne!(created.media_key(), &created.database_key);
+
+        let wrong = KeySource::passphrase("battery staple");
+        assert!(AtRestKeys::load_or_create(dir.path(), &wrong).is_err());
+        assert!(AtRestKeys::load_or_create(dir.path(), &KeySource::Keyring).is_err());
+    }
+}
@@ -22,6 +22,12 @@ pub mod llama;
 pub use llama::*;
 pub mod pipes;
 pub use pipes::*;
+pub mod pipe_permissions;
+pub use pipe_permissions::*;
+pub mod pipe_logs;
+pub use pipe_logs::*;
+pub mod pipe_lock;
+pub use pipe_lock::*;
 mod language;
 #[cfg(feature = "security")]
 pub mod pii_removal;
@@ -43,3 +49,5 @@ pub use embedding::*;
 pub mod sync;
 #[cfg(feature = "cloud-sync")]
 pub use sync::*;
+#[cfg(feature = "at-rest")]
+pub mod at_rest;
@@ -0,0 +1,643 @@
+//! Integrity of installed pipes
+//!
+//! Installing a pipe writes [`LOCK_FILE`] next to its files: where it came from (the
+//! source and, for GitHub, the commit it resolved to), the hash of its content and the
+//! publisher whose signature was verified. The content is checked against the lock
+//! before every start.
+//!
+//! The dependency lockfile, installed dependencies and build output are written by
+//! `bun install` and the build, after the publisher signed the content. They are hashed
+//! apart, after every install and build screenpipe runs, and checked before every start
+//! too.
+//!
+//! A pipe is signed by shipping [`SIGNATURE_FILE`], `{"publisher": "acme", "signature":
+//! "<base64>"}`, the ed25519 signature of its content hash by a key listed in
+//! [`TRUSTED_PUBLISHERS_FILE`] of the screenpipe directory:
+//!
+//! ```json
+//! { "require_signatures": false, "publishers": { "acme": "<base64 public key>" } }
+//! ```
+
+use anyhow::{anyhow, bail, Result};
+use base64::prelude::*;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::pipes::should_ignore;
+
+pub const LOCK_FILE: &str = "pipe.lock.json";
+pub const SIGNATURE_FILE: &str = "pipe.sig";
+pub const TRUSTED_PUBLISHERS_FILE: &str = "trusted_publishers.json";
+
+/// Files of a pipe directory written while it is installed or runs, not part of its
+/// content.
+const RUNTIME_FILES: [&str; 7] = [
+    LOCK_FILE,
+    SIGNATURE_FILE,
+    "logs",
+    "kv.json",
+    "bun.lockb",
+    "bun.lock",
+    "next-env.d.ts",
+];
+
+/// Files and directories of a pipe directory written by `bun install` and the build,
+/// see [`pipe_build_hash`].
+const BUILD_FILES: [&str; 6] = [
+    "bun.lock",
+    "bun.lockb",
+    "node_modules",
+    ".next",
+    "dist",
+    "build",
+];
+
+/// Caches written while a pipe runs, inside its build files. Directories named `.cache`
+/// are left out too.
+const BUILD_CACHES: [&str; 1] = [".next/cache"];
+
+/// Keys of pipe.json written by screenpipe or the user, not hashed.
+const MANAGED_CONFIG_KEYS: [&str; 8] = [
+    "enabled",
+    "source",
+    "version",
+    "id",
+    "port",
+    "build",
+    "buildStatus",
+    "is_nextjs",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PipeLock {
+    /// URL or path the pipe was installed from
+    pub source: String,
+    /// Commit of a GitHub source
+    pub commit: Option<String>,
+    /// `sha256:<hex>`, see [`pipe_content_hash`]
+    pub content_hash: String,
+    /// `sha256:<hex>`, see [`pipe_build_hash`]. Recorded on the first start of pipes
+    /// locked before it was.
+    #[serde(default)]
+    pub build_hash: Option<String>,
+    /// Publisher whose signature was verified
+    pub publisher: Option<String>,
+    pub installed_at: DateTime<Utc>,
+}
+
+impl PipeLock {
+    /// The lock of the pipe in `pipe_dir`, `None` for pipes installed before locks.
+    pub async fn load(pipe_dir: &Path) -> Result<Option<Self>> {
+        match tokio::fs::read_to_string(pipe_dir.join(LOCK_FILE)).await {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn save(&self, pipe_dir: &Path) -> Result<()> {
+        tokio::fs::write(
+            pipe_dir.join(LOCK_FILE),
+            serde_json::to_string_pretty(self)?,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TrustedPublishers {
+    /// Refuse pipes without a valid signature
+    #[serde(default)]
+    pub require_signatures: bool,
+    /// Base64 ed25519 public keys by publisher name
+    #[serde(default)]
+    pub publishers: HashMap<String, String>,
+}
+
+impl TrustedPublishers {
+    pub async fn load(screenpipe_dir: &Path) -> Result<Self> {
+        let path = screenpipe_dir.join(TRUSTED_PUBLISHERS_FILE);
+        match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| anyhow!("invalid {}: {}", TRUSTED_PUBLISHERS_FILE, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipeSignature {
+    pub publisher: String,
+    /// Base64 ed25519 signature of the content hash
+    pub signature: String,
+}
+
+/// Hash of the files the installer copies, runtime files aside, and of the pipe.json
+/// keys not managed by screenpipe. Independent of the platform and of pipe.json's layout.
+pub async fn pipe_content_hash(pipe_dir: &Path) -> Result<String> {
+    let pipe_dir = pipe_dir.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut hasher = Sha256::new();
+        for (relative, path) in hashed_files(&pipe_dir)? {
+            let content = std::fs::read(&path)?;
+            let content = if relative == "pipe.json" {
+                canonical_pipe_config(&content)?
+            } else {
+                content
+            };
+            hash_file(&mut hasher, &relative, &content);
+        }
+        Ok(format!("sha256:{}", hex::encode(hasher.finalize())))
+    })
+    .await?
+}
+
+/// Hash of the dependency lockfile, installed dependencies and build output. `.next` is
+/// only hashed once pipe.json records a finished build, it is removed and rebuilt from
+/// the verified sources before starting otherwise.
+pub async fn pipe_build_hash(pipe_dir: &Path) -> Result<String> {
+    let pipe_dir = pipe_dir.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut hasher = Sha256::new();
+        for (relative, path) in build_files(&pipe_dir)? {
+            // symlinks (e.g. `node_modules/.bin`) are hashed by target, not followed
+            let content = if std::fs::symlink_metadata(&path)?.is_symlink() {
+                std::fs::read_link(&path)?
+                    .to_string_lossy()
+                    .replace('\\', "/")
+                    .into_bytes()
+            } else {
+                std::fs::read(&path)?
+            };
+            hash_file(&mut hasher, &relative, &content);
+        }
+        Ok(format!("sha256:{}", hex::encode(hasher.finalize())))
+    })
+    .await?
+}
+
+fn hash_file(hasher: &mut Sha256, relative: &str, content: &[u8]) {
+    hasher.update(relative.as_bytes());
+    hasher.update([0]);
+    hasher.update((content.len() as u64).to_le_bytes());
+    hasher.update(content);
+}
+
+fn relative_path(pipe_dir: &Path, path: &Path) -> Result<String> {
+    Ok(path
+        .strip_prefix(pipe_dir)?
+        .to_string_lossy()
+        .replace('\\', "/"))
+}
+
+/// Files to hash by path relative to `pipe_dir`, `/` separated and sorted.
+fn hashed_files(pipe_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![pipe_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if should_ignore(&entry.file_name())
+                || (dir == pipe_dir && RUNTIME_FILES.iter().any(|f| entry.file_name() == *f))
+            {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push((relative_path(pipe_dir, &path)?, path));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Build files to hash by path relative to `pipe_dir`, `/` separated and sorted.
+fn build_files(pipe_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    for name in BUILD_FILES {
+        if name == ".next" && !is_built(pipe_dir) {
+            continue;
+        }
+        let path = pipe_dir.join(name);
+        match std::fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => dirs.push(path),
+            Ok(_) => files.push((name.to_string(), path)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let relative = relative_path(pipe_dir, &path)?;
+            if entry.file_name() == ".cache" || BUILD_CACHES.contains(&relative.as_str()) {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push((relative, path));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Whether pipe.json records a finished Next.js build.
+fn is_built(pipe_dir: &Path) -> bool {
+    std::fs::read(pipe_dir.join("pipe.json"))
+        .ok()
+        .and_then(|content| serde_json::from_slice::<Value>(&content).ok())
+        .and_then(|config| config.get("build").and_then(Value::as_bool))
+        .unwrap_or(false)
+}
+
+fn canonical_pipe_config(content: &[u8]) -> Result<Vec<u8>> {
+    let mut config: Value = serde_json::from_slice(content)?;
+    if let Some(config) = config.as_object_mut() {
+        for key in MANAGED_CONFIG_KEYS {
+            config.remove(key);
+        }
+        if let Some(fields) = config.get_mut("fields").and_then(Value::as_array_mut) {
+            for field in fields.iter_mut().filter_map(Value::as_object_mut) {
+                field.remove("value");
+            }
+        }
+    }
+    Ok(serde_json::to_vec(&sort_keys(config))?)
+}
+
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (key, sort_keys(value)))
+                .collect::<BTreeMap<_, _>>()
+                .into_iter()
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        value => value,
+    }
+}
+
+/// Publisher of the pipe in `pipe_dir` whose signature of `content_hash` verifies.
+///
+/// A signature by a publisher that isn't trusted counts as none, an invalid signature
+/// by a trusted one is an error, and so is no signature when signatures are required.
+pub async fn verify_pipe_signature(
+    pipe_dir: &Path,
+    content_hash: &str,
+    trusted: &TrustedPublishers,
+) -> Result<Option<String>> {
+    let publisher = match tokio::fs::read_to_string(pipe_dir.join(SIGNATURE_FILE)).await {
+        Ok(content) => {
+            let signature: PipeSignature = serde_json::from_str(&content)
+                .map_err(|e| anyhow!("invalid {}: {}", SIGNATURE_FILE, e))?;
+            match trusted.publishers.get(&signature.publisher) {
+                Some(key) => {
+                    verify_signature(key, &signature.signature, content_hash).map_err(|e| {
+                        anyhow!(
+                            "signature by {} does not verify: {}",
+                            signature.publisher,
+                            e
+                        )
+                    })?;
+                    Some(signature.publisher)
+                }
+                None => {
+                    warn!(
+                        "pipe in {:?} is signed by {}, who is not a trusted publisher",
+                        pipe_dir, signature.publisher
+                    );
+                    None
+                }
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    if publisher.is_none() && trusted.require_signatures {
+        bail!("pipe has no signature by a trusted publisher, which is required");
+    }
+    Ok(publisher)
+}
+
+fn verify_signature(public_key: &str, signature: &str, content_hash: &str) -> Result<()> {
+    let public_key = VerifyingKey::from_bytes(&decode_base64(public_key)?)?;
+    let signature = Signature::from_bytes(&decode_base64(signature)?);
+    public_key.verify_strict(content_hash.as_bytes(), &signature)?;
+    Ok(())
+}
+
+fn decode_base64<const N: usize>(value: &str) -> Result<[u8; N]> {
+    BASE64_STANDARD
+        .decode(value.trim())?
+        .try_into()
+        .map_err(|_| anyhow!("expected {} bytes", N))
+}
+
+/// Hash the pipe installed in `pipe_dir`, check its signature and write its lock.
+pub async fn lock_pipe(
+    pipe_dir: &Path,
+    source: &str,
+    commit: Option<String>,
+    trusted: &TrustedPublishers,
+) -> Result<PipeLock> {
+    let content_hash = pipe_content_hash(pipe_dir).await?;
+    let publisher = verify_pipe_signature(pipe_dir, &content_hash, trusted).await?;
+    let lock = PipeLock {
+        source: source.to_string(),
+        commit,
+        content_hash,
+        build_hash: Some(pipe_build_hash(pipe_dir).await?),
+        publisher,
+        installed_at: Utc::now(),
+    };
+    lock.save(pipe_dir).await?;
+    Ok(lock)
+}
+
+/// Record the build hash of the pipe in `pipe_dir` after screenpipe installed its
+/// dependencies or built it from its verified content.
+pub async fn record_pipe_build(pipe_dir: &Path) -> Result<()> {
+    if let Some(mut lock) = PipeLock::load(pipe_dir).await? {
+        lock.build_hash = Some(pipe_build_hash(pipe_dir).await?);
+        lock.save(pipe_dir).await?;
+    }
+    Ok(())
+}
+
+/// Check the pipe in `pipe_dir` still is what its lock records. Pipes installed before
+/// locks have none, which is only an error when signatures are required.
+pub async fn verify_pipe(pipe_dir: &Path, trusted: &TrustedPublishers) -> Result<Option<PipeLock>> {
+    let Some(mut lock) = PipeLock::load(pipe_dir).await? else {
+        if trusted.require_signatures {
+            bail!("pipe has no lock, reinstall it");
+        }
+        return Ok(None);
+    };
+
+    let content_hash = pipe_content_hash(pipe_dir).await?;
+    if content_hash != lock.content_hash {
+        bail!(
+            "pipe was modified since it was installed ({} instead of {}), reinstall or update it",
+            content_hash,
+            lock.content_hash
+        );
+    }
+    let publisher = verify_pipe_signature(pipe_dir, &content_hash, trusted).await?;
+    if publisher != lock.publisher {
+        bail!(
+            "pipe was signed by {:?} when installed, now by {:?}",
+            lock.publisher,
+            publisher
+        );
+    }
+
+    let build_hash = pipe_build_hash(pipe_dir).await?;
+    match &lock.build_hash {
+        Some(locked) if *locked != build_hash => bail!(
+            "pipe dependencies or build output were modified since they were installed ({} instead of {}), reinstall or update it",
+            build_hash,
+            locked
+        ),
+        Some(_) => {}
+        None => {
+            lock.build_hash = Some(build_hash);
+            lock.save(pipe_dir).await?;
+        }
+    }
+    Ok(Some(lock))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use serde_json::json;
+
+    async fn write_pipe(dir: &Path) {
+        tokio::fs::write(dir.join("pipe.js"), "console.log('hi')")
+            .await
+            .unwrap();
+        tokio::fs::create_dir_all(dir.join("lib")).await.unwrap();
+        tokio::fs::write(dir.join("lib/util.js"), "export {}")
+            .await
+            .unwrap();
+        tokio::fs::write(
+            dir.join("pipe.json"),
+            json!({
+                "permissions": { "apps": ["Slack"] },
+                "fields": [{ "name": "interval", "default": 60 }],
+            })
+            .to_string(),
+        )
+        .await
+        .unwrap();
+    }
+
+    fn trusting(key: &SigningKey) -> TrustedPublishers {
+        TrustedPublishers {
+            require_signatures: false,
+            publishers: HashMap::from([(
+                "acme".to_string(),
+                BASE64_STANDARD.encode(key.verifying_key().as_bytes()),
+            )]),
+        }
+    }
+
+    async fn sign(dir: &Path, key: &SigningKey) {
+        let hash = pipe_content_hash(dir).await.unwrap();
+        let signature = PipeSignature {
+            publisher: "acme".to_string(),
+            signature: BASE64_STANDARD.encode(key.sign(hash.as_bytes()).to_bytes()),
+        };
+        tokio::fs::write(
+            dir.join(SIGNATURE_FILE),
+            serde_json::to_string(&signature).unwrap(),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_content_hash_ignores_runtime_state() {
+        let dir = tempfile::tempdir().unwrap();
+        write_pipe(dir.path()).await;
+        let hash = pipe_content_hash(dir.path()).await.unwrap();
+
+        // written by screenpipe and the user
+        tokio::fs::write(
+            dir.path().join("pipe.json"),
+            json!({
+                "enabled": true,
+                "fields": [{ "default": 60, "name": "interval", "value": 30 }],
+                "permissions": { "apps": ["Slack"] },
+                "buildStatus": "success",
+            })
+            .to_string(),
+        )
+        .await
+        .unwrap();
+        tokio::fs::create_dir_all(dir.path().join("node_modules/left-pad"))
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("node_modules/left-pad/index.js"), "")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("kv.json"), "{}")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join(".cron_state.json"), "{}")
+            .await
+            .unwrap();
+        assert_eq!(pipe_content_hash(dir.path()).await.unwrap(), hash);
+
+        tokio::fs::write(dir.path().join("lib/util.js"), "export { evil }")
+            .await
+            .unwrap();
+        assert_ne!(pipe_content_hash(dir.path()).await.unwrap(), hash);
+    }
+
+    #[tokio::test]
+    async fn test_lock_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        write_pipe(dir.path()).await;
+        let trusted = TrustedPublishers::default();
+
+        let lock = lock_pipe(dir.path(), "/tmp/pipe", None, &trusted)
+            .await
+            .unwrap();
+        assert_eq!(lock.publisher, None);
+        assert_eq!(verify_pipe(dir.path(), &trusted).await.unwrap(), Some(lock));
+
+        // permissions are part of the content
+        tokio::fs::write(
+            dir.path().join("pipe.json"),
+            json!({ "permissions": { "apps": ["Slack", "Mail"] } }).to_string(),
+        )
+        .await
+        .unwrap();
+        assert!(verify_pipe(dir.path(), &trusted).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_build_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        write_pipe(dir.path()).await;
+        let trusted = TrustedPublishers::default();
+        tokio::fs::create_dir_all(dir.path().join("node_modules/left-pad/dist"))
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("node_modules/left-pad/dist/index.js"), "")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("bun.lock"), "{}")
+            .await
+            .unwrap();
+        tokio::fs::create_dir_all(dir.path().join(".next/cache"))
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join(".next/server.js"), "")
+            .await
+            .unwrap();
+
+        let lock = lock_pipe(dir.path(), "/tmp/pipe", None, &trusted)
+            .await
+            .unwrap();
+        assert!(lock.build_hash.is_some());
+
+        // caches, and .next before a finished build, are left out
+        tokio::fs::write(dir.path().join(".next/cache/image.webp"), "")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join(".next/server.js"), "dev")
+            .await
+            .unwrap();
+        assert!(verify_pipe(dir.path(), &trusted).await.is_ok());
+
+        tokio::fs::write(dir.path().join("bun.lock"), r#"{"evil": 1}"#)
+            .await
+            .unwrap();
+        let err = verify_pipe(dir.path(), &trusted).await.unwrap_err();
+        assert!(err.to_string().contains("build output"), "{}", err);
+        tokio::fs::write(dir.path().join("bun.lock"), "{}")
+            .await
+            .unwrap();
+
+        tokio::fs::write(
+            dir.path().join("node_modules/left-pad/dist/index.js"),
+            "evil()",
+        )
+        .await
+        .unwrap();
+        assert!(verify_pipe(dir.path(), &trusted).await.is_err());
+
+        // recorded after screenpipe installed or built the pipe
+        record_pipe_build(dir.path()).await.unwrap();
+        assert!(verify_pipe(dir.path(), &trusted).await.is_ok());
+
+        // locks written before build hashes get one on their first verification
+        let mut lock = PipeLock::load(dir.path()).await.unwrap().unwrap();
+        lock.build_hash = None;
+        lock.save(dir.path()).await.unwrap();
+        let lock = verify_pipe(dir.path(), &trusted).await.unwrap().unwrap();
+        assert_eq!(
+            lock.build_hash,
+            Some(pipe_build_hash(dir.path()).await.unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signatures() {
+        let dir = tempfile::tempdir().unwrap();
+        write_pipe(dir.path()).await;
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let trusted = trusting(&key);
+        let required = TrustedPublishers {
+            require_signatures: true,
+            ..trusted.clone()
+        };
+
+        // unsigned
+        assert!(lock_pipe(dir.path(), "/tmp/pipe", None, &required)
+            .await
+            .is_err());
+
+        sign(dir.path(), &key).await;
+        let lock = lock_pipe(dir.path(), "/tmp/pipe", None, &required)
+            .await
+            .unwrap();
+        assert_eq!(lock.publisher.as_deref(), Some("acme"));
+        // untrusted signature counts as none
+        assert!(verify_pipe(dir.path(), &TrustedPublishers::default())
+            .await
+            .is_err());
+
+        // signed by someone else
+        sign(dir.path(), &SigningKey::from_bytes(&[8; 32])).await;
+        let err = verify_pipe(dir.path(), &trusted).await.unwrap_err();
+        assert!(err.to_string().contains("does not verify"), "{}", err);
+
+        // signature removed
+        tokio::fs::remove_file(dir.path().join(SIGNATURE_FILE))
+            .await
+            .unwrap();
+        assert!(verify_pipe(dir.path(), &trusted).await.is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! Log files of pipes
+//!
+//! What a pipe prints, and what screenpipe reports about it (starts, exits, restarts),
+//! goes to `logs/pipe.log` in the pipe directory as JSON lines. The file is rotated at
+//! [`MAX_LOG_BYTES`] keeping [`ROTATED_LOGS`] older files, and new lines are broadcast
+//! to the [`PipeLog::subscribe`]rs for tailing.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+/// Size of a log file before it is rotated.
+pub const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Rotated files kept, `pipe.log.1` being the most recent.
+pub const ROTATED_LOGS: usize = 3;
+
+const LOG_DIR: &str = "logs";
+const LOG_FILE: &str = "pipe.log";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    /// Written by screenpipe
+    System,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PipeLogLine {
+    pub timestamp: DateTime<Utc>,
+    pub stream: LogStream,
+    pub line: String,
+}
+
+pub struct PipeLog {
+    dir: PathBuf,
+    file: Mutex<Option<(File, u64)>>,
+    sender: broadcast::Sender<PipeLogLine>,
+}
+
+static PIPE_LOGS: Lazy<Mutex<HashMap<PathBuf, Arc<PipeLog>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The log of the pipe in `pipe_dir`, shared by everything writing to or tailing it.
+pub fn pipe_log(pipe_dir: &Path) -> Arc<PipeLog> {
+    let dir = pipe_dir.join(LOG_DIR);
+    PIPE_LOGS
+        .lock()
+        .unwrap()
+        .entry(dir.clone())
+        .or_insert_with(|| {
+            Arc::new(PipeLog {
+                dir,
+                file: Mutex::new(None),
+                sender: broadcast::channel(1024).0,
+            })
+        })
+        .clone()
+}
+
+impl PipeLog {
+    /// Append a line. Failing to write it is only reported, the pipe keeps running.
+    pub fn write(&self, stream: LogStream, line: &str) {
+        let line = PipeLogLine {
+            timestamp: Utc::now(),
+            stream,
+            line: line.to_string(),
+        };
+        if let Err(e) = self.append(&line) {
+            warn!("failed to write pipe log in {:?}: {}", self.dir, e);
+        }
+        // no subscribers is fine
+        let _ = self.sender.send(line);
+    }
+
+    /// Lines written from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<PipeLogLine> {
+        self.sender.subscribe()
+    }
+
+    /// Close the file, reopened by the next line.
+    pub fn close(&self) {
+        *self.file.lock().unwrap() = None;
+    }
+
+    /// Run `f` with the file closed, to move or delete the pipe directory: lines written
+    /// meanwhile wait, and the next one reopens the file at its path.
+    pub fn while_closed<T>(&self, f: impl FnOnce() -> T) -> T {
+        let mut file = self.file.lock().unwrap();
+        *file = None;
+        f()
+    }
+
+    fn append(&self, line: &PipeLogLine) -> Result<()> {
+        let mut entry = serde_json::to_string(line)?;
+        entry.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        if file
+            .as_ref()
+            .is_some_and(|(_, size)| size + entry.len() as u64 > MAX_LOG_BYTES)
+        {
+            *file = None;
+            self.rotate()?;
+        }
+        if file.is_none() {
+            // nothing to log to once the pipe is deleted
+            if !self.dir.parent().is_some_and(Path::exists) {
+                return Ok(());
+            }
+            std::fs::create_dir_all(&self.dir)?;
+            let opened = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.dir.join(LOG_FILE))?;
+            let size = opened.metadata()?.len();
+            *file = Some((opened, size));
+        }
+
+        let (opened, size) = file.as_mut().unwrap();
+        opened.write_all(entry.as_bytes())?;
+        *size += entry.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&self) -> Result<()> {
+        for n in (1..ROTATED_LOGS).rev() {
+            let from = rotated_path(&self.dir, n);
+            if from.exists() {
+                std::fs::rename(&from, rotated_path(&self.dir, n + 1))?;
+            }
+        }
+        std::fs::rename(self.dir.join(LOG_FILE), rotated_path(&self.dir, 1))?;
+        Ok(())
+    }
+}
+
+fn rotated_path(dir: &Path, n: usize) -> PathBuf {
+    dir.join(format!("{}.{}", LOG_FILE, n))
+}
+
+/// The last `lines` lines logged by the pipe in `pipe_dir`, oldest first.
+pub async fn tail_pipe_log(pipe_dir: &Path, lines: usize) -> Result<Vec<PipeLogLine>> {
+    let dir = pipe_dir.join(LOG_DIR);
+    let files = std::iter::once(dir.join(LOG_FILE))
+        .chain((1..=ROTATED_LOGS).map(|n| rotated_path(&dir, n)));
+
+    let mut tail = VecDeque::new();
+    for path in files {
+        if tail.len() >= lines {
+            break;
+        }
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for entry in content.lines().rev() {
+            if tail.len() >= lines {
+                break;
+            }
+            // a line cut by a crash is skipped
+            if let Ok(line) = serde_json::from_str(entry) {
+                tail.push_front(line);
+            }
+        }
+    }
+    Ok(tail.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_log_rotates_and_tails_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = pipe_log(dir.path());
+        let mut lines = log.subscribe();
+
+        // enough to rotate a few times
+        let padding = "x".repeat(64 * 1024);
+        let count = (MAX_LOG_BYTES * 3 / padding.len() as u64) as usize;
+        for i in 0..count {
+            log.write(LogStream::Stdout, &format!("{} {}", i, padding));
+        }
+        log.write(LogStream::System, "exited");
+
+        assert_eq!(lines.recv().await.unwrap().stream, LogStream::Stdout);
+        assert!(rotated_path(&dir.path().join(LOG_DIR), 2).exists());
+        assert!(!rotated_path(&dir.path().join(LOG_DIR), ROTATED_LOGS + 1).exists());
+
+        let total = (MAX_LOG_BYTES / padding.len() as u64) as usize + 10;
+        let tail = tail_pipe_log(dir.path(), total).await.unwrap();
+        assert_eq!(tail.len(), total);
+        assert_eq!(tail.last().unwrap().line, "exited");
+        assert_eq!(
+            tail[tail.len() - 2].line,
+            format!("{} {}", count - 1, padding)
+        );
+        assert_eq!(tail[0].line, format!("{} {}", count - total + 1, padding));
+    }
+
+    #[tokio::test]
+    async fn test_log_reopens_after_pipe_dir_swap() {
+        let root = tempfile::tempdir().unwrap();
+        let pipe_dir = root.path().join("pipe");
+        let log = pipe_log(&pipe_dir);
+        log.write(LogStream::System, "old version");
+
+        // as an update swaps the versions
+        let staging_dir = root.path().join(".pipe.next");
+        std::fs::create_dir_all(&staging_dir).unwrap();
+        log.while_closed(|| {
+            std::fs::rename(&pipe_dir, root.path().join(".pipe.previous")).unwrap();
+            std::fs::rename(&staging_dir, &pipe_dir).unwrap();
+        });
+        log.write(LogStream::System, "new version");
+
+        let tail = tail_pipe_log(&pipe_dir, 10).await.unwrap();
+        assert_eq!(tail.len(), 1);
+        assert_eq!(tail[0].line, "new version");
+
+        // and doesn't recreate a deleted pipe
+        log.while_closed(|| std::fs::remove_dir_all(&pipe_dir).unwrap());
+        log.write(LogStream::System, "stopped");
+        assert!(!pipe_dir.exists());
+    }
+}
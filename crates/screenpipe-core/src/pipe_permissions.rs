@@ -0,0 +1,375 @@
+//! Permissions a pipe declares in the `permissions` section of its pipe.json
+//!
+//! ```json
+//! "permissions": {
+//!   "content_types": ["ocr", "audio"],
+//!   "apps": ["Slack"],
+//!   "time_window_hours": 24,
+//!   "endpoints": ["GET /search"],
+//!   "network": ["api.openai.com"],
+//!   "env": ["OPENAI_API_KEY"]
+//! }
+//! ```
+//!
+//! They are shown when the pipe is installed. Every pipe process, with or without the
+//! section, only gets the variables listed in `env` on top of [`BASE_ENV`]. The server
+//! checks the requests made with the pipe's API token against the other fields, see
+//! [`PipePermissions::check_request`]. That token only exists with auth enabled, so
+//! without it a pipe restricting its API access (see [`PipePermissions::restricts_api`])
+//! doesn't start. `network` is advisory, nothing enforces it.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+/// Variables a process needs to run, passed to every pipe.
+pub const BASE_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "USERNAME",
+    "LOGNAME",
+    "SHELL",
+    "LANG",
+    "LC_ALL",
+    "LC_CTYPE",
+    "TZ",
+    "TERM",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "NO_PROXY",
+    "BUN_INSTALL",
+    "SYSTEMROOT",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "USERPROFILE",
+    "PROGRAMDATA",
+    "PROGRAMFILES",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipePermissions {
+    /// `content_type`s the pipe searches (`ocr`, `audio`, `ui`, `input`, `vision`...),
+    /// any when empty
+    pub content_types: Vec<String>,
+    /// Apps whose data the pipe reads, any when empty
+    pub apps: Vec<String>,
+    /// How far back the pipe reads, unlimited when unset
+    pub time_window_hours: Option<u64>,
+    /// API endpoints the pipe calls, e.g. `GET /search` or `/meetings/*` (any method),
+    /// all those its scopes allow when empty
+    pub endpoints: Vec<String>,
+    /// Hosts the pipe connects to, advisory
+    pub network: Vec<String>,
+    /// Environment variables passed to the pipe
+    pub env: Vec<String>,
+}
+
+impl PipePermissions {
+    /// The `permissions` of a pipe.json, `None` when it has none.
+    pub fn from_config(config: &Value) -> Result<Option<Self>> {
+        match config.get("permissions") {
+            None | Some(Value::Null) => Ok(None),
+            Some(permissions) => serde_json::from_value(permissions.clone())
+                .map(Some)
+                .map_err(|e| anyhow::anyhow!("invalid permissions in pipe.json: {}", e)),
+        }
+    }
+
+    /// The permissions declared in the pipe.json of `pipe_dir`, `None` when it has none.
+    pub async fn load(pipe_dir: &Path) -> Result<Option<Self>> {
+        let path = pipe_dir.join("pipe.json");
+        if !path.exists() {
+            return Ok(None);
+        }
+        let config: Value = serde_json::from_str(&tokio::fs::read_to_string(&path).await?)?;
+        Self::from_config(&config)
+    }
+
+    /// Whether requests of the pipe are restricted beyond its scopes.
+    pub fn restricts_api(&self) -> bool {
+        !self.content_types.is_empty()
+            || !self.apps.is_empty()
+            || self.time_window_hours.is_some()
+            || !self.endpoints.is_empty()
+    }
+
+    /// Human readable description of the permissions and API `scopes` of a pipe, one line
+    /// per permission.
+    pub fn summary(&self, scopes: &[String]) -> Vec<String> {
+        let list = |values: &[String], any: &str| {
+            if values.is_empty() {
+                any.to_string()
+            } else {
+                values.join(", ")
+            }
+        };
+        vec![
+            format!("api scopes: {}", list(scopes, "none")),
+            format!("content types: {}", list(&self.content_types, "any")),
+            format!("apps: {}", list(&self.apps, "any")),
+            match self.time_window_hours {
+                Some(hours) => format!("time window: last {} hours", hours),
+                None => "time window: unlimited".to_string(),
+            },
+            format!(
+                "endpoints: {}",
+                list(&self.endpoints, "any allowed by its scopes")
+            ),
+            format!(
+                "network: {} (advisory, not enforced)",
+                list(&self.network, "none declared")
+            ),
+            format!("environment variables: {}", list(&self.env, "none")),
+        ]
+    }
+
+    /// Whether a request of the pipe is within its permissions, the reason otherwise.
+    ///
+    /// The data restrictions are checked on the query parameters: when `content_types`,
+    /// `apps` or `time_window_hours` is set, requests must pass a `content_type`,
+    /// `app_name` or `start_time` within it, so endpoints that don't take them are
+    /// refused.
+    pub fn check_request(
+        &self,
+        method: &str,
+        path: &str,
+        query: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> std::result::Result<(), String> {
+        self.check_endpoint(method, path)?;
+
+        let params: Vec<(String, String)> =
+            url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+                .into_owned()
+                .collect();
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        self.check_data(
+            param("content_type"),
+            param("app_name"),
+            param("start_time"),
+            now,
+        )
+    }
+
+    /// Whether the pipe declared the endpoint, when it declared any.
+    pub fn check_endpoint(&self, method: &str, path: &str) -> std::result::Result<(), String> {
+        if !self.endpoints.is_empty()
+            && !self
+                .endpoints
+                .iter()
+                .any(|endpoint| endpoint_matches(endpoint, method, path))
+        {
+            return Err(format!("{} {} is not a declared endpoint", method, path));
+        }
+        Ok(())
+    }
+
+    /// Whether data of `content_type` and `app_name` since `start_time` (RFC 3339) is
+    /// within the data restrictions. Unset values only pass unrestricted fields.
+    pub fn check_data(
+        &self,
+        content_type: Option<&str>,
+        app_name: Option<&str>,
+        start_time: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> std::result::Result<(), String> {
+        if !self.content_types.is_empty() {
+            let allowed = content_type.is_some_and(|requested| {
+                requested.split(['+', ' ']).all(|content_type| {
+                    self.content_types
+                        .iter()
+                        .any(|allowed| allowed.eq_ignore_ascii_case(content_type))
+                })
+            });
+            if !allowed {
+                return Err(format!(
+                    "content_type must be one of {}",
+                    self.content_types.join(", ")
+                ));
+            }
+        }
+
+        if !self.apps.is_empty() {
+            let allowed = app_name.is_some_and(|requested| {
+                self.apps
+                    .iter()
+                    .any(|app| app.eq_ignore_ascii_case(requested))
+            });
+            if !allowed {
+                return Err(format!("app_name must be one of {}", self.apps.join(", ")));
+            }
+        }
+
+        if let Some(hours) = self.time_window_hours {
+            let earliest = now - Duration::hours(hours as i64);
+            let allowed = start_time
+                .and_then(|start| DateTime::parse_from_rfc3339(start).ok())
+                .is_some_and(|start| start >= earliest);
+            if !allowed {
+                return Err(format!(
+                    "start_time must be within the last {} hours",
+                    hours
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `pattern` (`[METHOD] /path`, `/path/*` for a prefix) matches a request.
+fn endpoint_matches(pattern: &str, method: &str, path: &str) -> bool {
+    let (pattern_method, pattern_path) = match pattern.trim().split_once(' ') {
+        Some((pattern_method, pattern_path)) => (Some(pattern_method), pattern_path.trim()),
+        None => (None, pattern.trim()),
+    };
+    if pattern_method.is_some_and(|m| !m.eq_ignore_ascii_case(method)) {
+        return false;
+    }
+    let path = path.trim_end_matches('/');
+    match pattern_path.strip_suffix("/*") {
+        Some(prefix) => path == prefix || path.starts_with(&format!("{}/", prefix)),
+        None => path == pattern_path.trim_end_matches('/'),
+    }
+}
+
+/// Environment of a pipe process: [`BASE_ENV`] and the `declared` variables of the
+/// current process, nothing else.
+pub fn pipe_env(declared: &[String]) -> Vec<(String, String)> {
+    filter_env(std::env::vars(), declared)
+}
+
+fn filter_env(
+    vars: impl Iterator<Item = (String, String)>,
+    declared: &[String],
+) -> Vec<(String, String)> {
+    // names are case-insensitive on Windows
+    vars.filter(|(name, _)| {
+        BASE_ENV.iter().any(|base| base.eq_ignore_ascii_case(name))
+            || declared
+                .iter()
+                .any(|declared| declared.eq_ignore_ascii_case(name))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_config() {
+        assert_eq!(PipePermissions::from_config(&json!({})).unwrap(), None);
+        let permissions = PipePermissions::from_config(&json!({
+            "permissions": { "apps": ["Slack"], "env": ["OPENAI_API_KEY"] }
+        }))
+        .unwrap()
+        .unwrap();
+        assert_eq!(permissions.apps, vec!["Slack"]);
+        assert!(permissions.content_types.is_empty());
+        assert!(
+            PipePermissions::from_config(&json!({ "permissions": { "apps": "Slack" } })).is_err()
+        );
+    }
+
+    #[test]
+    fn test_restricts_api() {
+        assert!(!PipePermissions::default().restricts_api());
+        let permissions = PipePermissions {
+            network: vec!["api.openai.com".to_string()],
+            env: vec!["OPENAI_API_KEY".to_string()],
+            ..Default::default()
+        };
+        assert!(!permissions.restricts_api());
+        let permissions = PipePermissions {
+            time_window_hours: Some(24),
+            ..permissions
+        };
+        assert!(permissions.restricts_api());
+    }
+
+    #[test]
+    fn test_check_request() {
+        let now = Utc::now();
+        let permissions = PipePermissions {
+            content_types: vec!["ocr".to_string(), "audio".to_string()],
+            apps: vec!["Slack".to_string()],
+            time_window_hours: Some(24),
+            endpoints: vec!["GET /search".to_string(), "/meetings/*".to_string()],
+            ..Default::default()
+        };
+        let start = (now - Duration::hours(2)).to_rfc3339();
+        let query = |content_type: &str, app: &str, start: &str| {
+            url::form_urlencoded::Serializer::new(String::new())
+                .append_pair("content_type", content_type)
+                .append_pair("app_name", app)
+                .append_pair("start_time", start)
+                .finish()
+        };
+
+        let ok = query("audio+ocr", "slack", &start);
+        assert!(permissions
+            .check_request("GET", "/search", Some(&ok), now)
+            .is_ok());
+        assert!(permissions
+            .check_request("POST", "/search", Some(&ok), now)
+            .is_err());
+        assert!(permissions
+            .check_request("GET", "/raw_sql", Some(&ok), now)
+            .is_err());
+        assert!(permissions
+            .check_request("POST", "/meetings/3/split", Some(&ok), now)
+            .is_ok());
+
+        for query in [
+            query("all", "Slack", &start),
+            query("ocr+ui", "Slack", &start),
+            query("ocr", "Chrome", &start),
+            query("ocr", "Slack", &(now - Duration::hours(48)).to_rfc3339()),
+        ] {
+            assert!(permissions
+                .check_request("GET", "/search", Some(&query), now)
+                .is_err());
+        }
+        // restricted data needs the parameters
+        assert!(permissions
+            .check_request("GET", "/search", None, now)
+            .is_err());
+        assert!(PipePermissions::default()
+            .check_request("GET", "/frames/42", None, now)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_filter_env() {
+        let vars = [
+            ("PATH", "/usr/bin"),
+            ("HOME", "/home/me"),
+            ("SCREENPIPE_SYNC_PASSWORD", "secret"),
+            ("DEEPGRAM_API_KEY", "secret"),
+            ("OPENAI_API_KEY", "sk-1"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let env = filter_env(vars, &["OPENAI_API_KEY".to_string()]);
+        let names: Vec<&str> = env.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["PATH", "HOME", "OPENAI_API_KEY"]);
+    }
+}
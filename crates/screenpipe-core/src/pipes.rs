@@ -2,13 +2,11 @@ use dirs::home_dir;
 use regex::Regex;
 use sentry;
 use serde_json::Value;
-use std::collections::HashMap;
 use std::future::Future;
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::process::Command;
-use tokio::sync::watch;
 
 use anyhow::Result;
 use std::fs;
@@ -24,17 +22,19 @@ use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 
 use crate::pick_unused_port;
+use crate::pipe_lock::record_pipe_build;
+use crate::pipe_logs::{pipe_log, LogStream, PipeLog};
+use crate::pipe_permissions::{pipe_env, PipePermissions};
 use once_cell::sync::Lazy;
 
 // Add near other imports
 use http_cache_reqwest::{CACacheManager, Cache, CacheMode, HttpCache, HttpCacheOptions};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
-use reqwest_middleware::reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
 use reqwest_middleware::reqwest::Client;
 use reqwest_middleware::ClientBuilder;
 use std::collections::HashSet;
-use std::str::FromStr;
+use std::sync::Arc;
 
 // Add at top of file with other imports
 #[cfg(windows)]
@@ -46,10 +46,6 @@ pub enum PipeState {
     Pid(i32),
 }
 
-pub struct CronHandle {
-    shutdown: watch::Sender<bool>,
-}
-
 #[derive(Debug, Clone)]
 pub enum BuildStatus {
     NotStarted,
@@ -58,17 +54,9 @@ pub enum BuildStatus {
     Failed(String),
 }
 
-impl CronHandle {
-    pub fn stop(&self) {
-        let _ = self.shutdown.send(true);
-    }
-}
-
-static CRON_HANDLES: Lazy<tokio::sync::Mutex<HashMap<String, Vec<CronHandle>>>> =
-    Lazy::new(|| tokio::sync::Mutex::new(HashMap::new()));
-
-// Add this function to generate a secure cron secret
-fn generate_cron_secret() -> String {
+/// Secret a pipe gets as `CRON_SECRET`, sent by the scheduler as a bearer token so the
+/// pipe can tell its cron routes are called by screenpipe.
+pub fn generate_cron_secret() -> String {
     thread_rng()
         .sample_iter(&Alphanumeric)
         .take(32)
@@ -366,6 +354,16 @@ async fn spawn_watchdog(parent_pid: u32, child_pid: u32) -> Result<tokio::proces
 pub async fn run_pipe(
     pipe: &str,
     screenpipe_dir: PathBuf,
+) -> Result<(tokio::process::Child, PipeState)> {
+    run_pipe_with_env(pipe, screenpipe_dir, Vec::new()).await
+}
+
+/// Like [`run_pipe`], with extra environment variables for the pipe process (e.g. its
+/// API token).
+pub async fn run_pipe_with_env(
+    pipe: &str,
+    screenpipe_dir: PathBuf,
+    extra_env: Vec<(String, String)>,
 ) -> Result<(tokio::process::Child, PipeState)> {
     let bun_path = find_bun_path().ok_or_else(|| {
         let err = anyhow::anyhow!("bun not found");
@@ -410,9 +408,10 @@ pub async fn run_pipe(
         debug!("pipe {} is enabled, continuing", pipe);
     }
 
-    // Prepare environment variables
+    // Prepare environment variables, only those the pipe declared are passed on
     debug!("preparing environment variables for pipe: {}", pipe);
-    let mut env_vars = std::env::vars().collect::<Vec<(String, String)>>();
+    let permissions = PipePermissions::load(&pipe_dir).await?.unwrap_or_default();
+    let mut env_vars = pipe_env(&permissions.env);
     env_vars.push((
         "SCREENPIPE_DIR".to_string(),
         screenpipe_dir.to_str().unwrap().to_string(),
@@ -422,6 +421,7 @@ pub async fn run_pipe(
         "PIPE_DIR".to_string(),
         pipe_dir.to_str().unwrap().to_string(),
     ));
+    env_vars.extend(extra_env);
 
     if is_nextjs {
         debug!(
@@ -469,65 +469,19 @@ pub async fn run_pipe(
 
             env_vars.push(("PORT".to_string(), port.to_string()));
 
-            // Handle cron jobs if they exist
-            if let Some(crons) = pipe_config.get("crons").and_then(Value::as_array) {
-                info!(
-                    "[{}] found {} cron jobs in configuration",
-                    pipe,
-                    crons.len()
-                );
-                let base_url = format!("http://localhost:{}", port);
-                debug!("[{}] using base url: {} for cron jobs", pipe, base_url);
-
-                let cron_secret = generate_cron_secret();
-                debug!("[{}] generated cron secret: {}", pipe, cron_secret);
-                env_vars.push(("CRON_SECRET".to_string(), cron_secret.clone()));
-
-                let mut handles = Vec::new();
-
-                for cron in crons {
-                    let path = cron["path"]
-                        .as_str()
-                        .ok_or_else(|| anyhow::anyhow!("missing path"))?
-                        .to_string();
-                    let schedule = cron["schedule"]
-                        .as_str()
-                        .ok_or_else(|| anyhow::anyhow!("missing schedule"))?
-                        .to_string();
-
-                    let (tx, rx) = watch::channel(false);
-                    let handle = CronHandle { shutdown: tx };
-                    handles.push(handle);
-
-                    let base_url = base_url.clone();
-                    let pipe_clone = pipe.to_string();
-                    let secret_clone = cron_secret.clone();
-                    let screenpipe_dir = screenpipe_dir.clone();
-
-                    tokio::spawn(async move {
-                        run_cron_schedule(
-                            &pipe_clone,
-                            &base_url,
-                            &path,
-                            &secret_clone,
-                            &schedule,
-                            &screenpipe_dir,
-                            rx,
-                        )
-                        .await;
-                    });
-                }
-
-                // Store handles for later cleanup
-                CRON_HANDLES.lock().await.insert(pipe.to_string(), handles);
-            }
-
             // Install dependencies using bun
             info!("[{}] installing dependencies for next.js pipe", pipe);
 
-            let install_output = Command::new(&bun_path)
-                .arg("install")
+            // the lockfile is part of the build hash, keep the locked versions
+            let mut install = Command::new(&bun_path);
+            install.arg("install");
+            if pipe_dir.join("bun.lock").exists() || pipe_dir.join("bun.lockb").exists() {
+                install.arg("--frozen-lockfile");
+            }
+            let install_output = install
                 .current_dir(&pipe_dir)
+                .env_clear()
+                .envs(env_vars.clone())
                 .env("NPM_CONFIG_REGISTRY", "https://registry.npmjs.org")
                 .env("BUN_CONFIG_REGISTRY", "https://registry.npmjs.org")
                 .output()
@@ -557,7 +511,7 @@ pub async fn run_pipe(
         }
 
         // Try to build the Next.js project
-        let build_status = try_build_nextjs(&pipe_dir, &bun_path).await?;
+        let build_status = try_build_nextjs(&pipe_dir, &bun_path, &env_vars).await?;
         let build_success = matches!(build_status, BuildStatus::Success);
 
         if pipe_json_path.exists() {
@@ -578,6 +532,9 @@ pub async fn run_pipe(
             tokio::fs::write(&pipe_json_path, updated_pipe_json).await?;
         }
 
+        // installed and built from the content verified before the start
+        record_pipe_build(&pipe_dir).await?;
+
         let port = env_vars
             .iter()
             .find(|(k, _)| k == "PORT")
@@ -610,6 +567,7 @@ pub async fn run_pipe(
             .arg("--port")
             .arg(port.to_string())
             .current_dir(&pipe_dir)
+            .env_clear()
             .envs(env_vars)
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped());
@@ -617,7 +575,7 @@ pub async fn run_pipe(
         let mut child = command.spawn()?;
 
         debug!("[{}] streaming logs for next.js pipe", pipe);
-        stream_logs(pipe, &mut child).await?;
+        stream_logs(pipe, Some(pipe_log(&pipe_dir)), &mut child).await?;
 
         let child_pid = child.id().expect("Failed to get child PID") as u32;
         let parent_pid = std::process::id();
@@ -653,29 +611,39 @@ pub async fn run_pipe(
         .arg("run")
         .arg("--bun")
         .arg(&main_module)
+        .env_clear()
         .envs(env_vars)
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .spawn()?;
 
     // Stream logs
-    stream_logs(pipe, &mut child).await?;
+    stream_logs(pipe, Some(pipe_log(&pipe_dir)), &mut child).await?;
 
     let child_id = child.id().unwrap();
     Ok((child, PipeState::Pid(child_id as i32))) // Return 0 or handle port differently for non-Next.js projects
 }
 
-async fn stream_logs(pipe: &str, child: &mut tokio::process::Child) -> Result<()> {
+/// Forward the output of `child` to the screenpipe logs, and to `log` when given.
+async fn stream_logs(
+    pipe: &str,
+    log: Option<Arc<PipeLog>>,
+    child: &mut tokio::process::Child,
+) -> Result<()> {
     let stdout = child.stdout.take().expect("failed to get stdout");
     let stderr = child.stderr.take().expect("failed to get stderr");
 
     let pipe_clone = pipe.to_string();
+    let stdout_log = log.clone();
 
     // Spawn tasks to handle stdout and stderr
     let _stdout_handle = tokio::spawn(async move {
         let reader = BufReader::new(stdout);
         let mut lines = reader.lines();
         while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(log) = &stdout_log {
+                log.write(LogStream::Stdout, &line);
+            }
             info!("[{}] {}", pipe_clone, line);
         }
     });
@@ -736,6 +704,9 @@ async fn stream_logs(pipe: &str, child: &mut tokio::process::Child) -> Result<()
         let mut lines = reader.lines();
 
         while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(log) = &log {
+                log.write(LogStream::Stderr, &line);
+            }
             let line_lower = line.to_lowercase(); // Convert once for case-insensitive matching
 
             // Quick checks first
@@ -781,15 +752,19 @@ async fn retry_install(bun_path: &Path, dest_dir: &Path, max_retries: u32) -> Re
     let mut last_error = None;
 
     while attempt < max_retries {
+        // install scripts run before the user saw the pipe's permissions, so without
+        // any of its declared variables
         let mut install_child = Command::new(bun_path)
             .arg("i")
             .current_dir(dest_dir)
+            .env_clear()
+            .envs(pipe_env(&[]))
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
             .spawn()?;
 
         // Stream logs for npm install
-        if let Ok(()) = stream_logs("bun install", &mut install_child).await {
+        if let Ok(()) = stream_logs("bun install", None, &mut install_child).await {
             let status = install_child.wait().await?;
             if status.success() {
                 return Ok(());
@@ -1007,7 +982,7 @@ fn copy_dir_all_boxed(
     Box::pin(copy_dir_all(src, dst))
 }
 
-fn should_ignore(file_name: &std::ffi::OsStr) -> bool {
+pub(crate) fn should_ignore(file_name: &std::ffi::OsStr) -> bool {
     let ignore_list = [
         "node_modules",
         ".git",
@@ -1176,6 +1151,55 @@ fn download_github_folder(
     })
 }
 
+/// Resolve the branch of a GitHub source to the commit it points to, returning the source
+/// pinned to that commit and the commit. `None` for other sources.
+pub async fn pin_github_source(source: &str) -> anyhow::Result<Option<(String, String)>> {
+    let Ok(url) = Url::parse(source) else {
+        return Ok(None);
+    };
+    if url.host_str() != Some("github.com") {
+        return Ok(None);
+    }
+    let path_segments: Vec<&str> = url
+        .path_segments()
+        .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    if path_segments.len() < 2 {
+        anyhow::bail!("Invalid GitHub URL format");
+    }
+    let (owner, repo) = (path_segments[0], path_segments[1]);
+    let (branch, subfolder) = match path_segments.iter().position(|&s| s == "tree") {
+        Some(tree_pos) if tree_pos + 1 < path_segments.len() => {
+            (path_segments[tree_pos + 1], &path_segments[tree_pos + 2..])
+        }
+        // Default to main branch
+        _ => ("main", &path_segments[path_segments.len()..]),
+    };
+
+    let commit = Client::new()
+        .get(format!(
+            "https://api.github.com/repos/{}/{}/commits/{}",
+            owner, repo, branch
+        ))
+        .header("Accept", "application/vnd.github.sha")
+        .header("User-Agent", "screenpipe")
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?
+        .trim()
+        .to_string();
+    debug!("resolved {} of {}/{} to {}", branch, owner, repo, commit);
+
+    let mut pinned = format!("https://github.com/{}/{}/tree/{}", owner, repo, commit);
+    for segment in subfolder {
+        pinned.push('/');
+        pinned.push_str(segment);
+    }
+    Ok(Some((pinned, commit)))
+}
+
 fn get_raw_github_url(url: &str) -> anyhow::Result<String> {
     debug!("Attempting to get raw GitHub URL for: {}", url);
     let parsed_url = Url::parse(url)?;
@@ -1368,140 +1392,11 @@ pub async fn save_cron_execution(pipe_dir: &Path, path: &str) -> Result<()> {
     Ok(())
 }
 
-// Update the run_cron_schedule function
-async fn run_cron_schedule(
-    pipe: &str,
-    base_url: &str,
-    path: &str,
-    secret: &str,
-    schedule: &str,
-    screenpipe_dir: &Path,
-    mut shutdown: watch::Receiver<bool>,
-) {
-    let schedule = match cron::Schedule::from_str(schedule) {
-        Ok(s) => s,
-        Err(e) => {
-            let err_msg = format!("invalid cron schedule: {}", e);
-            error!("{}", err_msg);
-            sentry::capture_error(&anyhow::anyhow!(err_msg).source().unwrap());
-            return;
-        }
-    };
-
-    let client = Client::new();
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", secret)).unwrap(),
-    );
-
-    // Get pipe directory for state persistence
-    let pipe_dir = screenpipe_dir.join("pipes").join(pipe);
-
-    loop {
-        // Get last execution time at the start of each loop
-        let last_run = match get_last_cron_execution(&pipe_dir, path).await {
-            Ok(time) => time,
-            Err(e) => {
-                error!(
-                    "[{}] failed to get last cron execution: {}",
-                    pipe_dir.file_name().unwrap_or_default().to_string_lossy(),
-                    e
-                );
-                None
-            }
-        };
-
-        let now = chrono::Local::now();
-        let next = if let Some(last) = last_run {
-            // Get next occurrence after the last execution
-            let last_chrono = chrono::DateTime::<chrono::Local>::from(last);
-            schedule.after(&last_chrono).next()
-        } else {
-            schedule.after(&now).next()
-        };
-
-        let next = match next {
-            Some(next) => next,
-            None => {
-                error!("no next execution time found for cron schedule");
-                break;
-            }
-        };
-
-        if next <= now {
-            info!("next execution time is before or equal to the current time, recalculating...");
-            continue;
-        }
-        let duration = match (next - now).to_std() {
-            Ok(duration) => duration,
-            Err(e) => {
-                error!("invalid duration: {}", e);
-                continue; // falling back to minute is messing with cron schedule
-            }
-        };
-
-        info!(
-            "[{}] next cron execution for pipe at path {} in {} seconds",
-            pipe,
-            path,
-            duration.as_secs()
-        );
-
-        // Wait for either the next execution time or shutdown signal
-        tokio::select! {
-            _ = tokio::time::sleep(duration) => {
-                info!("executing cron job for pipe {} at path {}", pipe, path);
-                match client
-                    .get(format!("{}{}", base_url, path))
-                    .headers(headers.clone())
-                    .send()
-                    .await
-                {
-                    Ok(res) => {
-                        if !res.status().is_success() {
-                            let err_msg = format!("cron job failed with status: {}", res.status());
-                            error!("{}", err_msg);
-                            if let Ok(text) = res.text().await {
-                                error!("error response: {}", text);
-                                sentry::capture_message(
-                                    &format!("{}: {}", err_msg, text),
-                                    sentry::Level::Error
-                                );
-                            } else {
-                                sentry::capture_message(&err_msg, sentry::Level::Error);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        let err_msg = format!("failed to execute cron job: {}", e);
-                        error!("{}", err_msg);
-                        sentry::capture_error(&e);
-                    }
-                }
-            }
-            Ok(()) = shutdown.changed() => {
-                if *shutdown.borrow() {
-                    info!("shutting down cron job for pipe at path: {}", path);
-                    break;
-                }
-            }
-        }
-    }
-}
-
-pub async fn cleanup_pipe_crons(pipe: &str) -> Result<()> {
-    if let Some(handles) = CRON_HANDLES.lock().await.remove(pipe) {
-        info!("cleaning up {} cron jobs for pipe {}", handles.len(), pipe);
-        for handle in handles {
-            handle.stop();
-        }
-        info!("stopped all cron jobs for pipe: {}", pipe);
-    }
-    Ok(())
-}
-
-async fn try_build_nextjs(pipe_dir: &Path, bun_path: &Path) -> Result<BuildStatus> {
+async fn try_build_nextjs(
+    pipe_dir: &Path,
+    bun_path: &Path,
+    env_vars: &[(String, String)],
+) -> Result<BuildStatus> {
     info!(
         "[{}] checking if i need to build the next.js project",
         pipe_dir.file_name().unwrap_or_default().to_string_lossy()
@@ -1579,6 +1474,8 @@ async fn try_build_nextjs(pipe_dir: &Path, bun_path: &Path) -> Result<BuildStatu
         .arg("--bun")
         .arg("build")
         .current_dir(pipe_dir)
+        .env_clear()
+        .envs(env_vars.iter().cloned())
         .output()
         .await?;
 
@@ -1611,6 +1508,27 @@ async fn try_build_nextjs(pipe_dir: &Path, bun_path: &Path) -> Result<BuildStatu
     }
 }
 
+/// Build a Next.js pipe ahead of its start, which would fall back to dev mode when the
+/// build fails. Other pipes have nothing to build.
+pub async fn build_pipe(pipe_dir: &Path) -> Result<()> {
+    let package_json_path = pipe_dir.join("package.json");
+    if !package_json_path.exists() {
+        return Ok(());
+    }
+    let package_json = tokio::fs::read_to_string(&package_json_path).await?;
+    let package_data: Value = serde_json::from_str(&package_json)?;
+    if package_data["dependencies"].get("next").is_none() {
+        return Ok(());
+    }
+
+    let bun_path = find_bun_path().ok_or_else(|| anyhow::anyhow!("bun not found"))?;
+    let permissions = PipePermissions::load(pipe_dir).await?.unwrap_or_default();
+    match try_build_nextjs(pipe_dir, &bun_path, &pipe_env(&permissions.env)).await? {
+        BuildStatus::Failed(error) => anyhow::bail!("next.js build failed: {}", error),
+        _ => Ok(()),
+    }
+}
+
 // Add this helper function to check if a port is available
 fn is_port_available(port: u16) -> bool {
     use std::net::TcpListener;
@@ -456,6 +456,37 @@ impl SyncClient {
     // Data Management
     // =========================================================================
 
+    /// Delete specific blobs, e.g. ones holding data forgotten locally.
+    pub async fn delete_blobs(&self, blob_ids: &[String]) -> SyncResult<()> {
+        let url = format!("{}/blobs", self.config.api_url);
+
+        let response = self
+            .http
+            .delete(&url)
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.config.auth_token),
+            )
+            .json(&DeleteBlobsRequest { blob_ids })
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let error_body: ApiError = response.json().await.unwrap_or(ApiError {
+                success: false,
+                error: Some("unknown error".to_string()),
+                code: None,
+            });
+            return Err(SyncError::Server(
+                error_body
+                    .error
+                    .unwrap_or_else(|| "delete blobs failed".to_string()),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Delete all cloud data for this user.
     pub async fn delete_all_data(&self) -> SyncResult<()> {
         let url = format!("{}/data", self.config.api_url);
@@ -891,6 +922,11 @@ pub struct SyncDevice {
     pub created_at: String,
 }
 
+#[derive(Debug, Serialize)]
+struct DeleteBlobsRequest<'a> {
+    blob_ids: &'a [String],
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiError {
     #[allow(dead_code)]
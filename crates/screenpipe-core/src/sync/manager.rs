@@ -1,7 +1,8 @@
 //! High-level sync manager for coordinating sync operations.
 //!
 //! This module provides a simple interface for common sync operations,
-//! handling the encryption/decryption and API communication internally.
+//! handling the encryption/decryption internally and storage through a
+//! [`SyncTransport`].
 
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -14,24 +15,31 @@ use super::client::{
 use super::crypto::generate_search_token;
 use super::error::{SyncError, SyncResult};
 use super::keys::SyncKeys;
+use super::self_hosted::compare_times;
+use super::transport::SyncTransport;
 
 /// High-level manager for sync operations.
 ///
-/// This struct holds the sync client and encryption keys, providing
+/// This struct holds the sync transport and encryption keys, providing
 /// a simple interface for uploading, downloading, and searching data.
 pub struct SyncManager {
-    client: SyncClient,
+    transport: Arc<dyn SyncTransport>,
     keys: Arc<RwLock<Option<SyncKeys>>>,
 }
 
 impl SyncManager {
-    /// Create a new sync manager.
+    /// Create a new sync manager using the hosted sync API.
     pub fn new(config: SyncClientConfig) -> SyncResult<Self> {
         let client = SyncClient::new(config)?;
-        Ok(Self {
-            client,
+        Ok(Self::with_transport(Arc::new(client)))
+    }
+
+    /// Create a new sync manager storing data through `transport`.
+    pub fn with_transport(transport: Arc<dyn SyncTransport>) -> Self {
+        Self {
+            transport,
             keys: Arc::new(RwLock::new(None)),
-        })
+        }
     }
 
     /// Check if the manager is initialized (has keys).
@@ -42,11 +50,11 @@ impl SyncManager {
     /// Initialize sync with the user's password.
     ///
     /// This will either create new encryption keys (for new users) or
-    /// derive keys from the server's stored encrypted master key.
+    /// derive keys from the encrypted master key stored by the transport.
     ///
     /// Returns true if this is a new user, false if existing.
     pub async fn initialize(&self, password: &str) -> SyncResult<bool> {
-        let (keys, is_new) = self.client.init(password).await?;
+        let (keys, is_new) = self.transport.init(password).await?;
         *self.keys.write().await = Some(keys);
         Ok(is_new)
     }
@@ -73,7 +81,7 @@ impl SyncManager {
 
     /// Upload data to the cloud.
     ///
-    /// This encrypts the data locally and uploads it to S3.
+    /// This encrypts the data locally and hands the ciphertext to the transport.
     ///
     /// # Arguments
     /// * `data` - The plaintext data to upload
@@ -102,22 +110,9 @@ impl SyncManager {
         let metadata =
             BlobMetadata::from_encrypted_blob(&encrypted, time_start, time_end, keys.key_version());
 
-        // Request upload URL
-        let upload_info = self.client.request_upload(&metadata).await?;
-
-        // Upload to S3
-        self.client
-            .upload_to_s3(&upload_info.upload_url, &encrypted.ciphertext)
-            .await?;
-
-        // Mark upload complete
-        let complete = self.client.complete_upload(&upload_info.blob_id).await?;
-
-        Ok(UploadResult {
-            blob_id: upload_info.blob_id,
-            storage_used: complete.storage_used,
-            storage_limit: complete.storage_limit,
-        })
+        self.transport
+            .upload(&metadata, &encrypted.ciphertext)
+            .await
     }
 
     // =========================================================================
@@ -136,7 +131,7 @@ impl SyncManager {
             limit: None,
         };
 
-        let blobs = self.client.get_download_urls(request).await?;
+        let blobs = self.transport.list_blobs(request).await?;
         self.download_and_decrypt_blobs(&blobs, keys).await
     }
 
@@ -158,7 +153,7 @@ impl SyncManager {
             limit,
         };
 
-        let blobs = self.client.get_download_urls(request).await?;
+        let blobs = self.transport.list_blobs(request).await?;
         self.download_and_decrypt_blobs(&blobs, keys).await
     }
 
@@ -170,8 +165,8 @@ impl SyncManager {
         let mut results = Vec::with_capacity(blobs.len());
 
         for blob in blobs {
-            // Download from S3
-            let ciphertext = self.client.download_from_s3(&blob.download_url).await?;
+            // Download ciphertext
+            let ciphertext = self.transport.download(blob).await?;
 
             // Decrypt
             let decrypted = decrypt_blob(
@@ -195,7 +190,7 @@ impl SyncManager {
     /// Search for blobs containing the given keywords.
     ///
     /// This generates search tokens from the keywords and searches the
-    /// encrypted index on the server.
+    /// encrypted index of the transport.
     pub async fn search(
         &self,
         keywords: &[&str],
@@ -222,7 +217,7 @@ impl SyncManager {
             limit,
         };
 
-        let response = self.client.search(request).await?;
+        let response = self.transport.search(request).await?;
 
         Ok(SearchResult {
             blob_ids: response.matches.iter().map(|m| m.blob_id.clone()).collect(),
@@ -266,7 +261,7 @@ impl SyncManager {
 
     /// Get sync status and quota information.
     pub async fn get_status(&self) -> SyncResult<SyncStatus> {
-        self.client.get_status().await
+        self.transport.get_status().await
     }
 
     // =========================================================================
@@ -275,29 +270,74 @@ impl SyncManager {
 
     /// Get list of registered devices.
     pub async fn get_devices(&self) -> SyncResult<Vec<SyncDevice>> {
-        self.client.list_devices().await
+        self.transport.list_devices().await
     }
 
     /// Remove a device from sync.
     pub async fn remove_device(&self, device_id: &str) -> SyncResult<()> {
-        self.client.remove_device(device_id).await
+        self.transport.remove_device(device_id).await
+    }
+
+    /// Delete the blobs of `blob_type` whose time range contains one of `times`
+    /// (ISO 8601), e.g. because records they hold were forgotten locally.
+    ///
+    /// Returns the deleted blobs, their other records have to be uploaded again.
+    pub async fn delete_blobs_containing(
+        &self,
+        blob_type: BlobType,
+        times: &[String],
+    ) -> SyncResult<Vec<DownloadBlob>> {
+        let (Some(first), Some(last)) = (
+            times.iter().min_by(|a, b| compare_times(a, b)),
+            times.iter().max_by(|a, b| compare_times(a, b)),
+        ) else {
+            return Ok(Vec::new());
+        };
+
+        let request = DownloadRequest {
+            blob_ids: None,
+            time_range: Some(TimeRange {
+                start: Some(first.clone()),
+                end: Some(last.clone()),
+            }),
+            blob_types: Some(vec![blob_type]),
+            limit: None,
+        };
+        let blobs: Vec<DownloadBlob> = self
+            .transport
+            .list_blobs(request)
+            .await?
+            .into_iter()
+            .filter(|blob| {
+                times.iter().any(|t| {
+                    compare_times(&blob.time_start, t).is_le()
+                        && compare_times(t, &blob.time_end).is_le()
+                })
+            })
+            .collect();
+
+        if !blobs.is_empty() {
+            let ids: Vec<String> = blobs.iter().map(|b| b.blob_id.clone()).collect();
+            self.transport.delete_blobs(&ids).await?;
+        }
+        Ok(blobs)
     }
 
     /// Delete all cloud data for this user.
     pub async fn delete_all_data(&self) -> SyncResult<()> {
-        self.client.delete_all_data().await
+        self.transport.delete_all_data().await
     }
 
-    /// Get the client for advanced operations.
-    pub fn client(&self) -> &SyncClient {
-        &self.client
+    /// Get the transport for advanced operations.
+    pub fn transport(&self) -> &Arc<dyn SyncTransport> {
+        &self.transport
     }
 }
 
 /// Result of an upload operation.
 #[derive(Debug)]
 pub struct UploadResult {
-    /// The blob ID assigned by the server
+    /// The blob ID assigned by the transport
     pub blob_id: String,
     /// Current storage used in bytes
     pub storage_used: u64,
@@ -10,6 +10,9 @@
 //!
 //! - **Multi-device sync**: Encrypted data can be synced across multiple devices.
 //!
+//! - **Pluggable storage**: The hosted API, a shared directory or an S3-compatible
+//!   bucket (see [`SyncTransport`] and [`SyncBackend`]).
+//!
 //! ## Key Hierarchy
 //!
 //! ```text
@@ -91,7 +94,10 @@ pub mod crypto;
 pub mod error;
 pub mod keys;
 pub mod manager;
+pub mod s3;
+pub mod self_hosted;
 pub mod service;
+pub mod transport;
 
 // Re-exports for convenient access
 pub use blob::{decrypt_blob, encrypt_blob, BlobMetadata, BlobType, DecryptedBlob, EncryptedBlob};
@@ -107,7 +113,10 @@ pub use crypto::{
 pub use error::{SyncError, SyncResult};
 pub use keys::{ExistingUserKeyData, NewUserKeyData, SyncKeys};
 pub use manager::{SearchMatchInfo, SearchResult, SyncManager, UploadResult};
+pub use s3::{S3Config, S3Store};
+pub use self_hosted::{DirectoryStore, ObjectStore, SelfHostedTransport};
 pub use service::{
     PendingBlob, SyncCommand, SyncDataProvider, SyncEvent, SyncReport, SyncService,
     SyncServiceConfig, SyncServiceHandle,
 };
+pub use transport::{SyncBackend, SyncTransport};
@@ -0,0 +1,686 @@
+//! Self-hosted sync on top of a plain object store.
+//!
+//! Layout of the store:
+//!
+//! ```text
+//! keys.json                  password-encrypted master key (ExistingUserKeyData)
+//! devices/<device_id>.json   registered devices
+//! blobs/<blob_id>.bin        ciphertext
+//! blobs/<blob_id>.json       blob metadata with HMAC search tokens
+//! ```
+//!
+//! The metadata file is written after the ciphertext, so a blob only becomes visible
+//! to other devices once it is complete. Blobs are immutable, their metadata is cached
+//! in memory and only new entries are fetched on each listing.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use super::blob::{BlobMetadata, BlobType};
+use super::client::{
+    DownloadBlob, DownloadRequest, SearchMatch, SearchRequest, SearchResponse, SyncDevice,
+    SyncQuota, SyncStats, SyncStatus, TimeRange,
+};
+use super::error::{SyncError, SyncResult};
+use super::keys::{ExistingUserKeyData, NewUserKeyData, SyncKeys};
+use super::manager::UploadResult;
+use super::transport::SyncTransport;
+
+const KEYS_KEY: &str = "keys.json";
+const DEVICES_PREFIX: &str = "devices/";
+const BLOBS_PREFIX: &str = "blobs/";
+
+/// Minimal key/value storage used by [`SelfHostedTransport`].
+///
+/// Keys are relative, `/`-separated paths.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// Write an object, replacing any previous content.
+    async fn put(&self, key: &str, data: &[u8]) -> SyncResult<()>;
+
+    /// Read an object, `None` if it does not exist.
+    async fn get(&self, key: &str) -> SyncResult<Option<Vec<u8>>>;
+
+    /// List the keys directly under `prefix` (which ends with `/`).
+    async fn list(&self, prefix: &str) -> SyncResult<Vec<String>>;
+
+    /// Delete an object, deleting a missing object is not an error.
+    async fn delete(&self, key: &str) -> SyncResult<()>;
+}
+
+/// Reject keys that could escape the store (`..`, absolute paths).
+pub(crate) fn validate_key(key: &str) -> SyncResult<()> {
+    if key.is_empty()
+        || key.starts_with('/')
+        || key.contains('\\')
+        || key.split('/').any(|part| part == ".." || part == ".")
+    {
+        return Err(SyncError::Server(format!("invalid object key: {}", key)));
+    }
+    Ok(())
+}
+
+/// Object store backed by a local or mounted directory.
+pub struct DirectoryStore {
+    root: PathBuf,
+}
+
+impl DirectoryStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> SyncResult<PathBuf> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for DirectoryStore {
+    async fn put(&self, key: &str, data: &[u8]) -> SyncResult<()> {
+        let path = self.path(key)?;
+        let parent = path.parent().unwrap_or(Path::new("."));
+        tokio::fs::create_dir_all(parent).await?;
+
+        // Write then rename so folder sync tools never pick up a partial file
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let tmp = parent.join(format!(".{}.tmp", file_name));
+        tokio::fs::write(&tmp, data).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> SyncResult<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> SyncResult<Vec<String>> {
+        let mut entries = match tokio::fs::read_dir(self.path(prefix.trim_end_matches('/'))?).await
+        {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut keys = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Temp files and sync tool metadata (.stfolder, .stversions)
+            if name.starts_with('.') || !entry.file_type().await?.is_file() {
+                continue;
+            }
+            keys.push(format!("{}{}", prefix, name));
+        }
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> SyncResult<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Blob metadata as stored next to the ciphertext.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredBlob {
+    blob_id: String,
+    device_id: String,
+    created_at: DateTime<Utc>,
+    #[serde(flatten)]
+    metadata: BlobMetadata,
+}
+
+impl StoredBlob {
+    fn data_key(&self) -> String {
+        format!("{}{}.bin", BLOBS_PREFIX, self.blob_id)
+    }
+
+    fn to_download_blob(&self) -> DownloadBlob {
+        DownloadBlob {
+            blob_id: self.blob_id.clone(),
+            blob_type: self.metadata.blob_type,
+            download_url: self.data_key(),
+            encryption_nonce: self.metadata.encryption_nonce.clone(),
+            key_version: self.metadata.key_version,
+            size_bytes: self.metadata.size_bytes,
+            checksum: self.metadata.checksum.clone(),
+            time_start: self.metadata.time_start.clone(),
+            time_end: self.metadata.time_end.clone(),
+        }
+    }
+
+    fn matches(&self, time_range: Option<&TimeRange>, blob_types: Option<&[BlobType]>) -> bool {
+        if let Some(types) = blob_types {
+            if !types.contains(&self.metadata.blob_type) {
+                return false;
+            }
+        }
+        if let Some(range) = time_range {
+            if let Some(start) = &range.start {
+                if compare_times(&self.metadata.time_end, start).is_lt() {
+                    return false;
+                }
+            }
+            if let Some(end) = &range.end {
+                if compare_times(&self.metadata.time_start, end).is_gt() {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Compare two ISO 8601 timestamps, falling back to string order if either is not RFC 3339.
+pub(crate) fn compare_times(a: &str, b: &str) -> std::cmp::Ordering {
+    match (
+        DateTime::parse_from_rfc3339(a),
+        DateTime::parse_from_rfc3339(b),
+    ) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredDevice {
+    device_id: String,
+    device_name: String,
+    device_os: String,
+    created_at: DateTime<Utc>,
+    #[serde(default)]
+    last_sync_at: Option<DateTime<Utc>>,
+}
+
+/// Sync transport storing everything in an [`ObjectStore`] the user controls.
+pub struct SelfHostedTransport {
+    store: Arc<dyn ObjectStore>,
+    device_id: String,
+    device_name: String,
+    device_os: String,
+    /// Blob metadata already read from the store, keyed by blob id
+    index: RwLock<HashMap<String, StoredBlob>>,
+}
+
+impl SelfHostedTransport {
+    pub fn new(
+        store: Arc<dyn ObjectStore>,
+        device_id: String,
+        device_name: String,
+        device_os: String,
+    ) -> Self {
+        Self {
+            store,
+            device_id,
+            device_name,
+            device_os,
+            index: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn new_blob_id(&self) -> String {
+        let mut random = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut random);
+        format!(
+            "{}-{}-{}",
+            Utc::now().format("%Y%m%d%H%M%S"),
+            hex::encode(random),
+            sanitize_id(&self.device_id)
+        )
+    }
+
+    async fn register_device(&self, last_sync_at: Option<DateTime<Utc>>) -> SyncResult<()> {
+        let key = device_key(&self.device_id);
+        let created_at = match self.store.get(&key).await? {
+            Some(data) => serde_json::from_slice::<StoredDevice>(&data)
+                .map(|d| d.created_at)
+                .unwrap_or_else(|_| Utc::now()),
+            None => Utc::now(),
+        };
+        let device = StoredDevice {
+            device_id: self.device_id.clone(),
+            device_name: self.device_name.clone(),
+            device_os: self.device_os.clone(),
+            created_at,
+            last_sync_at,
+        };
+        self.store
+            .put(&key, &serde_json::to_vec_pretty(&device)?)
+            .await
+    }
+
+    /// Refresh the in-memory index with blobs written since the last call and return a
+    /// snapshot of it.
+    async fn load_index(&self) -> SyncResult<Vec<StoredBlob>> {
+        let keys = self.store.list(BLOBS_PREFIX).await?;
+        let listed: HashSet<String> = keys
+            .iter()
+            .filter_map(|key| {
+                key.strip_prefix(BLOBS_PREFIX)
+                    .and_then(|name| name.strip_suffix(".json"))
+                    .map(str::to_string)
+            })
+            .collect();
+
+        let missing: Vec<String> = {
+            let index = self.index.read().await;
+            listed
+                .iter()
+                .filter(|id| !index.contains_key(*id))
+                .cloned()
+                .collect()
+        };
+
+        let mut fetched = Vec::with_capacity(missing.len());
+        for blob_id in missing {
+            let key = format!("{}{}.json", BLOBS_PREFIX, blob_id);
+            let Some(data) = self.store.get(&key).await? else {
+                continue;
+            };
+            match serde_json::from_slice::<StoredBlob>(&data) {
+                Ok(blob) => fetched.push(blob),
+                Err(e) => tracing::warn!("skipping unreadable blob metadata {}: {}", key, e),
+            }
+        }
+
+        let mut index = self.index.write().await;
+        for blob in fetched {
+            index.insert(blob.blob_id.clone(), blob);
+        }
+        // Drop blobs deleted by another device
+        index.retain(|id, _| listed.contains(id));
+        Ok(index.values().cloned().collect())
+    }
+}
+
+fn device_key(device_id: &str) -> String {
+    format!("{}{}.json", DEVICES_PREFIX, sanitize_id(device_id))
+}
+
+/// Keep ids usable as file names on every platform.
+fn sanitize_id(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[async_trait]
+impl SyncTransport for SelfHostedTransport {
+    async fn init(&self, password: &str) -> SyncResult<(SyncKeys, bool)> {
+        let (keys, is_new) = match self.store.get(KEYS_KEY).await? {
+            Some(data) => {
+                let key_data: ExistingUserKeyData = serde_json::from_slice(&data)?;
+                (key_data.derive_keys(password)?, false)
+            }
+            None => {
+                let (new_data, keys) = NewUserKeyData::generate(password)?;
+                let key_data = ExistingUserKeyData {
+                    salt: new_data.salt,
+                    encrypted_master_key: new_data.encrypted_master_key,
+                    master_key_nonce: new_data.master_key_nonce,
+                    kdf_algorithm: "argon2id".to_string(),
+                    kdf_iterations: 3,
+                    kdf_memory: 65536,
+                    key_version: keys.key_version(),
+                };
+                self.store
+                    .put(KEYS_KEY, &serde_json::to_vec_pretty(&key_data)?)
+                    .await?;
+                (keys, true)
+            }
+        };
+
+        self.register_device(None).await?;
+        Ok((keys, is_new))
+    }
+
+    async fn upload(&self, metadata: &BlobMetadata, ciphertext: &[u8]) -> SyncResult<UploadResult> {
+        let stored = StoredBlob {
+            blob_id: self.new_blob_id(),
+            device_id: self.device_id.clone(),
+            created_at: Utc::now(),
+            metadata: BlobMetadata {
+                blob_id: None,
+                ..metadata.clone()
+            },
+        };
+
+        self.store.put(&stored.data_key(), ciphertext).await?;
+        self.store
+            .put(
+                &format!("{}{}.json", BLOBS_PREFIX, stored.blob_id),
+                &serde_json::to_vec(&stored)?,
+            )
+            .await?;
+
+        let blob_id = stored.blob_id.clone();
+        self.index.write().await.insert(blob_id.clone(), stored);
+
+        if let Err(e) = self.register_device(Some(Utc::now())).await {
+            tracing::debug!("failed to update device last sync: {}", e);
+        }
+
+        let storage_used = self
+            .index
+            .read()
+            .await
+            .values()
+            .map(|b| b.metadata.size_bytes)
+            .sum();
+        Ok(UploadResult {
+            blob_id,
+            storage_used,
+            // no quota on self-hosted storage
+            storage_limit: 0,
+        })
+    }
+
+    async fn list_blobs(&self, request: DownloadRequest) -> SyncResult<Vec<DownloadBlob>> {
+        let mut blobs: Vec<StoredBlob> = self
+            .load_index()
+            .await?
+            .into_iter()
+            .filter(|blob| match &request.blob_ids {
+                Some(ids) => ids.contains(&blob.blob_id),
+                None => true,
+            })
+            .filter(|blob| blob.matches(request.time_range.as_ref(), request.blob_types.as_deref()))
+            .collect();
+
+        blobs.sort_by(|a, b| compare_times(&a.metadata.time_start, &b.metadata.time_start));
+        if let Some(limit) = request.limit {
+            blobs.truncate(limit as usize);
+        }
+        Ok(blobs.iter().map(StoredBlob::to_download_blob).collect())
+    }
+
+    async fn download(&self, blob: &DownloadBlob) -> SyncResult<Vec<u8>> {
+        self.store
+            .get(&blob.download_url)
+            .await?
+            .ok_or_else(|| SyncError::NotFound(blob.blob_id.clone()))
+    }
+
+    async fn search(&self, request: SearchRequest) -> SyncResult<SearchResponse> {
+        let wanted: HashSet<&String> = request.search_tokens.iter().collect();
+        let mut matches: Vec<(u32, StoredBlob)> = self
+            .load_index()
+            .await?
+            .into_iter()
+            .filter(|blob| blob.matches(request.time_range.as_ref(), request.blob_types.as_deref()))
+            .filter_map(|blob| {
+                let count = blob
+                    .metadata
+                    .search_tokens
+                    .iter()
+                    .filter(|token| wanted.contains(token))
+                    .count() as u32;
+                (count > 0).then_some((count, blob))
+            })
+            .collect();
+
+        matches.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| compare_times(&b.1.metadata.time_start, &a.1.metadata.time_start))
+        });
+        let total_matches = matches.len() as u32;
+        if let Some(limit) = request.limit {
+            matches.truncate(limit as usize);
+        }
+
+        Ok(SearchResponse {
+            matches: matches
+                .into_iter()
+                .map(|(match_count, blob)| SearchMatch {
+                    blob_id: blob.blob_id,
+                    blob_type: blob.metadata.blob_type,
+                    time_start: blob.metadata.time_start,
+                    time_end: blob.metadata.time_end,
+                    match_count,
+                })
+                .collect(),
+            total_matches,
+        })
+    }
+
+    async fn get_status(&self) -> SyncResult<SyncStatus> {
+        let blobs = self.load_index().await?;
+        let devices = self.list_devices().await?;
+        let total_bytes: u64 = blobs.iter().map(|b| b.metadata.size_bytes).sum();
+
+        Ok(SyncStatus {
+            quota: SyncQuota {
+                storage_used: total_bytes,
+                storage_limit: 0,
+                device_count: devices.len() as u32,
+                device_limit: 0,
+                can_sync: true,
+                sync_tier: Some("self-hosted".to_string()),
+            },
+            stats: SyncStats {
+                total_blobs: blobs.len() as u32,
+                total_bytes,
+                last_sync_at: blobs
+                    .iter()
+                    .map(|b| b.created_at)
+                    .max()
+                    .map(|t| t.to_rfc3339()),
+                oldest_data: blobs
+                    .iter()
+                    .map(|b| &b.metadata.time_start)
+                    .min_by(|a, b| compare_times(a, b))
+                    .cloned(),
+                newest_data: blobs
+                    .iter()
+                    .map(|b| &b.metadata.time_end)
+                    .max_by(|a, b| compare_times(a, b))
+                    .cloned(),
+            },
+            storage_breakdown: None,
+        })
+    }
+
+    async fn list_devices(&self) -> SyncResult<Vec<SyncDevice>> {
+        let mut devices = Vec::new();
+        for key in self.store.list(DEVICES_PREFIX).await? {
+            let Some(data) = self.store.get(&key).await? else {
+                continue;
+            };
+            let Ok(device) = serde_json::from_slice::<StoredDevice>(&data) else {
+                continue;
+            };
+            devices.push(SyncDevice {
+                id: device.device_id.clone(),
+                device_id: device.device_id,
+                device_name: Some(device.device_name),
+                device_os: device.device_os,
+                last_sync_at: device.last_sync_at.map(|t| t.to_rfc3339()),
+                created_at: device.created_at.to_rfc3339(),
+            });
+        }
+        Ok(devices)
+    }
+
+    async fn remove_device(&self, device_id: &str) -> SyncResult<()> {
+        self.store.delete(&device_key(device_id)).await
+    }
+
+    async fn delete_blobs(&self, blob_ids: &[String]) -> SyncResult<()> {
+        for blob_id in blob_ids {
+            let blob_id = sanitize_id(blob_id);
+            self.store
+                .delete(&format!("{}{}.bin", BLOBS_PREFIX, blob_id))
+                .await?;
+            self.store
+                .delete(&format!("{}{}.json", BLOBS_PREFIX, blob_id))
+                .await?;
+            self.index.write().await.remove(&blob_id);
+        }
+        Ok(())
+    }
+
+    async fn delete_all_data(&self) -> SyncResult<()> {
+        // Key material and devices stay so other devices keep syncing
+        for key in self.store.list(BLOBS_PREFIX).await? {
+            self.store.delete(&key).await?;
+        }
+        self.index.write().await.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::manager::SyncManager;
+
+    fn transport(root: &Path, device_id: &str) -> Arc<SelfHostedTransport> {
+        Arc::new(SelfHostedTransport::new(
+            Arc::new(DirectoryStore::new(root)),
+            device_id.to_string(),
+            device_id.to_string(),
+            "linux".to_string(),
+        ))
+    }
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("blobs/abc.json").is_ok());
+        assert!(validate_key("../etc/passwd").is_err());
+        assert!(validate_key("blobs/../../x").is_err());
+        assert!(validate_key("/abs").is_err());
+        assert!(validate_key("").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_directory_sync_between_devices() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let laptop = SyncManager::with_transport(transport(dir.path(), "laptop"));
+        assert!(laptop.initialize("password").await.unwrap());
+        laptop
+            .upload(
+                b"meeting notes",
+                BlobType::Ocr,
+                "2024-01-01T10:00:00Z",
+                "2024-01-01T10:05:00Z",
+                Some("quarterly planning meeting"),
+            )
+            .await
+            .unwrap();
+
+        // ciphertext only on disk
+        for key in DirectoryStore::new(dir.path())
+            .list(BLOBS_PREFIX)
+            .await
+            .unwrap()
+        {
+            let data = std::fs::read(dir.path().join(&key)).unwrap();
+            assert!(!String::from_utf8_lossy(&data).contains("meeting notes"));
+        }
+
+        let desktop = SyncManager::with_transport(transport(dir.path(), "desktop"));
+        assert!(!desktop.initialize("password").await.unwrap());
+
+        let blobs = desktop
+            .download_by_time_range(Some("2024-01-01T00:00:00Z".to_string()), None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(blobs[0].data, b"meeting notes");
+
+        let result = desktop
+            .search(&["quarterly"], None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(result.total, 1);
+        let result = desktop
+            .search(&["unrelated"], None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(result.total, 0);
+
+        assert_eq!(desktop.get_devices().await.unwrap().len(), 2);
+
+        // wrong password cannot derive the keys
+        let intruder = SyncManager::with_transport(transport(dir.path(), "intruder"));
+        assert!(intruder.initialize("wrong").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delete_blobs_containing() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = SyncManager::with_transport(transport(dir.path(), "laptop"));
+        manager.initialize("password").await.unwrap();
+        for (start, end) in [
+            ("2024-01-01T10:00:00Z", "2024-01-01T10:05:00Z"),
+            ("2024-01-01T10:05:01Z", "2024-01-01T10:10:00Z"),
+        ] {
+            manager
+                .upload(b"frames", BlobType::Ocr, start, end, None)
+                .await
+                .unwrap();
+        }
+        manager
+            .upload(
+                b"transcript",
+                BlobType::Transcripts,
+                "2024-01-01T10:00:00Z",
+                "2024-01-01T10:05:00Z",
+                None,
+            )
+            .await
+            .unwrap();
+
+        let deleted = manager
+            .delete_blobs_containing(BlobType::Ocr, &["2024-01-01T10:02:00Z".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].time_start, "2024-01-01T10:00:00Z");
+
+        // a fresh transport only sees what is left in the store
+        let remaining = SyncManager::with_transport(transport(dir.path(), "laptop"));
+        remaining.initialize("password").await.unwrap();
+        let blobs = remaining
+            .download_by_time_range(None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(blobs.len(), 2);
+        assert_eq!(
+            blobs
+                .iter()
+                .filter(|b| b.blob_type == BlobType::Ocr)
+                .count(),
+            1
+        );
+        assert!(manager
+            .delete_blobs_containing(BlobType::Ocr, &[])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -93,9 +93,15 @@ pub enum SyncCommand {
 /// Handle for controlling a running sync service.
 pub struct SyncServiceHandle {
     command_tx: mpsc::Sender<SyncCommand>,
+    manager: Arc<SyncManager>,
 }
 
 impl SyncServiceHandle {
+    /// The manager the service uploads through.
+    pub fn manager(&self) -> &Arc<SyncManager> {
+        &self.manager
+    }
+
     /// Trigger an immediate sync.
     pub async fn sync_now(&self) -> SyncResult<()> {
         self.command_tx
@@ -205,7 +211,10 @@ impl SyncService {
         let (command_tx, command_rx) = mpsc::channel(16);
         let (event_tx, event_rx) = mpsc::channel(64);
 
-        let handle = SyncServiceHandle { command_tx };
+        let handle = SyncServiceHandle {
+            command_tx,
+            manager: self.manager.clone(),
+        };
 
         // Spawn the background task
         tokio::spawn(self.run(command_rx, event_tx));
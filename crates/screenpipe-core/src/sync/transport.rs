@@ -0,0 +1,200 @@
+//! Storage backends for sync.
+//!
+//! [`SyncManager`](super::manager::SyncManager) encrypts everything before it reaches a
+//! transport, so a transport only ever sees ciphertext, HMAC search tokens and the
+//! password-encrypted master key. The hosted API ([`SyncClient`]) is one transport,
+//! [`SelfHostedTransport`](super::self_hosted::SelfHostedTransport) stores the same data
+//! in a plain directory or an S3-compatible bucket.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::blob::BlobMetadata;
+use super::client::{
+    DownloadBlob, DownloadRequest, SearchRequest, SearchResponse, SyncClient, SyncClientConfig,
+    SyncDevice, SyncStatus,
+};
+use super::error::SyncResult;
+use super::keys::SyncKeys;
+use super::manager::UploadResult;
+use super::s3::{S3Config, S3Store};
+use super::self_hosted::{DirectoryStore, SelfHostedTransport};
+
+/// Where encrypted blobs, the search index and key material are stored.
+#[async_trait]
+pub trait SyncTransport: Send + Sync {
+    /// Fetch the encrypted master key and derive the keys from `password`, or create and
+    /// store new key material on first use.
+    ///
+    /// Returns true if new keys were created.
+    async fn init(&self, password: &str) -> SyncResult<(SyncKeys, bool)>;
+
+    /// Store an encrypted blob and its metadata.
+    async fn upload(&self, metadata: &BlobMetadata, ciphertext: &[u8]) -> SyncResult<UploadResult>;
+
+    /// List the blobs matching a download request.
+    async fn list_blobs(&self, request: DownloadRequest) -> SyncResult<Vec<DownloadBlob>>;
+
+    /// Fetch the ciphertext of a blob returned by [`SyncTransport::list_blobs`].
+    async fn download(&self, blob: &DownloadBlob) -> SyncResult<Vec<u8>>;
+
+    /// Find blobs by search tokens.
+    async fn search(&self, request: SearchRequest) -> SyncResult<SearchResponse>;
+
+    /// Get storage usage and statistics.
+    async fn get_status(&self) -> SyncResult<SyncStatus>;
+
+    /// List devices that registered with this backend.
+    async fn list_devices(&self) -> SyncResult<Vec<SyncDevice>>;
+
+    /// Remove a device.
+    async fn remove_device(&self, device_id: &str) -> SyncResult<()>;
+
+    /// Delete blobs by id, ids that don't exist are ignored.
+    async fn delete_blobs(&self, blob_ids: &[String]) -> SyncResult<()>;
+
+    /// Delete all synced data.
+    async fn delete_all_data(&self) -> SyncResult<()>;
+}
+
+#[async_trait]
+impl SyncTransport for SyncClient {
+    async fn init(&self, password: &str) -> SyncResult<(SyncKeys, bool)> {
+        SyncClient::init(self, password).await
+    }
+
+    async fn upload(&self, metadata: &BlobMetadata, ciphertext: &[u8]) -> SyncResult<UploadResult> {
+        let upload_info = self.request_upload(metadata).await?;
+        self.upload_to_s3(&upload_info.upload_url, ciphertext)
+            .await?;
+        let complete = self.complete_upload(&upload_info.blob_id).await?;
+
+        Ok(UploadResult {
+            blob_id: upload_info.blob_id,
+            storage_used: complete.storage_used,
+            storage_limit: complete.storage_limit,
+        })
+    }
+
+    async fn list_blobs(&self, request: DownloadRequest) -> SyncResult<Vec<DownloadBlob>> {
+        self.get_download_urls(request).await
+    }
+
+    async fn download(&self, blob: &DownloadBlob) -> SyncResult<Vec<u8>> {
+        self.download_from_s3(&blob.download_url).await
+    }
+
+    async fn search(&self, request: SearchRequest) -> SyncResult<SearchResponse> {
+        SyncClient::search(self, request).await
+    }
+
+    async fn get_status(&self) -> SyncResult<SyncStatus> {
+        SyncClient::get_status(self).await
+    }
+
+    async fn list_devices(&self) -> SyncResult<Vec<SyncDevice>> {
+        SyncClient::list_devices(self).await
+    }
+
+    async fn remove_device(&self, device_id: &str) -> SyncResult<()> {
+        SyncClient::remove_device(self, device_id).await
+    }
+
+    async fn delete_blobs(&self, blob_ids: &[String]) -> SyncResult<()> {
+        SyncClient::delete_blobs(self, blob_ids).await
+    }
+
+    async fn delete_all_data(&self) -> SyncResult<()> {
+        SyncClient::delete_all_data(self).await
+    }
+}
+
+/// Backend selection, as given on the command line or to `/sync/init`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SyncBackend {
+    /// The hosted screenpipe sync API
+    Hosted {
+        /// Authentication token
+        token: String,
+        /// Override the API URL
+        #[serde(default)]
+        api_url: Option<String>,
+    },
+    /// A directory shared between devices (NAS mount, Syncthing folder, ...)
+    Directory {
+        /// Root of the sync store
+        path: PathBuf,
+    },
+    /// An S3-compatible bucket (MinIO, Garage, Ceph, AWS, ...)
+    S3(S3Config),
+}
+
+impl SyncBackend {
+    /// Short name used in logs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SyncBackend::Hosted { .. } => "hosted",
+            SyncBackend::Directory { .. } => "directory",
+            SyncBackend::S3(_) => "s3",
+        }
+    }
+
+    /// Build the transport for this backend.
+    pub fn into_transport(
+        self,
+        device_id: String,
+        device_name: String,
+        device_os: String,
+    ) -> SyncResult<Arc<dyn SyncTransport>> {
+        Ok(match self {
+            SyncBackend::Hosted { token, api_url } => {
+                let mut config = SyncClientConfig::new(token, device_id, device_name, device_os);
+                if let Some(url) = api_url {
+                    config = config.with_api_url(url);
+                }
+                Arc::new(SyncClient::new(config)?)
+            }
+            SyncBackend::Directory { path } => Arc::new(SelfHostedTransport::new(
+                Arc::new(DirectoryStore::new(path)),
+                device_id,
+                device_name,
+                device_os,
+            )),
+            SyncBackend::S3(config) => Arc::new(SelfHostedTransport::new(
+                Arc::new(S3Store::new(config)?),
+                device_id,
+                device_name,
+                device_os,
+            )),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_deserialization() {
+        let backend: SyncBackend =
+            serde_json::from_str(r#"{"type": "directory", "path": "/mnt/nas/screenpipe"}"#)
+                .unwrap();
+        assert_eq!(backend.name(), "directory");
+
+        let backend: SyncBackend = serde_json::from_str(
+            r#"{"type": "s3", "endpoint": "http://localhost:9000", "bucket": "screenpipe",
+                "access_key_id": "minio", "secret_access_key": "minio123"}"#,
+        )
+        .unwrap();
+        match backend {
+            SyncBackend::S3(config) => {
+                assert_eq!(config.region, "us-east-1");
+                assert_eq!(config.prefix, None);
+            }
+            other => panic!("unexpected backend {:?}", other),
+        }
+    }
+}
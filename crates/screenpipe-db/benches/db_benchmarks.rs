@@ -2,7 +2,9 @@
 
 use criterion::{criterion_group, criterion_main, Criterion};
 use rand::Rng;
-use screenpipe_db::{AudioDevice, ContentType, DatabaseManager, DeviceType, OcrEngine};
+use screenpipe_db::{
+    AudioDevice, ContentType, DatabaseManager, DeviceType, OcrEngine, SearchOrder,
+};
 use std::sync::Arc;
 use tokio::runtime::Runtime;
 
@@ -87,6 +89,7 @@ fn bench_search(c: &mut Criterion) {
                                 None,
                                 None,
                                 None,
+                                SearchOrder::Time,
                             )
                             .await
                             .unwrap()
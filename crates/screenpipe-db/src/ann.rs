@@ -0,0 +1,1198 @@
+//! Approximate nearest neighbour index over `ocr_text_embeddings`.
+//!
+//! An IVF-flat index: vectors are normalized, clustered with spherical k-means and a
+//! query only scans the lists of its closest centroids. Small indexes use a single list,
+//! i.e. an exact scan.
+//!
+//! The vectors live in a memory-mapped file next to the database, only the list each one
+//! belongs to is kept in memory. A small header file names that vector file and records
+//! how many of its vectors are saved.
+//!
+//! The table stays the source of truth. Writers of the table hand new rows to the index
+//! as they insert them (see `DatabaseManager::attach_ocr_embedding_index`). The index
+//! remembers the highest embedding row id up to which it contains every row and catches
+//! up from there, so rows written by another process (e.g. `screenpipe add` while the
+//! server runs) get indexed too. Until then, searches scan the rows past that id exactly
+//! and merge them with the index hits. One process at a time maintains the index files.
+//!
+//! The index is rebuilt from the table, page by page, when its files are missing or
+//! corrupt, when it has grown well past the size it was trained on, or when many of its
+//! rows were deleted.
+
+use anyhow::{anyhow, Result};
+use memmap2::Mmap;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{debug, error, info, warn};
+
+use crate::{DatabaseManager, OCRResult};
+
+/// File name of the index of the `add --use-embedding` vectors in the data directory.
+pub const OCR_EMBEDDING_INDEX_FILE: &str = "ocr_embeddings.ann";
+
+const MAGIC: &[u8; 8] = b"SPANN\x00\x00\x02";
+
+const VECTORS_MAGIC: &[u8; 8] = b"SPVEC\x00\x00\x02";
+
+/// Magic, generation, dimension and list count of a vector file.
+const VECTORS_HEADER_LEN: usize = 32;
+
+/// Row id, frame id, list and padding before the vector of a record.
+const RECORD_HEADER_LEN: usize = 24;
+
+/// Below this many vectors the index is a single exact list.
+const FLAT_LIMIT: usize = 4096;
+
+/// Upper bound on the number of lists, keeps training in the order of a minute.
+const MAX_LISTS: usize = 1024;
+
+/// Training sample size per list.
+const TRAIN_SAMPLES_PER_LIST: usize = 64;
+
+const KMEANS_ITERATIONS: usize = 10;
+
+/// Rows read from the table per query when building or catching up.
+const SYNC_PAGE_SIZE: i64 = 5000;
+
+/// Interval between two catch-ups of the background task.
+const SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Candidates fetched per requested result, covers rows deleted since the last rebuild.
+const OVERFETCH: usize = 2;
+
+/// Spherical k-means IVF index of normalized vectors keyed by frame id.
+pub struct IvfIndex {
+    /// Header file, the vectors are in [`vectors_path`] of it
+    path: PathBuf,
+    generation: u64,
+    dim: usize,
+    /// `nlist * dim` centroids, empty for a single exact list
+    centroids: Vec<f32>,
+    /// Records of each list
+    lists: Vec<Vec<u32>>,
+    /// Every `ocr_text_embeddings.id` up to this one is in the index
+    max_row_id: i64,
+    /// Rows past `max_row_id` that are in the index too
+    ahead: BTreeSet<i64>,
+    len: usize,
+    /// Number of vectors the centroids were trained on
+    trained_len: usize,
+    vectors: VectorFile,
+}
+
+/// Records `(row_id, frame_id, list, vector)` of an index, appended and memory-mapped.
+struct VectorFile {
+    file: File,
+    map: Mmap,
+    data_start: usize,
+    record_len: usize,
+    count: usize,
+}
+
+impl VectorFile {
+    fn create(path: &Path, generation: u64, dim: usize, centroids: &[f32]) -> Result<Self> {
+        let nlist = if centroids.is_empty() {
+            1
+        } else {
+            centroids.len() / dim
+        };
+        let mut header = Vec::with_capacity(VECTORS_HEADER_LEN + centroids.len() * 4);
+        header.extend_from_slice(VECTORS_MAGIC);
+        header.extend_from_slice(&generation.to_le_bytes());
+        header.extend_from_slice(&(dim as u64).to_le_bytes());
+        header.extend_from_slice(&(nlist as u64).to_le_bytes());
+        put_f32s(&mut header, centroids);
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        file.write_all(&header)?;
+        Self::map(file, dim, header.len(), 0)
+    }
+
+    fn map(file: File, dim: usize, data_start: usize, count: usize) -> Result<Self> {
+        // SAFETY: the file is only written through this struct, by the one process
+        // holding the lock of the index (see `EmbeddingAnnIndex::lock_files`)
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self {
+            file,
+            map,
+            data_start,
+            record_len: RECORD_HEADER_LEN + dim * 4,
+            count,
+        })
+    }
+
+    fn record(&self, record: u32) -> &[u8] {
+        let start = self.data_start + record as usize * self.record_len;
+        &self.map[start..start + self.record_len]
+    }
+
+    /// Overwrite the frame id and vector of `record`, which stays in place as a removed
+    /// record.
+    fn blank(&mut self, record: u32) -> Result<()> {
+        let start = self.data_start + record as usize * self.record_len;
+        let mut blank = vec![0u8; self.record_len - 8];
+        blank[..8].copy_from_slice(&(-1i64).to_le_bytes());
+        self.file.seek(SeekFrom::Start(start as u64 + 8))?;
+        self.file.write_all(&blank)?;
+        Ok(())
+    }
+
+    /// Flush the records overwritten by [`Self::blank`] to disk.
+    fn sync(&mut self) -> Result<()> {
+        self.file.sync_data()?;
+        // SAFETY: see `map`
+        self.map = unsafe { Mmap::map(&self.file)? };
+        Ok(())
+    }
+
+    /// Append `records`, a multiple of `record_len` bytes.
+    fn append(&mut self, records: &[u8]) -> Result<()> {
+        let end = self.data_start + self.count * self.record_len;
+        self.file.seek(SeekFrom::Start(end as u64))?;
+        self.file.write_all(records)?;
+        self.count += records.len() / self.record_len;
+        // SAFETY: see `map`
+        self.map = unsafe { Mmap::map(&self.file)? };
+        Ok(())
+    }
+}
+
+impl IvfIndex {
+    /// An empty index of `dim` vectors and `centroids` (none for a single list), saved at
+    /// `path`.
+    fn create(path: &Path, generation: u64, dim: usize, centroids: Vec<f32>) -> Result<Self> {
+        let nlist = if centroids.is_empty() {
+            1
+        } else {
+            centroids.len() / dim
+        };
+        let vectors =
+            VectorFile::create(&vectors_path(path, generation), generation, dim, &centroids)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            generation,
+            dim,
+            centroids,
+            lists: vec![Vec::new(); nlist],
+            max_row_id: 0,
+            ahead: BTreeSet::new(),
+            len: 0,
+            trained_len: 0,
+            vectors,
+        })
+    }
+
+    /// Load the index saved at `path`. Vectors appended after the last save are dropped.
+    fn open(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        if bytes.len() < MAGIC.len() + 8 || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(anyhow!("not an embedding index"));
+        }
+        let (payload, checksum) = bytes.split_at(bytes.len() - 8);
+        if fnv1a(payload).to_le_bytes() != checksum {
+            return Err(anyhow!("embedding index checksum mismatch"));
+        }
+        let mut reader = Reader {
+            bytes: &payload[MAGIC.len()..],
+        };
+        let generation = reader.u64()?;
+        let dim = reader.u64()? as usize;
+        let nlist = reader.u64()? as usize;
+        let max_row_id = reader.u64()? as i64;
+        let trained_len = reader.u64()? as usize;
+        let count = reader.u64()? as usize;
+        if !reader.bytes.is_empty() {
+            return Err(anyhow!("trailing bytes in embedding index"));
+        }
+        if nlist == 0 || nlist > MAX_LISTS || (nlist > 1 && dim == 0) {
+            return Err(anyhow!("invalid list count {}", nlist));
+        }
+
+        let centroid_count = if nlist > 1 { nlist * dim } else { 0 };
+        let data_start = VECTORS_HEADER_LEN + centroid_count * 4;
+        let saved_len = count
+            .checked_mul(RECORD_HEADER_LEN + dim * 4)
+            .and_then(|len| len.checked_add(data_start))
+            .ok_or_else(|| anyhow!("invalid vector count {}", count))?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(vectors_path(path, generation))?;
+        let file_len = file.metadata()?.len();
+        if file_len < saved_len as u64 {
+            return Err(anyhow!("truncated embedding vectors"));
+        }
+        if file_len > saved_len as u64 {
+            file.set_len(saved_len as u64)?;
+        }
+        let vectors = VectorFile::map(file, dim, data_start, count)?;
+
+        let mut reader = Reader {
+            bytes: &vectors.map[..data_start],
+        };
+        if reader.take(VECTORS_MAGIC.len())? != VECTORS_MAGIC
+            || reader.u64()? != generation
+            || reader.u64()? as usize != dim
+            || reader.u64()? as usize != nlist
+        {
+            return Err(anyhow!("embedding vectors don't match the index"));
+        }
+        let centroids = reader.f32s(centroid_count)?;
+
+        let mut lists = vec![Vec::new(); nlist];
+        let mut ahead = BTreeSet::new();
+        let mut len = 0;
+        for record in 0..count as u32 {
+            let bytes = vectors.record(record);
+            let (row_id, frame_id, list) = record_header(bytes);
+            if frame_id < 0 {
+                continue;
+            }
+            lists
+                .get_mut(list)
+                .ok_or_else(|| anyhow!("invalid list {} of embedding {}", list, row_id))?
+                .push(record);
+            len += 1;
+            if row_id > max_row_id {
+                ahead.insert(row_id);
+            }
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            generation,
+            dim,
+            centroids,
+            lists,
+            max_row_id,
+            ahead,
+            len,
+            trained_len,
+            vectors,
+        })
+    }
+
+    /// Write the header, which makes the vectors appended so far part of the saved index.
+    fn save(&self) -> Result<()> {
+        let mut out = Vec::with_capacity(MAGIC.len() + 7 * 8);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&self.generation.to_le_bytes());
+        out.extend_from_slice(&(self.dim as u64).to_le_bytes());
+        out.extend_from_slice(&(self.lists.len() as u64).to_le_bytes());
+        out.extend_from_slice(&self.max_row_id.to_le_bytes());
+        out.extend_from_slice(&(self.trained_len as u64).to_le_bytes());
+        out.extend_from_slice(&(self.vectors.count as u64).to_le_bytes());
+        let checksum = fnv1a(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, out)?;
+        std::fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn max_row_id(&self) -> i64 {
+        self.max_row_id
+    }
+
+    /// Mark the embedding row `row_id` as indexed, false when it already was.
+    fn advance(&mut self, row_id: i64) -> bool {
+        if row_id <= self.max_row_id || !self.ahead.insert(row_id) {
+            return false;
+        }
+        while self.ahead.first() == Some(&(self.max_row_id + 1)) {
+            self.max_row_id += 1;
+            self.ahead.pop_first();
+        }
+        true
+    }
+
+    /// Every embedding row up to `row_id` is indexed.
+    fn caught_up(&mut self, row_id: i64) {
+        self.max_row_id = self.max_row_id.max(row_id);
+        let max_row_id = self.max_row_id;
+        self.ahead.retain(|id| *id > max_row_id);
+        while self.ahead.first() == Some(&(self.max_row_id + 1)) {
+            self.max_row_id += 1;
+            self.ahead.pop_first();
+        }
+    }
+
+    /// Add the embedding rows `(row_id, frame_id, vector)` that aren't indexed yet.
+    /// Vectors that don't fit the index (other dimension, zero vector) are skipped.
+    /// Returns the number of vectors added.
+    fn insert(&mut self, rows: Vec<(i64, i64, Vec<f32>)>) -> Result<usize> {
+        let mut fresh = Vec::with_capacity(rows.len());
+        for (row_id, frame_id, mut vector) in rows {
+            if !self.advance(row_id) {
+                continue;
+            }
+            if self.vectors.count == 0 && fresh.is_empty() && vector.len() != self.dim {
+                // first vector of an empty index decides the dimension
+                self.reset(vector.len())?;
+            }
+            if vector.len() == self.dim && normalize(&mut vector) {
+                fresh.push((frame_id, row_id, vector));
+            }
+        }
+        if fresh.is_empty() {
+            return Ok(0);
+        }
+
+        let mut records = Vec::with_capacity(fresh.len() * self.vectors.record_len);
+        let mut assigned = Vec::with_capacity(fresh.len());
+        for (frame_id, row_id, vector) in &fresh {
+            let list = if self.lists.len() == 1 {
+                0
+            } else {
+                nearest_centroid(&self.centroids, self.dim, vector)
+            };
+            records.extend_from_slice(&row_id.to_le_bytes());
+            records.extend_from_slice(&frame_id.to_le_bytes());
+            records.extend_from_slice(&(list as u32).to_le_bytes());
+            records.extend_from_slice(&0u32.to_le_bytes());
+            put_f32s(&mut records, vector);
+            assigned.push(list);
+        }
+        let first = self.vectors.count;
+        self.vectors.append(&records)?;
+        for (offset, list) in assigned.into_iter().enumerate() {
+            self.lists[list].push((first + offset) as u32);
+        }
+        self.len += fresh.len();
+        Ok(fresh.len())
+    }
+
+    /// Remove the vectors of `frame_ids` from the lists and wipe them from the vector file.
+    /// Returns the number of vectors removed.
+    fn remove(&mut self, frame_ids: &HashSet<i64>) -> Result<usize> {
+        let mut removed = 0;
+        for list in &mut self.lists {
+            let mut kept = Vec::with_capacity(list.len());
+            for &record in list.iter() {
+                let (_, frame_id, _) = record_header(self.vectors.record(record));
+                if frame_ids.contains(&frame_id) {
+                    self.vectors.blank(record)?;
+                    removed += 1;
+                } else {
+                    kept.push(record);
+                }
+            }
+            *list = kept;
+        }
+        if removed > 0 {
+            self.vectors.sync()?;
+            self.len -= removed;
+        }
+        Ok(removed)
+    }
+
+    /// Start over with an empty single list of `dim` vectors, in a new vector file.
+    fn reset(&mut self, dim: usize) -> Result<()> {
+        let generation = rand::random();
+        let vectors =
+            VectorFile::create(&vectors_path(&self.path, generation), generation, dim, &[])?;
+        let old = std::mem::replace(&mut self.vectors, vectors);
+        drop(old);
+        let _ = std::fs::remove_file(vectors_path(&self.path, self.generation));
+        self.generation = generation;
+        self.dim = dim;
+        self.centroids.clear();
+        self.lists = vec![Vec::new()];
+        self.len = 0;
+        Ok(())
+    }
+
+    /// Whether the index outgrew its centroids and should be retrained.
+    pub fn needs_retrain(&self) -> bool {
+        list_count(self.len) > 1 && self.len > 4 * self.trained_len.max(FLAT_LIMIT / 4)
+    }
+
+    /// Up to `k` frames closest to `query` with a cosine distance below `max_distance`,
+    /// closest first, as `(frame_id, distance)`.
+    pub fn search(&self, query: &[f32], k: usize, max_distance: f32) -> Vec<(i64, f32)> {
+        let mut query = query.to_vec();
+        if self.is_empty() || query.len() != self.dim || !normalize(&mut query) {
+            return Vec::new();
+        }
+
+        let probed: Vec<usize> = if self.lists.len() == 1 {
+            vec![0]
+        } else {
+            let mut by_similarity: Vec<(usize, f32)> = self
+                .centroids
+                .chunks_exact(self.dim)
+                .map(|centroid| dot(centroid, &query))
+                .enumerate()
+                .collect();
+            by_similarity.sort_by(|a, b| b.1.total_cmp(&a.1));
+            by_similarity
+                .into_iter()
+                .take(probe_count(self.lists.len()))
+                .map(|(list, _)| list)
+                .collect()
+        };
+
+        // frames can have several embeddings, keep the closest
+        let mut best: HashMap<i64, f32> = HashMap::new();
+        for list in probed {
+            for &record in &self.lists[list] {
+                let bytes = self.vectors.record(record);
+                let (_, frame_id, _) = record_header(bytes);
+                let distance = 1.0 - dot_le_bytes(&bytes[RECORD_HEADER_LEN..], &query);
+                if distance < max_distance {
+                    let entry = best.entry(frame_id).or_insert(distance);
+                    *entry = entry.min(distance);
+                }
+            }
+        }
+
+        let mut hits: Vec<(i64, f32)> = best.into_iter().collect();
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+        hits.truncate(k);
+        hits
+    }
+}
+
+/// Vector file of generation `generation` of the index saved at `path`.
+fn vectors_path(path: &Path, generation: u64) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{:016x}.vectors", generation));
+    path.with_file_name(name)
+}
+
+/// Delete the vector files of the index at `path` other than the one of `generation`,
+/// left behind by rebuilds that were interrupted, or all of them for `None`.
+fn remove_stale_vectors(path: &Path, generation: Option<u64>) {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let current = generation.map(|generation| vectors_path(path, generation));
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        if file_name.starts_with(&prefix)
+            && file_name.ends_with(".vectors")
+            && Some(entry.path()) != current
+        {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// `(row_id, frame_id, list)` of a record, the frame id is negative for removed records.
+fn record_header(record: &[u8]) -> (i64, i64, usize) {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&record[..8]);
+    let row_id = i64::from_le_bytes(word);
+    word.copy_from_slice(&record[8..16]);
+    let frame_id = i64::from_le_bytes(word);
+    let list = u32::from_le_bytes([record[16], record[17], record[18], record[19]]);
+    (row_id, frame_id, list as usize)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8]> {
+        if n > self.bytes.len() {
+            return Err(anyhow!("truncated embedding index"));
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn f32s(&mut self, n: usize) -> Result<Vec<f32>> {
+        let bytes = self.take(n.checked_mul(4).ok_or_else(|| anyhow!("invalid size"))?)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+}
+
+fn put_f32s(out: &mut Vec<u8>, values: &[f32]) {
+    for value in values {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// [`dot`] of little-endian f32 `bytes` and `b`.
+fn dot_le_bytes(bytes: &[u8], b: &[f32]) -> f32 {
+    bytes
+        .chunks_exact(4)
+        .zip(b)
+        .map(|(x, y)| f32::from_le_bytes([x[0], x[1], x[2], x[3]]) * y)
+        .sum()
+}
+
+/// Scale to unit length, false for zero vectors.
+fn normalize(vector: &mut [f32]) -> bool {
+    let norm = dot(vector, vector).sqrt();
+    if norm == 0.0 || !norm.is_finite() {
+        return false;
+    }
+    vector.iter_mut().for_each(|x| *x /= norm);
+    true
+}
+
+fn list_count(len: usize) -> usize {
+    if len < FLAT_LIMIT {
+        1
+    } else {
+        ((len as f64).sqrt() as usize).clamp(16, MAX_LISTS)
+    }
+}
+
+/// Lists scanned per query, a few percent of the index.
+fn probe_count(nlist: usize) -> usize {
+    (nlist / 16).max(8).min(nlist)
+}
+
+fn nearest_centroid(centroids: &[f32], dim: usize, vector: &[f32]) -> usize {
+    centroids
+        .chunks_exact(dim)
+        .map(|centroid| dot(centroid, vector))
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// Spherical k-means on normalized `samples`, at least `nlist` of them.
+fn train_centroids(samples: &[Vec<f32>], dim: usize, nlist: usize) -> Vec<f32> {
+    let mut rng = rand::thread_rng();
+    let mut centroids: Vec<f32> = sample(&mut rng, samples.len(), nlist)
+        .into_iter()
+        .flat_map(|i| samples[i].iter().copied())
+        .collect();
+    let mut assignments = vec![0; samples.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        for (assignment, vector) in assignments.iter_mut().zip(samples) {
+            *assignment = nearest_centroid(&centroids, dim, vector);
+        }
+
+        let mut sums = vec![0.0f32; nlist * dim];
+        let mut sizes = vec![0usize; nlist];
+        for (&list, vector) in assignments.iter().zip(samples) {
+            sizes[list] += 1;
+            for (sum, x) in sums[list * dim..(list + 1) * dim].iter_mut().zip(vector) {
+                *sum += x;
+            }
+        }
+        for (list, size) in sizes.iter().enumerate() {
+            let centroid = &mut sums[list * dim..(list + 1) * dim];
+            if *size == 0 || !normalize(centroid) {
+                // empty cluster, restart it from a random sample
+                let i = sample(&mut rng, samples.len(), 1).index(0);
+                centroid.copy_from_slice(&samples[i]);
+            }
+        }
+        centroids = sums;
+    }
+    centroids
+}
+
+/// Decode the rows `(row_id, frame_id, bytes)` of the table, skipping undecodable ones.
+fn decode_rows(rows: Vec<(i64, i64, Vec<u8>)>) -> Vec<(i64, i64, Vec<f32>)> {
+    rows.into_iter()
+        .filter_map(|(row_id, frame_id, bytes)| Some((row_id, frame_id, decode_embedding(&bytes)?)))
+        .collect()
+}
+
+/// Exact search over embedding rows `(row_id, frame_id, bytes)`: the frames with a cosine
+/// distance to `query` below `max_distance`, unsorted.
+fn scan(rows: &[(i64, i64, Vec<u8>)], query: &[f32], max_distance: f32) -> Vec<(i64, f32)> {
+    let mut query = query.to_vec();
+    if !normalize(&mut query) {
+        return Vec::new();
+    }
+    rows.iter()
+        .filter_map(|(_, frame_id, bytes)| {
+            let mut vector = decode_embedding(bytes)?;
+            if vector.len() != query.len() || !normalize(&mut vector) {
+                return None;
+            }
+            let distance = 1.0 - dot(&vector, &query);
+            (distance < max_distance).then_some((*frame_id, distance))
+        })
+        .collect()
+}
+
+/// Decode an embedding stored as little-endian f32 bytes or as a JSON array.
+pub fn decode_embedding(bytes: &[u8]) -> Option<Vec<f32>> {
+    if bytes.first() == Some(&b'[') {
+        return serde_json::from_slice(bytes).ok();
+    }
+    if bytes.is_empty() || bytes.len() % 4 != 0 {
+        return None;
+    }
+    Some(
+        bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    )
+}
+
+/// Persisted [`IvfIndex`] of the OCR embeddings of one model, kept in sync with the table.
+pub struct EmbeddingAnnIndex {
+    path: PathBuf,
+    /// `ocr_text_embeddings.model`, `None` for the Ollama vectors of `add --use-embedding`
+    model: Option<String>,
+    index: Arc<RwLock<Option<IvfIndex>>>,
+    sync_lock: Mutex<()>,
+    /// Lock file held while this process maintains the index files
+    owner: OnceLock<File>,
+}
+
+impl EmbeddingAnnIndex {
+    pub fn new(path: PathBuf, model: Option<String>) -> Self {
+        Self {
+            path,
+            model,
+            index: Arc::new(RwLock::new(None)),
+            sync_lock: Mutex::new(()),
+            owner: OnceLock::new(),
+        }
+    }
+
+    /// Load or build the index, then catch up with the table forever.
+    pub fn start(self: Arc<Self>, db: Arc<DatabaseManager>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SYNC_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.sync(&db).await {
+                    error!("embedding index sync failed: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Whether the index is loaded and can answer queries.
+    pub fn is_ready(&self) -> bool {
+        self.index
+            .read()
+            .map(|index| index.is_some())
+            .unwrap_or(false)
+    }
+
+    /// Number of vectors in the index, 0 while it isn't loaded.
+    pub fn len(&self) -> usize {
+        self.stats().0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bring the index up to date with `ocr_text_embeddings`, loading or rebuilding it
+    /// first when needed.
+    pub async fn sync(&self, db: &DatabaseManager) -> Result<()> {
+        let _guard = self.sync_lock.lock().await;
+        self.lock_files()?;
+        let model = self.model.as_deref();
+
+        if !self.is_ready() {
+            let path = self.path.clone();
+            match tokio::task::spawn_blocking(move || IvfIndex::open(&path)).await? {
+                Ok(index) => {
+                    info!("loaded embedding index with {} vectors", index.len());
+                    remove_stale_vectors(&self.path, Some(index.generation));
+                    self.set(index);
+                }
+                Err(e) => {
+                    warn!("rebuilding embedding index ({})", e);
+                    return self.rebuild(db).await;
+                }
+            }
+        }
+
+        let (count, max_id) = db.count_ocr_embeddings(model).await?;
+        let (len, indexed_max_id) = self.stats();
+        // rows were deleted (retention) or the table was reset
+        if max_id < indexed_max_id || (count as usize) < len * 4 / 5 {
+            return self.rebuild(db).await;
+        }
+
+        let mut after_id = indexed_max_id;
+        let mut added = 0;
+        loop {
+            let rows = db
+                .get_ocr_embeddings_after(model, after_id, SYNC_PAGE_SIZE)
+                .await?;
+            let Some(&(last_id, _, _)) = rows.last() else {
+                break;
+            };
+            after_id = last_id;
+            let rows = decode_rows(rows);
+            added += self
+                .update(move |index| {
+                    let added = index.insert(rows)?;
+                    index.caught_up(last_id);
+                    Ok(added)
+                })
+                .await?;
+        }
+        if added > 0 {
+            debug!("embedding index: caught up with {} vectors", added);
+        }
+
+        let needs_retrain = self
+            .index
+            .read()
+            .map(|index| index.as_ref().is_some_and(|i| i.needs_retrain()))
+            .unwrap_or(false);
+        if needs_retrain {
+            return self.rebuild(db).await;
+        }
+        Ok(())
+    }
+
+    /// Add the rows `(row_id, frame_id, vector)` just written to `ocr_text_embeddings`
+    /// with `model`. Rows of other models are left out, and nothing happens while the
+    /// index isn't loaded: the next sync picks the rows up from the table.
+    pub async fn insert(
+        &self,
+        model: Option<&str>,
+        rows: Vec<(i64, i64, Vec<f32>)>,
+    ) -> Result<usize> {
+        if model != self.model.as_deref() || rows.is_empty() || !self.is_ready() {
+            return Ok(0);
+        }
+        self.update(move |index| index.insert(rows)).await
+    }
+
+    /// Remove the vectors of `frame_ids` (e.g. forgotten frames) from the index and its
+    /// files. When the index isn't loaded yet its files are deleted, it's rebuilt from the
+    /// table on the next sync. Returns the number of vectors removed from the loaded index.
+    pub async fn remove(&self, frame_ids: &[i64]) -> Result<usize> {
+        if frame_ids.is_empty() {
+            return Ok(0);
+        }
+        // a rebuild in progress may have read the rows before they were deleted
+        let _guard = self.sync_lock.lock().await;
+        self.lock_files()?;
+        if !self.is_ready() {
+            let _ = std::fs::remove_file(&self.path);
+            remove_stale_vectors(&self.path, None);
+            return Ok(0);
+        }
+        let frame_ids: HashSet<i64> = frame_ids.iter().copied().collect();
+        self.update(move |index| index.remove(&frame_ids)).await
+    }
+
+    /// Frames closest to `embedding` with a cosine distance below `threshold`, `None`
+    /// while the index isn't loaded yet. `score` is the cosine similarity.
+    pub async fn search(
+        &self,
+        db: &DatabaseManager,
+        embedding: &[f32],
+        limit: u32,
+        threshold: f32,
+    ) -> Result<Option<Vec<OCRResult>>> {
+        let index = self.index.clone();
+        let query = embedding.to_vec();
+        let k = limit as usize * OVERFETCH;
+        let searched = tokio::task::spawn_blocking(move || {
+            let guard = index.read().map_err(|_| anyhow!("index lock poisoned"))?;
+            Ok::<_, anyhow::Error>(
+                guard
+                    .as_ref()
+                    .map(|index| (index.search(&query, k, threshold), index.max_row_id())),
+            )
+        })
+        .await??;
+        let Some((hits, indexed_max_id)) = searched else {
+            return Ok(None);
+        };
+
+        // rows another process inserted since the last catch-up aren't in the index yet
+        let mut best: HashMap<i64, f32> = HashMap::new();
+        let mut after_id = indexed_max_id;
+        loop {
+            let rows = db
+                .get_ocr_embeddings_after(self.model.as_deref(), after_id, SYNC_PAGE_SIZE)
+                .await?;
+            let Some(&(last_id, _, _)) = rows.last() else {
+                break;
+            };
+            after_id = last_id;
+            for (frame_id, distance) in scan(&rows, embedding, threshold) {
+                let entry = best.entry(frame_id).or_insert(distance);
+                *entry = entry.min(distance);
+            }
+        }
+        let hits = if best.is_empty() {
+            hits
+        } else {
+            for (frame_id, distance) in hits {
+                let entry = best.entry(frame_id).or_insert(distance);
+                *entry = entry.min(distance);
+            }
+            let mut merged: Vec<(i64, f32)> = best.into_iter().collect();
+            merged.sort_by(|a, b| a.1.total_cmp(&b.1));
+            merged.truncate(k);
+            merged
+        };
+
+        let frame_ids: Vec<i64> = hits.iter().map(|(id, _)| *id).collect();
+        let mut by_frame: HashMap<i64, OCRResult> = db
+            .get_ocr_results_by_frame_ids(&frame_ids)
+            .await?
+            .into_iter()
+            .map(|result| (result.frame_id, result))
+            .collect();
+
+        // frames deleted since they were indexed are skipped
+        Ok(Some(
+            hits.into_iter()
+                .filter_map(|(frame_id, distance)| {
+                    by_frame.remove(&frame_id).map(|mut result| {
+                        result.score = Some(1.0 - distance as f64);
+                        result
+                    })
+                })
+                .take(limit as usize)
+                .collect(),
+        ))
+    }
+
+    /// Build a new index from the table: a first pass over its pages samples the
+    /// training vectors, a second one fills the lists.
+    async fn rebuild(&self, db: &DatabaseManager) -> Result<()> {
+        let model = self.model.as_deref();
+        let (count, _) = db.count_ocr_embeddings(model).await?;
+        let capacity = (count as usize).min(MAX_LISTS * TRAIN_SAMPLES_PER_LIST);
+        let mut rng = StdRng::from_entropy();
+        let mut samples: Vec<Vec<f32>> = Vec::with_capacity(capacity);
+        let mut dim = None;
+        let mut seen = 0;
+        let mut after_id = 0;
+        loop {
+            let page = db
+                .get_ocr_embeddings_after(model, after_id, SYNC_PAGE_SIZE)
+                .await?;
+            let Some(&(last_id, _, _)) = page.last() else {
+                break;
+            };
+            after_id = last_id;
+            for (_, _, mut vector) in decode_rows(page) {
+                if *dim.get_or_insert(vector.len()) != vector.len() || !normalize(&mut vector) {
+                    continue;
+                }
+                // reservoir sampling, every vector is equally likely to be picked
+                seen += 1;
+                if samples.len() < capacity {
+                    samples.push(vector);
+                } else {
+                    let i = rng.gen_range(0..seen);
+                    if i < capacity {
+                        samples[i] = vector;
+                    }
+                }
+            }
+        }
+
+        let nlist = list_count(seen).min(samples.len().max(1));
+        let path = self.path.clone();
+        let generation = rng.gen();
+        let mut index = tokio::task::spawn_blocking(move || {
+            let dim = dim.unwrap_or(0);
+            let centroids = if nlist > 1 {
+                train_centroids(&samples, dim, nlist)
+            } else {
+                Vec::new()
+            };
+            IvfIndex::create(&path, generation, dim, centroids)
+        })
+        .await??;
+        index.trained_len = seen;
+
+        let mut after_id = 0;
+        loop {
+            let page = db
+                .get_ocr_embeddings_after(model, after_id, SYNC_PAGE_SIZE)
+                .await?;
+            let Some(&(last_id, _, _)) = page.last() else {
+                break;
+            };
+            after_id = last_id;
+            let rows = decode_rows(page);
+            index = tokio::task::spawn_blocking(move || {
+                index.insert(rows)?;
+                index.caught_up(last_id);
+                Ok::<_, anyhow::Error>(index)
+            })
+            .await??;
+        }
+        let index = tokio::task::spawn_blocking(move || {
+            index.save()?;
+            Ok::<_, anyhow::Error>(index)
+        })
+        .await??;
+
+        info!(
+            "built embedding index with {} vectors in {} lists",
+            index.len(),
+            index.lists.len()
+        );
+        let generation = index.generation;
+        self.set(index);
+        remove_stale_vectors(&self.path, Some(generation));
+        Ok(())
+    }
+
+    /// Apply `update` to the loaded index and save it. On failure the index is unloaded,
+    /// the next sync reloads or rebuilds it.
+    async fn update<T: Send + 'static>(
+        &self,
+        update: impl FnOnce(&mut IvfIndex) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let index = self.index.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut guard = index.write().map_err(|_| anyhow!("index lock poisoned"))?;
+            let loaded = guard
+                .as_mut()
+                .ok_or_else(|| anyhow!("embedding index isn't loaded"))?;
+            let result = update(loaded).and_then(|value| {
+                loaded.save()?;
+                Ok(value)
+            });
+            if result.is_err() {
+                *guard = None;
+            }
+            result
+        })
+        .await?
+    }
+
+    /// Take the lock file of the index, so that a second process (e.g. `screenpipe add`
+    /// while the server runs) doesn't write the same files.
+    fn lock_files(&self) -> Result<()> {
+        if self.owner.get().is_some() {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path.with_file_name(name))?;
+        file.try_lock()
+            .map_err(|_| anyhow!("embedding index is maintained by another process"))?;
+        let _ = self.owner.set(file);
+        Ok(())
+    }
+
+    fn set(&self, index: IvfIndex) {
+        if let Ok(mut guard) = self.index.write() {
+            *guard = Some(index);
+        }
+    }
+
+    fn stats(&self) -> (usize, i64) {
+        self.index
+            .read()
+            .ok()
+            .and_then(|guard| guard.as_ref().map(|i| (i.len(), i.max_row_id())))
+            .unwrap_or((0, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_index_path(name: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("screenpipe-ann-{}-{}", name, nanos));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(OCR_EMBEDDING_INDEX_FILE)
+    }
+
+    fn random_rows(count: usize, dim: usize) -> Vec<(i64, i64, Vec<f32>)> {
+        let mut rng = rand::thread_rng();
+        (1..=count as i64)
+            .map(|i| {
+                (
+                    i,
+                    i * 10,
+                    (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ivf_index_finds_inserted_vectors() {
+        let path = temp_index_path("search");
+        let rows = random_rows(FLAT_LIMIT * 2, 16);
+        let samples: Vec<Vec<f32>> = rows
+            .iter()
+            .map(|(_, _, vector)| {
+                let mut vector = vector.clone();
+                normalize(&mut vector);
+                vector
+            })
+            .collect();
+        let nlist = list_count(rows.len());
+        let centroids = train_centroids(&samples, 16, nlist);
+        let mut index = IvfIndex::create(&path, 1, 16, centroids).unwrap();
+        assert_eq!(index.insert(rows.clone()).unwrap(), rows.len());
+        assert!(index.lists.len() > 1);
+        assert_eq!(index.len(), rows.len());
+        assert_eq!(index.max_row_id(), rows.len() as i64);
+
+        for (_, frame_id, vector) in rows.iter().step_by(500) {
+            let hits = index.search(vector, 1, 0.5);
+            assert_eq!(hits[0].0, *frame_id);
+            assert!(hits[0].1 < 1e-4);
+        }
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_ivf_index_insert_and_reopen() {
+        let path = temp_index_path("reopen");
+        let mut index = IvfIndex::create(&path, 1, 0, Vec::new()).unwrap();
+        let added = index
+            .insert(vec![
+                (1, 10, vec![1.0, 0.0, 0.0]),
+                (2, 20, vec![0.0, 1.0, 0.0]),
+                (3, 30, vec![1.0, 0.0]),
+                (4, 40, vec![0.0, 0.0, 0.0]),
+                (6, 60, vec![0.0, 0.0, 1.0]),
+            ])
+            .unwrap();
+        assert_eq!(added, 3);
+        assert_eq!(index.max_row_id(), 4);
+        // already indexed rows are skipped
+        assert_eq!(index.insert(vec![(2, 20, vec![0.0, 1.0, 0.0])]).unwrap(), 0);
+        index.save().unwrap();
+        let generation = index.generation;
+
+        // not saved, dropped on reopen
+        index.insert(vec![(5, 50, vec![0.5, 0.5, 0.0])]).unwrap();
+        assert_eq!(index.max_row_id(), 6);
+        drop(index);
+
+        let mut loaded = IvfIndex::open(&path).unwrap();
+        assert_eq!(loaded.generation, generation);
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.max_row_id(), 4);
+        assert_eq!(loaded.search(&[0.9, 0.1, 0.0], 2, 1.0)[0].0, 10);
+        assert_eq!(loaded.search(&[0.0, 0.1, 0.9], 1, 1.0)[0].0, 60);
+        assert_eq!(
+            loaded.insert(vec![(5, 50, vec![0.5, 0.5, 0.0])]).unwrap(),
+            1
+        );
+        assert_eq!(loaded.max_row_id(), 6);
+        loaded.save().unwrap();
+        drop(loaded);
+        assert_eq!(IvfIndex::open(&path).unwrap().len(), 4);
+
+        let bytes = std::fs::read(&path).unwrap();
+        let mut corrupted = bytes.clone();
+        corrupted[MAGIC.len() + 20] ^= 0xff;
+        std::fs::write(&path, &corrupted).unwrap();
+        assert!(IvfIndex::open(&path).is_err());
+        std::fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        assert!(IvfIndex::open(&path).is_err());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_ivf_index_remove() {
+        let path = temp_index_path("remove");
+        let mut index = IvfIndex::create(&path, 1, 3, Vec::new()).unwrap();
+        index
+            .insert(vec![
+                (1, 10, vec![1.0, 0.0, 0.0]),
+                (2, 20, vec![0.6, 0.8, 0.0]),
+                (3, 20, vec![0.0, 0.8, 0.6]),
+            ])
+            .unwrap();
+        let mut removed = vec![0.6, 0.8, 0.0];
+        normalize(&mut removed);
+        let mut removed_bytes = Vec::new();
+        put_f32s(&mut removed_bytes, &removed);
+        let vectors = vectors_path(&path, 1);
+        let contains = |bytes: &[u8]| bytes.windows(12).any(|w| w == removed_bytes.as_slice());
+        assert!(contains(&std::fs::read(&vectors).unwrap()));
+
+        assert_eq!(index.remove(&HashSet::from([20])).unwrap(), 2);
+        assert_eq!(index.len(), 1);
+        let hits = index.search(&removed, 10, 2.0);
+        assert_eq!(hits.iter().map(|h| h.0).collect::<Vec<_>>(), [10]);
+        index.save().unwrap();
+        drop(index);
+
+        assert!(!contains(&std::fs::read(&vectors).unwrap()));
+        let loaded = IvfIndex::open(&path).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.max_row_id(), 3);
+        assert_eq!(loaded.search(&removed, 10, 2.0)[0].0, 10);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_decode_embedding() {
+        let floats = [0.5f32, -1.0];
+        let bytes: Vec<u8> = floats.iter().flat_map(|f| f.to_le_bytes()).collect();
+        assert_eq!(decode_embedding(&bytes), Some(floats.to_vec()));
+        assert_eq!(decode_embedding(b"[0.5,-1.0]"), Some(floats.to_vec()));
+        assert_eq!(decode_embedding(&[1, 2, 3]), None);
+    }
+}
@@ -4,16 +4,19 @@ use libsqlite3_sys::sqlite3_auto_extension;
 use sqlite_vec::sqlite3_vec_init;
 use sqlx::migrate::MigrateDatabase;
 use sqlx::pool::PoolConnection;
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteConnection, SqlitePool, SqlitePoolOptions, SqliteRow,
+};
 use sqlx::Column;
+use sqlx::Connection;
 use sqlx::Error as SqlxError;
 use sqlx::Row;
 use sqlx::Sqlite;
 use sqlx::TypeInfo;
 use sqlx::ValueRef;
 use std::sync::Arc;
-use std::time::Duration;
-use tracing::{debug, error, warn};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
 
 use std::collections::BTreeMap;
 
@@ -21,12 +24,16 @@ use zerocopy::AsBytes;
 
 use futures::future::try_join_all;
 
+use crate::ann::{decode_embedding, EmbeddingAnnIndex};
 use crate::{
-    text_similarity::is_similar_transcription, AudioChunksResponse, AudioDevice, AudioEntry,
-    AudioResult, AudioResultRaw, ContentType, DeviceType, FrameData, FrameRow, FrameWindowData,
-    InsertUiEvent, OCREntry, OCRResult, OCRResultRaw, OcrEngine, OcrTextBlock, Order, SearchMatch,
-    SearchResult, Speaker, TagContentType, TextBounds, TextPosition, TimeSeriesChunk, UiContent,
-    UiEventRecord, UiEventRow, VideoMetadata,
+    text_similarity::is_similar_transcription, ActivitySession, AudioChunksResponse, AudioDevice,
+    AudioEntry, AudioResult, AudioResultRaw, ContentType, DeviceType, FocusObservation,
+    ForgetFilter, ForgetResult, FrameData, FrameRow, FrameWindowData, InsertActivitySession,
+    InsertUiEvent, MediaChunkRow, Meeting, MeetingFrame, MeetingParticipant, OCREntry, OCRResult,
+    OCRResultRaw, OcrEngine, OcrTextBlock, Order, PendingAudioPurge, PiiRedaction, PipeRun,
+    PrunedRows, SearchMatch, SearchOrder, SearchResult, Speaker, TagContentType, TextBounds,
+    TextMatch, TextPosition, TimeSeriesChunk, TranscriptRow, UiContent, UiContentRaw,
+    UiEventRecord, UiEventRow, VideoMetadata, Webhook, WebhookDeadLetter, WebhookRow,
 };
 
 /// Time window (in seconds) to check for similar transcriptions across devices.
@@ -46,6 +53,8 @@ const DEDUP_SIMILARITY_THRESHOLD: f64 = 0.85;
 pub struct ImmediateTx {
     conn: Option<PoolConnection<Sqlite>>,
     committed: bool,
+    /// When the write lock was requested, for the write latency metric
+    started: Instant,
 }
 
 impl ImmediateTx {
@@ -60,6 +69,8 @@ impl ImmediateTx {
             sqlx::query("COMMIT").execute(&mut **conn).await?;
         }
         self.committed = true;
+        metrics::histogram!("screenpipe_db_write_duration_seconds")
+            .record(self.started.elapsed().as_secs_f64());
         Ok(())
     }
 
@@ -93,10 +104,21 @@ impl Drop for ImmediateTx {
 
 pub struct DatabaseManager {
     pub pool: SqlitePool,
+    /// Indexes handed the OCR embeddings this manager inserts
+    pub(crate) ocr_embedding_indexes: std::sync::RwLock<Vec<Arc<EmbeddingAnnIndex>>>,
 }
 
 impl DatabaseManager {
     pub async fn new(database_path: &str) -> Result<Self, sqlx::Error> {
+        Self::new_with_key(database_path, None).await
+    }
+
+    /// Open the database encrypted with SQLCipher when `key` is set.
+    ///
+    /// `key` is the value of `PRAGMA key`, e.g. `"x'<64 hex digits>'"` for a raw key.
+    /// A plaintext database found at `database_path` is encrypted first. Fails before
+    /// touching the file unless screenpipe-db was built with the `sqlcipher` feature.
+    pub async fn new_with_key(database_path: &str, key: Option<&str>) -> Result<Self, sqlx::Error> {
         debug!(
             "Initializing DatabaseManager with database path: {}",
             database_path
@@ -111,13 +133,30 @@ impl DatabaseManager {
             ));
         }
 
+        if let Some(key) = key {
+            // plain SQLite ignores `PRAGMA key` and would silently write plaintext
+            if !Self::has_sqlcipher().await? {
+                return Err(sqlx::Error::Configuration(
+                    "database encryption needs screenpipe built with the sqlcipher feature".into(),
+                ));
+            }
+            if Self::is_plaintext_database(database_path) {
+                info!("encrypting existing database {}", database_path);
+                Self::encrypt_database(database_path, key).await?;
+            }
+        }
+
         // Create the database if it doesn't exist
         if !sqlx::Sqlite::database_exists(&connection_string).await? {
             sqlx::Sqlite::create_database(&connection_string).await?;
         }
 
-        let connect_options: SqliteConnectOptions = connection_string
-            .parse::<SqliteConnectOptions>()?
+        let mut connect_options = connection_string.parse::<SqliteConnectOptions>()?;
+        if let Some(key) = key {
+            // sqlx always sends `key` first, before anything reads the file
+            connect_options = connect_options.pragma("key", key.to_string());
+        }
+        let connect_options = connect_options
             // busy_timeout is per-connection; setting it here ensures ALL pooled
             // connections wait before returning SQLITE_BUSY ("database is locked").
             .busy_timeout(Duration::from_secs(10))
@@ -134,7 +173,10 @@ impl DatabaseManager {
             .connect_with(connect_options)
             .await?;
 
-        let db_manager = DatabaseManager { pool };
+        let db_manager = DatabaseManager {
+            pool,
+            ocr_embedding_indexes: Default::default(),
+        };
 
         // Run migrations after establishing the connection
         Self::run_migrations(&db_manager.pool).await?;
@@ -142,6 +184,70 @@ impl DatabaseManager {
         Ok(db_manager)
     }
 
+    /// Whether the linked SQLite is SQLCipher, checked on an in-memory database.
+    async fn has_sqlcipher() -> Result<bool, sqlx::Error> {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await?;
+        let cipher_version: Option<String> = sqlx::query_scalar("PRAGMA cipher_version")
+            .fetch_optional(&mut conn)
+            .await?;
+        conn.close().await?;
+        Ok(cipher_version.is_some())
+    }
+
+    /// Whether `database_path` is an existing, unencrypted SQLite file.
+    fn is_plaintext_database(database_path: &str) -> bool {
+        let mut header = [0u8; 16];
+        std::fs::File::open(database_path)
+            .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut header))
+            .map(|_| &header == b"SQLite format 3\0")
+            .unwrap_or(false)
+    }
+
+    /// Encrypt a plaintext database in place with `sqlcipher_export`.
+    async fn encrypt_database(database_path: &str, key: &str) -> Result<(), sqlx::Error> {
+        let encrypted_path = format!("{}.encrypting", database_path);
+        let _ = std::fs::remove_file(&encrypted_path);
+        let result = Self::export_encrypted(database_path, &encrypted_path, key).await;
+        if result.is_err() {
+            let _ = std::fs::remove_file(&encrypted_path);
+        }
+        result
+    }
+
+    /// Copy `database_path` to a new database at `encrypted_path` encrypted with `key`,
+    /// then move the copy in place of the original.
+    async fn export_encrypted(
+        database_path: &str,
+        encrypted_path: &str,
+        key: &str,
+    ) -> Result<(), sqlx::Error> {
+        let options = format!("sqlite:{}", database_path).parse::<SqliteConnectOptions>()?;
+        let mut conn = SqliteConnection::connect_with(&options).await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&mut conn)
+            .await?;
+        sqlx::query(&format!(
+            "ATTACH DATABASE '{}' AS encrypted KEY {}",
+            encrypted_path.replace('\'', "''"),
+            key
+        ))
+        .execute(&mut conn)
+        .await?;
+        sqlx::query("SELECT sqlcipher_export('encrypted')")
+            .execute(&mut conn)
+            .await?;
+        sqlx::query("DETACH DATABASE encrypted")
+            .execute(&mut conn)
+            .await?;
+        conn.close().await?;
+
+        for suffix in ["-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", database_path, suffix));
+        }
+        std::fs::rename(encrypted_path, database_path)?;
+        Ok(())
+    }
+
     async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         let mut migrator = sqlx::migrate!("./src/migrations");
         migrator.set_ignore_missing(true);
@@ -162,10 +268,17 @@ impl DatabaseManager {
         &self,
     ) -> Result<ImmediateTx, sqlx::Error> {
         let max_retries = 5;
+        let started = Instant::now();
         for attempt in 1..=max_retries {
             let mut conn = self.pool.acquire().await?;
             match sqlx::query("BEGIN IMMEDIATE").execute(&mut *conn).await {
-                Ok(_) => return Ok(ImmediateTx { conn: Some(conn), committed: false }),
+                Ok(_) => {
+                    return Ok(ImmediateTx {
+                        conn: Some(conn),
+                        committed: false,
+                        started,
+                    })
+                }
                 Err(e) if attempt < max_retries && Self::is_busy_error(&e) => {
                     warn!(
                         "BEGIN IMMEDIATE busy (attempt {}/{}), retrying...",
@@ -192,10 +305,19 @@ impl DatabaseManager {
     }
 
     pub async fn insert_audio_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error> {
+        self.insert_audio_chunk_at(file_path, Utc::now()).await
+    }
+
+    /// Insert an audio chunk recorded at `timestamp`, e.g. an imported recording.
+    pub async fn insert_audio_chunk_at(
+        &self,
+        file_path: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
         let mut tx = self.begin_immediate_with_retry().await?;
         let id = sqlx::query("INSERT INTO audio_chunks (file_path, timestamp) VALUES (?1, ?2)")
             .bind(file_path)
-            .bind(Utc::now())
+            .bind(timestamp)
             .execute(&mut **tx.conn())
             .await?
             .last_insert_rowid();
@@ -265,6 +387,41 @@ impl DatabaseManager {
             return Ok(0);
         }
 
+        self.insert_audio_transcription_at(
+            audio_chunk_id,
+            transcription,
+            offset_index,
+            transcription_engine,
+            device,
+            speaker_id,
+            start_time,
+            end_time,
+            Utc::now(),
+        )
+        .await
+    }
+
+    /// Insert a transcription spoken at `timestamp`, e.g. from an imported recording.
+    ///
+    /// Unlike [`Self::insert_audio_transcription`] there is no cross-device dedup, which
+    /// only applies to live capture of the same sound by several devices.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn insert_audio_transcription_at(
+        &self,
+        audio_chunk_id: i64,
+        transcription: &str,
+        offset_index: i64,
+        transcription_engine: &str,
+        device: &AudioDevice,
+        speaker_id: Option<i64>,
+        start_time: Option<f64>,
+        end_time: Option<f64>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        if transcription.trim().is_empty() {
+            return Ok(0);
+        }
+
         let text_length = transcription.len() as i64;
         let mut tx = self.begin_immediate_with_retry().await?;
 
@@ -276,7 +433,7 @@ impl DatabaseManager {
         .bind(audio_chunk_id)
         .bind(transcription)
         .bind(offset_index)
-        .bind(Utc::now())
+        .bind(timestamp)
         .bind(transcription_engine)
         .bind(&device.name)
         .bind(device.device_type == DeviceType::Input)
@@ -650,6 +807,20 @@ impl DatabaseManager {
             .execute(&mut **tx.conn())
             .await?;
 
+            for redaction in &window.pii_redactions {
+                sqlx::query(
+                    "INSERT INTO pii_redactions (frame_id, pii_type, x, y, width, height) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )
+                .bind(frame_id)
+                .bind(&redaction.pii_type)
+                .bind(redaction.x)
+                .bind(redaction.y)
+                .bind(redaction.width)
+                .bind(redaction.height)
+                .execute(&mut **tx.conn())
+                .await?;
+            }
+
             results.push((frame_id, idx));
         }
 
@@ -681,6 +852,7 @@ impl DatabaseManager {
         browser_url: Option<&str>,
         focused: Option<bool>,
         speaker_name: Option<&str>,
+        order: SearchOrder,
     ) -> Result<Vec<SearchResult>, sqlx::Error> {
         let mut results = Vec::new();
 
@@ -708,6 +880,7 @@ impl DatabaseManager {
                                 frame_name,
                                 browser_url,
                                 focused,
+                                order,
                             ),
                             self.search_audio(
                                 query,
@@ -719,6 +892,7 @@ impl DatabaseManager {
                                 max_length,
                                 speaker_ids,
                                 speaker_name,
+                                order,
                             ),
                             self.search_ui_monitoring(
                                 query,
@@ -728,6 +902,7 @@ impl DatabaseManager {
                                 end_time,
                                 limit,
                                 offset,
+                                order,
                             )
                         )?;
                         (ocr, Some(audio), ui)
@@ -747,6 +922,7 @@ impl DatabaseManager {
                                 frame_name,
                                 browser_url,
                                 focused,
+                                order,
                             ),
                             self.search_ui_monitoring(
                                 query,
@@ -756,6 +932,7 @@ impl DatabaseManager {
                                 end_time,
                                 limit,
                                 offset,
+                                order,
                             )
                         )?;
                         (ocr, None, ui)
@@ -782,6 +959,7 @@ impl DatabaseManager {
                         frame_name,
                         browser_url,
                         focused,
+                        order,
                     )
                     .await?;
                 results.extend(ocr_results.into_iter().map(SearchResult::OCR));
@@ -799,6 +977,7 @@ impl DatabaseManager {
                             max_length,
                             speaker_ids,
                             speaker_name,
+                            order,
                         )
                         .await?;
                     results.extend(audio_results.into_iter().map(SearchResult::Audio));
@@ -814,6 +993,7 @@ impl DatabaseManager {
                         end_time,
                         limit,
                         offset,
+                        order,
                     )
                     .await?;
                 results.extend(ui_results.into_iter().map(SearchResult::UI));
@@ -830,6 +1010,7 @@ impl DatabaseManager {
                         max_length,
                         speaker_ids,
                         speaker_name,
+                        order,
                     )
                     .await?;
                 let ui_results = self
@@ -841,6 +1022,7 @@ impl DatabaseManager {
                         end_time,
                         limit / 2,
                         offset,
+                        order,
                     )
                     .await?;
 
@@ -862,6 +1044,7 @@ impl DatabaseManager {
                         frame_name,
                         browser_url,
                         focused,
+                        order,
                     )
                     .await?;
                 let ui_results = self
@@ -873,6 +1056,7 @@ impl DatabaseManager {
                         end_time,
                         limit / 2,
                         offset,
+                        order,
                     )
                     .await?;
 
@@ -891,6 +1075,7 @@ impl DatabaseManager {
                         max_length,
                         speaker_ids,
                         speaker_name,
+                        order,
                     )
                     .await?;
                 let ocr_results = self
@@ -907,6 +1092,7 @@ impl DatabaseManager {
                         frame_name,
                         browser_url,
                         focused,
+                        order,
                     )
                     .await?;
 
@@ -929,6 +1115,7 @@ impl DatabaseManager {
                         frame_name,
                         browser_url,
                         focused,
+                        order,
                     )
                     .await?;
                 let ui_results = self
@@ -940,6 +1127,7 @@ impl DatabaseManager {
                         end_time,
                         limit / 2,
                         offset,
+                        order,
                     )
                     .await?;
 
@@ -977,6 +1165,7 @@ impl DatabaseManager {
                         frame_name,
                         browser_url,
                         focused,
+                        order,
                     )
                     .await?;
                 let ui_results = self
@@ -988,6 +1177,7 @@ impl DatabaseManager {
                         end_time,
                         limit / 3,
                         offset,
+                        order,
                     )
                     .await?;
                 let input_results = self
@@ -1019,6 +1209,7 @@ impl DatabaseManager {
                         max_length,
                         speaker_ids,
                         speaker_name,
+                        order,
                     )
                     .await?;
                 let input_results = self
@@ -1052,6 +1243,7 @@ impl DatabaseManager {
                         frame_name,
                         browser_url,
                         focused,
+                        order,
                     )
                     .await?;
                 let ui_results = self
@@ -1063,6 +1255,7 @@ impl DatabaseManager {
                         end_time,
                         limit / 4,
                         offset,
+                        order,
                     )
                     .await?;
                 let audio_results = self
@@ -1076,6 +1269,7 @@ impl DatabaseManager {
                         max_length,
                         speaker_ids,
                         speaker_name,
+                        order,
                     )
                     .await?;
                 let input_results = self
@@ -1098,22 +1292,19 @@ impl DatabaseManager {
             }
         }
 
-        // Sort results by timestamp in descending order
-        results.sort_by(|a, b| {
-            let timestamp_a = match a {
-                SearchResult::OCR(ocr) => ocr.timestamp,
-                SearchResult::Audio(audio) => audio.timestamp,
-                SearchResult::UI(ui) => ui.timestamp,
-                SearchResult::Input(input) => input.timestamp,
-            };
-            let timestamp_b = match b {
-                SearchResult::OCR(ocr) => ocr.timestamp,
-                SearchResult::Audio(audio) => audio.timestamp,
-                SearchResult::UI(ui) => ui.timestamp,
-                SearchResult::Input(input) => input.timestamp,
-            };
-            timestamp_b.cmp(&timestamp_a)
-        });
+        if order == SearchOrder::Relevance && !query.trim().is_empty() {
+            // Most relevant first, unscored results (e.g. input events) last
+            results.sort_by(|a, b| {
+                let score_a = search_result_score(a).unwrap_or(f64::NEG_INFINITY);
+                let score_b = search_result_score(b).unwrap_or(f64::NEG_INFINITY);
+                score_b
+                    .total_cmp(&score_a)
+                    .then_with(|| search_result_timestamp(b).cmp(&search_result_timestamp(a)))
+            });
+        } else {
+            // Sort results by timestamp in descending order
+            results.sort_by_key(|result| std::cmp::Reverse(search_result_timestamp(result)));
+        }
 
         // Apply offset and limit after sorting
         results = results
@@ -1140,6 +1331,7 @@ impl DatabaseManager {
         frame_name: Option<&str>,
         browser_url: Option<&str>,
         focused: Option<bool>,
+        order: SearchOrder,
     ) -> Result<Vec<OCRResult>, sqlx::Error> {
         let mut frame_fts_parts = Vec::new();
 
@@ -1185,7 +1377,9 @@ impl DatabaseManager {
             video_chunks.device_name,
             GROUP_CONCAT(tags.name, ',') as tags,
             frames.browser_url,
-            frames.focused
+            frames.focused,
+            EXISTS(SELECT 1 FROM pii_redactions WHERE pii_redactions.frame_id = frames.id) AS redacted,
+            {rank_columns}
         FROM frames
         JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
         JOIN ocr_text ON frames.id = ocr_text.frame_id
@@ -1204,6 +1398,7 @@ impl DatabaseManager {
         ORDER BY {order_clause}
         LIMIT ?7 OFFSET ?8
         "#,
+            rank_columns = fts_rank_columns("ocr_text_fts", !query.trim().is_empty()),
             frame_fts_join = if frame_query.trim().is_empty() {
                 ""
             } else {
@@ -1225,10 +1420,10 @@ impl DatabaseManager {
                 "AND ocr_text_fts MATCH ?6"
             },
             // Use FTS5 rank (BM25 relevance) when searching, timestamp when browsing
-            order_clause = if query.trim().is_empty() {
-                "frames.timestamp DESC"
-            } else {
+            order_clause = if order == SearchOrder::Relevance && !query.trim().is_empty() {
                 "ocr_text_fts.rank, frames.timestamp DESC"
+            } else {
+                "frames.timestamp DESC"
             }
         );
 
@@ -1254,28 +1449,7 @@ impl DatabaseManager {
             .fetch_all(&self.pool)
             .await?;
 
-        Ok(raw_results
-            .into_iter()
-            .map(|raw| OCRResult {
-                frame_id: raw.frame_id,
-                ocr_text: raw.ocr_text,
-                text_json: raw.text_json,
-                timestamp: raw.timestamp,
-                frame_name: raw.frame_name,
-                file_path: raw.file_path,
-                offset_index: raw.offset_index,
-                app_name: raw.app_name,
-                ocr_engine: raw.ocr_engine,
-                window_name: raw.window_name,
-                device_name: raw.device_name,
-                tags: raw
-                    .tags
-                    .map(|t| t.split(',').map(String::from).collect())
-                    .unwrap_or_default(),
-                browser_url: raw.browser_url,
-                focused: raw.focused,
-            })
-            .collect())
+        Ok(raw_results.into_iter().map(ocr_result_from_raw).collect())
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -1290,9 +1464,10 @@ impl DatabaseManager {
         max_length: Option<usize>,
         speaker_ids: Option<Vec<i64>>,
         speaker_name: Option<&str>,
+        order: SearchOrder,
     ) -> Result<Vec<AudioResult>, sqlx::Error> {
         // base query for audio search
-        let mut base_sql = String::from(
+        let mut base_sql = format!(
             "SELECT
                 audio_transcriptions.audio_chunk_id,
                 audio_transcriptions.transcription,
@@ -1305,12 +1480,14 @@ impl DatabaseManager {
                 audio_transcriptions.is_input_device,
                 audio_transcriptions.speaker_id,
                 audio_transcriptions.start_time,
-                audio_transcriptions.end_time
+                audio_transcriptions.end_time,
+                {}
              FROM audio_transcriptions
              JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
              LEFT JOIN speakers ON audio_transcriptions.speaker_id = speakers.id
              LEFT JOIN audio_tags ON audio_chunks.id = audio_tags.audio_chunk_id
              LEFT JOIN tags ON audio_tags.tag_id = tags.id",
+            fts_rank_columns("audio_transcriptions_fts", !query.is_empty())
         );
         // if query is provided, join the corresponding fts table
         if !query.is_empty() {
@@ -1348,10 +1525,16 @@ impl DatabaseManager {
             format!("WHERE {}", conditions.join(" AND "))
         };
 
+        let order_clause = if order == SearchOrder::Relevance && !query.is_empty() {
+            "audio_transcriptions_fts.rank, audio_transcriptions.timestamp DESC"
+        } else {
+            "audio_transcriptions.timestamp DESC"
+        };
+
         // complete sql with group, order, limit and offset
         let sql = format!(
-            "{} {} GROUP BY audio_transcriptions.audio_chunk_id, audio_transcriptions.offset_index ORDER BY audio_transcriptions.timestamp DESC LIMIT ? OFFSET ?",
-            base_sql, where_clause
+            "{} {} GROUP BY audio_transcriptions.audio_chunk_id, audio_transcriptions.offset_index ORDER BY {} LIMIT ? OFFSET ?",
+            base_sql, where_clause, order_clause
         );
 
         // prepare binding for speaker_ids (if any)
@@ -1393,39 +1576,44 @@ impl DatabaseManager {
         // map raw results into audio result type
         let futures: Vec<_> = results_raw
             .into_iter()
-            .map(|raw| async move {
-                let speaker = match raw.speaker_id {
-                    Some(id) => (self.get_speaker_by_id(id).await).ok(),
-                    None => None,
-                };
-
-                Ok::<AudioResult, sqlx::Error>(AudioResult {
-                    audio_chunk_id: raw.audio_chunk_id,
-                    transcription: raw.transcription,
-                    timestamp: raw.timestamp,
-                    file_path: raw.file_path,
-                    offset_index: raw.offset_index,
-                    transcription_engine: raw.transcription_engine,
-                    tags: raw
-                        .tags
-                        .map(|s| s.split(',').map(|s| s.to_owned()).collect())
-                        .unwrap_or_default(),
-                    device_name: raw.device_name,
-                    device_type: if raw.is_input_device {
-                        DeviceType::Input
-                    } else {
-                        DeviceType::Output
-                    },
-                    speaker,
-                    start_time: raw.start_time,
-                    end_time: raw.end_time,
-                })
-            })
+            .map(|raw| self.audio_result_from_raw(raw))
             .collect();
 
         Ok(try_join_all(futures).await?.into_iter().collect())
     }
 
+    async fn audio_result_from_raw(&self, raw: AudioResultRaw) -> Result<AudioResult, sqlx::Error> {
+        let speaker = match raw.speaker_id {
+            Some(id) => (self.get_speaker_by_id(id).await).ok(),
+            None => None,
+        };
+
+        Ok(AudioResult {
+            matches: fts_match_offsets(raw.highlighted.as_deref(), &raw.transcription),
+            audio_chunk_id: raw.audio_chunk_id,
+            transcription: raw.transcription,
+            timestamp: raw.timestamp,
+            file_path: raw.file_path,
+            offset_index: raw.offset_index,
+            transcription_engine: raw.transcription_engine,
+            tags: raw
+                .tags
+                .map(|s| s.split(',').map(|s| s.to_owned()).collect())
+                .unwrap_or_default(),
+            device_name: raw.device_name,
+            device_type: if raw.is_input_device {
+                DeviceType::Input
+            } else {
+                DeviceType::Output
+            },
+            speaker,
+            start_time: raw.start_time,
+            end_time: raw.end_time,
+            score: raw.score,
+            snippet: raw.snippet,
+        })
+    }
+
     pub async fn get_frame(&self, frame_id: i64) -> Result<Option<(String, i64)>, sqlx::Error> {
         sqlx::query_as::<_, (String, i64)>(
             r#"
@@ -1510,6 +1698,19 @@ impl DatabaseManager {
         Ok(result.flatten())
     }
 
+    /// Regions blurred out of a frame at capture time.
+    pub async fn get_frame_pii_redactions(
+        &self,
+        frame_id: i64,
+    ) -> Result<Vec<PiiRedaction>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT pii_type, x, y, width, height FROM pii_redactions WHERE frame_id = ?1 ORDER BY id",
+        )
+        .bind(frame_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
     /// Get all OCR text positions with bounding boxes for a specific frame.
     /// Returns parsed TextPosition objects ready for text overlay rendering.
     pub async fn get_frame_text_positions(
@@ -2003,41 +2204,22 @@ impl DatabaseManager {
     pub async fn execute_raw_sql(&self, query: &str) -> Result<serde_json::Value, sqlx::Error> {
         let rows = sqlx::query(query).fetch_all(&self.pool).await?;
 
-        let result: Vec<serde_json::Map<String, serde_json::Value>> = rows
-            .iter()
-            .map(|row| {
-                let mut map = serde_json::Map::new();
-                for (i, column) in row.columns().iter().enumerate() {
-                    if let Ok(value) = row.try_get_raw(i) {
-                        let json_value = match value.type_info().name() {
-                            "TEXT" => {
-                                let s: String = row.try_get(i).unwrap_or_default();
-                                serde_json::Value::String(s)
-                            }
-                            "INTEGER" => {
-                                let i: i64 = row.try_get(i).unwrap_or_default();
-                                serde_json::Value::Number(i.into())
-                            }
-                            "REAL" => {
-                                let f: f64 = row.try_get(i).unwrap_or_default();
-                                serde_json::Value::Number(
-                                    serde_json::Number::from_f64(f).unwrap_or(0.into()),
-                                )
-                            }
-                            _ => serde_json::Value::Null,
-                        };
-                        map.insert(column.name().to_string(), json_value);
-                    }
-                }
-                map
-            })
-            .collect();
-
         Ok(serde_json::Value::Array(
-            result.into_iter().map(serde_json::Value::Object).collect(),
+            rows.iter()
+                .map(|row| serde_json::Value::Object(sqlite_row_to_json(row)))
+                .collect(),
         ))
     }
 
+    /// Run a statement that may write, for the opt-in `/raw_sql/write` endpoint. Returns
+    /// the number of rows changed.
+    pub async fn execute_raw_sql_write(&self, query: &str) -> Result<u64, sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        let result = sqlx::query(query).execute(&mut **tx.conn()).await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
     pub async fn find_video_chunks(
         &self,
         start: DateTime<Utc>,
@@ -2168,6 +2350,7 @@ impl DatabaseManager {
         end_time: Option<DateTime<Utc>>,
         limit: u32,
         offset: u32,
+        order: SearchOrder,
     ) -> Result<Vec<UiContent>, sqlx::Error> {
         // combine search aspects into single fts query
         let mut fts_parts = Vec::new();
@@ -2194,6 +2377,12 @@ impl DatabaseManager {
             "WHERE ui_monitoring_fts MATCH ?1"
         };
 
+        let order_clause = if order == SearchOrder::Relevance && !query.is_empty() {
+            "ui_monitoring_fts.rank, ui_monitoring.timestamp DESC"
+        } else {
+            "ui_monitoring.timestamp DESC"
+        };
+
         let sql = format!(
             r#"
             SELECT
@@ -2206,7 +2395,8 @@ impl DatabaseManager {
                 video_chunks.file_path,
                 frames.offset_index,
                 frames.name as frame_name,
-                frames.browser_url
+                frames.browser_url,
+                {}
             FROM {}
             LEFT JOIN frames ON
                 frames.timestamp BETWEEN
@@ -2217,13 +2407,16 @@ impl DatabaseManager {
                 AND (?2 IS NULL OR ui_monitoring.timestamp >= ?2)
                 AND (?3 IS NULL OR ui_monitoring.timestamp <= ?3)
             GROUP BY ui_monitoring.id
-            ORDER BY ui_monitoring.timestamp DESC
+            ORDER BY {}
             LIMIT ?4 OFFSET ?5
             "#,
-            base_sql, where_clause
+            fts_rank_columns("ui_monitoring_fts", !query.is_empty()),
+            base_sql,
+            where_clause,
+            order_clause
         );
 
-        sqlx::query_as(&sql)
+        let raw_results: Vec<UiContentRaw> = sqlx::query_as(&sql)
             .bind(if combined_query.is_empty() {
                 "*".to_owned()
             } else {
@@ -2234,7 +2427,16 @@ impl DatabaseManager {
             .bind(limit)
             .bind(offset)
             .fetch_all(&self.pool)
-            .await
+            .await?;
+
+        Ok(raw_results
+            .into_iter()
+            .map(|raw| {
+                let mut content = raw.content;
+                content.matches = fts_match_offsets(raw.highlighted.as_deref(), &content.text);
+                content
+            })
+            .collect())
     }
 
     /// Search UI events (user input actions)
@@ -2394,6 +2596,53 @@ impl DatabaseManager {
         .await
     }
 
+    /// Transcription segments for a transcript, ordered by when they were spoken: the
+    /// chunk start plus the segment offset (`start_time`) in the chunk.
+    pub async fn get_transcript_rows(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        device_name: Option<&str>,
+        speaker_ids: &[i64],
+        speaker_name: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<TranscriptRow>, sqlx::Error> {
+        let speaker_ids_json = serde_json::to_string(speaker_ids).unwrap_or_else(|_| "[]".into());
+        sqlx::query_as::<_, TranscriptRow>(
+            r#"
+            SELECT
+                at.id,
+                at.transcription,
+                at.device,
+                at.is_input_device,
+                at.speaker_id,
+                s.name AS speaker_name,
+                COALESCE(ac.timestamp, at.timestamp) AS chunk_timestamp,
+                at.timestamp,
+                at.start_time,
+                at.end_time
+            FROM audio_transcriptions at
+            JOIN audio_chunks ac ON at.audio_chunk_id = ac.id
+            LEFT JOIN speakers s ON at.speaker_id = s.id
+            WHERE (?1 IS NULL OR at.timestamp >= ?1)
+                AND (?2 IS NULL OR at.timestamp <= ?2)
+                AND (?3 IS NULL OR at.device = ?3)
+                AND (json_array_length(?4) = 0 OR at.speaker_id IN (SELECT value FROM json_each(?4)))
+                AND (?5 IS NULL OR s.name LIKE '%' || ?5 || '%' COLLATE NOCASE)
+            ORDER BY chunk_timestamp, COALESCE(at.start_time, 0), at.id
+            LIMIT ?6
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(device_name)
+        .bind(&speaker_ids_json)
+        .bind(speaker_name)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+    }
+
     // get unnamed speakers
     pub async fn get_unnamed_speakers(
         &self,
@@ -2710,14 +2959,43 @@ impl DatabaseManager {
         frame_id: i64,
         embedding: String,
     ) -> Result<(), sqlx::Error> {
-        sqlx::query("INSERT INTO ocr_text_embeddings (frame_id, embedding) VALUES (?1, ?2)")
-            .bind(frame_id)
-            .bind(embedding)
-            .execute(&self.pool)
-            .await?;
+        let vector = decode_embedding(embedding.as_bytes());
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO ocr_text_embeddings (frame_id, embedding) VALUES (?1, ?2) RETURNING id",
+        )
+        .bind(frame_id)
+        .bind(embedding)
+        .fetch_one(&self.pool)
+        .await?;
+        if let Some(vector) = vector {
+            self.index_ocr_embeddings(None, vec![(id, frame_id, vector)])
+                .await;
+        }
         Ok(())
     }
 
+    /// Hand the OCR embeddings inserted from now on to `index`, instead of leaving them to
+    /// its next sync.
+    pub fn attach_ocr_embedding_index(&self, index: Arc<EmbeddingAnnIndex>) {
+        if let Ok(mut indexes) = self.ocr_embedding_indexes.write() {
+            indexes.push(index);
+        }
+    }
+
+    /// Add rows `(row_id, frame_id, vector)` just inserted with `model` to the attached
+    /// indexes. Failures only delay the rows until the next sync.
+    async fn index_ocr_embeddings(&self, model: Option<&str>, rows: Vec<(i64, i64, Vec<f32>)>) {
+        let indexes = match self.ocr_embedding_indexes.read() {
+            Ok(indexes) => indexes.clone(),
+            Err(_) => return,
+        };
+        for index in indexes {
+            if let Err(e) = index.insert(model, rows.clone()).await {
+                warn!("failed to index ocr embeddings: {}", e);
+            }
+        }
+    }
+
     pub async fn search_similar_embeddings(
         &self,
         embedding: Vec<f32>,
@@ -2732,7 +3010,8 @@ impl DatabaseManager {
                     frame_id,
                     vec_distance_cosine(embedding, vec_f32(?1)) as similarity
                 FROM ocr_text_embeddings
-                WHERE vec_distance_cosine(embedding, vec_f32(?1)) < ?2
+                WHERE model IS NULL
+                    AND vec_distance_cosine(embedding, vec_f32(?1)) < ?2
                 ORDER BY similarity ASC
                 LIMIT ?3
             )
@@ -2748,7 +3027,8 @@ impl DatabaseManager {
                 ocr_text.ocr_engine,
                 frames.window_name,
                 GROUP_CONCAT(tags.name, ',') as tags,
-                frames.browser_url
+                frames.browser_url,
+                EXISTS(SELECT 1 FROM pii_redactions WHERE pii_redactions.frame_id = frames.id) AS redacted
             FROM embedding_matches
             JOIN ocr_text ON embedding_matches.frame_id = ocr_text.frame_id
             JOIN frames ON ocr_text.frame_id = frames.id
@@ -2788,62 +3068,412 @@ impl DatabaseManager {
                     .unwrap_or_default(),
                 browser_url: raw.browser_url,
                 focused: raw.focused,
+                score: None,
+                snippet: None,
+                matches: Vec::new(),
+                redacted: raw.redacted,
             })
             .collect())
     }
 
-    // Add method to update frame names
-    pub async fn update_frame_name(&self, frame_id: i64, name: &str) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE frames SET name = ?1 WHERE id = ?2")
-            .bind(name)
-            .bind(frame_id)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+    /// Rows of `ocr_text_embeddings` from `model` (`None` for untagged rows) with an id
+    /// above `after_id`, in id order, as `(id, frame_id, embedding)`.
+    pub async fn get_ocr_embeddings_after(
+        &self,
+        model: Option<&str>,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<(i64, i64, Vec<u8>)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, frame_id, CAST(embedding AS BLOB)
+            FROM ocr_text_embeddings
+            WHERE model IS ?1 AND id > ?2
+            ORDER BY id
+            LIMIT ?3
+            "#,
+        )
+        .bind(model)
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
     }
 
-    // Add method to update all frames in a video chunk
-    pub async fn update_video_chunk_frames_names(
+    /// Number of `ocr_text_embeddings` rows from `model` and their highest id.
+    pub async fn count_ocr_embeddings(
         &self,
-        video_chunk_id: i64,
-        name: &str,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE frames SET name = ?1 WHERE video_chunk_id = ?2")
-            .bind(name)
-            .bind(video_chunk_id)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+        model: Option<&str>,
+    ) -> Result<(i64, i64), sqlx::Error> {
+        sqlx::query_as(
+            "SELECT COUNT(*), COALESCE(MAX(id), 0) FROM ocr_text_embeddings WHERE model IS ?1",
+        )
+        .bind(model)
+        .fetch_one(&self.pool)
+        .await
     }
 
-    pub async fn repair_database(&self) -> Result<(), anyhow::Error> {
-        debug!("starting aggressive database repair process");
-
-        // Force close any pending transactions
-        let emergency_steps = [
-            "PRAGMA locking_mode = EXCLUSIVE;",
-            "ROLLBACK;",                    // Force rollback any stuck transactions
-            "PRAGMA busy_timeout = 60000;", // Increase timeout to 60s
-        ];
-
-        for step in emergency_steps {
-            if let Err(e) = sqlx::query(step).execute(&self.pool).await {
-                warn!("emergency step failed (continuing anyway): {}", e);
-            }
+    /// OCR results of `frame_ids`, in no particular order. Missing frames are skipped.
+    pub async fn get_ocr_results_by_frame_ids(
+        &self,
+        frame_ids: &[i64],
+    ) -> Result<Vec<OCRResult>, sqlx::Error> {
+        if frame_ids.is_empty() {
+            return Ok(Vec::new());
         }
 
-        // Force checkpoint and cleanup WAL files
-        let wal_cleanup = [
-            "PRAGMA wal_checkpoint(TRUNCATE);",
-            "PRAGMA journal_mode = DELETE;", // Temporarily disable WAL
-            "PRAGMA journal_size_limit = 0;", // Clear journal
-        ];
+        let raw_results: Vec<OCRResultRaw> = sqlx::query_as(
+            r#"
+            SELECT
+                ocr_text.frame_id,
+                ocr_text.text as ocr_text,
+                ocr_text.text_json,
+                frames.timestamp,
+                frames.name as frame_name,
+                video_chunks.file_path,
+                frames.offset_index,
+                frames.app_name,
+                ocr_text.ocr_engine,
+                frames.window_name,
+                video_chunks.device_name,
+                GROUP_CONCAT(tags.name, ',') as tags,
+                frames.browser_url,
+                frames.focused,
+                EXISTS(SELECT 1 FROM pii_redactions WHERE pii_redactions.frame_id = frames.id) AS redacted
+            FROM frames
+            JOIN ocr_text ON frames.id = ocr_text.frame_id
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            LEFT JOIN vision_tags ON frames.id = vision_tags.vision_id
+            LEFT JOIN tags ON vision_tags.tag_id = tags.id
+            WHERE frames.id IN (SELECT value FROM json_each(?1))
+            GROUP BY frames.id
+            "#,
+        )
+        .bind(ids_json(frame_ids))
+        .fetch_all(&self.pool)
+        .await?;
 
-        for step in wal_cleanup {
-            if let Err(e) = sqlx::query(step).execute(&self.pool).await {
-                warn!("wal cleanup failed (continuing anyway): {}", e);
-            }
-        }
+        Ok(raw_results.into_iter().map(ocr_result_from_raw).collect())
+    }
+
+    /// OCR text without an embedding from `model`, newest first, as `(frame_id, text)`.
+    pub async fn get_ocr_text_without_embedding(
+        &self,
+        model: &str,
+        limit: i64,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT ocr_text.frame_id, ocr_text.text
+            FROM ocr_text
+            WHERE TRIM(ocr_text.text) != ''
+              AND NOT EXISTS (
+                SELECT 1 FROM ocr_text_embeddings
+                WHERE ocr_text_embeddings.frame_id = ocr_text.frame_id
+                  AND ocr_text_embeddings.model = ?1
+              )
+              AND NOT EXISTS (
+                SELECT 1 FROM embedding_failures
+                WHERE embedding_failures.source = 'ocr_text'
+                  AND embedding_failures.row_id = ocr_text.frame_id
+                  AND embedding_failures.model = ?1
+              )
+            ORDER BY ocr_text.frame_id DESC
+            LIMIT ?2
+            "#,
+        )
+        .bind(model)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Audio transcriptions without an embedding from `model`, newest first, as
+    /// `(audio_transcription_id, transcription)`.
+    pub async fn get_transcriptions_without_embedding(
+        &self,
+        model: &str,
+        limit: i64,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT audio_transcriptions.id, audio_transcriptions.transcription
+            FROM audio_transcriptions
+            WHERE TRIM(audio_transcriptions.transcription) != ''
+              AND NOT EXISTS (
+                SELECT 1 FROM audio_transcription_embeddings
+                WHERE audio_transcription_embeddings.audio_transcription_id = audio_transcriptions.id
+                  AND audio_transcription_embeddings.model = ?1
+              )
+              AND NOT EXISTS (
+                SELECT 1 FROM embedding_failures
+                WHERE embedding_failures.source = 'audio_transcriptions'
+                  AND embedding_failures.row_id = audio_transcriptions.id
+                  AND embedding_failures.model = ?1
+              )
+            ORDER BY audio_transcriptions.id DESC
+            LIMIT ?2
+            "#,
+        )
+        .bind(model)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Record that `model` failed to embed the row `row_id` of `source` (`ocr_text` frame
+    /// ids or `audio_transcriptions` ids), which is no longer returned as missing an
+    /// embedding.
+    pub async fn mark_embedding_failure(
+        &self,
+        source: &str,
+        row_id: i64,
+        model: &str,
+        error: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO embedding_failures (source, row_id, model, error, failed_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+        )
+        .bind(source)
+        .bind(row_id)
+        .bind(model)
+        .bind(error)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Store `(frame_id, embedding)` pairs generated by `model`.
+    pub async fn insert_ocr_text_embeddings(
+        &self,
+        model: &str,
+        embeddings: &[(i64, Vec<f32>)],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        let mut rows = Vec::with_capacity(embeddings.len());
+        for (frame_id, embedding) in embeddings {
+            let id: i64 = sqlx::query_scalar(
+                "INSERT INTO ocr_text_embeddings (frame_id, model, embedding) VALUES (?1, ?2, ?3) RETURNING id",
+            )
+            .bind(frame_id)
+            .bind(model)
+            .bind(embedding.as_bytes())
+            .fetch_one(&mut **tx.conn())
+            .await?;
+            rows.push((id, *frame_id, embedding.clone()));
+        }
+        tx.commit().await?;
+        self.index_ocr_embeddings(Some(model), rows).await;
+        Ok(())
+    }
+
+    /// Store `(audio_transcription_id, embedding)` pairs generated by `model`.
+    pub async fn insert_transcription_embeddings(
+        &self,
+        model: &str,
+        embeddings: &[(i64, Vec<f32>)],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        for (audio_transcription_id, embedding) in embeddings {
+            sqlx::query(
+                "INSERT INTO audio_transcription_embeddings (audio_transcription_id, model, embedding) VALUES (?1, ?2, ?3)",
+            )
+            .bind(audio_transcription_id)
+            .bind(model)
+            .bind(embedding.as_bytes())
+            .execute(&mut **tx.conn())
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// OCR results closest to `embedding` among the vectors of `model`, most similar
+    /// first. `score` is the cosine similarity.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_ocr_by_embedding(
+        &self,
+        embedding: &[f32],
+        model: &str,
+        limit: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+    ) -> Result<Vec<OCRResult>, sqlx::Error> {
+        let raw_results: Vec<OCRResultRaw> = sqlx::query_as(
+            r#"
+            SELECT
+                ocr_text.frame_id,
+                ocr_text.text as ocr_text,
+                ocr_text.text_json,
+                frames.timestamp,
+                frames.name as frame_name,
+                video_chunks.file_path,
+                frames.offset_index,
+                frames.app_name,
+                ocr_text.ocr_engine,
+                frames.window_name,
+                video_chunks.device_name,
+                GROUP_CONCAT(tags.name, ',') as tags,
+                frames.browser_url,
+                frames.focused,
+                EXISTS(SELECT 1 FROM pii_redactions WHERE pii_redactions.frame_id = frames.id) AS redacted,
+                MAX(1.0 - vec_distance_cosine(ocr_text_embeddings.embedding, vec_f32(?1))) AS score
+            FROM ocr_text_embeddings
+            JOIN frames ON ocr_text_embeddings.frame_id = frames.id
+            JOIN ocr_text ON frames.id = ocr_text.frame_id
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            LEFT JOIN vision_tags ON frames.id = vision_tags.vision_id
+            LEFT JOIN tags ON vision_tags.tag_id = tags.id
+            WHERE ocr_text_embeddings.model = ?2
+                AND (?3 IS NULL OR frames.timestamp >= ?3)
+                AND (?4 IS NULL OR frames.timestamp <= ?4)
+                AND (?5 IS NULL OR frames.app_name LIKE '%' || ?5 || '%' COLLATE NOCASE)
+                AND (?6 IS NULL OR frames.window_name LIKE '%' || ?6 || '%' COLLATE NOCASE)
+            GROUP BY frames.id
+            ORDER BY score DESC
+            LIMIT ?7
+            "#,
+        )
+        .bind(embedding.as_bytes())
+        .bind(model)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(app_name.filter(|a| !a.is_empty()))
+        .bind(window_name.filter(|w| !w.is_empty()))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(raw_results.into_iter().map(ocr_result_from_raw).collect())
+    }
+
+    /// Audio transcriptions closest to `embedding` among the vectors of `model`, most
+    /// similar first. `score` is the cosine similarity.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_audio_by_embedding(
+        &self,
+        embedding: &[f32],
+        model: &str,
+        limit: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        speaker_ids: Option<Vec<i64>>,
+        speaker_name: Option<&str>,
+    ) -> Result<Vec<AudioResult>, sqlx::Error> {
+        let results_raw: Vec<AudioResultRaw> = sqlx::query_as(
+            r#"
+            SELECT
+                audio_transcriptions.audio_chunk_id,
+                audio_transcriptions.transcription,
+                audio_transcriptions.timestamp,
+                audio_chunks.file_path,
+                audio_transcriptions.offset_index,
+                audio_transcriptions.transcription_engine,
+                GROUP_CONCAT(tags.name, ',') as tags,
+                audio_transcriptions.device as device_name,
+                audio_transcriptions.is_input_device,
+                audio_transcriptions.speaker_id,
+                audio_transcriptions.start_time,
+                audio_transcriptions.end_time,
+                MAX(1.0 - vec_distance_cosine(audio_transcription_embeddings.embedding, vec_f32(?1))) AS score
+            FROM audio_transcription_embeddings
+            JOIN audio_transcriptions
+                ON audio_transcription_embeddings.audio_transcription_id = audio_transcriptions.id
+            JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            LEFT JOIN speakers ON audio_transcriptions.speaker_id = speakers.id
+            LEFT JOIN audio_tags ON audio_chunks.id = audio_tags.audio_chunk_id
+            LEFT JOIN tags ON audio_tags.tag_id = tags.id
+            WHERE audio_transcription_embeddings.model = ?2
+                AND (?3 IS NULL OR audio_transcriptions.timestamp >= ?3)
+                AND (?4 IS NULL OR audio_transcriptions.timestamp <= ?4)
+                AND (speakers.id IS NULL OR speakers.hallucination = 0)
+                AND (?5 IS NULL OR audio_transcriptions.speaker_id IN (SELECT value FROM json_each(?5)))
+                AND (?6 IS NULL OR speakers.name LIKE '%' || ?6 || '%' COLLATE NOCASE)
+            GROUP BY audio_transcriptions.audio_chunk_id, audio_transcriptions.offset_index
+            ORDER BY score DESC
+            LIMIT ?7
+            "#,
+        )
+        .bind(embedding.as_bytes())
+        .bind(model)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(
+            speaker_ids
+                .filter(|ids| !ids.is_empty())
+                .map(|ids| ids_json(&ids)),
+        )
+        .bind(speaker_name)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let futures: Vec<_> = results_raw
+            .into_iter()
+            .map(|raw| self.audio_result_from_raw(raw))
+            .collect();
+
+        try_join_all(futures).await
+    }
+
+    // Add method to update frame names
+    pub async fn update_frame_name(&self, frame_id: i64, name: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE frames SET name = ?1 WHERE id = ?2")
+            .bind(name)
+            .bind(frame_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // Add method to update all frames in a video chunk
+    pub async fn update_video_chunk_frames_names(
+        &self,
+        video_chunk_id: i64,
+        name: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE frames SET name = ?1 WHERE video_chunk_id = ?2")
+            .bind(name)
+            .bind(video_chunk_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn repair_database(&self) -> Result<(), anyhow::Error> {
+        debug!("starting aggressive database repair process");
+
+        // Force close any pending transactions
+        let emergency_steps = [
+            "PRAGMA locking_mode = EXCLUSIVE;",
+            "ROLLBACK;",                    // Force rollback any stuck transactions
+            "PRAGMA busy_timeout = 60000;", // Increase timeout to 60s
+        ];
+
+        for step in emergency_steps {
+            if let Err(e) = sqlx::query(step).execute(&self.pool).await {
+                warn!("emergency step failed (continuing anyway): {}", e);
+            }
+        }
+
+        // Force checkpoint and cleanup WAL files
+        let wal_cleanup = [
+            "PRAGMA wal_checkpoint(TRUNCATE);",
+            "PRAGMA journal_mode = DELETE;", // Temporarily disable WAL
+            "PRAGMA journal_size_limit = 0;", // Clear journal
+        ];
+
+        for step in wal_cleanup {
+            if let Err(e) = sqlx::query(step).execute(&self.pool).await {
+                warn!("wal cleanup failed (continuing anyway): {}", e);
+            }
+        }
 
         // Aggressive recovery steps
         let recovery_steps = [
@@ -3558,60 +4188,1758 @@ LIMIT ? OFFSET ?
 
         Ok(rows.into_iter().map(UiEventRecord::from).collect())
     }
-}
-
-pub fn find_matching_positions(blocks: &[OcrTextBlock], query: &str) -> Vec<TextPosition> {
-    let query_lower = query.to_lowercase();
-    let query_words: Vec<&str> = query_lower.split_whitespace().collect();
 
-    blocks
-        .iter()
-        .filter_map(|block| {
-            let text_lower = block.text.to_lowercase();
+    // ========================================================================
+    // Retention
+    // ========================================================================
 
-            // Check for exact match or any word match
-            let matches = text_lower.contains(&query_lower)
-                || query_words.iter().any(|&word| text_lower.contains(word));
+    /// Video chunks whose media file is still on disk, oldest first.
+    ///
+    /// With `before`, only chunks whose newest frame is older than the cutoff are
+    /// returned. The latest chunk of each device is never returned since it may
+    /// still be written to.
+    pub async fn list_video_chunks_for_retention(
+        &self,
+        before: Option<DateTime<Utc>>,
+        limit: Option<i64>,
+    ) -> Result<Vec<MediaChunkRow>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT video_chunks.id, video_chunks.file_path
+            FROM video_chunks
+            JOIN frames ON frames.video_chunk_id = video_chunks.id
+            WHERE video_chunks.media_pruned_at IS NULL
+              AND video_chunks.id NOT IN (SELECT MAX(id) FROM video_chunks GROUP BY device_name)
+            GROUP BY video_chunks.id
+            HAVING ?1 IS NULL OR MAX(frames.timestamp) < ?1
+            ORDER BY MAX(frames.timestamp) ASC
+            LIMIT ?2
+            "#,
+        )
+        .bind(before)
+        .bind(limit.unwrap_or(-1))
+        .fetch_all(&self.pool)
+        .await
+    }
 
-            if matches {
-                let vision_top = block.top.parse::<f32>().unwrap_or(0.0);
-                let height = block.height.parse::<f32>().unwrap_or(0.0);
-                // Convert from Apple Vision coordinates (bottom-left origin, Y up)
-                // to screen coordinates (top-left origin, Y down)
-                let screen_top = 1.0 - vision_top - height;
+    /// Audio chunks whose media file is still on disk, oldest first.
+    ///
+    /// Chunks recorded before the `timestamp` column existed fall back to their
+    /// earliest transcription.
+    pub async fn list_audio_chunks_for_retention(
+        &self,
+        before: Option<DateTime<Utc>>,
+        limit: Option<i64>,
+    ) -> Result<Vec<MediaChunkRow>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT audio_chunks.id, audio_chunks.file_path
+            FROM audio_chunks
+            LEFT JOIN audio_transcriptions ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            WHERE audio_chunks.media_pruned_at IS NULL
+            GROUP BY audio_chunks.id
+            HAVING ?1 IS NULL
+                OR COALESCE(audio_chunks.timestamp, MIN(audio_transcriptions.timestamp)) < ?1
+            ORDER BY COALESCE(audio_chunks.timestamp, MIN(audio_transcriptions.timestamp)) ASC,
+                     audio_chunks.id ASC
+            LIMIT ?2
+            "#,
+        )
+        .bind(before)
+        .bind(limit.unwrap_or(-1))
+        .fetch_all(&self.pool)
+        .await
+    }
 
-                Some(TextPosition {
-                    text: block.text.clone(),
-                    confidence: block.conf.parse::<f32>().unwrap_or(0.0),
-                    bounds: TextBounds {
-                        left: block.left.parse::<f32>().unwrap_or(0.0),
-                        top: screen_top,
-                        width: block.width.parse::<f32>().unwrap_or(0.0),
-                        height,
-                    },
-                })
-            } else {
-                None
-            }
-        })
-        .collect()
-}
+    /// Record that the media files of these video chunks were removed. Frames and
+    /// OCR text stay searchable.
+    pub async fn mark_video_chunks_media_pruned(&self, ids: &[i64]) -> Result<u64, sqlx::Error> {
+        self.mark_media_pruned("video_chunks", ids).await
+    }
 
-fn calculate_confidence(positions: &[TextPosition]) -> f32 {
-    if positions.is_empty() {
-        return 0.0;
+    /// Record that the media files of these audio chunks were removed.
+    /// Transcriptions stay searchable.
+    pub async fn mark_audio_chunks_media_pruned(&self, ids: &[i64]) -> Result<u64, sqlx::Error> {
+        self.mark_media_pruned("audio_chunks", ids).await
     }
 
-    positions.iter().map(|pos| pos.confidence).sum::<f32>() / positions.len() as f32
-}
+    async fn mark_media_pruned(&self, table: &str, ids: &[i64]) -> Result<u64, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let ids = ids_json(ids);
+        let mut tx = self.begin_immediate_with_retry().await?;
+        let result = sqlx::query(&format!(
+            "UPDATE {} SET media_pruned_at = ?1 WHERE id IN (SELECT value FROM json_each(?2))",
+            table
+        ))
+        .bind(Utc::now())
+        .bind(ids)
+        .execute(&mut **tx.conn())
+        .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
 
-/// Parse all OCR text blocks into TextPosition objects with bounding boxes.
-/// Unlike `find_matching_positions`, this returns ALL text positions without filtering.
-///
-/// Note: Apple Vision framework uses a coordinate system with origin at bottom-left,
-/// where Y increases upward. We convert to standard screen coordinates (origin at
-/// top-left, Y increases downward) by flipping the Y axis: screen_top = 1 - vision_top - height
-pub fn parse_all_text_positions(blocks: &[OcrTextBlock]) -> Vec<TextPosition> {
+    /// Video chunks older than `before` that still have their original media and have
+    /// not been downsampled yet, oldest first.
+    pub async fn list_video_chunks_for_downsampling(
+        &self,
+        before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<MediaChunkRow>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT video_chunks.id, video_chunks.file_path
+            FROM video_chunks
+            JOIN frames ON frames.video_chunk_id = video_chunks.id
+            WHERE video_chunks.media_pruned_at IS NULL
+              AND video_chunks.downsampled_at IS NULL
+              AND video_chunks.id NOT IN (SELECT MAX(id) FROM video_chunks GROUP BY device_name)
+            GROUP BY video_chunks.id
+            HAVING MAX(frames.timestamp) < ?1
+            ORDER BY MAX(frames.timestamp) ASC
+            LIMIT ?2
+            "#,
+        )
+        .bind(before)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Distinct frame offsets stored in a video chunk, ascending.
+    pub async fn get_video_chunk_offsets(
+        &self,
+        video_chunk_id: i64,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT DISTINCT offset_index FROM frames WHERE video_chunk_id = ?1 ORDER BY offset_index ASC",
+        )
+        .bind(video_chunk_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Point a video chunk and its frames at a re-encoded file.
+    ///
+    /// `offset_map` holds `(old_offset, new_offset)` pairs; every offset of the chunk has
+    /// to be present. Frames keep their OCR text, only the pixels they resolve to change.
+    pub async fn replace_downsampled_video_chunk(
+        &self,
+        video_chunk_id: i64,
+        new_file_path: &str,
+        offset_map: &[(i64, i64)],
+    ) -> Result<(), sqlx::Error> {
+        let offset_map = serde_json::to_string(offset_map).unwrap_or_else(|_| "[]".to_string());
+        let mut tx = self.begin_immediate_with_retry().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE frames
+            SET offset_index = (
+                    SELECT json_extract(value, '$[1]') FROM json_each(?2)
+                    WHERE json_extract(value, '$[0]') = frames.offset_index
+                ),
+                name = ?3
+            WHERE video_chunk_id = ?1
+              AND offset_index IN (SELECT json_extract(value, '$[0]') FROM json_each(?2))
+            "#,
+        )
+        .bind(video_chunk_id)
+        .bind(&offset_map)
+        .bind(new_file_path)
+        .execute(&mut **tx.conn())
+        .await?;
+
+        sqlx::query("UPDATE video_chunks SET file_path = ?1, downsampled_at = ?2 WHERE id = ?3")
+            .bind(new_file_path)
+            .bind(Utc::now())
+            .bind(video_chunk_id)
+            .execute(&mut **tx.conn())
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Skip a chunk in future downsampling passes without touching its file
+    /// (e.g. it can't be decoded or is already small enough).
+    pub async fn mark_video_chunk_downsampled(
+        &self,
+        video_chunk_id: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE video_chunks SET downsampled_at = ?1 WHERE id = ?2")
+            .bind(Utc::now())
+            .bind(video_chunk_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete up to `limit` frames older than `before` together with their OCR text,
+    /// tags and embeddings. Video chunks left without frames are deleted as well and
+    /// returned so the caller can remove their files.
+    ///
+    /// FTS tables are kept in sync by the delete triggers on `frames` and `ocr_text`.
+    pub async fn delete_frames_before(
+        &self,
+        before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<(PrunedRows, Vec<MediaChunkRow>), sqlx::Error> {
+        let mut pruned = PrunedRows::default();
+        let mut tx = self.begin_immediate_with_retry().await?;
+
+        let frame_ids: Vec<i64> = sqlx::query_scalar(
+            "SELECT id FROM frames WHERE timestamp < ?1 ORDER BY timestamp ASC LIMIT ?2",
+        )
+        .bind(before)
+        .bind(limit)
+        .fetch_all(&mut **tx.conn())
+        .await?;
+
+        if frame_ids.is_empty() {
+            return Ok((pruned, Vec::new()));
+        }
+        let frame_ids = ids_json(&frame_ids);
+
+        let chunk_ids: Vec<i64> = sqlx::query_scalar(
+            "SELECT DISTINCT video_chunk_id FROM frames WHERE id IN (SELECT value FROM json_each(?1))",
+        )
+        .bind(&frame_ids)
+        .fetch_all(&mut **tx.conn())
+        .await?;
+
+        (pruned.ocr_text, pruned.frames) = delete_frame_rows(&mut **tx.conn(), &frame_ids).await?;
+
+        let chunk_ids = ids_json(&chunk_ids);
+        let empty_chunks: Vec<MediaChunkRow> = sqlx::query_as(
+            r#"
+            SELECT id, file_path FROM video_chunks
+            WHERE id IN (SELECT value FROM json_each(?1))
+              AND NOT EXISTS (SELECT 1 FROM frames WHERE frames.video_chunk_id = video_chunks.id)
+            "#,
+        )
+        .bind(&chunk_ids)
+        .fetch_all(&mut **tx.conn())
+        .await?;
+
+        if !empty_chunks.is_empty() {
+            let ids: Vec<i64> = empty_chunks.iter().map(|c| c.id).collect();
+            let ids = ids_json(&ids);
+            pruned.video_chunks = sqlx::query(
+                "DELETE FROM video_chunks WHERE id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(ids)
+            .execute(&mut **tx.conn())
+            .await?
+            .rows_affected();
+        }
+
+        tx.commit().await?;
+        debug!(
+            "retention: deleted {} frames, {} video chunks",
+            pruned.frames, pruned.video_chunks
+        );
+        Ok((pruned, empty_chunks))
+    }
+
+    /// Delete up to `limit` audio chunks recorded before `before`, with their
+    /// transcriptions and tags. Returns the deleted chunks so the caller can remove
+    /// their files.
+    pub async fn delete_audio_chunks_before(
+        &self,
+        before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<(PrunedRows, Vec<MediaChunkRow>), sqlx::Error> {
+        let mut pruned = PrunedRows::default();
+        let mut tx = self.begin_immediate_with_retry().await?;
+
+        let chunks: Vec<MediaChunkRow> = sqlx::query_as(
+            r#"
+            SELECT audio_chunks.id, audio_chunks.file_path
+            FROM audio_chunks
+            LEFT JOIN audio_transcriptions ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            GROUP BY audio_chunks.id
+            HAVING COALESCE(audio_chunks.timestamp, MIN(audio_transcriptions.timestamp)) < ?1
+            ORDER BY audio_chunks.id ASC
+            LIMIT ?2
+            "#,
+        )
+        .bind(before)
+        .bind(limit)
+        .fetch_all(&mut **tx.conn())
+        .await?;
+
+        if chunks.is_empty() {
+            return Ok((pruned, chunks));
+        }
+        let ids: Vec<i64> = chunks.iter().map(|c| c.id).collect();
+        let ids = ids_json(&ids);
+
+        pruned.audio_transcriptions = sqlx::query(
+            "DELETE FROM audio_transcriptions WHERE audio_chunk_id IN (SELECT value FROM json_each(?1))",
+        )
+        .bind(&ids)
+        .execute(&mut **tx.conn())
+        .await?
+        .rows_affected();
+
+        for query in [
+            "DELETE FROM audio_tags WHERE audio_chunk_id IN (SELECT value FROM json_each(?1))",
+            "DELETE FROM chunked_text_entries WHERE audio_chunk_id IN (SELECT value FROM json_each(?1))",
+        ] {
+            sqlx::query(query)
+                .bind(&ids)
+                .execute(&mut **tx.conn())
+                .await?;
+        }
+
+        pruned.audio_chunks =
+            sqlx::query("DELETE FROM audio_chunks WHERE id IN (SELECT value FROM json_each(?1))")
+                .bind(&ids)
+                .execute(&mut **tx.conn())
+                .await?
+                .rows_affected();
+
+        tx.commit().await?;
+        debug!(
+            "retention: deleted {} audio chunks, {} transcriptions",
+            pruned.audio_chunks, pruned.audio_transcriptions
+        );
+        Ok((pruned, chunks))
+    }
+
+    /// Delete UI events, UI monitoring and accessibility snapshots older than `before`.
+    pub async fn delete_ui_events_before(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<PrunedRows, sqlx::Error> {
+        let mut pruned = PrunedRows::default();
+        let mut tx = self.begin_immediate_with_retry().await?;
+
+        pruned.ui_events = sqlx::query("DELETE FROM ui_events WHERE timestamp < ?1")
+            .bind(before)
+            .execute(&mut **tx.conn())
+            .await?
+            .rows_affected();
+
+        sqlx::query(
+            "DELETE FROM ui_monitoring_tags WHERE ui_monitoring_id IN (SELECT id FROM ui_monitoring WHERE timestamp < ?1)",
+        )
+        .bind(before)
+        .execute(&mut **tx.conn())
+        .await?;
+        pruned.ui_monitoring = sqlx::query("DELETE FROM ui_monitoring WHERE timestamp < ?1")
+            .bind(before)
+            .execute(&mut **tx.conn())
+            .await?
+            .rows_affected();
+
+        sqlx::query(
+            "DELETE FROM accessibility_tags WHERE accessibility_id IN (SELECT id FROM accessibility WHERE timestamp < ?1)",
+        )
+        .bind(before)
+        .execute(&mut **tx.conn())
+        .await?;
+        pruned.accessibility = sqlx::query("DELETE FROM accessibility WHERE timestamp < ?1")
+            .bind(before)
+            .execute(&mut **tx.conn())
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+        Ok(pruned)
+    }
+
+    /// Delete the frames, transcriptions, UI events and accessibility text recorded in the
+    /// time range of `filter` and matching its query, in one transaction. FTS entries
+    /// follow through the delete triggers.
+    ///
+    /// Frames sharing the position of a deleted frame in its video chunk go too, they show
+    /// the same pixels. The video frames and audio ranges holding deleted data are queued
+    /// in `pending_frame_purges` and `pending_audio_purges` for the media to be rewritten.
+    ///
+    /// Copies of the text kept by other features go in the same transaction: webhook
+    /// dead letters are deleted, meeting titles, activity session windows and pipe run
+    /// outputs are cleared (see `scrub_forgotten_copies`).
+    pub async fn forget(&self, filter: &ForgetFilter) -> Result<ForgetResult, sqlx::Error> {
+        let mut result = ForgetResult::default();
+        if filter.is_empty() {
+            return Ok(result);
+        }
+        let query = filter
+            .query
+            .as_deref()
+            .map(str::trim)
+            .filter(|q| !q.is_empty());
+        let mut tx = self.begin_immediate_with_retry().await?;
+        let conn = &mut **tx.conn();
+
+        // Frames
+        let matched: Vec<(i64, Option<i64>, i64, DateTime<Utc>)> = sqlx::query_as(&forget_sql(
+            "SELECT id, video_chunk_id, offset_index, timestamp FROM frames",
+            "frames.timestamp",
+            "frames.id IN (SELECT frame_id FROM ocr_text_fts WHERE ocr_text_fts MATCH ?3)",
+            query.is_some(),
+        ))
+        .bind(filter.start_time)
+        .bind(filter.end_time)
+        .bind(query)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut positions: Vec<(i64, i64)> = matched
+            .iter()
+            .filter_map(|(_, chunk_id, offset, _)| chunk_id.map(|id| (id, *offset)))
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        let positions = serde_json::to_string(&positions).unwrap_or_else(|_| "[]".to_string());
+
+        let mut frames: BTreeMap<i64, DateTime<Utc>> = matched
+            .into_iter()
+            .map(|(id, _, _, timestamp)| (id, timestamp))
+            .collect();
+        let siblings: Vec<(i64, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT frames.id, frames.timestamp
+            FROM json_each(?1) AS p
+            JOIN frames ON frames.video_chunk_id = json_extract(p.value, '$[0]')
+                       AND frames.offset_index = json_extract(p.value, '$[1]')
+            "#,
+        )
+        .bind(&positions)
+        .fetch_all(&mut *conn)
+        .await?;
+        frames.extend(siblings);
+
+        if !frames.is_empty() {
+            let ids: Vec<i64> = frames.keys().copied().collect();
+            (result.rows.ocr_text, result.rows.frames) =
+                delete_frame_rows(&mut *conn, &ids_json(&ids)).await?;
+            result.frames_to_purge = sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO pending_frame_purges (video_chunk_id, offset_index)
+                SELECT json_extract(value, '$[0]'), json_extract(value, '$[1]') FROM json_each(?1)
+                "#,
+            )
+            .bind(&positions)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+            result.frame_ids = ids;
+            result.frame_timestamps = frames.into_values().collect();
+        }
+
+        // Audio transcriptions, matched row by row: the FTS table only knows their chunk
+        let transcriptions: Vec<(i64, i64, Option<f64>, Option<f64>, DateTime<Utc>)> =
+            sqlx::query_as(&forget_sql(
+                "SELECT id, audio_chunk_id, start_time, end_time, timestamp FROM audio_transcriptions",
+                "audio_transcriptions.timestamp",
+                r#"audio_transcriptions.id IN (
+                    SELECT t.id FROM audio_transcriptions_fts
+                    JOIN audio_transcriptions t
+                      ON t.audio_chunk_id = audio_transcriptions_fts.audio_chunk_id
+                     AND t.transcription = audio_transcriptions_fts.transcription
+                    WHERE audio_transcriptions_fts MATCH ?3
+                )"#,
+                query.is_some(),
+            ))
+            .bind(filter.start_time)
+            .bind(filter.end_time)
+            .bind(query)
+            .fetch_all(&mut *conn)
+            .await?;
+
+        if !transcriptions.is_empty() {
+            let ids: Vec<i64> = transcriptions.iter().map(|t| t.0).collect();
+            let mut chunk_ids: Vec<i64> = transcriptions.iter().map(|t| t.1).collect();
+            chunk_ids.sort_unstable();
+            chunk_ids.dedup();
+            let chunk_ids = ids_json(&chunk_ids);
+
+            result.rows.audio_transcriptions = sqlx::query(
+                "DELETE FROM audio_transcriptions WHERE id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(ids_json(&ids))
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+
+            // The delete trigger drops the FTS rows of the whole chunk, index the rest again
+            sqlx::query(
+                r#"
+                INSERT INTO audio_transcriptions_fts(transcription, device, audio_chunk_id, speaker_id, start_time, end_time)
+                SELECT transcription, COALESCE(device, ''), audio_chunk_id, speaker_id, start_time, end_time
+                FROM audio_transcriptions
+                WHERE audio_chunk_id IN (SELECT value FROM json_each(?1))
+                  AND transcription IS NOT NULL AND transcription != ''
+                "#,
+            )
+            .bind(&chunk_ids)
+            .execute(&mut *conn)
+            .await?;
+            sqlx::query(
+                "DELETE FROM chunked_text_entries WHERE audio_chunk_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&chunk_ids)
+            .execute(&mut *conn)
+            .await?;
+
+            let segments: Vec<(i64, Option<f64>, Option<f64>)> = transcriptions
+                .iter()
+                .map(|(_, chunk_id, start, end, _)| (*chunk_id, *start, *end))
+                .collect();
+            result.audio_segments_to_purge = sqlx::query(
+                r#"
+                INSERT INTO pending_audio_purges (audio_chunk_id, start_time, end_time)
+                SELECT json_extract(value, '$[0]'), json_extract(value, '$[1]'), json_extract(value, '$[2]')
+                FROM json_each(?1)
+                "#,
+            )
+            .bind(serde_json::to_string(&segments).unwrap_or_else(|_| "[]".to_string()))
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+            result.transcription_timestamps = transcriptions.into_iter().map(|t| t.4).collect();
+        }
+
+        // UI events, accessibility and (legacy) UI monitoring text
+        let ui_events = select_forgotten(
+            &mut *conn,
+            &forget_sql(
+                "SELECT id, timestamp FROM ui_events",
+                "ui_events.timestamp",
+                "ui_events.id IN (SELECT rowid FROM ui_events_fts WHERE ui_events_fts MATCH ?3)",
+                query.is_some(),
+            ),
+            filter,
+            query,
+        )
+        .await?;
+        if !ui_events.is_empty() {
+            let ids: Vec<i64> = ui_events.iter().map(|e| e.0).collect();
+            result.rows.ui_events =
+                sqlx::query("DELETE FROM ui_events WHERE id IN (SELECT value FROM json_each(?1))")
+                    .bind(ids_json(&ids))
+                    .execute(&mut *conn)
+                    .await?
+                    .rows_affected();
+            result.ui_event_timestamps = ui_events.into_iter().map(|e| e.1).collect();
+        }
+
+        let accessibility = select_forgotten(
+            &mut *conn,
+            &forget_sql(
+                "SELECT id, timestamp FROM accessibility",
+                "accessibility.timestamp",
+                "accessibility.id IN (SELECT rowid FROM accessibility_fts WHERE accessibility_fts MATCH ?3)",
+                query.is_some(),
+            ),
+            filter,
+            query,
+        )
+        .await?;
+        if !accessibility.is_empty() {
+            let ids = ids_json(&accessibility.iter().map(|a| a.0).collect::<Vec<_>>());
+            sqlx::query(
+                "DELETE FROM accessibility_tags WHERE accessibility_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids)
+            .execute(&mut *conn)
+            .await?;
+            result.rows.accessibility = sqlx::query(
+                "DELETE FROM accessibility WHERE id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+            result.accessibility_timestamps = accessibility.into_iter().map(|a| a.1).collect();
+        }
+
+        let ui_monitoring = select_forgotten(
+            &mut *conn,
+            &forget_sql(
+                "SELECT id, timestamp FROM ui_monitoring",
+                "ui_monitoring.timestamp",
+                "ui_monitoring.id IN (SELECT ui_id FROM ui_monitoring_fts WHERE ui_monitoring_fts MATCH ?3)",
+                query.is_some(),
+            ),
+            filter,
+            query,
+        )
+        .await?;
+        if !ui_monitoring.is_empty() {
+            let ids = ids_json(&ui_monitoring.iter().map(|u| u.0).collect::<Vec<_>>());
+            sqlx::query(
+                "DELETE FROM ui_monitoring_tags WHERE ui_monitoring_id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids)
+            .execute(&mut *conn)
+            .await?;
+            result.rows.ui_monitoring = sqlx::query(
+                "DELETE FROM ui_monitoring WHERE id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&ids)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        }
+
+        result.derived_rows =
+            scrub_forgotten_copies(&mut *conn, filter, query.map(forget_terms)).await?;
+
+        tx.commit().await?;
+        info!(
+            "forget: deleted {} frames, {} transcriptions, {} ui events, {} accessibility records, scrubbed {} derived rows",
+            result.rows.frames,
+            result.rows.audio_transcriptions,
+            result.rows.ui_events,
+            result.rows.accessibility,
+            result.derived_rows
+        );
+        Ok(result)
+    }
+
+    /// Drop queued purges of chunks that no longer exist (e.g. removed by retention).
+    pub async fn delete_orphaned_purges(&self) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "DELETE FROM pending_frame_purges WHERE video_chunk_id NOT IN (SELECT id FROM video_chunks)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "DELETE FROM pending_audio_purges WHERE audio_chunk_id NOT IN (SELECT id FROM audio_chunks)",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Video chunks with frames queued for purging, except the latest chunk of each
+    /// monitor which may still be recorded.
+    pub async fn list_video_chunks_to_purge(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<MediaChunkRow>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT DISTINCT video_chunks.id, video_chunks.file_path
+            FROM pending_frame_purges
+            JOIN video_chunks ON video_chunks.id = pending_frame_purges.video_chunk_id
+            WHERE video_chunks.id NOT IN (SELECT MAX(id) FROM video_chunks GROUP BY device_name)
+            ORDER BY video_chunks.id ASC
+            LIMIT ?1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Offsets queued for purging in a video chunk, ascending.
+    pub async fn get_pending_frame_purges(
+        &self,
+        video_chunk_id: i64,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT offset_index FROM pending_frame_purges WHERE video_chunk_id = ?1 ORDER BY offset_index ASC",
+        )
+        .bind(video_chunk_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Record that the frames at `purged` offsets were cut out of a video chunk: later
+    /// frames (and purges queued meanwhile) move down by the number of purged frames
+    /// before them. A chunk left without frames is deleted, returns whether it was.
+    pub async fn finish_video_chunk_purge(
+        &self,
+        video_chunk_id: i64,
+        purged: &[i64],
+    ) -> Result<bool, sqlx::Error> {
+        let purged = ids_json(purged);
+        let mut tx = self.begin_immediate_with_retry().await?;
+
+        sqlx::query(
+            "DELETE FROM pending_frame_purges WHERE video_chunk_id = ?1 AND offset_index IN (SELECT value FROM json_each(?2))",
+        )
+        .bind(video_chunk_id)
+        .bind(&purged)
+        .execute(&mut **tx.conn())
+        .await?;
+
+        for table in ["frames", "pending_frame_purges"] {
+            sqlx::query(&format!(
+                r#"
+                UPDATE {table}
+                SET offset_index = offset_index
+                    - (SELECT COUNT(*) FROM json_each(?2) WHERE value < {table}.offset_index)
+                WHERE video_chunk_id = ?1
+                "#
+            ))
+            .bind(video_chunk_id)
+            .bind(&purged)
+            .execute(&mut **tx.conn())
+            .await?;
+        }
+
+        let deleted = sqlx::query(
+            r#"
+            DELETE FROM video_chunks
+            WHERE id = ?1
+              AND NOT EXISTS (SELECT 1 FROM frames WHERE frames.video_chunk_id = ?1)
+              AND NOT EXISTS (SELECT 1 FROM pending_frame_purges WHERE video_chunk_id = ?1)
+            "#,
+        )
+        .bind(video_chunk_id)
+        .execute(&mut **tx.conn())
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+        Ok(deleted > 0)
+    }
+
+    /// Audio ranges queued for silencing, oldest first.
+    pub async fn list_pending_audio_purges(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<PendingAudioPurge>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT pending_audio_purges.id, pending_audio_purges.audio_chunk_id,
+                   audio_chunks.file_path, pending_audio_purges.start_time,
+                   pending_audio_purges.end_time
+            FROM pending_audio_purges
+            JOIN audio_chunks ON audio_chunks.id = pending_audio_purges.audio_chunk_id
+            ORDER BY pending_audio_purges.id ASC
+            LIMIT ?1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn delete_pending_audio_purges(&self, ids: &[i64]) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "DELETE FROM pending_audio_purges WHERE id IN (SELECT value FROM json_each(?1))",
+        )
+        .bind(ids_json(ids))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn insert_webhook(
+        &self,
+        url: &str,
+        events: &[String],
+        predicate: Option<&str>,
+        secret: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO webhooks (url, events, predicate, secret, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(url)
+        .bind(serde_json::to_string(events).unwrap_or_else(|_| "[]".to_string()))
+        .bind(predicate)
+        .bind(secret)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn list_webhooks(&self) -> Result<Vec<Webhook>, sqlx::Error> {
+        let rows: Vec<WebhookRow> = sqlx::query_as("SELECT * FROM webhooks ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(Webhook::from).collect())
+    }
+
+    pub async fn get_webhook(&self, id: i64) -> Result<Option<Webhook>, sqlx::Error> {
+        let row: Option<WebhookRow> = sqlx::query_as("SELECT * FROM webhooks WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(Webhook::from))
+    }
+
+    /// Replace the settings of a webhook, returns false when it doesn't exist.
+    pub async fn update_webhook(
+        &self,
+        id: i64,
+        url: &str,
+        events: &[String],
+        predicate: Option<&str>,
+        enabled: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let updated = sqlx::query(
+            "UPDATE webhooks SET url = ?2, events = ?3, predicate = ?4, enabled = ?5 WHERE id = ?1",
+        )
+        .bind(id)
+        .bind(url)
+        .bind(serde_json::to_string(events).unwrap_or_else(|_| "[]".to_string()))
+        .bind(predicate)
+        .bind(enabled)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(updated > 0)
+    }
+
+    /// Delete a webhook and its dead letters, returns false when it doesn't exist.
+    pub async fn delete_webhook(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM webhooks WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    /// Delete the dead letters stored before `before`, they hold copies of recorded data.
+    pub async fn delete_webhook_dead_letters_before(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM webhook_dead_letters WHERE created_at < ?1")
+            .bind(before)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted)
+    }
+
+    pub async fn insert_webhook_dead_letter(
+        &self,
+        webhook_id: i64,
+        event_name: &str,
+        payload: &str,
+        attempts: i64,
+        last_error: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            r#"
+            INSERT INTO webhook_dead_letters (webhook_id, event_name, payload, attempts, last_error, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(event_name)
+        .bind(payload)
+        .bind(attempts)
+        .bind(last_error)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    /// Dead letters, newest first, of one webhook or of all of them.
+    /// Replace the payload and error of a dead letter, which counts as created now. False
+    /// when it doesn't exist (anymore).
+    pub async fn update_webhook_dead_letter(
+        &self,
+        id: i64,
+        event_name: &str,
+        payload: &str,
+        last_error: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let updated = sqlx::query(
+            r#"
+            UPDATE webhook_dead_letters
+            SET event_name = ?2, payload = ?3, last_error = ?4, created_at = ?5
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .bind(event_name)
+        .bind(payload)
+        .bind(last_error)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(updated > 0)
+    }
+
+    pub async fn list_webhook_dead_letters(
+        &self,
+        webhook_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<WebhookDeadLetter>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT * FROM webhook_dead_letters
+            WHERE ?1 IS NULL OR webhook_id = ?1
+            ORDER BY id DESC
+            LIMIT ?2
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_webhook_dead_letter(
+        &self,
+        id: i64,
+    ) -> Result<Option<WebhookDeadLetter>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM webhook_dead_letters WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn delete_webhook_dead_letter(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM webhook_dead_letters WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    pub async fn insert_meeting(
+        &self,
+        app: &str,
+        title: Option<&str>,
+        start_time: DateTime<Utc>,
+        end_time: Option<DateTime<Utc>>,
+        source: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            r#"
+            INSERT INTO meetings (app, title, start_time, end_time, source, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+        )
+        .bind(app)
+        .bind(title)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(source)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_meeting(&self, id: i64) -> Result<Option<Meeting>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM meetings WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Meetings overlapping the time range, newest first.
+    pub async fn list_meetings(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Meeting>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT * FROM meetings
+            WHERE (?1 IS NULL OR end_time IS NULL OR end_time >= ?1)
+                AND (?2 IS NULL OR start_time <= ?2)
+                AND (?3 IS NULL OR app LIKE '%' || ?3 || '%' COLLATE NOCASE)
+            ORDER BY start_time DESC, id DESC
+            LIMIT ?4 OFFSET ?5
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(app_name)
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// The latest meeting still in progress.
+    pub async fn get_open_meeting(&self) -> Result<Option<Meeting>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT * FROM meetings WHERE end_time IS NULL ORDER BY start_time DESC, id DESC LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// End a meeting in progress, returns false when it doesn't exist or already ended.
+    pub async fn end_meeting(&self, id: i64, end_time: DateTime<Utc>) -> Result<bool, sqlx::Error> {
+        let updated = sqlx::query(
+            "UPDATE meetings SET end_time = MAX(start_time, ?1) WHERE id = ?2 AND end_time IS NULL",
+        )
+        .bind(end_time)
+        .bind(id)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(updated > 0)
+    }
+
+    /// End the detected meetings left in progress by a previous run, at the last frame or
+    /// transcription recorded. Returns how many were ended.
+    pub async fn end_interrupted_meetings(&self) -> Result<u64, sqlx::Error> {
+        let updated = sqlx::query(
+            r#"
+            UPDATE meetings SET end_time = MAX(
+                start_time,
+                COALESCE((SELECT MAX(timestamp) FROM frames), start_time),
+                COALESCE((SELECT MAX(timestamp) FROM audio_transcriptions), start_time)
+            )
+            WHERE end_time IS NULL AND source = 'detected'
+            "#,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(updated)
+    }
+
+    /// Merge meetings into the earliest one, which spans all of them. Returns None when
+    /// one of them doesn't exist.
+    pub async fn merge_meetings(&self, ids: &[i64]) -> Result<Option<Meeting>, sqlx::Error> {
+        let ids_json = serde_json::to_string(ids).unwrap_or_else(|_| "[]".into());
+        let mut tx = self.begin_immediate_with_retry().await?;
+
+        let meetings: Vec<Meeting> = sqlx::query_as(
+            r#"
+            SELECT * FROM meetings
+            WHERE id IN (SELECT value FROM json_each(?1))
+            ORDER BY start_time, id
+            "#,
+        )
+        .bind(&ids_json)
+        .fetch_all(&mut **tx.conn())
+        .await?;
+
+        let mut unique_ids = ids.to_vec();
+        unique_ids.sort_unstable();
+        unique_ids.dedup();
+        if meetings.is_empty() || meetings.len() != unique_ids.len() {
+            return Ok(None);
+        }
+
+        let mut merged = meetings[0].clone();
+        merged.end_time = if meetings.iter().any(|m| m.end_time.is_none()) {
+            None
+        } else {
+            meetings.iter().filter_map(|m| m.end_time).max()
+        };
+        merged.title = meetings.iter().find_map(|m| m.title.clone());
+
+        sqlx::query("UPDATE meetings SET title = ?1, end_time = ?2 WHERE id = ?3")
+            .bind(&merged.title)
+            .bind(merged.end_time)
+            .bind(merged.id)
+            .execute(&mut **tx.conn())
+            .await?;
+        sqlx::query(
+            "DELETE FROM meetings WHERE id IN (SELECT value FROM json_each(?1)) AND id != ?2",
+        )
+        .bind(&ids_json)
+        .bind(merged.id)
+        .execute(&mut **tx.conn())
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(merged))
+    }
+
+    /// Split a meeting at `at`: it ends there and a copy of it covers the rest. Returns
+    /// the id of the new meeting, None when the meeting doesn't exist or `at` isn't
+    /// strictly inside it.
+    pub async fn split_meeting(
+        &self,
+        id: i64,
+        at: DateTime<Utc>,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+
+        let meeting: Option<Meeting> = sqlx::query_as(
+            r#"
+            SELECT * FROM meetings
+            WHERE id = ?1 AND start_time < ?2 AND (end_time IS NULL OR end_time > ?2)
+            "#,
+        )
+        .bind(id)
+        .bind(at)
+        .fetch_optional(&mut **tx.conn())
+        .await?;
+        let Some(meeting) = meeting else {
+            return Ok(None);
+        };
+
+        let new_id = sqlx::query(
+            r#"
+            INSERT INTO meetings (app, title, start_time, end_time, source, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+        )
+        .bind(&meeting.app)
+        .bind(&meeting.title)
+        .bind(at)
+        .bind(meeting.end_time)
+        .bind(&meeting.source)
+        .bind(Utc::now())
+        .execute(&mut **tx.conn())
+        .await?
+        .last_insert_rowid();
+        sqlx::query("UPDATE meetings SET end_time = ?1 WHERE id = ?2")
+            .bind(at)
+            .bind(id)
+            .execute(&mut **tx.conn())
+            .await?;
+
+        tx.commit().await?;
+        Ok(Some(new_id))
+    }
+
+    pub async fn delete_meeting(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM meetings WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    /// Speakers transcribed in the time range, most segments first.
+    pub async fn get_meeting_participants(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MeetingParticipant>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                at.speaker_id,
+                s.name,
+                COUNT(*) AS segments
+            FROM audio_transcriptions at
+            LEFT JOIN speakers s ON at.speaker_id = s.id
+            WHERE at.timestamp >= ?1
+                AND at.timestamp <= ?2
+                AND at.speaker_id IS NOT NULL
+                AND COALESCE(s.hallucination, 0) = 0
+            GROUP BY at.speaker_id
+            ORDER BY segments DESC, at.speaker_id
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Frames captured in the time range, oldest first.
+    pub async fn get_meeting_frames(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<MeetingFrame>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, timestamp, device_name, app_name, window_name
+            FROM frames
+            WHERE timestamp >= ?1 AND timestamp <= ?2
+            ORDER BY timestamp, id
+            LIMIT ?3
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// The most recent activity session.
+    pub async fn get_last_activity_session(&self) -> Result<Option<ActivitySession>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM activity_sessions ORDER BY start_time DESC, id DESC LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Replace the activity sessions starting at or after `from` with `sessions`.
+    pub async fn replace_activity_sessions(
+        &self,
+        from: DateTime<Utc>,
+        sessions: &[InsertActivitySession],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.begin_immediate_with_retry().await?;
+        sqlx::query("DELETE FROM activity_sessions WHERE start_time >= ?1")
+            .bind(from)
+            .execute(&mut **tx.conn())
+            .await?;
+        for session in sessions {
+            sqlx::query(
+                r#"
+                INSERT INTO activity_sessions
+                    (app_name, window_name, browser_url, domain, start_time, end_time, source)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                "#,
+            )
+            .bind(&session.app_name)
+            .bind(&session.window_name)
+            .bind(&session.browser_url)
+            .bind(&session.domain)
+            .bind(session.start_time)
+            .bind(session.end_time)
+            .bind(&session.source)
+            .execute(&mut **tx.conn())
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Activity sessions overlapping the time range, oldest first.
+    pub async fn get_activity_sessions(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        app_name: Option<&str>,
+    ) -> Result<Vec<ActivitySession>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT * FROM activity_sessions
+            WHERE end_time > ?1 AND start_time < ?2
+                AND (?3 IS NULL OR app_name LIKE '%' || ?3 || '%' COLLATE NOCASE)
+            ORDER BY start_time, id
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(app_name)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// App switch and window focus events in the time range, preceded by the last one
+    /// before it, oldest first.
+    pub async fn get_focus_events(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<FocusObservation>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT * FROM (
+                SELECT timestamp, app_name, window_title AS window_name, browser_url
+                FROM ui_events
+                WHERE event_type IN ('app_switch', 'window_focus') AND timestamp < ?1
+                ORDER BY timestamp DESC
+                LIMIT 1
+            )
+            UNION ALL
+            SELECT * FROM (
+                SELECT timestamp, app_name, window_title AS window_name, browser_url
+                FROM ui_events
+                WHERE event_type IN ('app_switch', 'window_focus')
+                    AND timestamp >= ?1 AND timestamp < ?2
+            )
+            ORDER BY timestamp
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Focused windows of the frames in the time range, preceded by the last one before
+    /// it, oldest first.
+    pub async fn get_frame_focus(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<FocusObservation>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT * FROM (
+                SELECT timestamp, app_name, window_name, browser_url
+                FROM frames
+                WHERE COALESCE(focused, 1) = 1 AND app_name IS NOT NULL AND timestamp < ?1
+                ORDER BY timestamp DESC
+                LIMIT 1
+            )
+            UNION ALL
+            SELECT * FROM (
+                SELECT timestamp, app_name, window_name, browser_url
+                FROM frames
+                WHERE COALESCE(focused, 1) = 1 AND app_name IS NOT NULL
+                    AND timestamp >= ?1 AND timestamp < ?2
+            )
+            ORDER BY timestamp
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Times of the UI events (input and focus) in the time range.
+    pub async fn get_ui_event_times(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT timestamp FROM ui_events WHERE timestamp >= ?1 AND timestamp < ?2 ORDER BY timestamp",
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Record a queued run of a pipe cron job.
+    pub async fn insert_pipe_run(
+        &self,
+        pipe_id: &str,
+        path: &str,
+        source: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            r#"
+            INSERT INTO pipe_runs (pipe_id, path, source, status, started_at)
+            VALUES (?1, ?2, ?3, 'queued', ?4)
+            "#,
+        )
+        .bind(pipe_id)
+        .bind(path)
+        .bind(source)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    /// Mark a queued run as started now.
+    pub async fn start_pipe_run(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE pipe_runs SET status = 'running', started_at = ?2 WHERE id = ?1")
+            .bind(id)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn finish_pipe_run(
+        &self,
+        id: i64,
+        status: &str,
+        duration_ms: i64,
+        http_status: Option<u16>,
+        output: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE pipe_runs SET status = ?2, duration_ms = ?3, http_status = ?4, output = ?5
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .bind(status)
+        .bind(duration_ms)
+        .bind(http_status)
+        .bind(output)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Runs of a pipe's cron jobs, newest first.
+    pub async fn list_pipe_runs(
+        &self,
+        pipe_id: &str,
+        path: Option<&str>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<PipeRun>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT * FROM pipe_runs
+            WHERE pipe_id = ?1 AND (?2 IS NULL OR path = ?2)
+            ORDER BY started_at DESC, id DESC
+            LIMIT ?3 OFFSET ?4
+            "#,
+        )
+        .bind(pipe_id)
+        .bind(path)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Start of the latest run of a cron job, manual runs excluded.
+    pub async fn last_scheduled_pipe_run(
+        &self,
+        pipe_id: &str,
+        path: &str,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT MAX(started_at) FROM pipe_runs WHERE pipe_id = ?1 AND path = ?2 AND source != 'manual'",
+        )
+        .bind(pipe_id)
+        .bind(path)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Fail the runs left queued or running by a previous run of screenpipe.
+    pub async fn fail_interrupted_pipe_runs(&self) -> Result<u64, sqlx::Error> {
+        let failed = sqlx::query(
+            r#"
+            UPDATE pipe_runs SET status = 'failed', output = 'interrupted by shutdown'
+            WHERE status IN ('queued', 'running')
+            "#,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(failed)
+    }
+}
+
+/// Convert a row of an arbitrary query to a JSON object keyed by column name. Values
+/// other than text and numbers (NULL, BLOB) become `null`.
+pub(crate) fn sqlite_row_to_json(row: &SqliteRow) -> serde_json::Map<String, serde_json::Value> {
+    let mut map = serde_json::Map::new();
+    for (i, column) in row.columns().iter().enumerate() {
+        if let Ok(value) = row.try_get_raw(i) {
+            let json_value = match value.type_info().name() {
+                "TEXT" => {
+                    let s: String = row.try_get(i).unwrap_or_default();
+                    serde_json::Value::String(s)
+                }
+                "INTEGER" => {
+                    let i: i64 = row.try_get(i).unwrap_or_default();
+                    serde_json::Value::Number(i.into())
+                }
+                "REAL" => {
+                    let f: f64 = row.try_get(i).unwrap_or_default();
+                    serde_json::Value::Number(serde_json::Number::from_f64(f).unwrap_or(0.into()))
+                }
+                _ => serde_json::Value::Null,
+            };
+            map.insert(column.name().to_string(), json_value);
+        }
+    }
+    map
+}
+
+/// `select` restricted to the rows of a forget: `timestamp` between ?1 and ?2 (NULL
+/// bounds are open) and, when there is a text query, `text_match`, which binds it as ?3.
+fn forget_sql(select: &str, timestamp: &str, text_match: &str, has_query: bool) -> String {
+    format!(
+        "{select} WHERE (?1 IS NULL OR {timestamp} >= ?1) AND (?2 IS NULL OR {timestamp} <= ?2) AND {}",
+        if has_query { text_match } else { "?3 IS NULL" }
+    )
+}
+
+/// Words of a forget query as a JSON array, lowercased and without FTS operators, to
+/// find it in columns that have no FTS index.
+fn forget_terms(query: &str) -> String {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .filter(|term| !matches!(*term, "AND" | "OR" | "NOT" | "NEAR"))
+        .map(|term| {
+            term.trim_matches(|c: char| matches!(c, '"' | '*' | '(' | ')' | '^' | '+' | '-'))
+                .to_lowercase()
+        })
+        .filter(|term| !term.is_empty())
+        .collect();
+    serde_json::to_string(&terms).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Delete or clear the copies of forgotten text kept outside the recorded data. Returns
+/// the number of rows changed.
+///
+/// Without a query, the rows of the time range go. With a query (`terms` from
+/// `forget_terms`), the rows containing any of its words go, including those made after
+/// the range: dead letters and pipe outputs are written after the data they hold.
+async fn scrub_forgotten_copies(
+    conn: &mut SqliteConnection,
+    filter: &ForgetFilter,
+    terms: Option<String>,
+) -> Result<u64, sqlx::Error> {
+    let contains = |column: &str| {
+        if terms.is_some() {
+            format!("EXISTS (SELECT 1 FROM json_each(?3) WHERE instr(lower({column}), value) > 0)")
+        } else {
+            "?3 IS NULL".to_string()
+        }
+    };
+    // a query matches copies made after the range, a time range alone does not
+    let made_in_range = |column: &str| {
+        format!(
+            "(?1 IS NULL OR {column} >= ?1) AND (?3 IS NOT NULL OR ?2 IS NULL OR {column} <= ?2)"
+        )
+    };
+    let overlaps_range =
+        "(?1 IS NULL OR end_time IS NULL OR end_time >= ?1) AND (?2 IS NULL OR start_time <= ?2)";
+
+    let queries = [
+        format!(
+            "DELETE FROM webhook_dead_letters WHERE {} AND {}",
+            made_in_range("created_at"),
+            contains("payload")
+        ),
+        format!(
+            "UPDATE pipe_runs SET output = NULL WHERE output IS NOT NULL AND {} AND {}",
+            made_in_range("started_at"),
+            contains("output")
+        ),
+        format!(
+            "UPDATE meetings SET title = NULL WHERE title IS NOT NULL AND {overlaps_range} AND {}",
+            contains("title")
+        ),
+        format!(
+            r#"UPDATE activity_sessions SET window_name = NULL, browser_url = NULL, domain = NULL
+            WHERE (window_name IS NOT NULL OR browser_url IS NOT NULL) AND {overlaps_range}
+              AND ({} OR {})"#,
+            contains("window_name"),
+            contains("browser_url")
+        ),
+    ];
+    let mut scrubbed = 0;
+    for query in &queries {
+        scrubbed += sqlx::query(query)
+            .bind(filter.start_time)
+            .bind(filter.end_time)
+            .bind(terms.as_deref())
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+    }
+    Ok(scrubbed)
+}
+
+/// Ids and timestamps of the rows selected by a `forget_sql` query.
+async fn select_forgotten(
+    conn: &mut SqliteConnection,
+    sql: &str,
+    filter: &ForgetFilter,
+    query: Option<&str>,
+) -> Result<Vec<(i64, DateTime<Utc>)>, sqlx::Error> {
+    sqlx::query_as(sql)
+        .bind(filter.start_time)
+        .bind(filter.end_time)
+        .bind(query)
+        .fetch_all(conn)
+        .await
+}
+
+/// Delete the frames in `frame_ids` (a JSON array) with their OCR text, tags, embeddings
+/// and redactions. Returns the number of deleted OCR text and frame rows.
+async fn delete_frame_rows(
+    conn: &mut SqliteConnection,
+    frame_ids: &str,
+) -> Result<(u64, u64), sqlx::Error> {
+    let ocr_text =
+        sqlx::query("DELETE FROM ocr_text WHERE frame_id IN (SELECT value FROM json_each(?1))")
+            .bind(frame_ids)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+
+    for query in [
+        "DELETE FROM ocr_text_embeddings WHERE frame_id IN (SELECT value FROM json_each(?1))",
+        "DELETE FROM vision_tags WHERE vision_id IN (SELECT value FROM json_each(?1))",
+        "DELETE FROM pii_redactions WHERE frame_id IN (SELECT value FROM json_each(?1))",
+        "DELETE FROM chunked_text_entries WHERE frame_id IN (SELECT value FROM json_each(?1))",
+        // ui events outlive frames when their own retention is longer, just drop the reference
+        "UPDATE ui_events SET frame_id = NULL WHERE frame_id IN (SELECT value FROM json_each(?1))",
+    ] {
+        sqlx::query(query)
+            .bind(frame_ids)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    let frames = sqlx::query("DELETE FROM frames WHERE id IN (SELECT value FROM json_each(?1))")
+        .bind(frame_ids)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    Ok((ocr_text, frames))
+}
+
+/// Encode ids as a JSON array for use with `json_each`.
+fn ids_json(ids: &[i64]) -> String {
+    serde_json::to_string(ids).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Markers around matches in `highlight()` output, `char(2)`/`char(3)` in SQL.
+const HIGHLIGHT_START: char = '\u{2}';
+const HIGHLIGHT_END: char = '\u{3}';
+
+/// `score`, `snippet` and `highlighted` columns for an FTS5 `table` whose first column is
+/// the searched text, NULL when there is no text query to rank by.
+fn fts_rank_columns(table: &str, has_query: bool) -> String {
+    if !has_query {
+        return "NULL AS score, NULL AS snippet, NULL AS highlighted".to_string();
+    }
+    format!(
+        "-bm25({table}) AS score, \
+         snippet({table}, 0, '<mark>', '</mark>', '…', 24) AS snippet, \
+         highlight({table}, 0, char(2), char(3)) AS highlighted"
+    )
+}
+
+/// Character offsets of the matches marked in `highlighted`.
+///
+/// Empty when the highlighted text is not `text`, e.g. the FTS row of another segment of
+/// the same audio chunk.
+fn fts_match_offsets(highlighted: Option<&str>, text: &str) -> Vec<TextMatch> {
+    let Some(highlighted) = highlighted else {
+        return Vec::new();
+    };
+
+    let mut matches = Vec::new();
+    let mut plain = String::with_capacity(text.len());
+    let mut position = 0;
+    let mut start = None;
+    for c in highlighted.chars() {
+        match c {
+            HIGHLIGHT_START => start = Some(position),
+            HIGHLIGHT_END => {
+                if let Some(start) = start.take().filter(|&start| start < position) {
+                    matches.push(TextMatch {
+                        start,
+                        end: position,
+                    });
+                }
+            }
+            c => {
+                plain.push(c);
+                position += 1;
+            }
+        }
+    }
+
+    if plain != text {
+        return Vec::new();
+    }
+    matches
+}
+
+fn ocr_result_from_raw(raw: OCRResultRaw) -> OCRResult {
+    OCRResult {
+        matches: fts_match_offsets(raw.highlighted.as_deref(), &raw.ocr_text),
+        frame_id: raw.frame_id,
+        ocr_text: raw.ocr_text,
+        text_json: raw.text_json,
+        timestamp: raw.timestamp,
+        frame_name: raw.frame_name,
+        file_path: raw.file_path,
+        offset_index: raw.offset_index,
+        app_name: raw.app_name,
+        ocr_engine: raw.ocr_engine,
+        window_name: raw.window_name,
+        device_name: raw.device_name,
+        tags: raw
+            .tags
+            .map(|t| t.split(',').map(String::from).collect())
+            .unwrap_or_default(),
+        browser_url: raw.browser_url,
+        focused: raw.focused,
+        score: raw.score,
+        snippet: raw.snippet,
+        redacted: raw.redacted,
+    }
+}
+
+fn search_result_score(result: &SearchResult) -> Option<f64> {
+    match result {
+        SearchResult::OCR(ocr) => ocr.score,
+        SearchResult::Audio(audio) => audio.score,
+        SearchResult::UI(ui) => ui.score,
+        SearchResult::Input(_) => None,
+    }
+}
+
+fn search_result_timestamp(result: &SearchResult) -> DateTime<Utc> {
+    match result {
+        SearchResult::OCR(ocr) => ocr.timestamp,
+        SearchResult::Audio(audio) => audio.timestamp,
+        SearchResult::UI(ui) => ui.timestamp,
+        SearchResult::Input(input) => input.timestamp,
+    }
+}
+
+pub fn find_matching_positions(blocks: &[OcrTextBlock], query: &str) -> Vec<TextPosition> {
+    let query_lower = query.to_lowercase();
+    let query_words: Vec<&str> = query_lower.split_whitespace().collect();
+
+    blocks
+        .iter()
+        .filter_map(|block| {
+            let text_lower = block.text.to_lowercase();
+
+            // Check for exact match or any word match
+            let matches = text_lower.contains(&query_lower)
+                || query_words.iter().any(|&word| text_lower.contains(word));
+
+            if matches {
+                let vision_top = block.top.parse::<f32>().unwrap_or(0.0);
+                let height = block.height.parse::<f32>().unwrap_or(0.0);
+                // Convert from Apple Vision coordinates (bottom-left origin, Y up)
+                // to screen coordinates (top-left origin, Y down)
+                let screen_top = 1.0 - vision_top - height;
+
+                Some(TextPosition {
+                    text: block.text.clone(),
+                    confidence: block.conf.parse::<f32>().unwrap_or(0.0),
+                    bounds: TextBounds {
+                        left: block.left.parse::<f32>().unwrap_or(0.0),
+                        top: screen_top,
+                        width: block.width.parse::<f32>().unwrap_or(0.0),
+                        height,
+                    },
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn calculate_confidence(positions: &[TextPosition]) -> f32 {
+    if positions.is_empty() {
+        return 0.0;
+    }
+
+    positions.iter().map(|pos| pos.confidence).sum::<f32>() / positions.len() as f32
+}
+
+/// Parse all OCR text blocks into TextPosition objects with bounding boxes.
+/// Unlike `find_matching_positions`, this returns ALL text positions without filtering.
+///
+/// Note: Apple Vision framework uses a coordinate system with origin at bottom-left,
+/// where Y increases upward. We convert to standard screen coordinates (origin at
+/// top-left, Y increases downward) by flipping the Y axis: screen_top = 1 - vision_top - height
+pub fn parse_all_text_positions(blocks: &[OcrTextBlock]) -> Vec<TextPosition> {
     blocks
         .iter()
         .filter_map(|block| {
@@ -3828,4 +6156,22 @@ mod tests {
         // Should match both "Hello" and "World" due to word-by-word matching
         assert_eq!(positions.len(), 2);
     }
+
+    #[test]
+    fn test_fts_match_offsets() {
+        let text = "café meeting notes, meeting at 3";
+        let highlighted = "café \u{2}meeting\u{3} notes, \u{2}meeting\u{3} at 3";
+
+        let matches = fts_match_offsets(Some(highlighted), text);
+
+        assert_eq!(
+            matches,
+            vec![
+                TextMatch { start: 5, end: 12 },
+                TextMatch { start: 20, end: 27 },
+            ]
+        );
+        assert!(fts_match_offsets(Some(highlighted), "other segment").is_empty());
+        assert!(fts_match_offsets(None, text).is_empty());
+    }
 }
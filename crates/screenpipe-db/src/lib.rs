@@ -1,14 +1,18 @@
+pub mod ann;
 mod db;
 mod migration_worker;
+pub mod raw_sql;
 pub mod text_normalizer;
 pub mod text_similarity;
 mod types;
 mod video_db;
 
+pub use ann::{EmbeddingAnnIndex, OCR_EMBEDDING_INDEX_FILE};
 pub use db::{parse_all_text_positions, DatabaseManager, ImmediateTx};
 pub use migration_worker::{
     create_migration_worker, MigrationCommand, MigrationConfig, MigrationResponse, MigrationStatus,
     MigrationWorker,
 };
+pub use raw_sql::{RawSqlError, RawSqlLimits, RawSqlPage, ReadOnlySql};
 pub use text_normalizer::expand_search_query;
 pub use types::*;
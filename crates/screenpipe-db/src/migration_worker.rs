@@ -507,7 +507,10 @@ async fn migrate_ocr_data_to_frames(
 async fn process_batch(pool: &SqlitePool, last_id: i64, batch_size: i64) -> Result<(i64, i64)> {
     // Use a temporary DatabaseManager to get ImmediateTx with auto-rollback
     use crate::DatabaseManager;
-    let db = DatabaseManager { pool: pool.clone() };
+    let db = DatabaseManager {
+        pool: pool.clone(),
+        ocr_embedding_indexes: Default::default(),
+    };
     let mut tx = db.begin_immediate_with_retry().await?;
 
     // Query to get a batch of records with unique frame_ids that need migration
@@ -0,0 +1,7 @@
+-- Track chunks whose media file was removed by the retention policy while
+-- their text (frames/ocr, transcriptions) is kept around for search.
+ALTER TABLE video_chunks ADD COLUMN media_pruned_at DATETIME DEFAULT NULL;
+ALTER TABLE audio_chunks ADD COLUMN media_pruned_at DATETIME DEFAULT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_video_chunks_media_pruned_at ON video_chunks(media_pruned_at);
+CREATE INDEX IF NOT EXISTS idx_audio_chunks_media_pruned_at ON audio_chunks(media_pruned_at);
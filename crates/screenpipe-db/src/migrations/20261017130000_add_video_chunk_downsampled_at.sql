@@ -0,0 +1,5 @@
+-- Video chunks re-encoded at a lower fps/resolution by the archival tier.
+-- Frames keep their OCR text, their offset_index points into the new file.
+ALTER TABLE video_chunks ADD COLUMN downsampled_at DATETIME DEFAULT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_video_chunks_downsampled_at ON video_chunks(downsampled_at);
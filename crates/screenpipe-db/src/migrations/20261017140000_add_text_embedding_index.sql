@@ -0,0 +1,24 @@
+-- Embeddings of OCR text and audio transcriptions for hybrid search.
+-- `model` tells vectors of the in-process embedding indexer apart from the ones
+-- generated by other models (e.g. `add --use-embedding` through Ollama).
+ALTER TABLE ocr_text_embeddings ADD COLUMN model TEXT DEFAULT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_ocr_text_embeddings_frame_id ON ocr_text_embeddings(frame_id);
+
+CREATE TABLE IF NOT EXISTS audio_transcription_embeddings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    audio_transcription_id INTEGER NOT NULL,
+    model TEXT DEFAULT NULL,
+    embedding BLOB NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (audio_transcription_id) REFERENCES audio_transcriptions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_audio_transcription_embeddings_transcription_id
+    ON audio_transcription_embeddings(audio_transcription_id);
+
+-- Foreign keys are not enforced, follow deletes of transcriptions (retention, dedupe)
+CREATE TRIGGER IF NOT EXISTS audio_transcription_embeddings_ad AFTER DELETE ON audio_transcriptions
+BEGIN
+    DELETE FROM audio_transcription_embeddings WHERE audio_transcription_id = old.id;
+END;
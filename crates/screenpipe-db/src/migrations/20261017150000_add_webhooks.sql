@@ -0,0 +1,32 @@
+-- Webhook subscriptions to the event bus.
+-- `events` is a JSON array of event names, empty for every event. `predicate` is an
+-- optional JSON path condition on the event data, e.g. `$.app_name == "zoom.us"`.
+CREATE TABLE IF NOT EXISTS webhooks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    url TEXT NOT NULL,
+    events TEXT NOT NULL DEFAULT '[]',
+    predicate TEXT DEFAULT NULL,
+    secret TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Deliveries that still failed after every retry
+CREATE TABLE IF NOT EXISTS webhook_dead_letters (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    webhook_id INTEGER NOT NULL,
+    event_name TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    last_error TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (webhook_id) REFERENCES webhooks(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_dead_letters_webhook_id ON webhook_dead_letters(webhook_id);
+
+-- Foreign keys are not enforced
+CREATE TRIGGER IF NOT EXISTS webhook_dead_letters_ad AFTER DELETE ON webhooks
+BEGIN
+    DELETE FROM webhook_dead_letters WHERE webhook_id = old.id;
+END;
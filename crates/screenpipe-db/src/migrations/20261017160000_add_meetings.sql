@@ -0,0 +1,18 @@
+-- Meetings, detected from the event bus or started by hand.
+-- Transcriptions, frames and participant speakers are linked by time overlap, so a
+-- meeting that is stopped, merged or split keeps matching the right data.
+CREATE TABLE IF NOT EXISTS meetings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    app TEXT NOT NULL DEFAULT '',
+    -- window name at detection
+    title TEXT DEFAULT NULL,
+    start_time DATETIME NOT NULL,
+    -- NULL while the meeting is in progress
+    end_time DATETIME DEFAULT NULL,
+    -- 'detected' or 'manual'
+    source TEXT NOT NULL DEFAULT 'detected',
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_meetings_start_time ON meetings(start_time);
+CREATE INDEX IF NOT EXISTS idx_meetings_end_time ON meetings(end_time);
@@ -0,0 +1,17 @@
+-- Focused-app sessions derived from app switch / window focus events, or from frames
+-- when there are none, with idle time cut out. Summed by /activity/summary.
+CREATE TABLE IF NOT EXISTS activity_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    app_name TEXT NOT NULL,
+    window_name TEXT DEFAULT NULL,
+    browser_url TEXT DEFAULT NULL,
+    -- host of browser_url, without "www."
+    domain TEXT DEFAULT NULL,
+    start_time DATETIME NOT NULL,
+    end_time DATETIME NOT NULL,
+    -- 'ui_events' or 'frames'
+    source TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_activity_sessions_start_time ON activity_sessions(start_time);
+CREATE INDEX IF NOT EXISTS idx_activity_sessions_end_time ON activity_sessions(end_time);
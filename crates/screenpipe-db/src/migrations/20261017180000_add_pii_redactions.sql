@@ -0,0 +1,14 @@
+-- Regions of frames blurred at capture time because their OCR text contained PII
+-- (--redact-pii-at-capture). The pixels are gone from the video, this is the record of it.
+CREATE TABLE IF NOT EXISTS pii_redactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    frame_id INTEGER NOT NULL,
+    pii_type TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    FOREIGN KEY (frame_id) REFERENCES frames(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_pii_redactions_frame_id ON pii_redactions(frame_id);
@@ -0,0 +1,20 @@
+-- Media still holding data deleted through /forget. The rows are gone already, the
+-- frames are dropped from their mp4 chunk and the audio ranges silenced once the
+-- chunk is no longer being recorded.
+CREATE TABLE IF NOT EXISTS pending_frame_purges (
+    video_chunk_id INTEGER NOT NULL,
+    offset_index INTEGER NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (video_chunk_id, offset_index)
+);
+
+CREATE TABLE IF NOT EXISTS pending_audio_purges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    audio_chunk_id INTEGER NOT NULL,
+    -- seconds from the start of the chunk, NULL when the whole chunk has to go
+    start_time REAL,
+    end_time REAL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_pending_audio_purges_chunk ON pending_audio_purges(audio_chunk_id);
@@ -0,0 +1,19 @@
+-- Runs of pipe cron jobs, started by the scheduler or by hand.
+CREATE TABLE IF NOT EXISTS pipe_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    pipe_id TEXT NOT NULL,
+    -- route of the pipe called by the job
+    path TEXT NOT NULL,
+    -- `schedule`, `catch_up` or `manual`
+    source TEXT NOT NULL,
+    -- `queued`, `running`, `success` or `failed`
+    status TEXT NOT NULL,
+    -- when the run was queued, then when it started
+    started_at TIMESTAMP NOT NULL,
+    duration_ms INTEGER,
+    http_status INTEGER,
+    -- response body or error, truncated
+    output TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_pipe_runs_pipe ON pipe_runs(pipe_id, path, started_at);
@@ -0,0 +1,10 @@
+-- Rows the embedding indexer gave up on after repeated failures, skipped from then on.
+CREATE TABLE IF NOT EXISTS embedding_failures (
+    -- `ocr_text` (row_id is the frame id) or `audio_transcriptions`
+    source TEXT NOT NULL,
+    row_id INTEGER NOT NULL,
+    model TEXT NOT NULL,
+    error TEXT NOT NULL,
+    failed_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (source, row_id, model)
+);
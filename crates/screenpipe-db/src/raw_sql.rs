@@ -0,0 +1,301 @@
+//! Read-only execution of user supplied SQL (`/raw_sql`).
+//!
+//! Queries run on a dedicated connection with `PRAGMA query_only` so they can't change
+//! the store, after a check that the text is a single SELECT, WITH or EXPLAIN statement.
+//! A progress handler interrupts queries running past the timeout, and results are
+//! capped in rows and bytes; the remaining rows are fetched with the returned cursor.
+
+use futures::TryStreamExt;
+use sqlx::sqlite::{SqliteConnection, SqlitePool};
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::db::sqlite_row_to_json;
+
+/// SQLite VM instructions between two deadline checks.
+const PROGRESS_HANDLER_OPS: i32 = 10_000;
+
+const ALLOWED_STATEMENTS: &[&str] = &["SELECT", "WITH", "EXPLAIN"];
+
+#[derive(Debug, Clone)]
+pub struct RawSqlLimits {
+    /// Longest a query may run, including the wait for the connection
+    pub timeout: Duration,
+    /// Rows returned per page
+    pub max_rows: usize,
+    /// JSON bytes returned per page, at least one row is always returned
+    pub max_bytes: usize,
+}
+
+impl Default for RawSqlLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_rows: 10_000,
+            max_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+/// One page of results.
+#[derive(Debug)]
+pub struct RawSqlPage {
+    pub rows: Vec<serde_json::Value>,
+    /// Cursor of the next page, `None` on the last one
+    pub next_cursor: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum RawSqlError {
+    /// The statement is not a single read-only statement
+    NotAllowed(String),
+    Timeout(Duration),
+    Sqlx(sqlx::Error),
+}
+
+impl fmt::Display for RawSqlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RawSqlError::NotAllowed(reason) => write!(f, "query not allowed: {}", reason),
+            RawSqlError::Timeout(timeout) => {
+                write!(f, "query interrupted after {}s", timeout.as_secs())
+            }
+            RawSqlError::Sqlx(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl StdError for RawSqlError {}
+
+impl From<sqlx::Error> for RawSqlError {
+    fn from(e: sqlx::Error) -> Self {
+        RawSqlError::Sqlx(e)
+    }
+}
+
+/// Runs `/raw_sql` queries one at a time on a read-only connection of the main database.
+pub struct ReadOnlySql {
+    pool: SqlitePool,
+    /// Taken out of the pool on first use, so it shares the pool's settings (and the
+    /// database itself for `sqlite::memory:`)
+    conn: Mutex<Option<SqliteConnection>>,
+    limits: RawSqlLimits,
+}
+
+impl ReadOnlySql {
+    pub fn new(pool: SqlitePool, limits: RawSqlLimits) -> Self {
+        Self {
+            pool,
+            conn: Mutex::new(None),
+            limits,
+        }
+    }
+
+    pub fn limits(&self) -> &RawSqlLimits {
+        &self.limits
+    }
+
+    /// Run `sql` and return the page of rows starting at `cursor`.
+    pub async fn query(&self, sql: &str, cursor: Option<u64>) -> Result<RawSqlPage, RawSqlError> {
+        check_read_only(sql)?;
+
+        let deadline = Instant::now() + self.limits.timeout;
+        let mut guard = tokio::time::timeout(self.limits.timeout, self.conn.lock())
+            .await
+            .map_err(|_| RawSqlError::Timeout(self.limits.timeout))?;
+        if guard.is_none() {
+            let mut conn = self.pool.acquire().await?.detach();
+            sqlx::query("PRAGMA query_only = ON")
+                .execute(&mut conn)
+                .await?;
+            *guard = Some(conn);
+        }
+        let conn = guard.as_mut().expect("connection initialized above");
+
+        let timed_out = Arc::new(AtomicBool::new(false));
+        {
+            let timed_out = timed_out.clone();
+            conn.lock_handle()
+                .await?
+                .set_progress_handler(PROGRESS_HANDLER_OPS, move || {
+                    if Instant::now() < deadline {
+                        true
+                    } else {
+                        timed_out.store(true, Ordering::Relaxed);
+                        false
+                    }
+                });
+        }
+
+        let result = self.fetch_page(conn, sql, cursor.unwrap_or(0)).await;
+
+        let broken = match conn.lock_handle().await {
+            Ok(mut handle) => {
+                handle.remove_progress_handler();
+                false
+            }
+            Err(e) => {
+                warn!("dropping raw sql connection: {}", e);
+                true
+            }
+        };
+        if broken {
+            // start over with a fresh connection next time
+            *guard = None;
+        }
+
+        match result {
+            Err(_) if timed_out.load(Ordering::Relaxed) => {
+                Err(RawSqlError::Timeout(self.limits.timeout))
+            }
+            result => result,
+        }
+    }
+
+    async fn fetch_page(
+        &self,
+        conn: &mut SqliteConnection,
+        sql: &str,
+        offset: u64,
+    ) -> Result<RawSqlPage, RawSqlError> {
+        let mut rows = sqlx::query(sql).fetch(conn);
+        let mut page = Vec::new();
+        let mut bytes = 0;
+        let mut index: u64 = 0;
+
+        while let Some(row) = rows.try_next().await? {
+            index += 1;
+            if index <= offset {
+                continue;
+            }
+
+            let row = serde_json::Value::Object(sqlite_row_to_json(&row));
+            let row_bytes = row.to_string().len();
+            if page.len() >= self.limits.max_rows
+                || (!page.is_empty() && bytes + row_bytes > self.limits.max_bytes)
+            {
+                debug!("raw sql page full at row {}", index - 1);
+                return Ok(RawSqlPage {
+                    rows: page,
+                    next_cursor: Some(index - 1),
+                });
+            }
+            bytes += row_bytes;
+            page.push(row);
+        }
+
+        Ok(RawSqlPage {
+            rows: page,
+            next_cursor: None,
+        })
+    }
+}
+
+/// Accept a single SELECT, WITH or EXPLAIN statement. Writes hidden behind an allowed
+/// keyword (e.g. a CTE followed by DELETE) are stopped by `query_only`.
+pub fn check_read_only(sql: &str) -> Result<(), RawSqlError> {
+    let statements = split_statements(sql);
+    let statement = match statements.as_slice() {
+        [] => return Err(RawSqlError::NotAllowed("empty query".to_string())),
+        [statement] => statement,
+        _ => {
+            return Err(RawSqlError::NotAllowed(
+                "only a single statement is allowed".to_string(),
+            ))
+        }
+    };
+
+    let keyword: String = statement
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_ascii_uppercase();
+    if ALLOWED_STATEMENTS.contains(&keyword.as_str()) {
+        Ok(())
+    } else {
+        Err(RawSqlError::NotAllowed(format!(
+            "only {} statements are allowed",
+            ALLOWED_STATEMENTS.join(", ")
+        )))
+    }
+}
+
+/// Split `sql` on `;` outside of literals, identifiers and comments. Comments are
+/// dropped and empty statements skipped.
+fn split_statements(sql: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' | '[' => {
+                let close = if c == '[' { ']' } else { c };
+                current.push(c);
+                for c in chars.by_ref() {
+                    current.push(c);
+                    // doubled quotes are read as two quoted strings, same result
+                    if c == close {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                current.push(' ');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = '\0';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+                current.push(' ');
+            }
+            ';' => {
+                statements.push(std::mem::take(&mut current));
+            }
+            c => current.push(c),
+        }
+    }
+    statements.push(current);
+
+    statements
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_read_only() {
+        assert!(check_read_only("SELECT * FROM frames").is_ok());
+        assert!(check_read_only("  -- count\n select count(*) from ocr_text;").is_ok());
+        assert!(check_read_only("WITH t AS (SELECT 1) SELECT * FROM t").is_ok());
+        assert!(check_read_only("EXPLAIN QUERY PLAN SELECT 1").is_ok());
+        assert!(check_read_only("/* a; b */ SELECT ';' AS \"x;y\"").is_ok());
+
+        assert!(check_read_only("DELETE FROM frames").is_err());
+        assert!(check_read_only("PRAGMA query_only = OFF").is_err());
+        assert!(check_read_only("SELECT 1; DELETE FROM frames").is_err());
+        assert!(check_read_only("SELECT 1; -- x\n PRAGMA query_only = OFF").is_err());
+        assert!(check_read_only("ATTACH 'x.db' AS x").is_err());
+        assert!(check_read_only(" ; -- nothing").is_err());
+    }
+}
@@ -15,6 +15,19 @@ pub struct FrameWindowData {
     pub focused: bool,
     pub text: String,
     pub text_json: String,
+    /// Regions of the frame blurred at capture time because this window's text had PII
+    pub pii_redactions: Vec<PiiRedaction>,
+}
+
+/// A region of a frame that was blurred at capture time because it showed PII.
+#[derive(OaSchema, Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct PiiRedaction {
+    /// e.g. "EMAIL", "OPENAI_KEY"
+    pub pii_type: String,
+    pub x: i64,
+    pub y: i64,
+    pub width: i64,
+    pub height: i64,
 }
 
 #[derive(OaSchema, Debug)]
@@ -50,6 +63,24 @@ pub struct Frame {
     pub app_name: String,
     pub window_name: String,
 }
+/// Ordering of search results.
+#[derive(OaSchema, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchOrder {
+    /// Most recent first
+    #[default]
+    Time,
+    /// Most relevant first (BM25), most recent first when there is no text query
+    Relevance,
+}
+
+/// Position of a query match in a result text, in characters, `end` excluded.
+#[derive(OaSchema, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextMatch {
+    pub start: usize,
+    pub end: usize,
+}
+
 #[derive(FromRow, Debug)]
 pub struct OCRResultRaw {
     pub frame_id: i64,
@@ -66,6 +97,14 @@ pub struct OCRResultRaw {
     pub browser_url: Option<String>,
     pub focused: Option<bool>,
     pub device_name: String,
+    #[sqlx(default)]
+    pub score: Option<f64>,
+    #[sqlx(default)]
+    pub snippet: Option<String>,
+    #[sqlx(default)]
+    pub highlighted: Option<String>,
+    #[sqlx(default)]
+    pub redacted: bool,
 }
 
 #[derive(OaSchema, Debug, Serialize, Deserialize)]
@@ -84,6 +123,19 @@ pub struct OCRResult {
     pub browser_url: Option<String>,
     pub focused: Option<bool>,
     pub device_name: String,
+    /// Relevance, higher is more relevant: BM25 for text queries, cosine similarity for
+    /// embedding search
+    #[serde(default)]
+    pub score: Option<f64>,
+    /// Excerpt around the matches, matches wrapped in `<mark>`/`</mark>`
+    #[serde(default)]
+    pub snippet: Option<String>,
+    /// Matches in `ocr_text`
+    #[serde(default)]
+    pub matches: Vec<TextMatch>,
+    /// Whether PII was blurred out of the frame at capture time
+    #[serde(default)]
+    pub redacted: bool,
 }
 
 /// Content type for search queries.
@@ -156,6 +208,12 @@ pub struct AudioResultRaw {
     pub speaker_id: Option<i64>,
     pub start_time: Option<f64>,
     pub end_time: Option<f64>,
+    #[sqlx(default)]
+    pub score: Option<f64>,
+    #[sqlx(default)]
+    pub snippet: Option<String>,
+    #[sqlx(default)]
+    pub highlighted: Option<String>,
 }
 
 #[derive(OaSchema, Debug, Serialize, Deserialize, FromRow, Clone)]
@@ -185,6 +243,16 @@ pub struct AudioResult {
     pub speaker: Option<Speaker>,
     pub start_time: Option<f64>,
     pub end_time: Option<f64>,
+    /// Relevance, higher is more relevant: BM25 for text queries, cosine similarity for
+    /// embedding search
+    #[serde(default)]
+    pub score: Option<f64>,
+    /// Excerpt around the matches, matches wrapped in `<mark>`/`</mark>`
+    #[serde(default)]
+    pub snippet: Option<String>,
+    /// Matches in `transcription`
+    #[serde(default)]
+    pub matches: Vec<TextMatch>,
 }
 
 #[derive(OaSchema, Debug, Deserialize, PartialEq)]
@@ -209,6 +277,26 @@ pub struct UiContent {
     pub offset_index: i64,
     pub frame_name: Option<String>,
     pub browser_url: Option<String>,
+    /// BM25 relevance, higher is more relevant (only set for text queries)
+    #[serde(default)]
+    #[sqlx(default)]
+    pub score: Option<f64>,
+    /// Excerpt around the matches, matches wrapped in `<mark>`/`</mark>`
+    #[serde(default)]
+    #[sqlx(default)]
+    pub snippet: Option<String>,
+    /// Matches in `text`
+    #[serde(default)]
+    #[sqlx(skip)]
+    pub matches: Vec<TextMatch>,
+}
+
+#[derive(FromRow)]
+pub struct UiContentRaw {
+    #[sqlx(flatten)]
+    pub content: UiContent,
+    #[sqlx(default)]
+    pub highlighted: Option<String>,
 }
 
 #[derive(OaSchema, Debug, Clone)]
@@ -280,6 +368,101 @@ pub struct AudioChunksResponse {
     pub timestamp: DateTime<Utc>,
 }
 
+/// A video or audio chunk together with the media file it points to.
+#[derive(Debug, Clone, FromRow)]
+pub struct MediaChunkRow {
+    pub id: i64,
+    pub file_path: String,
+}
+
+/// Row counts removed by a retention pass.
+#[derive(OaSchema, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PrunedRows {
+    pub frames: u64,
+    pub ocr_text: u64,
+    pub video_chunks: u64,
+    pub audio_chunks: u64,
+    pub audio_transcriptions: u64,
+    pub ui_events: u64,
+    pub ui_monitoring: u64,
+    pub accessibility: u64,
+    pub webhook_dead_letters: u64,
+}
+
+impl PrunedRows {
+    pub fn total(&self) -> u64 {
+        self.frames
+            + self.ocr_text
+            + self.video_chunks
+            + self.audio_chunks
+            + self.audio_transcriptions
+            + self.ui_events
+            + self.ui_monitoring
+            + self.accessibility
+            + self.webhook_dead_letters
+    }
+
+    pub fn add(&mut self, other: &PrunedRows) {
+        self.frames += other.frames;
+        self.ocr_text += other.ocr_text;
+        self.video_chunks += other.video_chunks;
+        self.audio_chunks += other.audio_chunks;
+        self.audio_transcriptions += other.audio_transcriptions;
+        self.ui_events += other.ui_events;
+        self.ui_monitoring += other.ui_monitoring;
+        self.accessibility += other.accessibility;
+        self.webhook_dead_letters += other.webhook_dead_letters;
+    }
+}
+
+/// Selects the data deleted by `DatabaseManager::forget`: everything recorded between
+/// `start_time` and `end_time` whose text matches `query`. Unset bounds are open, an
+/// empty filter matches nothing.
+#[derive(Debug, Clone, Default)]
+pub struct ForgetFilter {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// FTS query matched against OCR text, transcriptions, UI events and accessibility text
+    pub query: Option<String>,
+}
+
+impl ForgetFilter {
+    pub fn is_empty(&self) -> bool {
+        self.start_time.is_none()
+            && self.end_time.is_none()
+            && self.query.as_deref().map_or(true, |q| q.trim().is_empty())
+    }
+}
+
+/// What a forget deleted.
+#[derive(Debug, Default, Clone)]
+pub struct ForgetResult {
+    pub rows: PrunedRows,
+    pub frame_ids: Vec<i64>,
+    /// Frames queued to be cut out of their video chunk
+    pub frames_to_purge: u64,
+    /// Audio ranges queued to be silenced
+    pub audio_segments_to_purge: u64,
+    /// Webhook dead letters deleted, and meetings, activity sessions and pipe runs whose
+    /// text was cleared
+    pub derived_rows: u64,
+    /// Timestamps of the deleted rows, to find the sync blobs holding them
+    pub frame_timestamps: Vec<DateTime<Utc>>,
+    pub transcription_timestamps: Vec<DateTime<Utc>>,
+    pub accessibility_timestamps: Vec<DateTime<Utc>>,
+    pub ui_event_timestamps: Vec<DateTime<Utc>>,
+}
+
+/// A range of an audio chunk to silence, `None` bounds cover the whole file.
+#[derive(Debug, Clone, FromRow)]
+pub struct PendingAudioPurge {
+    pub id: i64,
+    pub audio_chunk_id: i64,
+    pub file_path: String,
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OcrTextBlock {
     pub block_num: String,
@@ -367,6 +550,7 @@ pub enum OcrEngine {
     WindowsNative,
     AppleNative,
     Custom(CustomOcrConfig),
+    Onnx,
 }
 
 #[derive(OaSchema, Debug, Clone, Serialize, Deserialize)]
@@ -583,3 +767,162 @@ pub struct InsertUiEvent {
     pub element_bounds: Option<String>,
     pub frame_id: Option<i64>,
 }
+
+/// A webhook subscription to the event bus.
+#[derive(OaSchema, Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    /// Event names delivered, every event when empty
+    pub events: Vec<String>,
+    /// JSON path condition on the event data
+    pub predicate: Option<String>,
+    /// Key of the HMAC signatures
+    pub secret: String,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Raw row from the webhooks table
+#[derive(Debug, FromRow)]
+pub struct WebhookRow {
+    pub id: i64,
+    pub url: String,
+    pub events: String,
+    pub predicate: Option<String>,
+    pub secret: String,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<WebhookRow> for Webhook {
+    fn from(row: WebhookRow) -> Self {
+        Webhook {
+            id: row.id,
+            url: row.url,
+            events: serde_json::from_str(&row.events).unwrap_or_default(),
+            predicate: row.predicate,
+            secret: row.secret,
+            enabled: row.enabled,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// A webhook delivery that failed after every retry.
+#[derive(OaSchema, Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDeadLetter {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub event_name: String,
+    /// JSON body of the delivery
+    pub payload: String,
+    pub attempts: i64,
+    pub last_error: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A transcription segment with what's needed to place it in a transcript.
+#[derive(Debug, Clone, FromRow)]
+pub struct TranscriptRow {
+    pub id: i64,
+    pub transcription: String,
+    pub device: String,
+    pub is_input_device: bool,
+    pub speaker_id: Option<i64>,
+    pub speaker_name: Option<String>,
+    /// Start of the audio chunk the segment was cut from
+    pub chunk_timestamp: DateTime<Utc>,
+    pub timestamp: DateTime<Utc>,
+    /// Offsets of the segment in the chunk, in seconds
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+}
+
+/// A meeting, detected or recorded by hand.
+#[derive(OaSchema, Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Meeting {
+    pub id: i64,
+    pub app: String,
+    /// Window name at detection
+    pub title: Option<String>,
+    pub start_time: DateTime<Utc>,
+    /// None while the meeting is in progress
+    pub end_time: Option<DateTime<Utc>>,
+    /// `detected` or `manual`
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A speaker heard during a meeting.
+#[derive(OaSchema, Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MeetingParticipant {
+    pub speaker_id: i64,
+    pub name: Option<String>,
+    /// Transcription segments of the speaker in the meeting
+    pub segments: i64,
+}
+
+/// A frame captured during a meeting.
+#[derive(OaSchema, Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MeetingFrame {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub device_name: String,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+}
+
+/// Time spent focused on an app window, without idle time.
+#[derive(OaSchema, Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ActivitySession {
+    pub id: i64,
+    pub app_name: String,
+    pub window_name: Option<String>,
+    pub browser_url: Option<String>,
+    /// Host of `browser_url`, without `www.`
+    pub domain: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    /// `ui_events` or `frames`
+    pub source: String,
+}
+
+/// Parameters for inserting an activity session
+#[derive(Debug, Clone, PartialEq)]
+pub struct InsertActivitySession {
+    pub app_name: String,
+    pub window_name: Option<String>,
+    pub browser_url: Option<String>,
+    pub domain: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub source: String,
+}
+
+/// The focused app at some point in time, from a UI event or a frame.
+#[derive(Debug, Clone, FromRow)]
+pub struct FocusObservation {
+    pub timestamp: DateTime<Utc>,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    pub browser_url: Option<String>,
+}
+
+/// A run of a pipe cron job.
+#[derive(OaSchema, Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PipeRun {
+    pub id: i64,
+    pub pipe_id: String,
+    /// Route of the pipe called by the job
+    pub path: String,
+    /// `schedule`, `catch_up` or `manual`
+    pub source: String,
+    /// `queued`, `running`, `success` or `failed`
+    pub status: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: Option<i64>,
+    pub http_status: Option<i64>,
+    /// Response body or error, truncated
+    pub output: Option<String>,
+}
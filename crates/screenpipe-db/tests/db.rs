@@ -4,7 +4,9 @@ mod tests {
 
     use chrono::Utc;
     use screenpipe_db::{
-        AudioDevice, ContentType, DatabaseManager, DeviceType, Frame, OcrEngine, SearchResult,
+        AudioDevice, ContentType, DatabaseManager, DeviceType, EmbeddingAnnIndex, ForgetFilter,
+        Frame, FrameWindowData, InsertActivitySession, OcrEngine, PiiRedaction, RawSqlError,
+        RawSqlLimits, ReadOnlySql, SearchOrder, SearchResult,
     };
 
     async fn setup_test_db() -> DatabaseManager {
@@ -74,6 +76,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -86,6 +89,403 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_search_ocr_relevance_order() {
+        let db = setup_test_db().await;
+        let _ = db
+            .insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        for text in [
+            "invoice invoice overdue invoice",
+            "weekly notes mentioning an invoice among many other unrelated words in a long text",
+        ] {
+            let frame_id = db
+                .insert_frame(
+                    "test_device",
+                    None,
+                    None,
+                    Some("test"),
+                    Some(""),
+                    false,
+                    None,
+                )
+                .await
+                .unwrap();
+            db.insert_ocr_text(frame_id, text, "", Arc::new(OcrEngine::Tesseract))
+                .await
+                .unwrap();
+        }
+
+        let results = db
+            .search(
+                "invoice",
+                ContentType::OCR,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                SearchOrder::Relevance,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        let (SearchResult::OCR(best), SearchResult::OCR(other)) = (&results[0], &results[1]) else {
+            panic!("expected OCR results");
+        };
+        assert_eq!(best.ocr_text, "invoice invoice overdue invoice");
+        assert!(best.score.unwrap() > other.score.unwrap());
+        assert!(best
+            .snippet
+            .as_deref()
+            .unwrap()
+            .contains("<mark>invoice</mark>"));
+        assert_eq!(best.matches.len(), 3);
+        assert_eq!(
+            (best.matches[0].start, best.matches[0].end),
+            (0, "invoice".len())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_ocr_time_order_with_query() {
+        let db = setup_test_db().await;
+        let _ = db
+            .insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let now = Utc::now();
+        // the most relevant text is the oldest
+        for (text, age) in [
+            ("invoice invoice overdue invoice", 3),
+            ("an invoice among many other unrelated words", 2),
+            ("invoice sent with notes and other unrelated words", 1),
+        ] {
+            let frame_id = db
+                .insert_frame(
+                    "test_device",
+                    Some(now - chrono::Duration::minutes(age)),
+                    None,
+                    Some("test"),
+                    Some(""),
+                    false,
+                    None,
+                )
+                .await
+                .unwrap();
+            db.insert_ocr_text(frame_id, text, "", Arc::new(OcrEngine::Tesseract))
+                .await
+                .unwrap();
+        }
+
+        let results = db
+            .search(
+                "invoice",
+                ContentType::OCR,
+                2,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                SearchOrder::Time,
+            )
+            .await
+            .unwrap();
+        let texts: Vec<&str> = results
+            .iter()
+            .map(|result| match result {
+                SearchResult::OCR(ocr) => ocr.ocr_text.as_str(),
+                _ => panic!("expected OCR results"),
+            })
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "invoice sent with notes and other unrelated words",
+                "an invoice among many other unrelated words",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ocr_embedding_index_and_search() {
+        let db = setup_test_db().await;
+        let _ = db
+            .insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let mut frame_ids = Vec::new();
+        for text in ["quarterly report", "holiday photos"] {
+            let frame_id = db
+                .insert_frame(
+                    "test_device",
+                    None,
+                    None,
+                    Some("test"),
+                    Some(""),
+                    false,
+                    None,
+                )
+                .await
+                .unwrap();
+            db.insert_ocr_text(frame_id, text, "", Arc::new(OcrEngine::Tesseract))
+                .await
+                .unwrap();
+            frame_ids.push(frame_id);
+        }
+
+        let pending = db.get_ocr_text_without_embedding("test", 10).await.unwrap();
+        assert_eq!(pending.len(), 2);
+
+        // rows the model failed on are skipped for that model only
+        db.mark_embedding_failure("ocr_text", frame_ids[1], "failing", "bad input")
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_ocr_text_without_embedding("failing", 10)
+                .await
+                .unwrap(),
+            [(frame_ids[0], "quarterly report".to_string())]
+        );
+
+        db.insert_ocr_text_embeddings(
+            "test",
+            &[
+                (frame_ids[0], vec![1.0, 0.0, 0.0]),
+                (frame_ids[1], vec![0.0, 1.0, 0.0]),
+            ],
+        )
+        .await
+        .unwrap();
+        assert!(db
+            .get_ocr_text_without_embedding("test", 10)
+            .await
+            .unwrap()
+            .is_empty());
+        // other models still have to index everything
+        assert_eq!(
+            db.get_ocr_text_without_embedding("other", 10)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let results = db
+            .search_ocr_by_embedding(&[0.9, 0.1, 0.0], "test", 10, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].frame_id, frame_ids[0]);
+        assert!(results[0].score.unwrap() > results[1].score.unwrap());
+
+        let results = db
+            .search_ocr_by_embedding(&[0.9, 0.1, 0.0], "other", 10, None, None, None, None)
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_embedding_ann_index_sync_and_rebuild() {
+        let db = setup_test_db().await;
+        let _ = db
+            .insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let mut frame_ids = Vec::new();
+        for (text, embedding) in [("invoice", [1.0, 0.0, 0.0]), ("beach", [0.0, 1.0, 0.0])] {
+            let frame_id = db
+                .insert_frame(
+                    "test_device",
+                    None,
+                    None,
+                    Some("test"),
+                    Some(""),
+                    false,
+                    None,
+                )
+                .await
+                .unwrap();
+            db.insert_ocr_text(frame_id, text, "", Arc::new(OcrEngine::Tesseract))
+                .await
+                .unwrap();
+            db.insert_embeddings(frame_id, serde_json::to_string(&embedding).unwrap())
+                .await
+                .unwrap();
+            frame_ids.push(frame_id);
+        }
+
+        let dir = std::env::temp_dir().join(format!(
+            "screenpipe-ann-{}",
+            Utc::now().timestamp_nanos_opt().unwrap()
+        ));
+        let path = dir.join("ocr_embeddings.ann");
+        let index = Arc::new(EmbeddingAnnIndex::new(path.clone(), None));
+        assert!(index
+            .search(&db, &[1.0, 0.0, 0.0], 10, 0.5)
+            .await
+            .unwrap()
+            .is_none());
+
+        index.sync(&db).await.unwrap();
+        assert_eq!(index.len(), 2);
+        let results = index
+            .search(&db, &[0.9, 0.1, 0.0], 10, 0.5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].frame_id, frame_ids[0]);
+        assert_eq!(results[0].ocr_text, "invoice");
+
+        // rows inserted by another process are found before the next sync
+        let frame_id = db
+            .insert_frame(
+                "test_device",
+                None,
+                None,
+                Some("test"),
+                Some(""),
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+        db.insert_ocr_text(frame_id, "receipt", "", Arc::new(OcrEngine::Tesseract))
+            .await
+            .unwrap();
+        db.insert_embeddings(frame_id, serde_json::to_string(&[0.9, 0.0, 0.1]).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(index.len(), 2);
+        let results = index
+            .search(&db, &[1.0, 0.0, 0.1], 10, 0.5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            results.iter().map(|r| r.frame_id).collect::<Vec<_>>(),
+            [frame_id, frame_ids[0]]
+        );
+
+        // only one process maintains the files
+        assert!(EmbeddingAnnIndex::new(path.clone(), None)
+            .sync(&db)
+            .await
+            .is_err());
+
+        // rows inserted through the manager are indexed right away
+        db.attach_ocr_embedding_index(index.clone());
+        db.insert_embeddings(
+            frame_ids[1],
+            serde_json::to_string(&[0.0, 0.9, 0.1]).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(index.len(), 3);
+        index.sync(&db).await.unwrap();
+        assert_eq!(index.len(), 4);
+
+        // a corrupted file is rebuilt from the table
+        let other = dir.join("other.ann");
+        std::fs::write(&other, b"garbage").unwrap();
+        let index = EmbeddingAnnIndex::new(other, None);
+        index.sync(&db).await.unwrap();
+        assert_eq!(index.len(), 4);
+        let results = index
+            .search(&db, &[0.1, 0.9, 0.0], 10, 0.5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(results[0].frame_id, frame_ids[1]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_embedding_ann_index_forget() {
+        let db = setup_test_db().await;
+        let _ = db
+            .insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let mut frame_ids = Vec::new();
+        for (text, embedding) in [("invoice", [1.0, 0.0, 0.0]), ("receipt", [0.9, 0.1, 0.0])] {
+            let frame_id = db
+                .insert_frame(
+                    "test_device",
+                    None,
+                    None,
+                    Some("test"),
+                    Some(""),
+                    false,
+                    None,
+                )
+                .await
+                .unwrap();
+            db.insert_ocr_text(frame_id, text, "", Arc::new(OcrEngine::Tesseract))
+                .await
+                .unwrap();
+            db.insert_embeddings(frame_id, serde_json::to_string(&embedding).unwrap())
+                .await
+                .unwrap();
+            frame_ids.push(frame_id);
+        }
+
+        let dir = std::env::temp_dir().join(format!(
+            "screenpipe-ann-forget-{}",
+            Utc::now().timestamp_nanos_opt().unwrap()
+        ));
+        let index = EmbeddingAnnIndex::new(dir.join("ocr_embeddings.ann"), None);
+        index.sync(&db).await.unwrap();
+        assert_eq!(index.len(), 2);
+
+        let result = db
+            .forget(&ForgetFilter {
+                query: Some("invoice".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(result.frame_ids, [frame_ids[0]]);
+        assert_eq!(index.remove(&result.frame_ids).await.unwrap(), 1);
+        assert_eq!(index.len(), 1);
+
+        let results = index
+            .search(&db, &[1.0, 0.0, 0.0], 10, 0.5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            results.iter().map(|r| r.frame_id).collect::<Vec<_>>(),
+            [frame_ids[1]]
+        );
+        // nothing left for the next sync to rebuild or catch up with
+        index.sync(&db).await.unwrap();
+        assert_eq!(index.len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
     #[tokio::test]
     async fn test_insert_and_search_audio() {
         let db = setup_test_db().await;
@@ -123,6 +523,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -145,6 +546,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -201,6 +603,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -223,6 +626,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -319,6 +723,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -341,6 +746,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -497,14 +903,36 @@ mod tests {
 
         // After inserting both audio transcriptions, let's check all audio entries
         let all_audio = db
-            .search_audio("", 100, 0, None, None, None, None, None, None)
+            .search_audio(
+                "",
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                SearchOrder::Time,
+            )
             .await
             .unwrap();
         println!("All audio entries: {:?}", all_audio);
 
         // Then try specific search
         let audio_results = db
-            .search_audio("2", 100, 0, None, None, None, None, None, None)
+            .search_audio(
+                "2",
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                SearchOrder::Time,
+            )
             .await
             .unwrap();
         println!("Audio results for '2': {:?}", audio_results);
@@ -529,6 +957,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -554,6 +983,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -578,6 +1008,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -606,6 +1037,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -629,6 +1061,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -751,6 +1184,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -883,7 +1317,7 @@ mod tests {
         }
 
         // insert a speaker with a name
-        let speaker = db.insert_speaker(&vec![0.1; 512]).await.unwrap();
+        let speaker = db.insert_speaker(&[0.1; 512]).await.unwrap();
         db.update_speaker_name(speaker.id, "test name")
             .await
             .unwrap();
@@ -950,7 +1384,7 @@ mod tests {
         }
 
         // insert a speaker with a name
-        let speaker = db.insert_speaker(&vec![0.1; 512]).await.unwrap();
+        let speaker = db.insert_speaker(&[0.1; 512]).await.unwrap();
         db.update_speaker_name(speaker.id, "test name")
             .await
             .unwrap();
@@ -972,7 +1406,7 @@ mod tests {
     async fn test_merge_speakers() {
         let db = setup_test_db().await;
 
-        let speaker_1 = db.insert_speaker(&vec![0.1; 512]).await.unwrap();
+        let speaker_1 = db.insert_speaker(&[0.1; 512]).await.unwrap();
         db.update_speaker_name(speaker_1.id, "speaker 1")
             .await
             .unwrap();
@@ -1019,7 +1453,7 @@ mod tests {
     async fn test_search_speakers() {
         let db = setup_test_db().await;
 
-        let speaker = db.insert_speaker(&vec![0.1; 512]).await.unwrap();
+        let speaker = db.insert_speaker(&[0.1; 512]).await.unwrap();
         db.update_speaker_name(speaker.id, "test name")
             .await
             .unwrap();
@@ -1033,7 +1467,7 @@ mod tests {
     async fn test_delete_speaker() {
         let db = setup_test_db().await;
 
-        let speaker = db.insert_speaker(&vec![0.1; 512]).await.unwrap();
+        let speaker = db.insert_speaker(&[0.1; 512]).await.unwrap();
 
         let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
         db.insert_audio_transcription(
@@ -1066,7 +1500,7 @@ mod tests {
     async fn test_mark_speaker_as_hallucination() {
         let db = setup_test_db().await;
 
-        let speaker = db.insert_speaker(&vec![0.1; 512]).await.unwrap();
+        let speaker = db.insert_speaker(&[0.1; 512]).await.unwrap();
         db.mark_speaker_as_hallucination(speaker.id).await.unwrap();
 
         let speakers = db.search_speakers("").await.unwrap();
@@ -1078,7 +1512,7 @@ mod tests {
         let db = setup_test_db().await;
 
         // Create first speaker with audio data
-        let speaker = db.insert_speaker(&vec![0.1; 512]).await.unwrap();
+        let speaker = db.insert_speaker(&[0.1; 512]).await.unwrap();
         db.update_speaker_name(speaker.id, "test name")
             .await
             .unwrap();
@@ -1196,6 +1630,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -1224,6 +1659,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -1252,6 +1688,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -1328,6 +1765,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -1358,6 +1796,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -1381,6 +1820,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -1404,6 +1844,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -1542,4 +1983,907 @@ mod tests {
             .unwrap();
         assert_eq!(count, 0, "Should count zero results for non-matching query");
     }
+
+    #[tokio::test]
+    async fn test_retention_deletes_old_frames_and_empty_chunks() {
+        let db = setup_test_db().await;
+        let old = Utc::now() - chrono::Duration::days(40);
+
+        db.insert_video_chunk("old_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let old_frame = db
+            .insert_frame(
+                "test_device",
+                Some(old),
+                None,
+                Some("app"),
+                Some(""),
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+        db.insert_ocr_text(
+            old_frame,
+            "ancient text",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+        )
+        .await
+        .unwrap();
+
+        db.insert_video_chunk("new_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let new_frame = db
+            .insert_frame(
+                "test_device",
+                None,
+                None,
+                Some("app"),
+                Some(""),
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+        db.insert_ocr_text(new_frame, "fresh text", "", Arc::new(OcrEngine::Tesseract))
+            .await
+            .unwrap();
+
+        // the old chunk is the only one eligible for media pruning
+        let cutoff = Utc::now() - chrono::Duration::days(30);
+        let chunks = db
+            .list_video_chunks_for_retention(Some(cutoff), None)
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].file_path, "old_video.mp4");
+
+        let (pruned, removed) = db.delete_frames_before(cutoff, 1000).await.unwrap();
+        assert_eq!(pruned.frames, 1);
+        assert_eq!(pruned.ocr_text, 1);
+        assert_eq!(pruned.video_chunks, 1);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].file_path, "old_video.mp4");
+
+        // fts must not return the deleted text anymore
+        let fts_rows: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM ocr_text_fts WHERE ocr_text_fts MATCH 'ancient'",
+        )
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(fts_rows, 0);
+
+        let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM frames")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, 1);
+    }
+
+    #[tokio::test]
+    async fn test_retention_audio_media_and_rows() {
+        let db = setup_test_db().await;
+        let device = AudioDevice {
+            name: "test".to_string(),
+            device_type: DeviceType::Input,
+        };
+        let chunk_id = db.insert_audio_chunk("old_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(
+            chunk_id,
+            "old meeting notes",
+            0,
+            "",
+            &device,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        sqlx::query("UPDATE audio_chunks SET timestamp = ?1 WHERE id = ?2")
+            .bind(Utc::now() - chrono::Duration::days(10))
+            .bind(chunk_id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        db.insert_audio_chunk("new_audio.mp4").await.unwrap();
+
+        let cutoff = Utc::now() - chrono::Duration::days(7);
+        let chunks = db
+            .list_audio_chunks_for_retention(Some(cutoff), None)
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+
+        // pruning the media keeps the transcription searchable
+        db.mark_audio_chunks_media_pruned(&[chunk_id])
+            .await
+            .unwrap();
+        assert!(db
+            .list_audio_chunks_for_retention(Some(cutoff), None)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(db.count_audio_transcriptions(chunk_id).await.unwrap(), 1);
+
+        let (pruned, removed) = db.delete_audio_chunks_before(cutoff, 1000).await.unwrap();
+        assert_eq!(pruned.audio_chunks, 1);
+        assert_eq!(pruned.audio_transcriptions, 1);
+        assert_eq!(removed[0].file_path, "old_audio.mp4");
+        assert_eq!(db.count_audio_transcriptions(chunk_id).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_replace_downsampled_video_chunk_remaps_offsets() {
+        let db = setup_test_db().await;
+        let old = Utc::now() - chrono::Duration::days(10);
+
+        let chunk_id = db
+            .insert_video_chunk("full.mp4", "test_device")
+            .await
+            .unwrap();
+        let mut frame_ids = Vec::new();
+        for offset in 0..4 {
+            let frame_id = db
+                .insert_frame(
+                    "test_device",
+                    Some(old),
+                    None,
+                    Some("app"),
+                    Some(""),
+                    false,
+                    Some(offset),
+                )
+                .await
+                .unwrap();
+            frame_ids.push(frame_id);
+        }
+        // a newer chunk so the old one isn't considered in-progress
+        db.insert_video_chunk("current.mp4", "test_device")
+            .await
+            .unwrap();
+
+        let chunks = db
+            .list_video_chunks_for_downsampling(Utc::now() - chrono::Duration::days(7), 10)
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(
+            db.get_video_chunk_offsets(chunk_id).await.unwrap(),
+            vec![0, 1, 2, 3]
+        );
+
+        db.replace_downsampled_video_chunk(
+            chunk_id,
+            "small.mp4",
+            &[(0, 0), (1, 0), (2, 1), (3, 1)],
+        )
+        .await
+        .unwrap();
+
+        let (path, offset) = db.get_frame(frame_ids[2]).await.unwrap().unwrap();
+        assert_eq!(path, "small.mp4");
+        assert_eq!(offset, 1);
+        let (_, offset) = db.get_frame(frame_ids[1]).await.unwrap().unwrap();
+        assert_eq!(offset, 0);
+
+        assert!(db
+            .list_video_chunks_for_downsampling(Utc::now() - chrono::Duration::days(7), 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_raw_sql_read_only_and_paging() {
+        let db = setup_test_db().await;
+        for i in 0..5 {
+            db.insert_video_chunk(&format!("video_{}.mp4", i), "test_device")
+                .await
+                .unwrap();
+        }
+
+        let raw_sql = ReadOnlySql::new(
+            db.pool.clone(),
+            RawSqlLimits {
+                max_rows: 2,
+                ..Default::default()
+            },
+        );
+        let query = "SELECT file_path FROM video_chunks ORDER BY id";
+
+        let mut paths = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = raw_sql.query(query, cursor).await.unwrap();
+            assert!(page.rows.len() <= 2);
+            paths.extend(page.rows.iter().map(|r| r["file_path"].clone()));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(paths.len(), 5);
+        assert_eq!(paths[4], "video_4.mp4");
+
+        assert!(matches!(
+            raw_sql.query("DELETE FROM video_chunks", None).await,
+            Err(RawSqlError::NotAllowed(_))
+        ));
+        // passes the keyword check, stopped by query_only
+        assert!(matches!(
+            raw_sql
+                .query("WITH t AS (SELECT 1) DELETE FROM video_chunks", None)
+                .await,
+            Err(RawSqlError::Sqlx(_))
+        ));
+
+        let page = raw_sql
+            .query("SELECT COUNT(*) AS n FROM video_chunks", None)
+            .await
+            .unwrap();
+        assert_eq!(page.rows[0]["n"], 5);
+
+        let deleted = db
+            .execute_raw_sql_write("DELETE FROM video_chunks WHERE file_path = 'video_0.mp4'")
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+    }
+
+    #[tokio::test]
+    async fn test_webhooks_and_dead_letters() {
+        let db = setup_test_db().await;
+
+        let events = vec!["meeting_started".to_string()];
+        let id = db
+            .insert_webhook("http://localhost:9000/hook", &events, None, "secret")
+            .await
+            .unwrap();
+        let webhook = db.get_webhook(id).await.unwrap().unwrap();
+        assert_eq!(webhook.events, events);
+        assert!(webhook.enabled);
+
+        assert!(db
+            .update_webhook(id, &webhook.url, &[], Some("$.app_name"), false)
+            .await
+            .unwrap());
+        let webhook = db.get_webhook(id).await.unwrap().unwrap();
+        assert!(webhook.events.is_empty());
+        assert_eq!(webhook.predicate.as_deref(), Some("$.app_name"));
+        assert!(!webhook.enabled);
+
+        db.insert_webhook_dead_letter(id, "meeting_started", "{}", 5, "http status 500")
+            .await
+            .unwrap();
+        assert_eq!(
+            db.list_webhook_dead_letters(Some(id), 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        assert_eq!(
+            db.delete_webhook_dead_letters_before(Utc::now() - chrono::Duration::days(1))
+                .await
+                .unwrap(),
+            0
+        );
+        let letter_id = db
+            .insert_webhook_dead_letter(id, "meeting_started", "{}", 0, "1 event dropped")
+            .await
+            .unwrap();
+        assert!(db
+            .update_webhook_dead_letter(letter_id, "meeting_ended", "{\"a\":1}", "2 events dropped")
+            .await
+            .unwrap());
+        let letter = db
+            .get_webhook_dead_letter(letter_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(letter.event_name, "meeting_ended");
+        assert_eq!(letter.payload, "{\"a\":1}");
+        assert_eq!(letter.last_error, "2 events dropped");
+        assert_eq!(
+            db.delete_webhook_dead_letters_before(Utc::now() + chrono::Duration::seconds(1))
+                .await
+                .unwrap(),
+            2
+        );
+        assert!(!db
+            .update_webhook_dead_letter(letter_id, "meeting_ended", "{}", "3 events dropped")
+            .await
+            .unwrap());
+        db.insert_webhook_dead_letter(id, "meeting_started", "{}", 5, "http status 500")
+            .await
+            .unwrap();
+
+        // dead letters go with their webhook
+        assert!(db.delete_webhook(id).await.unwrap());
+        assert!(db.get_webhook(id).await.unwrap().is_none());
+        assert!(db
+            .list_webhook_dead_letters(None, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(!db.delete_webhook(id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_get_transcript_rows() {
+        let db = setup_test_db().await;
+        let recorded_at = Utc::now() - chrono::Duration::hours(1);
+        let chunk_id = db
+            .insert_audio_chunk_at("meeting.m4a", recorded_at)
+            .await
+            .unwrap();
+        let mic = AudioDevice {
+            name: "mic".to_string(),
+            device_type: DeviceType::Input,
+        };
+        let speakers = AudioDevice {
+            name: "speakers".to_string(),
+            device_type: DeviceType::Output,
+        };
+        for (text, device, start) in [
+            ("second", &mic, 5.0),
+            ("first", &mic, 1.0),
+            ("other device", &speakers, 3.0),
+        ] {
+            db.insert_audio_transcription_at(
+                chunk_id,
+                text,
+                0,
+                "",
+                device,
+                None,
+                Some(start),
+                Some(start + 1.0),
+                recorded_at + chrono::Duration::seconds(start as i64),
+            )
+            .await
+            .unwrap();
+        }
+
+        let rows = db
+            .get_transcript_rows(None, None, Some("mic"), &[], None, 100)
+            .await
+            .unwrap();
+        let texts: Vec<_> = rows.iter().map(|r| r.transcription.as_str()).collect();
+        assert_eq!(texts, ["first", "second"]);
+        assert_eq!(rows[0].chunk_timestamp, recorded_at);
+        assert_eq!(rows[0].start_time, Some(1.0));
+
+        let rows = db
+            .get_transcript_rows(Some(Utc::now()), None, None, &[], None, 100)
+            .await
+            .unwrap();
+        assert!(rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_meetings() {
+        let db = setup_test_db().await;
+        let start = Utc::now() - chrono::Duration::hours(2);
+
+        let id = db
+            .insert_meeting("zoom.us", Some("Weekly sync"), start, None, "detected")
+            .await
+            .unwrap();
+        assert_eq!(db.get_open_meeting().await.unwrap().unwrap().id, id);
+
+        // transcriptions during the meeting make its participants
+        let speaker = db.insert_speaker(&[0.1; 512]).await.unwrap();
+        let chunk_id = db.insert_audio_chunk_at("sync.m4a", start).await.unwrap();
+        let mic = AudioDevice {
+            name: "mic".to_string(),
+            device_type: DeviceType::Input,
+        };
+        for (text, minutes) in [("hello", 10), ("bye", 50), ("after", 90)] {
+            db.insert_audio_transcription_at(
+                chunk_id,
+                text,
+                0,
+                "",
+                &mic,
+                Some(speaker.id),
+                None,
+                None,
+                start + chrono::Duration::minutes(minutes),
+            )
+            .await
+            .unwrap();
+        }
+
+        let end = start + chrono::Duration::hours(1);
+        assert!(db.end_meeting(id, end).await.unwrap());
+        assert!(!db.end_meeting(id, end).await.unwrap());
+        assert!(db.get_open_meeting().await.unwrap().is_none());
+        let participants = db.get_meeting_participants(start, end).await.unwrap();
+        assert_eq!(participants.len(), 1);
+        assert_eq!(participants[0].speaker_id, speaker.id);
+        assert_eq!(participants[0].segments, 2);
+
+        // split then merge back
+        let at = start + chrono::Duration::minutes(30);
+        let second = db.split_meeting(id, at).await.unwrap().unwrap();
+        assert!(db.split_meeting(id, end).await.unwrap().is_none());
+        assert_eq!(
+            db.get_meeting(id).await.unwrap().unwrap().end_time,
+            Some(at)
+        );
+        let second_meeting = db.get_meeting(second).await.unwrap().unwrap();
+        assert_eq!(second_meeting.start_time, at);
+        assert_eq!(second_meeting.end_time, Some(end));
+        assert_eq!(second_meeting.title.as_deref(), Some("Weekly sync"));
+
+        let listed = db
+            .list_meetings(Some(at + chrono::Duration::minutes(1)), None, None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, second);
+
+        let merged = db.merge_meetings(&[second, id]).await.unwrap().unwrap();
+        assert_eq!(merged.id, id);
+        assert_eq!(merged.end_time, Some(end));
+        assert!(db.get_meeting(second).await.unwrap().is_none());
+        assert!(db.merge_meetings(&[id, second]).await.unwrap().is_none());
+
+        // detected meetings left open by a crash are ended, manual ones aren't
+        let manual = db
+            .insert_meeting("", None, Utc::now(), None, "manual")
+            .await
+            .unwrap();
+        let crashed = db
+            .insert_meeting("teams", None, start, None, "detected")
+            .await
+            .unwrap();
+        assert_eq!(db.end_interrupted_meetings().await.unwrap(), 1);
+        assert!(db
+            .get_meeting(crashed)
+            .await
+            .unwrap()
+            .unwrap()
+            .end_time
+            .is_some());
+        assert_eq!(db.get_open_meeting().await.unwrap().unwrap().id, manual);
+
+        assert!(db.delete_meeting(id).await.unwrap());
+        assert!(!db.delete_meeting(id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_activity_sessions() {
+        let db = setup_test_db().await;
+        let start = Utc::now() - chrono::Duration::hours(1);
+        let minutes = |m| start + chrono::Duration::minutes(m);
+        db.insert_video_chunk("activity.mp4", "monitor")
+            .await
+            .unwrap();
+        for (m, app, focused) in [
+            (0, "Code", true),
+            (10, "Arc", true),
+            (11, "Slack", false),
+            (20, "Code", true),
+        ] {
+            db.insert_frame(
+                "monitor",
+                Some(minutes(m)),
+                None,
+                Some(app),
+                None,
+                focused,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+
+        // the focused frame before the range comes first
+        let focus = db.get_frame_focus(minutes(5), minutes(30)).await.unwrap();
+        let apps: Vec<_> = focus.iter().filter_map(|f| f.app_name.as_deref()).collect();
+        assert_eq!(apps, ["Code", "Arc", "Code"]);
+        assert!(db
+            .get_focus_events(minutes(5), minutes(30))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let session = |app: &str, from, to| InsertActivitySession {
+            app_name: app.to_string(),
+            window_name: None,
+            browser_url: None,
+            domain: None,
+            start_time: minutes(from),
+            end_time: minutes(to),
+            source: "frames".to_string(),
+        };
+        db.replace_activity_sessions(
+            minutes(0),
+            &[session("Code", 0, 10), session("Arc", 10, 20)],
+        )
+        .await
+        .unwrap();
+        // sessions from the start of the last one are replaced
+        db.replace_activity_sessions(
+            minutes(10),
+            &[session("Arc", 10, 20), session("Code", 20, 25)],
+        )
+        .await
+        .unwrap();
+
+        let sessions = db
+            .get_activity_sessions(minutes(5), minutes(30), None)
+            .await
+            .unwrap();
+        let apps: Vec<_> = sessions.iter().map(|s| s.app_name.as_str()).collect();
+        assert_eq!(apps, ["Code", "Arc", "Code"]);
+        assert_eq!(
+            db.get_last_activity_session()
+                .await
+                .unwrap()
+                .unwrap()
+                .end_time,
+            minutes(25)
+        );
+        assert_eq!(
+            db.get_activity_sessions(minutes(0), minutes(30), Some("arc"))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pii_redactions_reported_in_search() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "monitor_1")
+            .await
+            .unwrap();
+        let redaction = PiiRedaction {
+            pii_type: "OPENAI_KEY".to_string(),
+            x: 10,
+            y: 20,
+            width: 300,
+            height: 18,
+        };
+        let window = |app: &str, text: &str, pii_redactions: Vec<PiiRedaction>| FrameWindowData {
+            app_name: Some(app.to_string()),
+            window_name: None,
+            browser_url: None,
+            focused: true,
+            text: text.to_string(),
+            text_json: String::new(),
+            pii_redactions,
+        };
+        let inserted = db
+            .insert_frames_with_ocr_batch(
+                "monitor_1",
+                None,
+                0,
+                &[
+                    window(
+                        "Terminal",
+                        "export KEY=[OPENAI_KEY]",
+                        vec![redaction.clone()],
+                    ),
+                    window("Notes", "shopping list", Vec::new()),
+                ],
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+        let (terminal_frame, _) = inserted[0];
+
+        assert_eq!(
+            db.get_frame_pii_redactions(terminal_frame).await.unwrap(),
+            [redaction]
+        );
+
+        let results = db
+            .search(
+                "",
+                ContentType::OCR,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                SearchOrder::Time,
+            )
+            .await
+            .unwrap();
+        let mut redacted: Vec<_> = results
+            .iter()
+            .map(|r| match r {
+                SearchResult::OCR(ocr) => (ocr.app_name.as_str(), ocr.redacted),
+                _ => panic!("Expected OCR result"),
+            })
+            .collect();
+        redacted.sort();
+        assert_eq!(redacted, [("Notes", false), ("Terminal", true)]);
+    }
+
+    #[tokio::test]
+    async fn test_forget_deletes_matches_and_queues_media_purges() {
+        let db = setup_test_db().await;
+        let chunk_id = db
+            .insert_video_chunk("screen.mp4", "test_device")
+            .await
+            .unwrap();
+        let mut frame_ids = Vec::new();
+        for (offset, text) in [
+            (0, "inbox"),
+            (1, "password hunter2"),
+            (1, "terminal"),
+            (2, "calendar"),
+        ] {
+            let frame_id = db
+                .insert_frame(
+                    "test_device",
+                    None,
+                    None,
+                    Some("app"),
+                    Some(""),
+                    false,
+                    Some(offset),
+                )
+                .await
+                .unwrap();
+            db.insert_ocr_text(frame_id, text, "", Arc::new(OcrEngine::Tesseract))
+                .await
+                .unwrap();
+            frame_ids.push(frame_id);
+        }
+        db.insert_video_chunk("current.mp4", "test_device")
+            .await
+            .unwrap();
+
+        let device = AudioDevice {
+            name: "mic".to_string(),
+            device_type: DeviceType::Input,
+        };
+        let audio_chunk_id = db.insert_audio_chunk("audio.mp4").await.unwrap();
+        for (text, start) in [("my password is hunter2", 0.0), ("buy groceries", 5.0)] {
+            db.insert_audio_transcription(
+                audio_chunk_id,
+                text,
+                0,
+                "",
+                &device,
+                None,
+                Some(start),
+                Some(start + 4.0),
+            )
+            .await
+            .unwrap();
+        }
+
+        // copies of the text kept by other features
+        let webhook_id = db
+            .insert_webhook("http://localhost:9000/hook", &[], None, "secret")
+            .await
+            .unwrap();
+        for payload in [r#"{"text":"Password Hunter2"}"#, r#"{"text":"inbox"}"#] {
+            db.insert_webhook_dead_letter(webhook_id, "ocr", payload, 5, "http status 500")
+                .await
+                .unwrap();
+        }
+        for output in ["found hunter2 on screen", "nothing found"] {
+            let run = db
+                .insert_pipe_run("digest", "/api/digest", "manual")
+                .await
+                .unwrap();
+            db.finish_pipe_run(run, "success", 10, Some(200), Some(output))
+                .await
+                .unwrap();
+        }
+        let now = Utc::now();
+        let meeting_id = db
+            .insert_meeting("zoom.us", Some("hunter2 rotation"), now, None, "detected")
+            .await
+            .unwrap();
+        let sessions: Vec<InsertActivitySession> = ["vault - hunter2", "calendar"]
+            .into_iter()
+            .map(|window| InsertActivitySession {
+                app_name: "app".to_string(),
+                window_name: Some(window.to_string()),
+                browser_url: None,
+                domain: None,
+                start_time: now - chrono::Duration::minutes(1),
+                end_time: now,
+                source: "frames".to_string(),
+            })
+            .collect();
+        db.replace_activity_sessions(now - chrono::Duration::minutes(1), &sessions)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            db.forget(&ForgetFilter::default())
+                .await
+                .unwrap()
+                .rows
+                .total(),
+            0
+        );
+
+        let result = db
+            .forget(&ForgetFilter {
+                query: Some("hunter2".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        // both frames at offset 1 show the password
+        assert_eq!(result.rows.frames, 2);
+        assert_eq!(result.rows.ocr_text, 2);
+        assert_eq!(result.rows.audio_transcriptions, 1);
+        assert_eq!(result.frame_ids, vec![frame_ids[1], frame_ids[2]]);
+        assert_eq!(result.frames_to_purge, 1);
+        assert_eq!(result.audio_segments_to_purge, 1);
+        // a dead letter deleted, a pipe output, a meeting title and a session window cleared
+        assert_eq!(result.derived_rows, 4);
+
+        let dead_letters = db
+            .list_webhook_dead_letters(Some(webhook_id), 10)
+            .await
+            .unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].payload, r#"{"text":"inbox"}"#);
+        let mut outputs: Vec<Option<String>> = db
+            .list_pipe_runs("digest", None, 10, 0)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|run| run.output)
+            .collect();
+        outputs.sort();
+        assert_eq!(outputs, [None, Some("nothing found".to_string())]);
+        let meeting = db.get_meeting(meeting_id).await.unwrap().unwrap();
+        assert_eq!(meeting.title, None);
+        let mut windows: Vec<Option<String>> = db
+            .get_activity_sessions(now - chrono::Duration::hours(1), now, None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|session| session.window_name)
+            .collect();
+        windows.sort();
+        assert_eq!(windows, [None, Some("calendar".to_string())]);
+
+        for (table, query, expected) in [
+            ("ocr_text_fts", "hunter2", 0),
+            ("ocr_text_fts", "calendar", 1),
+            ("audio_transcriptions_fts", "hunter2", 0),
+            // the other transcription of the chunk is still indexed
+            ("audio_transcriptions_fts", "groceries", 1),
+        ] {
+            let count: i64 = sqlx::query_scalar(&format!(
+                "SELECT COUNT(*) FROM {table} WHERE {table} MATCH ?1"
+            ))
+            .bind(query)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+            assert_eq!(count, expected, "{} {}", table, query);
+        }
+
+        let chunks = db.list_video_chunks_to_purge(10).await.unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].id, chunk_id);
+        assert_eq!(db.get_pending_frame_purges(chunk_id).await.unwrap(), [1]);
+        assert!(!db.finish_video_chunk_purge(chunk_id, &[1]).await.unwrap());
+        let (_, offset) = db.get_frame(frame_ids[3]).await.unwrap().unwrap();
+        assert_eq!(offset, 1);
+        assert!(db.list_video_chunks_to_purge(10).await.unwrap().is_empty());
+
+        let audio = db.list_pending_audio_purges(10).await.unwrap();
+        assert_eq!(audio.len(), 1);
+        assert_eq!(audio[0].file_path, "audio.mp4");
+        assert_eq!(
+            (audio[0].start_time, audio[0].end_time),
+            (Some(0.0), Some(4.0))
+        );
+        db.delete_pending_audio_purges(&[audio[0].id])
+            .await
+            .unwrap();
+        assert!(db.list_pending_audio_purges(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pipe_runs() {
+        let db = setup_test_db().await;
+
+        let scheduled = db
+            .insert_pipe_run("obsidian", "/api/log", "schedule")
+            .await
+            .unwrap();
+        db.start_pipe_run(scheduled).await.unwrap();
+        db.finish_pipe_run(scheduled, "success", 1200, Some(200), Some("ok"))
+            .await
+            .unwrap();
+        let manual = db
+            .insert_pipe_run("obsidian", "/api/log", "manual")
+            .await
+            .unwrap();
+        db.insert_pipe_run("obsidian", "/api/digest", "catch_up")
+            .await
+            .unwrap();
+
+        let runs = db
+            .list_pipe_runs("obsidian", Some("/api/log"), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            runs.iter().map(|run| run.id).collect::<Vec<_>>(),
+            vec![manual, scheduled]
+        );
+        assert_eq!(runs[1].status, "success");
+        assert_eq!(runs[1].http_status, Some(200));
+
+        // manual runs don't move the schedule
+        assert_eq!(
+            db.last_scheduled_pipe_run("obsidian", "/api/log")
+                .await
+                .unwrap(),
+            Some(runs[1].started_at)
+        );
+        assert_eq!(
+            db.last_scheduled_pipe_run("obsidian", "/api/other")
+                .await
+                .unwrap(),
+            None
+        );
+
+        assert_eq!(db.fail_interrupted_pipe_runs().await.unwrap(), 2);
+        let runs = db.list_pipe_runs("obsidian", None, 10, 0).await.unwrap();
+        assert!(runs.iter().all(|run| run.status != "queued"));
+    }
+
+    #[cfg(not(feature = "sqlcipher"))]
+    #[tokio::test]
+    async fn test_new_with_key_requires_sqlcipher() {
+        let path =
+            std::env::temp_dir().join(format!("screenpipe-key-test-{}.sqlite", std::process::id()));
+        let key = format!("\"x'{}'\"", "ab".repeat(32));
+
+        let result = DatabaseManager::new_with_key(&path.to_string_lossy(), Some(&key)).await;
+        assert!(matches!(result, Err(sqlx::Error::Configuration(_))));
+        let _ = std::fs::remove_file(&path);
+
+        // an existing plaintext database is left untouched
+        let path = std::env::temp_dir().join(format!(
+            "screenpipe-key-test-plaintext-{}.sqlite",
+            std::process::id()
+        ));
+        let path_str = path.to_string_lossy().to_string();
+        let db = DatabaseManager::new(&path_str).await.unwrap();
+        db.pool.close().await;
+        let before = std::fs::read(&path).unwrap();
+
+        let result = DatabaseManager::new_with_key(&path_str, Some(&key)).await;
+        assert!(matches!(result, Err(sqlx::Error::Configuration(_))));
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+        assert!(!std::path::Path::new(&format!("{}.encrypting", path_str)).exists());
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path_str, suffix));
+        }
+    }
 }
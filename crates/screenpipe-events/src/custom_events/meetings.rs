@@ -63,6 +63,7 @@ pub async fn poll_meetings_events() -> Result<()> {
                         "meeting_started",
                         MeetingEvent {
                             app: ui_frame.app.clone(),
+                            title: Some(ui_frame.window.clone()),
                             timestamp: Utc::now(),
                         },
                     )?;
@@ -80,6 +81,7 @@ pub async fn poll_meetings_events() -> Result<()> {
                         "meeting_ended",
                         MeetingEvent {
                             app: ui_frame.app.clone(),
+                            title: None,
                             timestamp: Utc::now(),
                         },
                     )?;
@@ -119,6 +121,8 @@ pub async fn poll_meetings_events() -> Result<()> {
                         "meeting_started",
                         MeetingEvent {
                             app: window_ocr.app_name.clone(),
+                            title: (!window_ocr.window_name.is_empty())
+                                .then(|| window_ocr.window_name.clone()),
                             timestamp: Utc::now(),
                         },
                     )?;
@@ -136,6 +140,7 @@ pub async fn poll_meetings_events() -> Result<()> {
                         "meeting_ended",
                         MeetingEvent {
                             app: window_ocr.app_name.clone(),
+                            title: None,
                             timestamp: Utc::now(),
                         },
                     )?;
@@ -159,6 +164,7 @@ pub async fn poll_meetings_events() -> Result<()> {
                             "meeting_started",
                             MeetingEvent {
                                 app: "Unknown (detected via audio)".to_string(),
+                                title: None,
                                 timestamp: Utc::now(),
                             },
                         )?;
@@ -192,6 +198,7 @@ pub async fn poll_meetings_events() -> Result<()> {
                         "meeting_ended",
                         MeetingEvent {
                             app: "Unknown (detected via audio)".to_string(),
+                            title: None,
                             timestamp: Utc::now(),
                         },
                     )?;
@@ -203,10 +210,14 @@ pub async fn poll_meetings_events() -> Result<()> {
     Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct MeetingEvent {
-    app: String,
-    timestamp: DateTime<Utc>,
+/// Payload of the `meeting_started` and `meeting_ended` events.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeetingEvent {
+    pub app: String,
+    /// Window name of the meeting, when detected from the screen
+    #[serde(default)]
+    pub title: Option<String>,
+    pub timestamp: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
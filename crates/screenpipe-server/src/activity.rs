@@ -0,0 +1,661 @@
+//! App usage (`/activity/summary`)
+//!
+//! Derives focused-app sessions into the `activity_sessions` table: app switch and
+//! window focus UI events give the focused window when they were recorded, frames are
+//! sampled otherwise. Idle time is cut out of the sessions, it is read from the activity
+//! feed when adaptive FPS runs, and from gaps in UI events and frames. The summary
+//! endpoint sums the sessions per app, domain or window, per hour or day.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use screenpipe_db::{ActivitySession, DatabaseManager, FocusObservation, InsertActivitySession};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, error};
+
+use crate::server::AppState;
+
+pub const SOURCE_UI_EVENTS: &str = "ui_events";
+pub const SOURCE_FRAMES: &str = "frames";
+
+const PROCESS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Recent data is left for the next run, frames and UI events are written late.
+const PROCESS_LAG_SECS: i64 = 30;
+
+/// Without UI events, frames or activity for this long, the user is idle.
+const IDLE_GAP_SECS: i64 = 180;
+
+/// History processed on the first run.
+const BACKFILL_DAYS: i64 = 7;
+
+/// History read per query.
+const BATCH_HOURS: i64 = 6;
+
+/// Shorter sessions are dropped.
+const MIN_SESSION_MS: i64 = 1000;
+
+/// A period without input. `end` is None while still idle.
+#[derive(Debug, Clone, Copy)]
+struct IdlePeriod {
+    start: DateTime<Utc>,
+    end: Option<DateTime<Utc>>,
+}
+
+/// Keeps `activity_sessions` up to date.
+pub struct ActivityTracker {
+    db: Arc<DatabaseManager>,
+    #[cfg(feature = "adaptive-fps")]
+    activity_feed: screenpipe_vision::ActivityFeedOption,
+    /// Idle periods seen on the activity feed
+    idle: Mutex<Vec<IdlePeriod>>,
+    /// End of the range processed by the last run
+    processed_until: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl ActivityTracker {
+    pub fn new(db: Arc<DatabaseManager>) -> Self {
+        Self {
+            db,
+            #[cfg(feature = "adaptive-fps")]
+            activity_feed: None,
+            idle: Mutex::new(Vec::new()),
+            processed_until: Mutex::new(None),
+        }
+    }
+
+    /// Read idle periods from the activity feed
+    #[cfg(feature = "adaptive-fps")]
+    pub fn with_activity_feed(
+        mut self,
+        activity_feed: screenpipe_vision::ActivityFeedOption,
+    ) -> Self {
+        self.activity_feed = activity_feed;
+        self
+    }
+
+    /// Derive sessions every minute, forever.
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        #[cfg(feature = "adaptive-fps")]
+        if self.activity_feed.is_some() {
+            let tracker = self.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(10));
+                loop {
+                    interval.tick().await;
+                    tracker.sample_idle();
+                }
+            });
+        }
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PROCESS_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.process().await {
+                    error!("failed to derive activity sessions: {}", e);
+                }
+            }
+        })
+    }
+
+    #[cfg(feature = "adaptive-fps")]
+    fn sample_idle(&self) {
+        let Some(feed) = &self.activity_feed else {
+            return;
+        };
+        let idle_ms = feed.idle_ms() as i64;
+        let last_activity = Utc::now() - Duration::milliseconds(idle_ms);
+        let mut periods = self.idle.lock().unwrap();
+        let open = periods.last_mut().filter(|p| p.end.is_none());
+        match open {
+            Some(period) if idle_ms < IDLE_GAP_SECS * 1000 => period.end = Some(last_activity),
+            None if idle_ms >= IDLE_GAP_SECS * 1000 => periods.push(IdlePeriod {
+                start: last_activity,
+                end: None,
+            }),
+            _ => {}
+        }
+        let horizon = Utc::now() - Duration::days(BACKFILL_DAYS);
+        periods.retain(|p| p.end.map_or(true, |end| end > horizon));
+    }
+
+    /// Re-derive the sessions from the start of the last one, which may have continued,
+    /// or from a bit before the last run when it had ended.
+    async fn process(&self) -> Result<(), sqlx::Error> {
+        let until = Utc::now() - Duration::seconds(PROCESS_LAG_SECS);
+        let last = self.db.get_last_activity_session().await?;
+        let processed_until = *self.processed_until.lock().unwrap();
+        let resume = processed_until.map(|t| t - Duration::seconds(IDLE_GAP_SECS));
+        let last = last.filter(|session| resume.map_or(true, |resume| session.end_time >= resume));
+        let from = match (&last, resume) {
+            (Some(session), _) => session.start_time,
+            (None, Some(resume)) => resume,
+            (None, None) => until - Duration::days(BACKFILL_DAYS),
+        };
+        if from >= until {
+            return Ok(());
+        }
+
+        let feed_idle: Vec<(DateTime<Utc>, DateTime<Utc>)> = self
+            .idle
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|p| (p.start, p.end.unwrap_or(until)))
+            .collect();
+
+        // the last session was active when it started
+        let mut last_activity = last.map(|session| session.start_time);
+        let mut sessions: Vec<InsertActivitySession> = Vec::new();
+        let mut batch_start = from;
+        while batch_start < until {
+            let batch_end = (batch_start + Duration::hours(BATCH_HOURS)).min(until);
+            let focus_events = self.db.get_focus_events(batch_start, batch_end).await?;
+            let frames = self.db.get_frame_focus(batch_start, batch_end).await?;
+            let ui_event_times = self.db.get_ui_event_times(batch_start, batch_end).await?;
+
+            let (observations, source) = if focus_events.iter().any(|e| e.timestamp >= batch_start)
+            {
+                (&focus_events, SOURCE_UI_EVENTS)
+            } else {
+                (&frames, SOURCE_FRAMES)
+            };
+            let mut activity: Vec<DateTime<Utc>> = last_activity
+                .into_iter()
+                .chain(frames.iter().map(|f| f.timestamp))
+                .chain(focus_events.iter().map(|e| e.timestamp))
+                .chain(ui_event_times)
+                .collect();
+            activity.sort();
+            last_activity = activity.last().copied().or(last_activity);
+
+            for session in sessionize(
+                observations,
+                &activity,
+                &feed_idle,
+                batch_start,
+                batch_end,
+                source,
+            ) {
+                // sessions going on across batches are joined
+                match sessions.last_mut() {
+                    Some(previous)
+                        if previous.end_time == session.start_time
+                            && same_focus(previous, &session) =>
+                    {
+                        previous.end_time = session.end_time
+                    }
+                    _ => sessions.push(session),
+                }
+            }
+            batch_start = batch_end;
+        }
+
+        debug!("{} activity sessions since {}", sessions.len(), from);
+        self.db.replace_activity_sessions(from, &sessions).await?;
+        *self.processed_until.lock().unwrap() = Some(until);
+        Ok(())
+    }
+}
+
+fn same_focus(a: &InsertActivitySession, b: &InsertActivitySession) -> bool {
+    a.app_name == b.app_name && a.window_name == b.window_name && a.browser_url == b.browser_url
+}
+
+/// Host of a url, without `www.`.
+pub fn domain(url: &str) -> Option<String> {
+    let url = url.trim();
+    let parsed = if url.contains("://") {
+        reqwest::Url::parse(url)
+    } else {
+        reqwest::Url::parse(&format!("https://{}", url))
+    };
+    let host = parsed.ok()?.host_str()?.to_lowercase();
+    Some(host.strip_prefix("www.").unwrap_or(&host).to_string())
+}
+
+/// Merged idle periods: the given ones, and gaps longer than `IDLE_GAP_SECS` between
+/// activity times (sorted) up to `until`.
+fn idle_periods(
+    activity: &[DateTime<Utc>],
+    idle: &[(DateTime<Utc>, DateTime<Utc>)],
+    from: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let gap = Duration::seconds(IDLE_GAP_SECS);
+    let mut periods: Vec<(DateTime<Utc>, DateTime<Utc>)> = idle.to_vec();
+    let mut previous = None;
+    for &time in activity.iter().chain(std::iter::once(&until)) {
+        let since = previous.unwrap_or(from);
+        if time - since > gap {
+            periods.push((since, time));
+        }
+        previous = Some(previous.map_or(time, |p: DateTime<Utc>| p.max(time)));
+    }
+
+    periods.sort();
+    let mut merged: Vec<(DateTime<Utc>, DateTime<Utc>)> = Vec::with_capacity(periods.len());
+    for (start, end) in periods {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Sessions between `from` and `until`: each observation holds until the next one, less
+/// the idle periods. Consecutive pieces on the same window are joined.
+pub fn sessionize(
+    observations: &[FocusObservation],
+    activity: &[DateTime<Utc>],
+    idle: &[(DateTime<Utc>, DateTime<Utc>)],
+    from: DateTime<Utc>,
+    until: DateTime<Utc>,
+    source: &str,
+) -> Vec<InsertActivitySession> {
+    let idle = idle_periods(activity, idle, from, until);
+    let mut sessions: Vec<InsertActivitySession> = Vec::new();
+
+    for (i, observation) in observations.iter().enumerate() {
+        let Some(app_name) = observation
+            .app_name
+            .as_deref()
+            .map(str::trim)
+            .filter(|app| !app.is_empty())
+        else {
+            continue;
+        };
+        let mut start = observation.timestamp.max(from);
+        let end = observations
+            .get(i + 1)
+            .map_or(until, |next| next.timestamp)
+            .min(until);
+
+        let mut pieces = Vec::new();
+        for &(idle_start, idle_end) in &idle {
+            if idle_end <= start || start >= end {
+                continue;
+            }
+            if idle_start >= end {
+                break;
+            }
+            if idle_start > start {
+                pieces.push((start, idle_start));
+            }
+            start = idle_end;
+        }
+        if start < end {
+            pieces.push((start, end));
+        }
+
+        let browser_url = observation.browser_url.clone().filter(|u| !u.is_empty());
+        for (piece_start, piece_end) in pieces {
+            let session = InsertActivitySession {
+                app_name: app_name.to_string(),
+                window_name: observation.window_name.clone().filter(|w| !w.is_empty()),
+                domain: browser_url.as_deref().and_then(domain),
+                browser_url: browser_url.clone(),
+                start_time: piece_start,
+                end_time: piece_end,
+                source: source.to_string(),
+            };
+            match sessions.last_mut() {
+                Some(last) if last.end_time == piece_start && same_focus(last, &session) => {
+                    last.end_time = piece_end
+                }
+                _ => sessions.push(session),
+            }
+        }
+    }
+
+    sessions.retain(|s| (s.end_time - s.start_time).num_milliseconds() >= MIN_SESSION_MS);
+    sessions
+}
+
+// ============================================================================
+// API
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    #[default]
+    App,
+    /// Browser time per domain, other apps are left out
+    Domain,
+    /// Per app and window
+    Window,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    Hour,
+    #[default]
+    Day,
+    /// The whole time range
+    Total,
+}
+
+#[derive(Deserialize)]
+pub struct ActivitySummaryQuery {
+    /// Defaults to a day before `end_time`
+    pub start_time: Option<DateTime<Utc>>,
+    /// Defaults to now
+    pub end_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub group_by: GroupBy,
+    #[serde(default)]
+    pub bucket: Bucket,
+    /// Case-insensitive partial match
+    pub app_name: Option<String>,
+    /// Offset of the local time, hours and days start at local midnight
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActivitySummaryItem {
+    /// Start of the hour or day, in local time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bucket_start: Option<DateTime<FixedOffset>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_name: Option<String>,
+    pub seconds: f64,
+}
+
+#[derive(Serialize)]
+pub struct ActivitySummary {
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub group_by: GroupBy,
+    pub bucket: Bucket,
+    pub total_seconds: f64,
+    /// Per bucket, longest first
+    pub items: Vec<ActivitySummaryItem>,
+}
+
+type SummaryKey = (
+    Option<DateTime<FixedOffset>>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+/// Start of the bucket containing `time`, in `offset` local time.
+fn bucket_start(time: DateTime<Utc>, bucket: Bucket, offset: FixedOffset) -> DateTime<FixedOffset> {
+    let length = match bucket {
+        Bucket::Hour => 3600,
+        _ => 86400,
+    };
+    let local_secs = time.timestamp() + offset.local_minus_utc() as i64;
+    let start = local_secs - local_secs.rem_euclid(length) - offset.local_minus_utc() as i64;
+    DateTime::from_timestamp(start, 0)
+        .unwrap_or(time)
+        .with_timezone(&offset)
+}
+
+/// Sum the sessions clipped to the time range per bucket and group.
+pub fn summarize(
+    sessions: &[ActivitySession],
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    group_by: GroupBy,
+    bucket: Bucket,
+    offset: FixedOffset,
+) -> Vec<ActivitySummaryItem> {
+    let mut totals: BTreeMap<SummaryKey, i64> = BTreeMap::new();
+
+    for session in sessions {
+        let (app_name, domain, window_name) = match group_by {
+            GroupBy::App => (Some(session.app_name.clone()), None, None),
+            GroupBy::Domain => match &session.domain {
+                Some(domain) => (None, Some(domain.clone()), None),
+                None => continue,
+            },
+            GroupBy::Window => (
+                Some(session.app_name.clone()),
+                None,
+                Some(session.window_name.clone().unwrap_or_default()),
+            ),
+        };
+
+        let mut start = session.start_time.max(start_time);
+        let end = session.end_time.min(end_time);
+        while start < end {
+            let (key_start, piece_end) = match bucket {
+                Bucket::Total => (None, end),
+                _ => {
+                    let bucket_start = bucket_start(start, bucket, offset);
+                    let length = match bucket {
+                        Bucket::Hour => Duration::hours(1),
+                        _ => Duration::days(1),
+                    };
+                    let bucket_end = bucket_start.with_timezone(&Utc) + length;
+                    (Some(bucket_start), bucket_end.min(end))
+                }
+            };
+            *totals
+                .entry((
+                    key_start,
+                    app_name.clone(),
+                    domain.clone(),
+                    window_name.clone(),
+                ))
+                .or_default() += (piece_end - start).num_milliseconds();
+            start = piece_end;
+        }
+    }
+
+    let mut items: Vec<ActivitySummaryItem> = totals
+        .into_iter()
+        .map(
+            |((bucket_start, app_name, domain, window_name), ms)| ActivitySummaryItem {
+                bucket_start,
+                app_name,
+                domain,
+                window_name,
+                seconds: ms as f64 / 1000.0,
+            },
+        )
+        .collect();
+    items.sort_by(|a, b| {
+        a.bucket_start
+            .cmp(&b.bucket_start)
+            .then(b.seconds.total_cmp(&a.seconds))
+    });
+    items
+}
+
+pub async fn activity_summary_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ActivitySummaryQuery>,
+) -> Result<Json<ActivitySummary>, (StatusCode, Json<Value>)> {
+    let end_time = query.end_time.unwrap_or_else(Utc::now);
+    let start_time = query
+        .start_time
+        .unwrap_or_else(|| end_time - Duration::days(1));
+    let Some(offset) = FixedOffset::east_opt(query.utc_offset_minutes * 60) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid utc_offset_minutes"})),
+        ));
+    };
+
+    let sessions = state
+        .db
+        .get_activity_sessions(start_time, end_time, query.app_name.as_deref())
+        .await
+        .map_err(|e| {
+            error!("failed to read activity sessions: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+        })?;
+
+    let items = summarize(
+        &sessions,
+        start_time,
+        end_time,
+        query.group_by,
+        query.bucket,
+        offset,
+    );
+    Ok(Json(ActivitySummary {
+        start_time,
+        end_time,
+        group_by: query.group_by,
+        bucket: query.bucket,
+        total_seconds: items.iter().map(|item| item.seconds).sum(),
+        items,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-10-01T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+            + Duration::minutes(minutes)
+    }
+
+    fn observation(minutes: i64, app: &str, url: Option<&str>) -> FocusObservation {
+        FocusObservation {
+            timestamp: at(minutes),
+            app_name: Some(app.to_string()),
+            window_name: Some(format!("{} window", app)),
+            browser_url: url.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_sessionize() {
+        let observations = vec![
+            observation(-10, "Code", None),
+            observation(5, "Arc", Some("https://www.github.com/screenpipe")),
+            observation(20, "Code", None),
+        ];
+        // something happens every minute, except between 25 and 40
+        let activity: Vec<_> = (0..=25).chain(40..=50).map(at).collect();
+        let sessions = sessionize(
+            &observations,
+            &activity,
+            &[],
+            at(0),
+            at(50),
+            SOURCE_UI_EVENTS,
+        );
+
+        let spans: Vec<_> = sessions
+            .iter()
+            .map(|s| (s.app_name.as_str(), s.start_time, s.end_time))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ("Code", at(0), at(5)),
+                ("Arc", at(5), at(20)),
+                ("Code", at(20), at(25)),
+                ("Code", at(40), at(50)),
+            ]
+        );
+        assert_eq!(sessions[1].domain.as_deref(), Some("github.com"));
+
+        // idle periods from the activity feed are cut out too
+        let sessions = sessionize(
+            &observations,
+            &activity,
+            &[(at(8), at(12))],
+            at(0),
+            at(20),
+            SOURCE_UI_EVENTS,
+        );
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(sessions[1].end_time, at(8));
+        assert_eq!(sessions[2].start_time, at(12));
+    }
+
+    #[test]
+    fn test_summarize() {
+        let session = |id, app: &str, start, end| ActivitySession {
+            id,
+            app_name: app.to_string(),
+            window_name: None,
+            browser_url: None,
+            domain: None,
+            start_time: at(start),
+            end_time: at(end),
+            source: SOURCE_FRAMES.to_string(),
+        };
+        let sessions = vec![
+            session(1, "Code", 30, 90),
+            session(2, "Slack", 90, 100),
+            session(3, "Code", 100, 110),
+        ];
+
+        let items = summarize(
+            &sessions,
+            at(0),
+            at(180),
+            GroupBy::App,
+            Bucket::Hour,
+            FixedOffset::east_opt(0).unwrap(),
+        );
+        let hours: Vec<_> = items
+            .iter()
+            .map(|i| {
+                (
+                    i.bucket_start.unwrap().to_rfc3339(),
+                    i.app_name.as_deref().unwrap(),
+                    i.seconds,
+                )
+            })
+            .collect();
+        assert_eq!(
+            hours,
+            [
+                ("2024-10-01T09:00:00+00:00".to_string(), "Code", 1800.0),
+                ("2024-10-01T10:00:00+00:00".to_string(), "Code", 2400.0),
+                ("2024-10-01T10:00:00+00:00".to_string(), "Slack", 600.0),
+            ]
+        );
+
+        // days start at local midnight
+        let items = summarize(
+            &sessions,
+            at(0),
+            at(180),
+            GroupBy::App,
+            Bucket::Day,
+            FixedOffset::east_opt(-10 * 3600).unwrap(),
+        );
+        let days: Vec<_> = items
+            .iter()
+            .map(|i| (i.bucket_start.unwrap().to_rfc3339(), i.seconds))
+            .collect();
+        assert_eq!(
+            days,
+            [
+                ("2024-09-30T00:00:00-10:00".to_string(), 1800.0),
+                ("2024-10-01T00:00:00-10:00".to_string(), 2400.0),
+                ("2024-10-01T00:00:00-10:00".to_string(), 600.0),
+            ]
+        );
+    }
+}
@@ -1,20 +1,22 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use regex::Regex;
-use screenpipe_db::DatabaseManager;
+use screenpipe_audio::{
+    core::{
+        device::{AudioDevice, DeviceType},
+        engine::AudioTranscriptionEngine,
+    },
+    transcription::{file_import::AudioFileTranscriber, handle_new_transcript},
+    vad::VadEngineEnum,
+    TranscriptionResult,
+};
+use screenpipe_core::Language;
+use screenpipe_db::{DatabaseManager, EmbeddingAnnIndex, OCR_EMBEDDING_INDEX_FILE};
 use screenpipe_vision::frame_comparison::{
     calculate_image_hash, FrameComparer, FrameComparisonConfig,
 };
 use screenpipe_vision::utils::OcrEngine;
 
-#[cfg(target_os = "macos")]
-use screenpipe_vision::perform_ocr_apple;
-
-#[cfg(target_os = "windows")]
-use screenpipe_vision::perform_ocr_windows;
-
-#[allow(unused)]
-use screenpipe_vision::perform_ocr_tesseract;
-
 use serde_json::json;
 use std::path::Path;
 use std::path::PathBuf;
@@ -32,6 +34,23 @@ use crate::{
     video_utils::{extract_frames_from_video, get_video_metadata, VideoMetadataOverrides},
 };
 
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mov", "avi"];
+const AUDIO_EXTENSIONS: &[&str] = &["wav", "mp3", "m4a", "aac", "flac", "ogg"];
+
+/// Device of imported audio when the metadata override doesn't name one.
+const IMPORTED_AUDIO_DEVICE: &str = "imported";
+
+/// Audio transcription settings of `add`, taken from the recorder flags.
+pub struct AudioImportOptions {
+    pub transcription_engine: Arc<AudioTranscriptionEngine>,
+    pub vad_engine: VadEngineEnum,
+    pub deepgram_api_key: Option<String>,
+    pub languages: Vec<Language>,
+    pub use_pii_removal: bool,
+    /// Also transcribe the audio track of video files
+    pub transcribe_videos: bool,
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn handle_index_command(
     screenpipe_dir: PathBuf,
@@ -43,6 +62,7 @@ pub async fn handle_index_command(
     metadata_override: Option<PathBuf>,
     copy_videos: bool,
     use_embedding: bool,
+    audio_options: AudioImportOptions,
 ) -> Result<()> {
     // Load metadata override if provided
     let metadata_overrides = if let Some(path) = metadata_override {
@@ -52,15 +72,20 @@ pub async fn handle_index_command(
         None
     };
 
-    // Get list of video files
-    let video_files = find_video_files(&path, pattern.as_deref())?;
-    info!("found {} video files to process", video_files.len());
+    // Get list of video and audio files
+    let video_files = find_media_files(&path, pattern.as_deref(), VIDEO_EXTENSIONS)?;
+    let audio_files = find_media_files(&path, pattern.as_deref(), AUDIO_EXTENSIONS)?;
+    info!(
+        "found {} video files and {} audio files to process",
+        video_files.len(),
+        audio_files.len()
+    );
 
     // Validate that we have metadata for all files if overrides are provided
     if let Some(ref overrides) = metadata_overrides {
         let mut unmatched_files = Vec::new();
 
-        for video_path in &video_files {
+        for video_path in video_files.iter().chain(&audio_files) {
             let file_str = video_path.to_string_lossy();
             let matched = overrides
                 .overrides
@@ -80,8 +105,26 @@ pub async fn handle_index_command(
         }
     }
 
+    // Index the embeddings as they are added, unless the server maintains the index: it
+    // catches up with the table by itself
+    if use_embedding {
+        let index = Arc::new(EmbeddingAnnIndex::new(
+            screenpipe_dir.join(OCR_EMBEDDING_INDEX_FILE),
+            None,
+        ));
+        match index.sync(&db).await {
+            Ok(()) => db.attach_ocr_embedding_index(index),
+            Err(e) => debug!("not updating the embedding index: {}", e),
+        }
+    }
+
     let mut total_frames = 0;
     let mut total_text = 0;
+    let mut total_audio_files = 0;
+    let mut total_segments = 0;
+
+    // Loading the audio models takes a while, only done when there is audio to transcribe
+    let mut audio_pipeline: Option<AudioPipeline> = None;
 
     // Setup channel for OCR results
 
@@ -132,6 +175,28 @@ pub async fn handle_index_command(
             video_path.clone()
         };
 
+        if audio_options.transcribe_videos {
+            let recorded_at = metadata.creation_time;
+            let device_name = metadata.device_name.clone();
+            match transcribe_audio_file(
+                &mut audio_pipeline,
+                &audio_options,
+                &db,
+                &video_path,
+                device_name,
+                recorded_at,
+            )
+            .await
+            {
+                Ok(segments) => total_segments += segments,
+                Err(e) => warn!(
+                    "failed to transcribe audio of {}: {}",
+                    video_path.display(),
+                    e
+                ),
+            }
+        }
+
         let frames = extract_frames_from_video(&video_path, None).await?;
 
         // Create video chunk and frames first
@@ -177,20 +242,14 @@ pub async fn handle_index_command(
             };
 
             // Do OCR processing directly
-            let (text, _, confidence): (String, String, Option<f64>) = match engine.clone() {
-                #[cfg(target_os = "macos")]
-                OcrEngine::AppleNative => perform_ocr_apple(frame, &[]),
-                #[cfg(target_os = "windows")]
-                OcrEngine::WindowsNative => perform_ocr_windows(&frame).await.unwrap(),
-                _ => {
-                    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-                    {
-                        perform_ocr_tesseract(&frame, Vec::new());
+            let (text, _, confidence): (String, String, Option<f64>) =
+                match engine.backend().perform_ocr(frame, &[]).await {
+                    Ok(result) => result,
+                    Err(e) => {
+                        warn!("ocr failed for frame {}: {}", frame_counter, e);
+                        ("".to_string(), "".to_string(), None)
                     }
-                    warn!("unsupported ocr engine");
-                    ("".to_string(), "".to_string(), None)
-                }
-            };
+                };
 
             // Handle OCR results
             total_frames += 1;
@@ -264,6 +323,74 @@ pub async fn handle_index_command(
         }
     }
 
+    for audio_path in audio_files {
+        info!("processing audio: {}", audio_path.display());
+
+        let mut metadata = get_video_metadata(audio_path.to_str().unwrap()).await?;
+        if let Some(ref overrides) = metadata_overrides {
+            let file_str = audio_path.to_string_lossy();
+            if let Some(override_item) = overrides
+                .overrides
+                .iter()
+                .find(|item| item.file_path == file_str)
+            {
+                override_item.metadata.apply_to(&mut metadata);
+            }
+        }
+
+        let audio_path = if copy_videos {
+            let ext = audio_path.extension().unwrap_or_default();
+            let new_filename = format!("{}.{}", Uuid::new_v4(), ext.to_string_lossy());
+            let target_path = Path::new(&screenpipe_dir).join("data").join(new_filename);
+            info!("copying audio to: {}", target_path.display());
+            fs::copy(&audio_path, &target_path).await?;
+            target_path
+        } else {
+            audio_path
+        };
+
+        let segments = match transcribe_audio_file(
+            &mut audio_pipeline,
+            &audio_options,
+            &db,
+            &audio_path,
+            metadata.device_name,
+            metadata.creation_time,
+        )
+        .await
+        {
+            Ok(segments) => segments,
+            Err(e) => {
+                error!("failed to transcribe {}: {}", audio_path.display(), e);
+                continue;
+            }
+        };
+        total_segments += segments;
+
+        if output_format == crate::cli::OutputFormat::Json {
+            if total_frames > 0 || total_audio_files > 0 {
+                print!(",");
+            }
+            print!(
+                "{}",
+                serde_json::to_string(&json!({
+                    "type": "audio",
+                    "data": {
+                        "audio_path": audio_path.to_string_lossy(),
+                        "recorded_at": metadata.creation_time,
+                        "segments": segments
+                    }
+                }))?
+            );
+        }
+        total_audio_files += 1;
+    }
+
+    // Flush the remaining transcripts to the db
+    if let Some(audio_pipeline) = audio_pipeline {
+        audio_pipeline.finish().await?;
+    }
+
     // wait few seconds for remaining OCR tasks
     tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
 
@@ -280,7 +407,9 @@ pub async fn handle_index_command(
                     "type": "summary",
                     "data": {
                         "total_frames": total_frames,
-                        "total_text_chars": total_text
+                        "total_text_chars": total_text,
+                        "total_audio_files": total_audio_files,
+                        "total_audio_segments": total_segments
                     }
                 }))?
             );
@@ -291,14 +420,97 @@ pub async fn handle_index_command(
                 "processed {} frames, extracted {} characters of text",
                 total_frames, total_text
             );
+            info!(
+                "transcribed {} audio files into {} segments",
+                total_audio_files, total_segments
+            );
         }
     }
 
     Ok(())
 }
 
-fn find_video_files(root: &str, pattern: Option<&str>) -> Result<Vec<PathBuf>> {
-    let mut video_files = Vec::new();
+/// Transcription of audio files. Transcripts go through the same dedup and speaker
+/// matching as live audio.
+struct AudioPipeline {
+    transcriber: AudioFileTranscriber,
+    sender: crossbeam::channel::Sender<TranscriptionResult>,
+    transcript_handler: tokio::task::JoinHandle<()>,
+}
+
+impl AudioPipeline {
+    async fn start(options: &AudioImportOptions, db: Arc<DatabaseManager>) -> Result<Self> {
+        let transcriber = AudioFileTranscriber::new(
+            options.transcription_engine.clone(),
+            options.vad_engine.clone(),
+            options.deepgram_api_key.clone(),
+            options.languages.clone(),
+        )
+        .await?;
+
+        let (sender, receiver) = crossbeam::channel::bounded(1000);
+        let engine = options.transcription_engine.clone();
+        let use_pii_removal = options.use_pii_removal;
+        let runtime = tokio::runtime::Handle::current();
+        // handle_new_transcript blocks on the channel, keep it off the async workers
+        let transcript_handler = tokio::task::spawn_blocking(move || {
+            runtime.block_on(handle_new_transcript(
+                db,
+                Arc::new(receiver),
+                engine,
+                use_pii_removal,
+            ))
+        });
+
+        Ok(Self {
+            transcriber,
+            sender,
+            transcript_handler,
+        })
+    }
+
+    /// Wait until every transcript is in the db.
+    async fn finish(self) -> Result<()> {
+        drop(self.sender);
+        self.transcript_handler.await?;
+        Ok(())
+    }
+}
+
+/// Store `path` as an audio chunk recorded at `recorded_at` and transcribe it, starting
+/// the pipeline on first use. Returns the number of speech segments.
+async fn transcribe_audio_file(
+    pipeline: &mut Option<AudioPipeline>,
+    options: &AudioImportOptions,
+    db: &Arc<DatabaseManager>,
+    path: &Path,
+    device_name: Option<String>,
+    recorded_at: DateTime<Utc>,
+) -> Result<usize> {
+    let pipeline = match pipeline {
+        Some(pipeline) => pipeline,
+        None => pipeline.insert(AudioPipeline::start(options, db.clone()).await?),
+    };
+
+    let chunk_path = path.to_string_lossy();
+    db.insert_audio_chunk_at(&chunk_path, recorded_at).await?;
+    let device = Arc::new(AudioDevice::new(
+        device_name.unwrap_or_else(|| IMPORTED_AUDIO_DEVICE.to_string()),
+        DeviceType::Input,
+    ));
+
+    pipeline
+        .transcriber
+        .transcribe_file(path, &chunk_path, device, recorded_at, &pipeline.sender)
+        .await
+}
+
+fn find_media_files(
+    root: &str,
+    pattern: Option<&str>,
+    extensions: &[&str],
+) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
     let regex = pattern.map(Regex::new).transpose()?;
 
     for entry in WalkDir::new(root)
@@ -309,18 +521,19 @@ fn find_video_files(root: &str, pattern: Option<&str>) -> Result<Vec<PathBuf>> {
         let path = entry.path();
         if path.is_file() {
             if let Some(ext) = path.extension() {
-                if ext == "mp4" || ext == "mov" || ext == "avi" {
+                let ext = ext.to_string_lossy().to_lowercase();
+                if extensions.contains(&ext.as_str()) {
                     if let Some(ref regex) = regex {
                         if regex.is_match(&path.to_string_lossy()) {
-                            video_files.push(path.to_path_buf());
+                            files.push(path.to_path_buf());
                         }
                     } else {
-                        video_files.push(path.to_path_buf());
+                        files.push(path.to_path_buf());
                     }
                 }
             }
         }
     }
 
-    Ok(video_files)
+    Ok(files)
 }
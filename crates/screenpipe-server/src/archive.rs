@@ -0,0 +1,1002 @@
+//! Portable archives of screenpipe history
+//!
+//! `screenpipe export` writes a zip holding a `manifest.json`, a `db.sqlite` with the
+//! selected rows (same schema as the main database) and the media files those rows
+//! reference. `screenpipe import` replays the archive through
+//! [`ScreenpipeSyncProvider::import_chunk`], which dedupes on `sync_id`, so importing an
+//! archive twice or merging archives of overlapping time ranges never duplicates data.
+//!
+//! Rows that were never synced get a deterministic `sync_id` (`<machine>:<table>:<id>`)
+//! in the archive, so two exports of the same data carry the same ids.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use screenpipe_core::at_rest;
+use screenpipe_db::DatabaseManager;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqliteConnection;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::sync_provider::{
+    AccessibilityRecord, FrameRecord, ImportResult, OcrRecord, ScreenpipeSyncProvider, SyncChunk,
+    TranscriptionRecord, UiEventSyncRecord, SCHEMA_VERSION,
+};
+
+/// Bumped when the layout of the archive itself changes
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_NAME: &str = "manifest.json";
+const DB_NAME: &str = "db.sqlite";
+
+/// Rows handed to `import_chunk` at once
+const IMPORT_BATCH_SIZE: i64 = 500;
+
+/// Selects frames, their OCR text and video chunks follow
+const SCREEN_FILTER: &str = "(?1 IS NULL OR timestamp >= ?1) AND (?2 IS NULL OR timestamp <= ?2) \
+     AND (?3 IS NULL OR app_name = ?3) AND (?4 IS NULL OR device_name = ?4)";
+
+/// Audio has no app, an app filter leaves it out entirely
+const AUDIO_FILTER: &str = "(?1 IS NULL OR timestamp >= ?1) AND (?2 IS NULL OR timestamp <= ?2) \
+     AND ?3 IS NULL AND (?4 IS NULL OR device = ?4)";
+
+/// Accessibility and UI events are not tied to a device, a device filter leaves them out
+const INPUT_FILTER: &str = "(?1 IS NULL OR timestamp >= ?1) AND (?2 IS NULL OR timestamp <= ?2) \
+     AND (?3 IS NULL OR app_name = ?3) AND ?4 IS NULL";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportFilter {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Only screen content of this app, audio is skipped when set
+    pub app_name: Option<String>,
+    /// Only frames of this monitor or transcriptions of this audio device
+    pub device_name: Option<String>,
+    /// Copy the referenced video and audio files into the archive
+    pub include_media: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveCounts {
+    pub frames: u64,
+    pub ocr_text: u64,
+    pub video_chunks: u64,
+    pub audio_chunks: u64,
+    pub audio_transcriptions: u64,
+    pub accessibility: u64,
+    pub ui_events: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Video,
+    Audio,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaEntry {
+    /// Path inside the archive, also stored in the archive's `file_path` columns
+    pub path: String,
+    pub kind: MediaKind,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format_version: u32,
+    pub screenpipe_version: String,
+    /// Latest database migration applied when exporting
+    pub db_schema_version: i64,
+    pub machine_id: String,
+    pub created_at: DateTime<Utc>,
+    pub filter: ExportFilter,
+    pub counts: ArchiveCounts,
+    #[serde(default)]
+    pub media: Vec<MediaEntry>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportSummary {
+    pub frames: usize,
+    pub ocr: usize,
+    pub transcriptions: usize,
+    pub accessibility: usize,
+    pub ui_events: usize,
+    /// Rows already present locally
+    pub skipped: usize,
+    pub media_files: usize,
+}
+
+impl ImportSummary {
+    fn add(&mut self, result: &ImportResult) {
+        self.frames += result.imported_frames;
+        self.ocr += result.imported_ocr;
+        self.transcriptions += result.imported_transcriptions;
+        self.accessibility += result.imported_accessibility;
+        self.ui_events += result.imported_ui_events;
+        self.skipped += result.skipped;
+    }
+}
+
+/// Media file waiting to be written into the archive
+struct PendingMedia {
+    source: PathBuf,
+    entry_name: String,
+    kind: MediaKind,
+}
+
+/// Write the rows matching `filter` (and their media) to a zip archive at `output`.
+pub async fn export_archive(
+    db: &DatabaseManager,
+    machine_id: &str,
+    filter: &ExportFilter,
+    output: &Path,
+) -> Result<ArchiveManifest> {
+    let staging = tempfile::tempdir()?;
+    let archive_db_path = staging.path().join(DB_NAME);
+
+    // Creating it through DatabaseManager runs the migrations, so the archive has
+    // exactly the schema of the database it is copied from
+    let archive_db = DatabaseManager::new(&archive_db_path.to_string_lossy()).await?;
+    archive_db.pool.close().await;
+
+    let db_schema_version = schema_version(db).await?;
+
+    let mut conn = db.pool.acquire().await?;
+    // An empty key keeps the archive unencrypted when the main database uses SQLCipher
+    sqlx::query("ATTACH DATABASE ? AS archive KEY ''")
+        .bind(archive_db_path.to_string_lossy().into_owned())
+        .execute(&mut *conn)
+        .await?;
+    let filled = fill_archive_db(&mut conn, machine_id, filter).await;
+    // Detach even on error so the connection goes back to the pool clean
+    let restored = restore_connection(&mut conn).await;
+    let (counts, media) = filled?;
+    restored?;
+    drop(conn);
+
+    let manifest = ArchiveManifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        screenpipe_version: env!("CARGO_PKG_VERSION").to_string(),
+        db_schema_version,
+        machine_id: machine_id.to_string(),
+        created_at: Utc::now(),
+        filter: filter.clone(),
+        counts,
+        media: Vec::new(),
+    };
+
+    let output = output.to_path_buf();
+    let manifest = tokio::task::spawn_blocking(move || {
+        let written = write_archive(&output, &archive_db_path, &media, manifest);
+        if written.is_err() {
+            let _ = std::fs::remove_file(&output);
+        }
+        written
+    })
+    .await??;
+
+    info!(
+        "exported {} frames, {} transcriptions and {} media files",
+        manifest.counts.frames,
+        manifest.counts.audio_transcriptions,
+        manifest.media.len()
+    );
+    Ok(manifest)
+}
+
+/// Copy the matching rows into the attached `archive` database and rewrite their media
+/// paths to archive entries.
+async fn fill_archive_db(
+    conn: &mut SqliteConnection,
+    machine_id: &str,
+    filter: &ExportFilter,
+) -> Result<(ArchiveCounts, Vec<PendingMedia>)> {
+    // Rows keep their ids, references to tables that are not exported (speakers,
+    // frames of ui_events outside the filter) would fail the checks
+    sqlx::query("PRAGMA foreign_keys = OFF")
+        .execute(&mut *conn)
+        .await?;
+
+    let copies = [
+        format!(
+            "INSERT INTO archive.video_chunks SELECT * FROM main.video_chunks
+             WHERE id IN (SELECT video_chunk_id FROM main.frames WHERE {SCREEN_FILTER})"
+        ),
+        format!("INSERT INTO archive.frames SELECT * FROM main.frames WHERE {SCREEN_FILTER}"),
+        format!(
+            "INSERT INTO archive.ocr_text SELECT * FROM main.ocr_text
+             WHERE frame_id IN (SELECT id FROM main.frames WHERE {SCREEN_FILTER})"
+        ),
+        format!(
+            "INSERT INTO archive.audio_chunks SELECT * FROM main.audio_chunks
+             WHERE id IN (SELECT audio_chunk_id FROM main.audio_transcriptions WHERE {AUDIO_FILTER})"
+        ),
+        format!(
+            "INSERT INTO archive.audio_transcriptions SELECT * FROM main.audio_transcriptions
+             WHERE {AUDIO_FILTER}"
+        ),
+        format!("INSERT INTO archive.accessibility SELECT * FROM main.accessibility WHERE {INPUT_FILTER}"),
+        format!("INSERT INTO archive.ui_events SELECT * FROM main.ui_events WHERE {INPUT_FILTER}"),
+    ];
+    for statement in &copies {
+        sqlx::query(statement)
+            .bind(filter.start_time)
+            .bind(filter.end_time)
+            .bind(&filter.app_name)
+            .bind(&filter.device_name)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    let sync_ids = [
+        "UPDATE archive.frames SET sync_id = ?1 || ':frame:' || id WHERE sync_id IS NULL",
+        "UPDATE archive.ocr_text SET sync_id =
+             (SELECT f.sync_id FROM archive.frames f WHERE f.id = ocr_text.frame_id) || ':ocr'
+         WHERE sync_id IS NULL",
+        "UPDATE archive.audio_transcriptions SET sync_id = ?1 || ':transcription:' || id
+         WHERE sync_id IS NULL",
+        "UPDATE archive.accessibility SET sync_id = ?1 || ':accessibility:' || id
+         WHERE sync_id IS NULL",
+        "UPDATE archive.ui_events SET sync_id = ?1 || ':ui_event:' || id WHERE sync_id IS NULL",
+    ];
+    for statement in sync_ids {
+        let query = sqlx::query(statement);
+        // the OCR statement derives its id from the frame's
+        let query = if statement.contains("?1") {
+            query.bind(machine_id)
+        } else {
+            query
+        };
+        query.execute(&mut *conn).await?;
+    }
+    sqlx::query(
+        "UPDATE archive.ui_events SET frame_id = NULL
+         WHERE frame_id IS NOT NULL AND frame_id NOT IN (SELECT id FROM archive.frames)",
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let media = if filter.include_media {
+        collect_media(conn).await?
+    } else {
+        Vec::new()
+    };
+
+    let counts: (i64, i64, i64, i64, i64, i64, i64) = sqlx::query_as(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM archive.frames),
+            (SELECT COUNT(*) FROM archive.ocr_text),
+            (SELECT COUNT(*) FROM archive.video_chunks),
+            (SELECT COUNT(*) FROM archive.audio_chunks),
+            (SELECT COUNT(*) FROM archive.audio_transcriptions),
+            (SELECT COUNT(*) FROM archive.accessibility),
+            (SELECT COUNT(*) FROM archive.ui_events)
+        "#,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query("PRAGMA archive.wal_checkpoint(TRUNCATE)")
+        .execute(&mut *conn)
+        .await?;
+
+    Ok((
+        ArchiveCounts {
+            frames: counts.0 as u64,
+            ocr_text: counts.1 as u64,
+            video_chunks: counts.2 as u64,
+            audio_chunks: counts.3 as u64,
+            audio_transcriptions: counts.4 as u64,
+            accessibility: counts.5 as u64,
+            ui_events: counts.6 as u64,
+        },
+        media,
+    ))
+}
+
+async fn restore_connection(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query("DETACH DATABASE archive")
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("PRAGMA foreign_keys = ON")
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Point the archive's chunks at `media/<kind>/<id>_<file name>` and list the files to
+/// copy. Chunks whose file is gone (pruned, deleted by hand) keep their original path.
+async fn collect_media(conn: &mut SqliteConnection) -> Result<Vec<PendingMedia>> {
+    let mut media = Vec::new();
+    for (table, kind, dir) in [
+        ("video_chunks", MediaKind::Video, "video"),
+        ("audio_chunks", MediaKind::Audio, "audio"),
+    ] {
+        let chunks: Vec<(i64, String)> = sqlx::query_as(&format!(
+            "SELECT id, file_path FROM archive.{table}
+             WHERE media_pruned_at IS NULL AND file_path NOT LIKE 'cloud://%'"
+        ))
+        .fetch_all(&mut *conn)
+        .await?;
+
+        for (id, file_path) in chunks {
+            let source = PathBuf::from(&file_path);
+            let Some(file_name) = source.file_name().map(|n| n.to_string_lossy().into_owned())
+            else {
+                continue;
+            };
+            if !source.is_file() {
+                warn!("skipping missing media file {}", file_path);
+                continue;
+            }
+            let entry_name = format!("media/{}/{}_{}", dir, id, file_name);
+            sqlx::query(&format!(
+                "UPDATE archive.{table} SET file_path = ? WHERE id = ?"
+            ))
+            .bind(&entry_name)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+            media.push(PendingMedia {
+                source,
+                entry_name,
+                kind,
+            });
+        }
+    }
+    Ok(media)
+}
+
+fn write_archive(
+    output: &Path,
+    db_path: &Path,
+    media: &[PendingMedia],
+    mut manifest: ArchiveManifest,
+) -> Result<ArchiveManifest> {
+    let mut zip = ZipWriter::new(File::create(output)?);
+    let deflated = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(true);
+    // Video and audio are already compressed
+    let stored = FileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+
+    zip.start_file(DB_NAME, deflated)?;
+    std::io::copy(&mut File::open(db_path)?, &mut zip)?;
+
+    for item in media {
+        zip.start_file(item.entry_name.as_str(), stored)?;
+        let mut source = File::open(&item.source)
+            .with_context(|| format!("failed to open {}", item.source.display()))?;
+        let (size, sha256) = if at_rest::is_sealed(&item.source)? {
+            // Media encrypted at rest is exported decrypted, archives are portable
+            let key = at_rest::media_key().ok_or_else(|| {
+                anyhow!(
+                    "{} is encrypted at rest but no key is configured",
+                    item.source.display()
+                )
+            })?;
+            let mut plaintext = Vec::new();
+            at_rest::decrypt_stream(&mut BufReader::new(source), &mut plaintext, key)?;
+            copy_hashing(&mut plaintext.as_slice(), &mut zip)?
+        } else {
+            copy_hashing(&mut source, &mut zip)?
+        };
+        manifest.media.push(MediaEntry {
+            path: item.entry_name.clone(),
+            kind: item.kind,
+            size,
+            sha256,
+        });
+    }
+
+    zip.start_file(MANIFEST_NAME, deflated)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    zip.finish()?;
+    Ok(manifest)
+}
+
+/// Copy `reader` into `writer`, returning the byte count and hex sha256 of the data.
+fn copy_hashing(reader: &mut impl Read, writer: &mut impl Write) -> std::io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        writer.write_all(&buffer[..read])?;
+        size += read as u64;
+    }
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+async fn schema_version(db: &DatabaseManager) -> Result<i64> {
+    let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations")
+        .fetch_one(&db.pool)
+        .await?;
+    Ok(version.unwrap_or(0))
+}
+
+/// Archive entries are only ever extracted under the staging directory.
+fn is_safe_entry(name: &str) -> bool {
+    !name.is_empty()
+        && Path::new(name)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+fn read_manifest(archive: &mut ZipArchive<File>) -> Result<ArchiveManifest> {
+    let mut entry = archive
+        .by_name(MANIFEST_NAME)
+        .map_err(|_| anyhow!("not a screenpipe archive: {} missing", MANIFEST_NAME))?;
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    let manifest: ArchiveManifest = serde_json::from_slice(&data)?;
+    if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        bail!(
+            "archive format {} is newer than supported ({}), update screenpipe",
+            manifest.format_version,
+            ARCHIVE_FORMAT_VERSION
+        );
+    }
+    Ok(manifest)
+}
+
+/// Extract the database and media of the archive into `staging`, checking media
+/// checksums against the manifest.
+fn unpack_archive(archive_path: &Path, staging: &Path) -> Result<ArchiveManifest> {
+    let mut archive = ZipArchive::new(File::open(archive_path)?)?;
+    let manifest = read_manifest(&mut archive)?;
+
+    {
+        let mut entry = archive
+            .by_name(DB_NAME)
+            .map_err(|_| anyhow!("not a screenpipe archive: {} missing", DB_NAME))?;
+        std::io::copy(&mut entry, &mut File::create(staging.join(DB_NAME))?)?;
+    }
+
+    for media in &manifest.media {
+        if !is_safe_entry(&media.path) {
+            bail!("refusing to extract unsafe path {}", media.path);
+        }
+        let destination = staging.join(&media.path);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut entry = archive
+            .by_name(&media.path)
+            .map_err(|_| anyhow!("media file {} missing from archive", media.path))?;
+        let (size, sha256) = copy_hashing(&mut entry, &mut File::create(&destination)?)?;
+        if size != media.size || sha256 != media.sha256 {
+            bail!("media file {} is corrupted (checksum mismatch)", media.path);
+        }
+    }
+
+    Ok(manifest)
+}
+
+/// Merge an archive into the local database. Media is copied under
+/// `<data_dir>/data/imported/<source machine>/`.
+pub async fn import_archive(
+    db: Arc<DatabaseManager>,
+    machine_id: &str,
+    archive_path: &Path,
+    data_dir: &Path,
+) -> Result<ImportSummary> {
+    let staging = tempfile::tempdir()?;
+    let manifest = {
+        let archive_path = archive_path.to_path_buf();
+        let staging = staging.path().to_path_buf();
+        tokio::task::spawn_blocking(move || unpack_archive(&archive_path, &staging)).await??
+    };
+
+    let local_version = schema_version(&db).await?;
+    if manifest.db_schema_version > local_version {
+        bail!(
+            "archive was created with a newer database schema ({} > {}), update screenpipe first",
+            manifest.db_schema_version,
+            local_version
+        );
+    }
+
+    if manifest.machine_id == machine_id {
+        warn!("archive was exported from this machine, nothing to import");
+        return Ok(ImportSummary::default());
+    }
+
+    // Opening it migrates archives written by older versions up to the local schema
+    let archive_db = DatabaseManager::new(&staging.path().join(DB_NAME).to_string_lossy()).await?;
+
+    let mut summary = ImportSummary::default();
+    let media_paths = copy_media(&manifest, staging.path(), data_dir, &mut summary).await?;
+
+    let provider = ScreenpipeSyncProvider::new(db.clone(), machine_id.to_string());
+    import_frames(
+        &archive_db,
+        &db,
+        &provider,
+        &manifest,
+        &media_paths,
+        &mut summary,
+    )
+    .await?;
+    import_transcriptions(
+        &archive_db,
+        &db,
+        &provider,
+        &manifest,
+        &media_paths,
+        &mut summary,
+    )
+    .await?;
+    import_accessibility(&archive_db, &provider, &manifest, &mut summary).await?;
+    import_ui_events(&archive_db, &provider, &manifest, &mut summary).await?;
+
+    archive_db.pool.close().await;
+    info!(
+        "imported archive from {}: {} frames, {} transcriptions, {} skipped",
+        manifest.machine_id, summary.frames, summary.transcriptions, summary.skipped
+    );
+    Ok(summary)
+}
+
+/// Copy the archive's media next to the local recordings, keyed by archive entry name.
+async fn copy_media(
+    manifest: &ArchiveManifest,
+    staging: &Path,
+    data_dir: &Path,
+    summary: &mut ImportSummary,
+) -> Result<HashMap<String, String>> {
+    let mut paths = HashMap::new();
+    if manifest.media.is_empty() {
+        return Ok(paths);
+    }
+
+    let media_dir = data_dir
+        .join("data")
+        .join("imported")
+        .join(&manifest.machine_id);
+    tokio::fs::create_dir_all(&media_dir).await?;
+
+    for media in &manifest.media {
+        let Some(file_name) = Path::new(&media.path).file_name() else {
+            continue;
+        };
+        let destination = media_dir.join(file_name);
+        tokio::fs::copy(staging.join(&media.path), &destination).await?;
+        paths.insert(
+            media.path.clone(),
+            destination.to_string_lossy().into_owned(),
+        );
+        summary.media_files += 1;
+    }
+    Ok(paths)
+}
+
+fn empty_chunk(manifest: &ArchiveManifest, time_start: &str, time_end: &str) -> SyncChunk {
+    SyncChunk {
+        schema_version: SCHEMA_VERSION,
+        machine_id: manifest.machine_id.clone(),
+        time_start: time_start.to_string(),
+        time_end: time_end.to_string(),
+        frames: Vec::new(),
+        ocr_records: Vec::new(),
+        transcriptions: Vec::new(),
+        accessibility_records: Vec::new(),
+        ui_events: Vec::new(),
+    }
+}
+
+async fn run_import(
+    provider: &ScreenpipeSyncProvider,
+    chunk: &SyncChunk,
+    summary: &mut ImportSummary,
+) -> Result<()> {
+    let result = provider
+        .import_chunk(chunk)
+        .await
+        .map_err(|e| anyhow!("failed to import archive chunk: {}", e))?;
+    summary.add(&result);
+    Ok(())
+}
+
+/// `import_chunk` creates one `cloud://` chunk per imported row, point the ones whose
+/// media came with the archive at the copied file.
+async fn attach_media(
+    db: &DatabaseManager,
+    table: &str,
+    by_file: HashMap<&String, Vec<&String>>,
+    media_paths: &HashMap<String, String>,
+) -> Result<()> {
+    for (archive_path, sync_ids) in by_file {
+        let Some(local_path) = media_paths.get(archive_path) else {
+            continue;
+        };
+        sqlx::query(&format!(
+            "UPDATE {table} SET file_path = ?1
+             WHERE file_path LIKE 'cloud://%' AND sync_id IN (SELECT value FROM json_each(?2))"
+        ))
+        .bind(local_path)
+        .bind(serde_json::to_string(&sync_ids)?)
+        .execute(&db.pool)
+        .await?;
+    }
+    Ok(())
+}
+
+type ArchiveFrameRow = (
+    i64,
+    String,
+    String,
+    i64,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    String,
+    String,
+);
+
+async fn import_frames(
+    archive_db: &DatabaseManager,
+    db: &DatabaseManager,
+    provider: &ScreenpipeSyncProvider,
+    manifest: &ArchiveManifest,
+    media_paths: &HashMap<String, String>,
+    summary: &mut ImportSummary,
+) -> Result<()> {
+    let mut last_id = 0;
+    loop {
+        let rows: Vec<ArchiveFrameRow> = sqlx::query_as(
+            r#"
+            SELECT f.id, COALESCE(f.sync_id, ?1 || ':frame:' || f.id), f.timestamp, f.offset_index,
+                f.app_name, f.window_name, f.browser_url, f.device_name, vc.file_path
+            FROM frames f
+            JOIN video_chunks vc ON vc.id = f.video_chunk_id
+            WHERE f.id > ?2
+            ORDER BY f.id
+            LIMIT ?3
+            "#,
+        )
+        .bind(&manifest.machine_id)
+        .bind(last_id)
+        .bind(IMPORT_BATCH_SIZE)
+        .fetch_all(&archive_db.pool)
+        .await?;
+        let (Some(first), Some(last)) = (rows.first(), rows.last()) else {
+            break;
+        };
+
+        let ocr: Vec<(String, String, String, bool)> = sqlx::query_as(
+            r#"
+            SELECT COALESCE(o.sync_id, COALESCE(f.sync_id, ?1 || ':frame:' || f.id) || ':ocr'),
+                COALESCE(f.sync_id, ?1 || ':frame:' || f.id), o.text, COALESCE(o.focused, 0)
+            FROM ocr_text o
+            JOIN frames f ON f.id = o.frame_id
+            WHERE o.frame_id >= ?2 AND o.frame_id <= ?3
+            "#,
+        )
+        .bind(&manifest.machine_id)
+        .bind(first.0)
+        .bind(last.0)
+        .fetch_all(&archive_db.pool)
+        .await?;
+
+        let mut chunk = empty_chunk(manifest, &first.2, &last.2);
+        chunk.frames = rows
+            .iter()
+            .map(|row| FrameRecord {
+                sync_id: row.1.clone(),
+                timestamp: row.2.clone(),
+                offset_index: row.3,
+                app_name: row.4.clone(),
+                window_name: row.5.clone(),
+                browser_url: row.6.clone(),
+                device_name: row.7.clone(),
+                cloud_frame_path: None,
+            })
+            .collect();
+        chunk.ocr_records = ocr
+            .into_iter()
+            .map(|(sync_id, frame_sync_id, text, focused)| OcrRecord {
+                sync_id,
+                frame_sync_id,
+                text,
+                focused,
+            })
+            .collect();
+        run_import(provider, &chunk, summary).await?;
+
+        let mut by_file: HashMap<&String, Vec<&String>> = HashMap::new();
+        for row in &rows {
+            by_file.entry(&row.8).or_default().push(&row.1);
+        }
+        attach_media(db, "video_chunks", by_file, media_paths).await?;
+
+        debug!("imported archive frames up to id {}", last.0);
+        last_id = last.0;
+    }
+    Ok(())
+}
+
+type ArchiveTranscriptionRow = (i64, String, String, String, String, bool, String);
+
+async fn import_transcriptions(
+    archive_db: &DatabaseManager,
+    db: &DatabaseManager,
+    provider: &ScreenpipeSyncProvider,
+    manifest: &ArchiveManifest,
+    media_paths: &HashMap<String, String>,
+    summary: &mut ImportSummary,
+) -> Result<()> {
+    let mut last_id = 0;
+    loop {
+        let rows: Vec<ArchiveTranscriptionRow> = sqlx::query_as(
+            r#"
+            SELECT t.id, COALESCE(t.sync_id, ?1 || ':transcription:' || t.id), t.timestamp,
+                t.transcription, COALESCE(t.device, ''), COALESCE(t.is_input_device, 0), ac.file_path
+            FROM audio_transcriptions t
+            JOIN audio_chunks ac ON ac.id = t.audio_chunk_id
+            WHERE t.id > ?2
+            ORDER BY t.id
+            LIMIT ?3
+            "#,
+        )
+        .bind(&manifest.machine_id)
+        .bind(last_id)
+        .bind(IMPORT_BATCH_SIZE)
+        .fetch_all(&archive_db.pool)
+        .await?;
+        let (Some(first), Some(last)) = (rows.first(), rows.last()) else {
+            break;
+        };
+
+        let mut chunk = empty_chunk(manifest, &first.2, &last.2);
+        chunk.transcriptions = rows
+            .iter()
+            .map(|row| TranscriptionRecord {
+                sync_id: row.1.clone(),
+                timestamp: row.2.clone(),
+                transcription: row.3.clone(),
+                device: row.4.clone(),
+                is_input_device: row.5,
+                // speaker ids are local to the machine that recorded them
+                speaker_id: None,
+            })
+            .collect();
+        run_import(provider, &chunk, summary).await?;
+
+        let mut by_file: HashMap<&String, Vec<&String>> = HashMap::new();
+        for row in &rows {
+            by_file.entry(&row.6).or_default().push(&row.1);
+        }
+        attach_media(db, "audio_chunks", by_file, media_paths).await?;
+
+        last_id = last.0;
+    }
+    Ok(())
+}
+
+async fn import_accessibility(
+    archive_db: &DatabaseManager,
+    provider: &ScreenpipeSyncProvider,
+    manifest: &ArchiveManifest,
+    summary: &mut ImportSummary,
+) -> Result<()> {
+    let mut last_id = 0;
+    loop {
+        let rows: Vec<(i64, String, String, String, String, String, Option<String>)> =
+            sqlx::query_as(
+                r#"
+                SELECT id, COALESCE(sync_id, ?1 || ':accessibility:' || id), timestamp,
+                    app_name, window_name, text_content, browser_url
+                FROM accessibility
+                WHERE id > ?2
+                ORDER BY id
+                LIMIT ?3
+                "#,
+            )
+            .bind(&manifest.machine_id)
+            .bind(last_id)
+            .bind(IMPORT_BATCH_SIZE)
+            .fetch_all(&archive_db.pool)
+            .await?;
+        let (Some(first), Some(last)) = (rows.first(), rows.last()) else {
+            break;
+        };
+
+        let mut chunk = empty_chunk(manifest, &first.2, &last.2);
+        last_id = last.0;
+        chunk.accessibility_records = rows
+            .into_iter()
+            .map(
+                |(_, sync_id, timestamp, app_name, window_name, text_content, browser_url)| {
+                    AccessibilityRecord {
+                        sync_id,
+                        timestamp,
+                        app_name,
+                        window_name,
+                        text_content,
+                        browser_url,
+                    }
+                },
+            )
+            .collect();
+        run_import(provider, &chunk, summary).await?;
+    }
+    Ok(())
+}
+
+type ArchiveUiEventRow = (
+    i64,
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<i32>,
+    Option<i32>,
+    Option<i32>,
+    Option<i32>,
+    Option<String>,
+    Option<String>,
+);
+
+async fn import_ui_events(
+    archive_db: &DatabaseManager,
+    provider: &ScreenpipeSyncProvider,
+    manifest: &ArchiveManifest,
+    summary: &mut ImportSummary,
+) -> Result<()> {
+    let mut last_id = 0;
+    loop {
+        let rows: Vec<ArchiveUiEventRow> = sqlx::query_as(
+            r#"
+            SELECT id, COALESCE(sync_id, ?1 || ':ui_event:' || id), timestamp, event_type,
+                app_name, window_title, browser_url, text_content, x, y, key_code, modifiers,
+                element_role, element_name
+            FROM ui_events
+            WHERE id > ?2
+            ORDER BY id
+            LIMIT ?3
+            "#,
+        )
+        .bind(&manifest.machine_id)
+        .bind(last_id)
+        .bind(IMPORT_BATCH_SIZE)
+        .fetch_all(&archive_db.pool)
+        .await?;
+        let (Some(first), Some(last)) = (rows.first(), rows.last()) else {
+            break;
+        };
+
+        let mut chunk = empty_chunk(manifest, &first.2, &last.2);
+        last_id = last.0;
+        chunk.ui_events = rows
+            .into_iter()
+            .map(|row| UiEventSyncRecord {
+                sync_id: row.1,
+                timestamp: row.2,
+                event_type: row.3,
+                app_name: row.4,
+                window_title: row.5,
+                browser_url: row.6,
+                text_content: row.7,
+                x: row.8,
+                y: row.9,
+                key_code: row.10,
+                modifiers: row.11,
+                element_role: row.12,
+                element_name: row.13,
+            })
+            .collect();
+        run_import(provider, &chunk, summary).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_hashing() {
+        let mut output = Vec::new();
+        let (size, sha256) = copy_hashing(&mut &b"screenpipe"[..], &mut output).unwrap();
+        assert_eq!(size, 10);
+        assert_eq!(output, b"screenpipe");
+        assert_eq!(sha256, format!("{:x}", Sha256::digest(b"screenpipe")));
+    }
+
+    #[test]
+    fn test_is_safe_entry() {
+        assert!(is_safe_entry("media/video/1_monitor_1.mp4"));
+        assert!(!is_safe_entry("../outside.mp4"));
+        assert!(!is_safe_entry("media/../../outside.mp4"));
+        assert!(!is_safe_entry("/etc/passwd"));
+        assert!(!is_safe_entry(""));
+    }
+
+    #[tokio::test]
+    async fn test_export_import_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = DatabaseManager::new("sqlite::memory:").await.unwrap();
+
+        let chunk_id = source
+            .insert_video_chunk("/nonexistent/monitor_1.mp4", "monitor_1")
+            .await
+            .unwrap();
+        let frame_id = source
+            .insert_frame(
+                "monitor_1",
+                None,
+                None,
+                Some("Code"),
+                Some("main.rs"),
+                true,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(frame_id > 0 && chunk_id > 0);
+        source
+            .insert_ocr_text(
+                frame_id,
+                "fn main() {}",
+                "",
+                Arc::new(screenpipe_db::OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+
+        let output = dir.path().join("export.zip");
+        let manifest = export_archive(&source, "laptop-a", &ExportFilter::default(), &output)
+            .await
+            .unwrap();
+        assert_eq!(manifest.counts.frames, 1);
+        assert_eq!(manifest.counts.ocr_text, 1);
+        assert!(manifest.media.is_empty());
+
+        let target = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        let first = import_archive(target.clone(), "laptop-b", &output, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(first.frames, 1);
+        assert_eq!(first.ocr, 1);
+
+        // importing the same archive again must not duplicate anything
+        let second = import_archive(target.clone(), "laptop-b", &output, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(second.frames, 0);
+        assert_eq!(second.ocr, 0);
+
+        let frames: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM frames")
+            .fetch_one(&target.pool)
+            .await
+            .unwrap();
+        assert_eq!(frames, 1);
+
+        // exporting on the machine that recorded the data is a no-op on import
+        let own = import_archive(target, "laptop-a", &output, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(own.frames, 0);
+    }
+}
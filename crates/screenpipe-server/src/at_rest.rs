@@ -0,0 +1,429 @@
+//! Encryption at rest for media files
+//!
+//! `MediaSealer` encrypts finished video and audio chunks in the data directory in place
+//! (see `screenpipe_core::at_rest`). A chunk counts as finished once it hasn't been
+//! written to for `SEAL_AFTER` and a newer chunk of the same monitor or device exists,
+//! so the files ffmpeg is still writing are left alone.
+//! File paths in the database don't change; readers decrypt through `readable_path`.
+
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+};
+use chrono::NaiveDateTime;
+use futures::StreamExt;
+use screenpipe_core::at_rest::{self, readable_path, ReadablePath};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use tracing::{debug, error, info, warn};
+
+use crate::server::{api_error, ApiError, AppState};
+
+/// Time since the last write after which a media file is sealed.
+const SEAL_AFTER: Duration = Duration::from_secs(5 * 60);
+
+const SEAL_INTERVAL: Duration = Duration::from_secs(60);
+
+const MEDIA_EXTENSIONS: &[&str] = &["mp4", "m4a", "mp3", "wav"];
+
+/// Length of the `%Y-%m-%d_%H-%M-%S` time at the end of chunk names
+const CHUNK_TIME_FORMAT_LEN: usize = 19;
+
+/// Seals finished media files.
+pub struct MediaSealer {
+    data_dir: PathBuf,
+}
+
+impl MediaSealer {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self { data_dir }
+    }
+
+    /// Seal media files forever, every minute.
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SEAL_INTERVAL);
+            loop {
+                interval.tick().await;
+                let sealer = self.clone();
+                match tokio::task::spawn_blocking(move || sealer.seal_finished()).await {
+                    Ok(0) => {}
+                    Ok(n) => info!("encrypted {} media files", n),
+                    Err(e) => error!("media encryption pass failed: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Seal every media file not written to for `SEAL_AFTER`, returns how many were sealed.
+    pub fn seal_finished(&self) -> usize {
+        let Some(key) = at_rest::media_key() else {
+            return 0;
+        };
+        let files = media_files(&self.data_dir);
+        // ffmpeg may still be writing the last chunk of a paused recording
+        let latest = latest_chunks(&files);
+        let mut sealed = 0;
+        for path in files {
+            if latest.contains(&path) {
+                continue;
+            }
+            let finished = std::fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age >= SEAL_AFTER);
+            if !finished {
+                continue;
+            }
+            match at_rest::seal_file(&path, key) {
+                Ok(true) => {
+                    debug!("encrypted {}", path.display());
+                    sealed += 1;
+                }
+                Ok(false) => {}
+                // e.g. the file is open elsewhere on windows, retried next pass
+                Err(e) => warn!("failed to encrypt {}: {}", path.display(), e),
+            }
+        }
+        sealed
+    }
+}
+
+/// Media files under `dir`, recursively.
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+fn media_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                // skips the decrypted copies in `.decrypted`
+                Ok(t) if t.is_dir() && !is_hidden(&path) => dirs.push(path),
+                Ok(t) if t.is_file() && is_media(&path) => files.push(path),
+                _ => {}
+            }
+        }
+    }
+    files
+}
+
+/// The newest chunk of every recording source (monitor or audio device).
+///
+/// Chunks are named `<source>_<%Y-%m-%d_%H-%M-%S>.<ext>`, other files have no source.
+fn latest_chunks(files: &[PathBuf]) -> HashSet<PathBuf> {
+    let mut latest: HashMap<(Option<&Path>, &str), &PathBuf> = HashMap::new();
+    for path in files {
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let Some(split) = stem.len().checked_sub(CHUNK_TIME_FORMAT_LEN + 1) else {
+            continue;
+        };
+        let (Some(source), Some(time)) = (stem.get(..split), stem.get(split + 1..)) else {
+            continue;
+        };
+        if NaiveDateTime::parse_from_str(time, "%Y-%m-%d_%H-%M-%S").is_err() {
+            continue;
+        }
+        let entry = latest.entry((path.parent(), source)).or_insert(path);
+        if path.file_name() > entry.file_name() {
+            *entry = path;
+        }
+    }
+    latest.into_values().cloned().collect()
+}
+
+fn is_media(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| MEDIA_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+// ============================================================================
+// API
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct MediaFileQuery {
+    /// A video or audio file path as returned by /search
+    pub path: String,
+}
+
+/// Serve a recorded video or audio file, decrypted when it is encrypted at rest.
+///
+/// Only files inside the data directory are served. The file is streamed, and a
+/// single `Range` of bytes is honoured so players can seek.
+pub async fn media_file_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<MediaFileQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let not_found = || api_error(StatusCode::NOT_FOUND, "media file not found");
+    let data_dir = state
+        .screenpipe_dir
+        .join("data")
+        .canonicalize()
+        .map_err(|_| not_found())?;
+    let path = Path::new(&query.path)
+        .canonicalize()
+        .map_err(|_| not_found())?;
+    if !path.starts_with(&data_dir) || !is_media(&path) {
+        return Err(api_error(
+            StatusCode::FORBIDDEN,
+            "only media files of the data directory can be read",
+        ));
+    }
+
+    let readable = readable_path(&path).await.map_err(|e| {
+        error!("failed to decrypt {}: {}", path.display(), e);
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
+    })?;
+    let content_type = match path.extension().and_then(|e| e.to_str()) {
+        Some("mp3") => "audio/mpeg",
+        Some("wav") => "audio/wav",
+        Some("m4a") => "audio/mp4",
+        _ => "video/mp4",
+    };
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok());
+    media_response(readable, content_type, range).await
+}
+
+/// A `Range` header resolved against the length of a file.
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    /// No range, or one we don't serve (several ranges, other units): the whole file
+    Full,
+    /// First and last byte, inclusive
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+fn byte_range(range: Option<&str>, len: u64) -> ByteRange {
+    let Some(spec) = range.and_then(|range| range.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (start, end) = match (start.trim(), end.trim()) {
+        // the last `end` bytes
+        ("", end) => match end.parse::<u64>() {
+            Ok(0) | Err(_) => return ByteRange::Unsatisfiable,
+            Ok(suffix) => (len.saturating_sub(suffix), len.saturating_sub(1)),
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+            _ => return ByteRange::Full,
+        },
+    };
+    if start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial(start, end)
+}
+
+async fn media_response(
+    readable: ReadablePath,
+    content_type: &str,
+    range: Option<&str>,
+) -> Result<Response, ApiError> {
+    let internal = |e: std::io::Error| api_error(StatusCode::INTERNAL_SERVER_ERROR, e);
+    let mut file = tokio::fs::File::open(readable.path())
+        .await
+        .map_err(internal)?;
+    let len = file.metadata().await.map_err(internal)?.len();
+
+    let response = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::ACCEPT_RANGES, "bytes");
+    let (response, start, end) = match byte_range(range, len) {
+        ByteRange::Full => (response.status(StatusCode::OK), 0, len),
+        ByteRange::Partial(start, end) => (
+            response.status(StatusCode::PARTIAL_CONTENT).header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, len),
+            ),
+            start,
+            end + 1,
+        ),
+        ByteRange::Unsatisfiable => {
+            return Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Body::empty())
+                .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e));
+        }
+    };
+
+    file.seek(SeekFrom::Start(start)).await.map_err(internal)?;
+    // the decrypted copy lives as long as its reader, so keep it until the body is sent
+    let stream = ReaderStream::new(file.take(end - start)).map(move |chunk| {
+        let _readable = &readable;
+        chunk
+    });
+    response
+        .header(header::CONTENT_LENGTH, end - start)
+        .body(Body::from_stream(stream))
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_chunks() {
+        let files: Vec<PathBuf> = [
+            "data/monitor_1_2024-10-19_02-51-20.mp4",
+            "data/monitor_1_2024-10-19_02-52-20.mp4",
+            "data/monitor_2_2024-10-19_02-51-20.mp4",
+            "data/MacBook Pro Microphone (input)_2024-10-19_02-51-30.mp4",
+            "data/MacBook Pro Microphone (input)_2024-10-19_02-50-30.mp4",
+            "data/imported.mp4",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+
+        let mut latest: Vec<_> = latest_chunks(&files).into_iter().collect();
+        latest.sort();
+        assert_eq!(
+            latest,
+            [
+                PathBuf::from("data/MacBook Pro Microphone (input)_2024-10-19_02-51-30.mp4"),
+                PathBuf::from("data/monitor_1_2024-10-19_02-52-20.mp4"),
+                PathBuf::from("data/monitor_2_2024-10-19_02-51-20.mp4"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_media_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("archive")).unwrap();
+        std::fs::create_dir(dir.path().join(".decrypted")).unwrap();
+        for name in [
+            "monitor_1.mp4",
+            "archive/mic.MP4",
+            ".decrypted/copy.mp4",
+            "notes.txt",
+            "db.sqlite",
+        ] {
+            std::fs::write(dir.path().join(name), b"x").unwrap();
+        }
+
+        let mut files: Vec<_> = media_files(dir.path())
+            .into_iter()
+            .map(|p| p.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                PathBuf::from("archive/mic.MP4"),
+                PathBuf::from("monitor_1.mp4")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_byte_range() {
+        assert_eq!(byte_range(None, 100), ByteRange::Full);
+        assert_eq!(byte_range(Some("bytes=0-"), 100), ByteRange::Partial(0, 99));
+        assert_eq!(
+            byte_range(Some("bytes=10-19"), 100),
+            ByteRange::Partial(10, 19)
+        );
+        assert_eq!(
+            byte_range(Some("bytes=90-200"), 100),
+            ByteRange::Partial(90, 99)
+        );
+        assert_eq!(
+            byte_range(Some("bytes=-10"), 100),
+            ByteRange::Partial(90, 99)
+        );
+        assert_eq!(
+            byte_range(Some("bytes=-200"), 100),
+            ByteRange::Partial(0, 99)
+        );
+        assert_eq!(byte_range(Some("bytes=0-1,5-6"), 100), ByteRange::Full);
+        assert_eq!(byte_range(Some("items=0-1"), 100), ByteRange::Full);
+        assert_eq!(
+            byte_range(Some("bytes=100-"), 100),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(byte_range(Some("bytes=-0"), 100), ByteRange::Unsatisfiable);
+        assert_eq!(byte_range(Some("bytes=0-"), 0), ByteRange::Unsatisfiable);
+    }
+
+    #[tokio::test]
+    async fn test_media_response() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("monitor_1.mp4");
+        let content: Vec<u8> = (0..=255).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        let response = media_response(readable_path(&path).await.unwrap(), "video/mp4", None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "256");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, content);
+
+        let response = media_response(
+            readable_path(&path).await.unwrap(),
+            "video/mp4",
+            Some("bytes=16-31"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 16-31/256");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "16");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, content[16..32]);
+
+        let response = media_response(
+            readable_path(&path).await.unwrap(),
+            "video/mp4",
+            Some("bytes=256-"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */256");
+    }
+}
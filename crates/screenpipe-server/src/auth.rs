@@ -0,0 +1,517 @@
+//! API authentication
+//!
+//! Optional bearer tokens for the HTTP server. Tokens carry scopes and are checked by
+//! a middleware against the scope each route requires. Tokens created with
+//! `screenpipe token create` are stored hashed (SHA-256) in `api_tokens.json` in the
+//! data dir, the server picks up changes to the file without a restart. Pipes get an
+//! in-memory token when they start, revoked when they stop, also checked against the
+//! permissions declared in their pipe.json.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use screenpipe_core::PipePermissions;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+/// File of the persisted tokens, relative to the data dir.
+pub const TOKENS_FILE: &str = "api_tokens.json";
+
+/// Environment variable holding the token of a pipe, also read by the CLI.
+pub const TOKEN_ENV: &str = "SCREENPIPE_API_TOKEN";
+
+const TOKEN_PREFIX: &str = "sp_";
+
+/// What a token gives access to. `admin` grants every scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    /// Search, speakers, meetings, app usage, UI events and device lists
+    #[serde(rename = "read:search")]
+    ReadSearch,
+    /// Frames, video streams, media files and exports
+    #[serde(rename = "read:media")]
+    ReadMedia,
+    /// `/metrics`, for monitoring systems
+    #[serde(rename = "read:metrics")]
+    ReadMetrics,
+    /// Adding and removing tags
+    #[serde(rename = "write:tags")]
+    WriteTags,
+    /// Installing, configuring and running pipes
+    #[serde(rename = "admin:pipes")]
+    AdminPipes,
+    /// `/raw_sql` and `/raw_sql/write`
+    #[serde(rename = "admin:raw_sql")]
+    AdminRawSql,
+    /// Everything, including recording control, sync and retention
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 7] = [
+        Scope::ReadSearch,
+        Scope::ReadMedia,
+        Scope::ReadMetrics,
+        Scope::WriteTags,
+        Scope::AdminPipes,
+        Scope::AdminRawSql,
+        Scope::Admin,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ReadSearch => "read:search",
+            Scope::ReadMedia => "read:media",
+            Scope::ReadMetrics => "read:metrics",
+            Scope::WriteTags => "write:tags",
+            Scope::AdminPipes => "admin:pipes",
+            Scope::AdminRawSql => "admin:raw_sql",
+            Scope::Admin => "admin",
+        }
+    }
+
+    /// `admin` and the `admin:*` scopes, never granted to pipes.
+    pub fn is_admin(&self) -> bool {
+        matches!(self, Scope::AdminPipes | Scope::AdminRawSql | Scope::Admin)
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Scope::ALL.iter().map(Scope::as_str).collect();
+                format!(
+                    "unknown scope '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Scope required by a request, `None` for public routes.
+pub fn required_scope(method: &Method, path: &str) -> Option<Scope> {
+    if method == Method::OPTIONS {
+        return None;
+    }
+
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let is_read = method == Method::GET || method == Method::HEAD;
+    match segments.as_slice() {
+        ["health"] | ["ws", "health"] | ["vision", "status"] => None,
+        ["openapi.yaml"] | ["openapi.json"] => None,
+        ["metrics"] => Some(Scope::ReadMetrics),
+        ["raw_sql", ..] => Some(Scope::AdminRawSql),
+        ["pipes", ..] => Some(Scope::AdminPipes),
+        ["tags", ..] => Some(Scope::WriteTags),
+        ["frames", ..] | ["stream", "frames"] | ["experimental", ..] | ["media", "file"] => {
+            Some(Scope::ReadMedia)
+        }
+        ["search", ..] | ["semantic-search"] | ["ui-events", ..] | ["ws", "events"] => {
+            Some(Scope::ReadSearch)
+        }
+        ["audio", "list"] | ["audio", "transcript"] | ["vision", "list"] | ["v1", "embeddings"] => {
+            Some(Scope::ReadSearch)
+        }
+        ["speakers", ..] | ["meetings", ..] if is_read => Some(Scope::ReadSearch),
+        ["activity", "summary"] => Some(Scope::ReadSearch),
+        _ => Some(Scope::Admin),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    /// Hex SHA-256 of the secret
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    hash: String,
+    /// Pipe tokens only live in memory
+    #[serde(skip)]
+    ephemeral: bool,
+    /// Permissions of the pipe holding the token
+    #[serde(skip)]
+    permissions: Option<Arc<PipePermissions>>,
+}
+
+impl ApiToken {
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope)
+    }
+
+    /// The token without its hash, for listings.
+    pub fn redacted(&self) -> ApiToken {
+        ApiToken {
+            hash: String::new(),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Default)]
+struct Tokens {
+    tokens: Vec<ApiToken>,
+    /// Modification time and size of the file when it was last read
+    loaded_version: Option<(SystemTime, u64)>,
+}
+
+/// API tokens of a data dir.
+pub struct TokenStore {
+    path: PathBuf,
+    inner: RwLock<Tokens>,
+}
+
+impl TokenStore {
+    pub fn open(data_dir: &Path) -> anyhow::Result<Self> {
+        let store = Self {
+            path: data_dir.join(TOKENS_FILE),
+            inner: RwLock::new(Tokens::default()),
+        };
+        store.reload()?;
+        Ok(store)
+    }
+
+    /// Create a persisted token, returns it with its secret. The secret can't be
+    /// recovered later.
+    pub fn create(&self, name: &str, scopes: Vec<Scope>) -> anyhow::Result<(ApiToken, String)> {
+        self.reload()?;
+        let (token, secret) = new_token(name, scopes, false);
+        let mut inner = self.inner.write().unwrap();
+        inner.tokens.push(token.clone());
+        self.save(&mut inner)?;
+        Ok((token.redacted(), secret))
+    }
+
+    /// Create an in-memory token, e.g. for a pipe. Revoke it with [`TokenStore::revoke`].
+    pub fn issue_ephemeral(&self, name: &str, scopes: Vec<Scope>) -> (ApiToken, String) {
+        let (token, secret) = new_token(name, scopes, true);
+        self.inner.write().unwrap().tokens.push(token.clone());
+        (token.redacted(), secret)
+    }
+
+    /// Like [`TokenStore::issue_ephemeral`], for a pipe whose requests must stay within
+    /// `permissions`.
+    pub fn issue_restricted(
+        &self,
+        name: &str,
+        scopes: Vec<Scope>,
+        permissions: PipePermissions,
+    ) -> (ApiToken, String) {
+        let (mut token, secret) = new_token(name, scopes, true);
+        token.permissions = Some(Arc::new(permissions));
+        self.inner.write().unwrap().tokens.push(token.clone());
+        (token.redacted(), secret)
+    }
+
+    /// Revoke a token by id, returns false when there is no such token.
+    pub fn revoke(&self, id: &str) -> anyhow::Result<bool> {
+        self.reload()?;
+        let mut inner = self.inner.write().unwrap();
+        let Some(position) = inner.tokens.iter().position(|t| t.id == id) else {
+            return Ok(false);
+        };
+        let token = inner.tokens.remove(position);
+        if !token.ephemeral {
+            self.save(&mut inner)?;
+        }
+        Ok(true)
+    }
+
+    pub fn list(&self) -> anyhow::Result<Vec<ApiToken>> {
+        self.reload()?;
+        Ok(self
+            .inner
+            .read()
+            .unwrap()
+            .tokens
+            .iter()
+            .map(ApiToken::redacted)
+            .collect())
+    }
+
+    /// Token matching `secret`, if any.
+    pub fn authenticate(&self, secret: &str) -> Option<ApiToken> {
+        if let Err(e) = self.reload() {
+            warn!("failed to reload api tokens: {}", e);
+        }
+        let hash = hash_secret(secret);
+        self.inner
+            .read()
+            .unwrap()
+            .tokens
+            .iter()
+            .find(|t| t.hash == hash)
+            .cloned()
+    }
+
+    /// Re-read the file if it changed since it was last read, keeping in-memory tokens.
+    fn reload(&self) -> anyhow::Result<()> {
+        let version = file_version(&self.path)?;
+        if self.inner.read().unwrap().loaded_version == version {
+            return Ok(());
+        }
+
+        let persisted: Vec<ApiToken> = match version {
+            Some(_) => serde_json::from_str(&std::fs::read_to_string(&self.path)?)?,
+            None => Vec::new(),
+        };
+        debug!("loaded {} api tokens from {:?}", persisted.len(), self.path);
+
+        let mut inner = self.inner.write().unwrap();
+        inner.tokens.retain(|t| t.ephemeral);
+        inner.tokens.extend(persisted);
+        inner.loaded_version = version;
+        Ok(())
+    }
+
+    fn save(&self, inner: &mut Tokens) -> anyhow::Result<()> {
+        let persisted: Vec<&ApiToken> = inner.tokens.iter().filter(|t| !t.ephemeral).collect();
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&persisted)?)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&tmp, &self.path)?;
+        inner.loaded_version = file_version(&self.path)?;
+        Ok(())
+    }
+}
+
+fn file_version(path: &Path) -> std::io::Result<Option<(SystemTime, u64)>> {
+    match std::fs::metadata(path) {
+        Ok(metadata) => Ok(Some((metadata.modified()?, metadata.len()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn new_token(name: &str, scopes: Vec<Scope>, ephemeral: bool) -> (ApiToken, String) {
+    let secret = format!(
+        "{}{}{}",
+        TOKEN_PREFIX,
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    let token = ApiToken {
+        id: uuid::Uuid::new_v4().simple().to_string()[..12].to_string(),
+        name: name.to_string(),
+        scopes,
+        created_at: Utc::now(),
+        hash: hash_secret(&secret),
+        ephemeral,
+        permissions: None,
+    };
+    (token, secret)
+}
+
+fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// Secret of a request, from the `Authorization: Bearer` header or, for clients that
+/// can't set headers (websockets, `<video>` tags), the `token` query parameter.
+fn request_secret(req: &Request) -> Option<&str> {
+    if let Some(value) = req.headers().get(header::AUTHORIZATION) {
+        return value.to_str().ok()?.strip_prefix("Bearer ").map(str::trim);
+    }
+    req.uri()
+        .query()?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+}
+
+/// Middleware rejecting requests without a token holding the route's scope. Does
+/// nothing when auth is disabled (`None`).
+pub async fn require_token(
+    State(store): State<Option<Arc<TokenStore>>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(store) = store else {
+        return next.run(req).await;
+    };
+    let Some(scope) = required_scope(req.method(), req.uri().path()) else {
+        return next.run(req).await;
+    };
+
+    let Some(secret) = request_secret(&req) else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            Json(json!({"error": "missing api token"})),
+        )
+            .into_response();
+    };
+    match store.authenticate(secret) {
+        Some(token) if token.allows(scope) => {
+            if let Some(permissions) = &token.permissions {
+                let checked = permissions.check_request(
+                    req.method().as_str(),
+                    req.uri().path(),
+                    req.uri().query(),
+                    Utc::now(),
+                );
+                if let Err(reason) = checked {
+                    debug!("pipe token {} denied: {}", token.id, reason);
+                    return (
+                        StatusCode::FORBIDDEN,
+                        Json(
+                            json!({"error": format!("outside the pipe's permissions: {}", reason)}),
+                        ),
+                    )
+                        .into_response();
+                }
+            }
+            next.run(req).await
+        }
+        Some(token) => {
+            debug!(
+                "token {} lacks scope {} for {}",
+                token.id,
+                scope,
+                req.uri().path()
+            );
+            (
+                StatusCode::FORBIDDEN,
+                Json(json!({"error": format!("token lacks scope {}", scope)})),
+            )
+                .into_response()
+        }
+        None => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            Json(json!({"error": "invalid api token"})),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_scope() {
+        assert_eq!(required_scope(&Method::GET, "/health"), None);
+        assert_eq!(
+            required_scope(&Method::GET, "/search"),
+            Some(Scope::ReadSearch)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/frames/42"),
+            Some(Scope::ReadMedia)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/media/file"),
+            Some(Scope::ReadMedia)
+        );
+        assert_eq!(
+            required_scope(&Method::DELETE, "/tags/vision/1"),
+            Some(Scope::WriteTags)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/raw_sql"),
+            Some(Scope::AdminRawSql)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/metrics"),
+            Some(Scope::ReadMetrics)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/speakers/search"),
+            Some(Scope::ReadSearch)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/speakers/merge"),
+            Some(Scope::Admin)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/meetings/3"),
+            Some(Scope::ReadSearch)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/meetings/3/split"),
+            Some(Scope::Admin)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/audio/stop"),
+            Some(Scope::Admin)
+        );
+    }
+
+    #[test]
+    fn test_token_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TokenStore::open(dir.path()).unwrap();
+
+        let (token, secret) = store.create("dashboard", vec![Scope::ReadSearch]).unwrap();
+        let (pipe_token, pipe_secret) = store.issue_ephemeral("pipe:test", vec![Scope::ReadMedia]);
+
+        let found = store.authenticate(&secret).unwrap();
+        assert_eq!(found.id, token.id);
+        assert!(found.allows(Scope::ReadSearch));
+        assert!(!found.allows(Scope::ReadMedia));
+        assert!(store.authenticate("sp_wrong").is_none());
+
+        // secrets are not written to disk, pipe tokens not at all
+        let file = std::fs::read_to_string(dir.path().join(TOKENS_FILE)).unwrap();
+        assert!(!file.contains(&secret));
+        assert!(!file.contains(&pipe_token.id));
+
+        // a second store (the CLI) sees the token, revocation reaches the first one
+        let cli = TokenStore::open(dir.path()).unwrap();
+        assert!(cli.authenticate(&secret).is_some());
+        assert!(cli.revoke(&token.id).unwrap());
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert!(store.authenticate(&secret).is_none());
+        assert!(store.authenticate(&pipe_secret).is_some());
+
+        assert!(store.revoke(&pipe_token.id).unwrap());
+        assert!(store.authenticate(&pipe_secret).is_none());
+    }
+
+    #[test]
+    fn test_restricted_token_keeps_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TokenStore::open(dir.path()).unwrap();
+        let permissions = PipePermissions {
+            endpoints: vec!["GET /search".to_string()],
+            ..Default::default()
+        };
+        let (_, secret) =
+            store.issue_restricted("pipe:test", vec![Scope::ReadSearch], permissions.clone());
+
+        let token = store.authenticate(&secret).unwrap();
+        assert_eq!(token.permissions.as_deref(), Some(&permissions));
+        // pipe tokens are never written to disk
+        assert!(!dir.path().join(TOKENS_FILE).exists());
+    }
+}
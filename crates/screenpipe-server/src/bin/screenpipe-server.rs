@@ -12,28 +12,37 @@ use screenpipe_audio::{
         default_input_device, default_output_device, list_audio_devices, parse_audio_device,
     },
 };
+use screenpipe_core::at_rest;
 use screenpipe_core::find_ffmpeg_path;
 use screenpipe_core::sync::{
-    BlobType, SyncClientConfig, SyncEvent, SyncManager, SyncService, SyncServiceConfig,
+    BlobType, S3Config, SyncBackend, SyncEvent, SyncManager, SyncService, SyncServiceConfig,
 };
+use screenpipe_core::{LogStream, PipeLogLine, PipePermissions};
 use screenpipe_db::{
     create_migration_worker, DatabaseManager, MigrationCommand, MigrationConfig, MigrationStatus,
 };
 use screenpipe_server::{
     analytics,
+    archive::{export_archive, import_archive, ExportFilter},
+    at_rest::MediaSealer,
+    auth::{TokenStore, TOKEN_ENV},
     cli::{
         get_or_create_machine_id, AudioCommand, Cli, CliAudioTranscriptionEngine, CliOcrEngine,
-        Command, McpCommand, MigrationSubCommand, OutputFormat, PipeCommand, SyncCommand,
-        VisionCommand,
+        CliSyncBackend, Command, McpCommand, McpTransport, MigrationSubCommand, OutputFormat,
+        PipeCommand, SyncCommand, TokenCommand, VisionCommand,
     },
+    embedding::indexer::EmbeddingIndexer,
     handle_index_command,
+    mcp::{serve_http, serve_stdio, McpServer},
+    metrics_api,
     pipe_manager::PipeInfo,
     start_continuous_recording, start_sleep_monitor, start_ui_recording,
     sync_provider::ScreenpipeSyncProvider,
+    transcript::{build_transcript, render, TranscriptFilter},
     vision_manager::{
         start_monitor_watcher, stop_monitor_watcher, VisionManager, VisionManagerConfig,
     },
-    watch_pid, PipeManager, ResourceMonitor, SCServer,
+    watch_pid, AudioImportOptions, PipeManager, ResourceMonitor, RetentionManager, SCServer,
 };
 use screenpipe_vision::monitor::list_monitors;
 use serde::Deserialize;
@@ -151,6 +160,22 @@ fn get_base_dir(custom_path: &Option<String>) -> anyhow::Result<PathBuf> {
     Ok(base_dir)
 }
 
+/// Open `db.sqlite` of `data_dir`, encrypted when encryption at rest is enabled.
+async fn open_database(cli: &Cli, data_dir: &Path) -> anyhow::Result<DatabaseManager> {
+    let keys = cli.at_rest_keys(data_dir)?;
+    if let Some(keys) = &keys {
+        at_rest::install_media_key(keys);
+        at_rest::init_decrypted_dir(data_dir)?;
+    }
+    let db_key = keys.as_ref().map(|keys| keys.database_key_pragma());
+    let db = DatabaseManager::new_with_key(
+        &format!("{}/db.sqlite", data_dir.to_string_lossy()),
+        db_key.as_ref().map(|key| key.as_str()),
+    )
+    .await?;
+    Ok(db)
+}
+
 fn setup_logging(local_data_dir: &PathBuf, cli: &Cli) -> anyhow::Result<WorkerGuard> {
     let file_appender = RollingFileAppender::builder()
         .rotation(Rotation::DAILY)
@@ -309,6 +334,7 @@ async fn main() -> anyhow::Result<()> {
                 map.insert("use_all_monitors".into(), json!(cli.use_all_monitors));
                 map.insert("languages".into(), json!(cli.language.iter().map(|l| format!("{:?}", l)).collect::<Vec<_>>()));
                 map.insert("use_pii_removal".into(), json!(cli.use_pii_removal));
+                map.insert("redact_pii_at_capture".into(), json!(cli.redact_pii_at_capture));
                 map.insert("disable_vision".into(), json!(cli.disable_vision));
                 map.insert("vad_engine".into(), json!(format!("{:?}", cli.vad_engine)));
                 map.insert("vad_sensitivity".into(), json!(format!("{:?}", cli.vad_sensitivity)));
@@ -320,6 +346,7 @@ async fn main() -> anyhow::Result<()> {
                 map.insert("enable_ui_events".into(), json!(cli.enable_ui_events));
                 map.insert("enable_sync".into(), json!(cli.enable_sync));
                 map.insert("sync_interval_secs".into(), json!(cli.sync_interval_secs));
+                map.insert("retention_enabled".into(), json!(cli.to_retention_config().is_enabled()));
                 map.insert("debug".into(), json!(cli.debug));
                 // Only send counts for privacy-sensitive lists (not actual values)
                 map.insert("audio_device_count".into(), json!(cli.audio_device.len()));
@@ -368,6 +395,22 @@ async fn main() -> anyhow::Result<()> {
             output: OutputFormat::Text,
             ..
         }) => true,
+        // token secrets are printed to stdout
+        Some(Command::Token { .. }) => false,
+        Some(Command::Forget {
+            output: OutputFormat::Json,
+            ..
+        }) => false,
+        // so is the transcript, unless written to a file
+        Some(Command::Transcript { output, .. }) => output.is_some(),
+        // stdout is the protocol channel for the stdio transport
+        Some(Command::Mcp {
+            subcommand:
+                McpCommand::Serve {
+                    transport: McpTransport::Stdio,
+                    ..
+                },
+        }) => false,
         _ => true,
     };
 
@@ -378,8 +421,16 @@ async fn main() -> anyhow::Result<()> {
         None
     };
 
+    let token_store = if cli.enable_auth {
+        Some(Arc::new(TokenStore::open(&local_data_dir)?))
+    } else {
+        None
+    };
+
     let pipe_manager = if cli.enable_pipe_manager {
-        Arc::new(PipeManager::new(local_data_dir_clone.clone()))
+        Arc::new(
+            PipeManager::new(local_data_dir_clone.clone()).with_token_store(token_store.clone()),
+        )
     } else {
         Arc::new(PipeManager::new(PathBuf::from("")))
     };
@@ -464,17 +515,10 @@ async fn main() -> anyhow::Result<()> {
             } => {
                 // Initialize the database
                 let local_data_dir = get_base_dir(data_dir)?;
-                let db = Arc::new(
-                    DatabaseManager::new(&format!(
-                        "{}/db.sqlite",
-                        local_data_dir.to_string_lossy()
-                    ))
-                    .await
-                    .map_err(|e| {
-                        error!("failed to initialize database: {:?}", e);
-                        e
-                    })?,
-                );
+                let db = Arc::new(open_database(&cli, &local_data_dir).await.map_err(|e| {
+                    error!("failed to initialize database: {:?}", e);
+                    e
+                })?);
 
                 // Create a migration worker config
                 let config = MigrationConfig::new(*batch_size, *batch_delay_ms, *continue_on_error);
@@ -645,6 +689,7 @@ async fn main() -> anyhow::Result<()> {
                 ocr_engine,
                 metadata_override,
                 copy_videos,
+                transcribe_videos,
                 debug,
                 use_embedding,
             } => {
@@ -664,17 +709,10 @@ async fn main() -> anyhow::Result<()> {
                     debug!("debug logging enabled");
                 }
 
-                let db = Arc::new(
-                    DatabaseManager::new(&format!(
-                        "{}/db.sqlite",
-                        local_data_dir.to_string_lossy()
-                    ))
-                    .await
-                    .map_err(|e| {
-                        error!("failed to initialize database: {:?}", e);
-                        e
-                    })?,
-                );
+                let db = Arc::new(open_database(&cli, &local_data_dir).await.map_err(|e| {
+                    error!("failed to initialize database: {:?}", e);
+                    e
+                })?);
                 handle_index_command(
                     local_data_dir,
                     path.to_string(),
@@ -685,18 +723,128 @@ async fn main() -> anyhow::Result<()> {
                     metadata_override.clone(),
                     *copy_videos,
                     *use_embedding,
+                    AudioImportOptions {
+                        transcription_engine: Arc::new(
+                            cli.audio_transcription_engine.clone().into(),
+                        ),
+                        vad_engine: cli.vad_engine.clone().into(),
+                        deepgram_api_key: cli.deepgram_api_key.clone(),
+                        languages: cli.language.clone(),
+                        use_pii_removal: cli.use_pii_removal,
+                        transcribe_videos: *transcribe_videos,
+                    },
+                )
+                .await?;
+                return Ok(());
+            }
+            Command::Export {
+                output,
+                start_time,
+                end_time,
+                app_name,
+                device_name,
+                no_media,
+                data_dir,
+            } => {
+                let local_data_dir = get_base_dir(data_dir)?;
+                let db = open_database(&cli, &local_data_dir).await?;
+                let filter = ExportFilter {
+                    start_time: *start_time,
+                    end_time: *end_time,
+                    app_name: app_name.clone(),
+                    device_name: device_name.clone(),
+                    include_media: !*no_media,
+                };
+                let manifest =
+                    export_archive(&db, &get_or_create_machine_id(None), &filter, output).await?;
+                println!(
+                    "exported {} frames, {} transcriptions, {} accessibility records, {} ui events and {} media files to {}",
+                    manifest.counts.frames,
+                    manifest.counts.audio_transcriptions,
+                    manifest.counts.accessibility,
+                    manifest.counts.ui_events,
+                    manifest.media.len(),
+                    output.display()
+                );
+                return Ok(());
+            }
+            Command::Import { archive, data_dir } => {
+                let local_data_dir = get_base_dir(data_dir)?;
+                let db = Arc::new(open_database(&cli, &local_data_dir).await?);
+                let summary = import_archive(
+                    db,
+                    &get_or_create_machine_id(None),
+                    archive,
+                    &local_data_dir,
                 )
                 .await?;
+                println!(
+                    "imported {} frames, {} transcriptions, {} accessibility records, {} ui events and {} media files ({} already present)",
+                    summary.frames,
+                    summary.transcriptions,
+                    summary.accessibility,
+                    summary.ui_events,
+                    summary.media_files,
+                    summary.skipped
+                );
+                return Ok(());
+            }
+            Command::Transcript {
+                start_time,
+                end_time,
+                device_name,
+                speaker_ids,
+                speaker_name,
+                format,
+                output,
+                data_dir,
+            } => {
+                let local_data_dir = get_base_dir(data_dir)?;
+                let db = open_database(&cli, &local_data_dir).await?;
+                let filter = TranscriptFilter {
+                    start_time: *start_time,
+                    end_time: *end_time,
+                    device_name: device_name.clone(),
+                    speaker_ids: speaker_ids.clone(),
+                    speaker_name: speaker_name.clone(),
+                };
+                let entries = build_transcript(&db, &filter).await?;
+                let transcript = render(&entries, *format);
+                match output {
+                    Some(path) => {
+                        std::fs::write(path, transcript)?;
+                        println!(
+                            "wrote {} transcript entries to {}",
+                            entries.len(),
+                            path.display()
+                        );
+                    }
+                    None => print!("{}", transcript),
+                }
+                return Ok(());
+            }
+            Command::Forget {
+                start_time,
+                end_time,
+                query,
+                output,
+                port,
+            } => {
+                handle_forget_command(*start_time, *end_time, query.clone(), output, *port).await?;
                 return Ok(());
             }
             Command::Mcp { subcommand } => {
-                handle_mcp_command(subcommand, &local_data_dir_clone).await?;
+                handle_mcp_command(subcommand, &local_data_dir_clone, &cli).await?;
                 return Ok(());
             }
             Command::Sync { subcommand } => {
                 handle_sync_command(subcommand).await?;
                 return Ok(());
             }
+            Command::Token { subcommand } => {
+                handle_token_command(subcommand)?;
+                return Ok(());
+            }
         }
     }
 
@@ -768,6 +916,8 @@ async fn main() -> anyhow::Result<()> {
     }
 
     let audio_devices_clone = audio_devices.clone();
+    // Before recording starts, so /metrics counts from the first frame
+    metrics_api::install();
     let resource_monitor = ResourceMonitor::new(!cli.disable_telemetry);
     resource_monitor.start_monitoring(Duration::from_secs(30), Some(Duration::from_secs(60)));
 
@@ -782,14 +932,27 @@ async fn main() -> anyhow::Result<()> {
     // This tracks sleep/wake events and checks if recording is degraded after wake
     start_sleep_monitor();
 
-    let db = Arc::new(
-        DatabaseManager::new(&format!("{}/db.sqlite", local_data_dir.to_string_lossy()))
-            .await
-            .map_err(|e| {
-                eprintln!("failed to initialize database: {:?}", e);
-                e
-            })?,
-    );
+    let db = Arc::new(open_database(&cli, &local_data_dir).await.map_err(|e| {
+        eprintln!("failed to initialize database: {:?}", e);
+        e
+    })?);
+
+    // WebAssembly pipes search the database in-process
+    let pipe_manager = if cli.enable_pipe_manager {
+        Arc::new(
+            PipeManager::new(local_data_dir_clone.clone())
+                .with_token_store(token_store.clone())
+                .with_database(db.clone()),
+        )
+    } else {
+        pipe_manager
+    };
+
+    // Seal finished video and audio chunks when encryption at rest is enabled
+    if at_rest::media_key().is_some() {
+        Arc::new(MediaSealer::new(local_data_dir.join("data"))).start();
+        info!("encryption at rest enabled");
+    }
 
     // Start cloud sync service if enabled
     let sync_service_handle = if cli.enable_sync {
@@ -807,6 +970,22 @@ async fn main() -> anyhow::Result<()> {
         None
     };
 
+    // Start the retention policy if any rule is configured
+    let retention_config = cli.to_retention_config();
+    let retention_manager = if retention_config.is_enabled() {
+        let manager = Arc::new(RetentionManager::new(db.clone(), retention_config));
+        manager.clone().start();
+        info!("retention policy enabled");
+        Some(manager)
+    } else {
+        None
+    };
+
+    if cli.enable_embedding_index {
+        Arc::new(EmbeddingIndexer::new(db.clone())).start();
+        info!("embedding index enabled");
+    }
+
     let db_server = db.clone();
 
     let warning_ocr_engine_clone = cli.ocr_engine.clone();
@@ -869,6 +1048,10 @@ async fn main() -> anyhow::Result<()> {
         }
     };
 
+    // The activity feed started for adaptive FPS also gives idle time to the activity tracker
+    #[cfg(feature = "adaptive-fps")]
+    let mut tracker_activity_feed: screenpipe_vision::ActivityFeedOption = None;
+
     // Create VisionManager for dynamic monitor detection if enabled
     let vision_manager: Option<Arc<VisionManager>> = if cli.use_all_monitors && !cli.disable_vision
     {
@@ -895,6 +1078,11 @@ async fn main() -> anyhow::Result<()> {
             None
         };
 
+        #[cfg(feature = "adaptive-fps")]
+        {
+            tracker_activity_feed = activity_feed.clone();
+        }
+
         #[cfg(not(feature = "adaptive-fps"))]
         let activity_feed: screenpipe_vision::ActivityFeedOption = None;
 
@@ -904,6 +1092,7 @@ async fn main() -> anyhow::Result<()> {
             video_chunk_duration: Duration::from_secs(cli.video_chunk_duration),
             ocr_engine: Arc::new(cli.ocr_engine.clone().into()),
             use_pii_removal: cli.use_pii_removal,
+            redact_pii_at_capture: cli.redact_pii_at_capture,
             ignored_windows: cli.ignored_windows.clone(),
             included_windows: cli.included_windows.clone(),
             ignored_urls: cli.ignored_urls.clone(),
@@ -974,6 +1163,11 @@ async fn main() -> anyhow::Result<()> {
             None
         };
 
+        #[cfg(feature = "adaptive-fps")]
+        {
+            tracker_activity_feed = activity_feed_legacy.clone();
+        }
+
         #[cfg(not(feature = "adaptive-fps"))]
         let activity_feed_legacy: screenpipe_vision::ActivityFeedOption = None;
 
@@ -990,6 +1184,7 @@ async fn main() -> anyhow::Result<()> {
                     Arc::new(cli.ocr_engine.clone().into()),
                     monitor_ids_clone.clone(),
                     cli.use_pii_removal,
+                    cli.redact_pii_at_capture,
                     cli.disable_vision,
                     &vision_handle,
                     &cli.ignored_windows,
@@ -1054,6 +1249,21 @@ async fn main() -> anyhow::Result<()> {
         server
     };
 
+    let server = if let Some(ref manager) = retention_manager {
+        server.with_retention(manager.clone())
+    } else {
+        server
+    };
+
+    let server = server
+        .with_raw_sql_write(cli.enable_raw_sql_write)
+        .with_embedding_index(cli.enable_embedding_index)
+        .with_auth(token_store.clone())
+        .with_cors_origins(&cli.cors_origins);
+
+    #[cfg(feature = "adaptive-fps")]
+    let server = server.with_activity_feed(tracker_activity_feed);
+
     // print screenpipe in gradient
     println!("\n\n{}", DISPLAY.truecolor(147, 112, 219).bold());
     println!(
@@ -1114,6 +1324,10 @@ async fn main() -> anyhow::Result<()> {
     println!("│ local llm              │ {:<34} │", cli.enable_llm);
 
     println!("│ use pii removal        │ {:<34} │", cli.use_pii_removal);
+    println!(
+        "│ redact pii at capture  │ {:<34} │",
+        cli.redact_pii_at_capture
+    );
     println!("│ use all monitors       │ {:<34} │", cli.use_all_monitors);
     println!(
         "│ ignored windows        │ {:<34} │",
@@ -1145,6 +1359,28 @@ async fn main() -> anyhow::Result<()> {
             format!("{} seconds", cli.sync_interval_secs)
         );
     }
+    if let Some(ref manager) = retention_manager {
+        let config = manager.config();
+        let days = |d: Option<u32>| d.map_or("forever".to_string(), |d| format!("{} days", d));
+        println!(
+            "│ retention video/audio  │ {:<34} │",
+            format!("{} / {}", days(config.video_days), days(config.audio_days))
+        );
+        println!(
+            "│ retention ocr/audio tx │ {:<34} │",
+            format!(
+                "{} / {}",
+                days(config.ocr_days),
+                days(config.transcription_days)
+            )
+        );
+        if let Some(after_days) = config.downsample_after_days {
+            println!(
+                "│ downsample after       │ {:<34} │",
+                format!("{} days ({} fps)", after_days, config.downsample_fps)
+            );
+        }
+    }
     println!(
         "│ auto-destruct pid      │ {:<34} │",
         cli.auto_destruct_pid.unwrap_or(0)
@@ -1474,11 +1710,31 @@ async fn main() -> anyhow::Result<()> {
         drop(audio_manager);
     });
 
+    if let Err(e) = at_rest::remove_decrypted_dir() {
+        warn!("failed to remove decrypted media copies: {}", e);
+    }
+
     info!("shutdown complete");
 
     Ok(())
 }
 
+fn print_pipe_log_line(line: &PipeLogLine) {
+    let stream = match line.stream {
+        LogStream::Stdout => "out",
+        LogStream::Stderr => "err",
+        LogStream::System => "screenpipe",
+    };
+    println!(
+        "{} {:<10} {}",
+        line.timestamp
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M:%S"),
+        stream,
+        line.line
+    );
+}
+
 async fn handle_pipe_command(
     command: &PipeCommand,
     pipe_manager: &Arc<PipeManager>,
@@ -1489,7 +1745,7 @@ async fn handle_pipe_command(
         return Ok(());
     }
 
-    let client = reqwest::Client::new();
+    let client = api_client()?;
     let server_url = "http://localhost";
 
     match command {
@@ -1551,28 +1807,48 @@ async fn handle_pipe_command(
                     let data: Value = response.json().await?;
                     match output {
                         OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&data)?),
-                        OutputFormat::Text => println!(
-                            "pipe downloaded successfully. id: {}",
-                            data["pipe_id"].as_str().unwrap_or("unknown")
-                        ),
+                        OutputFormat::Text => {
+                            println!(
+                                "pipe downloaded successfully. id: {}",
+                                data["data"]["pipe_id"].as_str().unwrap_or("unknown")
+                            );
+                            print_pipe_permissions(
+                                serde_json::from_value(data["data"]["scopes"].clone())
+                                    .unwrap_or_default(),
+                                serde_json::from_value(data["data"]["permissions"].clone())
+                                    .unwrap_or_default(),
+                            );
+                        }
                     }
                 }
                 _ => match pipe_manager.download_pipe(url).await {
-                    Ok(pipe_id) => match output {
-                        OutputFormat::Json => println!(
-                            "{}",
-                            serde_json::to_string_pretty(&json!({
-                                "data": {
-                                    "pipe_id": pipe_id,
-                                    "message": "pipe downloaded successfully"
-                                },
-                                "success": true
-                            }))?
-                        ),
-                        OutputFormat::Text => {
-                            println!("pipe downloaded successfully. id: {}", pipe_id)
+                    Ok(pipe_id) => {
+                        let permissions = pipe_manager.pipe_permissions(&pipe_id).await?;
+                        let scopes: Vec<String> = pipe_manager
+                            .pipe_scopes(&pipe_id)
+                            .await
+                            .iter()
+                            .map(|scope| scope.to_string())
+                            .collect();
+                        match output {
+                            OutputFormat::Json => println!(
+                                "{}",
+                                serde_json::to_string_pretty(&json!({
+                                    "data": {
+                                        "pipe_id": pipe_id,
+                                        "message": "pipe downloaded successfully",
+                                        "permissions": permissions,
+                                        "scopes": scopes
+                                    },
+                                    "success": true
+                                }))?
+                            ),
+                            OutputFormat::Text => {
+                                println!("pipe downloaded successfully. id: {}", pipe_id);
+                                print_pipe_permissions(scopes, permissions);
+                            }
                         }
-                    },
+                    }
                     Err(e) => {
                         let error_msg = format!("failed to download pipe: {}", e);
                         match output {
@@ -1699,6 +1975,23 @@ async fn handle_pipe_command(
             }
         }
 
+        PipeCommand::Logs { id, lines, follow } => {
+            let mut last = None;
+            for line in pipe_manager.pipe_logs(id, *lines).await? {
+                print_pipe_log_line(&line);
+                last = Some(line.timestamp);
+            }
+            while *follow {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                for line in pipe_manager.pipe_logs(id, 1000).await? {
+                    if last.map_or(true, |last| line.timestamp > last) {
+                        print_pipe_log_line(&line);
+                        last = Some(line.timestamp);
+                    }
+                }
+            }
+        }
+
         PipeCommand::Purge { yes, port } => {
             if !yes {
                 print!("are you sure you want to purge all pipes? this action cannot be undone. (y/N): ");
@@ -1732,20 +2025,32 @@ async fn handle_pipe_command(
 pub async fn handle_mcp_command(
     command: &McpCommand,
     local_data_dir: &PathBuf,
+    cli: &Cli,
 ) -> Result<(), anyhow::Error> {
     let client = Client::new();
 
-    // Check if Python is installed
-    if !is_command_available("python") || !is_command_available("python3") {
-        warn!("note: python is not installed. please install it from the official website: https://www.python.org/");
-    }
-
-    // Check if uv is installed
-    if !is_command_available("uv") {
-        warn!("note: uv is not installed. please install it using the instructions at: https://docs.astral.sh/uv/#installation");
-    }
-
     match command {
+        McpCommand::Serve {
+            transport,
+            port,
+            host,
+            data_dir,
+        } => {
+            let local_data_dir = match data_dir {
+                Some(_) => get_base_dir(data_dir)?,
+                None => local_data_dir.clone(),
+            };
+            let db = Arc::new(open_database(cli, &local_data_dir).await.map_err(|e| {
+                error!("failed to initialize database: {:?}", e);
+                e
+            })?);
+            let server = Arc::new(McpServer::new(db));
+
+            match transport {
+                McpTransport::Stdio => serve_stdio(server).await?,
+                McpTransport::Http => serve_http(server, SocketAddr::new(*host, *port)).await?,
+            }
+        }
         McpCommand::Setup {
             directory,
             output,
@@ -1753,6 +2058,16 @@ pub async fn handle_mcp_command(
             update,
             purge,
         } => {
+            // Check if Python is installed
+            if !is_command_available("python") || !is_command_available("python3") {
+                warn!("note: python is not installed. please install it from the official website: https://www.python.org/");
+            }
+
+            // Check if uv is installed
+            if !is_command_available("uv") {
+                warn!("note: uv is not installed. please install it using the instructions at: https://docs.astral.sh/uv/#installation");
+            }
+
             let mcp_dir = directory
                 .as_ref()
                 .map(PathBuf::from)
@@ -1917,6 +2232,8 @@ pub async fn handle_mcp_command(
                     println!("Config file: {}", config_path.display());
                     println!("\nTo run the MCP server, use this command:");
                     println!("$ {}", run_command);
+                    println!("\nOr use the built-in server (no python/uv needed):");
+                    println!("$ screenpipe mcp serve");
                 }
             }
         }
@@ -2017,15 +2334,44 @@ fn is_command_available(command: &str) -> bool {
         .unwrap_or(false)
 }
 
+/// Build the sync backend selected with `--sync-backend`
+fn sync_backend_from_cli(cli: &Cli) -> anyhow::Result<SyncBackend> {
+    fn required<'a>(value: &'a Option<String>, flag: &str) -> anyhow::Result<&'a String> {
+        value
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("{} required for this sync backend", flag))
+    }
+
+    Ok(match cli.sync_backend {
+        CliSyncBackend::Hosted => SyncBackend::Hosted {
+            token: cli.sync_token.clone().ok_or_else(|| {
+                anyhow::anyhow!("--sync-token or SCREENPIPE_SYNC_TOKEN required for sync")
+            })?,
+            api_url: None,
+        },
+        CliSyncBackend::Directory => SyncBackend::Directory {
+            path: cli.sync_dir.clone().ok_or_else(|| {
+                anyhow::anyhow!("--sync-dir or SCREENPIPE_SYNC_DIR required for directory sync")
+            })?,
+        },
+        CliSyncBackend::S3 => SyncBackend::S3(S3Config {
+            endpoint: required(&cli.sync_s3_endpoint, "--sync-s3-endpoint")?.clone(),
+            bucket: required(&cli.sync_s3_bucket, "--sync-s3-bucket")?.clone(),
+            region: cli.sync_s3_region.clone(),
+            access_key_id: required(&cli.sync_s3_access_key, "--sync-s3-access-key")?.clone(),
+            secret_access_key: required(&cli.sync_s3_secret_key, "--sync-s3-secret-key")?.clone(),
+            prefix: cli.sync_s3_prefix.clone(),
+        }),
+    })
+}
+
 /// Start the cloud sync service
 async fn start_sync_service(
     cli: &Cli,
     db: Arc<DatabaseManager>,
 ) -> anyhow::Result<Arc<screenpipe_core::sync::SyncServiceHandle>> {
     // Validate required credentials
-    let token = cli.sync_token.as_ref().ok_or_else(|| {
-        anyhow::anyhow!("--sync-token or SCREENPIPE_SYNC_TOKEN required for sync")
-    })?;
+    let backend = sync_backend_from_cli(cli)?;
 
     let password = cli.sync_password.as_ref().ok_or_else(|| {
         anyhow::anyhow!("--sync-password or SCREENPIPE_SYNC_PASSWORD required for sync")
@@ -2042,8 +2388,9 @@ async fn start_sync_service(
     let device_os = std::env::consts::OS.to_string();
 
     // Create sync manager
-    let config = SyncClientConfig::new(token.clone(), machine_id.clone(), device_name, device_os);
-    let manager = SyncManager::new(config)?;
+    info!("sync backend: {}", backend.name());
+    let transport = backend.into_transport(machine_id.clone(), device_name, device_os)?;
+    let manager = SyncManager::with_transport(transport);
 
     // Initialize with password
     let is_new_user = manager.initialize(password).await?;
@@ -2107,9 +2454,148 @@ async fn start_sync_service(
     Ok(Arc::new(handle))
 }
 
+/// Show what an installed pipe is allowed to do.
+fn print_pipe_permissions(scopes: Vec<String>, permissions: Option<PipePermissions>) {
+    match &permissions {
+        Some(_) => println!("the pipe requests:"),
+        None => println!("the pipe declares no permissions, it gets:"),
+    }
+    for line in permissions.unwrap_or_default().summary(&scopes) {
+        println!("  {}", line);
+    }
+    println!(
+        "api scopes and permissions are only enforced with --enable-auth (always for wasm pipes), other pipes restricting api access don't start without it"
+    );
+}
+
+/// Client for the local API, authenticated with $SCREENPIPE_API_TOKEN when set
+fn api_client() -> anyhow::Result<Client> {
+    let mut headers = HeaderMap::new();
+    if let Ok(token) = env::var(TOKEN_ENV) {
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", token))?,
+        );
+    }
+    Ok(Client::builder().default_headers(headers).build()?)
+}
+
+/// Ask the running server to forget the matching data
+async fn handle_forget_command(
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    query: Option<String>,
+    output: &OutputFormat,
+    port: u16,
+) -> anyhow::Result<()> {
+    if start_time.is_none() && end_time.is_none() && query.is_none() {
+        return Err(anyhow::anyhow!(
+            "--start-time, --end-time or --query is required"
+        ));
+    }
+    let response = api_client()?
+        .post(format!("http://localhost:{}/forget", port))
+        .json(&json!({
+            "start_time": start_time,
+            "end_time": end_time,
+            "query": query,
+        }))
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to reach the server on port {}: {}", port, e))?;
+    let status = response.status();
+    let body: Value = response.json().await?;
+    if !status.is_success() {
+        return Err(anyhow::anyhow!(
+            "forget failed: {}",
+            body["error"].as_str().unwrap_or("unknown error")
+        ));
+    }
+
+    match output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&body)?),
+        OutputFormat::Text => {
+            let rows = &body["rows"];
+            println!(
+                "deleted {} frames, {} ocr texts, {} transcriptions, {} accessibility records and {} ui events",
+                rows["frames"], rows["ocr_text"], rows["audio_transcriptions"], rows["accessibility"], rows["ui_events"]
+            );
+            println!(
+                "{} frames and {} audio ranges will be removed from the media files",
+                body["frames_to_purge"], body["audio_segments_to_purge"]
+            );
+            if body["derived_rows"].as_u64().unwrap_or(0) > 0 {
+                println!(
+                    "cleared {} dead letters, meeting titles, activity sessions and pipe outputs",
+                    body["derived_rows"]
+                );
+            }
+            if body["sync_blobs_deleted"].as_u64().unwrap_or(0) > 0 {
+                println!("deleted {} synced blobs", body["sync_blobs_deleted"]);
+            }
+            for error in body["errors"].as_array().into_iter().flatten() {
+                println!("warning: {}", error.as_str().unwrap_or_default());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handle token subcommands
+fn handle_token_command(command: &TokenCommand) -> anyhow::Result<()> {
+    match command {
+        TokenCommand::Create {
+            name,
+            scopes,
+            output,
+            data_dir,
+        } => {
+            let store = TokenStore::open(&get_base_dir(data_dir)?)?;
+            let (token, secret) = store.create(name, scopes.clone())?;
+            match output {
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&json!({ "token": token, "secret": secret }))?
+                ),
+                OutputFormat::Text => {
+                    println!("created token {} ({})", token.id, token.name);
+                    println!("{}", secret);
+                    println!("store it now, it can't be shown again");
+                }
+            }
+        }
+        TokenCommand::List { output, data_dir } => {
+            let tokens = TokenStore::open(&get_base_dir(data_dir)?)?.list()?;
+            match output {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&tokens)?),
+                OutputFormat::Text => {
+                    for token in tokens {
+                        let scopes: Vec<_> = token.scopes.iter().map(|s| s.as_str()).collect();
+                        println!(
+                            "  id: {}, name: {}, scopes: {}, created: {}",
+                            token.id,
+                            token.name,
+                            scopes.join(" "),
+                            token.created_at
+                        );
+                    }
+                }
+            }
+        }
+        TokenCommand::Revoke { id, data_dir } => {
+            if TokenStore::open(&get_base_dir(data_dir)?)?.revoke(id)? {
+                println!("revoked token {}", id);
+            } else {
+                anyhow::bail!("no token with id {}", id);
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Handle sync subcommands
 async fn handle_sync_command(command: &SyncCommand) -> anyhow::Result<()> {
-    let client = reqwest::Client::new();
+    let client = api_client()?;
     let server_url = "http://localhost";
 
     match command {
@@ -1,5 +1,12 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::auth::Scope;
+use crate::transcript::TranscriptFormat;
 
+use chrono::{DateTime, Utc};
 use clap::CommandFactory;
 use clap::ValueEnum;
 use clap::{Parser, Subcommand, ValueHint};
@@ -8,6 +15,7 @@ use screenpipe_audio::{
     core::engine::AudioTranscriptionEngine as CoreAudioTranscriptionEngine,
     vad::{VadEngineEnum, VadSensitivity},
 };
+use screenpipe_core::at_rest::{self, AtRestKeys, KeySource};
 use screenpipe_core::Language;
 use screenpipe_db::CustomOcrConfig as DBCustomOcrConfig;
 use screenpipe_db::OcrEngine as DBOcrEngine;
@@ -64,6 +72,7 @@ pub enum CliOcrEngine {
     #[cfg(target_os = "macos")]
     AppleNative,
     Custom,
+    Onnx,
 }
 
 impl From<CliOcrEngine> for Arc<DBOcrEngine> {
@@ -77,6 +86,7 @@ impl From<CliOcrEngine> for Arc<DBOcrEngine> {
             #[cfg(target_os = "windows")]
             CliOcrEngine::WindowsNative => Arc::new(DBOcrEngine::WindowsNative),
             CliOcrEngine::Custom => Arc::new(DBOcrEngine::Custom(DBCustomOcrConfig::default())),
+            CliOcrEngine::Onnx => Arc::new(DBOcrEngine::Onnx),
         }
     }
 }
@@ -105,6 +115,7 @@ impl From<CliOcrEngine> for CoreOcrEngine {
                     CoreOcrEngine::Custom(CustomOcrConfig::default())
                 }
             }
+            CliOcrEngine::Onnx => CoreOcrEngine::Onnx,
         }
     }
 }
@@ -142,6 +153,16 @@ impl From<CliVadSensitivity> for VadSensitivity {
     }
 }
 
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliSyncBackend {
+    /// The hosted screenpipe sync API
+    Hosted,
+    /// A local or mounted directory (NAS, Syncthing folder, ...)
+    Directory,
+    /// An S3-compatible bucket (MinIO, Garage, AWS, ...)
+    S3,
+}
+
 #[derive(Parser)]
 #[command(
     author,
@@ -222,6 +243,7 @@ pub struct Cli {
     /// WindowsNative is a local OCR engine for Windows.
     /// Unstructured is a cloud OCR engine (free of charge on us for now), recommended for high quality OCR.
     /// Tesseract is a local OCR engine (not supported on macOS)
+    /// Onnx is a local OCR engine running PaddleOCR models on CPU, models are downloaded on first use.
     #[cfg_attr(
         target_os = "macos",
         arg(short = 'o', long, value_enum, default_value_t = CliOcrEngine::AppleNative)
@@ -253,6 +275,11 @@ pub struct Cli {
     #[arg(long, default_value_t = true)]
     pub use_pii_removal: bool,
 
+    /// Blur PII detected by OCR (API keys, emails, credit cards, ...) out of the screen before it is recorded.
+    /// Unlike --use-pii-removal, which only cleans text, this keeps the secrets out of the video files too.
+    #[arg(long, default_value_t = false)]
+    pub redact_pii_at_capture: bool,
+
     /// Disable vision recording
     #[arg(long, default_value_t = false)]
     pub disable_vision: bool,
@@ -321,12 +348,82 @@ pub struct Cli {
     #[arg(long, default_value_t = false)]
     pub enable_pipe_manager: bool,
 
+    /// Require a bearer token on API requests. Create tokens with `screenpipe token create`,
+    /// pipes get their own token (default: false)
+    #[arg(long, default_value_t = false)]
+    pub enable_auth: bool,
+
+    /// Origin allowed to call the API from a browser, e.g. http://localhost:3000.
+    /// Repeat for several origins. Default: any origin
+    #[arg(long = "cors-origin")]
+    pub cors_origins: Vec<String>,
+
+    /// Enable POST /raw_sql/write, which runs SQL that can modify the database.
+    /// /raw_sql itself is always read-only (default: false)
+    #[arg(long, default_value_t = false)]
+    pub enable_raw_sql_write: bool,
+
     /// Enable UI event capture (keyboard, mouse, clipboard).
     /// Requires accessibility and input monitoring permissions on macOS.
     /// Currently supported on macOS only.
     #[arg(long, default_value_t = false)]
     pub enable_ui_events: bool,
 
+    /// Embed OCR text and audio transcriptions in the background for /search/hybrid.
+    /// Downloads the embedding model (~550 MB) on first start.
+    #[arg(long, default_value_t = false)]
+    pub enable_embedding_index: bool,
+
+    // =========================================================================
+    // Retention Options
+    // =========================================================================
+    /// Delete video files older than this many days.
+    /// Frames and OCR text stay searchable unless --retention-ocr-days is also set.
+    #[arg(long)]
+    pub retention_video_days: Option<u32>,
+
+    /// Delete audio files older than this many days.
+    /// Transcriptions stay searchable unless --retention-transcription-days is also set.
+    #[arg(long)]
+    pub retention_audio_days: Option<u32>,
+
+    /// Delete frames and OCR text (and their video files) older than this many days
+    #[arg(long)]
+    pub retention_ocr_days: Option<u32>,
+
+    /// Delete audio transcriptions (and their audio files) older than this many days
+    #[arg(long)]
+    pub retention_transcription_days: Option<u32>,
+
+    /// Delete UI events and accessibility text older than this many days
+    #[arg(long)]
+    pub retention_ui_events_days: Option<u32>,
+
+    /// Maximum disk space for video files in GB, oldest files are deleted first
+    #[arg(long)]
+    pub retention_video_max_gb: Option<f64>,
+
+    /// Maximum disk space for audio files in GB, oldest files are deleted first
+    #[arg(long)]
+    pub retention_audio_max_gb: Option<f64>,
+
+    /// Re-encode video older than this many days at a lower fps and resolution.
+    /// OCR text is kept and the timeline stays browsable, only the pixels get cheaper.
+    #[arg(long)]
+    pub downsample_after_days: Option<u32>,
+
+    /// Frame rate of downsampled video (default: 0.2 = one frame every 5 seconds)
+    #[arg(long, default_value_t = 0.2)]
+    pub downsample_fps: f64,
+
+    /// Resolution factor of downsampled video (default: 0.5 = half width and height)
+    #[arg(long, default_value_t = 0.5)]
+    pub downsample_scale: f64,
+
+    /// Interval between retention passes in seconds (default: 3600 = 1 hour)
+    #[arg(long, default_value_t = 3600)]
+    pub retention_interval_secs: u64,
+
     // =========================================================================
     // Cloud Sync Options
     // =========================================================================
@@ -355,6 +452,59 @@ pub struct Cli {
     #[arg(long)]
     pub sync_machine_id: Option<String>,
 
+    /// Where synced data is stored. Data is end-to-end encrypted with every backend.
+    #[arg(long, value_enum, default_value_t = CliSyncBackend::Hosted)]
+    pub sync_backend: CliSyncBackend,
+
+    /// Directory used by the `directory` sync backend
+    #[arg(long, env = "SCREENPIPE_SYNC_DIR")]
+    pub sync_dir: Option<PathBuf>,
+
+    /// Endpoint URL used by the `s3` sync backend, e.g. http://nas.local:9000
+    #[arg(long, env = "SCREENPIPE_SYNC_S3_ENDPOINT")]
+    pub sync_s3_endpoint: Option<String>,
+
+    /// Bucket used by the `s3` sync backend
+    #[arg(long, env = "SCREENPIPE_SYNC_S3_BUCKET")]
+    pub sync_s3_bucket: Option<String>,
+
+    /// Region used to sign S3 requests
+    #[arg(long, default_value = "us-east-1")]
+    pub sync_s3_region: String,
+
+    /// Access key ID for the `s3` sync backend
+    #[arg(long, env = "SCREENPIPE_SYNC_S3_ACCESS_KEY")]
+    pub sync_s3_access_key: Option<String>,
+
+    /// Secret access key for the `s3` sync backend
+    #[arg(long, env = "SCREENPIPE_SYNC_S3_SECRET_KEY")]
+    pub sync_s3_secret_key: Option<String>,
+
+    /// Optional key prefix inside the S3 bucket
+    #[arg(long)]
+    pub sync_s3_prefix: Option<String>,
+
+    // =========================================================================
+    // Encryption At Rest Options
+    // =========================================================================
+    /// Encrypt the database and finished video and audio files in the data directory.
+    /// Stays on once enabled. Refused by builds without the `sqlcipher` feature, which
+    /// can't encrypt the database.
+    #[arg(
+        long,
+        global = true,
+        default_value_t = false,
+        action = clap::ArgAction::SetTrue,
+        value_parser = parse_encrypt_at_rest
+    )]
+    pub encrypt_at_rest: bool,
+
+    /// Passphrase the at-rest encryption key is derived from.
+    /// Without it the key is kept in the OS keyring.
+    /// Can also be set via SCREENPIPE_ENCRYPTION_PASSPHRASE environment variable.
+    #[arg(long, global = true, env = "SCREENPIPE_ENCRYPTION_PASSPHRASE")]
+    pub encryption_passphrase: Option<String>,
+
     #[command(subcommand)]
     pub command: Option<Command>,
 }
@@ -375,6 +525,37 @@ impl Cli {
         Ok(())
     }
 
+    /// Keys for encryption at rest of `data_dir`, if enabled now or by an earlier run.
+    pub fn at_rest_keys(&self, data_dir: &Path) -> anyhow::Result<Option<AtRestKeys>> {
+        if !self.encrypt_at_rest && !data_dir.join(at_rest::KEY_FILE_NAME).exists() {
+            return Ok(None);
+        }
+        let source = match &self.encryption_passphrase {
+            Some(passphrase) => KeySource::passphrase(passphrase.as_str()),
+            None => KeySource::Keyring,
+        };
+        let keys = AtRestKeys::load_or_create(data_dir, &source)
+            .map_err(|e| anyhow::anyhow!("failed to load the at-rest encryption key: {}", e))?;
+        Ok(Some(keys))
+    }
+
+    /// Create retention configuration from CLI arguments
+    pub fn to_retention_config(&self) -> crate::retention::RetentionConfig {
+        crate::retention::RetentionConfig {
+            video_days: self.retention_video_days,
+            audio_days: self.retention_audio_days,
+            ocr_days: self.retention_ocr_days,
+            transcription_days: self.retention_transcription_days,
+            ui_events_days: self.retention_ui_events_days,
+            video_max_gb: self.retention_video_max_gb,
+            audio_max_gb: self.retention_audio_max_gb,
+            downsample_after_days: self.downsample_after_days,
+            downsample_fps: self.downsample_fps,
+            downsample_scale: self.downsample_scale,
+            interval_secs: self.retention_interval_secs,
+        }
+    }
+
     /// Create UI recorder configuration from CLI arguments
     #[cfg(feature = "ui-events")]
     pub fn to_ui_recorder_config(&self) -> crate::ui_recorder::UiRecorderConfig {
@@ -420,9 +601,11 @@ pub enum Command {
         #[command(subcommand)]
         subcommand: McpCommand,
     },
-    /// Add video files to existing screenpipe data (OCR only) - DOES NOT SUPPORT AUDIO
+    /// Add video and audio files to existing screenpipe data. Videos are OCR'd, audio
+    /// files (wav, mp3, m4a, ...) are transcribed with the audio options of the recorder
+    /// (-a, -l, --vad-engine, --deepgram-api-key, --use-pii-removal)
     Add {
-        /// Path to folder containing video files
+        /// Path to folder containing video or audio files
         path: String,
         /// Data directory. Default to $HOME/.screenpipe
         #[arg(long, value_hint = ValueHint::DirPath)]
@@ -439,9 +622,12 @@ pub enum Command {
         /// Path to JSON file containing metadata overrides
         #[arg(long, value_hint = ValueHint::FilePath)]
         metadata_override: Option<PathBuf>,
-        /// Copy videos to screenpipe data directory
+        /// Copy videos and audio files to screenpipe data directory
         #[arg(long, default_value_t = true)]
         copy_videos: bool,
+        /// Also transcribe the audio track of video files
+        #[arg(long, default_value_t = false)]
+        transcribe_videos: bool,
         /// Enable debug logging for screenpipe modules
         #[arg(long)]
         debug: bool,
@@ -449,6 +635,86 @@ pub enum Command {
         #[arg(long, default_value_t = false)]
         use_embedding: bool,
     },
+    /// Export recorded data to a portable archive (database subset + media)
+    Export {
+        /// Path of the archive to write
+        #[arg(value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+        /// Only data recorded after this time (RFC 3339, e.g. 2024-10-01T00:00:00Z)
+        #[arg(long)]
+        start_time: Option<DateTime<Utc>>,
+        /// Only data recorded before this time (RFC 3339)
+        #[arg(long)]
+        end_time: Option<DateTime<Utc>>,
+        /// Only screen data of this app (audio is not exported)
+        #[arg(long)]
+        app_name: Option<String>,
+        /// Only data of this monitor or audio device
+        #[arg(long)]
+        device_name: Option<String>,
+        /// Export the database rows only, without video and audio files
+        #[arg(long, default_value_t = false)]
+        no_media: bool,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+    },
+    /// Import an archive created by `screenpipe export`, skipping data already present
+    Import {
+        /// Path of the archive to import
+        #[arg(value_hint = ValueHint::FilePath)]
+        archive: PathBuf,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+    },
+    /// Print the transcript of recorded audio as SRT, WebVTT, text with speaker labels or JSON
+    Transcript {
+        /// Start of the transcript (RFC 3339, e.g. 2024-10-01T09:00:00Z). Default to a
+        /// day before the end
+        #[arg(long)]
+        start_time: Option<DateTime<Utc>>,
+        /// End of the transcript (RFC 3339). Default to now
+        #[arg(long)]
+        end_time: Option<DateTime<Utc>>,
+        /// Only this audio device
+        #[arg(long)]
+        device_name: Option<String>,
+        /// Only this speaker, can be repeated
+        #[arg(long = "speaker-id")]
+        speaker_ids: Vec<i64>,
+        /// Only speakers whose name contains this (case-insensitive)
+        #[arg(long)]
+        speaker_name: Option<String>,
+        /// Transcript format
+        #[arg(short, long, value_enum, default_value_t = TranscriptFormat::Text)]
+        format: TranscriptFormat,
+        /// Write the transcript to this file instead of stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+    },
+    /// Delete everything recorded in a time range and/or matching a text query, including
+    /// the media and the synced copies. Requires a running server
+    Forget {
+        /// Only data recorded after this time (RFC 3339, e.g. 2024-10-01T00:00:00Z)
+        #[arg(long)]
+        start_time: Option<DateTime<Utc>>,
+        /// Only data recorded before this time (RFC 3339)
+        #[arg(long)]
+        end_time: Option<DateTime<Utc>>,
+        /// Only data whose text matches this full-text query, e.g. a password
+        #[arg(short, long)]
+        query: Option<String>,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+        /// Server port
+        #[arg(short = 'p', long, default_value_t = 3030)]
+        port: u16,
+    },
     /// Run data migrations in the background
     Migrate {
         /// The name of the migration to run
@@ -473,6 +739,11 @@ pub enum Command {
         #[arg(long, default_value_t = true)]
         continue_on_error: bool,
     },
+    /// API token management, see --enable-auth
+    Token {
+        #[command(subcommand)]
+        subcommand: TokenCommand,
+    },
     /// Generate shell completions
     Completions {
         /// The shell to generate completions for
@@ -593,6 +864,17 @@ pub enum PipeCommand {
         #[arg(short = 'p', long, default_value_t = 3030)]
         port: u16,
     },
+    /// Show the logs of a pipe, restarts and exits included
+    Logs {
+        /// ID of the pipe
+        id: String,
+        /// Number of past lines to show
+        #[arg(short = 'n', long, default_value_t = 100)]
+        lines: usize,
+        /// Keep printing new lines as the pipe writes them
+        #[arg(short, long)]
+        follow: bool,
+    },
     /// Delete a pipe
     Delete {
         /// ID of the pipe to delete
@@ -606,6 +888,42 @@ pub enum PipeCommand {
     },
 }
 
+#[derive(Subcommand)]
+pub enum TokenCommand {
+    /// Create a token, the secret is only shown once
+    Create {
+        /// Name of the token, e.g. the app using it
+        name: String,
+        /// Scope granted to the token: read:search, read:media, write:tags, admin:pipes,
+        /// admin:raw_sql or admin. Repeat for several scopes
+        #[arg(long = "scope", required = true)]
+        scopes: Vec<Scope>,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+    },
+    /// List tokens
+    List {
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+    },
+    /// Revoke a token
+    Revoke {
+        /// ID of the token
+        id: String,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum McpCommand {
     /// Setup MCP server configuration
@@ -626,6 +944,27 @@ pub enum McpCommand {
         #[arg(long)]
         purge: bool,
     },
+    /// Run the built-in MCP server (no Node/Bun/Python required)
+    Serve {
+        /// Transport to use: stdio for local agents, http for the streamable HTTP transport
+        #[arg(short, long, value_enum, default_value_t = McpTransport::Stdio)]
+        transport: McpTransport,
+        /// Port for the http transport
+        #[arg(short = 'p', long, default_value_t = 3031)]
+        port: u16,
+        /// Address to bind the http transport to
+        #[arg(long, default_value = "127.0.0.1")]
+        host: std::net::IpAddr,
+        /// Data directory. Default to $HOME/.screenpipe
+        #[arg(long, value_hint = ValueHint::DirPath)]
+        data_dir: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum McpTransport {
+    Stdio,
+    Http,
 }
 
 #[derive(Clone, Debug, ValueEnum, PartialEq)]
@@ -663,6 +1002,20 @@ pub enum SyncCommand {
 }
 
 /// Get or create a persistent machine ID for sync
+/// Value parser of `--encrypt-at-rest`, fails on builds that can't encrypt the database.
+fn parse_encrypt_at_rest(value: &str) -> Result<bool, String> {
+    let enabled: bool = value
+        .parse()
+        .map_err(|e: std::str::ParseBoolError| e.to_string())?;
+    if enabled && !cfg!(feature = "sqlcipher") {
+        return Err(
+            "this build can't encrypt the database, rebuild screenpipe with the sqlcipher feature"
+                .to_string(),
+        );
+    }
+    Ok(enabled)
+}
+
 pub fn get_or_create_machine_id(override_id: Option<String>) -> String {
     if let Some(id) = override_id {
         return id;
@@ -676,3 +1029,15 @@ pub fn get_or_create_machine_id(override_id: Option<String>) -> String {
         uuid::Uuid::new_v4().to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "sqlcipher"))]
+    #[test]
+    fn test_encrypt_at_rest_requires_sqlcipher() {
+        assert!(!Cli::try_parse_from(["screenpipe"]).unwrap().encrypt_at_rest);
+        assert!(Cli::try_parse_from(["screenpipe", "--encrypt-at-rest"]).is_err());
+    }
+}
@@ -3,7 +3,7 @@ use anyhow::Result;
 use futures::future::join_all;
 use screenpipe_core::pii_removal::{remove_pii, remove_pii_from_text_json};
 use screenpipe_core::Language;
-use screenpipe_db::{DatabaseManager, FrameWindowData, Speaker};
+use screenpipe_db::{DatabaseManager, FrameWindowData, PiiRedaction, Speaker};
 use screenpipe_events::{poll_meetings_events, send_event};
 use screenpipe_vision::core::WindowOcr;
 use screenpipe_vision::OcrEngine;
@@ -21,6 +21,7 @@ pub async fn start_continuous_recording(
     ocr_engine: Arc<OcrEngine>,
     monitor_ids: Vec<u32>,
     use_pii_removal: bool,
+    redact_pii_at_capture: bool,
     vision_disabled: bool,
     vision_handle: &Handle,
     ignored_windows: &[String],
@@ -60,6 +61,7 @@ pub async fn start_continuous_recording(
                             ocr_engine.clone(),
                             monitor_id,
                             use_pii_removal,
+                            redact_pii_at_capture,
                             &ignored_windows_video,
                             &include_windows_video,
                             &ignored_urls_video,
@@ -127,6 +129,7 @@ pub async fn record_video(
     ocr_engine: Arc<OcrEngine>,
     monitor_id: u32,
     use_pii_removal: bool,
+    redact_pii_at_capture: bool,
     ignored_windows: &[String],
     include_windows: &[String],
     ignored_urls: &[String],
@@ -185,6 +188,7 @@ pub async fn record_video(
         capture_unfocused_windows,
         activity_feed,
         video_quality,
+        redact_pii_at_capture,
     );
 
     info!(
@@ -299,6 +303,17 @@ pub async fn record_video(
                     focused: window_result.focused,
                     text: text.clone(),
                     text_json: text_json.clone(),
+                    pii_redactions: window_result
+                        .pii_redactions
+                        .iter()
+                        .map(|region| PiiRedaction {
+                            pii_type: region.pii_type.clone(),
+                            x: region.x.into(),
+                            y: region.y.into(),
+                            width: region.width.into(),
+                            height: region.height.into(),
+                        })
+                        .collect(),
                 });
 
                 // Store metadata for realtime events (sent after DB insert)
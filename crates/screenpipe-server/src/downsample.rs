@@ -0,0 +1,254 @@
+//! Archival tier for old recordings
+//!
+//! Re-encodes a video chunk at a lower fps and resolution and rewrites the
+//! `offset_index` of its frames so `get_frame_data` and the frame cache keep resolving
+//! them. OCR text is left untouched, only the pixels get cheaper.
+//!
+//! Frames dropped by the lower fps point at the closest earlier frame that was kept.
+
+use anyhow::{anyhow, Result};
+use image::imageops::FilterType;
+use image::ImageFormat;
+use screenpipe_core::at_rest::readable_path;
+use screenpipe_core::find_ffmpeg_path;
+use screenpipe_db::{DatabaseManager, MediaChunkRow};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+use crate::video::{finish_ffmpeg_process, spawn_ffmpeg_loggers, start_ffmpeg_process};
+use crate::video_utils::{get_video_fps, validate_media};
+
+/// Quality preset used for archived chunks (maps to the highest CRF).
+const ARCHIVE_VIDEO_QUALITY: &str = "low";
+
+/// Suffix of re-encoded files, keeps the `monitor_<id>_<date>_<time>` prefix parseable.
+const ARCHIVE_SUFFIX: &str = "_archive";
+
+#[derive(Debug, Clone, Copy)]
+pub struct DownsampleOptions {
+    /// Frame rate of the re-encoded chunk
+    pub fps: f64,
+    /// Resolution factor applied to both dimensions (0.5 = half width and height)
+    pub scale: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DownsampleOutcome {
+    /// The chunk was re-encoded, sizes are in bytes
+    Downsampled { old_size: u64, new_size: u64 },
+    /// Nothing to gain (already at or below the target), chunk marked as done
+    Skipped,
+}
+
+/// Offsets to keep so that one frame out of `step` survives, always keeping the first.
+fn kept_offsets(offsets: &[i64], step: usize) -> Vec<i64> {
+    offsets.iter().step_by(step.max(1)).copied().collect()
+}
+
+/// Map every original offset to its index in the re-encoded file: the latest kept
+/// offset at or before it.
+fn remap_offsets(offsets: &[i64], kept: &[i64]) -> Vec<(i64, i64)> {
+    offsets
+        .iter()
+        .map(|&offset| {
+            let new_index = kept.partition_point(|&k| k <= offset).saturating_sub(1);
+            (offset, new_index as i64)
+        })
+        .collect()
+}
+
+fn archive_path(file_path: &str) -> PathBuf {
+    let path = Path::new(file_path);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}{}.mp4", stem, ARCHIVE_SUFFIX))
+}
+
+/// Re-encode one chunk and point its frames at the new file. The original file is
+/// removed once the database references the new one.
+pub async fn downsample_video_chunk(
+    db: &DatabaseManager,
+    chunk: &MediaChunkRow,
+    options: DownsampleOptions,
+) -> Result<DownsampleOutcome> {
+    let ffmpeg_path = find_ffmpeg_path().ok_or_else(|| anyhow!("ffmpeg not found"))?;
+
+    let offsets = db.get_video_chunk_offsets(chunk.id).await?;
+    if offsets.is_empty() {
+        db.mark_video_chunk_downsampled(chunk.id).await?;
+        return Ok(DownsampleOutcome::Skipped);
+    }
+
+    // decrypted copy when the chunk is encrypted at rest
+    let source = readable_path(&chunk.file_path).await?;
+    let source_fps = get_video_fps(&ffmpeg_path, source.as_str()).await?;
+    let step = (source_fps / options.fps).round().max(1.0) as usize;
+    let scale = options.scale.clamp(0.05, 1.0);
+    if step == 1 && scale >= 1.0 {
+        db.mark_video_chunk_downsampled(chunk.id).await?;
+        return Ok(DownsampleOutcome::Skipped);
+    }
+
+    let kept = kept_offsets(&offsets, step);
+    let frames_dir = tempfile::tempdir()?;
+    decode_frames(&ffmpeg_path, source.as_str(), frames_dir.path()).await?;
+
+    let output = archive_path(&chunk.file_path);
+    let output_str = output.to_string_lossy().into_owned();
+    let new_fps = source_fps / step as f64;
+    let mut child = start_ffmpeg_process(&output_str, new_fps, ARCHIVE_VIDEO_QUALITY).await?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("failed to open ffmpeg stdin"))?;
+    spawn_ffmpeg_loggers(child.stderr.take(), child.stdout.take());
+
+    let mut last_frame: Option<Vec<u8>> = None;
+    for offset in &kept {
+        let frame_path = frames_dir.path().join(format!("frame{}.png", offset));
+        // A frame missing from the decode (truncated file) repeats the previous one so
+        // the indexes of the new file stay aligned with `kept`
+        let png = match tokio::fs::read(&frame_path).await {
+            Ok(data) => resize_png(&data, scale)?,
+            Err(_) => match &last_frame {
+                Some(previous) => previous.clone(),
+                None => return Err(anyhow!("first frame of {} missing", chunk.file_path)),
+            },
+        };
+        stdin.write_all(&png).await?;
+        last_frame = Some(png);
+    }
+    stdin.flush().await?;
+    finish_ffmpeg_process(child, Some(stdin)).await;
+
+    if let Err(e) = validate_media(&output_str).await {
+        let _ = tokio::fs::remove_file(&output).await;
+        return Err(anyhow!("re-encoded chunk is invalid: {}", e));
+    }
+
+    let old_size = tokio::fs::metadata(&chunk.file_path).await?.len();
+    let new_size = tokio::fs::metadata(&output).await?.len();
+    if new_size >= old_size {
+        debug!(
+            "downsampling {} saved nothing ({} -> {} bytes), keeping original",
+            chunk.file_path, old_size, new_size
+        );
+        let _ = tokio::fs::remove_file(&output).await;
+        db.mark_video_chunk_downsampled(chunk.id).await?;
+        return Ok(DownsampleOutcome::Skipped);
+    }
+
+    let offset_map = remap_offsets(&offsets, &kept);
+    db.replace_downsampled_video_chunk(chunk.id, &output_str, &offset_map)
+        .await?;
+
+    if let Err(e) = tokio::fs::remove_file(&chunk.file_path).await {
+        warn!(
+            "downsampled {} but failed to remove original: {}",
+            chunk.file_path, e
+        );
+    }
+
+    info!(
+        "downsampled {} ({} frames -> {}, {} -> {} bytes)",
+        chunk.file_path,
+        offsets.len(),
+        kept.len(),
+        old_size,
+        new_size
+    );
+    Ok(DownsampleOutcome::Downsampled { old_size, new_size })
+}
+
+/// Decode every frame of `video_path` as `frame<offset>.png` into `output_dir`.
+pub(crate) async fn decode_frames(
+    ffmpeg_path: &Path,
+    video_path: &str,
+    output_dir: &Path,
+) -> Result<()> {
+    let pattern = output_dir.join("frame%d.png");
+    let mut command = Command::new(ffmpeg_path);
+    command.args([
+        "-i",
+        video_path,
+        "-vsync",
+        "0",
+        "-start_number",
+        "0",
+        "-threads",
+        "2",
+        "-y",
+        pattern.to_str().unwrap(),
+    ]);
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let output = command.output().await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "ffmpeg failed to decode {}: {}",
+            video_path,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+fn resize_png(data: &[u8], scale: f64) -> Result<Vec<u8>> {
+    let image = image::load_from_memory(data)?;
+    let image = if scale < 1.0 {
+        let width = ((image.width() as f64 * scale) as u32).max(2);
+        let height = ((image.height() as f64 * scale) as u32).max(2);
+        image.resize_exact(width, height, FilterType::Triangle)
+    } else {
+        image
+    };
+    let mut buffer = Vec::new();
+    image.write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kept_offsets_and_remap() {
+        let offsets = vec![0, 1, 2, 3, 4];
+        let kept = kept_offsets(&offsets, 2);
+        assert_eq!(kept, vec![0, 2, 4]);
+        assert_eq!(
+            remap_offsets(&offsets, &kept),
+            vec![(0, 0), (1, 0), (2, 1), (3, 1), (4, 2)]
+        );
+
+        // sparse offsets (dropped frames during capture) still map in order
+        let offsets = vec![0, 3, 4, 9];
+        let kept = kept_offsets(&offsets, 3);
+        assert_eq!(kept, vec![0, 9]);
+        assert_eq!(
+            remap_offsets(&offsets, &kept),
+            vec![(0, 0), (3, 0), (4, 0), (9, 1)]
+        );
+
+        assert_eq!(kept_offsets(&offsets, 1), offsets);
+    }
+
+    #[test]
+    fn test_archive_path_keeps_parseable_name() {
+        let path = archive_path("/data/monitor_1_2024-10-19_02-51-20.mp4");
+        assert_eq!(
+            path,
+            PathBuf::from("/data/monitor_1_2024-10-19_02-51-20_archive.mp4")
+        );
+    }
+}
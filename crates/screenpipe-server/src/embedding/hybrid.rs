@@ -0,0 +1,146 @@
+//! Hybrid search: reciprocal rank fusion (RRF) of keyword (FTS5) and embedding results.
+
+use chrono::{DateTime, Utc};
+use screenpipe_db::SearchResult;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+/// RRF damping constant, keeps the top ranks of one list from drowning the other.
+pub const RRF_K: f64 = 60.0;
+
+#[derive(Hash, PartialEq, Eq)]
+enum ResultKey {
+    Frame(i64),
+    Audio { chunk_id: i64, offset_index: i64 },
+    Ui(i64),
+    Input(i64),
+}
+
+fn result_key(result: &SearchResult) -> ResultKey {
+    match result {
+        SearchResult::OCR(ocr) => ResultKey::Frame(ocr.frame_id),
+        SearchResult::Audio(audio) => ResultKey::Audio {
+            chunk_id: audio.audio_chunk_id,
+            offset_index: audio.offset_index,
+        },
+        SearchResult::UI(ui) => ResultKey::Ui(ui.id),
+        SearchResult::Input(input) => ResultKey::Input(input.id),
+    }
+}
+
+fn result_timestamp(result: &SearchResult) -> DateTime<Utc> {
+    match result {
+        SearchResult::OCR(ocr) => ocr.timestamp,
+        SearchResult::Audio(audio) => audio.timestamp,
+        SearchResult::UI(ui) => ui.timestamp,
+        SearchResult::Input(input) => input.timestamp,
+    }
+}
+
+fn set_score(result: &mut SearchResult, score: f64) {
+    match result {
+        SearchResult::OCR(ocr) => ocr.score = Some(score),
+        SearchResult::Audio(audio) => audio.score = Some(score),
+        SearchResult::UI(ui) => ui.score = Some(score),
+        SearchResult::Input(_) => {}
+    }
+}
+
+/// Fuse ranked lists into one, best first.
+///
+/// A result scores `1 / (RRF_K + rank)` (rank starting at 1) for every list it appears
+/// in, the sum replaces its `score`. Ties go to the most recent result. When a result is
+/// in several lists the first occurrence is kept, pass the keyword list first to keep
+/// its snippet and match offsets.
+pub fn reciprocal_rank_fusion(lists: Vec<Vec<SearchResult>>) -> Vec<SearchResult> {
+    let mut fused: Vec<(SearchResult, f64)> = Vec::new();
+    let mut positions: HashMap<ResultKey, usize> = HashMap::new();
+
+    for list in lists {
+        for (rank, result) in list.into_iter().enumerate() {
+            let contribution = 1.0 / (RRF_K + rank as f64 + 1.0);
+            match positions.entry(result_key(&result)) {
+                Entry::Occupied(entry) => fused[*entry.get()].1 += contribution,
+                Entry::Vacant(entry) => {
+                    entry.insert(fused.len());
+                    fused.push((result, contribution));
+                }
+            }
+        }
+    }
+
+    fused.sort_by(|(a, score_a), (b, score_b)| {
+        score_b
+            .total_cmp(score_a)
+            .then_with(|| result_timestamp(b).cmp(&result_timestamp(a)))
+    });
+
+    fused
+        .into_iter()
+        .map(|(mut result, score)| {
+            set_score(&mut result, score);
+            result
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use screenpipe_db::OCRResult;
+
+    fn ocr(frame_id: i64) -> SearchResult {
+        SearchResult::OCR(OCRResult {
+            frame_id,
+            frame_name: String::new(),
+            ocr_text: format!("frame {}", frame_id),
+            text_json: "[]".to_string(),
+            timestamp: Utc::now(),
+            file_path: String::new(),
+            offset_index: 0,
+            app_name: String::new(),
+            ocr_engine: String::new(),
+            window_name: String::new(),
+            tags: Vec::new(),
+            browser_url: None,
+            focused: None,
+            device_name: String::new(),
+            score: None,
+            snippet: None,
+            matches: Vec::new(),
+            redacted: false,
+        })
+    }
+
+    fn frame_ids(results: &[SearchResult]) -> Vec<i64> {
+        results
+            .iter()
+            .map(|r| match r {
+                SearchResult::OCR(ocr) => ocr.frame_id,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let keyword = vec![ocr(1), ocr(2), ocr(3)];
+        let semantic = vec![ocr(3), ocr(4), ocr(1)];
+
+        let fused = reciprocal_rank_fusion(vec![keyword, semantic]);
+
+        // 1: 1/61 + 1/63, 3: 1/63 + 1/61, both lists beat single-list hits
+        assert_eq!(fused.len(), 4);
+        let ids = frame_ids(&fused);
+        assert!(ids[..2].contains(&1) && ids[..2].contains(&3));
+        let mut single = ids[2..].to_vec();
+        single.sort();
+        assert_eq!(single, [2, 4]);
+
+        let SearchResult::OCR(best) = &fused[0] else {
+            unreachable!()
+        };
+        let expected = 1.0 / 61.0 + 1.0 / 63.0;
+        assert!((best.score.unwrap() - expected).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,177 @@
+//! Background embedding of OCR text and audio transcriptions for hybrid search.
+//!
+//! Rows are embedded newest first with the in-process [`EmbeddingModel`] and stored
+//! with [`EMBEDDING_MODEL_NAME`], so vectors from other models are never compared.
+//! When a batch fails its rows are embedded one by one, rows still failing after
+//! `MAX_ATTEMPTS` passes are marked in `embedding_failures` and skipped.
+
+use screenpipe_core::embedding::model::EmbeddingModel;
+use screenpipe_db::DatabaseManager;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{debug, error, info, warn};
+
+use super::embedding_endpoint::get_or_initialize_model;
+
+/// Model tag of the vectors written by the indexer.
+pub const EMBEDDING_MODEL_NAME: &str = "jina-embeddings-v2-base-en";
+
+/// Rows embedded per model call.
+const BATCH_SIZE: i64 = 16;
+
+/// Characters of a text that are embedded, long OCR dumps are cut.
+const MAX_TEXT_CHARS: usize = 2000;
+
+/// Pause when everything is indexed.
+const IDLE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Delay before the first pass so the model download doesn't compete with startup.
+const INITIAL_DELAY: Duration = Duration::from_secs(30);
+
+/// Passes in which a row failed to embed before it is skipped for good.
+const MAX_ATTEMPTS: u32 = 3;
+
+pub struct EmbeddingIndexer {
+    db: Arc<DatabaseManager>,
+    /// Failed passes of the rows that couldn't be embedded, by table and id
+    failures: Mutex<HashMap<(&'static str, i64), u32>>,
+}
+
+impl EmbeddingIndexer {
+    pub fn new(db: Arc<DatabaseManager>) -> Self {
+        Self {
+            db,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Index new rows forever.
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            tokio::time::sleep(INITIAL_DELAY).await;
+            let model = match get_or_initialize_model().await {
+                Ok(model) => model,
+                Err(e) => {
+                    error!("embedding indexer disabled, failed to load model: {}", e);
+                    return;
+                }
+            };
+            info!("embedding indexer started");
+
+            loop {
+                match self.index_batch(&model).await {
+                    Ok(0) => tokio::time::sleep(IDLE_INTERVAL).await,
+                    Ok(n) => {
+                        debug!("embedded {} rows", n);
+                        tokio::task::yield_now().await;
+                    }
+                    Err(e) => {
+                        error!("embedding indexer pass failed: {}", e);
+                        tokio::time::sleep(IDLE_INTERVAL).await;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Embed one batch of OCR text and one of transcriptions, returns the number of rows
+    /// embedded.
+    pub async fn index_batch(&self, model: &Arc<Mutex<EmbeddingModel>>) -> anyhow::Result<usize> {
+        let ocr = self
+            .db
+            .get_ocr_text_without_embedding(EMBEDDING_MODEL_NAME, BATCH_SIZE)
+            .await?;
+        let ocr = self.embed(model, "ocr_text", ocr).await?;
+        self.db
+            .insert_ocr_text_embeddings(EMBEDDING_MODEL_NAME, &ocr)
+            .await?;
+
+        let audio = self
+            .db
+            .get_transcriptions_without_embedding(EMBEDDING_MODEL_NAME, BATCH_SIZE)
+            .await?;
+        let audio = self.embed(model, "audio_transcriptions", audio).await?;
+        self.db
+            .insert_transcription_embeddings(EMBEDDING_MODEL_NAME, &audio)
+            .await?;
+
+        Ok(ocr.len() + audio.len())
+    }
+
+    /// Embed `rows` of the table `source`. When the batch fails the rows are embedded one
+    /// by one, so a single bad text doesn't hold back the others, and the rows failing
+    /// for the `MAX_ATTEMPTS`th time are marked as failed.
+    async fn embed(
+        &self,
+        model: &Arc<Mutex<EmbeddingModel>>,
+        source: &'static str,
+        rows: Vec<(i64, String)>,
+    ) -> anyhow::Result<Vec<(i64, Vec<f32>)>> {
+        let e = match embed_rows(model, rows.clone()).await {
+            Ok(embeddings) => return Ok(embeddings),
+            Err(e) => e,
+        };
+        debug!(
+            "embedding {} batch failed, retrying row by row: {}",
+            source, e
+        );
+
+        let mut embeddings = Vec::new();
+        for (id, text) in rows {
+            let e = match embed_rows(model, vec![(id, text)]).await {
+                Ok(embedded) => {
+                    self.failures.lock().await.remove(&(source, id));
+                    embeddings.extend(embedded);
+                    continue;
+                }
+                Err(e) => e,
+            };
+            let mut failures = self.failures.lock().await;
+            let attempts = failures.entry((source, id)).or_insert(0);
+            *attempts += 1;
+            if *attempts < MAX_ATTEMPTS {
+                continue;
+            }
+            warn!(
+                "skipping {} {} after {} failed embedding attempts: {}",
+                source, id, MAX_ATTEMPTS, e
+            );
+            self.db
+                .mark_embedding_failure(source, id, EMBEDDING_MODEL_NAME, &e.to_string())
+                .await?;
+            failures.remove(&(source, id));
+        }
+        Ok(embeddings)
+    }
+}
+
+/// Embed `(id, text)` rows off the async runtime.
+async fn embed_rows(
+    model: &Arc<Mutex<EmbeddingModel>>,
+    rows: Vec<(i64, String)>,
+) -> anyhow::Result<Vec<(i64, Vec<f32>)>> {
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (ids, texts): (Vec<i64>, Vec<String>) = rows
+        .into_iter()
+        .map(|(id, text)| (id, text.chars().take(MAX_TEXT_CHARS).collect()))
+        .unzip();
+    let model = model.clone();
+    let embeddings = tokio::task::spawn_blocking(move || {
+        model.blocking_lock().generate_batch_embeddings(&texts)
+    })
+    .await??;
+
+    Ok(ids.into_iter().zip(embeddings).collect())
+}
+
+/// Embed a search query with the indexer's model.
+pub async fn embed_query(query: &str) -> anyhow::Result<Vec<f32>> {
+    let model = get_or_initialize_model().await?;
+    let query = query.to_string();
+    tokio::task::spawn_blocking(move || model.blocking_lock().generate_embedding(&query)).await?
+}
@@ -1 +1,3 @@
 pub mod embedding_endpoint;
+pub mod hybrid;
+pub mod indexer;
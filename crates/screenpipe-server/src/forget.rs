@@ -0,0 +1,522 @@
+//! Forgetting recorded data
+//!
+//! `POST /forget` deletes the OCR text, transcriptions, UI events and accessibility text
+//! matching a time range and/or text query (`DatabaseManager::forget`), evicts them from
+//! the server caches and the embedding index and deletes the sync blobs that already hold
+//! them.
+//!
+//! The media follows in the background: `MediaPurger` re-encodes video chunks without
+//! the affected frames and silences the affected audio ranges. Chunks still being
+//! recorded are left alone until a newer chunk of the same monitor exists.
+
+use anyhow::{anyhow, Result};
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use screenpipe_core::at_rest::{self, readable_path};
+use screenpipe_core::find_ffmpeg_path;
+use screenpipe_core::sync::{BlobType, SyncManager};
+use screenpipe_db::{
+    DatabaseManager, ForgetFilter, ForgetResult, MediaChunkRow, PendingAudioPurge, PrunedRows,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::{Mutex, Notify};
+use tracing::{debug, error, info, warn};
+
+use crate::downsample::decode_frames;
+use crate::server::{api_error, ApiError, AppState};
+use crate::sync_provider::mark_records_unsynced;
+use crate::video::{finish_ffmpeg_process, spawn_ffmpeg_loggers, start_ffmpeg_process};
+use crate::video_utils::{get_video_fps, validate_media};
+
+/// Time between two purge passes when nothing was forgotten meanwhile.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Chunks (or audio ranges) rewritten per pass, the rest is picked up by the next one.
+const PURGE_BATCH_SIZE: i64 = 50;
+
+/// Rewrites the media of forgotten data.
+pub struct MediaPurger {
+    db: Arc<DatabaseManager>,
+    /// Quality preset of the re-encoded video chunks
+    video_quality: String,
+    wake: Notify,
+    /// Held for the duration of a pass so a chunk is never rewritten twice at once
+    run_lock: Mutex<()>,
+}
+
+impl MediaPurger {
+    pub fn new(db: Arc<DatabaseManager>, video_quality: String) -> Self {
+        Self {
+            db,
+            video_quality,
+            wake: Notify::new(),
+            run_lock: Mutex::new(()),
+        }
+    }
+
+    /// Run purge passes forever, every `PURGE_INTERVAL` or as soon as `wake` is called.
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.run_once().await {
+                    error!("forget: media purge failed: {}", e);
+                }
+                let _ = tokio::time::timeout(PURGE_INTERVAL, self.wake.notified()).await;
+            }
+        })
+    }
+
+    /// Start a pass now instead of waiting for the next one.
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Rewrite the media files with queued purges once.
+    pub async fn run_once(&self) -> Result<()> {
+        let _guard = self.run_lock.lock().await;
+        self.db.delete_orphaned_purges().await?;
+
+        for chunk in self.db.list_video_chunks_to_purge(PURGE_BATCH_SIZE).await? {
+            if let Err(e) = self.purge_video_chunk(&chunk).await {
+                warn!(
+                    "forget: failed to purge frames from {}: {}",
+                    chunk.file_path, e
+                );
+            }
+        }
+
+        let mut audio: BTreeMap<i64, Vec<PendingAudioPurge>> = BTreeMap::new();
+        for purge in self.db.list_pending_audio_purges(PURGE_BATCH_SIZE).await? {
+            audio.entry(purge.audio_chunk_id).or_default().push(purge);
+        }
+        for purges in audio.values() {
+            if let Err(e) = self.purge_audio_chunk(purges).await {
+                warn!(
+                    "forget: failed to silence audio in {}: {}",
+                    purges[0].file_path, e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-encode a video chunk without the frames queued for purging.
+    async fn purge_video_chunk(&self, chunk: &MediaChunkRow) -> Result<()> {
+        let purged = self.db.get_pending_frame_purges(chunk.id).await?;
+        if purged.is_empty() {
+            return Ok(());
+        }
+        if !Path::new(&chunk.file_path).exists() {
+            // media already pruned, there are no pixels left to remove
+            self.db.finish_video_chunk_purge(chunk.id, &purged).await?;
+            return Ok(());
+        }
+
+        let ffmpeg_path = find_ffmpeg_path().ok_or_else(|| anyhow!("ffmpeg not found"))?;
+        let source = readable_path(&chunk.file_path).await?;
+        let fps = get_video_fps(&ffmpeg_path, source.as_str()).await?;
+        let frames_dir = tempfile::tempdir()?;
+        decode_frames(&ffmpeg_path, source.as_str(), frames_dir.path()).await?;
+
+        let frame_path = |offset: i64| frames_dir.path().join(format!("frame{}.png", offset));
+        let mut frame_count = 0;
+        while frame_path(frame_count).exists() {
+            frame_count += 1;
+        }
+        let kept = kept_offsets(frame_count, &purged);
+
+        if kept.is_empty() {
+            self.db.finish_video_chunk_purge(chunk.id, &purged).await?;
+            remove_media_file(&chunk.file_path).await?;
+            info!(
+                "forget: removed {}, all its frames were forgotten",
+                chunk.file_path
+            );
+            return Ok(());
+        }
+
+        let output = purging_path(&chunk.file_path);
+        let output_str = output.to_string_lossy().into_owned();
+        let mut child = start_ffmpeg_process(&output_str, fps, &self.video_quality).await?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("failed to open ffmpeg stdin"))?;
+        spawn_ffmpeg_loggers(child.stderr.take(), child.stdout.take());
+        for offset in &kept {
+            stdin
+                .write_all(&tokio::fs::read(frame_path(*offset)).await?)
+                .await?;
+        }
+        stdin.flush().await?;
+        finish_ffmpeg_process(child, Some(stdin)).await;
+
+        if let Err(e) = validate_media(&output_str).await {
+            let _ = tokio::fs::remove_file(&output).await;
+            return Err(anyhow!("re-encoded chunk is invalid: {}", e));
+        }
+        replace_media_file(&chunk.file_path, &output).await?;
+        self.db.finish_video_chunk_purge(chunk.id, &purged).await?;
+
+        info!(
+            "forget: removed {} frames from {}",
+            frame_count as usize - kept.len(),
+            chunk.file_path
+        );
+        Ok(())
+    }
+
+    /// Silence the queued ranges of one audio chunk.
+    async fn purge_audio_chunk(&self, purges: &[PendingAudioPurge]) -> Result<()> {
+        let file_path = &purges[0].file_path;
+        let ids: Vec<i64> = purges.iter().map(|p| p.id).collect();
+        if !Path::new(file_path).exists() {
+            self.db.delete_pending_audio_purges(&ids).await?;
+            return Ok(());
+        }
+
+        let ffmpeg_path = find_ffmpeg_path().ok_or_else(|| anyhow!("ffmpeg not found"))?;
+        let source = readable_path(file_path).await?;
+        let ranges: Vec<(Option<f64>, Option<f64>)> =
+            purges.iter().map(|p| (p.start_time, p.end_time)).collect();
+        let output = purging_path(file_path);
+
+        let mut command = Command::new(&ffmpeg_path);
+        command.args([
+            "-i",
+            source.as_str(),
+            "-af",
+            &silence_filter(&ranges),
+            "-y",
+            output.to_str().unwrap(),
+        ]);
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            const CREATE_NO_WINDOW: u32 = 0x08000000;
+            command.creation_flags(CREATE_NO_WINDOW);
+        }
+
+        let result = command.output().await?;
+        if !result.status.success() {
+            let _ = tokio::fs::remove_file(&output).await;
+            return Err(anyhow!(
+                "ffmpeg failed: {}",
+                String::from_utf8_lossy(&result.stderr)
+            ));
+        }
+        replace_media_file(file_path, &output).await?;
+        self.db.delete_pending_audio_purges(&ids).await?;
+
+        info!("forget: silenced {} ranges of {}", purges.len(), file_path);
+        Ok(())
+    }
+}
+
+/// Offsets of a chunk with `frame_count` frames left once `purged` (ascending) are cut.
+fn kept_offsets(frame_count: i64, purged: &[i64]) -> Vec<i64> {
+    (0..frame_count)
+        .filter(|offset| purged.binary_search(offset).is_err())
+        .collect()
+}
+
+/// `volume` filter muting `ranges` (seconds from the start), a range without bounds
+/// mutes the whole file.
+fn silence_filter(ranges: &[(Option<f64>, Option<f64>)]) -> String {
+    let mut between = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match range {
+            (Some(start), Some(end)) => between.push(format!("between(t,{:.3},{:.3})", start, end)),
+            _ => return "volume=0".to_string(),
+        }
+    }
+    format!("volume=enable='{}':volume=0", between.join("+"))
+}
+
+/// Temporary file a chunk is rewritten to, next to it and with the same extension so
+/// ffmpeg picks the same container.
+fn purging_path(file_path: &str) -> PathBuf {
+    let path = Path::new(file_path);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.purging.{}", stem, ext.to_string_lossy()),
+        None => format!("{}.purging", stem),
+    };
+    path.with_file_name(name)
+}
+
+/// Move the rewritten `output` over `file_path`, encrypting it first when the original
+/// was encrypted at rest.
+async fn replace_media_file(file_path: &str, output: &Path) -> Result<()> {
+    if at_rest::is_sealed(Path::new(file_path)).unwrap_or(false) {
+        let Some(key) = at_rest::media_key() else {
+            let _ = tokio::fs::remove_file(output).await;
+            return Err(anyhow!(
+                "{} is encrypted but no key is configured",
+                file_path
+            ));
+        };
+        let sealed = output.to_path_buf();
+        tokio::task::spawn_blocking(move || at_rest::seal_file(&sealed, key)).await??;
+    }
+    tokio::fs::rename(output, file_path).await?;
+    Ok(())
+}
+
+async fn remove_media_file(file_path: &str) -> Result<()> {
+    match tokio::fs::remove_file(file_path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+// ============================================================================
+// API
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct ForgetRequest {
+    #[serde(default)]
+    pub start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub end_time: Option<DateTime<Utc>>,
+    /// Full-text query, e.g. a password or a customer name
+    #[serde(default)]
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ForgetResponse {
+    /// Database rows deleted, per table
+    pub rows: PrunedRows,
+    /// Video frames queued to be cut out of their chunk
+    pub frames_to_purge: u64,
+    /// Audio ranges queued to be silenced
+    pub audio_segments_to_purge: u64,
+    /// Webhook dead letters deleted, and meetings, activity sessions and pipe runs whose
+    /// text was cleared
+    pub derived_rows: u64,
+    /// Blobs deleted from the sync backend, their other records are uploaded again
+    pub sync_blobs_deleted: u64,
+    /// Non fatal errors (e.g. the sync backend could not be reached)
+    pub errors: Vec<String>,
+}
+
+/// Delete everything matching a time range and/or query, locally and from sync.
+pub async fn forget_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ForgetRequest>,
+) -> Result<Json<ForgetResponse>, ApiError> {
+    let filter = ForgetFilter {
+        start_time: request.start_time,
+        end_time: request.end_time,
+        query: request.query,
+    };
+    if filter.is_empty() {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "start_time, end_time or query is required",
+        ));
+    }
+    if let (Some(start), Some(end)) = (filter.start_time, filter.end_time) {
+        if start > end {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                "start_time must be before end_time",
+            ));
+        }
+    }
+
+    let result = state.db.forget(&filter).await.map_err(|e| {
+        error!("forget failed: {}", e);
+        api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("forget failed: {}", e),
+        )
+    })?;
+
+    // Cached search results and frame images would still show the data
+    state.search_cache.invalidate_all();
+    if let Some(cache) = &state.frame_image_cache {
+        let mut cache = cache.lock().await;
+        for frame_id in &result.frame_ids {
+            if let Some((path, _)) = cache.pop(frame_id) {
+                let _ = tokio::fs::remove_file(path).await;
+            }
+        }
+    }
+    state.media_purger.wake();
+
+    let mut response = ForgetResponse {
+        rows: result.rows.clone(),
+        frames_to_purge: result.frames_to_purge,
+        audio_segments_to_purge: result.audio_segments_to_purge,
+        derived_rows: result.derived_rows,
+        sync_blobs_deleted: 0,
+        errors: Vec::new(),
+    };
+    // The embedding index file would still hold the vectors of the deleted frames
+    if let Err(e) = state.ocr_embedding_index.remove(&result.frame_ids).await {
+        warn!("forget: failed to remove embeddings from the index: {}", e);
+        response
+            .errors
+            .push(format!("failed to remove embeddings from the index: {}", e));
+    }
+    if let Some(manager) = sync_manager(&state).await {
+        delete_synced_blobs(&state.db, &manager, &result, &mut response).await;
+    }
+    Ok(Json(response))
+}
+
+/// The manager of the sync started on `/sync/init`, or from the command line.
+async fn sync_manager(state: &AppState) -> Option<Arc<SyncManager>> {
+    if let Some(runtime) = state.sync_state.read().await.as_ref() {
+        return Some(runtime.manager.clone());
+    }
+    state
+        .sync_handle
+        .as_ref()
+        .map(|handle| handle.manager().clone())
+}
+
+/// Delete the blobs holding forgotten records. The records of those blobs that were not
+/// forgotten are marked unsynced to be uploaded again.
+async fn delete_synced_blobs(
+    db: &DatabaseManager,
+    manager: &SyncManager,
+    result: &ForgetResult,
+    response: &mut ForgetResponse,
+) {
+    for (blob_type, timestamps) in [
+        (BlobType::Ocr, &result.frame_timestamps),
+        (BlobType::Transcripts, &result.transcription_timestamps),
+        (BlobType::Accessibility, &result.accessibility_timestamps),
+        (BlobType::Input, &result.ui_event_timestamps),
+    ] {
+        if timestamps.is_empty() {
+            continue;
+        }
+        let times: Vec<String> = timestamps.iter().map(|t| t.to_rfc3339()).collect();
+        let blobs = match manager.delete_blobs_containing(blob_type, &times).await {
+            Ok(blobs) => blobs,
+            Err(e) => {
+                warn!(
+                    "forget: failed to delete synced {:?} blobs: {}",
+                    blob_type, e
+                );
+                response.errors.push(format!(
+                    "failed to delete synced {:?} blobs: {}",
+                    blob_type, e
+                ));
+                continue;
+            }
+        };
+        response.sync_blobs_deleted += blobs.len() as u64;
+        for blob in &blobs {
+            if let Err(e) =
+                mark_records_unsynced(db, blob_type, &blob.time_start, &blob.time_end).await
+            {
+                response.errors.push(e.to_string());
+            }
+        }
+        debug!(
+            "forget: deleted {} synced {:?} blobs",
+            blobs.len(),
+            blob_type
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kept_offsets() {
+        assert_eq!(kept_offsets(5, &[1, 3]), vec![0, 2, 4]);
+        assert_eq!(kept_offsets(2, &[0, 1]), Vec::<i64>::new());
+        // purges past the end of a truncated file are ignored
+        assert_eq!(kept_offsets(2, &[7]), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_silence_filter() {
+        assert_eq!(
+            silence_filter(&[(Some(1.0), Some(2.5)), (Some(10.0), Some(12.0))]),
+            "volume=enable='between(t,1.000,2.500)+between(t,10.000,12.000)':volume=0"
+        );
+        assert_eq!(
+            silence_filter(&[(Some(1.0), Some(2.5)), (None, None)]),
+            "volume=0"
+        );
+    }
+
+    #[test]
+    fn test_purging_path_keeps_extension() {
+        assert_eq!(
+            purging_path("/data/monitor_1_2024-10-19_02-51-20.mp4"),
+            PathBuf::from("/data/monitor_1_2024-10-19_02-51-20.purging.mp4")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_once_settles_purges_of_missing_files() {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        db.insert_video_chunk("/nonexistent/screen.mp4", "monitor_1")
+            .await
+            .unwrap();
+        let mut frame_ids = Vec::new();
+        for offset in 0..3 {
+            let frame_id = db
+                .insert_frame(
+                    "monitor_1",
+                    None,
+                    None,
+                    Some("app"),
+                    Some(""),
+                    false,
+                    Some(offset),
+                )
+                .await
+                .unwrap();
+            frame_ids.push(frame_id);
+        }
+        db.insert_ocr_text(
+            frame_ids[0],
+            "customer acme corp",
+            "",
+            Arc::new(screenpipe_db::OcrEngine::Tesseract),
+        )
+        .await
+        .unwrap();
+        db.insert_video_chunk("/nonexistent/current.mp4", "monitor_1")
+            .await
+            .unwrap();
+
+        let result = db
+            .forget(&ForgetFilter {
+                query: Some("acme".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(result.rows.frames, 1);
+
+        let purger = MediaPurger::new(db.clone(), "balanced".to_string());
+        purger.run_once().await.unwrap();
+
+        assert!(db.list_video_chunks_to_purge(10).await.unwrap().is_empty());
+        let (_, offset) = db.get_frame(frame_ids[2]).await.unwrap().unwrap();
+        assert_eq!(offset, 1);
+    }
+}
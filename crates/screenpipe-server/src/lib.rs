@@ -1,33 +1,49 @@
 mod add;
+pub mod activity;
 pub mod analytics;
+pub mod archive;
+pub mod at_rest;
+pub mod auth;
 #[cfg(feature = "apple-intelligence")]
 mod apple_intelligence_api;
 mod auto_destruct;
+mod downsample;
 pub mod chunking;
 pub mod cli;
 pub mod cloud_search;
 pub mod core;
 pub mod filtering;
+pub mod forget;
+pub mod mcp;
+pub mod meetings;
+pub mod metrics_api;
 pub mod pipe_manager;
+pub mod pipe_scheduler;
 mod resource_monitor;
+pub mod retention;
 mod server;
 pub mod sleep_monitor;
 mod sync_api;
 pub mod sync_provider;
 pub mod text_embeds;
+pub mod transcript;
 pub mod ui_events_api;
 pub mod ui_recorder;
 mod video;
 pub mod video_cache;
 pub mod video_utils;
 pub mod vision_manager;
-pub use add::handle_index_command;
+#[cfg(feature = "wasm-pipes")]
+pub mod wasm_pipes;
+pub mod webhooks;
+pub use add::{handle_index_command, AudioImportOptions};
 pub use auto_destruct::watch_pid;
 pub use axum::Json as JsonResponse;
 pub use cli::Cli;
 pub use core::{record_video, start_continuous_recording};
 pub use pipe_manager::PipeManager;
 pub use resource_monitor::{ResourceMonitor, RestartSignal};
+pub use retention::{RetentionConfig, RetentionManager, RetentionReport};
 pub use screenpipe_core::Language;
 pub use server::health_check;
 pub use server::AppState;
@@ -0,0 +1,253 @@
+//! Native MCP (Model Context Protocol) server
+//!
+//! Exposes screenpipe's search, OCR, UI event and speaker APIs as MCP tools and
+//! resources straight from `DatabaseManager`, so agents can query history without the
+//! Node/Bun helper in `screenpipe-integrations/screenpipe-mcp`.
+//!
+//! Two transports are supported: newline-delimited JSON-RPC over stdio and the
+//! streamable HTTP transport (single `/mcp` endpoint, JSON responses).
+
+pub mod protocol;
+mod resources;
+mod tools;
+mod transport;
+
+use screenpipe_db::DatabaseManager;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::debug;
+
+use protocol::{
+    JsonRpcError, JsonRpcRequest, JsonRpcResponse, INVALID_REQUEST, LATEST_PROTOCOL_VERSION,
+    METHOD_NOT_FOUND, PARSE_ERROR, SUPPORTED_PROTOCOL_VERSIONS,
+};
+pub use transport::{mcp_router, serve_http, serve_stdio};
+
+pub struct McpServer {
+    db: Arc<DatabaseManager>,
+}
+
+impl McpServer {
+    pub fn new(db: Arc<DatabaseManager>) -> Self {
+        Self { db }
+    }
+
+    /// Handle one raw message (a single request/notification or a batch).
+    /// Returns `None` when nothing has to be sent back (notifications only).
+    pub async fn handle_message(&self, message: Value) -> Option<Value> {
+        match message {
+            Value::Array(batch) => {
+                if batch.is_empty() {
+                    return Some(error_value(Value::Null, INVALID_REQUEST, "empty batch"));
+                }
+                let mut responses = Vec::new();
+                for item in batch {
+                    if let Some(response) = self.handle_single(item).await {
+                        responses.push(response);
+                    }
+                }
+                if responses.is_empty() {
+                    None
+                } else {
+                    Some(Value::Array(responses))
+                }
+            }
+            single => self.handle_single(single).await,
+        }
+    }
+
+    /// Parse and handle a line of text as received on the stdio transport.
+    pub async fn handle_text(&self, text: &str) -> Option<Value> {
+        match serde_json::from_str::<Value>(text) {
+            Ok(message) => self.handle_message(message).await,
+            Err(e) => Some(error_value(
+                Value::Null,
+                PARSE_ERROR,
+                &format!("parse error: {}", e),
+            )),
+        }
+    }
+
+    async fn handle_single(&self, message: Value) -> Option<Value> {
+        // Responses from the client (to server-initiated requests) are ignored, we never send any
+        if message.get("method").is_none()
+            && (message.get("result").is_some() || message.get("error").is_some())
+        {
+            return None;
+        }
+
+        let request: JsonRpcRequest = match serde_json::from_value(message) {
+            Ok(request) => request,
+            Err(e) => {
+                return Some(error_value(
+                    Value::Null,
+                    INVALID_REQUEST,
+                    &format!("invalid request: {}", e),
+                ))
+            }
+        };
+
+        if request.jsonrpc != "2.0" {
+            return Some(error_value(
+                request.id.unwrap_or(Value::Null),
+                INVALID_REQUEST,
+                "jsonrpc must be \"2.0\"",
+            ));
+        }
+
+        debug!("mcp request: {}", request.method);
+
+        if request.is_notification() {
+            // notifications/initialized, notifications/cancelled, ... need no answer
+            return None;
+        }
+
+        let id = request.id.clone().unwrap_or(Value::Null);
+        let response = match self.dispatch(&request.method, request.params).await {
+            Ok(result) => JsonRpcResponse::success(id, result),
+            Err(error) => JsonRpcResponse::failure(id, error),
+        };
+        serde_json::to_value(response).ok()
+    }
+
+    async fn dispatch(&self, method: &str, params: Option<Value>) -> Result<Value, JsonRpcError> {
+        let params = params.unwrap_or_else(|| json!({}));
+        match method {
+            "initialize" => {
+                let requested = params
+                    .get("protocolVersion")
+                    .and_then(Value::as_str)
+                    .unwrap_or(LATEST_PROTOCOL_VERSION);
+                let protocol_version = if SUPPORTED_PROTOCOL_VERSIONS.contains(&requested) {
+                    requested
+                } else {
+                    LATEST_PROTOCOL_VERSION
+                };
+                Ok(json!({
+                    "protocolVersion": protocol_version,
+                    "capabilities": {
+                        "tools": { "listChanged": false },
+                        "resources": { "subscribe": false, "listChanged": false }
+                    },
+                    "serverInfo": {
+                        "name": "screenpipe",
+                        "version": env!("CARGO_PKG_VERSION")
+                    },
+                    "instructions": "Query the user's screen (OCR), audio transcription and UI event history recorded by screenpipe. \
+                        Read screenpipe://context for the current time before building time-based queries."
+                }))
+            }
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tools::list_tools() })),
+            "tools/call" => {
+                let name = params
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| JsonRpcError::invalid_params("missing tool name"))?;
+                tools::call_tool(&self.db, name, params.get("arguments").cloned()).await
+            }
+            "resources/list" => Ok(json!({ "resources": resources::list_resources() })),
+            "resources/templates/list" => Ok(json!({
+                "resourceTemplates": resources::list_resource_templates()
+            })),
+            "resources/read" => {
+                let uri = params
+                    .get("uri")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| JsonRpcError::invalid_params("missing resource uri"))?;
+                resources::read_resource(&self.db, uri).await
+            }
+            "prompts/list" => Ok(json!({ "prompts": [] })),
+            other => Err(JsonRpcError::new(
+                METHOD_NOT_FOUND,
+                format!("method not found: {}", other),
+            )),
+        }
+    }
+}
+
+fn error_value(id: Value, code: i64, message: &str) -> Value {
+    serde_json::to_value(JsonRpcResponse::failure(id, JsonRpcError::new(code, message)))
+        .unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn test_server() -> McpServer {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        McpServer::new(Arc::new(db))
+    }
+
+    #[tokio::test]
+    async fn test_initialize_and_list_tools() {
+        let server = test_server().await;
+
+        let response = server
+            .handle_message(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": { "protocolVersion": "2024-11-05", "capabilities": {} }
+            }))
+            .await
+            .unwrap();
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+        assert_eq!(response["result"]["serverInfo"]["name"], "screenpipe");
+
+        let response = server
+            .handle_message(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }))
+            .await
+            .unwrap();
+        let names: Vec<&str> = response["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|t| t["name"].as_str())
+            .collect();
+        assert!(names.contains(&"search-content"));
+        assert!(names.contains(&"search-ui-events"));
+        assert!(names.contains(&"get-frame-ocr"));
+    }
+
+    #[tokio::test]
+    async fn test_notifications_get_no_response() {
+        let server = test_server().await;
+        let response = server
+            .handle_message(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await;
+        assert!(response.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_method_and_parse_error() {
+        let server = test_server().await;
+        let response = server
+            .handle_message(json!({ "jsonrpc": "2.0", "id": "a", "method": "nope" }))
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(response["id"], "a");
+
+        let response = server.handle_text("{not json").await.unwrap();
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_search_content_on_empty_db() {
+        let server = test_server().await;
+        let response = server
+            .handle_message(json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "tools/call",
+                "params": { "name": "search-content", "arguments": { "q": "hello" } }
+            }))
+            .await
+            .unwrap();
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.starts_with("No results found"));
+        assert!(response["result"].get("isError").is_none());
+    }
+}
@@ -0,0 +1,119 @@
+//! MCP resources: current time context, known speakers and per-frame OCR.
+
+use chrono::{Duration, Local, Utc};
+use screenpipe_db::DatabaseManager;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::protocol::JsonRpcError;
+
+const CONTEXT_URI: &str = "screenpipe://context";
+const SPEAKERS_URI: &str = "screenpipe://speakers";
+const FRAME_OCR_PREFIX: &str = "screenpipe://frames/";
+const FRAME_OCR_SUFFIX: &str = "/ocr";
+
+pub fn list_resources() -> Vec<Value> {
+    vec![
+        json!({
+            "uri": CONTEXT_URI,
+            "name": "Current Context",
+            "description": "Current date/time and pre-computed timestamps for common time ranges",
+            "mimeType": "application/json"
+        }),
+        json!({
+            "uri": SPEAKERS_URI,
+            "name": "Speakers",
+            "description": "Named speakers known to screenpipe",
+            "mimeType": "application/json"
+        }),
+    ]
+}
+
+pub fn list_resource_templates() -> Vec<Value> {
+    vec![json!({
+        "uriTemplate": "screenpipe://frames/{frame_id}/ocr",
+        "name": "Frame OCR",
+        "description": "OCR text blocks with bounding boxes for a frame",
+        "mimeType": "application/json"
+    })]
+}
+
+fn parse_frame_ocr_uri(uri: &str) -> Option<i64> {
+    uri.strip_prefix(FRAME_OCR_PREFIX)?
+        .strip_suffix(FRAME_OCR_SUFFIX)?
+        .parse()
+        .ok()
+}
+
+fn json_contents(uri: &str, value: &Value) -> Value {
+    json!({
+        "contents": [{
+            "uri": uri,
+            "mimeType": "application/json",
+            "text": serde_json::to_string_pretty(value).unwrap_or_default()
+        }]
+    })
+}
+
+pub async fn read_resource(db: &Arc<DatabaseManager>, uri: &str) -> Result<Value, JsonRpcError> {
+    if uri == CONTEXT_URI {
+        let now = Utc::now();
+        let today_start = now.date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc();
+        return Ok(json_contents(
+            uri,
+            &json!({
+                "current_time": now.to_rfc3339(),
+                "current_date_local": Local::now().format("%A, %B %-d, %Y").to_string(),
+                "timestamps": {
+                    "now": now.to_rfc3339(),
+                    "one_hour_ago": (now - Duration::hours(1)).to_rfc3339(),
+                    "three_hours_ago": (now - Duration::hours(3)).to_rfc3339(),
+                    "today_start": today_start.to_rfc3339(),
+                    "yesterday_start": (today_start - Duration::days(1)).to_rfc3339(),
+                    "one_week_ago": (now - Duration::weeks(1)).to_rfc3339(),
+                }
+            }),
+        ));
+    }
+
+    if uri == SPEAKERS_URI {
+        let speakers = db
+            .search_speakers("")
+            .await
+            .map_err(|e| JsonRpcError::internal(format!("failed to list speakers: {}", e)))?;
+        return Ok(json_contents(uri, &json!(speakers)));
+    }
+
+    if let Some(frame_id) = parse_frame_ocr_uri(uri) {
+        let text_json = db
+            .get_frame_ocr_text_json(frame_id)
+            .await
+            .map_err(|e| JsonRpcError::internal(format!("failed to read frame ocr: {}", e)))?
+            .ok_or_else(|| JsonRpcError::invalid_params(format!("no ocr data for frame {}", frame_id)))?;
+        return Ok(json!({
+            "contents": [{
+                "uri": uri,
+                "mimeType": "application/json",
+                "text": text_json
+            }]
+        }));
+    }
+
+    Err(JsonRpcError::invalid_params(format!(
+        "unknown resource: {}",
+        uri
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_frame_ocr_uri() {
+        assert_eq!(parse_frame_ocr_uri("screenpipe://frames/42/ocr"), Some(42));
+        assert_eq!(parse_frame_ocr_uri("screenpipe://frames/abc/ocr"), None);
+        assert_eq!(parse_frame_ocr_uri("screenpipe://frames/42"), None);
+        assert_eq!(parse_frame_ocr_uri("screenpipe://context"), None);
+    }
+}
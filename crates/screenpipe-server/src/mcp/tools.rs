@@ -0,0 +1,624 @@
+//! MCP tools backed directly by `DatabaseManager`.
+//!
+//! Tool arguments mirror the HTTP `SearchQuery` filters so agents can use the
+//! same vocabulary whether they talk to `/search` or to the MCP server.
+
+use chrono::{DateTime, Utc};
+use screenpipe_db::{
+    ContentType, DatabaseManager, SearchOrder, SearchResult, Speaker, UiEventRecord,
+};
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::protocol::JsonRpcError;
+
+/// Hard cap on results per tool call so a single request can't dump the whole db.
+const MAX_LIMIT: u32 = 500;
+
+fn default_limit() -> u32 {
+    10
+}
+
+fn default_ui_events_limit() -> u32 {
+    50
+}
+
+/// Accept speaker ids either as a JSON array or as a comma separated string ("1,2,3"),
+/// which is what the HTTP API takes.
+fn ids_from_list_or_csv<'de, D>(deserializer: D) -> Result<Option<Vec<i64>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Ids {
+        List(Vec<i64>),
+        Csv(String),
+    }
+
+    match Option::<Ids>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Ids::List(ids)) => Ok(Some(ids)),
+        Some(Ids::Csv(s)) => s
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<i64>().map_err(serde::de::Error::custom))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchContentArgs {
+    #[serde(default)]
+    pub q: Option<String>,
+    #[serde(default)]
+    pub content_type: ContentType,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub offset: u32,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    pub frame_name: Option<String>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    #[serde(default, deserialize_with = "ids_from_list_or_csv")]
+    pub speaker_ids: Option<Vec<i64>>,
+    pub focused: Option<bool>,
+    pub browser_url: Option<String>,
+    pub speaker_name: Option<String>,
+    #[serde(default)]
+    pub order: SearchOrder,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchAudioArgs {
+    #[serde(default)]
+    pub q: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub offset: u32,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    #[serde(default, deserialize_with = "ids_from_list_or_csv")]
+    pub speaker_ids: Option<Vec<i64>>,
+    pub speaker_name: Option<String>,
+    #[serde(default)]
+    pub order: SearchOrder,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FrameOcrArgs {
+    pub frame_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchUiEventsArgs {
+    pub q: Option<String>,
+    pub event_type: Option<String>,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    #[serde(default = "default_ui_events_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub offset: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchSpeakersArgs {
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnnamedSpeakersArgs {
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub offset: u32,
+    #[serde(default, deserialize_with = "ids_from_list_or_csv")]
+    pub speaker_ids: Option<Vec<i64>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimilarSpeakersArgs {
+    pub speaker_id: i64,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSpeakerArgs {
+    pub id: i64,
+    pub name: String,
+}
+
+fn time_range_properties() -> Value {
+    json!({
+        "start_time": {
+            "type": "string",
+            "format": "date-time",
+            "description": "ISO 8601 UTC start time (e.g., 2024-01-15T10:00:00Z)"
+        },
+        "end_time": {
+            "type": "string",
+            "format": "date-time",
+            "description": "ISO 8601 UTC end time (e.g., 2024-01-15T18:00:00Z)"
+        }
+    })
+}
+
+fn merge_properties(mut base: Value, extra: Value) -> Value {
+    if let (Some(base), Some(extra)) = (base.as_object_mut(), extra.as_object()) {
+        for (k, v) in extra {
+            base.insert(k.clone(), v.clone());
+        }
+    }
+    base
+}
+
+/// Tool descriptors returned by `tools/list`.
+pub fn list_tools() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "search-content",
+            "description": "Search screenpipe's recorded content: screen text (OCR), audio transcriptions, UI text and input events. \
+                Returns timestamped results with app context, newest first. Call with no parameters to get recent activity. \
+                Use the 'screenpipe://context' resource for the current time when building time-based queries.",
+            "annotations": { "title": "Search Content", "readOnlyHint": true },
+            "inputSchema": {
+                "type": "object",
+                "properties": merge_properties(json!({
+                    "q": { "type": "string", "description": "Full text search query. Optional - omit to return recent content." },
+                    "content_type": {
+                        "type": "string",
+                        "enum": ["all", "vision", "audio", "input", "ocr", "ui", "audio+ui", "ocr+ui", "audio+ocr", "vision+input", "audio+input", "vision+audio+input"],
+                        "default": "all"
+                    },
+                    "limit": { "type": "integer", "default": 10 },
+                    "offset": { "type": "integer", "default": 0 },
+                    "app_name": { "type": "string", "description": "Filter by app (e.g., 'Google Chrome', 'Slack', 'zoom.us')" },
+                    "window_name": { "type": "string", "description": "Filter by window title" },
+                    "frame_name": { "type": "string" },
+                    "browser_url": { "type": "string", "description": "Filter by browser URL (OCR only)" },
+                    "focused": { "type": "boolean", "description": "Only focused windows (OCR only)" },
+                    "min_length": { "type": "integer" },
+                    "max_length": { "type": "integer" },
+                    "speaker_ids": { "type": "array", "items": { "type": "integer" } },
+                    "speaker_name": { "type": "string", "description": "Case-insensitive partial match on speaker name" },
+                    "order": { "type": "string", "enum": ["time", "relevance"], "default": "time", "description": "'relevance' ranks matches of q best first" }
+                }), time_range_properties())
+            }
+        }),
+        json!({
+            "name": "search-audio",
+            "description": "Search audio transcriptions only, with speaker information and segment start/end offsets.",
+            "annotations": { "title": "Search Audio", "readOnlyHint": true },
+            "inputSchema": {
+                "type": "object",
+                "properties": merge_properties(json!({
+                    "q": { "type": "string" },
+                    "limit": { "type": "integer", "default": 10 },
+                    "offset": { "type": "integer", "default": 0 },
+                    "min_length": { "type": "integer" },
+                    "max_length": { "type": "integer" },
+                    "speaker_ids": { "type": "array", "items": { "type": "integer" } },
+                    "speaker_name": { "type": "string" },
+                    "order": { "type": "string", "enum": ["time", "relevance"], "default": "time" }
+                }), time_range_properties())
+            }
+        }),
+        json!({
+            "name": "get-frame-ocr",
+            "description": "Get the raw OCR blocks (text with bounding boxes) for a frame id returned by search-content.",
+            "annotations": { "title": "Frame OCR", "readOnlyHint": true },
+            "inputSchema": {
+                "type": "object",
+                "properties": { "frame_id": { "type": "integer" } },
+                "required": ["frame_id"]
+            }
+        }),
+        json!({
+            "name": "search-ui-events",
+            "description": "Search UI input events: clicks, typed text, scrolls, app/window switches and clipboard operations.",
+            "annotations": { "title": "Search UI Events", "readOnlyHint": true },
+            "inputSchema": {
+                "type": "object",
+                "properties": merge_properties(json!({
+                    "q": { "type": "string" },
+                    "event_type": {
+                        "type": "string",
+                        "enum": ["click", "text", "scroll", "key", "app_switch", "window_focus", "clipboard"]
+                    },
+                    "app_name": { "type": "string" },
+                    "window_name": { "type": "string" },
+                    "limit": { "type": "integer", "default": 50 },
+                    "offset": { "type": "integer", "default": 0 }
+                }), time_range_properties())
+            }
+        }),
+        json!({
+            "name": "search-speakers",
+            "description": "List known speakers whose name starts with the given prefix (all named speakers when omitted).",
+            "annotations": { "title": "Search Speakers", "readOnlyHint": true },
+            "inputSchema": {
+                "type": "object",
+                "properties": { "name": { "type": "string" } }
+            }
+        }),
+        json!({
+            "name": "get-unnamed-speakers",
+            "description": "List speakers that have not been named yet, with sample audio paths and transcripts in their metadata.",
+            "annotations": { "title": "Unnamed Speakers", "readOnlyHint": true },
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "limit": { "type": "integer", "default": 10 },
+                    "offset": { "type": "integer", "default": 0 },
+                    "speaker_ids": { "type": "array", "items": { "type": "integer" } }
+                }
+            }
+        }),
+        json!({
+            "name": "get-similar-speakers",
+            "description": "Find speakers whose voice embeddings are close to the given speaker (candidates for merging).",
+            "annotations": { "title": "Similar Speakers", "readOnlyHint": true },
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "speaker_id": { "type": "integer" },
+                    "limit": { "type": "integer", "default": 10 }
+                },
+                "required": ["speaker_id"]
+            }
+        }),
+        json!({
+            "name": "update-speaker",
+            "description": "Rename a speaker.",
+            "annotations": { "title": "Update Speaker", "readOnlyHint": false, "idempotentHint": true },
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "integer" },
+                    "name": { "type": "string" }
+                },
+                "required": ["id", "name"]
+            }
+        }),
+    ]
+}
+
+fn parse_args<T: for<'de> Deserialize<'de>>(arguments: Option<Value>) -> Result<T, JsonRpcError> {
+    serde_json::from_value(arguments.unwrap_or_else(|| json!({})))
+        .map_err(|e| JsonRpcError::invalid_params(format!("invalid arguments: {}", e)))
+}
+
+fn text_content(text: String) -> Value {
+    json!({ "content": [{ "type": "text", "text": text }] })
+}
+
+/// Tool execution errors are reported in the result (not as JSON-RPC errors) so the
+/// model can see them and adjust its query.
+fn tool_error(text: String) -> Value {
+    json!({ "content": [{ "type": "text", "text": text }], "isError": true })
+}
+
+/// Execute a tool by name. Unknown tools and malformed arguments are protocol errors,
+/// database failures are returned as `isError` results.
+pub async fn call_tool(
+    db: &Arc<DatabaseManager>,
+    name: &str,
+    arguments: Option<Value>,
+) -> Result<Value, JsonRpcError> {
+    let result = match name {
+        "search-content" => search_content(db, parse_args(arguments)?).await,
+        "search-audio" => search_audio(db, parse_args(arguments)?).await,
+        "get-frame-ocr" => get_frame_ocr(db, parse_args(arguments)?).await,
+        "search-ui-events" => search_ui_events(db, parse_args(arguments)?).await,
+        "search-speakers" => {
+            let args: SearchSpeakersArgs = parse_args(arguments)?;
+            db.search_speakers(args.name.as_deref().unwrap_or(""))
+                .await
+                .map(|speakers| format_speakers(&speakers))
+        }
+        "get-unnamed-speakers" => {
+            let args: UnnamedSpeakersArgs = parse_args(arguments)?;
+            db.get_unnamed_speakers(args.limit.min(MAX_LIMIT), args.offset, args.speaker_ids)
+                .await
+                .map(|speakers| format_speakers(&speakers))
+        }
+        "get-similar-speakers" => {
+            let args: SimilarSpeakersArgs = parse_args(arguments)?;
+            db.get_similar_speakers(args.speaker_id, args.limit.min(MAX_LIMIT))
+                .await
+                .map(|speakers| format_speakers(&speakers))
+        }
+        "update-speaker" => {
+            let args: UpdateSpeakerArgs = parse_args(arguments)?;
+            db.update_speaker_name(args.id, &args.name)
+                .await
+                .map(|id| format!("speaker {} renamed to '{}'", id, args.name))
+        }
+        other => {
+            return Err(JsonRpcError::invalid_params(format!(
+                "unknown tool: {}",
+                other
+            )))
+        }
+    };
+
+    Ok(match result {
+        Ok(text) => text_content(text),
+        Err(e) => tool_error(format!("{} failed: {}", name, e)),
+    })
+}
+
+async fn search_content(
+    db: &Arc<DatabaseManager>,
+    args: SearchContentArgs,
+) -> Result<String, sqlx::Error> {
+    let query = args.q.as_deref().unwrap_or("");
+    let limit = args.limit.min(MAX_LIMIT);
+
+    let (results, total) = tokio::try_join!(
+        db.search(
+            query,
+            args.content_type.clone(),
+            limit,
+            args.offset,
+            args.start_time,
+            args.end_time,
+            args.app_name.as_deref(),
+            args.window_name.as_deref(),
+            args.min_length,
+            args.max_length,
+            args.speaker_ids.clone(),
+            args.frame_name.as_deref(),
+            args.browser_url.as_deref(),
+            args.focused,
+            args.speaker_name.as_deref(),
+            args.order,
+        ),
+        db.count_search_results(
+            query,
+            args.content_type.clone(),
+            args.start_time,
+            args.end_time,
+            args.app_name.as_deref(),
+            args.window_name.as_deref(),
+            args.min_length,
+            args.max_length,
+            args.speaker_ids.clone(),
+            args.frame_name.as_deref(),
+            args.browser_url.as_deref(),
+            args.focused,
+            args.speaker_name.as_deref(),
+        ),
+    )?;
+
+    // Same display-time filter as the /search endpoint
+    let is_screenpipe_app = |app: &str| app.to_lowercase().contains("screenpipe");
+    let formatted: Vec<String> = results
+        .iter()
+        .filter(|r| match r {
+            SearchResult::OCR(ocr) => !is_screenpipe_app(&ocr.app_name),
+            SearchResult::UI(ui) => !is_screenpipe_app(&ui.app_name),
+            SearchResult::Input(input) => {
+                input.app_name.as_deref().is_none_or(|a| !is_screenpipe_app(a))
+            }
+            SearchResult::Audio(_) => true,
+        })
+        .map(format_search_result)
+        .collect();
+
+    if formatted.is_empty() {
+        return Ok(
+            "No results found. Try: broader search terms, different content_type, or wider time range."
+                .to_string(),
+        );
+    }
+
+    Ok(format!(
+        "{}\n\n{}",
+        pagination_header(formatted.len(), args.offset, total as i64),
+        formatted.join("\n---\n")
+    ))
+}
+
+async fn search_audio(
+    db: &Arc<DatabaseManager>,
+    args: SearchAudioArgs,
+) -> Result<String, sqlx::Error> {
+    let results = db
+        .search_audio(
+            args.q.as_deref().unwrap_or(""),
+            args.limit.min(MAX_LIMIT),
+            args.offset,
+            args.start_time,
+            args.end_time,
+            args.min_length,
+            args.max_length,
+            args.speaker_ids,
+            args.speaker_name.as_deref(),
+            args.order,
+        )
+        .await?;
+
+    if results.is_empty() {
+        return Ok("No audio transcriptions found.".to_string());
+    }
+
+    Ok(results
+        .into_iter()
+        .map(|audio| format_search_result(&SearchResult::Audio(audio)))
+        .collect::<Vec<_>>()
+        .join("\n---\n"))
+}
+
+async fn get_frame_ocr(
+    db: &Arc<DatabaseManager>,
+    args: FrameOcrArgs,
+) -> Result<String, sqlx::Error> {
+    match db.get_frame_ocr_text_json(args.frame_id).await? {
+        Some(text_json) => Ok(text_json),
+        None => Ok(format!("No OCR data for frame {}", args.frame_id)),
+    }
+}
+
+async fn search_ui_events(
+    db: &Arc<DatabaseManager>,
+    args: SearchUiEventsArgs,
+) -> Result<String, sqlx::Error> {
+    let events = db
+        .search_ui_events(
+            args.q.as_deref(),
+            args.event_type.as_deref(),
+            args.app_name.as_deref(),
+            args.window_name.as_deref(),
+            args.start_time,
+            args.end_time,
+            args.limit.min(MAX_LIMIT),
+            args.offset,
+        )
+        .await?;
+
+    if events.is_empty() {
+        return Ok("No UI events found.".to_string());
+    }
+
+    Ok(events
+        .iter()
+        .map(format_ui_event)
+        .collect::<Vec<_>>()
+        .join("\n---\n"))
+}
+
+fn pagination_header(returned: usize, offset: u32, total: i64) -> String {
+    let mut header = format!("Results: {}/{}", returned, total);
+    if total > offset as i64 + returned as i64 {
+        header.push_str(&format!(
+            " (use offset={} for more)",
+            offset as usize + returned
+        ));
+    }
+    header
+}
+
+fn format_search_result(result: &SearchResult) -> String {
+    match result {
+        SearchResult::OCR(ocr) => format!(
+            "[OCR] {} | {} (frame_id: {})\n{}\n{}",
+            ocr.app_name,
+            ocr.window_name,
+            ocr.frame_id,
+            ocr.timestamp.to_rfc3339(),
+            ocr.ocr_text
+        ),
+        SearchResult::Audio(audio) => {
+            let speaker = audio
+                .speaker
+                .as_ref()
+                .map(|s| {
+                    if s.name.is_empty() {
+                        format!("speaker #{}", s.id)
+                    } else {
+                        s.name.clone()
+                    }
+                })
+                .unwrap_or_else(|| "unknown speaker".to_string());
+            format!(
+                "[Audio] {} | {} (chunk_id: {})\n{}\n{}",
+                audio.device_name,
+                speaker,
+                audio.audio_chunk_id,
+                audio.timestamp.to_rfc3339(),
+                audio.transcription
+            )
+        }
+        SearchResult::UI(ui) => format!(
+            "[UI] {} | {}\n{}\n{}",
+            ui.app_name,
+            ui.window_name,
+            ui.timestamp.to_rfc3339(),
+            ui.text
+        ),
+        SearchResult::Input(input) => format_ui_event(input),
+    }
+}
+
+fn format_ui_event(event: &UiEventRecord) -> String {
+    let mut line = format!(
+        "[{}] {} | {}\n{}",
+        event.event_type,
+        event.app_name.as_deref().unwrap_or("?"),
+        event.window_title.as_deref().unwrap_or("?"),
+        event.timestamp.to_rfc3339()
+    );
+    if let Some(text) = &event.text_content {
+        line.push('\n');
+        line.push_str(text);
+    }
+    if let Some(name) = event.element.as_ref().and_then(|e| e.name.as_deref()) {
+        line.push_str(&format!("\nelement: {}", name));
+    }
+    line
+}
+
+fn format_speakers(speakers: &[Speaker]) -> String {
+    if speakers.is_empty() {
+        return "No speakers found.".to_string();
+    }
+    serde_json::to_string_pretty(speakers).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speaker_ids_accept_list_and_csv() {
+        let args: SearchAudioArgs = serde_json::from_value(json!({ "speaker_ids": [1, 2] })).unwrap();
+        assert_eq!(args.speaker_ids, Some(vec![1, 2]));
+
+        let args: SearchAudioArgs =
+            serde_json::from_value(json!({ "speaker_ids": "3, 4" })).unwrap();
+        assert_eq!(args.speaker_ids, Some(vec![3, 4]));
+
+        let args: SearchAudioArgs = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(args.speaker_ids, None);
+        assert_eq!(args.limit, 10);
+    }
+
+    #[test]
+    fn test_search_content_args_use_search_query_names() {
+        let args: SearchContentArgs = serde_json::from_value(json!({
+            "q": "invoice",
+            "content_type": "ocr",
+            "start_time": "2024-01-15T10:00:00Z",
+            "focused": true
+        }))
+        .unwrap();
+        assert_eq!(args.content_type, ContentType::OCR);
+        assert_eq!(args.focused, Some(true));
+        assert!(args.start_time.is_some());
+    }
+
+    #[test]
+    fn test_pagination_header() {
+        assert_eq!(pagination_header(10, 0, 10), "Results: 10/10");
+        assert_eq!(
+            pagination_header(10, 20, 100),
+            "Results: 10/100 (use offset=30 for more)"
+        );
+    }
+}
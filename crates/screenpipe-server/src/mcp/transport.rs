@@ -0,0 +1,155 @@
+//! stdio and streamable HTTP transports for the MCP server.
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde_json::Value;
+use std::{net::SocketAddr, sync::Arc};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tracing::{error, info, warn};
+
+use super::McpServer;
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Serve MCP over stdin/stdout, one JSON-RPC message per line.
+///
+/// Nothing else may write to stdout while this runs, logs have to go to stderr or a file.
+pub async fn serve_stdio(server: Arc<McpServer>) -> anyhow::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(response) = server.handle_text(line).await {
+            let mut out = serde_json::to_vec(&response)?;
+            out.push(b'\n');
+            stdout.write_all(&out).await?;
+            stdout.flush().await?;
+        }
+    }
+
+    info!("mcp stdio transport closed");
+    Ok(())
+}
+
+/// Router exposing the streamable HTTP transport on `/mcp`.
+pub fn mcp_router(server: Arc<McpServer>) -> Router {
+    Router::new()
+        .route(
+            "/mcp",
+            post(handle_post)
+                .get(method_not_allowed)
+                .delete(handle_delete),
+        )
+        .with_state(server)
+}
+
+/// Serve the streamable HTTP transport until the listener fails.
+pub async fn serve_http(server: Arc<McpServer>, addr: SocketAddr) -> anyhow::Result<()> {
+    if !addr.ip().is_loopback() {
+        warn!(
+            "mcp http transport listening on non-loopback address {}, your history is reachable from the network",
+            addr
+        );
+    }
+    let listener = TcpListener::bind(addr).await?;
+    info!("mcp server listening on http://{}/mcp", addr);
+    axum::serve(listener, mcp_router(server)).await?;
+    Ok(())
+}
+
+/// Reject browser requests coming from non-local origins (DNS rebinding protection).
+fn is_allowed_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        // Non-browser clients don't send an Origin header
+        return true;
+    };
+    let Ok(origin) = origin.to_str() else {
+        return false;
+    };
+    match url_host(origin) {
+        Some(host) => matches!(host, "localhost" | "127.0.0.1" | "[::1]"),
+        None => false,
+    }
+}
+
+fn url_host(origin: &str) -> Option<&str> {
+    let rest = origin.split_once("://")?.1;
+    let authority = rest.split('/').next()?;
+    if authority.starts_with('[') {
+        return authority.split_inclusive(']').next();
+    }
+    authority.split(':').next()
+}
+
+async fn handle_post(
+    State(server): State<Arc<McpServer>>,
+    headers: HeaderMap,
+    Json(message): Json<Value>,
+) -> Response {
+    if !is_allowed_origin(&headers) {
+        return (StatusCode::FORBIDDEN, "origin not allowed").into_response();
+    }
+
+    let is_initialize = message.get("method").and_then(Value::as_str) == Some("initialize");
+
+    match server.handle_message(message).await {
+        Some(body) => {
+            let mut response = Json(body).into_response();
+            if is_initialize {
+                let session_id = uuid::Uuid::new_v4().to_string();
+                match HeaderValue::from_str(&session_id) {
+                    Ok(value) => {
+                        response.headers_mut().insert(SESSION_HEADER, value);
+                    }
+                    Err(e) => error!("invalid mcp session id header: {}", e),
+                }
+            }
+            response
+        }
+        // Notifications and client responses are acknowledged without a body
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+/// We don't push server-initiated messages, so there is no SSE stream to open.
+async fn method_not_allowed() -> StatusCode {
+    StatusCode::METHOD_NOT_ALLOWED
+}
+
+/// Sessions are stateless on our side; terminating one is always successful.
+async fn handle_delete() -> StatusCode {
+    StatusCode::OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_validation() {
+        let mut headers = HeaderMap::new();
+        assert!(is_allowed_origin(&headers));
+
+        headers.insert(header::ORIGIN, HeaderValue::from_static("http://localhost:3000"));
+        assert!(is_allowed_origin(&headers));
+
+        headers.insert(header::ORIGIN, HeaderValue::from_static("http://[::1]:3000"));
+        assert!(is_allowed_origin(&headers));
+
+        headers.insert(header::ORIGIN, HeaderValue::from_static("https://evil.example.com"));
+        assert!(!is_allowed_origin(&headers));
+
+        headers.insert(header::ORIGIN, HeaderValue::from_static("null"));
+        assert!(!is_allowed_origin(&headers));
+    }
+}
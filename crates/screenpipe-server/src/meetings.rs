@@ -0,0 +1,340 @@
+//! Meetings (`/meetings`)
+//!
+//! Persists the `meeting_started` / `meeting_ended` events of the meeting detector as
+//! meeting records, and lets meetings be started, stopped, merged and split by hand.
+//! Transcriptions, frames and participant speakers are not copied into a meeting, they
+//! are the ones recorded between its start and end.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use screenpipe_db::{DatabaseManager, Meeting, MeetingFrame, MeetingParticipant};
+use screenpipe_events::{subscribe_to_all_events, Event as ScreenpipeEvent, MeetingEvent};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+
+use crate::server::{api_error, ApiError, AppState};
+use crate::transcript::{build_transcript, TranscriptEntry, TranscriptFilter};
+
+pub const SOURCE_DETECTED: &str = "detected";
+pub const SOURCE_MANUAL: &str = "manual";
+
+/// Records the meetings found by the detector.
+pub struct MeetingRecorder {
+    db: Arc<DatabaseManager>,
+}
+
+impl MeetingRecorder {
+    pub fn new(db: Arc<DatabaseManager>) -> Self {
+        Self { db }
+    }
+
+    /// Record meeting events forever.
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            match self.db.end_interrupted_meetings().await {
+                Ok(0) => {}
+                Ok(n) => info!("ended {} meetings interrupted by the last shutdown", n),
+                Err(e) => error!("failed to end interrupted meetings: {}", e),
+            }
+
+            let mut events = subscribe_to_all_events();
+            while let Some(event) = events.next().await {
+                if let Err(e) = self.handle(event).await {
+                    error!("failed to record meeting: {}", e);
+                }
+            }
+        })
+    }
+
+    async fn handle(&self, event: ScreenpipeEvent) -> Result<(), sqlx::Error> {
+        let started = match event.name.as_str() {
+            "meeting_started" => true,
+            "meeting_ended" => false,
+            _ => return Ok(()),
+        };
+        let meeting: MeetingEvent = match serde_json::from_value(event.data) {
+            Ok(meeting) => meeting,
+            Err(e) => {
+                warn!("invalid {} event: {}", event.name, e);
+                return Ok(());
+            }
+        };
+
+        let open = self.db.get_open_meeting().await?;
+        if started {
+            // a meeting started by hand, or still going on, is not duplicated
+            if let Some(open) = open {
+                debug!("meeting {} already in progress", open.id);
+                return Ok(());
+            }
+            let title = meeting
+                .title
+                .as_deref()
+                .map(str::trim)
+                .filter(|t| !t.is_empty());
+            let id = self
+                .db
+                .insert_meeting(
+                    &meeting.app,
+                    title,
+                    meeting.timestamp,
+                    None,
+                    SOURCE_DETECTED,
+                )
+                .await?;
+            info!("meeting {} started in {}", id, meeting.app);
+        } else if let Some(open) = open.filter(|m| m.source == SOURCE_DETECTED) {
+            // meetings started by hand are only stopped by hand
+            self.db.end_meeting(open.id, meeting.timestamp).await?;
+            info!("meeting {} ended", open.id);
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// API
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct MeetingsQuery {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Case-insensitive partial match
+    pub app_name: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub offset: u32,
+}
+
+fn default_limit() -> u32 {
+    50
+}
+
+#[derive(Deserialize)]
+pub struct MeetingDetailQuery {
+    /// Frames returned with the meeting
+    #[serde(default = "default_frame_limit")]
+    pub frame_limit: u32,
+}
+
+fn default_frame_limit() -> u32 {
+    500
+}
+
+/// A meeting with what was recorded during it.
+#[derive(Serialize)]
+pub struct MeetingDetail {
+    #[serde(flatten)]
+    pub meeting: Meeting,
+    pub participants: Vec<MeetingParticipant>,
+    pub transcript: Vec<TranscriptEntry>,
+    pub frames: Vec<MeetingFrame>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct StartMeetingRequest {
+    #[serde(default)]
+    pub app: String,
+    pub title: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct StopMeetingRequest {
+    /// Defaults to now
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct MergeMeetingsRequest {
+    pub ids: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct SplitMeetingRequest {
+    pub at: DateTime<Utc>,
+}
+
+fn internal_error(e: sqlx::Error) -> ApiError {
+    error!("meeting database error: {}", e);
+    api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
+}
+
+async fn find_meeting(state: &AppState, id: i64) -> Result<Meeting, ApiError> {
+    state
+        .db
+        .get_meeting(id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, "meeting not found"))
+}
+
+pub async fn list_meetings(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<MeetingsQuery>,
+) -> Result<Json<Vec<Meeting>>, ApiError> {
+    state
+        .db
+        .list_meetings(
+            query.start_time,
+            query.end_time,
+            query.app_name.as_deref(),
+            query.limit,
+            query.offset,
+        )
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+pub async fn get_meeting(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(query): Query<MeetingDetailQuery>,
+) -> Result<Json<MeetingDetail>, ApiError> {
+    let meeting = find_meeting(&state, id).await?;
+    let end_time = meeting.end_time.unwrap_or_else(Utc::now);
+
+    let participants = state
+        .db
+        .get_meeting_participants(meeting.start_time, end_time)
+        .await
+        .map_err(internal_error)?;
+    let transcript = build_transcript(
+        &state.db,
+        &TranscriptFilter {
+            start_time: Some(meeting.start_time),
+            end_time: Some(end_time),
+            ..Default::default()
+        },
+    )
+    .await
+    .map_err(internal_error)?;
+    let frames = state
+        .db
+        .get_meeting_frames(meeting.start_time, end_time, query.frame_limit)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(MeetingDetail {
+        meeting,
+        participants,
+        transcript,
+        frames,
+    }))
+}
+
+/// Start a meeting by hand. Fails while another meeting is in progress.
+pub async fn start_meeting(
+    State(state): State<Arc<AppState>>,
+    request: Option<Json<StartMeetingRequest>>,
+) -> Result<(StatusCode, Json<Meeting>), ApiError> {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    if let Some(open) = state.db.get_open_meeting().await.map_err(internal_error)? {
+        return Err(api_error(
+            StatusCode::CONFLICT,
+            format!("meeting {} is in progress", open.id),
+        ));
+    }
+
+    let title = request
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty());
+    let id = state
+        .db
+        .insert_meeting(&request.app, title, Utc::now(), None, SOURCE_MANUAL)
+        .await
+        .map_err(internal_error)?;
+    info!("meeting {} started by hand", id);
+    Ok((StatusCode::CREATED, Json(find_meeting(&state, id).await?)))
+}
+
+pub async fn stop_meeting(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    request: Option<Json<StopMeetingRequest>>,
+) -> Result<Json<Meeting>, ApiError> {
+    let end_time = request
+        .and_then(|Json(request)| request.end_time)
+        .unwrap_or_else(Utc::now);
+    let meeting = find_meeting(&state, id).await?;
+    if !state
+        .db
+        .end_meeting(meeting.id, end_time)
+        .await
+        .map_err(internal_error)?
+    {
+        return Err(api_error(StatusCode::CONFLICT, "meeting already ended"));
+    }
+    Ok(Json(find_meeting(&state, id).await?))
+}
+
+/// Merge meetings into the earliest one.
+pub async fn merge_meetings(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<MergeMeetingsRequest>,
+) -> Result<Json<Meeting>, ApiError> {
+    let mut ids = request.ids;
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.len() < 2 {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "at least two meetings are needed to merge",
+        ));
+    }
+
+    match state
+        .db
+        .merge_meetings(&ids)
+        .await
+        .map_err(internal_error)?
+    {
+        Some(meeting) => Ok(Json(meeting)),
+        None => Err(api_error(StatusCode::NOT_FOUND, "meeting not found")),
+    }
+}
+
+/// Split a meeting in two at `at`, returns both parts.
+pub async fn split_meeting(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(request): Json<SplitMeetingRequest>,
+) -> Result<Json<Vec<Meeting>>, ApiError> {
+    find_meeting(&state, id).await?;
+    let Some(new_id) = state
+        .db
+        .split_meeting(id, request.at)
+        .await
+        .map_err(internal_error)?
+    else {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "split time is not inside the meeting",
+        ));
+    };
+    Ok(Json(vec![
+        find_meeting(&state, id).await?,
+        find_meeting(&state, new_id).await?,
+    ]))
+}
+
+pub async fn delete_meeting(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, ApiError> {
+    if !state.db.delete_meeting(id).await.map_err(internal_error)? {
+        return Err(api_error(StatusCode::NOT_FOUND, "meeting not found"));
+    }
+    Ok(Json(json!({ "success": true })))
+}
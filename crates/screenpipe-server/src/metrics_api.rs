@@ -0,0 +1,106 @@
+//! Prometheus metrics (`/metrics`)
+//!
+//! The capture, audio and database crates record through the `metrics` facade: OCR and
+//! DB write latencies, frame comparison and OCR cache counters, per-monitor frame
+//! counters, audio dedup counters and resource usage samples. This module installs the
+//! Prometheus recorder collecting them and renders them in the text exposition format,
+//! along with gauges read from the server state at scrape time.
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::server::AppState;
+
+/// Histogram buckets of the `*_seconds` metrics.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Histogram samples are buffered until drained, also when nothing scrapes.
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+static HANDLE: OnceLock<Option<PrometheusHandle>> = OnceLock::new();
+
+/// Install the Prometheus recorder, once per process. Call it before recording starts,
+/// metrics recorded earlier are lost.
+pub fn install() -> Option<&'static PrometheusHandle> {
+    HANDLE
+        .get_or_init(|| {
+            let recorder = PrometheusBuilder::new()
+                .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), LATENCY_BUCKETS)
+                .and_then(|builder| builder.install_recorder());
+            match recorder {
+                Ok(handle) => {
+                    let upkeep = handle.clone();
+                    std::thread::Builder::new()
+                        .name("metrics-upkeep".to_string())
+                        .spawn(move || loop {
+                            std::thread::sleep(UPKEEP_INTERVAL);
+                            upkeep.run_upkeep();
+                        })
+                        .map_err(|e| warn!("failed to start metrics upkeep: {}", e))
+                        .ok();
+                    Some(handle)
+                }
+                Err(e) => {
+                    warn!(
+                        "failed to install metrics recorder, /metrics disabled: {}",
+                        e
+                    );
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+pub async fn metrics_handler(State(state): State<Arc<AppState>>) -> Response {
+    let Some(handle) = install() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "metrics recorder not installed",
+        )
+            .into_response();
+    };
+
+    metrics::gauge!("screenpipe_uptime_seconds")
+        .set((Utc::now() - state.app_start_time).num_seconds() as f64);
+    metrics::gauge!("screenpipe_ws_connections")
+        .set(state.ws_connection_count.load(Ordering::Relaxed) as f64);
+    let (transcription_queue, storage_queue) = state.audio_manager.queue_depths();
+    metrics::gauge!("screenpipe_audio_transcription_queue_depth").set(transcription_queue as f64);
+    metrics::gauge!("screenpipe_audio_storage_queue_depth").set(storage_queue as f64);
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle.render(),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let handle = install().unwrap();
+        metrics::counter!("screenpipe_test_total").increment(2);
+        metrics::histogram!("screenpipe_test_duration_seconds").record(0.3);
+
+        let output = handle.render();
+        assert!(output.contains("screenpipe_test_total 2"));
+        // latency histograms are rendered with buckets, not as summaries
+        assert!(output.contains("screenpipe_test_duration_seconds_bucket{le=\"0.5\"} 1"));
+        assert!(output.contains("screenpipe_test_duration_seconds_bucket{le=\"0.25\"} 0"));
+    }
+}
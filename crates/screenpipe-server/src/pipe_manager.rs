@@ -1,17 +1,29 @@
 use anyhow::Result;
-use screenpipe_core::{download_pipe, download_pipe_private, PipeState};
+use chrono::{DateTime, Utc};
+use screenpipe_core::{
+    build_pipe, download_pipe, download_pipe_private, lock_pipe, pin_github_source, pipe_log,
+    tail_pipe_log, verify_pipe, LogStream, PipeLock, PipeLogLine, PipePermissions, PipeState,
+    TrustedPublishers,
+};
+use screenpipe_db::DatabaseManager;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::fmt;
 use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc::{self, Sender};
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
+use crate::auth::{Scope, TokenStore, TOKEN_ENV};
+#[cfg(feature = "wasm-pipes")]
+use crate::wasm_pipes::{is_wasm_pipe, run_wasm_pipe, WasmPipeConfig};
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct PipeInfo {
     pub id: String,
@@ -22,16 +34,269 @@ pub struct PipeInfo {
     pub is_nextjs: bool,
     pub desc: String,
     pub build_status: Option<Value>,
+    #[serde(default)]
+    pub health: PipeHealth,
+    /// What was installed, `None` for pipes installed before locks
+    #[serde(default)]
+    pub lock: Option<PipeLock>,
+}
+
+/// What happened to a pipe since screenpipe started.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct PipeHealth {
+    pub running: bool,
+    /// Exits with an error, failures to start included
+    pub crashes: u32,
+    pub restarts: u32,
+    pub last_exit: Option<PipeExit>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PipeExit {
+    pub at: DateTime<Utc>,
+    pub success: bool,
+    /// Exit code of the process, `None` when killed by a signal or not a process
+    pub code: Option<i32>,
+    /// Why the pipe failed to start or run
+    pub error: Option<String>,
+}
+
+impl PipeExit {
+    fn from_status(status: std::process::ExitStatus) -> Self {
+        PipeExit {
+            at: Utc::now(),
+            success: status.success(),
+            code: status.code(),
+            error: None,
+        }
+    }
+
+    fn from_result(result: Result<()>) -> Self {
+        PipeExit {
+            at: Utc::now(),
+            success: result.is_ok(),
+            code: None,
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for PipeExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.error, self.code) {
+            (Some(error), _) => write!(f, "failed: {}", error),
+            (None, Some(code)) => write!(f, "exited with status {}", code),
+            (None, None) if self.success => write!(f, "exited"),
+            (None, None) => write!(f, "was killed"),
+        }
+    }
+}
+
+/// When a pipe is restarted after it exits, set by the `restart` section of its pipe.json:
+///
+/// ```json
+/// "restart": {
+///   "policy": "on-failure",
+///   "backoff_secs": 1,
+///   "max_backoff_secs": 300,
+///   "max_restarts": 5
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartConfig {
+    pub policy: RestartPolicy,
+    /// Delay before restarting, doubled after each consecutive failure
+    pub backoff_secs: u64,
+    pub max_backoff_secs: u64,
+    /// Consecutive failures after which the pipe stays down, unlimited when unset
+    pub max_restarts: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    OnFailure,
+    Always,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        RestartConfig {
+            policy: RestartPolicy::Never,
+            backoff_secs: 1,
+            max_backoff_secs: 300,
+            max_restarts: None,
+        }
+    }
+}
+
+/// A pipe running this long is healthy again, its next failure is restarted quickly.
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+impl RestartConfig {
+    /// Whether to restart after `exit`, `failures` being the consecutive failures so far.
+    fn should_restart(&self, exit: &PipeExit, failures: u32) -> bool {
+        let wanted = match self.policy {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => !exit.success,
+            RestartPolicy::Always => true,
+        };
+        wanted && self.max_restarts.map_or(true, |max| failures <= max)
+    }
+
+    fn backoff(&self, failures: u32) -> Duration {
+        let factor = 2u64.saturating_pow(failures.saturating_sub(1));
+        Duration::from_secs(
+            self.backoff_secs
+                .saturating_mul(factor)
+                .min(self.max_backoff_secs),
+        )
+    }
+}
+
+async fn restart_config(pipe_dir: &Path) -> RestartConfig {
+    let config: Value = match tokio::fs::read_to_string(pipe_dir.join("pipe.json")).await {
+        Ok(config) => serde_json::from_str(&config).unwrap_or_default(),
+        Err(_) => Value::Null,
+    };
+    match config.get("restart") {
+        None | Some(Value::Null) => RestartConfig::default(),
+        Some(restart) => serde_json::from_value(restart.clone()).unwrap_or_else(|e| {
+            warn!("ignoring invalid restart config of pipe: {}", e);
+            RestartConfig::default()
+        }),
+    }
+}
+
+/// Files a pipe writes while it runs, kept when it is updated.
+const PIPE_STATE_FILES: [&str; 3] = ["logs", "kv.json", ".cron_state.json"];
+
+/// How a pipe runs.
+enum PipeRuntime {
+    /// A bun process with these extra environment variables
+    Bun { env: Vec<(String, String)> },
+    #[cfg(feature = "wasm-pipes")]
+    Wasm(WasmPipeConfig),
+}
+
+/// Run a pipe until it exits, `None` when it was stopped through `kill_rx`.
+async fn run_pipe_once(
+    id: &str,
+    screenpipe_dir: &Path,
+    runtime: &PipeRuntime,
+    running_pipes: &RwLock<HashMap<String, PipeHandle>>,
+    kill_tx: &Sender<()>,
+    kill_rx: &mut mpsc::Receiver<()>,
+) -> Option<PipeExit> {
+    match runtime {
+        PipeRuntime::Bun { env } => {
+            let started =
+                screenpipe_core::run_pipe_with_env(id, screenpipe_dir.to_path_buf(), env.clone())
+                    .await;
+            let (mut child, pipe_state) = match started {
+                Ok(started) => started,
+                Err(e) => {
+                    error!("[{}] failed to start pipe {}:", id, e);
+                    return Some(PipeExit::from_result(Err(e)));
+                }
+            };
+
+            if let Some(handle) = running_pipes
+                .write()
+                .await
+                .get_mut(id)
+                .filter(|handle| handle.kill_tx.same_channel(kill_tx))
+            {
+                handle.state = Some(pipe_state);
+            }
+            match pipe_state {
+                PipeState::Port(port) => {
+                    info!("started pipe: {} on port {}", id, port);
+                }
+                PipeState::Pid(pid) => {
+                    info!("started pipe: {} on pid {}", id, pid);
+                }
+            }
+
+            tokio::select! {
+                status = child.wait() => Some(match status {
+                    Ok(status) => PipeExit::from_status(status),
+                    Err(e) => PipeExit::from_result(Err(e.into())),
+                }),
+                _ = kill_rx.recv() => {
+                    // Kill received through channel
+                    let _ = child.kill().await;
+                    None
+                }
+            }
+        }
+        #[cfg(feature = "wasm-pipes")]
+        PipeRuntime::Wasm(config) => {
+            info!("started wasm pipe: {}", id);
+            tokio::select! {
+                result = run_wasm_pipe(config.clone()) => Some(PipeExit::from_result(result)),
+                _ = kill_rx.recv() => None,
+            }
+        }
+    }
 }
 
 struct PipeHandle {
-    state: PipeState,
+    /// `None` for WebAssembly pipes, which run in-process
+    state: Option<PipeState>,
     kill_tx: Sender<()>,
+    /// `CRON_SECRET` of a pipe with cron jobs
+    cron_secret: Option<String>,
+}
+
+/// API token of a running pipe, revoked when dropped.
+struct PipeToken {
+    token_store: Arc<TokenStore>,
+    id: String,
+}
+
+impl Drop for PipeToken {
+    fn drop(&mut self) {
+        if let Err(e) = self.token_store.revoke(&self.id) {
+            warn!("failed to revoke pipe token {}: {}", self.id, e);
+        }
+    }
+}
+
+/// Scopes of a pipe's token: the `scopes` of its pipe.json, `read:search` by default.
+/// Pipes can't get `admin` nor the `admin:*` scopes, with which they could rewrite the
+/// history or install other pipes.
+async fn pipe_scopes(pipe_dir: &Path) -> Vec<Scope> {
+    let config: Value = match tokio::fs::read_to_string(pipe_dir.join("pipe.json")).await {
+        Ok(config) => serde_json::from_str(&config).unwrap_or_default(),
+        Err(_) => Value::Null,
+    };
+    let Some(requested) = config.get("scopes").and_then(Value::as_array) else {
+        return vec![Scope::ReadSearch];
+    };
+
+    requested
+        .iter()
+        .filter_map(Value::as_str)
+        .filter_map(|scope| match scope.parse::<Scope>() {
+            Ok(scope) if !scope.is_admin() => Some(scope),
+            _ => {
+                warn!("ignoring scope {} requested by pipe", scope);
+                None
+            }
+        })
+        .collect()
 }
 
 pub struct PipeManager {
     screenpipe_dir: PathBuf,
     running_pipes: Arc<RwLock<HashMap<String, PipeHandle>>>,
+    token_store: Option<Arc<TokenStore>>,
+    db: Option<Arc<DatabaseManager>>,
+    health: Arc<RwLock<HashMap<String, PipeHealth>>>,
 }
 
 impl PipeManager {
@@ -39,9 +304,43 @@ impl PipeManager {
         PipeManager {
             screenpipe_dir,
             running_pipes: Arc::new(RwLock::new(HashMap::new())),
+            token_store: None,
+            db: None,
+            health: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
+    /// Give every pipe an API token from `token_store` while it runs (when auth is enabled)
+    pub fn with_token_store(mut self, token_store: Option<Arc<TokenStore>>) -> Self {
+        self.token_store = token_store;
+        self
+    }
+
+    /// Database searched by WebAssembly pipes, which can't start without it
+    pub fn with_database(mut self, db: Arc<DatabaseManager>) -> Self {
+        self.db = Some(db);
+        self
+    }
+
+    /// How to run the pipe in `pipe_dir` in-process, `None` when it isn't a WebAssembly pipe.
+    #[cfg(feature = "wasm-pipes")]
+    async fn wasm_pipe_config(&self, id: &str, pipe_dir: &Path) -> Result<Option<WasmPipeConfig>> {
+        if !is_wasm_pipe(pipe_dir) {
+            return Ok(None);
+        }
+        let db = self
+            .db
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("wasm pipe {} needs the database", id))?;
+        Ok(Some(WasmPipeConfig {
+            id: id.to_string(),
+            pipe_dir: pipe_dir.to_path_buf(),
+            db,
+            scopes: pipe_scopes(pipe_dir).await,
+            permissions: PipePermissions::load(pipe_dir).await?,
+        }))
+    }
+
     pub async fn update_config(&self, id: &str, new_config: Value) -> Result<()> {
         debug!("Updating config for pipe: {}", id);
         let pipe_dir = self.screenpipe_dir.join("pipes").join(id);
@@ -176,6 +475,11 @@ impl PipeManager {
                 .unwrap_or(false),
             desc: desc_pipe,
             build_status: config.get("buildStatus").cloned(),
+            health: PipeHealth::default(),
+            lock: PipeLock::load(&pipe_path).await.unwrap_or_else(|e| {
+                warn!("invalid lock of pipe {:?}: {}", pipe_path, e);
+                None
+            }),
         }
     }
 
@@ -201,14 +505,61 @@ impl PipeManager {
             }
         }
 
+        let health = self.health.read().await;
+        for pipe in &mut pipe_infos {
+            if let Some(health) = health.get(&pipe.id) {
+                pipe.health = health.clone();
+            }
+        }
         pipe_infos
     }
 
+    /// Port and `CRON_SECRET` of a running pipe serving its cron routes.
+    pub async fn cron_target(&self, id: &str) -> Option<(u16, String)> {
+        let pipes = self.running_pipes.read().await;
+        let handle = pipes.get(id)?;
+        match (handle.state, &handle.cron_secret) {
+            (Some(PipeState::Port(port)), Some(secret)) => Some((port, secret.clone())),
+            _ => None,
+        }
+    }
+
+    /// Directory of an installed pipe.
+    pub fn pipe_dir(&self, id: &str) -> PathBuf {
+        self.screenpipe_dir.join("pipes").join(id)
+    }
+
+    /// The last `lines` lines of an installed pipe's log.
+    pub async fn pipe_logs(&self, id: &str, lines: usize) -> Result<Vec<PipeLogLine>> {
+        let pipe_dir = self.screenpipe_dir.join("pipes").join(id);
+        if !pipe_dir.exists() {
+            return Err(anyhow::anyhow!("pipe '{}' does not exist", id));
+        }
+        tail_pipe_log(&pipe_dir, lines).await
+    }
+
+    /// Permissions declared by an installed pipe, `None` when its pipe.json has none.
+    pub async fn pipe_permissions(&self, id: &str) -> Result<Option<PipePermissions>> {
+        PipePermissions::load(&self.screenpipe_dir.join("pipes").join(id)).await
+    }
+
+    /// API scopes an installed pipe gets, see [`pipe_scopes`].
+    pub async fn pipe_scopes(&self, id: &str) -> Vec<Scope> {
+        pipe_scopes(&self.screenpipe_dir.join("pipes").join(id)).await
+    }
+
     pub async fn download_pipe(&self, url: &str) -> Result<String> {
         // Remove any surrounding quotes and normalize backslashes
         let normalized_url = url.trim_matches('"').replace("\\", "/");
 
-        let pipe_dir = download_pipe(&normalized_url, self.screenpipe_dir.clone()).await?;
+        // GitHub sources are downloaded at the commit their branch points to now
+        let (download_url, commit) = match pin_github_source(&normalized_url).await? {
+            Some((pinned_url, commit)) => (pinned_url, Some(commit)),
+            None => (normalized_url.clone(), None),
+        };
+        let pipe_dir = download_pipe(&download_url, self.screenpipe_dir.clone()).await?;
+        self.lock_or_remove(&pipe_dir, &normalized_url, commit)
+            .await?;
 
         // update the config with the source url
         self.update_config(
@@ -235,6 +586,7 @@ impl PipeManager {
         pipe_id: &str,
     ) -> Result<String> {
         let pipe_dir = download_pipe_private(pipe_name, url, self.screenpipe_dir.clone()).await?;
+        self.lock_or_remove(&pipe_dir, url, None).await?;
 
         let package_json_path = pipe_dir.join("package.json");
         let version = if package_json_path.exists() {
@@ -268,6 +620,35 @@ impl PipeManager {
         Ok(pipe_dir.file_name().unwrap().to_string_lossy().into_owned())
     }
 
+    /// Hash the pipe installed in `pipe_dir`, check its signature and write its lock.
+    async fn lock(
+        &self,
+        pipe_dir: &Path,
+        source: &str,
+        commit: Option<String>,
+    ) -> Result<PipeLock> {
+        let trusted = TrustedPublishers::load(&self.screenpipe_dir).await?;
+        lock_pipe(pipe_dir, source, commit, &trusted).await
+    }
+
+    /// Lock a freshly installed pipe, removing it when its signature doesn't verify.
+    async fn lock_or_remove(
+        &self,
+        pipe_dir: &Path,
+        source: &str,
+        commit: Option<String>,
+    ) -> Result<PipeLock> {
+        match self.lock(pipe_dir, source, commit).await {
+            Ok(lock) => Ok(lock),
+            Err(e) => {
+                if let Err(e) = tokio::fs::remove_dir_all(pipe_dir).await {
+                    warn!("failed to remove rejected pipe {:?}: {}", pipe_dir, e);
+                }
+                Err(anyhow::anyhow!("pipe rejected: {}", e))
+            }
+        }
+    }
+
     pub async fn purge_pipes(&self) -> Result<()> {
         let mut retries = 3;
 
@@ -335,7 +716,9 @@ impl PipeManager {
         // Then delete the directory
         let pipe_dir = self.screenpipe_dir.join("pipes").join(id);
         if pipe_dir.exists() {
-            tokio::fs::remove_dir_all(pipe_dir).await?;
+            let dir = pipe_dir.clone();
+            with_log_closed(&pipe_dir, move || std::fs::remove_dir_all(dir)).await?;
+            self.health.write().await.remove(id);
             debug!("deleted pipe: {}", id);
             Ok(())
         } else {
@@ -351,9 +734,6 @@ impl PipeManager {
             // Send kill signal and wait for confirmation
             handle.kill_tx.send(()).await?;
 
-            // Clean up any running cron jobs
-            screenpipe_core::pipes::cleanup_pipe_crons(id).await?;
-
             // Wait a bit for the process to actually terminate
             tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
@@ -391,7 +771,7 @@ impl PipeManager {
             }
 
             match handle.state {
-                PipeState::Port(port) => {
+                Some(PipeState::Port(port)) => {
                     tokio::task::spawn(async move {
                         // killport doesn't seems working
                         #[cfg(unix)]
@@ -484,7 +864,7 @@ impl PipeManager {
                     .await
                     .map_err(|e| anyhow::anyhow!("Failed to kill port: {}", e))?;
                 }
-                PipeState::Pid(pid) => {
+                Some(PipeState::Pid(pid)) => {
                     // Force kill the process if it's still running
                     #[cfg(unix)]
                     {
@@ -506,11 +886,9 @@ impl PipeManager {
                         }
                     }
                 }
+                None => {}
             }
 
-            // Clean up cron jobs
-            screenpipe_core::pipes::cleanup_pipe_crons(id).await?;
-
             info!("stopped pipe: {}", id);
         }
         Ok(())
@@ -519,59 +897,157 @@ impl PipeManager {
     pub async fn start_pipe_task(&self, id: String) -> Result<impl Future<Output = Result<()>>> {
         let screenpipe_dir = self.screenpipe_dir.clone();
         let running_pipes = self.running_pipes.clone();
-        let id_for_map = id.clone();
+        let health = self.health.clone();
+        let pipe_dir = screenpipe_dir.join("pipes").join(&id);
+
+        let trusted = TrustedPublishers::load(&screenpipe_dir).await?;
+        if let Err(e) = verify_pipe(&pipe_dir, &trusted).await {
+            let e = anyhow::anyhow!("refusing to start pipe {}: {}", id, e);
+            pipe_log(&pipe_dir).write(LogStream::System, &e.to_string());
+            return Err(e);
+        }
+
+        #[cfg(feature = "wasm-pipes")]
+        let wasm = self.wasm_pipe_config(&id, &pipe_dir).await?;
+        #[cfg(not(feature = "wasm-pipes"))]
+        if pipe_dir.join("pipe.wasm").exists() {
+            anyhow::bail!("wasm pipe {} needs a build with the wasm-pipes feature", id);
+        }
+
+        #[cfg(feature = "wasm-pipes")]
+        let is_wasm = wasm.is_some();
+        #[cfg(not(feature = "wasm-pipes"))]
+        let is_wasm = false;
+
+        // wasm pipes are checked in-process, bun pipes only through their token
+        let permissions = PipePermissions::load(&pipe_dir).await?;
+        if !is_wasm
+            && self.token_store.is_none()
+            && permissions
+                .as_ref()
+                .is_some_and(PipePermissions::restricts_api)
+        {
+            let e = anyhow::anyhow!(
+                "refusing to start pipe {}: its permissions restrict api access, which is only enforced with --enable-auth",
+                id
+            );
+            pipe_log(&pipe_dir).write(LogStream::System, &e.to_string());
+            return Err(e);
+        }
+
+        let mut env = Vec::new();
+        let mut token = None;
+        if let Some(token_store) = self.token_store.as_ref().filter(|_| !is_wasm) {
+            let scopes = pipe_scopes(&pipe_dir).await;
+            let name = format!("pipe:{}", id);
+            let (api_token, secret) = match permissions {
+                Some(permissions) => token_store.issue_restricted(&name, scopes, permissions),
+                None => token_store.issue_ephemeral(&name, scopes),
+            };
+            env.push((TOKEN_ENV.to_string(), secret));
+            token = Some(PipeToken {
+                token_store: token_store.clone(),
+                id: api_token.id,
+            });
+        }
+
+        // cron jobs call the pipe's routes, see PipeScheduler
+        let has_crons = !crate::pipe_scheduler::cron_jobs(&pipe_dir).await.is_empty();
+        let cron_secret = has_crons.then(screenpipe_core::generate_cron_secret);
+        if let Some(secret) = &cron_secret {
+            env.push(("CRON_SECRET".to_string(), secret.clone()));
+        }
+
+        #[cfg(feature = "wasm-pipes")]
+        let runtime = match wasm {
+            Some(config) => PipeRuntime::Wasm(config),
+            None => PipeRuntime::Bun { env },
+        };
+        #[cfg(not(feature = "wasm-pipes"))]
+        let runtime = PipeRuntime::Bun { env };
+        let restart = restart_config(&pipe_dir).await;
+        let log = pipe_log(&pipe_dir);
 
         Ok(async move {
-            match screenpipe_core::run_pipe(&id, screenpipe_dir.clone()).await {
-                Ok((mut child, pipe_state)) => {
-                    let (kill_tx, mut kill_rx) = mpsc::channel::<()>(1);
-
-                    running_pipes.write().await.insert(
-                        id_for_map.clone(),
-                        PipeHandle {
-                            state: pipe_state,
-                            kill_tx: kill_tx.clone(),
-                        },
-                    );
+            let _token = token;
+            let (kill_tx, mut kill_rx) = mpsc::channel::<()>(1);
+            running_pipes.write().await.insert(
+                id.clone(),
+                PipeHandle {
+                    state: None,
+                    kill_tx: kill_tx.clone(),
+                    cron_secret,
+                },
+            );
 
-                    match pipe_state {
-                        PipeState::Port(port) => {
-                            info!("started pipe: {} on port {}", id, port);
-                        }
-                        PipeState::Pid(pid) => {
-                            info!("started pipe: {} on pid {}", id, pid);
-                        }
-                    }
+            let mut failures = 0;
+            let result = loop {
+                health.write().await.entry(id.clone()).or_default().running = true;
+                log.write(LogStream::System, "starting");
+                let started = Instant::now();
+                let Some(exit) = run_pipe_once(
+                    &id,
+                    &screenpipe_dir,
+                    &runtime,
+                    &running_pipes,
+                    &kill_tx,
+                    &mut kill_rx,
+                )
+                .await
+                else {
+                    log.write(LogStream::System, "stopped");
+                    break Ok(());
+                };
+
+                if started.elapsed() >= STABLE_RUN {
+                    failures = 0;
+                }
+                if !exit.success {
+                    failures += 1;
+                    warn!("pipe {} {}", id, exit);
+                }
+                log.write(LogStream::System, &exit.to_string());
+                {
+                    let mut health = health.write().await;
+                    let health = health.entry(id.clone()).or_default();
+                    health.running = false;
+                    health.crashes += u32::from(!exit.success);
+                    health.last_exit = Some(exit.clone());
+                }
 
-                    tokio::select! {
-                        status = child.wait() => {
-                            match status {
-                                Ok(status) if !status.success() => {
-                                    println!("pipe {} exited with status: {}", id, status);
-                                    running_pipes.write().await.remove(&id_for_map);
-                                    anyhow::bail!("pipe exited with non-zero status: {}", status);
-                                }
-                                Err(e) => {
-                                    println!("error waiting for pipe {}: {}", id, e);
-                                    running_pipes.write().await.remove(&id_for_map);
-                                    anyhow::bail!("error waiting for pipe: {}", e);
-                                }
-                                Ok(_) => Ok(())
-                            }
-                        }
-                        _ = kill_rx.recv() => {
-                            // Kill received through channel
-                            let _ = child.kill().await;
-                            running_pipes.write().await.remove(&id_for_map);
-                            Ok(())
-                        }
-                    }
+                if !restart.should_restart(&exit, failures) {
+                    break if exit.success {
+                        Ok(())
+                    } else {
+                        Err(anyhow::anyhow!("pipe {} {}", id, exit))
+                    };
                 }
-                Err(e) => {
-                    error!("[{}] failed to start pipe {}:", id, e);
-                    Err(e)
+                let delay = restart.backoff(failures);
+                info!("restarting pipe {} in {:?}", id, delay);
+                log.write(
+                    LogStream::System,
+                    &format!("restarting in {}s", delay.as_secs()),
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = kill_rx.recv() => break Ok(()),
                 }
+                health.write().await.entry(id.clone()).or_default().restarts += 1;
+            };
+            log.close();
+
+            if let Some(health) = health.write().await.get_mut(&id) {
+                health.running = false;
+            }
+            // stop_pipe may have replaced the handle by a new run already
+            let mut pipes = running_pipes.write().await;
+            if pipes
+                .get(&id)
+                .is_some_and(|handle| handle.kill_tx.same_channel(&kill_tx))
+            {
+                pipes.remove(&id);
             }
+            result
         })
     }
 
@@ -584,9 +1060,8 @@ impl PipeManager {
         let config = tokio::fs::read_to_string(&pipe_json_path).await?;
         let mut config: Value = serde_json::from_str(&config)?;
 
-        // Save the build status from the existing config
-        let build_status = config.get("buildStatus").cloned();
-        debug!("preserving build status: {:?}", build_status);
+        // Restored if the new version fails to build
+        let previous_config = config.clone();
 
         // Update build status to indicate update has started
         if let Some(obj) = config.as_object_mut() {
@@ -753,6 +1228,25 @@ impl PipeManager {
             }
         }
 
+        // Stage the new version next to the installed one, with the installed config
+        let staging_dir = pipe_dir.with_file_name(format!(".{}.next", id));
+        let backup_dir = pipe_dir.with_file_name(format!(".{}.previous", id));
+        for dir in [&staging_dir, &backup_dir] {
+            if dir.exists() {
+                tokio::fs::remove_dir_all(dir).await?;
+            }
+        }
+        copy_dir_all(&tmp_pipe_dir, &staging_dir).await?;
+        tokio::fs::remove_dir_all(&tmp_dir).await?;
+        debug!("staged new version in {:?}", staging_dir);
+
+        // the previous build is left behind with the previous version
+        if let Some(obj) = config.as_object_mut() {
+            obj.insert("build".to_string(), Value::Bool(false));
+        }
+        let updated_config = serde_json::to_string_pretty(&config)?;
+        tokio::fs::write(staging_dir.join("pipe.json"), updated_config).await?;
+
         // 2. Stop current pipe if running
         if let Err(e) = self.stop_pipe(id).await {
             // Update build status to indicate stopping failure
@@ -773,81 +1267,73 @@ impl PipeManager {
         }
         debug!("stopped running pipe");
 
-        // Update build status to indicate removing old files
-        if let Some(obj) = config.as_object_mut() {
-            obj.insert(
-                "buildStatus".to_string(),
-                serde_json::json!({
-                    "status": "in_progress",
-                    "step": "removing old files",
-                    "message": "Removing old files"
-                }),
-            );
-            let updated_config = serde_json::to_string_pretty(&config)?;
-            tokio::fs::write(&pipe_json_path, updated_config).await?;
-        }
-
-        // 3. Remove old files
-        let files_to_remove = ["node_modules", "bun.lockb", "package.json"];
-        for file in files_to_remove {
-            let path = pipe_dir.join(file);
-            if path.exists() {
-                if path.is_dir() {
-                    tokio::fs::remove_dir_all(&path).await?;
-                } else {
-                    tokio::fs::remove_file(&path).await?;
-                }
-                debug!("removed: {}", file);
-            }
-        }
-
-        debug!("moved old files to trash");
+        // 3. Swap the versions, keeping what the pipe wrote while it ran
+        copy_state_files(&pipe_dir, &staging_dir).await?;
+        let (from, to, staged) = (pipe_dir.clone(), backup_dir.clone(), staging_dir.clone());
+        with_log_closed(&pipe_dir, move || {
+            std::fs::rename(&from, &to)?;
+            std::fs::rename(&staged, &from)
+        })
+        .await?;
+        debug!("moved previous version to {:?}", backup_dir);
 
-        // Update build status to indicate copying new files
+        // Update build status to indicate building
         if let Some(obj) = config.as_object_mut() {
             obj.insert(
                 "buildStatus".to_string(),
                 serde_json::json!({
                     "status": "in_progress",
-                    "step": "copying new files",
-                    "message": "Copying new files"
+                    "step": "building",
+                    "message": "Building new version"
                 }),
             );
             let updated_config = serde_json::to_string_pretty(&config)?;
             tokio::fs::write(&pipe_json_path, updated_config).await?;
         }
 
-        // 4. Move new files from temp to pipe dir
-        let mut entries = tokio::fs::read_dir(&tmp_pipe_dir).await?;
-        while let Some(entry) = entries.next_entry().await? {
-            let file_name = entry.file_name();
-            let src_path = entry.path();
-            let dst_path = pipe_dir.join(&file_name);
+        // 4. Build and lock the new version, rolling back to the previous one on failure
+        let installed = match build_pipe(&pipe_dir).await {
+            Ok(()) => self.lock(&pipe_dir, source, None).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = installed {
+            warn!("update of pipe {} failed, rolling back: {}", id, e);
+            copy_state_files(&pipe_dir, &backup_dir).await?;
+            let (failed, previous) = (pipe_dir.clone(), backup_dir.clone());
+            with_log_closed(&pipe_dir, move || {
+                std::fs::remove_dir_all(&failed)?;
+                std::fs::rename(&previous, &failed)
+            })
+            .await?;
 
-            // Skip pipe.json to preserve configuration
-            if file_name == "pipe.json" {
-                continue;
+            let mut config = previous_config;
+            if let Some(obj) = config.as_object_mut() {
+                obj.insert(
+                    "buildStatus".to_string(),
+                    serde_json::json!({
+                        "status": "error",
+                        "step": "building",
+                        "error": format!("Update failed, rolled back: {}", e)
+                    }),
+                );
             }
+            let updated_config = serde_json::to_string_pretty(&config)?;
+            tokio::fs::write(&pipe_json_path, updated_config).await?;
 
-            if src_path.is_dir() {
-                if dst_path.exists() {
-                    debug!("removing old dir: {:?}", dst_path);
-                    tokio::fs::remove_dir_all(&dst_path).await?;
-                }
-                debug!("creating new dir: {:?}", dst_path);
-                tokio::fs::create_dir_all(&dst_path).await?;
-                debug!("copying new files: {:?}", src_path);
-                copy_dir_all(&src_path, &dst_path).await?;
-            } else {
-                debug!("copying new file: {:?}", src_path);
-                tokio::fs::copy(&src_path, &dst_path).await?;
+            if config
+                .get("enabled")
+                .and_then(Value::as_bool)
+                .unwrap_or(false)
+            {
+                tokio::spawn(self.start_pipe_task(id.to_string()).await?);
             }
-            debug!("moved: {:?}", file_name);
+            return Err(anyhow::anyhow!(
+                "failed to update pipe {}, rolled back: {}",
+                id,
+                e
+            ));
         }
-
-        // Clean up temp directory
-        tokio::fs::remove_dir_all(&tmp_dir).await?;
-        debug!("cleaned up temp dir");
+        tokio::fs::remove_dir_all(&backup_dir).await?;
 
         // Update build status to indicate restarting pipe
         if let Some(obj) = config.as_object_mut() {
@@ -926,6 +1412,29 @@ impl PipeManager {
     }
 }
 
+/// Copy the [`PIPE_STATE_FILES`] of a pipe directory to another version of it.
+async fn copy_state_files(from: &Path, to: &Path) -> Result<()> {
+    for file in PIPE_STATE_FILES {
+        let path = from.join(file);
+        if path.is_dir() {
+            copy_dir_all(&path, to.join(file)).await?;
+        } else if path.exists() {
+            tokio::fs::copy(&path, to.join(file)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Move or delete `pipe_dir` with `f`, its log closed meanwhile.
+async fn with_log_closed(
+    pipe_dir: &Path,
+    f: impl FnOnce() -> std::io::Result<()> + Send + 'static,
+) -> Result<()> {
+    let log = pipe_log(pipe_dir);
+    tokio::task::spawn_blocking(move || log.while_closed(f)).await??;
+    Ok(())
+}
+
 // Helper function to recursively copy directories
 async fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()> {
     let src = src.as_ref();
@@ -953,3 +1462,64 @@ async fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pipe_scopes_exclude_admin() {
+        let dir = tempfile::tempdir().unwrap();
+        tokio::fs::write(
+            dir.path().join("pipe.json"),
+            r#"{"scopes": ["read:media", "admin", "admin:pipes", "admin:raw_sql", "nope"]}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(pipe_scopes(dir.path()).await, vec![Scope::ReadMedia]);
+
+        tokio::fs::write(dir.path().join("pipe.json"), "{}")
+            .await
+            .unwrap();
+        assert_eq!(pipe_scopes(dir.path()).await, vec![Scope::ReadSearch]);
+    }
+
+    #[test]
+    fn test_restart_policy_and_backoff() {
+        let failed = PipeExit {
+            at: Utc::now(),
+            success: false,
+            code: Some(1),
+            error: None,
+        };
+        let done = PipeExit {
+            success: true,
+            code: Some(0),
+            ..failed.clone()
+        };
+
+        let never = RestartConfig::default();
+        assert!(!never.should_restart(&failed, 1));
+
+        let on_failure: RestartConfig = serde_json::from_value(serde_json::json!({
+            "policy": "on-failure",
+            "backoff_secs": 2,
+            "max_backoff_secs": 30,
+            "max_restarts": 3
+        }))
+        .unwrap();
+        assert!(on_failure.should_restart(&failed, 3));
+        assert!(!on_failure.should_restart(&failed, 4));
+        assert!(!on_failure.should_restart(&done, 0));
+        assert_eq!(on_failure.backoff(1), Duration::from_secs(2));
+        assert_eq!(on_failure.backoff(3), Duration::from_secs(8));
+        assert_eq!(on_failure.backoff(10), Duration::from_secs(30));
+
+        let always = RestartConfig {
+            policy: RestartPolicy::Always,
+            ..Default::default()
+        };
+        assert!(always.should_restart(&done, 0));
+        assert_eq!(always.backoff(0), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,686 @@
+//! Scheduler of pipe cron jobs
+//!
+//! Pipes declare cron jobs in their pipe.json:
+//!
+//! ```json
+//! "crons": [
+//!   { "path": "/api/digest", "schedule": "0 9 * * 1-5", "timezone": "Europe/Paris",
+//!     "catch_up": "skip", "timeout_secs": 600 }
+//! ]
+//! ```
+//!
+//! A job calls `GET <path>` on the running pipe with `Authorization: Bearer $CRON_SECRET`.
+//! Schedules are standard 5-field cron expressions, or 6 and 7-field ones starting with
+//! seconds, evaluated in the timezone of the job or else in local time.
+//!
+//! Occurrences missed while the computer slept or screenpipe was not running are run
+//! once as soon as possible (`"catch_up": "once"`, the default) or dropped (`"skip"`).
+//! At most [`MAX_CONCURRENT_RUNS`] jobs run at once and a job never overlaps itself.
+//! Every run is recorded in `pipe_runs`, listed by `GET /pipes/:id/runs`, and
+//! `POST /pipes/:id/runs` runs jobs now.
+
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Local, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use screenpipe_core::get_last_cron_execution;
+use screenpipe_db::{DatabaseManager, PipeRun};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tracing::{debug, error, info, warn};
+
+use crate::pipe_manager::PipeManager;
+use crate::server::{api_error, ApiError, AppState};
+use crate::sleep_monitor::recently_woke_from_sleep;
+
+/// Jobs running at once, across all pipes.
+pub const MAX_CONCURRENT_RUNS: usize = 4;
+
+const TICK: Duration = Duration::from_secs(1);
+
+/// Time between two reads of the cron jobs of the enabled pipes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Delay after which an occurrence counts as missed rather than late.
+const MISSED_AFTER: chrono::Duration = chrono::Duration::seconds(60);
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Bytes of the response body kept in the run history.
+const MAX_OUTPUT_BYTES: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CatchUp {
+    /// Run once for all the missed occurrences
+    #[default]
+    Once,
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CronJobConfig {
+    pub path: String,
+    pub schedule: String,
+    /// IANA name, local time when unset
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub catch_up: CatchUp,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    schedule: Schedule,
+    timezone: Option<Tz>,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str, timezone: Option<&str>) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let expression = match fields.as_slice() {
+            // `cron` wants seconds first and counts days of the week from 1 for Sunday
+            [minute, hour, day, month, weekday] => format!(
+                "0 {} {} {} {} {}",
+                minute,
+                hour,
+                day,
+                month,
+                standard_weekdays(weekday)
+            ),
+            _ => fields.join(" "),
+        };
+        let schedule = Schedule::from_str(&expression)
+            .map_err(|e| anyhow!("invalid cron schedule '{}': {}", expression, e))?;
+        let timezone = timezone
+            .map(|tz| {
+                tz.parse::<Tz>()
+                    .map_err(|e| anyhow!("invalid timezone '{}': {}", tz, e))
+            })
+            .transpose()?;
+        Ok(Self { schedule, timezone })
+    }
+
+    /// First occurrence strictly after `time`.
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self.timezone {
+            Some(tz) => self
+                .schedule
+                .after(&time.with_timezone(&tz))
+                .next()
+                .map(|next| next.with_timezone(&Utc)),
+            None => self
+                .schedule
+                .after(&time.with_timezone(&Local))
+                .next()
+                .map(|next| next.with_timezone(&Utc)),
+        }
+    }
+}
+
+/// Day of week field of standard cron (0 or 7 for Sunday) in the numbering of `cron`.
+fn standard_weekdays(field: &str) -> String {
+    let day = |day: &str| match day.parse::<u8>() {
+        Ok(7) => "1".to_string(),
+        Ok(day) => (day + 1).to_string(),
+        Err(_) => day.to_string(),
+    };
+    field
+        .split(',')
+        .map(|part| {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, Some(step)),
+                None => (part, None),
+            };
+            let range = match range.split_once('-') {
+                // ranges can't wrap around to Sunday
+                Some((start, "7")) if step.is_none() => format!("{}-7,1", day(start)),
+                Some((start, end)) => format!("{}-{}", day(start), day(end)),
+                None => day(range),
+            };
+            match step {
+                Some(step) => format!("{}/{}", range, step),
+                None => range,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[derive(Debug, Clone)]
+pub struct CronJob {
+    pub config: CronJobConfig,
+    pub schedule: CronSchedule,
+}
+
+impl CronJob {
+    fn timeout(&self) -> Duration {
+        self.config
+            .timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TIMEOUT)
+    }
+}
+
+/// Cron jobs of the pipe in `pipe_dir`, invalid ones skipped.
+pub async fn cron_jobs(pipe_dir: &std::path::Path) -> Vec<CronJob> {
+    let config: Value = match tokio::fs::read_to_string(pipe_dir.join("pipe.json")).await {
+        Ok(config) => serde_json::from_str(&config).unwrap_or_default(),
+        Err(_) => Value::Null,
+    };
+    let Some(crons) = config.get("crons").and_then(Value::as_array) else {
+        return Vec::new();
+    };
+
+    crons
+        .iter()
+        .filter_map(|cron| {
+            let job = serde_json::from_value::<CronJobConfig>(cron.clone())
+                .map_err(anyhow::Error::from)
+                .and_then(|config| {
+                    let schedule =
+                        CronSchedule::parse(&config.schedule, config.timezone.as_deref())?;
+                    Ok(CronJob { config, schedule })
+                });
+            match job {
+                Ok(job) => Some(job),
+                Err(e) => {
+                    warn!("ignoring cron job {} in {:?}: {}", cron, pipe_dir, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunSource {
+    Schedule,
+    CatchUp,
+    Manual,
+}
+
+impl RunSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            RunSource::Schedule => "schedule",
+            RunSource::CatchUp => "catch_up",
+            RunSource::Manual => "manual",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Due {
+    NotYet,
+    Run(RunSource),
+    /// Missed and not caught up, wait for the next occurrence
+    Skip,
+    /// Missed, caught up once the system settled
+    Wait,
+}
+
+fn due(next: DateTime<Utc>, now: DateTime<Utc>, catch_up: CatchUp, just_woke: bool) -> Due {
+    if now < next {
+        return Due::NotYet;
+    }
+    if now - next <= MISSED_AFTER {
+        return Due::Run(RunSource::Schedule);
+    }
+    match catch_up {
+        CatchUp::Skip => Due::Skip,
+        // the network is often not back right after a wake
+        CatchUp::Once if just_woke => Due::Wait,
+        CatchUp::Once => Due::Run(RunSource::CatchUp),
+    }
+}
+
+/// Pipe and path of a job
+type JobKey = (String, String);
+
+struct ScheduledJob {
+    job: CronJob,
+    /// In the past while due, `None` when the schedule has no occurrence left
+    next: Option<DateTime<Utc>>,
+}
+
+/// Runs the cron jobs of the enabled pipes.
+pub struct PipeScheduler {
+    db: Arc<DatabaseManager>,
+    pipe_manager: Arc<PipeManager>,
+    client: reqwest::Client,
+    permits: Arc<Semaphore>,
+    /// Jobs queued or running
+    running: Mutex<HashSet<JobKey>>,
+}
+
+impl PipeScheduler {
+    pub fn new(db: Arc<DatabaseManager>, pipe_manager: Arc<PipeManager>) -> Self {
+        Self {
+            db,
+            pipe_manager,
+            client: reqwest::Client::new(),
+            permits: Arc::new(Semaphore::new(MAX_CONCURRENT_RUNS)),
+            running: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            match self.db.fail_interrupted_pipe_runs().await {
+                Ok(0) => {}
+                Ok(failed) => info!("marked {} interrupted pipe runs as failed", failed),
+                Err(e) => error!("failed to update interrupted pipe runs: {}", e),
+            }
+
+            let mut jobs = HashMap::new();
+            let mut reloaded: Option<Instant> = None;
+            let mut interval = tokio::time::interval(TICK);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                if reloaded.map_or(true, |at| at.elapsed() >= RELOAD_INTERVAL) {
+                    self.reload(&mut jobs).await;
+                    reloaded = Some(Instant::now());
+                }
+                self.tick(&mut jobs).await;
+            }
+        })
+    }
+
+    /// Read the jobs of the enabled pipes, keeping the next occurrence of unchanged ones.
+    async fn reload(&self, jobs: &mut HashMap<JobKey, ScheduledJob>) {
+        let mut current = HashMap::new();
+        for pipe in self.pipe_manager.list_pipes().await {
+            if !pipe.enabled {
+                continue;
+            }
+            for job in cron_jobs(&self.pipe_manager.pipe_dir(&pipe.id)).await {
+                let key = (pipe.id.clone(), job.config.path.clone());
+                let scheduled = match jobs.remove(&key) {
+                    Some(scheduled) if scheduled.job.config == job.config => scheduled,
+                    _ => {
+                        let next = self.first_occurrence(&pipe.id, &job).await;
+                        debug!(
+                            "scheduled cron job {} of pipe {} at {:?}",
+                            key.1, key.0, next
+                        );
+                        ScheduledJob { job, next }
+                    }
+                };
+                current.insert(key, scheduled);
+            }
+        }
+        *jobs = current;
+    }
+
+    /// Occurrence following the last run of a job, in the past when runs were missed.
+    async fn first_occurrence(&self, pipe_id: &str, job: &CronJob) -> Option<DateTime<Utc>> {
+        let path = &job.config.path;
+        let last_run = match self.db.last_scheduled_pipe_run(pipe_id, path).await {
+            Ok(Some(last_run)) => Some(last_run),
+            // runs before the scheduler existed are only in the state file
+            Ok(None) => get_last_cron_execution(&self.pipe_manager.pipe_dir(pipe_id), path)
+                .await
+                .ok()
+                .flatten()
+                .map(DateTime::<Utc>::from),
+            Err(e) => {
+                warn!("failed to get last run of cron job {}: {}", path, e);
+                None
+            }
+        };
+        job.schedule.next_after(last_run.unwrap_or_else(Utc::now))
+    }
+
+    async fn tick(self: &Arc<Self>, jobs: &mut HashMap<JobKey, ScheduledJob>) {
+        let now = Utc::now();
+        let just_woke = recently_woke_from_sleep();
+        for ((pipe_id, path), scheduled) in jobs.iter_mut() {
+            let Some(next) = scheduled.next else {
+                continue;
+            };
+            let source = match due(next, now, scheduled.job.config.catch_up, just_woke) {
+                Due::NotYet | Due::Wait => continue,
+                Due::Skip => {
+                    info!("skipping missed cron job {} of pipe {}", path, pipe_id);
+                    scheduled.next = scheduled.job.schedule.next_after(now);
+                    continue;
+                }
+                Due::Run(source) => source,
+            };
+            // stays due until the pipe is up
+            let Some(target) = self.pipe_manager.cron_target(pipe_id).await else {
+                continue;
+            };
+
+            scheduled.next = scheduled.job.schedule.next_after(now);
+            match self
+                .queue_run(pipe_id, &scheduled.job, target, source)
+                .await
+            {
+                Ok(Some(_)) => {}
+                Ok(None) => warn!(
+                    "cron job {} of pipe {} is still running, skipping this run",
+                    path, pipe_id
+                ),
+                Err(e) => error!(
+                    "failed to queue cron job {} of pipe {}: {}",
+                    path, pipe_id, e
+                ),
+            }
+        }
+    }
+
+    /// Record a run and start it once a permit is free. `None` if the job is already
+    /// queued or running.
+    async fn queue_run(
+        self: &Arc<Self>,
+        pipe_id: &str,
+        job: &CronJob,
+        (port, secret): (u16, String),
+        source: RunSource,
+    ) -> Result<Option<i64>> {
+        let key = (pipe_id.to_string(), job.config.path.clone());
+        if !self.running.lock().unwrap().insert(key.clone()) {
+            return Ok(None);
+        }
+        let id = match self
+            .db
+            .insert_pipe_run(pipe_id, &job.config.path, source.as_str())
+            .await
+        {
+            Ok(id) => id,
+            Err(e) => {
+                self.running.lock().unwrap().remove(&key);
+                return Err(e.into());
+            }
+        };
+
+        let scheduler = self.clone();
+        let url = format!("http://localhost:{}{}", port, job.config.path);
+        let timeout = job.timeout();
+        tokio::spawn(async move {
+            // the semaphore is never closed
+            let _permit = scheduler.permits.clone().acquire_owned().await;
+            scheduler.execute(id, &url, &secret, timeout).await;
+            scheduler.running.lock().unwrap().remove(&key);
+        });
+        Ok(Some(id))
+    }
+
+    async fn execute(&self, id: i64, url: &str, secret: &str, timeout: Duration) {
+        if let Err(e) = self.db.start_pipe_run(id).await {
+            warn!("failed to mark pipe run {} as started: {}", id, e);
+        }
+        info!("running cron job {}", url);
+
+        let started = Instant::now();
+        let (success, http_status, output) = match self
+            .client
+            .get(url)
+            .bearer_auth(secret)
+            .timeout(timeout)
+            .send()
+            .await
+        {
+            Ok(response) => {
+                let status = response.status();
+                let body = response
+                    .text()
+                    .await
+                    .unwrap_or_else(|e| format!("failed to read response: {}", e));
+                (status.is_success(), Some(status.as_u16()), body)
+            }
+            Err(e) => (false, None, e.to_string()),
+        };
+        let duration_ms = started.elapsed().as_millis() as i64;
+        if !success {
+            warn!("cron job {} failed: {:?} {}", url, http_status, output);
+        }
+
+        let status = if success { "success" } else { "failed" };
+        let output = truncate_output(&output);
+        if let Err(e) = self
+            .db
+            .finish_pipe_run(
+                id,
+                status,
+                duration_ms,
+                http_status,
+                (!output.is_empty()).then_some(output),
+            )
+            .await
+        {
+            error!("failed to record pipe run {}: {}", id, e);
+        }
+    }
+}
+
+fn truncate_output(output: &str) -> &str {
+    if output.len() <= MAX_OUTPUT_BYTES {
+        return output;
+    }
+    let mut end = MAX_OUTPUT_BYTES;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    &output[..end]
+}
+
+// ============================================================================
+// API
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct PipeRunsQuery {
+    /// Runs of the job at this path only
+    pub path: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub offset: u32,
+}
+
+fn default_limit() -> u32 {
+    50
+}
+
+#[derive(Deserialize, Default)]
+pub struct RunPipeRequest {
+    /// Run the job at this path only, all the jobs of the pipe otherwise
+    pub path: Option<String>,
+}
+
+fn check_pipes_enabled(state: &AppState) -> Result<(), ApiError> {
+    if state.enable_pipe_manager {
+        Ok(())
+    } else {
+        Err(api_error(
+            StatusCode::FORBIDDEN,
+            "pipe functionality is disabled",
+        ))
+    }
+}
+
+pub async fn list_pipe_runs(
+    State(state): State<Arc<AppState>>,
+    Path(pipe_id): Path<String>,
+    Query(query): Query<PipeRunsQuery>,
+) -> Result<Json<Vec<PipeRun>>, ApiError> {
+    check_pipes_enabled(&state)?;
+    state
+        .db
+        .list_pipe_runs(&pipe_id, query.path.as_deref(), query.limit, query.offset)
+        .await
+        .map(Json)
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Run the cron jobs of a pipe now, the body being optional.
+pub async fn run_pipe_now(
+    State(state): State<Arc<AppState>>,
+    Path(pipe_id): Path<String>,
+    body: Option<Json<RunPipeRequest>>,
+) -> Result<Json<Value>, ApiError> {
+    check_pipes_enabled(&state)?;
+    let request = body.map(|Json(request)| request).unwrap_or_default();
+
+    let pipe_dir = state.pipe_manager.pipe_dir(&pipe_id);
+    if !pipe_dir.exists() {
+        return Err(api_error(
+            StatusCode::NOT_FOUND,
+            format!("pipe '{}' does not exist", pipe_id),
+        ));
+    }
+    let jobs: Vec<CronJob> = cron_jobs(&pipe_dir)
+        .await
+        .into_iter()
+        .filter(|job| {
+            request
+                .path
+                .as_ref()
+                .map_or(true, |path| job.config.path == *path)
+        })
+        .collect();
+    if jobs.is_empty() {
+        return Err(api_error(StatusCode::NOT_FOUND, "no matching cron job"));
+    }
+    let target = state
+        .pipe_manager
+        .cron_target(&pipe_id)
+        .await
+        .ok_or_else(|| api_error(StatusCode::CONFLICT, "pipe is not running"))?;
+
+    let mut run_ids = Vec::new();
+    for job in &jobs {
+        match state
+            .pipe_scheduler
+            .queue_run(&pipe_id, job, target.clone(), RunSource::Manual)
+            .await
+        {
+            Ok(Some(id)) => run_ids.push(id),
+            Ok(None) => debug!(
+                "cron job {} of pipe {} already running",
+                job.config.path, pipe_id
+            ),
+            Err(e) => return Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        }
+    }
+    if run_ids.is_empty() {
+        return Err(api_error(StatusCode::CONFLICT, "already running"));
+    }
+    Ok(Json(json!({ "success": true, "run_ids": run_ids })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn test_standard_schedule() {
+        // 2026-01-04 is a Sunday
+        let weekdays = CronSchedule::parse("0 9 * * 1-5", Some("UTC")).unwrap();
+        assert_eq!(
+            weekdays.next_after(utc(2026, 1, 4, 10, 0)),
+            Some(utc(2026, 1, 5, 9, 0))
+        );
+        let weekend = CronSchedule::parse("0 9 * * 6-7", Some("UTC")).unwrap();
+        assert_eq!(
+            weekend.next_after(utc(2026, 1, 3, 10, 0)),
+            Some(utc(2026, 1, 4, 9, 0))
+        );
+
+        // with seconds, as before
+        let seconds = CronSchedule::parse("*/10 * * * * *", None).unwrap();
+        assert_eq!(
+            seconds.next_after(utc(2026, 1, 4, 10, 0)),
+            Some(utc(2026, 1, 4, 10, 0) + chrono::Duration::seconds(10))
+        );
+
+        assert!(CronSchedule::parse("0 9 * *", None).is_err());
+        assert!(CronSchedule::parse("0 9 * * *", Some("Mars/Olympus")).is_err());
+    }
+
+    #[test]
+    fn test_schedule_timezone() {
+        let schedule = CronSchedule::parse("30 9 * * *", Some("America/New_York")).unwrap();
+        // EST in winter, EDT in summer
+        assert_eq!(
+            schedule.next_after(utc(2026, 1, 10, 0, 0)),
+            Some(utc(2026, 1, 10, 14, 30))
+        );
+        assert_eq!(
+            schedule.next_after(utc(2026, 7, 10, 0, 0)),
+            Some(utc(2026, 7, 10, 13, 30))
+        );
+    }
+
+    #[test]
+    fn test_catch_up() {
+        let next = utc(2026, 1, 5, 9, 0);
+        let late = next + chrono::Duration::seconds(5);
+        let missed = next + chrono::Duration::hours(3);
+
+        assert_eq!(
+            due(
+                next,
+                next - chrono::Duration::seconds(1),
+                CatchUp::Once,
+                false
+            ),
+            Due::NotYet
+        );
+        assert_eq!(
+            due(next, late, CatchUp::Skip, true),
+            Due::Run(RunSource::Schedule)
+        );
+        assert_eq!(
+            due(next, missed, CatchUp::Once, false),
+            Due::Run(RunSource::CatchUp)
+        );
+        assert_eq!(due(next, missed, CatchUp::Once, true), Due::Wait);
+        assert_eq!(due(next, missed, CatchUp::Skip, false), Due::Skip);
+    }
+
+    #[tokio::test]
+    async fn test_cron_jobs_skip_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        tokio::fs::write(
+            dir.path().join("pipe.json"),
+            json!({
+                "crons": [
+                    { "path": "/api/digest", "schedule": "0 9 * * *", "catch_up": "skip" },
+                    { "path": "/api/broken", "schedule": "every day" },
+                    { "schedule": "0 9 * * *" }
+                ]
+            })
+            .to_string(),
+        )
+        .await
+        .unwrap();
+
+        let jobs = cron_jobs(dir.path()).await;
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].config.path, "/api/digest");
+        assert_eq!(jobs[0].config.catch_up, CatchUp::Skip);
+        assert_eq!(jobs[0].timeout(), DEFAULT_TIMEOUT);
+    }
+}
@@ -159,6 +159,12 @@ impl ResourceMonitor {
         let memory_usage_percent = (total_memory / system_total_memory) * 100.0;
         let runtime = self.start_time.elapsed();
 
+        // Latest sample for /metrics (including child processes)
+        let gb = 1024.0 * 1024.0 * 1024.0;
+        metrics::gauge!("screenpipe_process_resident_memory_bytes").set(total_memory * gb);
+        metrics::gauge!("screenpipe_process_virtual_memory_bytes").set(max_virtual_memory * gb);
+        metrics::gauge!("screenpipe_process_cpu_percent").set(total_cpu as f64);
+
         (
             total_memory,
             system_total_memory,
@@ -0,0 +1,538 @@
+//! Data retention
+//!
+//! Prunes old recordings according to per content type age limits and disk quotas.
+//! Rows are deleted through `DatabaseManager` (FTS tables follow via triggers) and the
+//! referenced mp4/audio files are removed from disk.
+//!
+//! Media and text have separate limits: dropping the video files of a chunk keeps its
+//! frames and OCR text searchable, dropping the OCR text removes the chunk entirely.
+//! Webhook dead letters hold copies of any kind of text, they follow the shortest text
+//! limit.
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use screenpipe_db::{DatabaseManager, MediaChunkRow, PrunedRows};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, error, info, warn};
+
+use crate::downsample::{downsample_video_chunk, DownsampleOptions, DownsampleOutcome};
+use crate::server::AppState;
+
+/// Rows deleted per transaction so recording isn't blocked on the write lock for long.
+const DELETE_BATCH_SIZE: i64 = 2000;
+
+/// Chunks re-encoded per pass, the rest is picked up by the next one.
+const DOWNSAMPLE_BATCH_SIZE: i64 = 200;
+
+/// Delay before the first pass so startup isn't slowed down by pruning.
+const INITIAL_DELAY: Duration = Duration::from_secs(60);
+
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Retention rules. Every limit is optional, `None` keeps data forever.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    /// Delete video files older than this many days (frames and OCR text are kept)
+    pub video_days: Option<u32>,
+    /// Delete audio files older than this many days (transcriptions are kept)
+    pub audio_days: Option<u32>,
+    /// Delete frames and OCR text older than this many days
+    pub ocr_days: Option<u32>,
+    /// Delete audio transcriptions older than this many days
+    pub transcription_days: Option<u32>,
+    /// Delete UI events, UI monitoring and accessibility text older than this many days
+    pub ui_events_days: Option<u32>,
+    /// Keep the total size of video files under this many GB, oldest deleted first
+    pub video_max_gb: Option<f64>,
+    /// Keep the total size of audio files under this many GB, oldest deleted first
+    pub audio_max_gb: Option<f64>,
+    /// Re-encode video chunks older than this many days at a lower fps/resolution
+    pub downsample_after_days: Option<u32>,
+    /// Frame rate of downsampled chunks
+    pub downsample_fps: f64,
+    /// Resolution factor of downsampled chunks (0.5 = half width and height)
+    pub downsample_scale: f64,
+    /// Seconds between two retention passes
+    pub interval_secs: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            video_days: None,
+            audio_days: None,
+            ocr_days: None,
+            transcription_days: None,
+            ui_events_days: None,
+            video_max_gb: None,
+            audio_max_gb: None,
+            downsample_after_days: None,
+            downsample_fps: 0.2,
+            downsample_scale: 0.5,
+            interval_secs: 3600,
+        }
+    }
+}
+
+impl RetentionConfig {
+    /// Whether at least one rule is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.video_days.is_some()
+            || self.audio_days.is_some()
+            || self.ocr_days.is_some()
+            || self.transcription_days.is_some()
+            || self.ui_events_days.is_some()
+            || self.video_max_gb.is_some()
+            || self.audio_max_gb.is_some()
+            || self.downsample_after_days.is_some()
+    }
+}
+
+/// What a retention pass removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// Database rows deleted, per table
+    pub rows: PrunedRows,
+    pub video_files_deleted: u64,
+    pub audio_files_deleted: u64,
+    pub video_chunks_downsampled: u64,
+    pub bytes_freed: u64,
+    /// Non fatal errors (e.g. files that could not be removed)
+    pub errors: Vec<String>,
+}
+
+impl RetentionReport {
+    fn new() -> Self {
+        let now = Utc::now();
+        Self {
+            started_at: now,
+            finished_at: now,
+            rows: PrunedRows::default(),
+            video_files_deleted: 0,
+            audio_files_deleted: 0,
+            video_chunks_downsampled: 0,
+            bytes_freed: 0,
+            errors: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum MediaKind {
+    Video,
+    Audio,
+}
+
+pub struct RetentionManager {
+    db: Arc<DatabaseManager>,
+    config: RetentionConfig,
+    last_report: RwLock<Option<RetentionReport>>,
+    /// Held for the duration of a pass so manual and scheduled runs don't overlap
+    run_lock: Mutex<()>,
+}
+
+impl RetentionManager {
+    pub fn new(db: Arc<DatabaseManager>, config: RetentionConfig) -> Self {
+        Self {
+            db,
+            config,
+            last_report: RwLock::new(None),
+            run_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn config(&self) -> &RetentionConfig {
+        &self.config
+    }
+
+    pub async fn last_report(&self) -> Option<RetentionReport> {
+        self.last_report.read().await.clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.run_lock.try_lock().is_err()
+    }
+
+    /// Run retention passes forever, every `interval_secs`.
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            tokio::time::sleep(INITIAL_DELAY).await;
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(self.config.interval_secs.max(60)));
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.run_once().await {
+                    error!("retention pass failed: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Apply all configured rules once and return what was pruned.
+    pub async fn run_once(&self) -> anyhow::Result<RetentionReport> {
+        let _guard = self.run_lock.lock().await;
+        let mut report = RetentionReport::new();
+        let now = Utc::now();
+        let cutoff = |days: u32| now - ChronoDuration::days(days as i64);
+
+        // Text rules first, they delete whole chunks and make the media rules cheaper
+        if let Some(days) = self.config.ocr_days {
+            loop {
+                let (rows, chunks) = self
+                    .db
+                    .delete_frames_before(cutoff(days), DELETE_BATCH_SIZE)
+                    .await?;
+                report.rows.add(&rows);
+                remove_files(&chunks, MediaKind::Video, &mut report).await;
+                if (rows.frames as i64) < DELETE_BATCH_SIZE {
+                    break;
+                }
+                tokio::task::yield_now().await;
+            }
+        }
+
+        if let Some(days) = self.config.transcription_days {
+            loop {
+                let (rows, chunks) = self
+                    .db
+                    .delete_audio_chunks_before(cutoff(days), DELETE_BATCH_SIZE)
+                    .await?;
+                report.rows.add(&rows);
+                remove_files(&chunks, MediaKind::Audio, &mut report).await;
+                if (rows.audio_chunks as i64) < DELETE_BATCH_SIZE {
+                    break;
+                }
+                tokio::task::yield_now().await;
+            }
+        }
+
+        if let Some(days) = self.config.ui_events_days {
+            let rows = self.db.delete_ui_events_before(cutoff(days)).await?;
+            report.rows.add(&rows);
+        }
+
+        let text_days = [
+            self.config.ocr_days,
+            self.config.transcription_days,
+            self.config.ui_events_days,
+        ];
+        if let Some(days) = text_days.into_iter().flatten().min() {
+            report.rows.webhook_dead_letters = self
+                .db
+                .delete_webhook_dead_letters_before(cutoff(days))
+                .await?;
+        }
+
+        if let Some(days) = self.config.video_days {
+            let chunks = self
+                .db
+                .list_video_chunks_for_retention(Some(cutoff(days)), None)
+                .await?;
+            self.prune_media(&chunks, MediaKind::Video, &mut report)
+                .await?;
+        }
+
+        if let Some(days) = self.config.audio_days {
+            let chunks = self
+                .db
+                .list_audio_chunks_for_retention(Some(cutoff(days)), None)
+                .await?;
+            self.prune_media(&chunks, MediaKind::Audio, &mut report)
+                .await?;
+        }
+
+        // Downsample before enforcing quotas, cheaper chunks may be enough to fit
+        if let Some(days) = self.config.downsample_after_days {
+            self.downsample_old_chunks(cutoff(days), &mut report)
+                .await?;
+        }
+
+        if let Some(max_gb) = self.config.video_max_gb {
+            let chunks = self.db.list_video_chunks_for_retention(None, None).await?;
+            let over_quota = chunks_over_quota(&chunks, max_gb).await;
+            self.prune_media(&over_quota, MediaKind::Video, &mut report)
+                .await?;
+        }
+
+        if let Some(max_gb) = self.config.audio_max_gb {
+            let chunks = self.db.list_audio_chunks_for_retention(None, None).await?;
+            let over_quota = chunks_over_quota(&chunks, max_gb).await;
+            self.prune_media(&over_quota, MediaKind::Audio, &mut report)
+                .await?;
+        }
+
+        report.finished_at = Utc::now();
+        if report.rows.total() > 0 || report.bytes_freed > 0 {
+            info!(
+                "retention: deleted {} rows, {} video files, {} audio files, downsampled {} chunks, freed {:.1} MB",
+                report.rows.total(),
+                report.video_files_deleted,
+                report.audio_files_deleted,
+                report.video_chunks_downsampled,
+                report.bytes_freed as f64 / (1024.0 * 1024.0)
+            );
+        } else {
+            debug!("retention: nothing to prune");
+        }
+
+        *self.last_report.write().await = Some(report.clone());
+        Ok(report)
+    }
+
+    async fn downsample_old_chunks(
+        &self,
+        before: DateTime<Utc>,
+        report: &mut RetentionReport,
+    ) -> anyhow::Result<()> {
+        let options = DownsampleOptions {
+            fps: self.config.downsample_fps,
+            scale: self.config.downsample_scale,
+        };
+        let chunks = self
+            .db
+            .list_video_chunks_for_downsampling(before, DOWNSAMPLE_BATCH_SIZE)
+            .await?;
+        for chunk in &chunks {
+            match downsample_video_chunk(&self.db, chunk, options).await {
+                Ok(DownsampleOutcome::Downsampled { old_size, new_size }) => {
+                    report.video_chunks_downsampled += 1;
+                    report.bytes_freed += old_size.saturating_sub(new_size);
+                }
+                Ok(DownsampleOutcome::Skipped) => {}
+                Err(e) => {
+                    warn!("retention: failed to downsample {}: {}", chunk.file_path, e);
+                    report
+                        .errors
+                        .push(format!("failed to downsample {}: {}", chunk.file_path, e));
+                    // Don't retry unreadable chunks on every pass, they keep their original file
+                    self.db.mark_video_chunk_downsampled(chunk.id).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the media files of `chunks` and mark them as pruned, keeping their text.
+    async fn prune_media(
+        &self,
+        chunks: &[MediaChunkRow],
+        kind: MediaKind,
+        report: &mut RetentionReport,
+    ) -> anyhow::Result<()> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        let removed = remove_files(chunks, kind, report).await;
+        match kind {
+            MediaKind::Video => self.db.mark_video_chunks_media_pruned(&removed).await?,
+            MediaKind::Audio => self.db.mark_audio_chunks_media_pruned(&removed).await?,
+        };
+        Ok(())
+    }
+}
+
+/// Delete the files of `chunks`, returning the ids whose file is gone afterwards
+/// (deleted now or already missing).
+async fn remove_files(
+    chunks: &[MediaChunkRow],
+    kind: MediaKind,
+    report: &mut RetentionReport,
+) -> Vec<i64> {
+    let mut removed = Vec::with_capacity(chunks.len());
+    for chunk in chunks {
+        let size = match tokio::fs::metadata(&chunk.file_path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                removed.push(chunk.id);
+                continue;
+            }
+            Err(e) => {
+                report
+                    .errors
+                    .push(format!("failed to stat {}: {}", chunk.file_path, e));
+                continue;
+            }
+        };
+        match tokio::fs::remove_file(&chunk.file_path).await {
+            Ok(()) => {
+                removed.push(chunk.id);
+                report.bytes_freed += size;
+                match kind {
+                    MediaKind::Video => report.video_files_deleted += 1,
+                    MediaKind::Audio => report.audio_files_deleted += 1,
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => removed.push(chunk.id),
+            Err(e) => {
+                warn!("retention: failed to delete {}: {}", chunk.file_path, e);
+                report
+                    .errors
+                    .push(format!("failed to delete {}: {}", chunk.file_path, e));
+            }
+        }
+    }
+    removed
+}
+
+/// Given chunks ordered oldest first, return the oldest ones that have to go for the
+/// remaining files to fit in `max_gb`.
+async fn chunks_over_quota(chunks: &[MediaChunkRow], max_gb: f64) -> Vec<MediaChunkRow> {
+    let mut sizes = Vec::with_capacity(chunks.len());
+    let mut total: u64 = 0;
+    for chunk in chunks {
+        let size = tokio::fs::metadata(&chunk.file_path)
+            .await
+            .map(|m| m.len())
+            .unwrap_or(0);
+        total += size;
+        sizes.push(size);
+    }
+    select_over_quota(
+        chunks,
+        &sizes,
+        total,
+        (max_gb.max(0.0) * BYTES_PER_GB) as u64,
+    )
+}
+
+fn select_over_quota(
+    chunks: &[MediaChunkRow],
+    sizes: &[u64],
+    mut total: u64,
+    quota: u64,
+) -> Vec<MediaChunkRow> {
+    let mut selected = Vec::new();
+    for (chunk, size) in chunks.iter().zip(sizes) {
+        if total <= quota {
+            break;
+        }
+        total = total.saturating_sub(*size);
+        selected.push(chunk.clone());
+    }
+    selected
+}
+
+// ============================================================================
+// API
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct RetentionStatusResponse {
+    pub enabled: bool,
+    pub running: bool,
+    pub config: Option<RetentionConfig>,
+    pub last_report: Option<RetentionReport>,
+}
+
+/// Current retention rules and the report of the last pass.
+pub async fn retention_status(State(state): State<Arc<AppState>>) -> Json<RetentionStatusResponse> {
+    match &state.retention {
+        Some(manager) => Json(RetentionStatusResponse {
+            enabled: true,
+            running: manager.is_running(),
+            config: Some(manager.config().clone()),
+            last_report: manager.last_report().await,
+        }),
+        None => Json(RetentionStatusResponse {
+            enabled: false,
+            running: false,
+            config: None,
+            last_report: None,
+        }),
+    }
+}
+
+/// Run a retention pass now and return what was pruned.
+pub async fn retention_run(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<RetentionReport>, (StatusCode, Json<Value>)> {
+    let Some(manager) = &state.retention else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "retention is not configured, start screenpipe with --retention-* or --downsample-after-days",
+                "success": false
+            })),
+        ));
+    };
+
+    manager.run_once().await.map(Json).map_err(|e| {
+        error!("retention pass failed: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "error": format!("retention pass failed: {}", e),
+                "success": false
+            })),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: i64) -> MediaChunkRow {
+        MediaChunkRow {
+            id,
+            file_path: format!("/tmp/{}.mp4", id),
+        }
+    }
+
+    #[test]
+    fn test_select_over_quota_drops_oldest_first() {
+        let chunks = vec![chunk(1), chunk(2), chunk(3), chunk(4)];
+        let sizes = vec![100, 100, 100, 100];
+
+        let selected = select_over_quota(&chunks, &sizes, 400, 250);
+        assert_eq!(
+            selected.iter().map(|c| c.id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        assert!(select_over_quota(&chunks, &sizes, 400, 400).is_empty());
+        assert_eq!(select_over_quota(&chunks, &sizes, 400, 0).len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_run_once_removes_old_audio_files() {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.mp4");
+        std::fs::write(&path, vec![0u8; 1024]).unwrap();
+
+        let chunk_id = db.insert_audio_chunk(path.to_str().unwrap()).await.unwrap();
+        sqlx::query("UPDATE audio_chunks SET timestamp = ?1 WHERE id = ?2")
+            .bind(Utc::now() - ChronoDuration::days(3))
+            .bind(chunk_id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let manager = RetentionManager::new(
+            db.clone(),
+            RetentionConfig {
+                audio_days: Some(1),
+                ..Default::default()
+            },
+        );
+        let report = manager.run_once().await.unwrap();
+
+        assert_eq!(report.audio_files_deleted, 1);
+        assert_eq!(report.bytes_freed, 1024);
+        assert!(!path.exists());
+        // media pruning keeps the chunk row
+        assert_eq!(report.rows.audio_chunks, 0);
+        assert!(manager.last_report().await.is_some());
+
+        // second pass has nothing left to do
+        let report = manager.run_once().await.unwrap();
+        assert_eq!(report.audio_files_deleted, 0);
+    }
+}
@@ -4,7 +4,7 @@ use axum::{
         ws::{Message, WebSocket, WebSocketUpgrade},
         Json, Path, Query, State,
     },
-    http::StatusCode,
+    http::{HeaderValue, StatusCode},
     response::{IntoResponse, Json as JsonResponse, Response},
     routing::get,
     serve, Router,
@@ -13,8 +13,9 @@ use oasgen::{oasgen, OaSchema, Server};
 
 use chrono::TimeZone;
 use screenpipe_db::{
-    ContentType, DatabaseManager, FrameData, Order, SearchMatch, SearchResult, Speaker,
-    TagContentType, TextPosition,
+    ContentType, DatabaseManager, EmbeddingAnnIndex, FrameData, Order, RawSqlError, RawSqlLimits,
+    ReadOnlySql, SearchMatch, SearchOrder, SearchResult, Speaker, TagContentType, TextMatch,
+    TextPosition, OCR_EMBEDDING_INDEX_FILE,
 };
 
 use tokio_util::io::ReaderStream;
@@ -30,7 +31,12 @@ use screenpipe_events::{send_event, subscribe_to_all_events, Event as Screenpipe
 
 use crate::{
     analytics,
-    embedding::embedding_endpoint::create_embeddings,
+    auth::TokenStore,
+    embedding::{
+        embedding_endpoint::create_embeddings,
+        hybrid::reciprocal_rank_fusion,
+        indexer::{embed_query, EMBEDDING_MODEL_NAME},
+    },
     video::{finish_ffmpeg_process, start_ffmpeg_process, write_frame_to_ffmpeg, MAX_FPS},
     video_cache::{AudioEntry, DeviceFrame, FrameCache, FrameMetadata, TimeSeriesFrame},
     video_utils::{
@@ -49,9 +55,16 @@ use screenpipe_audio::{
 };
 use screenpipe_core::pii_removal::detect_pii_regions;
 use screenpipe_core::sync::SyncServiceHandle;
+use screenpipe_core::{pipe_log, tail_pipe_log, PipePermissions};
 use tracing::{debug, error, info, warn};
 
+use crate::activity::ActivityTracker;
+use crate::forget::MediaPurger;
+use crate::meetings::{self, MeetingRecorder};
+use crate::pipe_scheduler::{self, PipeScheduler};
+use crate::retention::{self, RetentionManager};
 use crate::sync_api::{self, SyncState};
+use crate::webhooks::{self, WebhookDispatcher};
 
 use screenpipe_vision::monitor::{get_monitor_by_id, list_monitors, list_monitors_detailed, MonitorListError};
 use screenpipe_vision::OcrEngine;
@@ -83,8 +96,11 @@ use tokio::{
     time::timeout,
 };
 
-use tower_http::{cors::Any, trace::TraceLayer};
 use tower_http::{cors::CorsLayer, trace::DefaultMakeSpan};
+use tower_http::{
+    cors::{AllowHeaders, AllowOrigin, Any},
+    trace::TraceLayer,
+};
 
 use std::str::FromStr;
 
@@ -98,6 +114,16 @@ pub type FrameImageCache = LruCache<i64, (String, Instant)>;
 /// Cache key for search results (hash of query parameters)
 pub type SearchCache = MokaCache<u64, Arc<SearchResponse>>;
 
+/// Error of a handler, answered as `{"error": "...", "success": false}`.
+pub(crate) type ApiError = (StatusCode, JsonResponse<Value>);
+
+pub(crate) fn api_error(status: StatusCode, error: impl ToString) -> ApiError {
+    (
+        status,
+        JsonResponse(json!({ "error": error.to_string(), "success": false })),
+    )
+}
+
 pub struct AppState {
     pub db: Arc<DatabaseManager>,
     pub audio_manager: Arc<AudioManager>,
@@ -125,6 +151,22 @@ pub struct AppState {
     pub video_quality: String,
     /// API request counter for usage analytics
     pub api_request_count: Arc<AtomicUsize>,
+    /// Retention policy (if configured via CLI)
+    pub retention: Option<Arc<RetentionManager>>,
+    /// ANN index of the embeddings searched by /semantic-search
+    pub ocr_embedding_index: Arc<EmbeddingAnnIndex>,
+    /// Read-only connection used by /raw_sql
+    pub raw_sql: Arc<ReadOnlySql>,
+    /// Allow POST /raw_sql/write (opt-in via CLI)
+    pub enable_raw_sql_write: bool,
+    /// The embedding indexer runs (opt-in via CLI), /search/hybrid embeds queries
+    pub enable_embedding_index: bool,
+    /// Delivers events to the webhooks registered on /webhooks
+    pub webhooks: Arc<WebhookDispatcher>,
+    /// Rewrites the media of data deleted through /forget
+    pub media_purger: Arc<MediaPurger>,
+    /// Runs the cron jobs of pipes
+    pub pipe_scheduler: Arc<PipeScheduler>,
 }
 
 // Update the SearchQuery struct
@@ -166,6 +208,9 @@ pub(crate) struct SearchQuery {
     /// Include cloud-synced data in search results (requires cloud sync to be enabled)
     #[serde(default)]
     include_cloud: bool,
+    /// `time` (most recent first, default) or `relevance` (BM25, needs `q`)
+    #[serde(default)]
+    order: SearchOrder,
 }
 
 #[derive(OaSchema, Deserialize)]
@@ -268,6 +313,15 @@ pub struct OCRContent {
     pub browser_url: Option<String>,
     pub focused: Option<bool>,
     pub device_name: String,
+    #[serde(default)]
+    pub score: Option<f64>,
+    #[serde(default)]
+    pub snippet: Option<String>,
+    #[serde(default)]
+    pub matches: Vec<TextMatch>,
+    /// PII was blurred out of the frame when it was recorded
+    #[serde(default)]
+    pub redacted: bool,
 }
 
 #[derive(OaSchema, Serialize, Deserialize, Debug, Clone)]
@@ -283,6 +337,12 @@ pub struct AudioContent {
     pub speaker: Option<Speaker>,
     pub start_time: Option<f64>,
     pub end_time: Option<f64>,
+    #[serde(default)]
+    pub score: Option<f64>,
+    #[serde(default)]
+    pub snippet: Option<String>,
+    #[serde(default)]
+    pub matches: Vec<TextMatch>,
 }
 
 #[derive(OaSchema, Serialize, Deserialize, Debug, Clone)]
@@ -297,6 +357,12 @@ pub struct UiContent {
     pub offset_index: i64,
     pub frame_name: Option<String>,
     pub browser_url: Option<String>,
+    #[serde(default)]
+    pub score: Option<f64>,
+    #[serde(default)]
+    pub snippet: Option<String>,
+    #[serde(default)]
+    pub matches: Vec<TextMatch>,
 }
 
 /// User input event content (clicks, keystrokes, clipboard, etc.)
@@ -403,9 +469,121 @@ fn compute_search_cache_key(query: &SearchQuery) -> u64 {
     query.browser_url.hash(&mut hasher);
     query.speaker_name.hash(&mut hasher);
     query.include_cloud.hash(&mut hasher);
+    format!("{:?}", query.order).hash(&mut hasher);
     hasher.finish()
 }
 
+/// Response items of search results, screenpipe's own windows are left out.
+fn search_results_to_content_items(results: &[SearchResult]) -> Vec<ContentItem> {
+    // Helper to check if app name contains "screenpipe" (case insensitive)
+    let is_screenpipe_app =
+        |app_name: &str| -> bool { app_name.to_lowercase().contains("screenpipe") };
+
+    results
+        .iter()
+        // Filter out screenpipe results at display time
+        .filter(|result| match result {
+            SearchResult::OCR(ocr) => !is_screenpipe_app(&ocr.app_name),
+            SearchResult::Audio(_) => true, // Audio doesn't have app_name
+            SearchResult::UI(ui) => !is_screenpipe_app(&ui.app_name),
+            SearchResult::Input(input) => input
+                .app_name
+                .as_ref()
+                .is_none_or(|app| !is_screenpipe_app(app)),
+        })
+        .map(|result| match result {
+            SearchResult::OCR(ocr) => ContentItem::OCR(OCRContent {
+                frame_id: ocr.frame_id,
+                text: ocr.ocr_text.clone(),
+                timestamp: ocr.timestamp,
+                file_path: ocr.file_path.clone(),
+                offset_index: ocr.offset_index,
+                app_name: ocr.app_name.clone(),
+                window_name: ocr.window_name.clone(),
+                tags: ocr.tags.clone(),
+                frame: None,
+                frame_name: Some(ocr.frame_name.clone()),
+                browser_url: ocr.browser_url.clone(),
+                focused: ocr.focused,
+                device_name: ocr.device_name.clone(),
+                score: ocr.score,
+                snippet: ocr.snippet.clone(),
+                matches: ocr.matches.clone(),
+                redacted: ocr.redacted,
+            }),
+            SearchResult::Audio(audio) => ContentItem::Audio(AudioContent {
+                chunk_id: audio.audio_chunk_id,
+                transcription: audio.transcription.clone(),
+                timestamp: audio.timestamp,
+                file_path: audio.file_path.clone(),
+                offset_index: audio.offset_index,
+                tags: audio.tags.clone(),
+                device_name: audio.device_name.clone(),
+                device_type: audio.device_type.clone().into(),
+                speaker: audio.speaker.clone(),
+                start_time: audio.start_time,
+                end_time: audio.end_time,
+                score: audio.score,
+                snippet: audio.snippet.clone(),
+                matches: audio.matches.clone(),
+            }),
+            SearchResult::UI(ui) => ContentItem::UI(UiContent {
+                id: ui.id,
+                text: ui.text.clone(),
+                timestamp: ui.timestamp,
+                app_name: ui.app_name.clone(),
+                window_name: ui.window_name.clone(),
+                initial_traversal_at: ui.initial_traversal_at,
+                file_path: ui.file_path.clone(),
+                offset_index: ui.offset_index,
+                frame_name: ui.frame_name.clone(),
+                browser_url: ui.browser_url.clone(),
+                score: ui.score,
+                snippet: ui.snippet.clone(),
+                matches: ui.matches.clone(),
+            }),
+            SearchResult::Input(input) => ContentItem::Input(InputContent {
+                id: input.id,
+                timestamp: input.timestamp,
+                event_type: input.event_type.to_string(),
+                app_name: input.app_name.clone(),
+                window_title: input.window_title.clone(),
+                browser_url: input.browser_url.clone(),
+                text_content: input.text_content.clone(),
+                x: input.x,
+                y: input.y,
+                key_code: input.key_code,
+                modifiers: input.modifiers,
+                element_role: input.element.as_ref().and_then(|e| e.role.clone()),
+                element_name: input.element.as_ref().and_then(|e| e.name.clone()),
+            }),
+        })
+        .collect()
+}
+
+/// Extract the frame of every OCR item.
+async fn attach_frames(items: &mut [ContentItem]) {
+    debug!("extracting frames for ocr content");
+    let frame_futures: Vec<_> = items
+        .iter()
+        .filter_map(|item| {
+            if let ContentItem::OCR(ocr_content) = item {
+                Some(extract_frame(&ocr_content.file_path, ocr_content.offset_index))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let frames = try_join_all(frame_futures).await.unwrap(); // TODO: handle error
+
+    for (item, frame) in items.iter_mut().zip(frames.into_iter()) {
+        if let ContentItem::OCR(ref mut ocr_content) = item {
+            ocr_content.frame = Some(frame);
+        }
+    }
+}
+
 // Update the search function
 #[oasgen]
 pub(crate) async fn search(
@@ -461,6 +639,7 @@ pub(crate) async fn search(
             query.browser_url.as_deref(),
             query.focused,
             query.speaker_name.as_deref(),
+            query.order,
         ),
         state.db.count_search_results(
             query_str,
@@ -487,104 +666,10 @@ pub(crate) async fn search(
         )
     })?;
 
-    // Helper to check if app name contains "screenpipe" (case insensitive)
-    let is_screenpipe_app =
-        |app_name: &str| -> bool { app_name.to_lowercase().contains("screenpipe") };
-
-    let mut content_items: Vec<ContentItem> = results
-        .iter()
-        // Filter out screenpipe results at display time
-        .filter(|result| match result {
-            SearchResult::OCR(ocr) => !is_screenpipe_app(&ocr.app_name),
-            SearchResult::Audio(_) => true, // Audio doesn't have app_name
-            SearchResult::UI(ui) => !is_screenpipe_app(&ui.app_name),
-            SearchResult::Input(input) => input
-                .app_name
-                .as_ref()
-                .is_none_or(|app| !is_screenpipe_app(app)),
-        })
-        .map(|result| match result {
-            SearchResult::OCR(ocr) => ContentItem::OCR(OCRContent {
-                frame_id: ocr.frame_id,
-                text: ocr.ocr_text.clone(),
-                timestamp: ocr.timestamp,
-                file_path: ocr.file_path.clone(),
-                offset_index: ocr.offset_index,
-                app_name: ocr.app_name.clone(),
-                window_name: ocr.window_name.clone(),
-                tags: ocr.tags.clone(),
-                frame: None,
-                frame_name: Some(ocr.frame_name.clone()),
-                browser_url: ocr.browser_url.clone(),
-                focused: ocr.focused,
-                device_name: ocr.device_name.clone(),
-            }),
-            SearchResult::Audio(audio) => ContentItem::Audio(AudioContent {
-                chunk_id: audio.audio_chunk_id,
-                transcription: audio.transcription.clone(),
-                timestamp: audio.timestamp,
-                file_path: audio.file_path.clone(),
-                offset_index: audio.offset_index,
-                tags: audio.tags.clone(),
-                device_name: audio.device_name.clone(),
-                device_type: audio.device_type.clone().into(),
-                speaker: audio.speaker.clone(),
-                start_time: audio.start_time,
-                end_time: audio.end_time,
-            }),
-            SearchResult::UI(ui) => ContentItem::UI(UiContent {
-                id: ui.id,
-                text: ui.text.clone(),
-                timestamp: ui.timestamp,
-                app_name: ui.app_name.clone(),
-                window_name: ui.window_name.clone(),
-                initial_traversal_at: ui.initial_traversal_at,
-                file_path: ui.file_path.clone(),
-                offset_index: ui.offset_index,
-                frame_name: ui.frame_name.clone(),
-                browser_url: ui.browser_url.clone(),
-            }),
-            SearchResult::Input(input) => ContentItem::Input(InputContent {
-                id: input.id,
-                timestamp: input.timestamp,
-                event_type: input.event_type.to_string(),
-                app_name: input.app_name.clone(),
-                window_title: input.window_title.clone(),
-                browser_url: input.browser_url.clone(),
-                text_content: input.text_content.clone(),
-                x: input.x,
-                y: input.y,
-                key_code: input.key_code,
-                modifiers: input.modifiers,
-                element_role: input.element.as_ref().and_then(|e| e.role.clone()),
-                element_name: input.element.as_ref().and_then(|e| e.name.clone()),
-            }),
-        })
-        .collect();
+    let mut content_items = search_results_to_content_items(&results);
 
     if query.include_frames {
-        debug!("extracting frames for ocr content");
-        let frame_futures: Vec<_> = content_items
-            .iter()
-            .filter_map(|item| {
-                if let ContentItem::OCR(ocr_content) = item {
-                    Some(extract_frame(
-                        &ocr_content.file_path,
-                        ocr_content.offset_index,
-                    ))
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        let frames = try_join_all(frame_futures).await.unwrap(); // TODO: handle error
-
-        for (item, frame) in content_items.iter_mut().zip(frames.into_iter()) {
-            if let ContentItem::OCR(ref mut ocr_content) = item {
-                ocr_content.frame = Some(frame);
-            }
-        }
+        attach_frames(&mut content_items).await;
     }
 
     info!("search completed: found {} results", total);
@@ -638,6 +723,175 @@ pub(crate) async fn search(
     Ok(JsonResponse(response))
 }
 
+/// Keyword and embedding search fused with reciprocal rank fusion.
+///
+/// Takes the `/search` filters. The embedding side covers OCR text and audio transcriptions
+/// indexed by `--enable-embedding-index` and is skipped for filters it can't apply
+/// (frame name, browser url, focus, text length). Keyword results are returned alone when
+/// the embedding index is disabled, without loading the model, or when the model is
+/// unavailable.
+#[oasgen]
+pub(crate) async fn hybrid_search_handler(
+    Query(query): Query<SearchQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<SearchResponse>, (StatusCode, JsonResponse<Value>)> {
+    let query_str = query.q.as_deref().unwrap_or("").trim();
+    if query_str.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": "q is required for hybrid search"})),
+        ));
+    }
+
+    let internal_error = |e: sqlx::Error| {
+        error!("hybrid search failed: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("hybrid search failed: {}", e)})),
+        )
+    };
+
+    // Candidates per side, enough for the requested page to be fused from both
+    let depth = (query.pagination.offset + query.pagination.limit)
+        .saturating_mul(2)
+        .clamp(20, 500);
+    let content_type = query.content_type.clone();
+
+    let keyword_results = state
+        .db
+        .search(
+            query_str,
+            content_type.clone(),
+            depth,
+            0,
+            query.start_time,
+            query.end_time,
+            query.app_name.as_deref(),
+            query.window_name.as_deref(),
+            query.min_length,
+            query.max_length,
+            query.speaker_ids.clone(),
+            query.frame_name.as_deref(),
+            query.browser_url.as_deref(),
+            query.focused,
+            query.speaker_name.as_deref(),
+            SearchOrder::Relevance,
+        )
+        .await
+        .map_err(internal_error)?;
+
+    let plain_filters = query.frame_name.is_none()
+        && query.browser_url.is_none()
+        && query.focused.is_none()
+        && query.min_length.is_none()
+        && query.max_length.is_none();
+    let with_ocr = plain_filters
+        && matches!(
+            content_type,
+            ContentType::All
+                | ContentType::Vision
+                | ContentType::OCR
+                | ContentType::OcrAndUi
+                | ContentType::AudioAndOcr
+                | ContentType::VisionAndInput
+                | ContentType::VisionAudioInput
+        );
+    // Same as keyword search, app and window filters exclude audio
+    let with_audio = plain_filters
+        && query.app_name.is_none()
+        && query.window_name.is_none()
+        && matches!(
+            content_type,
+            ContentType::All
+                | ContentType::Audio
+                | ContentType::AudioAndUi
+                | ContentType::AudioAndOcr
+                | ContentType::AudioAndInput
+                | ContentType::VisionAudioInput
+        );
+
+    let mut semantic_results: Vec<SearchResult> = Vec::new();
+    // Without the indexer there is nothing worth loading the model for
+    if state.enable_embedding_index && (with_ocr || with_audio) {
+        match embed_query(query_str).await {
+            Ok(embedding) => {
+                if with_ocr {
+                    let ocr = state
+                        .db
+                        .search_ocr_by_embedding(
+                            &embedding,
+                            EMBEDDING_MODEL_NAME,
+                            depth,
+                            query.start_time,
+                            query.end_time,
+                            query.app_name.as_deref(),
+                            query.window_name.as_deref(),
+                        )
+                        .await
+                        .map_err(internal_error)?;
+                    semantic_results.extend(ocr.into_iter().map(SearchResult::OCR));
+                }
+                if with_audio {
+                    let audio = state
+                        .db
+                        .search_audio_by_embedding(
+                            &embedding,
+                            EMBEDDING_MODEL_NAME,
+                            depth,
+                            query.start_time,
+                            query.end_time,
+                            query.speaker_ids.clone(),
+                            query.speaker_name.as_deref(),
+                        )
+                        .await
+                        .map_err(internal_error)?;
+                    semantic_results.extend(audio.into_iter().map(SearchResult::Audio));
+                }
+            }
+            Err(e) => warn!(
+                "hybrid search without embeddings, failed to embed query: {}",
+                e
+            ),
+        }
+    }
+
+    // OCR and audio vectors come from the same model, their similarities compare
+    semantic_results.sort_by(|a, b| {
+        let similarity = |result: &SearchResult| match result {
+            SearchResult::OCR(ocr) => ocr.score,
+            SearchResult::Audio(audio) => audio.score,
+            _ => None,
+        };
+        similarity(b)
+            .unwrap_or(f64::NEG_INFINITY)
+            .total_cmp(&similarity(a).unwrap_or(f64::NEG_INFINITY))
+    });
+    semantic_results.truncate(depth as usize);
+
+    let fused = reciprocal_rank_fusion(vec![keyword_results, semantic_results]);
+    let total = fused.len();
+    let page: Vec<SearchResult> = fused
+        .into_iter()
+        .skip(query.pagination.offset as usize)
+        .take(query.pagination.limit as usize)
+        .collect();
+
+    let mut content_items = search_results_to_content_items(&page);
+    if query.include_frames {
+        attach_frames(&mut content_items).await;
+    }
+
+    Ok(JsonResponse(SearchResponse {
+        data: content_items,
+        pagination: PaginationInfo {
+            limit: query.pagination.limit,
+            offset: query.pagination.offset,
+            total: total as i64,
+        },
+        cloud: None,
+    }))
+}
+
 #[oasgen]
 pub(crate) async fn api_list_audio_devices(
     State(_state): State<Arc<AppState>>,
@@ -1024,6 +1278,18 @@ struct UpdatePipeVersionRequest {
     source: String,
 }
 
+/// Permissions declared by a freshly installed pipe, shown to the user.
+async fn installed_pipe_permissions(state: &AppState, pipe_id: &str) -> Option<PipePermissions> {
+    state
+        .pipe_manager
+        .pipe_permissions(pipe_id)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("pipe {} declares invalid permissions: {}", pipe_id, e);
+            None
+        })
+}
+
 #[oasgen]
 async fn download_pipe_handler(
     State(state): State<Arc<AppState>>,
@@ -1043,7 +1309,9 @@ async fn download_pipe_handler(
         Ok(pipe_dir) => Ok(JsonResponse(json!({
             "data": {
                 "pipe_id": pipe_dir,
-                "message": "pipe downloaded successfully"
+                "message": "pipe downloaded successfully",
+                "permissions": installed_pipe_permissions(&state, &pipe_dir).await,
+                "scopes": state.pipe_manager.pipe_scopes(&pipe_dir).await
             },
             "success": true
         }))),
@@ -1082,7 +1350,9 @@ async fn download_pipe_private_handler(
         Ok(pipe_dir) => Ok(JsonResponse(json!({
             "data": {
                 "pipe_id": pipe_dir,
-                "message": "pipe downloaded successfully"
+                "message": "pipe downloaded successfully",
+                "permissions": installed_pipe_permissions(&state, &pipe_dir).await,
+                "scopes": state.pipe_manager.pipe_scopes(&pipe_dir).await
             },
             "success": true
         }))),
@@ -1288,6 +1558,127 @@ async fn get_pipe_info_handler(
     }
 }
 
+#[derive(OaSchema, Deserialize)]
+struct PipeLogsQuery {
+    /// Last lines to return, 200 by default
+    lines: Option<usize>,
+}
+
+const MAX_PIPE_LOG_LINES: usize = 10_000;
+
+/// Directory of an installed pipe, or the error response.
+async fn installed_pipe_dir(
+    state: &AppState,
+    pipe_id: &str,
+) -> Result<PathBuf, (StatusCode, JsonResponse<Value>)> {
+    if !state.enable_pipe_manager {
+        return Err((
+            StatusCode::FORBIDDEN,
+            JsonResponse(json!({
+                "error": "pipe functionality is disabled",
+                "success": false
+            })),
+        ));
+    }
+    if state.pipe_manager.get_pipe_info(pipe_id).await.is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({
+                "error": "pipe not found",
+                "success": false
+            })),
+        ));
+    }
+    Ok(state.screenpipe_dir.join("pipes").join(pipe_id))
+}
+
+#[oasgen]
+async fn get_pipe_logs_handler(
+    State(state): State<Arc<AppState>>,
+    Path(pipe_id): Path<String>,
+    Query(query): Query<PipeLogsQuery>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let pipe_dir = installed_pipe_dir(&state, &pipe_id).await?;
+    let lines = query.lines.unwrap_or(200).min(MAX_PIPE_LOG_LINES);
+    match tail_pipe_log(&pipe_dir, lines).await {
+        Ok(lines) => Ok(JsonResponse(json!({
+            "data": lines,
+            "success": true
+        }))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({
+                "error": format!("failed to read pipe logs: {}", e),
+                "success": false
+            })),
+        )),
+    }
+}
+
+/// The last `lines` lines of a pipe's log, then the new ones as they are written.
+async fn ws_pipe_logs_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    Path(pipe_id): Path<String>,
+    Query(query): Query<PipeLogsQuery>,
+) -> Response {
+    let pipe_dir = match installed_pipe_dir(&state, &pipe_id).await {
+        Ok(pipe_dir) => pipe_dir,
+        Err(e) => return e.into_response(),
+    };
+    let lines = query.lines.unwrap_or(200).min(MAX_PIPE_LOG_LINES);
+    match try_acquire_ws_connection(&state.ws_connection_count) {
+        Some(guard) => {
+            ws.on_upgrade(move |socket| handle_pipe_logs_socket(socket, pipe_dir, lines, guard))
+        }
+        None => Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body(Body::from("Too many WebSocket connections"))
+            .unwrap(),
+    }
+}
+
+async fn handle_pipe_logs_socket(
+    mut socket: WebSocket,
+    pipe_dir: PathBuf,
+    lines: usize,
+    _guard: WsConnectionGuard,
+) {
+    // subscribe first so nothing written while reading the backlog is missed
+    let mut new_lines = pipe_log(&pipe_dir).subscribe();
+    let backlog = tail_pipe_log(&pipe_dir, lines).await.unwrap_or_default();
+    let last_sent = backlog.last().map(|line| line.timestamp);
+    for line in backlog {
+        let text = serde_json::to_string(&line).unwrap_or_default();
+        if socket.send(Message::Text(text)).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            line = new_lines.recv() => match line {
+                Ok(line) if last_sent.is_some_and(|sent| line.timestamp <= sent) => {}
+                Ok(line) => {
+                    let text = serde_json::to_string(&line).unwrap_or_default();
+                    if socket.send(Message::Text(text)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("pipe log tail lagged, skipped {} lines", skipped);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    debug!("pipe log websocket closed");
+}
+
 #[oasgen]
 async fn list_pipes_handler(
     State(state): State<Arc<AppState>>,
@@ -1320,6 +1711,13 @@ pub struct SCServer {
     use_pii_removal: bool,
     sync_handle: Option<Arc<SyncServiceHandle>>,
     video_quality: String,
+    retention: Option<Arc<RetentionManager>>,
+    enable_raw_sql_write: bool,
+    enable_embedding_index: bool,
+    auth: Option<Arc<TokenStore>>,
+    cors_origins: Vec<HeaderValue>,
+    #[cfg(feature = "adaptive-fps")]
+    activity_feed: screenpipe_vision::ActivityFeedOption,
 }
 
 impl SCServer {
@@ -1348,6 +1746,13 @@ impl SCServer {
             use_pii_removal,
             sync_handle: None,
             video_quality,
+            retention: None,
+            enable_raw_sql_write: false,
+            enable_embedding_index: false,
+            auth: None,
+            cors_origins: Vec::new(),
+            #[cfg(feature = "adaptive-fps")]
+            activity_feed: None,
         }
     }
 
@@ -1363,6 +1768,55 @@ impl SCServer {
         self
     }
 
+    /// Set the retention manager exposed on /retention/*
+    pub fn with_retention(mut self, retention: Arc<RetentionManager>) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Enable POST /raw_sql/write
+    pub fn with_raw_sql_write(mut self, enabled: bool) -> Self {
+        self.enable_raw_sql_write = enabled;
+        self
+    }
+
+    /// Embed /search/hybrid queries, only useful while the embedding indexer runs
+    pub fn with_embedding_index(mut self, enabled: bool) -> Self {
+        self.enable_embedding_index = enabled;
+        self
+    }
+
+    /// Require tokens from `token_store` on API requests, `None` leaves the API open
+    pub fn with_auth(mut self, token_store: Option<Arc<TokenStore>>) -> Self {
+        self.auth = token_store;
+        self
+    }
+
+    /// Cut idle time out of the activity sessions using the activity feed
+    #[cfg(feature = "adaptive-fps")]
+    pub fn with_activity_feed(
+        mut self,
+        activity_feed: screenpipe_vision::ActivityFeedOption,
+    ) -> Self {
+        self.activity_feed = activity_feed;
+        self
+    }
+
+    /// Only allow these browser origins, any origin is allowed when empty
+    pub fn with_cors_origins(mut self, origins: &[String]) -> Self {
+        self.cors_origins = origins
+            .iter()
+            .filter_map(|origin| match HeaderValue::from_str(origin) {
+                Ok(origin) => Some(origin),
+                Err(_) => {
+                    warn!("ignoring invalid cors origin: {}", origin);
+                    None
+                }
+            })
+            .collect();
+        self
+    }
+
     pub async fn start(self, enable_frame_cache: bool) -> Result<(), std::io::Error> {
         // Create the OpenAPI server
         let app = self.create_router(enable_frame_cache).await;
@@ -1420,6 +1874,39 @@ impl SCServer {
             }
         });
 
+        // Embeddings of `add --use-embedding` are stored untagged
+        let ocr_embedding_index = Arc::new(EmbeddingAnnIndex::new(
+            self.screenpipe_dir.join(OCR_EMBEDDING_INDEX_FILE),
+            None,
+        ));
+        self.db
+            .attach_ocr_embedding_index(ocr_embedding_index.clone());
+        ocr_embedding_index.clone().start(self.db.clone());
+
+        let webhooks = Arc::new(WebhookDispatcher::new(self.db.clone()));
+        webhooks.clone().start();
+
+        Arc::new(MeetingRecorder::new(self.db.clone())).start();
+
+        let activity_tracker = ActivityTracker::new(self.db.clone());
+        #[cfg(feature = "adaptive-fps")]
+        let activity_tracker = activity_tracker.with_activity_feed(self.activity_feed.clone());
+        Arc::new(activity_tracker).start();
+
+        let media_purger = Arc::new(MediaPurger::new(
+            self.db.clone(),
+            self.video_quality.clone(),
+        ));
+        media_purger.clone().start();
+
+        let pipe_scheduler = Arc::new(PipeScheduler::new(
+            self.db.clone(),
+            self.pipe_manager.clone(),
+        ));
+        if self.enable_pipe {
+            pipe_scheduler.clone().start();
+        }
+
         let app_state = Arc::new(AppState {
             db: self.db.clone(),
             audio_manager: self.audio_manager.clone(),
@@ -1467,15 +1954,33 @@ impl SCServer {
             sync_state: sync_api::new_sync_state(),
             video_quality: self.video_quality.clone(),
             api_request_count: api_request_count.clone(),
+            retention: self.retention.clone(),
+            ocr_embedding_index,
+            raw_sql: Arc::new(ReadOnlySql::new(
+                self.db.pool.clone(),
+                RawSqlLimits::default(),
+            )),
+            enable_raw_sql_write: self.enable_raw_sql_write,
+            enable_embedding_index: self.enable_embedding_index,
+            webhooks,
+            media_purger,
+            pipe_scheduler,
         });
 
+        let allowed_origins = if self.cors_origins.is_empty() {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(self.cors_origins.clone())
+        };
         let cors = CorsLayer::new()
-            .allow_origin(Any)
+            .allow_origin(allowed_origins)
             .allow_methods(Any)
-            .allow_headers(Any)
+            // `*` doesn't cover the Authorization header
+            .allow_headers(AllowHeaders::mirror_request())
             .expose_headers([
                 axum::http::header::CONTENT_TYPE,
                 axum::http::header::CACHE_CONTROL,
+                axum::http::HeaderName::from_static(RAW_SQL_CURSOR_HEADER),
             ]);
         let server = Server::axum()
             .get("/search", search)
@@ -1485,6 +1990,7 @@ impl SCServer {
             .delete("/tags/:content_type/:id", remove_tags)
             .get("/pipes/info/:pipe_id", get_pipe_info_handler)
             .get("/pipes/list", list_pipes_handler)
+            .get("/pipes/logs/:pipe_id", get_pipe_logs_handler)
             .post("/pipes/download", download_pipe_handler)
             .post("/pipes/download-private", download_pipe_private_handler)
             .post("/pipes/enable", run_pipe_handler)
@@ -1497,7 +2003,6 @@ impl SCServer {
             .get("/frames/:frame_id/ocr", get_frame_ocr_data)
             .get("/frames/next-valid", get_next_valid_frame)
             .get("/health", health_check)
-            .post("/raw_sql", execute_raw_sql)
             .post("/add", add_to_database)
             .get("/speakers/unnamed", get_unnamed_speakers_handler)
             .post("/speakers/update", update_speaker_handler)
@@ -1514,6 +2019,7 @@ impl SCServer {
             .get("/semantic-search", semantic_search_handler)
             .get("/pipes/build-status/:pipe_id", get_pipe_build_status)
             .get("/search/keyword", keyword_search_handler)
+            .get("/search/hybrid", hybrid_search_handler)
             .post("/v1/embeddings", create_embeddings)
             .post("/audio/device/start", start_audio_device)
             .post("/audio/device/stop", stop_audio_device)
@@ -1536,6 +2042,73 @@ impl SCServer {
                 "/sync/download",
                 axum::routing::post(sync_api::sync_download),
             )
+            // Retention API routes
+            .route("/retention/status", get(retention::retention_status))
+            .route(
+                "/retention/run",
+                axum::routing::post(retention::retention_run),
+            )
+            // Delete matching text, media and synced blobs
+            .route(
+                "/forget",
+                axum::routing::post(crate::forget::forget_handler),
+            )
+            // Raw SQL (not in OpenAPI spec, the page cursor is returned in a header)
+            .route("/raw_sql", axum::routing::post(execute_raw_sql))
+            .route("/raw_sql/write", axum::routing::post(execute_raw_sql_write))
+            // Transcript export (SRT, WebVTT, text or JSON, not in OpenAPI spec)
+            .route("/audio/transcript", get(crate::transcript::transcript_handler))
+            // Recorded media, decrypted when encrypted at rest (binary, not in OpenAPI spec)
+            .route("/media/file", get(crate::at_rest::media_file_handler))
+            // Prometheus metrics (text exposition format, not JSON)
+            .route("/metrics", get(crate::metrics_api::metrics_handler))
+            // Webhook API routes
+            .route(
+                "/webhooks",
+                get(webhooks::list_webhooks).post(webhooks::create_webhook),
+            )
+            .route(
+                "/webhooks/:id",
+                get(webhooks::get_webhook)
+                    .put(webhooks::update_webhook)
+                    .delete(webhooks::delete_webhook),
+            )
+            .route("/webhooks/dead-letters", get(webhooks::list_dead_letters))
+            .route(
+                "/webhooks/dead-letters/:id",
+                axum::routing::delete(webhooks::delete_dead_letter),
+            )
+            .route(
+                "/webhooks/dead-letters/:id/retry",
+                axum::routing::post(webhooks::retry_dead_letter),
+            )
+            // App usage per hour or day (not in OpenAPI spec)
+            .route(
+                "/activity/summary",
+                get(crate::activity::activity_summary_handler),
+            )
+            // Meeting API routes
+            .route("/meetings", get(meetings::list_meetings))
+            .route(
+                "/meetings/start",
+                axum::routing::post(meetings::start_meeting),
+            )
+            .route(
+                "/meetings/merge",
+                axum::routing::post(meetings::merge_meetings),
+            )
+            .route(
+                "/meetings/:id",
+                get(meetings::get_meeting).delete(meetings::delete_meeting),
+            )
+            .route(
+                "/meetings/:id/stop",
+                axum::routing::post(meetings::stop_meeting),
+            )
+            .route(
+                "/meetings/:id/split",
+                axum::routing::post(meetings::split_meeting),
+            )
             // Vision status endpoint (not in OpenAPI spec to avoid oasgen registration issues)
             .route("/vision/status", get(api_vision_status));
 
@@ -1553,12 +2126,23 @@ impl SCServer {
             .route("/stream/frames", get(stream_frames_handler))
             .route("/ws/events", get(ws_events_handler))
             .route("/ws/health", get(ws_health_handler))
+            .route("/pipes/logs/:pipe_id/ws", get(ws_pipe_logs_handler))
+            // Runs of pipe cron jobs (not in OpenAPI spec)
+            .route(
+                "/pipes/:pipe_id/runs",
+                get(pipe_scheduler::list_pipe_runs).post(pipe_scheduler::run_pipe_now),
+            )
             .route("/frames/export", get(handle_video_export_ws))
             .with_state(app_state.clone())
+            .layer(axum::middleware::from_fn_with_state(
+                self.auth.clone(),
+                crate::auth::require_token,
+            ))
             .layer(axum::middleware::from_fn(move |req: axum::extract::Request, next: axum::middleware::Next| {
                 let counter = app_state.api_request_count.clone();
                 async move {
                     counter.fetch_add(1, Ordering::Relaxed);
+                    metrics::counter!("screenpipe_api_requests_total").increment(1);
                     next.run(req).await
                 }
             }))
@@ -1600,20 +2184,63 @@ async fn validate_media_handler(
     }
 }
 
-#[derive(OaSchema, Deserialize)]
+/// Response header holding the cursor of the next /raw_sql page, absent on the last page.
+pub const RAW_SQL_CURSOR_HEADER: &str = "x-next-cursor";
+
+#[derive(Deserialize)]
 struct RawSqlQuery {
     query: String,
+    /// Cursor from the `x-next-cursor` header of the previous page
+    #[serde(default)]
+    cursor: Option<u64>,
 }
 
-#[oasgen]
+/// Run a read-only query. The body is the array of rows of one page.
 async fn execute_raw_sql(
     State(state): State<Arc<AppState>>,
     JsonResponse(payload): JsonResponse<RawSqlQuery>,
-) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
-    match state.db.execute_raw_sql(&payload.query).await {
-        Ok(result) => Ok(JsonResponse(result)),
+) -> Result<Response, (StatusCode, JsonResponse<serde_json::Value>)> {
+    match state.raw_sql.query(&payload.query, payload.cursor).await {
+        Ok(page) => {
+            let mut response = JsonResponse(Value::Array(page.rows)).into_response();
+            if let Some(cursor) = page.next_cursor {
+                response
+                    .headers_mut()
+                    .insert(RAW_SQL_CURSOR_HEADER, cursor.into());
+            }
+            Ok(response)
+        }
         Err(e) => {
+            let status = match e {
+                RawSqlError::NotAllowed(_) => StatusCode::BAD_REQUEST,
+                RawSqlError::Timeout(_) => StatusCode::REQUEST_TIMEOUT,
+                RawSqlError::Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
             error!("Failed to execute raw SQL query: {}", e);
+            Err((status, JsonResponse(json!({"error": e.to_string()}))))
+        }
+    }
+}
+
+/// Run a statement that may modify the database, only when enabled with
+/// `--enable-raw-sql-write`.
+async fn execute_raw_sql_write(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(payload): JsonResponse<RawSqlQuery>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    if !state.enable_raw_sql_write {
+        return Err((
+            StatusCode::FORBIDDEN,
+            JsonResponse(json!({
+                "error": "raw sql writes are disabled, start screenpipe with --enable-raw-sql-write"
+            })),
+        ));
+    }
+
+    match state.db.execute_raw_sql_write(&payload.query).await {
+        Ok(rows_affected) => Ok(JsonResponse(json!({ "rows_affected": rows_affected }))),
+        Err(e) => {
+            error!("Failed to execute raw SQL write: {}", e);
             Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 JsonResponse(json!({"error": e.to_string()})),
@@ -1985,7 +2612,7 @@ pub struct GetSimilarSpeakersRequest {
     limit: u32,
 }
 
-fn from_comma_separated_array<'de, D>(deserializer: D) -> Result<Option<Vec<i64>>, D::Error>
+pub(crate) fn from_comma_separated_array<'de, D>(deserializer: D) -> Result<Option<Vec<i64>>, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -2346,12 +2973,32 @@ async fn semantic_search_handler(
         }
     };
 
-    // Search database for similar embeddings
-    match state
-        .db
-        .search_similar_embeddings(embedding, limit, threshold)
+    // Search the ANN index, scan the table while it's loading or if it fails
+    let results = match state
+        .ocr_embedding_index
+        .search(&state.db, &embedding, limit, threshold)
         .await
     {
+        Ok(Some(results)) => Ok(results),
+        Ok(None) => {
+            state
+                .db
+                .search_similar_embeddings(embedding, limit, threshold)
+                .await
+        }
+        Err(e) => {
+            warn!(
+                "embedding index search failed, falling back to full scan: {}",
+                e
+            );
+            state
+                .db
+                .search_similar_embeddings(embedding, limit, threshold)
+                .await
+        }
+    };
+
+    match results {
         Ok(results) => {
             // Filter out screenpipe results at display time
             let filtered: Vec<_> = results
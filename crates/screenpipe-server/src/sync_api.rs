@@ -7,7 +7,7 @@
 
 use axum::{extract::State, http::StatusCode, Json};
 use screenpipe_core::sync::{
-    BlobType, SyncClientConfig, SyncManager, SyncService, SyncServiceConfig, SyncServiceHandle,
+    BlobType, SyncBackend, SyncManager, SyncService, SyncServiceConfig, SyncServiceHandle,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
@@ -54,7 +54,8 @@ pub fn new_sync_state() -> SyncState {
 /// Request to initialize sync at runtime.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SyncInitRequest {
-    /// API token for cloud authentication
+    /// API token for cloud authentication (hosted backend only)
+    #[serde(default)]
     pub token: String,
     /// Password for encryption key derivation
     pub password: String,
@@ -62,6 +63,9 @@ pub struct SyncInitRequest {
     pub machine_id: Option<String>,
     /// Sync interval in seconds (optional, defaults to 300)
     pub sync_interval_secs: Option<u64>,
+    /// Storage backend (optional, defaults to the hosted API with `token`)
+    #[serde(default)]
+    pub backend: Option<SyncBackend>,
 }
 
 /// Response from sync initialization.
@@ -105,20 +109,31 @@ pub async fn sync_init(
     let device_os = std::env::consts::OS.to_string();
 
     // Create sync manager
-    let config = SyncClientConfig::new(
-        request.token.clone(),
-        machine_id.clone(),
-        device_name,
-        device_os,
-    );
+    let backend = match request.backend {
+        Some(backend) => backend,
+        None if request.token.is_empty() => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "token or backend is required"})),
+            ));
+        }
+        None => SyncBackend::Hosted {
+            token: request.token.clone(),
+            api_url: None,
+        },
+    };
+    info!("sync backend: {}", backend.name());
 
-    let manager = SyncManager::new(config).map_err(|e| {
-        error!("failed to create sync manager: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": format!("failed to create sync manager: {}", e)})),
-        )
-    })?;
+    let transport = backend
+        .into_transport(machine_id.clone(), device_name, device_os)
+        .map_err(|e| {
+            error!("failed to create sync manager: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("failed to create sync manager: {}", e)})),
+            )
+        })?;
+    let manager = SyncManager::with_transport(transport);
 
     // Initialize with password
     let is_new_user = manager.initialize(&request.password).await.map_err(|e| {
@@ -465,6 +480,7 @@ mod tests {
             password: "test-password".to_string(),
             machine_id: Some("test-machine".to_string()),
             sync_interval_secs: Some(300),
+            backend: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -472,6 +488,17 @@ mod tests {
         assert!(json.contains("test-machine"));
     }
 
+    #[test]
+    fn test_sync_init_request_self_hosted_backend() {
+        let request: SyncInitRequest = serde_json::from_str(
+            r#"{"password": "pw", "backend": {"type": "directory", "path": "/mnt/nas/screenpipe"}}"#,
+        )
+        .unwrap();
+
+        assert!(request.token.is_empty());
+        assert_eq!(request.backend.map(|b| b.name()), Some("directory"));
+    }
+
     #[test]
     fn test_sync_status_response_serialization() {
         let response = SyncStatusResponse {
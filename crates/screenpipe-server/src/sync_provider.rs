@@ -104,8 +104,35 @@ pub struct UiEventSyncRecord {
     pub element_name: Option<String>,
 }
 
+/// Clear `synced_at` of the records of `blob_type` in a time range, so the sync service
+/// uploads them again (e.g. after the blob holding them was deleted by a forget).
+pub(crate) async fn mark_records_unsynced(
+    db: &DatabaseManager,
+    blob_type: BlobType,
+    time_start: &str,
+    time_end: &str,
+) -> SyncResult<()> {
+    let table = match blob_type {
+        BlobType::Ocr => "frames",
+        BlobType::Transcripts => "audio_transcriptions",
+        BlobType::Accessibility => "accessibility",
+        BlobType::Input => "ui_events",
+        _ => return Ok(()),
+    };
+    sqlx::query(&format!(
+        "UPDATE {} SET synced_at = NULL WHERE timestamp >= ? AND timestamp <= ?",
+        table
+    ))
+    .bind(time_start)
+    .bind(time_end)
+    .execute(&db.pool)
+    .await
+    .map_err(|e| SyncError::Database(format!("failed to mark {} unsynced: {}", table, e)))?;
+    Ok(())
+}
+
 /// Current schema version for sync chunks
-const SCHEMA_VERSION: u32 = 2;
+pub(crate) const SCHEMA_VERSION: u32 = 2;
 
 /// Data provider implementation for screenpipe database.
 pub struct ScreenpipeSyncProvider {
@@ -582,13 +609,12 @@ impl ScreenpipeSyncProvider {
             // Create audio chunk for synced transcription
             let audio_chunk_id: i64 = sqlx::query_scalar(
                 r#"
-                INSERT INTO audio_chunks (file_path, device_name, sync_id, machine_id)
-                VALUES ('cloud://' || ?, ?, ?, ?)
+                INSERT INTO audio_chunks (file_path, sync_id, machine_id)
+                VALUES ('cloud://' || ?, ?, ?)
                 RETURNING id
                 "#,
             )
             .bind(&trans.sync_id)
-            .bind(&trans.device)
             .bind(&trans.sync_id)
             .bind(&chunk.machine_id)
             .fetch_one(pool)
@@ -0,0 +1,344 @@
+//! Transcripts (`/audio/transcript`, `screenpipe transcript`)
+//!
+//! Builds a transcript from the stored transcription segments: segments are placed at
+//! their chunk start plus their offset in the chunk, ordered across chunks and devices,
+//! and consecutive segments of the same speaker are merged into one cue. The result is
+//! rendered as SRT, WebVTT, plain text with speaker labels or JSON.
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use screenpipe_db::{DatabaseManager, TranscriptRow};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt::Write;
+use std::sync::Arc;
+use tracing::error;
+
+use crate::server::{from_comma_separated_array, AppState};
+
+/// Segments of the same speaker separated by at most this gap are merged.
+const MERGE_GAP_SECS: f64 = 2.0;
+
+/// Merged cues stop growing past this duration, to stay readable as subtitles.
+const MAX_CUE_SECS: f64 = 20.0;
+
+/// Duration given to segments stored without offsets.
+const DEFAULT_SEGMENT_SECS: f64 = 2.0;
+
+/// Segments read for one transcript.
+pub const MAX_SEGMENTS: u32 = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    Srt,
+    Vtt,
+    #[default]
+    Text,
+    Json,
+}
+
+impl TranscriptFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            TranscriptFormat::Srt => "application/x-subrip; charset=utf-8",
+            TranscriptFormat::Vtt => "text/vtt; charset=utf-8",
+            TranscriptFormat::Text => "text/plain; charset=utf-8",
+            TranscriptFormat::Json => "application/json",
+        }
+    }
+}
+
+/// One cue of the transcript.
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptEntry {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub speaker_id: Option<i64>,
+    /// Speaker name, `Speaker <id>` for unnamed speakers
+    pub speaker: String,
+    pub device: String,
+    pub is_input_device: bool,
+    pub text: String,
+}
+
+/// Which segments go into a transcript.
+#[derive(Debug, Clone, Default)]
+pub struct TranscriptFilter {
+    /// Defaults to a day before `end_time`
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub device_name: Option<String>,
+    pub speaker_ids: Vec<i64>,
+    /// Case-insensitive partial match
+    pub speaker_name: Option<String>,
+}
+
+/// Read the segments matching `filter` and merge them into transcript entries.
+pub async fn build_transcript(
+    db: &DatabaseManager,
+    filter: &TranscriptFilter,
+) -> Result<Vec<TranscriptEntry>, sqlx::Error> {
+    let start_time = filter
+        .start_time
+        .unwrap_or_else(|| filter.end_time.unwrap_or_else(Utc::now) - Duration::days(1));
+    let rows = db
+        .get_transcript_rows(
+            Some(start_time),
+            filter.end_time,
+            filter.device_name.as_deref(),
+            &filter.speaker_ids,
+            filter.speaker_name.as_deref(),
+            MAX_SEGMENTS,
+        )
+        .await?;
+    Ok(merge_segments(rows))
+}
+
+fn speaker_label(row: &TranscriptRow) -> String {
+    match (&row.speaker_name, row.speaker_id) {
+        (Some(name), _) if !name.trim().is_empty() => name.trim().to_string(),
+        (_, Some(id)) => format!("Speaker {}", id),
+        _ => "Unknown".to_string(),
+    }
+}
+
+fn seconds(secs: f64) -> Duration {
+    Duration::milliseconds((secs * 1000.0).round() as i64)
+}
+
+/// Place rows in time, sort them and merge consecutive segments of a speaker.
+pub fn merge_segments(rows: Vec<TranscriptRow>) -> Vec<TranscriptEntry> {
+    let mut segments: Vec<TranscriptEntry> = rows
+        .into_iter()
+        .filter(|row| !row.transcription.trim().is_empty())
+        .map(|row| {
+            let (start, end) = match (row.start_time, row.end_time) {
+                (Some(start), Some(end)) => (
+                    row.chunk_timestamp + seconds(start),
+                    row.chunk_timestamp + seconds(end.max(start)),
+                ),
+                _ => (row.timestamp, row.timestamp + seconds(DEFAULT_SEGMENT_SECS)),
+            };
+            TranscriptEntry {
+                start,
+                end,
+                speaker_id: row.speaker_id,
+                speaker: speaker_label(&row),
+                device: row.device.clone(),
+                is_input_device: row.is_input_device,
+                text: row.transcription.trim().to_string(),
+            }
+        })
+        .collect();
+    segments.sort_by_key(|segment| segment.start);
+
+    let mut entries: Vec<TranscriptEntry> = Vec::with_capacity(segments.len());
+    for segment in segments {
+        if let Some(last) = entries.last_mut() {
+            let gap = (segment.start - last.end).num_milliseconds() as f64 / 1000.0;
+            let duration = (segment.end - last.start).num_milliseconds() as f64 / 1000.0;
+            if last.speaker_id == segment.speaker_id
+                && last.device == segment.device
+                && gap <= MERGE_GAP_SECS
+                && duration <= MAX_CUE_SECS
+            {
+                last.end = last.end.max(segment.end);
+                last.text.push(' ');
+                last.text.push_str(&segment.text);
+                continue;
+            }
+        }
+        entries.push(segment);
+    }
+    entries
+}
+
+/// `HH:MM:SS<separator>mmm` offset from the transcript start.
+fn format_offset(offset: Duration, separator: char) -> String {
+    let millis = offset.num_milliseconds().max(0);
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+/// Render entries, times of subtitles and text are relative to the first entry.
+pub fn render(entries: &[TranscriptEntry], format: TranscriptFormat) -> String {
+    let origin = entries.first().map(|e| e.start).unwrap_or_else(Utc::now);
+    let mut out = String::new();
+
+    match format {
+        TranscriptFormat::Srt => {
+            for (i, entry) in entries.iter().enumerate() {
+                let _ = write!(
+                    out,
+                    "{}\n{} --> {}\n{}: {}\n\n",
+                    i + 1,
+                    format_offset(entry.start - origin, ','),
+                    format_offset(entry.end - origin, ','),
+                    entry.speaker,
+                    entry.text
+                );
+            }
+        }
+        TranscriptFormat::Vtt => {
+            out.push_str("WEBVTT\n\n");
+            for entry in entries {
+                // voice span, with the characters that would end it escaped
+                let speaker = entry.speaker.replace('&', "&amp;").replace('>', "&gt;");
+                let text = entry
+                    .text
+                    .replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;");
+                let _ = write!(
+                    out,
+                    "{} --> {}\n<v {}>{}\n\n",
+                    format_offset(entry.start - origin, '.'),
+                    format_offset(entry.end - origin, '.'),
+                    speaker,
+                    text
+                );
+            }
+        }
+        TranscriptFormat::Text => {
+            for entry in entries {
+                let offset = format_offset(entry.start - origin, '.');
+                let _ = writeln!(
+                    out,
+                    "[{}] {}: {}",
+                    &offset[..offset.len() - 4],
+                    entry.speaker,
+                    entry.text
+                );
+            }
+        }
+        TranscriptFormat::Json => {
+            out = serde_json::to_string_pretty(entries).unwrap_or_else(|_| "[]".to_string());
+        }
+    }
+    out
+}
+
+#[derive(Deserialize)]
+pub struct TranscriptQuery {
+    /// Defaults to a day before `end_time`
+    #[serde(default)]
+    pub start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub end_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub device_name: Option<String>,
+    /// Comma separated speaker ids
+    #[serde(default, deserialize_with = "from_comma_separated_array")]
+    pub speaker_ids: Option<Vec<i64>>,
+    #[serde(default)]
+    pub speaker_name: Option<String>,
+    /// `srt`, `vtt`, `text` (default) or `json`
+    #[serde(default)]
+    pub format: TranscriptFormat,
+}
+
+pub async fn transcript_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TranscriptQuery>,
+) -> Response {
+    let filter = TranscriptFilter {
+        start_time: query.start_time,
+        end_time: query.end_time,
+        device_name: query.device_name,
+        speaker_ids: query.speaker_ids.unwrap_or_default(),
+        speaker_name: query.speaker_name,
+    };
+
+    match build_transcript(&state.db, &filter).await {
+        Ok(entries) => (
+            [(header::CONTENT_TYPE, query.format.content_type())],
+            render(&entries, query.format),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("failed to build transcript: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(
+        id: i64,
+        speaker_id: i64,
+        chunk: DateTime<Utc>,
+        start: f64,
+        end: f64,
+        text: &str,
+    ) -> TranscriptRow {
+        TranscriptRow {
+            id,
+            transcription: text.to_string(),
+            device: "MacBook Pro Microphone (input)".to_string(),
+            is_input_device: true,
+            speaker_id: Some(speaker_id),
+            speaker_name: (speaker_id == 1).then(|| "Alice".to_string()),
+            chunk_timestamp: chunk,
+            timestamp: chunk,
+            start_time: Some(start),
+            end_time: Some(end),
+        }
+    }
+
+    #[test]
+    fn test_merge_and_render() {
+        let first_chunk = DateTime::parse_from_rfc3339("2024-10-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let second_chunk = first_chunk + Duration::seconds(30);
+        let entries = merge_segments(vec![
+            // out of order across chunks
+            row(3, 2, second_chunk, 1.0, 4.5, "Sounds good & <done>."),
+            row(1, 1, first_chunk, 0.5, 3.0, "Hi all,"),
+            row(2, 1, first_chunk, 3.5, 6.0, "let's start."),
+        ]);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].text, "Hi all, let's start.");
+        assert_eq!(entries[0].speaker, "Alice");
+        assert_eq!(entries[1].speaker, "Speaker 2");
+
+        let srt = render(&entries, TranscriptFormat::Srt);
+        assert!(srt.starts_with(
+            "1\n00:00:00,000 --> 00:00:05,500\nAlice: Hi all, let's start.\n\n2\n00:00:30,500 --> "
+        ));
+
+        let vtt = render(&entries, TranscriptFormat::Vtt);
+        assert!(vtt.starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:05.500\n<v Alice>"));
+        assert!(vtt.contains("Sounds good &amp; &lt;done&gt;."));
+
+        let text = render(&entries, TranscriptFormat::Text);
+        assert_eq!(
+            text,
+            "[00:00:00] Alice: Hi all, let's start.\n[00:00:30] Speaker 2: Sounds good & <done>.\n"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&entries, TranscriptFormat::Json)).unwrap();
+        assert_eq!(json[1]["speaker_id"], 2);
+    }
+}
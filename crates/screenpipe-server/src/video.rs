@@ -129,6 +129,7 @@ impl VideoCapture {
         capture_unfocused_windows: bool,
         activity_feed: screenpipe_vision::ActivityFeedOption,
         video_quality: String,
+        redact_pii_at_capture: bool,
     ) -> Self {
         let fps = if fps.is_finite() && fps > 0.0 {
             fps
@@ -194,6 +195,7 @@ impl VideoCapture {
                     capture_languages.clone(),
                     capture_unfocused,
                     capture_activity_feed.clone(),
+                    redact_pii_at_capture,
                 )
                 .await
                 {
@@ -717,7 +719,7 @@ fn create_output_file(output_path: &str, monitor_id: u32) -> String {
         .to_string()
 }
 
-fn spawn_ffmpeg_loggers(stderr: Option<ChildStderr>, stdout: Option<ChildStdout>) {
+pub(crate) fn spawn_ffmpeg_loggers(stderr: Option<ChildStderr>, stdout: Option<ChildStdout>) {
     if let Some(stderr) = stderr {
         tokio::spawn(log_ffmpeg_output(BufReader::new(stderr), "stderr"));
     }
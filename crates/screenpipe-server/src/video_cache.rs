@@ -2,6 +2,7 @@ use anyhow::Result;
 use bincode;
 use chrono::{DateTime, Duration, Utc};
 use dirs::cache_dir;
+use screenpipe_core::at_rest::readable_path;
 use screenpipe_core::find_ffmpeg_path;
 use screenpipe_db::{DatabaseManager, FrameData, OCREntry};
 use serde::{Deserialize, Serialize};
@@ -671,6 +672,10 @@ async fn extract_frame(
         return Ok(0);
     }
 
+    // Encrypted at rest: ffmpeg reads a decrypted copy
+    let readable = readable_path(&video_file_path).await?;
+    let video_file_path = readable.as_str().to_string();
+
     // Get source FPS from video metadata
     let source_fps = match get_video_fps(&ffmpeg, &video_file_path).await {
         Ok(fps) => fps,
@@ -856,8 +861,9 @@ async fn is_video_file_complete(ffmpeg_path: &PathBuf, file_path: &str) -> Resul
         }
     }
 
+    let readable = readable_path(file_path).await?;
     let mut cmd = Command::new(ffmpeg_path);
-    cmd.args(["-v", "error", "-i", file_path, "-f", "null", "-"]);
+    cmd.args(["-v", "error", "-i", readable.as_str(), "-f", "null", "-"]);
 
     #[cfg(windows)]
     {
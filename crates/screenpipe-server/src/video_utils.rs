@@ -3,12 +3,13 @@ use base64::{engine::general_purpose, Engine as _};
 use chrono::NaiveDateTime;
 use chrono::{DateTime, Utc};
 use image::codecs::jpeg::JpegEncoder;
-use image::{DynamicImage, GenericImageView, Rgba};
-use imageproc::filter::gaussian_blur_f32;
+use image::{DynamicImage, Rgba};
 use oasgen::OaSchema;
+use screenpipe_core::at_rest::readable_path;
 use screenpipe_core::find_ffmpeg_path;
 use screenpipe_core::pii_removal::PiiRegion;
 use screenpipe_db::VideoMetadata as DBVideoMetadata;
+use screenpipe_vision::pii_redaction::blur_regions;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::Cursor;
@@ -77,6 +78,9 @@ struct Stream {
 pub async fn extract_frame(file_path: &str, offset_index: i64) -> Result<String> {
     let ffmpeg_path = find_ffmpeg_path().expect("failed to find ffmpeg path");
 
+    let readable = readable_path(file_path).await?;
+    let file_path = readable.as_str();
+
     let offset_seconds = offset_index as f64 / 1000.0;
     let offset_str = format!("{:.3}", offset_seconds);
 
@@ -160,6 +164,9 @@ pub async fn validate_media(file_path: &str) -> Result<()> {
         return Err(anyhow::anyhow!("media file does not exist: {}", file_path));
     }
 
+    let readable = readable_path(file_path).await?;
+    let file_path = readable.as_str();
+
     let ffmpeg_path = find_ffmpeg_path().expect("failed to find ffmpeg path");
     let mut cmd = Command::new(ffmpeg_path);
     cmd.args(["-v", "error", "-i", file_path, "-f", "null", "-"]);
@@ -200,19 +207,23 @@ pub async fn merge_videos(
     // create a temporary file to store the list of input videos
     let temp_file = output_dir.join("input_list.txt");
     let mut file = tokio::fs::File::create(&temp_file).await?;
+    // decrypted copies of videos encrypted at rest, kept until ffmpeg is done
+    let mut readable_videos = Vec::with_capacity(request.video_paths.len());
     for video_path in &request.video_paths {
         // video validation before writing in txt
         if let Err(e) = validate_media(video_path).await {
             error!("invalid file in merging, skipping: {:?}", e);
             continue;
         }
+        let readable = readable_path(video_path).await?;
         // Escape single quotes in the file path
-        let escaped_path = video_path.replace("'", "'\\''");
+        let escaped_path = readable.as_str().replace("'", "'\\''");
         tokio::io::AsyncWriteExt::write_all(
             &mut file,
             format!("file '{}'\n", escaped_path).as_bytes(),
         )
         .await?;
+        readable_videos.push(readable);
     }
 
     let ffmpeg_path = find_ffmpeg_path().expect("failed to find ffmpeg path");
@@ -295,6 +306,9 @@ pub async fn extract_frames_from_video(
         ));
     }
 
+    let readable = readable_path(video_path).await?;
+    let video_path = readable.path();
+
     // Get source FPS and calculate target FPS
     let source_fps = match get_video_fps(&ffmpeg_path, video_path.to_str().unwrap()).await {
         Ok(fps) => fps,
@@ -373,7 +387,7 @@ pub async fn extract_frames_from_video(
     Ok(frames)
 }
 
-async fn get_video_fps(ffmpeg_path: &PathBuf, video_path: &str) -> Result<f64> {
+pub(crate) async fn get_video_fps(ffmpeg_path: &PathBuf, video_path: &str) -> Result<f64> {
     let (fps, _) = get_video_fps_and_duration(ffmpeg_path, video_path).await?;
     Ok(fps)
 }
@@ -724,6 +738,10 @@ pub async fn extract_frame_from_video(file_path: &str, offset_index: i64, jpeg_q
         }
     }
 
+    // Encrypted at rest: ffmpeg reads a decrypted copy
+    let readable = readable_path(file_path).await?;
+    let file_path = readable.as_str();
+
     // Get video FPS and duration - if this fails, the video is likely corrupted
     let (source_fps, video_duration) =
         match get_video_fps_and_duration(&ffmpeg_path, file_path).await {
@@ -871,6 +889,9 @@ pub async fn extract_high_quality_frame(
 ) -> Result<String> {
     let ffmpeg_path = find_ffmpeg_path().expect("failed to find ffmpeg path");
 
+    let readable = readable_path(file_path).await?;
+    let file_path = readable.as_str();
+
     let source_fps = match get_video_fps(&ffmpeg_path, file_path).await {
         Ok(fps) => fps,
         Err(e) => {
@@ -947,39 +968,7 @@ pub fn redact_frame_pii(image_data: &[u8], regions: &[PiiRegion]) -> Result<Vec<
     // Load the image
     let img = image::load_from_memory(image_data)?;
     let mut img_rgba = img.to_rgba8();
-    let (img_width, img_height) = img_rgba.dimensions();
-
-    for region in regions {
-        // Validate region bounds
-        if region.x >= img_width || region.y >= img_height {
-            continue;
-        }
-
-        // Clamp region to image bounds
-        let x = region.x;
-        let y = region.y;
-        let w = region.width.min(img_width - x);
-        let h = region.height.min(img_height - y);
-
-        if w == 0 || h == 0 {
-            continue;
-        }
-
-        // Extract the region to blur
-        let sub_img = img_rgba.view(x, y, w, h).to_image();
-
-        // Apply Gaussian blur (sigma of 10.0 provides strong blur)
-        let blurred = gaussian_blur_f32(&sub_img, 10.0);
-
-        // Copy blurred region back to the image
-        for (dx, dy, pixel) in blurred.enumerate_pixels() {
-            let target_x = x + dx;
-            let target_y = y + dy;
-            if target_x < img_width && target_y < img_height {
-                img_rgba.put_pixel(target_x, target_y, *pixel);
-            }
-        }
-    }
+    blur_regions(&mut img_rgba, regions);
 
     // Encode back to JPEG
     let mut output = Cursor::new(Vec::new());
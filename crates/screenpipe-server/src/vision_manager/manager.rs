@@ -23,6 +23,7 @@ pub struct VisionManagerConfig {
     pub video_chunk_duration: Duration,
     pub ocr_engine: Arc<OcrEngine>,
     pub use_pii_removal: bool,
+    pub redact_pii_at_capture: bool,
     pub ignored_windows: Vec<String>,
     pub included_windows: Vec<String>,
     pub ignored_urls: Vec<String>,
@@ -159,6 +160,7 @@ impl VisionManager {
         let video_chunk_duration = self.config.video_chunk_duration;
         let ocr_engine = self.config.ocr_engine.clone();
         let use_pii_removal = self.config.use_pii_removal;
+        let redact_pii_at_capture = self.config.redact_pii_at_capture;
         let ignored_windows = self.config.ignored_windows.clone();
         let included_windows = self.config.included_windows.clone();
         let ignored_urls = self.config.ignored_urls.clone();
@@ -178,6 +180,7 @@ impl VisionManager {
                     ocr_engine.clone(),
                     monitor_id,
                     use_pii_removal,
+                    redact_pii_at_capture,
                     &ignored_windows,
                     &included_windows,
                     &ignored_urls,
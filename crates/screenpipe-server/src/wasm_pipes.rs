@@ -0,0 +1,403 @@
+//! WebAssembly pipes
+//!
+//! A pipe shipping a `pipe.wasm` component of the `pipe` world (`wit/pipe.wit`) runs
+//! in-process with wasmtime instead of Bun. It gets no files, sockets or environment
+//! variables beyond those it declares: everything it reads goes through the host
+//! functions, checked against its scopes and permissions like its API token would be.
+//! Each call into the pipe is bounded by fuel and its memory by `memory_mb`, both set
+//! in the `wasm` section of its pipe.json.
+
+use anyhow::{anyhow, Result};
+use axum::http::Method;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use screenpipe_core::{pipe_log, LogStream, PipeLog, PipePermissions};
+use screenpipe_db::{ContentType, DatabaseManager, SearchOrder};
+use screenpipe_events::{send_event, subscribe_to_all_events};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, warn};
+use wasmtime::component::{Component, Linker, ResourceTable};
+use wasmtime::{Config, Engine, Store, StoreLimits, StoreLimitsBuilder};
+use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiView};
+
+use crate::auth::{required_scope, Scope};
+
+wasmtime::component::bindgen!({
+    path: "wit/pipe.wit",
+    world: "pipe",
+    async: true,
+});
+
+use screenpipe::pipe::host::{Host, SearchQuery};
+
+/// Component of a WebAssembly pipe, relative to its directory.
+pub const WASM_MODULE: &str = "pipe.wasm";
+
+/// Key-value storage of a pipe, relative to its directory.
+const KV_FILE: &str = "kv.json";
+
+/// Size limit of the key-value storage of a pipe.
+const KV_MAX_BYTES: usize = 1024 * 1024;
+
+const MAX_SEARCH_LIMIT: u32 = 1000;
+
+/// Instructions between two yields to the runtime, so a busy pipe can't starve it.
+const FUEL_YIELD_INTERVAL: u64 = 100_000;
+
+/// The `wasm` section of a pipe.json.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct WasmLimits {
+    /// Fuel (roughly instructions) of each call into the pipe
+    fuel: u64,
+    /// Linear memory of the pipe
+    memory_mb: usize,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        Self {
+            fuel: 1_000_000_000,
+            memory_mb: 64,
+        }
+    }
+}
+
+/// What a WebAssembly pipe runs with.
+#[derive(Clone)]
+pub struct WasmPipeConfig {
+    pub id: String,
+    pub pipe_dir: PathBuf,
+    pub db: Arc<DatabaseManager>,
+    pub scopes: Vec<Scope>,
+    pub permissions: Option<PipePermissions>,
+}
+
+/// State of a running pipe, reached by its host functions.
+struct PipeHost {
+    id: String,
+    db: Arc<DatabaseManager>,
+    scopes: Vec<Scope>,
+    permissions: Option<PipePermissions>,
+    subscriptions: Vec<String>,
+    kv: KvStore,
+    log: Arc<PipeLog>,
+    limits: StoreLimits,
+    wasi: WasiCtx,
+    table: ResourceTable,
+}
+
+impl WasiView for PipeHost {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
+}
+
+impl PipeHost {
+    /// Same checks as the API applies to the pipe's token for a `GET` of `path`.
+    fn authorize(
+        &self,
+        path: &str,
+        content_type: Option<&str>,
+        app_name: Option<&str>,
+        start_time: Option<&str>,
+    ) -> Result<(), String> {
+        if let Some(scope) = required_scope(&Method::GET, path) {
+            if !self.scopes.contains(&scope) {
+                return Err(format!("pipe lacks scope {}", scope));
+            }
+        }
+        if let Some(permissions) = &self.permissions {
+            permissions.check_endpoint("GET", path)?;
+            permissions.check_data(content_type, app_name, start_time, Utc::now())?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Host for PipeHost {
+    async fn search(&mut self, query: SearchQuery) -> Result<String, String> {
+        self.authorize(
+            "/search",
+            query.content_type.as_deref(),
+            query.app_name.as_deref(),
+            query.start_time.as_deref(),
+        )?;
+
+        let content_type: ContentType = match &query.content_type {
+            Some(content_type) => serde_json::from_value(json!(content_type))
+                .map_err(|_| format!("unknown content_type {}", content_type))?,
+            None => ContentType::All,
+        };
+        let results = self
+            .db
+            .search(
+                query.q.as_deref().unwrap_or_default(),
+                content_type,
+                query.limit.unwrap_or(20).min(MAX_SEARCH_LIMIT),
+                query.offset.unwrap_or(0),
+                parse_time(query.start_time.as_deref())?,
+                parse_time(query.end_time.as_deref())?,
+                query.app_name.as_deref(),
+                query.window_name.as_deref(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                SearchOrder::default(),
+            )
+            .await
+            .map_err(|e| format!("search failed: {}", e))?;
+        serde_json::to_string(&results).map_err(|e| e.to_string())
+    }
+
+    async fn subscribe(&mut self, event: String) -> Result<(), String> {
+        // events can't be filtered, like /ws/events they need unrestricted data
+        self.authorize("/ws/events", None, None, None)?;
+        if !self.subscriptions.contains(&event) {
+            self.subscriptions.push(event);
+        }
+        Ok(())
+    }
+
+    async fn kv_get(&mut self, key: String) -> Option<String> {
+        self.kv.values.get(&key).cloned()
+    }
+
+    async fn kv_set(&mut self, key: String, value: String) -> Result<(), String> {
+        self.kv.set(key, value).await
+    }
+
+    async fn kv_delete(&mut self, key: String) -> Result<(), String> {
+        self.kv.delete(&key).await
+    }
+
+    async fn notify(&mut self, title: String, body: String) {
+        let notification = json!({ "pipe_id": self.id, "title": title, "body": body });
+        if let Err(e) = send_event("pipe_notification", notification) {
+            warn!("[{}] failed to send notification: {}", self.id, e);
+        }
+    }
+
+    async fn log(&mut self, message: String) {
+        self.log.write(LogStream::Stdout, &message);
+        info!("[{}] {}", self.id, message);
+    }
+}
+
+fn parse_time(time: Option<&str>) -> Result<Option<DateTime<Utc>>, String> {
+    time.map(|time| {
+        DateTime::parse_from_rfc3339(time)
+            .map(|time| time.with_timezone(&Utc))
+            .map_err(|e| format!("invalid time {}: {}", time, e))
+    })
+    .transpose()
+}
+
+/// Key-value storage of a pipe, a JSON object written on every change.
+struct KvStore {
+    path: PathBuf,
+    values: BTreeMap<String, String>,
+}
+
+impl KvStore {
+    async fn open(path: PathBuf) -> Result<Self> {
+        let values = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, values })
+    }
+
+    fn size(&self) -> usize {
+        self.values.iter().map(|(k, v)| k.len() + v.len()).sum()
+    }
+
+    async fn set(&mut self, key: String, value: String) -> Result<(), String> {
+        let replaced = self.values.get(&key).map_or(0, |old| key.len() + old.len());
+        if self.size() - replaced + key.len() + value.len() > KV_MAX_BYTES {
+            return Err(format!(
+                "key-value storage is limited to {} bytes",
+                KV_MAX_BYTES
+            ));
+        }
+        self.values.insert(key, value);
+        self.save().await
+    }
+
+    async fn delete(&mut self, key: &str) -> Result<(), String> {
+        if self.values.remove(key).is_some() {
+            self.save().await?;
+        }
+        Ok(())
+    }
+
+    async fn save(&self) -> Result<(), String> {
+        let content = serde_json::to_string(&self.values).map_err(|e| e.to_string())?;
+        let tmp = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, content)
+            .await
+            .map_err(|e| e.to_string())?;
+        tokio::fs::rename(&tmp, &self.path)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Whether the pipe in `pipe_dir` is a WebAssembly pipe.
+pub fn is_wasm_pipe(pipe_dir: &Path) -> bool {
+    pipe_dir.join(WASM_MODULE).exists()
+}
+
+async fn read_limits(pipe_dir: &Path) -> WasmLimits {
+    let config: Value = match tokio::fs::read_to_string(pipe_dir.join("pipe.json")).await {
+        Ok(config) => serde_json::from_str(&config).unwrap_or_default(),
+        Err(_) => Value::Null,
+    };
+    config
+        .get("wasm")
+        .and_then(|limits| serde_json::from_value(limits.clone()).ok())
+        .unwrap_or_default()
+}
+
+fn engine() -> Result<Engine> {
+    let mut config = Config::new();
+    config
+        .wasm_component_model(true)
+        .async_support(true)
+        .consume_fuel(true);
+    Engine::new(&config)
+}
+
+/// Run a WebAssembly pipe until it's done or fails. Dropping the future stops it.
+pub async fn run_wasm_pipe(config: WasmPipeConfig) -> Result<()> {
+    let started = Instant::now();
+    let limits = read_limits(&config.pipe_dir).await;
+    let engine = engine()?;
+    let component = {
+        let engine = engine.clone();
+        let path = config.pipe_dir.join(WASM_MODULE);
+        tokio::task::spawn_blocking(move || Component::from_file(&engine, path)).await??
+    };
+
+    let mut linker = Linker::new(&engine);
+    wasmtime_wasi::add_to_linker_async(&mut linker)?;
+    Pipe::add_to_linker(&mut linker, |host: &mut PipeHost| host)?;
+
+    let declared_env: Vec<(String, String)> = std::env::vars()
+        .filter(|(name, _)| {
+            config
+                .permissions
+                .as_ref()
+                .is_some_and(|permissions| permissions.env.contains(name))
+        })
+        .chain([("PIPE_ID".to_string(), config.id.clone())])
+        .collect();
+    let wasi = WasiCtxBuilder::new()
+        .inherit_stdout()
+        .inherit_stderr()
+        .envs(&declared_env)
+        .build();
+
+    let host = PipeHost {
+        id: config.id.clone(),
+        db: config.db,
+        scopes: config.scopes,
+        permissions: config.permissions,
+        subscriptions: Vec::new(),
+        kv: KvStore::open(config.pipe_dir.join(KV_FILE)).await?,
+        log: pipe_log(&config.pipe_dir),
+        limits: StoreLimitsBuilder::new()
+            .memory_size(limits.memory_mb * 1024 * 1024)
+            .build(),
+        wasi,
+        table: ResourceTable::new(),
+    };
+    let mut store = Store::new(&engine, host);
+    store.limiter(|host| &mut host.limits);
+    store.fuel_async_yield_interval(Some(FUEL_YIELD_INTERVAL))?;
+    store.set_fuel(limits.fuel)?;
+
+    let pipe = Pipe::instantiate_async(&mut store, &component, &linker).await?;
+    pipe.call_init(&mut store)
+        .await?
+        .map_err(|e| anyhow!("pipe init failed: {}", e))?;
+    info!(
+        "[{}] wasm pipe initialized in {:?}",
+        config.id,
+        started.elapsed()
+    );
+
+    if store.data().subscriptions.is_empty() {
+        return Ok(());
+    }
+    let mut events = subscribe_to_all_events();
+    loop {
+        let Some(mut event) = events.next().await else {
+            return Ok(());
+        };
+        if !store.data().subscriptions.contains(&event.name) {
+            continue;
+        }
+        if let Some(data) = event.data.as_object_mut() {
+            data.remove("image");
+        }
+
+        store.set_fuel(limits.fuel)?;
+        let data = event.data.to_string();
+        let handled = pipe.call_on_event(&mut store, &event.name, &data).await?;
+        if let Err(e) = handled {
+            warn!("[{}] failed to handle {}: {}", config.id, event.name, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_kv_store_persists_and_limits_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(KV_FILE);
+
+        let mut kv = KvStore::open(path.clone()).await.unwrap();
+        kv.set("last_run".to_string(), "2024-10-01T09:00:00Z".to_string())
+            .await
+            .unwrap();
+        kv.set("seen".to_string(), "3".to_string()).await.unwrap();
+        kv.delete("seen").await.unwrap();
+        assert!(kv
+            .set("big".to_string(), "x".repeat(KV_MAX_BYTES))
+            .await
+            .is_err());
+
+        let kv = KvStore::open(path).await.unwrap();
+        assert_eq!(
+            kv.values.get("last_run").map(String::as_str),
+            Some("2024-10-01T09:00:00Z")
+        );
+        assert_eq!(kv.values.len(), 1);
+    }
+
+    #[test]
+    fn test_read_limits_defaults() {
+        let limits: WasmLimits = serde_json::from_value(json!({ "memory_mb": 16 })).unwrap();
+        assert_eq!(limits.memory_mb, 16);
+        assert_eq!(limits.fuel, WasmLimits::default().fuel);
+    }
+}
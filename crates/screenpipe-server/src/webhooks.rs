@@ -0,0 +1,794 @@
+//! Webhooks
+//!
+//! Pushes event bus events (`meeting_started`, `window_ocr`, `ui_frame`, ...) to HTTP
+//! endpoints registered through `/webhooks`. A webhook filters events by name and
+//! optionally by a JSON path predicate on the event data. Deliveries are signed with
+//! HMAC-SHA256, retried with exponential backoff, and kept in a dead-letter table once
+//! every attempt failed, from where they can be retried through the API. Events dropped
+//! because too many deliveries are in progress are counted in one dead letter per
+//! webhook, holding the last of them, updated every few seconds.
+//!
+//! Delivery body: `{"event": <name>, "data": <event data>, "timestamp": <rfc3339>}`.
+//! Signature: `x-screenpipe-signature: sha256=<hex hmac of "<x-screenpipe-timestamp>.<body>">`.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use screenpipe_db::{DatabaseManager, Webhook, WebhookDeadLetter};
+use screenpipe_events::{subscribe_to_all_events, Event as ScreenpipeEvent};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{RwLock, Semaphore};
+use tracing::{debug, error, info, warn};
+
+use crate::server::{api_error, ApiError, AppState};
+
+pub const SIGNATURE_HEADER: &str = "x-screenpipe-signature";
+pub const TIMESTAMP_HEADER: &str = "x-screenpipe-timestamp";
+pub const EVENT_HEADER: &str = "x-screenpipe-event";
+pub const DELIVERY_HEADER: &str = "x-screenpipe-delivery";
+
+/// Attempts per delivery before it goes to the dead-letter table.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Wait before the first retry, doubled for every following one.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Deliveries in progress (including their retries). Events go to the dead-letter table
+/// beyond that so a slow endpoint can't pile up tasks on frequent events like `ui_frame`.
+const MAX_IN_FLIGHT: usize = 64;
+
+/// Interval between two writes of the events dropped because of `MAX_IN_FLIGHT`.
+const OVERFLOW_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Events carrying a base64 screenshot, removed from the delivered data.
+const IMAGE_EVENTS: &[&str] = &["ocr_result", "ui_frame"];
+
+struct Subscription {
+    webhook: Webhook,
+    predicate: Option<Predicate>,
+}
+
+impl Subscription {
+    fn matches(&self, event: &ScreenpipeEvent) -> bool {
+        self.webhook.enabled
+            && (self.webhook.events.is_empty() || self.webhook.events.contains(&event.name))
+            && self
+                .predicate
+                .as_ref()
+                .map_or(true, |predicate| predicate.matches(&event.data))
+    }
+}
+
+/// Events of a webhook dropped since the last flush, the last one is kept.
+struct Overflow {
+    dropped: u64,
+    event_name: String,
+    body: String,
+}
+
+/// Dead letter counting the dropped events of a webhook.
+struct OverflowLetter {
+    id: i64,
+    dropped: u64,
+}
+
+pub struct WebhookDispatcher {
+    db: Arc<DatabaseManager>,
+    client: reqwest::Client,
+    subscriptions: RwLock<Vec<Arc<Subscription>>>,
+    in_flight: Arc<Semaphore>,
+    /// Dropped events per webhook id, written by `flush_overflow`
+    overflow: Mutex<HashMap<i64, Overflow>>,
+}
+
+impl WebhookDispatcher {
+    pub fn new(db: Arc<DatabaseManager>) -> Self {
+        Self {
+            db,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            subscriptions: RwLock::new(Vec::new()),
+            in_flight: Arc::new(Semaphore::new(MAX_IN_FLIGHT)),
+            overflow: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Re-read the webhooks from the database, called after every change.
+    pub async fn reload(&self) -> Result<(), sqlx::Error> {
+        let subscriptions = self
+            .db
+            .list_webhooks()
+            .await?
+            .into_iter()
+            .map(|webhook| {
+                let predicate = webhook.predicate.as_deref().and_then(|p| {
+                    Predicate::parse(p)
+                        .map_err(|e| warn!("webhook {} predicate ignored: {}", webhook.id, e))
+                        .ok()
+                });
+                Arc::new(Subscription { webhook, predicate })
+            })
+            .collect();
+        *self.subscriptions.write().await = subscriptions;
+        Ok(())
+    }
+
+    /// Deliver events to the registered webhooks forever.
+    pub fn start(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            if let Err(e) = self.reload().await {
+                error!("failed to load webhooks: {}", e);
+            }
+
+            let dispatcher = self.clone();
+            tokio::spawn(async move {
+                let mut letters = HashMap::new();
+                let mut interval = tokio::time::interval(OVERFLOW_FLUSH_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    dispatcher.flush_overflow(&mut letters).await;
+                }
+            });
+
+            let mut events = subscribe_to_all_events();
+            while let Some(event) = events.next().await {
+                self.dispatch(event).await;
+            }
+        })
+    }
+
+    async fn dispatch(self: &Arc<Self>, mut event: ScreenpipeEvent) {
+        let subscriptions: Vec<Arc<Subscription>> = self
+            .subscriptions
+            .read()
+            .await
+            .iter()
+            .filter(|s| s.matches(&event))
+            .cloned()
+            .collect();
+        if subscriptions.is_empty() {
+            return;
+        }
+
+        if IMAGE_EVENTS.contains(&event.name.as_str()) {
+            if let Some(data) = event.data.as_object_mut() {
+                data.remove("image");
+            }
+        }
+        let body = json!({
+            "event": event.name,
+            "data": event.data,
+            "timestamp": Utc::now().to_rfc3339(),
+        })
+        .to_string();
+
+        for subscription in subscriptions {
+            let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+                self.drop_event(subscription.webhook.id, &event.name, &body);
+                continue;
+            };
+
+            let dispatcher = self.clone();
+            let event_name = event.name.clone();
+            let body = body.clone();
+            tokio::spawn(async move {
+                let webhook = &subscription.webhook;
+                if let Err(e) = dispatcher.deliver(webhook, &event_name, &body).await {
+                    warn!(
+                        "webhook {} delivery of {} failed after {} attempts: {}",
+                        webhook.id, event_name, MAX_ATTEMPTS, e
+                    );
+                    if let Err(e) = dispatcher
+                        .db
+                        .insert_webhook_dead_letter(
+                            webhook.id,
+                            &event_name,
+                            &body,
+                            MAX_ATTEMPTS as i64,
+                            &e,
+                        )
+                        .await
+                    {
+                        error!("failed to store webhook dead letter: {}", e);
+                    }
+                }
+                drop(permit);
+            });
+        }
+    }
+
+    /// Count an event not delivered because too many deliveries are in progress.
+    fn drop_event(&self, webhook_id: i64, event_name: &str, body: &str) {
+        let mut overflow = self.overflow.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = overflow.entry(webhook_id).or_insert_with(|| {
+            warn!(
+                "too many webhook deliveries in progress, dropping events for webhook {}",
+                webhook_id
+            );
+            Overflow {
+                dropped: 0,
+                event_name: String::new(),
+                body: String::new(),
+            }
+        });
+        entry.dropped += 1;
+        entry.event_name = event_name.to_string();
+        entry.body = body.to_string();
+    }
+
+    /// Write the events dropped since the last flush: the dead letter of each webhook
+    /// (`letters`) is updated, or created when there is none or it was retried or deleted.
+    async fn flush_overflow(&self, letters: &mut HashMap<i64, OverflowLetter>) {
+        let overflow =
+            std::mem::take(&mut *self.overflow.lock().unwrap_or_else(|e| e.into_inner()));
+        for (webhook_id, overflow) in overflow {
+            if let Some(letter) = letters.get_mut(&webhook_id) {
+                letter.dropped += overflow.dropped;
+                match self
+                    .db
+                    .update_webhook_dead_letter(
+                        letter.id,
+                        &overflow.event_name,
+                        &overflow.body,
+                        &overflow_error(letter.dropped),
+                    )
+                    .await
+                {
+                    Ok(true) => continue,
+                    Ok(false) => {}
+                    Err(e) => {
+                        error!("failed to update webhook dead letter: {}", e);
+                        continue;
+                    }
+                }
+            }
+
+            match self
+                .db
+                .insert_webhook_dead_letter(
+                    webhook_id,
+                    &overflow.event_name,
+                    &overflow.body,
+                    0,
+                    &overflow_error(overflow.dropped),
+                )
+                .await
+            {
+                Ok(id) => {
+                    letters.insert(
+                        webhook_id,
+                        OverflowLetter {
+                            id,
+                            dropped: overflow.dropped,
+                        },
+                    );
+                }
+                Err(e) => error!("failed to store webhook dead letter: {}", e),
+            }
+        }
+    }
+
+    /// POST `body` to the webhook, retrying with backoff. Returns the last error when
+    /// every attempt failed.
+    async fn deliver(&self, webhook: &Webhook, event_name: &str, body: &str) -> Result<(), String> {
+        let delivery_id = uuid::Uuid::new_v4().to_string();
+        let mut backoff = INITIAL_BACKOFF;
+        let mut last_error = String::new();
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            let timestamp = Utc::now().timestamp().to_string();
+            let result = self
+                .client
+                .post(&webhook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, event_name)
+                .header(DELIVERY_HEADER, &delivery_id)
+                .header(TIMESTAMP_HEADER, &timestamp)
+                .header(SIGNATURE_HEADER, sign(&webhook.secret, &timestamp, body))
+                .body(body.to_string())
+                .send()
+                .await;
+
+            match result {
+                Ok(response) if response.status().is_success() => {
+                    debug!("delivered {} to webhook {}", event_name, webhook.id);
+                    return Ok(());
+                }
+                Ok(response) => last_error = format!("http status {}", response.status()),
+                Err(e) => last_error = e.to_string(),
+            }
+
+            if attempt < MAX_ATTEMPTS {
+                debug!(
+                    "webhook {} attempt {}/{} failed ({}), retrying in {:?}",
+                    webhook.id, attempt, MAX_ATTEMPTS, last_error, backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+
+        Err(last_error)
+    }
+}
+
+fn overflow_error(dropped: u64) -> String {
+    format!(
+        "{} events not attempted while {} deliveries were in progress, payload of the last one",
+        dropped, MAX_IN_FLIGHT
+    )
+}
+
+/// `sha256=<hex>` HMAC of `<timestamp>.<body>` keyed with the webhook secret.
+pub fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Condition on the event data: `<path>` (present and not null/false) or
+/// `<path> <op> <json value>`, with `$.key`, `$.key[0]` paths and `==`, `!=`, `>`,
+/// `>=`, `<`, `<=`, `contains` operators, e.g. `$.app_name == "zoom.us"`.
+#[derive(Debug, Clone)]
+pub struct Predicate {
+    path: Vec<PathSegment>,
+    comparison: Option<(Op, Value)>,
+}
+
+impl Predicate {
+    pub fn parse(predicate: &str) -> Result<Self, String> {
+        let predicate = predicate.trim();
+        let Some(rest) = predicate.strip_prefix('$') else {
+            return Err("predicate must start with $".to_string());
+        };
+
+        let path_len = rest
+            .find(|c: char| c.is_whitespace() || "=!<>".contains(c))
+            .unwrap_or(rest.len());
+        let path = parse_path(&rest[..path_len])?;
+
+        let rest = rest[path_len..].trim();
+        if rest.is_empty() {
+            return Ok(Self {
+                path,
+                comparison: None,
+            });
+        }
+
+        const OPS: &[(&str, Op)] = &[
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            (">=", Op::Ge),
+            ("<=", Op::Le),
+            (">", Op::Gt),
+            ("<", Op::Lt),
+            ("contains", Op::Contains),
+        ];
+        let (op, value) = OPS
+            .iter()
+            .find_map(|(token, op)| rest.strip_prefix(token).map(|value| (*op, value)))
+            .ok_or_else(|| format!("unknown operator in '{}'", rest))?;
+        let value: Value = serde_json::from_str(value.trim())
+            .map_err(|e| format!("invalid value '{}': {}", value.trim(), e))?;
+
+        Ok(Self {
+            path,
+            comparison: Some((op, value)),
+        })
+    }
+
+    pub fn matches(&self, data: &Value) -> bool {
+        let mut current = data;
+        for segment in &self.path {
+            let next = match segment {
+                PathSegment::Key(key) => current.get(key),
+                PathSegment::Index(index) => current.get(index),
+            };
+            match next {
+                Some(next) => current = next,
+                None => return false,
+            }
+        }
+
+        let Some((op, expected)) = &self.comparison else {
+            return !matches!(current, Value::Null | Value::Bool(false));
+        };
+        match op {
+            Op::Eq => values_equal(current, expected),
+            Op::Ne => !values_equal(current, expected),
+            Op::Contains => match (current, expected) {
+                (Value::String(s), Value::String(needle)) => s.contains(needle.as_str()),
+                (Value::Array(items), expected) => {
+                    items.iter().any(|item| values_equal(item, expected))
+                }
+                _ => false,
+            },
+            Op::Gt | Op::Ge | Op::Lt | Op::Le => {
+                let ordering = match (current, expected) {
+                    (Value::Number(a), Value::Number(b)) => {
+                        a.as_f64().partial_cmp(&b.as_f64()).flatten()
+                    }
+                    (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                    _ => None,
+                };
+                match ordering {
+                    Some(ordering) => match op {
+                        Op::Gt => ordering.is_gt(),
+                        Op::Ge => ordering.is_ge(),
+                        Op::Lt => ordering.is_lt(),
+                        _ => ordering.is_le(),
+                    },
+                    None => false,
+                }
+            }
+        }
+    }
+}
+
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => a == b,
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    let mut segments = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(format!("empty key in path '${}'", path));
+            }
+            segments.push(PathSegment::Key(after[..end].to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after
+                .find(']')
+                .ok_or_else(|| format!("unclosed [ in path '${}'", path))?;
+            let index = after[..end]
+                .parse()
+                .map_err(|_| format!("invalid index in path '${}'", path))?;
+            segments.push(PathSegment::Index(index));
+            rest = &after[end + 1..];
+        } else {
+            return Err(format!("invalid path '${}'", path));
+        }
+    }
+    Ok(segments)
+}
+
+// ============================================================================
+// API
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    /// Event names to deliver, every event when empty
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub predicate: Option<String>,
+    /// HMAC key, generated when not given
+    pub secret: Option<String>,
+}
+
+/// Fields to change, the others are kept. An empty `predicate` removes it.
+#[derive(Deserialize)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub predicate: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct DeadLettersQuery {
+    pub webhook_id: Option<i64>,
+    #[serde(default = "default_dead_letters_limit")]
+    pub limit: i64,
+}
+
+fn default_dead_letters_limit() -> i64 {
+    100
+}
+
+fn internal_error(e: sqlx::Error) -> ApiError {
+    error!("webhook database error: {}", e);
+    api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
+}
+
+fn validate(url: &str, predicate: Option<&str>) -> Result<(), ApiError> {
+    match reqwest::Url::parse(url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+        _ => {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                format!("invalid webhook url: {}", url),
+            ))
+        }
+    }
+    if let Some(predicate) = predicate {
+        Predicate::parse(predicate)
+            .map_err(|e| api_error(StatusCode::BAD_REQUEST, format!("invalid predicate: {}", e)))?;
+    }
+    Ok(())
+}
+
+async fn reload(state: &AppState) {
+    if let Err(e) = state.webhooks.reload().await {
+        error!("failed to reload webhooks: {}", e);
+    }
+}
+
+pub async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Webhook>>, ApiError> {
+    state
+        .db
+        .list_webhooks()
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<Webhook>), ApiError> {
+    let predicate = request.predicate.filter(|p| !p.trim().is_empty());
+    validate(&request.url, predicate.as_deref())?;
+    let secret = request
+        .secret
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+
+    let id = state
+        .db
+        .insert_webhook(&request.url, &request.events, predicate.as_deref(), &secret)
+        .await
+        .map_err(internal_error)?;
+    reload(&state).await;
+    info!("webhook {} registered for {}", id, request.url);
+
+    match state.db.get_webhook(id).await.map_err(internal_error)? {
+        Some(webhook) => Ok((StatusCode::CREATED, Json(webhook))),
+        None => Err(api_error(StatusCode::NOT_FOUND, "webhook not found")),
+    }
+}
+
+pub async fn get_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Webhook>, ApiError> {
+    match state.db.get_webhook(id).await.map_err(internal_error)? {
+        Some(webhook) => Ok(Json(webhook)),
+        None => Err(api_error(StatusCode::NOT_FOUND, "webhook not found")),
+    }
+}
+
+pub async fn update_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(request): Json<UpdateWebhookRequest>,
+) -> Result<Json<Webhook>, ApiError> {
+    let Some(webhook) = state.db.get_webhook(id).await.map_err(internal_error)? else {
+        return Err(api_error(StatusCode::NOT_FOUND, "webhook not found"));
+    };
+
+    let url = request.url.unwrap_or(webhook.url);
+    let events = request.events.unwrap_or(webhook.events);
+    let predicate = match request.predicate {
+        Some(predicate) if predicate.trim().is_empty() => None,
+        Some(predicate) => Some(predicate),
+        None => webhook.predicate,
+    };
+    let enabled = request.enabled.unwrap_or(webhook.enabled);
+    validate(&url, predicate.as_deref())?;
+
+    state
+        .db
+        .update_webhook(id, &url, &events, predicate.as_deref(), enabled)
+        .await
+        .map_err(internal_error)?;
+    reload(&state).await;
+
+    get_webhook(State(state), Path(id)).await
+}
+
+pub async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, ApiError> {
+    if !state.db.delete_webhook(id).await.map_err(internal_error)? {
+        return Err(api_error(StatusCode::NOT_FOUND, "webhook not found"));
+    }
+    reload(&state).await;
+    Ok(Json(json!({ "success": true })))
+}
+
+pub async fn list_dead_letters(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DeadLettersQuery>,
+) -> Result<Json<Vec<WebhookDeadLetter>>, ApiError> {
+    state
+        .db
+        .list_webhook_dead_letters(query.webhook_id, query.limit)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+/// Deliver a dead letter again (with retries), it is removed once delivered.
+pub async fn retry_dead_letter(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, ApiError> {
+    let Some(dead_letter) = state
+        .db
+        .get_webhook_dead_letter(id)
+        .await
+        .map_err(internal_error)?
+    else {
+        return Err(api_error(StatusCode::NOT_FOUND, "dead letter not found"));
+    };
+    let Some(webhook) = state
+        .db
+        .get_webhook(dead_letter.webhook_id)
+        .await
+        .map_err(internal_error)?
+    else {
+        return Err(api_error(StatusCode::NOT_FOUND, "webhook not found"));
+    };
+
+    state
+        .webhooks
+        .deliver(&webhook, &dead_letter.event_name, &dead_letter.payload)
+        .await
+        .map_err(|e| api_error(StatusCode::BAD_GATEWAY, format!("delivery failed: {}", e)))?;
+    state
+        .db
+        .delete_webhook_dead_letter(id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(json!({ "success": true })))
+}
+
+pub async fn delete_dead_letter(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, ApiError> {
+    if !state
+        .db
+        .delete_webhook_dead_letter(id)
+        .await
+        .map_err(internal_error)?
+    {
+        return Err(api_error(StatusCode::NOT_FOUND, "dead letter not found"));
+    }
+    Ok(Json(json!({ "success": true })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dropped_events_share_a_dead_letter() {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        let id = db
+            .insert_webhook("http://localhost:9/hook", &[], None, "secret")
+            .await
+            .unwrap();
+        let dispatcher = WebhookDispatcher::new(db.clone());
+        let mut letters = HashMap::new();
+
+        dispatcher.drop_event(id, "ui_frame", r#"{"n":1}"#);
+        dispatcher.drop_event(id, "ui_frame", r#"{"n":2}"#);
+        dispatcher.flush_overflow(&mut letters).await;
+        dispatcher.drop_event(id, "ui_frame", r#"{"n":3}"#);
+        dispatcher.flush_overflow(&mut letters).await;
+        // nothing dropped meanwhile
+        dispatcher.flush_overflow(&mut letters).await;
+
+        let dead_letters = db.list_webhook_dead_letters(Some(id), 10).await.unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].payload, r#"{"n":3}"#);
+        assert_eq!(dead_letters[0].attempts, 0);
+        assert_eq!(dead_letters[0].last_error, overflow_error(3));
+
+        // once retried, the next drops start a new dead letter
+        assert!(db
+            .delete_webhook_dead_letter(dead_letters[0].id)
+            .await
+            .unwrap());
+        dispatcher.drop_event(id, "ui_frame", r#"{"n":4}"#);
+        dispatcher.flush_overflow(&mut letters).await;
+        let dead_letters = db.list_webhook_dead_letters(Some(id), 10).await.unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].payload, r#"{"n":4}"#);
+        assert_eq!(dead_letters[0].last_error, overflow_error(1));
+    }
+
+    #[test]
+    fn test_predicate() {
+        let data = json!({
+            "app_name": "zoom.us",
+            "window_name": "Weekly sync",
+            "confidence": 0.92,
+            "speakers": ["alice", "bob"],
+            "focused": false,
+        });
+        let matches = |p: &str| Predicate::parse(p).unwrap().matches(&data);
+
+        assert!(matches(r#"$.app_name == "zoom.us""#));
+        assert!(!matches(r#"$.app_name != "zoom.us""#));
+        assert!(matches(r#"$.window_name contains "sync""#));
+        assert!(matches(r#"$.speakers contains "bob""#));
+        assert!(matches("$.speakers[1] == \"bob\""));
+        assert!(matches("$.confidence >= 0.9"));
+        assert!(!matches("$.confidence < 0.5"));
+        assert!(matches("$.app_name"));
+        assert!(!matches("$.focused"));
+        assert!(!matches("$.missing"));
+        assert!(!matches("$.missing == null"));
+
+        assert!(Predicate::parse("app_name == 1").is_err());
+        assert!(Predicate::parse("$.app_name ~ 1").is_err());
+        assert!(Predicate::parse("$.app_name == zoom").is_err());
+        assert!(Predicate::parse("$.speakers[x]").is_err());
+    }
+
+    #[test]
+    fn test_sign() {
+        let signature = sign("secret", "1700000000", r#"{"event":"test"}"#);
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_eq!(
+            signature,
+            sign("secret", "1700000000", r#"{"event":"test"}"#)
+        );
+        assert_ne!(
+            signature,
+            sign("other", "1700000000", r#"{"event":"test"}"#)
+        );
+        assert_ne!(
+            signature,
+            sign("secret", "1700000001", r#"{"event":"test"}"#)
+        );
+    }
+}
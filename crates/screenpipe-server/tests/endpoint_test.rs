@@ -7,7 +7,7 @@ mod tests {
     use chrono::DateTime;
     use chrono::{Duration, Utc};
     use screenpipe_audio::audio_manager::AudioManagerBuilder;
-    use screenpipe_db::{ContentType, DatabaseManager, SearchResult};
+    use screenpipe_db::{ContentType, DatabaseManager, SearchOrder, SearchResult};
     use screenpipe_server::PipeManager;
     use screenpipe_server::SCServer;
     use screenpipe_server::{ContentItem, PaginatedResponse};
@@ -468,6 +468,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -490,6 +491,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -513,6 +515,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -540,6 +543,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -673,6 +677,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -704,6 +709,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -751,6 +757,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
@@ -775,6 +782,7 @@ mod tests {
                 None,
                 None,
                 None,
+                SearchOrder::Time,
             )
             .await
             .unwrap();
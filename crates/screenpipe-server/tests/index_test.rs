@@ -5,8 +5,9 @@ use std::{
 
 use anyhow::Result;
 use dirs::home_dir;
+use screenpipe_audio::{core::engine::AudioTranscriptionEngine, vad::VadEngineEnum};
 use screenpipe_db::DatabaseManager;
-use screenpipe_server::{cli::OutputFormat, handle_index_command};
+use screenpipe_server::{cli::OutputFormat, handle_index_command, AudioImportOptions};
 use tempfile::tempdir;
 use tokio::fs;
 use tracing::debug;
@@ -57,6 +58,17 @@ async fn copy_test_video(temp_dir: &Path) -> Result<PathBuf> {
     Ok(target_path)
 }
 
+fn audio_options() -> AudioImportOptions {
+    AudioImportOptions {
+        transcription_engine: Arc::new(AudioTranscriptionEngine::WhisperTinyQuantized),
+        vad_engine: VadEngineEnum::Silero,
+        deepgram_api_key: None,
+        languages: Vec::new(),
+        use_pii_removal: false,
+        transcribe_videos: false,
+    }
+}
+
 #[tokio::test]
 #[ignore]
 async fn test_index_command_with_sql() -> Result<()> {
@@ -78,6 +90,7 @@ async fn test_index_command_with_sql() -> Result<()> {
         None,
         false,
         false,
+        audio_options(),
     )
     .await?;
 
@@ -163,3 +176,71 @@ async fn test_index_command_with_sql() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore] // downloads the whisper and pyannote models
+async fn test_index_command_with_audio() -> Result<()> {
+    let (temp_dir, db) = setup_test_db().await?;
+    let audio_path = temp_dir.path().join("meeting.wav");
+    fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../screenpipe-audio/test_data/accuracy1.wav"),
+        &audio_path,
+    )
+    .await?;
+
+    let override_path = temp_dir.path().join("overrides.json");
+    fs::write(
+        &override_path,
+        serde_json::to_string(&serde_json::json!({
+            "overrides": [{
+                "file_path": audio_path.to_string_lossy(),
+                "metadata": { "creation_time": "2024-03-01T10:00:00Z", "device_name": "zoom" }
+            }]
+        }))?,
+    )
+    .await?;
+
+    handle_index_command(
+        temp_dir.path().into(),
+        temp_dir.path().to_string_lossy().to_string(),
+        None,
+        db.clone(),
+        OutputFormat::Text,
+        None,
+        Some(override_path),
+        false,
+        false,
+        audio_options(),
+    )
+    .await?;
+
+    let chunks = db
+        .execute_raw_sql("SELECT timestamp FROM audio_chunks WHERE file_path LIKE '%meeting.wav'")
+        .await?;
+    let chunks = chunks.as_array().unwrap();
+    assert_eq!(chunks.len(), 1);
+    assert!(chunks[0]["timestamp"]
+        .as_str()
+        .unwrap()
+        .starts_with("2024-03-01"));
+
+    let transcriptions = db
+        .execute_raw_sql(
+            "SELECT t.transcription, t.timestamp, t.device, t.speaker_id FROM audio_transcriptions t
+         JOIN audio_chunks c ON t.audio_chunk_id = c.id
+         WHERE c.file_path LIKE '%meeting.wav'",
+        )
+        .await?;
+    let transcriptions = transcriptions.as_array().unwrap();
+    assert!(!transcriptions.is_empty(), "should have transcriptions");
+    for transcription in transcriptions {
+        assert_eq!(transcription["device"], "zoom");
+        assert!(transcription["timestamp"]
+            .as_str()
+            .unwrap()
+            .starts_with("2024-03-01"));
+        assert!(!transcription["speaker_id"].is_null());
+    }
+
+    Ok(())
+}
@@ -49,6 +49,7 @@ async fn main() {
         languages.clone(),
         false,
         None, // activity_feed - None disables adaptive FPS
+        false, // redact_pii
     )
     .await;
 
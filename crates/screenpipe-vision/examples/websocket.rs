@@ -104,6 +104,7 @@ async fn main() -> Result<()> {
             vec![],
             false,
             None, // activity_feed - None disables adaptive FPS
+            false, // redact_pii
         )
         .await
     });
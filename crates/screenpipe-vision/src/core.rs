@@ -1,14 +1,9 @@
-#[cfg(target_os = "macos")]
-use crate::apple::perform_ocr_apple;
 use crate::capture_screenshot_by_window::CapturedWindow;
 use crate::capture_screenshot_by_window::WindowFilters;
-use crate::custom_ocr::perform_ocr_custom;
 use crate::frame_comparison::{FrameComparer, FrameComparisonConfig};
-#[cfg(target_os = "windows")]
-use crate::microsoft::perform_ocr_windows;
 use crate::monitor::get_monitor_by_id;
 use crate::ocr_cache::{WindowCacheKey, WindowOcrCache};
-use crate::tesseract::perform_ocr_tesseract;
+use crate::pii_redaction::{redact_capture, window_pii_regions, WindowPlacement};
 use crate::utils::capture_screenshot;
 use crate::utils::OcrEngine;
 use anyhow::Result;
@@ -17,8 +12,8 @@ use chrono::{DateTime, Utc};
 use image::codecs::jpeg::JpegEncoder;
 use image::DynamicImage;
 use image::GenericImageView;
+use screenpipe_core::pii_removal::PiiRegion;
 use screenpipe_core::Language;
-use screenpipe_integrations::unstructured_ocr::perform_ocr_cloud;
 use serde::Deserialize;
 use serde::Deserializer;
 use serde::Serialize;
@@ -119,6 +114,8 @@ pub struct WindowOcrResult {
     pub focused: bool,
     pub confidence: f64,
     pub browser_url: Option<String>,
+    /// Regions of the frame blurred at capture time because this window's text had PII
+    pub pii_redactions: Vec<PiiRegion>,
 }
 
 pub struct OcrTaskData {
@@ -129,6 +126,8 @@ pub struct OcrTaskData {
     /// Wall-clock timestamp captured atomically with the screenshot
     pub captured_at: DateTime<Utc>,
     pub result_tx: Sender<CaptureResult>,
+    /// Blur PII out of the frame before it is sent on, see `pii_redaction`
+    pub redact_pii: bool,
 }
 
 #[derive(Debug)]
@@ -152,6 +151,7 @@ pub type ActivityFeedOption = Option<screenpipe_accessibility::ActivityFeed>;
 #[cfg(not(feature = "adaptive-fps"))]
 pub type ActivityFeedOption = Option<()>;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn continuous_capture(
     result_tx: Sender<CaptureResult>,
     interval: Duration,
@@ -161,6 +161,7 @@ pub async fn continuous_capture(
     languages: Vec<Language>,
     capture_unfocused_windows: bool,
     activity_feed: ActivityFeedOption,
+    redact_pii: bool,
 ) -> Result<(), ContinuousCaptureError> {
     let mut frame_counter: u64 = 0;
     let mut max_average: Option<MaxAverageFrame> = None;
@@ -201,6 +202,16 @@ pub async fn continuous_capture(
     #[cfg(not(feature = "adaptive-fps"))]
     let _ = activity_feed;
 
+    // Capture and recording rates per monitor, `rate()` of these is the fps
+    let frames_captured = metrics::counter!(
+        "screenpipe_frames_captured_total",
+        "monitor" => monitor_id.to_string()
+    );
+    let frames_processed = metrics::counter!(
+        "screenpipe_frames_processed_total",
+        "monitor" => monitor_id.to_string()
+    );
+
     loop {
         // 3. Capture screenshot and wall-clock time atomically
         let captured_at = Utc::now();
@@ -217,6 +228,7 @@ pub async fn continuous_capture(
 
         // 4. Process captured image
         let (image, window_images, image_hash, _capture_duration) = capture_result;
+        frames_captured.increment(1);
 
         // Use optimized frame comparison (hash early exit + downscaled + single metric)
         let current_diff = frame_comparer.compare(&image, image_hash);
@@ -272,10 +284,13 @@ pub async fn continuous_capture(
                 &ocr_engine,
                 languages.clone(),
                 ocr_cache.clone(),
+                redact_pii,
             )
             .await
             {
                 error!("Error processing max average frame: {}", e);
+            } else {
+                frames_processed.increment(1);
             }
             frame_counter = 0;
             max_avg_value = 0.0;
@@ -310,6 +325,7 @@ async fn process_max_average_frame(
     ocr_engine: &OcrEngine,
     languages: Vec<Language>,
     ocr_cache: Arc<Mutex<WindowOcrCache>>,
+    redact_pii: bool,
 ) -> Result<(), ContinuousCaptureError> {
     let ocr_task_data = OcrTaskData {
         image: max_avg_frame.image,
@@ -318,6 +334,7 @@ async fn process_max_average_frame(
         timestamp: max_avg_frame.timestamp,
         captured_at: max_avg_frame.captured_at,
         result_tx: max_avg_frame.result_tx,
+        redact_pii,
     };
 
     if let Err(e) = process_ocr_task(ocr_task_data, ocr_engine, languages, ocr_cache).await {
@@ -347,12 +364,13 @@ pub async fn process_ocr_task(
     ocr_cache: Arc<Mutex<WindowOcrCache>>,
 ) -> Result<(), ContinuousCaptureError> {
     let OcrTaskData {
-        image,
+        mut image,
         window_images,
         frame_number,
         timestamp,
         captured_at,
         result_tx,
+        redact_pii,
     } = ocr_task_data;
 
     let start_time = Instant::now();
@@ -369,6 +387,7 @@ pub async fn process_ocr_task(
 
     // Get screen dimensions for coordinate transformation
     let (screen_width, screen_height) = image.dimensions();
+    let coordinates = ocr_engine.backend().coordinates();
 
     for captured_window in window_images {
         // Calculate hash for this window's image
@@ -387,7 +406,9 @@ pub async fn process_ocr_task(
             cache.get(&cache_key)
         };
 
-        let ocr_result = if let Some(cached) = cached_result {
+        let placement = WindowPlacement::of(&captured_window);
+        // the text blocks of the window image, as returned by the engine
+        let (mut ocr_result, window_blocks) = if let Some(cached) = cached_result {
             // Cache hit - reuse previous OCR result
             cache_hits += 1;
             debug!(
@@ -398,7 +419,7 @@ pub async fn process_ocr_task(
             // Still need to transform coordinates for the current position
             let parsed_json = parse_json_output(&cached.text_json);
             let transformed_json = transform_ocr_coordinates_to_screen(
-                parsed_json,
+                parsed_json.clone(),
                 captured_window.window_x,
                 captured_window.window_y,
                 captured_window.window_width,
@@ -410,7 +431,7 @@ pub async fn process_ocr_task(
             total_confidence += cached.confidence;
             window_count += 1;
 
-            WindowOcrResult {
+            let result = WindowOcrResult {
                 image: captured_window.image,
                 window_name: captured_window.window_name,
                 app_name: captured_window.app_name,
@@ -419,11 +440,13 @@ pub async fn process_ocr_task(
                 focused: captured_window.is_focused,
                 confidence: cached.confidence,
                 browser_url: captured_window.browser_url,
-            }
+                pii_redactions: Vec::new(),
+            };
+            (result, parsed_json)
         } else {
             // Cache miss - perform OCR
             cache_misses += 1;
-            let result = process_window_ocr(
+            let (result, window_blocks) = process_window_ocr(
                 captured_window,
                 ocr_engine,
                 &languages,
@@ -435,19 +458,26 @@ pub async fn process_ocr_task(
             .await
             .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string()))?;
 
-            // Cache the result for future use (serialize JSON for storage)
+            // Cache the result for future use (serialize JSON for storage), window-relative
+            // since the window can move
             {
                 let mut cache = ocr_cache.lock().await;
-                let json_str = serde_json::to_string(&result.text_json).unwrap_or_default();
+                let json_str = serde_json::to_string(&window_blocks).unwrap_or_default();
                 cache.insert(cache_key, result.text.clone(), json_str, result.confidence);
             }
 
-            result
+            (result, window_blocks)
         };
 
+        if redact_pii {
+            ocr_result.pii_redactions = window_pii_regions(&window_blocks, coordinates, placement);
+        }
         window_ocr_results.push(ocr_result);
     }
 
+    metrics::counter!("screenpipe_ocr_cache_hits_total").increment(cache_hits);
+    metrics::counter!("screenpipe_ocr_cache_misses_total").increment(cache_misses);
+
     // Log cache performance
     if cache_hits > 0 || cache_misses > 0 {
         debug!(
@@ -463,6 +493,17 @@ pub async fn process_ocr_task(
         );
     }
 
+    // The frame is encoded into the video from the capture result, so this has to happen
+    // before it is sent
+    if redact_pii && redact_capture(&mut image, &mut window_ocr_results) {
+        let regions: usize = window_ocr_results
+            .iter()
+            .map(|w| w.pii_redactions.len())
+            .sum();
+        debug!("Redacted {} PII regions in frame {}", regions, frame_number);
+        metrics::counter!("screenpipe_pii_regions_redacted_total").increment(regions as u64);
+    }
+
     // Create and send the result
     let capture_result = CaptureResult {
         image,
@@ -490,16 +531,19 @@ async fn process_window_ocr(
     window_count: &mut u32,
     screen_width: u32,
     screen_height: u32,
-) -> Result<WindowOcrResult, ContinuousCaptureError> {
+) -> Result<(WindowOcrResult, Vec<HashMap<String, String>>), ContinuousCaptureError> {
     // Use the browser URL that was captured atomically with the screenshot
     // This prevents timing mismatches where URL is fetched after browser navigation
     let browser_url = captured_window.browser_url.clone();
 
     // Perform OCR based on the selected engine
+    let ocr_start = Instant::now();
     let (window_text, window_json_output, confidence) =
         perform_ocr_with_engine(ocr_engine, &captured_window.image, languages.to_vec())
             .await
             .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string()))?;
+    metrics::histogram!("screenpipe_ocr_duration_seconds")
+        .record(ocr_start.elapsed().as_secs_f64());
 
     // Update confidence metrics
     if let Some(conf) = confidence {
@@ -510,7 +554,7 @@ async fn process_window_ocr(
     // Parse the OCR JSON and transform coordinates from window-relative to screen-relative
     let parsed_json = parse_json_output(&window_json_output);
     let transformed_json = transform_ocr_coordinates_to_screen(
-        parsed_json,
+        parsed_json.clone(),
         captured_window.window_x,
         captured_window.window_y,
         captured_window.window_width,
@@ -519,7 +563,7 @@ async fn process_window_ocr(
         screen_height,
     );
 
-    Ok(WindowOcrResult {
+    let result = WindowOcrResult {
         image: captured_window.image,
         window_name: captured_window.window_name,
         app_name: captured_window.app_name,
@@ -528,7 +572,9 @@ async fn process_window_ocr(
         focused: captured_window.is_focused,
         confidence: confidence.unwrap_or(0.0),
         browser_url,
-    })
+        pii_redactions: Vec::new(),
+    };
+    Ok((result, parsed_json))
 }
 
 async fn perform_ocr_with_engine(
@@ -536,24 +582,11 @@ async fn perform_ocr_with_engine(
     image: &DynamicImage,
     languages: Vec<Language>,
 ) -> Result<(String, String, Option<f64>), ContinuousCaptureError> {
-    match ocr_engine {
-        OcrEngine::Unstructured => perform_ocr_cloud(image, languages)
-            .await
-            .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string())),
-        OcrEngine::Tesseract => Ok(perform_ocr_tesseract(image, languages)),
-        #[cfg(target_os = "windows")]
-        OcrEngine::WindowsNative => perform_ocr_windows(image)
-            .await
-            .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string())),
-        #[cfg(target_os = "macos")]
-        OcrEngine::AppleNative => Ok(perform_ocr_apple(image, &languages)),
-        OcrEngine::Custom(config) => perform_ocr_custom(image, languages, config)
-            .await
-            .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string())),
-        _ => Err(ContinuousCaptureError::ErrorProcessingOcr(
-            "Unsupported OCR engine".to_string(),
-        )),
-    }
+    ocr_engine
+        .backend()
+        .perform_ocr(image, &languages)
+        .await
+        .map_err(|e| ContinuousCaptureError::ErrorProcessingOcr(e.to_string()))
 }
 
 async fn send_ocr_result(
@@ -590,6 +623,7 @@ fn log_ocr_performance(
     frame_number: u64,
 ) {
     let duration = start_time.elapsed();
+    metrics::histogram!("screenpipe_ocr_frame_duration_seconds").record(duration.as_secs_f64());
     let avg_confidence = if window_count > 0 {
         total_confidence / window_count as f64
     } else {
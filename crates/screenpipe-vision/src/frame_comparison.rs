@@ -141,6 +141,7 @@ impl FrameComparer {
     /// * `1.0` - First frame (no previous to compare) or completely different
     pub fn compare(&mut self, current_image: &DynamicImage, current_hash: u64) -> f64 {
         self.comparison_count += 1;
+        metrics::counter!("screenpipe_frame_comparisons_total").increment(1);
 
         // First frame - no previous to compare
         if self.previous_hash.is_none() {
@@ -153,6 +154,7 @@ impl FrameComparer {
             if let Some(prev_hash) = self.previous_hash {
                 if prev_hash == current_hash {
                     self.hash_hits += 1;
+                    metrics::counter!("screenpipe_frame_hash_hits_total").increment(1);
                     debug!(
                         "Hash match - skipping comparison (hits: {}/{})",
                         self.hash_hits, self.comparison_count
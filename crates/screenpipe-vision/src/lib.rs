@@ -14,17 +14,22 @@ pub mod frame_comparison;
 pub mod microsoft;
 pub mod monitor;
 pub use monitor::MonitorListError;
+pub mod ocr_backend;
 pub mod ocr_cache;
+pub mod onnx_ocr;
+pub mod pii_redaction;
 pub mod tesseract;
 pub mod utils;
 #[cfg(target_os = "macos")]
 pub use apple::perform_ocr_apple;
 pub use core::{continuous_capture, process_ocr_task, CaptureResult, RealtimeVisionEvent};
 // pub use types::CaptureResult;
+pub use ocr_backend::{BlockCoordinates, OcrBackend};
 pub use utils::OcrEngine;
 pub mod capture_screenshot_by_window;
 pub use custom_ocr::perform_ocr_custom;
 #[cfg(target_os = "windows")]
 pub use microsoft::perform_ocr_windows;
+pub use onnx_ocr::perform_ocr_onnx;
 pub use tesseract::perform_ocr_tesseract;
 pub mod browser_utils;
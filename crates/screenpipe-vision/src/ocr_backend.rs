@@ -0,0 +1,251 @@
+//! OCR engines behind a common interface.
+//!
+//! [`OcrEngine`] stays the user-facing selection (CLI, database), [`OcrEngine::backend`]
+//! resolves it to the [`OcrBackend`] doing the work.
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use image::DynamicImage;
+use screenpipe_core::Language;
+use screenpipe_integrations::unstructured_ocr::perform_ocr_cloud;
+use std::sync::Arc;
+
+#[cfg(target_os = "macos")]
+use crate::apple::perform_ocr_apple;
+use crate::custom_ocr::{perform_ocr_custom, CustomOcrConfig};
+#[cfg(target_os = "windows")]
+use crate::microsoft::perform_ocr_windows;
+use crate::onnx_ocr::perform_ocr_onnx;
+use crate::tesseract::perform_ocr_tesseract;
+use crate::utils::OcrEngine;
+
+/// How an engine places the text blocks of `text_json` on the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockCoordinates {
+    /// Pixels of the image, from its top-left corner
+    Pixels,
+    /// Fractions of the image size, from its top-left corner
+    Normalized,
+    /// Fractions of the image size, from its bottom-left corner
+    NormalizedBottomLeft,
+}
+
+/// An OCR engine turning a window screenshot into text.
+///
+/// Returns `(text, text_json, confidence)`, where `text_json` is a JSON array of text blocks
+/// whose values are all strings: `text`, `conf`, and `left`/`top`/`width`/`height`
+/// relative to `image`, see [`OcrBackend::coordinates`].
+#[async_trait]
+pub trait OcrBackend: Send + Sync {
+    /// Engine name, for logs
+    fn name(&self) -> &'static str;
+
+    /// How the boxes of text blocks are expressed
+    fn coordinates(&self) -> BlockCoordinates {
+        BlockCoordinates::Pixels
+    }
+
+    async fn perform_ocr(
+        &self,
+        image: &DynamicImage,
+        languages: &[Language],
+    ) -> Result<(String, String, Option<f64>)>;
+}
+
+/// Local Tesseract OCR.
+pub struct TesseractOcr;
+
+#[async_trait]
+impl OcrBackend for TesseractOcr {
+    fn name(&self) -> &'static str {
+        "tesseract"
+    }
+
+    async fn perform_ocr(
+        &self,
+        image: &DynamicImage,
+        languages: &[Language],
+    ) -> Result<(String, String, Option<f64>)> {
+        Ok(perform_ocr_tesseract(image, languages.to_vec()))
+    }
+}
+
+/// Unstructured cloud OCR.
+pub struct UnstructuredOcr;
+
+#[async_trait]
+impl OcrBackend for UnstructuredOcr {
+    fn name(&self) -> &'static str {
+        "unstructured"
+    }
+
+    async fn perform_ocr(
+        &self,
+        image: &DynamicImage,
+        languages: &[Language],
+    ) -> Result<(String, String, Option<f64>)> {
+        perform_ocr_cloud(image, languages.to_vec()).await
+    }
+}
+
+/// macOS Vision framework OCR.
+#[cfg(target_os = "macos")]
+pub struct AppleNativeOcr;
+
+#[cfg(target_os = "macos")]
+#[async_trait]
+impl OcrBackend for AppleNativeOcr {
+    fn name(&self) -> &'static str {
+        "apple-native"
+    }
+
+    fn coordinates(&self) -> BlockCoordinates {
+        BlockCoordinates::NormalizedBottomLeft
+    }
+
+    async fn perform_ocr(
+        &self,
+        image: &DynamicImage,
+        languages: &[Language],
+    ) -> Result<(String, String, Option<f64>)> {
+        Ok(perform_ocr_apple(image, languages))
+    }
+}
+
+/// Windows.Media.Ocr.
+#[cfg(target_os = "windows")]
+pub struct WindowsNativeOcr;
+
+#[cfg(target_os = "windows")]
+#[async_trait]
+impl OcrBackend for WindowsNativeOcr {
+    fn name(&self) -> &'static str {
+        "windows-native"
+    }
+
+    async fn perform_ocr(
+        &self,
+        image: &DynamicImage,
+        _languages: &[Language],
+    ) -> Result<(String, String, Option<f64>)> {
+        perform_ocr_windows(image).await
+    }
+}
+
+/// User-provided OCR HTTP endpoint.
+pub struct CustomOcr {
+    config: CustomOcrConfig,
+}
+
+impl CustomOcr {
+    pub fn new(config: CustomOcrConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl OcrBackend for CustomOcr {
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+
+    async fn perform_ocr(
+        &self,
+        image: &DynamicImage,
+        languages: &[Language],
+    ) -> Result<(String, String, Option<f64>)> {
+        perform_ocr_custom(image, languages.to_vec(), &self.config).await
+    }
+}
+
+/// Local PaddleOCR models on ONNX Runtime, see [`crate::onnx_ocr`].
+///
+/// The default models cover Latin and Chinese scripts, `languages` is not used.
+pub struct OnnxOcr;
+
+#[async_trait]
+impl OcrBackend for OnnxOcr {
+    fn name(&self) -> &'static str {
+        "onnx"
+    }
+
+    fn coordinates(&self) -> BlockCoordinates {
+        BlockCoordinates::Normalized
+    }
+
+    async fn perform_ocr(
+        &self,
+        image: &DynamicImage,
+        _languages: &[Language],
+    ) -> Result<(String, String, Option<f64>)> {
+        perform_ocr_onnx(image).await
+    }
+}
+
+/// Engine not available on this platform.
+struct UnsupportedOcr(&'static str);
+
+#[async_trait]
+impl OcrBackend for UnsupportedOcr {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    async fn perform_ocr(
+        &self,
+        _image: &DynamicImage,
+        _languages: &[Language],
+    ) -> Result<(String, String, Option<f64>)> {
+        Err(anyhow!(
+            "Unsupported OCR engine {} on this platform",
+            self.0
+        ))
+    }
+}
+
+impl OcrEngine {
+    /// The backend implementing this engine.
+    pub fn backend(&self) -> Arc<dyn OcrBackend> {
+        match self {
+            OcrEngine::Unstructured => Arc::new(UnstructuredOcr),
+            OcrEngine::Tesseract => Arc::new(TesseractOcr),
+            #[cfg(target_os = "windows")]
+            OcrEngine::WindowsNative => Arc::new(WindowsNativeOcr),
+            #[cfg(not(target_os = "windows"))]
+            OcrEngine::WindowsNative => Arc::new(UnsupportedOcr("windows-native")),
+            #[cfg(target_os = "macos")]
+            OcrEngine::AppleNative => Arc::new(AppleNativeOcr),
+            #[cfg(not(target_os = "macos"))]
+            OcrEngine::AppleNative => Arc::new(UnsupportedOcr("apple-native")),
+            OcrEngine::Custom(config) => Arc::new(CustomOcr::new(config.clone())),
+            OcrEngine::Onnx => Arc::new(OnnxOcr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_backend_resolution() {
+        assert_eq!(OcrEngine::Tesseract.backend().name(), "tesseract");
+        assert_eq!(OcrEngine::Onnx.backend().name(), "onnx");
+        assert_eq!(
+            OcrEngine::Custom(CustomOcrConfig::default())
+                .backend()
+                .name(),
+            "custom"
+        );
+
+        #[cfg(not(target_os = "macos"))]
+        {
+            let image = DynamicImage::new_rgb8(1, 1);
+            let result = OcrEngine::AppleNative
+                .backend()
+                .perform_ocr(&image, &[])
+                .await;
+            assert!(result.is_err());
+        }
+    }
+}
@@ -0,0 +1,760 @@
+//! Local OCR with PaddleOCR models running on ONNX Runtime (CPU).
+//!
+//! Two models are used: a DBNet detector producing a per-pixel text probability map, and a
+//! CRNN/SVTR recognizer decoded with greedy CTC. Both are downloaded on first use into the
+//! screenpipe model cache. The detector works on the raw image instead of a binarized one,
+//! which is what makes light-on-dark text (dark-mode IDEs, terminals) and small UI fonts
+//! usable, where Tesseract struggles.
+
+use anyhow::{anyhow, Context, Result};
+use image::{imageops::FilterType, DynamicImage, GenericImageView, RgbImage};
+use ndarray::{s, Array4};
+use ort::{GraphOptimizationLevel, Session};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use tracing::{debug, info};
+
+const DET_MODEL_FILE: &str = "ch_PP-OCRv4_det_infer.onnx";
+const DET_MODEL_URL: &str =
+    "https://huggingface.co/SWHL/RapidOCR/resolve/main/PP-OCRv4/ch_PP-OCRv4_det_infer.onnx";
+const REC_MODEL_FILE: &str = "ch_PP-OCRv4_rec_infer.onnx";
+const REC_MODEL_URL: &str =
+    "https://huggingface.co/SWHL/RapidOCR/resolve/main/PP-OCRv4/ch_PP-OCRv4_rec_infer.onnx";
+/// Character dictionary, only needed when the recognizer does not embed it in its metadata
+const DICT_FILE: &str = "ppocr_keys_v1.txt";
+
+/// Longest side of the image fed to the detector
+const DET_LIMIT_SIDE: u32 = 1600;
+const DET_MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const DET_STD: [f32; 3] = [0.229, 0.224, 0.225];
+/// Probability above which a pixel is considered text
+const DET_THRESH: f32 = 0.3;
+/// Minimum mean probability of a detected region
+const DET_BOX_THRESH: f32 = 0.5;
+/// DBNet predicts shrunk text regions, they are expanded back by this ratio
+const DET_UNCLIP_RATIO: f32 = 1.6;
+const DET_MIN_SIZE: usize = 3;
+
+const REC_HEIGHT: u32 = 48;
+const REC_MIN_WIDTH: u32 = 320;
+const REC_MAX_WIDTH: u32 = 3200;
+const REC_BATCH_SIZE: usize = 8;
+/// Lines recognized with a lower mean confidence are dropped
+const REC_DROP_SCORE: f32 = 0.5;
+
+static ENGINE: OnceCell<Arc<OnnxOcrEngine>> = OnceCell::const_new();
+
+/// Run OCR with the shared ONNX engine, loading (and downloading) the models on first use.
+pub async fn perform_ocr_onnx(image: &DynamicImage) -> Result<(String, String, Option<f64>)> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return Ok(("".to_string(), "[]".to_string(), None));
+    }
+
+    let engine = ENGINE
+        .get_or_try_init(|| async { OnnxOcrEngine::load().await.map(Arc::new) })
+        .await?
+        .clone();
+
+    // Inference is CPU bound, keep it off the async workers
+    let image = image.to_rgb8();
+    tokio::task::spawn_blocking(move || engine.recognize(&image)).await?
+}
+
+/// Text line detected in an image, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TextBox {
+    left: u32,
+    top: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Word recognized inside a [`TextBox`], with its horizontal extent as a fraction of the box.
+#[derive(Debug, Clone, PartialEq)]
+struct RecognizedWord {
+    text: String,
+    start: f32,
+    end: f32,
+    confidence: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct RecognizedLine {
+    words: Vec<RecognizedWord>,
+    confidence: f32,
+}
+
+/// PaddleOCR detection and recognition sessions.
+pub struct OnnxOcrEngine {
+    det: Session,
+    det_output: String,
+    rec: Session,
+    rec_output: String,
+    /// CTC labels, index 0 being the blank label
+    characters: Vec<String>,
+}
+
+impl OnnxOcrEngine {
+    /// Load the models from the screenpipe model cache, downloading them if missing.
+    pub async fn load() -> Result<Self> {
+        let det_path = get_or_download_model(DET_MODEL_FILE, DET_MODEL_URL).await?;
+        let rec_path = get_or_download_model(REC_MODEL_FILE, REC_MODEL_URL).await?;
+        let dict_path = model_dir()?.join(DICT_FILE);
+        let dict_path = dict_path.exists().then_some(dict_path);
+
+        tokio::task::spawn_blocking(move || {
+            Self::from_files(&det_path, &rec_path, dict_path.as_deref())
+        })
+        .await?
+    }
+
+    /// Load the models from explicit paths.
+    ///
+    /// The character dictionary is read from the recognizer metadata (`character` key), or
+    /// from `dict_path` (one character per line) for models that do not embed it.
+    pub fn from_files(det_path: &Path, rec_path: &Path, dict_path: Option<&Path>) -> Result<Self> {
+        let det = create_session(det_path)
+            .with_context(|| format!("failed to load detection model {:?}", det_path))?;
+        let rec = create_session(rec_path)
+            .with_context(|| format!("failed to load recognition model {:?}", rec_path))?;
+
+        let dictionary = match rec.metadata()?.custom("character")? {
+            Some(characters) => characters,
+            None => {
+                let path = dict_path.ok_or_else(|| {
+                    anyhow!(
+                        "recognition model has no embedded dictionary, {} required",
+                        DICT_FILE
+                    )
+                })?;
+                std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read dictionary {:?}", path))?
+            }
+        };
+        let characters = ctc_labels(&dictionary);
+
+        let det_output = det
+            .outputs
+            .first()
+            .map(|o| o.name.clone())
+            .ok_or_else(|| anyhow!("detection model has no output"))?;
+        let rec_output = rec
+            .outputs
+            .first()
+            .map(|o| o.name.clone())
+            .ok_or_else(|| anyhow!("recognition model has no output"))?;
+
+        info!(
+            "onnx ocr models loaded ({} characters)",
+            characters.len() - 1
+        );
+        Ok(Self {
+            det,
+            det_output,
+            rec,
+            rec_output,
+            characters,
+        })
+    }
+
+    /// Run detection and recognition, returning `(text, text_json, confidence)`.
+    ///
+    /// `text_json` holds one block per word, with coordinates normalized to the image size.
+    pub fn recognize(&self, image: &RgbImage) -> Result<(String, String, Option<f64>)> {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Ok(("".to_string(), "[]".to_string(), None));
+        }
+
+        let boxes = self.detect(image)?;
+        let lines = self.recognize_boxes(image, &boxes)?;
+        debug!(
+            "onnx ocr: {} regions detected, {} lines recognized",
+            boxes.len(),
+            lines.iter().filter(|l| l.is_some()).count()
+        );
+
+        let recognized: Vec<(TextBox, RecognizedLine)> = boxes
+            .into_iter()
+            .zip(lines)
+            .filter_map(|(text_box, line)| line.map(|line| (text_box, line)))
+            .collect();
+
+        Ok(format_output(&recognized, width, height))
+    }
+
+    fn detect(&self, image: &RgbImage) -> Result<Vec<TextBox>> {
+        let (width, height) = image.dimensions();
+        let (input_width, input_height) = det_input_size(width, height);
+        let resized =
+            image::imageops::resize(image, input_width, input_height, FilterType::Triangle);
+
+        let mut input = Array4::<f32>::zeros((1, 3, input_height as usize, input_width as usize));
+        write_normalized(&resized, &mut input, 0, DET_MEAN, DET_STD);
+
+        let outputs = self.det.run(ort::inputs![input.view()]?)?;
+        let prob_map = outputs
+            .get(self.det_output.as_str())
+            .context("detection output not found")?
+            .try_extract_tensor::<f32>()?;
+        let prob_map: Vec<f32> = prob_map.iter().copied().collect();
+        if prob_map.len() != (input_width * input_height) as usize {
+            return Err(anyhow!(
+                "unexpected detection output size {}",
+                prob_map.len()
+            ));
+        }
+
+        let scale_x = width as f32 / input_width as f32;
+        let scale_y = height as f32 / input_height as f32;
+        let mut boxes: Vec<TextBox> =
+            boxes_from_prob_map(&prob_map, input_width as usize, input_height as usize)
+                .into_iter()
+                .filter_map(|[x0, y0, x1, y1]| {
+                    let left = (x0 * scale_x).floor().max(0.0) as u32;
+                    let top = (y0 * scale_y).floor().max(0.0) as u32;
+                    let right = ((x1 * scale_x).ceil() as u32).min(width);
+                    let bottom = ((y1 * scale_y).ceil() as u32).min(height);
+                    (right > left && bottom > top).then(|| TextBox {
+                        left,
+                        top,
+                        width: right - left,
+                        height: bottom - top,
+                    })
+                })
+                .collect();
+
+        sort_reading_order(&mut boxes);
+        Ok(boxes)
+    }
+
+    fn recognize_boxes(
+        &self,
+        image: &RgbImage,
+        boxes: &[TextBox],
+    ) -> Result<Vec<Option<RecognizedLine>>> {
+        let mut results = vec![None; boxes.len()];
+
+        // Batch crops of similar aspect ratio together to limit padding
+        let mut order: Vec<usize> = (0..boxes.len()).collect();
+        order.sort_by(|&a, &b| aspect_ratio(&boxes[a]).total_cmp(&aspect_ratio(&boxes[b])));
+
+        for batch in order.chunks(REC_BATCH_SIZE) {
+            let max_ratio = batch
+                .iter()
+                .map(|&i| aspect_ratio(&boxes[i]))
+                .fold(0.0f32, f32::max);
+            let batch_width =
+                ((REC_HEIGHT as f32 * max_ratio).ceil() as u32).clamp(REC_MIN_WIDTH, REC_MAX_WIDTH);
+
+            let mut input =
+                Array4::<f32>::zeros((batch.len(), 3, REC_HEIGHT as usize, batch_width as usize));
+            let mut crop_widths = Vec::with_capacity(batch.len());
+            for (n, &i) in batch.iter().enumerate() {
+                let b = boxes[i];
+                let crop_width =
+                    ((REC_HEIGHT as f32 * aspect_ratio(&b)).ceil() as u32).clamp(1, batch_width);
+                let crop =
+                    image::imageops::crop_imm(image, b.left, b.top, b.width, b.height).to_image();
+                let crop =
+                    image::imageops::resize(&crop, crop_width, REC_HEIGHT, FilterType::Triangle);
+                write_normalized(&crop, &mut input, n, [0.5; 3], [0.5; 3]);
+                crop_widths.push(crop_width);
+            }
+
+            let outputs = self.rec.run(ort::inputs![input.view()]?)?;
+            let probs = outputs
+                .get(self.rec_output.as_str())
+                .context("recognition output not found")?
+                .try_extract_tensor::<f32>()?;
+            let shape = probs.shape().to_vec();
+            if shape.len() != 3 || shape[0] != batch.len() {
+                return Err(anyhow!("unexpected recognition output shape {:?}", shape));
+            }
+            let (steps, classes) = (shape[1], shape[2]);
+            if classes != self.characters.len() {
+                return Err(anyhow!(
+                    "recognition model has {} classes but dictionary has {} labels",
+                    classes,
+                    self.characters.len()
+                ));
+            }
+
+            // Width of the input covered by one CTC step
+            let step_width = batch_width as f32 / steps as f32;
+            for (n, &i) in batch.iter().enumerate() {
+                let sequence: Vec<f32> = probs.slice(s![n, .., ..]).iter().copied().collect();
+                let decoded = ctc_greedy_decode(&sequence, steps, classes);
+                results[i] = build_line(
+                    &decoded,
+                    &self.characters,
+                    step_width / crop_widths[n] as f32,
+                );
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+fn create_session(path: &Path) -> Result<Session> {
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get().min(4))
+        .unwrap_or(1);
+    let session = Session::builder()?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .with_intra_threads(threads)?
+        .with_inter_threads(1)?
+        .commit_from_file(path)?;
+    Ok(session)
+}
+
+fn model_dir() -> Result<PathBuf> {
+    let cache_dir = dirs::cache_dir().ok_or_else(|| anyhow!("failed to get cache dir"))?;
+    Ok(cache_dir.join("screenpipe").join("models").join("ocr"))
+}
+
+async fn get_or_download_model(filename: &str, url: &str) -> Result<PathBuf> {
+    let dir = model_dir()?;
+    let path = dir.join(filename);
+    if path.exists() {
+        debug!("found existing ocr model at: {:?}", path);
+        return Ok(path);
+    }
+
+    info!("downloading {} model from {}", filename, url);
+    tokio::fs::create_dir_all(&dir).await?;
+    let data = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+
+    // Write then rename so an interrupted download is never picked up
+    let tmp = dir.join(format!("{}.download", filename));
+    tokio::fs::write(&tmp, &data).await?;
+    tokio::fs::rename(&tmp, &path).await?;
+    info!(
+        "{} model ({} bytes) saved to {:?}",
+        filename,
+        data.len(),
+        path
+    );
+    Ok(path)
+}
+
+/// CTC labels from a dictionary: blank, the dictionary characters, then space.
+fn ctc_labels(dictionary: &str) -> Vec<String> {
+    let mut labels = vec![String::new()];
+    labels.extend(
+        dictionary
+            .lines()
+            .map(|line| line.trim_end_matches('\r').to_string()),
+    );
+    labels.push(" ".to_string());
+    labels
+}
+
+/// Detector input size: longest side capped at [`DET_LIMIT_SIDE`], both sides multiples of 32.
+fn det_input_size(width: u32, height: u32) -> (u32, u32) {
+    let longest = width.max(height) as f32;
+    let ratio = if longest > DET_LIMIT_SIDE as f32 {
+        DET_LIMIT_SIDE as f32 / longest
+    } else {
+        1.0
+    };
+    let round = |side: u32| (((side as f32 * ratio) / 32.0).round() as u32 * 32).max(32);
+    (round(width), round(height))
+}
+
+fn aspect_ratio(text_box: &TextBox) -> f32 {
+    text_box.width as f32 / text_box.height.max(1) as f32
+}
+
+/// Write `image` into `tensor[index]` as normalized BGR, the channel order PaddleOCR models
+/// are trained on. Pixels beyond the image are left untouched (zero padding).
+fn write_normalized(
+    image: &RgbImage,
+    tensor: &mut Array4<f32>,
+    index: usize,
+    mean: [f32; 3],
+    std: [f32; 3],
+) {
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let bgr = [pixel[2], pixel[1], pixel[0]];
+        for (c, ((value, mean), std)) in bgr.iter().zip(mean).zip(std).enumerate() {
+            tensor[[index, c, y as usize, x as usize]] = (*value as f32 / 255.0 - mean) / std;
+        }
+    }
+}
+
+/// Extract text regions from a detection probability map, as `[x0, y0, x1, y1]` rectangles
+/// in map coordinates, expanded to compensate for DBNet shrinking.
+fn boxes_from_prob_map(prob_map: &[f32], width: usize, height: usize) -> Vec<[f32; 4]> {
+    let mut visited = vec![false; prob_map.len()];
+    let mut stack = Vec::new();
+    let mut boxes = Vec::new();
+
+    for start in 0..prob_map.len() {
+        if visited[start] || prob_map[start] <= DET_THRESH {
+            continue;
+        }
+
+        // Flood fill the connected region
+        visited[start] = true;
+        stack.push(start);
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (usize::MAX, usize::MAX, 0, 0);
+        let mut score_sum = 0.0;
+        let mut count = 0usize;
+
+        while let Some(idx) = stack.pop() {
+            let (x, y) = (idx % width, idx / width);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+            score_sum += prob_map[idx];
+            count += 1;
+
+            let mut visit = |n: usize| {
+                if !visited[n] && prob_map[n] > DET_THRESH {
+                    visited[n] = true;
+                    stack.push(n);
+                }
+            };
+            if x > 0 {
+                visit(idx - 1);
+            }
+            if x + 1 < width {
+                visit(idx + 1);
+            }
+            if y > 0 {
+                visit(idx - width);
+            }
+            if y + 1 < height {
+                visit(idx + width);
+            }
+        }
+
+        let (box_width, box_height) = (max_x - min_x + 1, max_y - min_y + 1);
+        if box_width.min(box_height) < DET_MIN_SIZE || score_sum / (count as f32) < DET_BOX_THRESH {
+            continue;
+        }
+
+        // Same expansion as PaddleOCR's unclip: offset = area * ratio / perimeter
+        let area = (box_width * box_height) as f32;
+        let perimeter = 2.0 * (box_width + box_height) as f32;
+        let offset = area * DET_UNCLIP_RATIO / perimeter;
+        boxes.push([
+            (min_x as f32 - offset).max(0.0),
+            (min_y as f32 - offset).max(0.0),
+            ((max_x + 1) as f32 + offset).min(width as f32),
+            ((max_y + 1) as f32 + offset).min(height as f32),
+        ]);
+    }
+
+    boxes
+}
+
+/// Whether two boxes sit on the same text line.
+fn same_line(a: &TextBox, b: &TextBox) -> bool {
+    let tolerance = a.height.min(b.height) / 2;
+    a.top.abs_diff(b.top) <= tolerance
+}
+
+/// Sort boxes top to bottom, then left to right within a line.
+fn sort_reading_order(boxes: &mut [TextBox]) {
+    boxes.sort_by_key(|b| (b.top, b.left));
+    for i in 1..boxes.len() {
+        let mut j = i;
+        while j > 0 && same_line(&boxes[j - 1], &boxes[j]) && boxes[j].left < boxes[j - 1].left {
+            boxes.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+/// Greedy CTC decoding of a `steps x classes` probability matrix.
+///
+/// Returns `(class, step, probability)` for each emitted label, skipping blanks (class 0)
+/// and repeated labels.
+fn ctc_greedy_decode(probs: &[f32], steps: usize, classes: usize) -> Vec<(usize, usize, f32)> {
+    let mut decoded = Vec::new();
+    let mut previous = 0;
+    for step in 0..steps {
+        let row = &probs[step * classes..(step + 1) * classes];
+        let (class, prob) = row
+            .iter()
+            .copied()
+            .enumerate()
+            .fold(
+                (0, f32::MIN),
+                |best, (i, p)| if p > best.1 { (i, p) } else { best },
+            );
+        if class != 0 && class != previous {
+            decoded.push((class, step, prob));
+        }
+        previous = class;
+    }
+    decoded
+}
+
+/// Split decoded labels into words, locating each word with the CTC steps that emitted it.
+///
+/// `step_fraction` is the share of the crop width covered by one CTC step.
+fn build_line(
+    decoded: &[(usize, usize, f32)],
+    characters: &[String],
+    step_fraction: f32,
+) -> Option<RecognizedLine> {
+    let mut words = Vec::new();
+    let mut current: Option<(String, usize, usize, f32, usize)> = None;
+    let mut total_prob = 0.0;
+
+    for &(class, step, prob) in decoded {
+        total_prob += prob;
+        let character = characters.get(class).map(String::as_str).unwrap_or("");
+        if character.trim().is_empty() {
+            if let Some(word) = current.take() {
+                words.push(word);
+            }
+            continue;
+        }
+        match current.as_mut() {
+            Some((text, _, end, prob_sum, count)) => {
+                text.push_str(character);
+                *end = step;
+                *prob_sum += prob;
+                *count += 1;
+            }
+            None => current = Some((character.to_string(), step, step, prob, 1)),
+        }
+    }
+    if let Some(word) = current.take() {
+        words.push(word);
+    }
+
+    if words.is_empty() {
+        return None;
+    }
+    let confidence = total_prob / decoded.len() as f32;
+    if confidence < REC_DROP_SCORE {
+        return None;
+    }
+
+    Some(RecognizedLine {
+        words: words
+            .into_iter()
+            .map(|(text, start, end, prob_sum, count)| RecognizedWord {
+                text,
+                start: (start as f32 * step_fraction).clamp(0.0, 1.0),
+                end: ((end + 1) as f32 * step_fraction).clamp(0.0, 1.0),
+                confidence: prob_sum / count as f32,
+            })
+            .collect(),
+        confidence,
+    })
+}
+
+/// Build `(text, text_json, confidence)` in the same block format as the other engines.
+///
+/// Confidences use Tesseract's 0-100 scale.
+fn format_output(
+    lines: &[(TextBox, RecognizedLine)],
+    image_width: u32,
+    image_height: u32,
+) -> (String, String, Option<f64>) {
+    let (image_width, image_height) = (image_width as f32, image_height as f32);
+    let mut text = String::new();
+    let mut blocks: Vec<HashMap<&str, String>> = Vec::new();
+    let mut line_num = 0;
+    let mut previous: Option<&TextBox> = None;
+
+    for (text_box, line) in lines {
+        let new_line = !matches!(previous, Some(p) if same_line(p, text_box));
+        if new_line {
+            line_num += 1;
+        }
+        if !text.is_empty() {
+            text.push(if new_line { '\n' } else { ' ' });
+        }
+        previous = Some(text_box);
+
+        for (word_num, word) in line.words.iter().enumerate() {
+            if word_num > 0 {
+                text.push(' ');
+            }
+            text.push_str(&word.text);
+
+            let left = text_box.left as f32 + word.start * text_box.width as f32;
+            let width = (word.end - word.start) * text_box.width as f32;
+            blocks.push(HashMap::from([
+                ("text", word.text.clone()),
+                ("conf", format!("{:.2}", word.confidence * 100.0)),
+                ("left", (left / image_width).to_string()),
+                ("top", (text_box.top as f32 / image_height).to_string()),
+                ("width", (width / image_width).to_string()),
+                (
+                    "height",
+                    (text_box.height as f32 / image_height).to_string(),
+                ),
+                ("level", "5".to_string()),
+                ("page_num", "1".to_string()),
+                ("block_num", "1".to_string()),
+                ("par_num", "1".to_string()),
+                ("line_num", line_num.to_string()),
+                ("word_num", (word_num + 1).to_string()),
+            ]));
+        }
+    }
+
+    let confidence = if lines.is_empty() {
+        None
+    } else {
+        let sum: f32 = lines.iter().map(|(_, line)| line.confidence).sum();
+        Some((sum / lines.len() as f32 * 100.0) as f64)
+    };
+    let json_output = serde_json::to_string(&blocks).unwrap_or_else(|_| "[]".to_string());
+
+    (text, json_output, confidence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_box(left: u32, top: u32, width: u32, height: u32) -> TextBox {
+        TextBox {
+            left,
+            top,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn test_det_input_size() {
+        assert_eq!(det_input_size(100, 50), (96, 64));
+        assert_eq!(det_input_size(10, 10), (32, 32));
+        assert_eq!(det_input_size(3200, 1800), (1600, 896));
+    }
+
+    #[test]
+    fn test_boxes_from_prob_map() {
+        // Two separate regions on a 12x6 map, plus a single noisy pixel
+        let (width, height) = (12, 6);
+        let mut map = vec![0.0; width * height];
+        for y in 1..4 {
+            for x in 1..5 {
+                map[y * width + x] = 0.9;
+            }
+            for x in 7..11 {
+                map[y * width + x] = 0.8;
+            }
+        }
+        map[5 * width + 11] = 0.95;
+
+        let boxes = boxes_from_prob_map(&map, width, height);
+        assert_eq!(boxes.len(), 2);
+        let [x0, y0, x1, y1] = boxes[0];
+        assert!(x0 < 1.0 && y0 < 1.0 && x1 > 5.0 && y1 > 4.0);
+        assert!(boxes[1][0] < 7.0 && boxes[1][2] <= width as f32);
+    }
+
+    #[test]
+    fn test_sort_reading_order() {
+        let mut boxes = vec![
+            text_box(300, 52, 80, 20),
+            text_box(10, 10, 80, 20),
+            text_box(10, 50, 80, 20),
+            text_box(200, 8, 80, 20),
+        ];
+        sort_reading_order(&mut boxes);
+        let order: Vec<(u32, u32)> = boxes.iter().map(|b| (b.left, b.top)).collect();
+        assert_eq!(order, vec![(10, 10), (200, 8), (10, 50), (300, 52)]);
+    }
+
+    #[test]
+    fn test_ctc_decode_and_words() {
+        let characters = ctc_labels("a\nb\nc");
+        assert_eq!(characters.len(), 5);
+        let space = 4;
+
+        // Steps: a a blank b space blank c c
+        let classes = characters.len();
+        let sequence = [1, 1, 0, 2, space, 0, 3, 3];
+        let mut probs = vec![0.0; sequence.len() * classes];
+        for (step, &class) in sequence.iter().enumerate() {
+            probs[step * classes + class] = 0.9;
+        }
+
+        let decoded = ctc_greedy_decode(&probs, sequence.len(), classes);
+        let labels: Vec<usize> = decoded.iter().map(|d| d.0).collect();
+        assert_eq!(labels, vec![1, 2, space, 3]);
+
+        let line = build_line(&decoded, &characters, 0.125).unwrap();
+        let words: Vec<&str> = line.words.iter().map(|w| w.text.as_str()).collect();
+        assert_eq!(words, vec!["ab", "c"]);
+        assert_eq!(line.words[0].start, 0.0);
+        assert_eq!(line.words[0].end, 0.5);
+        assert_eq!(line.words[1].start, 0.75);
+        assert_eq!(line.words[1].end, 0.875);
+    }
+
+    #[test]
+    fn test_low_confidence_line_dropped() {
+        let characters = ctc_labels("a");
+        assert!(build_line(&[(1, 0, 0.2)], &characters, 0.1).is_none());
+        assert!(build_line(&[], &characters, 0.1).is_none());
+    }
+
+    #[test]
+    fn test_format_output() {
+        let lines = vec![
+            (
+                text_box(0, 0, 100, 20),
+                RecognizedLine {
+                    words: vec![
+                        RecognizedWord {
+                            text: "fn".to_string(),
+                            start: 0.0,
+                            end: 0.2,
+                            confidence: 0.9,
+                        },
+                        RecognizedWord {
+                            text: "main()".to_string(),
+                            start: 0.25,
+                            end: 0.75,
+                            confidence: 0.8,
+                        },
+                    ],
+                    confidence: 0.85,
+                },
+            ),
+            (
+                text_box(0, 40, 50, 20),
+                RecognizedLine {
+                    words: vec![RecognizedWord {
+                        text: "}".to_string(),
+                        start: 0.0,
+                        end: 0.5,
+                        confidence: 0.95,
+                    }],
+                    confidence: 0.95,
+                },
+            ),
+        ];
+
+        let (text, json, confidence) = format_output(&lines, 200, 100);
+        assert_eq!(text, "fn main()\n}");
+        assert!((confidence.unwrap() - 90.0).abs() < 0.01);
+
+        let blocks: Vec<HashMap<String, String>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[1]["text"], "main()");
+        assert_eq!(blocks[1]["left"], "0.125");
+        assert_eq!(blocks[1]["width"], "0.25");
+        assert_eq!(blocks[1]["conf"], "80.00");
+        assert_eq!(blocks[2]["line_num"], "2");
+        assert_eq!(blocks[2]["top"], "0.4");
+    }
+}
@@ -0,0 +1,314 @@
+//! Pixel-level PII redaction of captured frames
+//!
+//! With capture-time redaction, `process_ocr_task` blurs every OCR block that contains PII
+//! before the frame is sent on, so the unredacted pixels never reach the video encoder.
+//! Blocks are placed on the screen from the OCR of each window image, as returned by the
+//! engine, and where that window was captured.
+
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use imageproc::filter::gaussian_blur_f32;
+use screenpipe_core::pii_removal::{
+    get_pii_type, remove_pii, remove_pii_from_text_json, PiiRegion,
+};
+use std::collections::HashMap;
+
+use crate::capture_screenshot_by_window::CapturedWindow;
+use crate::core::WindowOcrResult;
+use crate::ocr_backend::BlockCoordinates;
+
+/// Sigma of the gaussian blur, strong enough that blurred text can't be read back.
+const BLUR_SIGMA: f32 = 10.0;
+
+/// Pixels blurred around each block, OCR boxes are tight around the glyphs
+const PADDING: f64 = 5.0;
+
+/// Where a window image lies on the screen image.
+#[derive(Debug, Clone, Copy)]
+pub struct WindowPlacement {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Size of the window image, the OCR input
+    pub image_width: u32,
+    pub image_height: u32,
+}
+
+impl WindowPlacement {
+    pub fn of(window: &CapturedWindow) -> Self {
+        Self {
+            x: window.window_x,
+            y: window.window_y,
+            width: window.window_width,
+            height: window.window_height,
+            image_width: window.image.width(),
+            image_height: window.image.height(),
+        }
+    }
+}
+
+/// Screen regions of the OCR `blocks` of a window that contain PII.
+///
+/// `blocks` are the text blocks of the engine for the window image, in `coordinates`.
+pub fn window_pii_regions(
+    blocks: &[HashMap<String, String>],
+    coordinates: BlockCoordinates,
+    window: WindowPlacement,
+) -> Vec<PiiRegion> {
+    if window.image_width == 0 || window.image_height == 0 {
+        return Vec::new();
+    }
+    let image_width = window.image_width as f64;
+    let image_height = window.image_height as f64;
+
+    let mut regions = Vec::new();
+    for block in blocks {
+        let Some(pii_type) = block.get("text").and_then(|text| get_pii_type(text)) else {
+            continue;
+        };
+        let value = |key: &str| block.get(key).and_then(|v| v.parse::<f64>().ok());
+        let (Some(left), Some(top), Some(width), Some(height)) =
+            (value("left"), value("top"), value("width"), value("height"))
+        else {
+            continue;
+        };
+
+        // as fractions of the window image, from its top-left corner
+        let (left, top, width, height) = match coordinates {
+            BlockCoordinates::Pixels => (
+                left / image_width,
+                top / image_height,
+                width / image_width,
+                height / image_height,
+            ),
+            BlockCoordinates::Normalized => (left, top, width, height),
+            BlockCoordinates::NormalizedBottomLeft => (left, 1.0 - top - height, width, height),
+        };
+        let x = window.x as f64 + left * window.width as f64;
+        let y = window.y as f64 + top * window.height as f64;
+        let right = x + width * window.width as f64;
+        let bottom = y + height * window.height as f64;
+
+        // windows can stick out of the screen, `as` saturates at 0
+        let x = (x - PADDING).floor() as u32;
+        let y = (y - PADDING).floor() as u32;
+        let right = (right + PADDING).ceil() as u32;
+        let bottom = (bottom + PADDING).ceil() as u32;
+        if right <= x || bottom <= y {
+            continue;
+        }
+        regions.push(PiiRegion {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+            pii_type,
+        });
+    }
+    regions
+}
+
+/// Blur `regions` of `image` in place, regions are clamped to the image.
+pub fn blur_regions(image: &mut RgbaImage, regions: &[PiiRegion]) {
+    let (img_width, img_height) = image.dimensions();
+
+    for region in regions {
+        if region.x >= img_width || region.y >= img_height {
+            continue;
+        }
+        let x = region.x;
+        let y = region.y;
+        let w = region.width.min(img_width - x);
+        let h = region.height.min(img_height - y);
+        if w == 0 || h == 0 {
+            continue;
+        }
+
+        let blurred = gaussian_blur_f32(&image.view(x, y, w, h).to_image(), BLUR_SIGMA);
+        for (dx, dy, pixel) in blurred.enumerate_pixels() {
+            image.put_pixel(x + dx, y + dy, *pixel);
+        }
+    }
+}
+
+/// Blur the `pii_redactions` of each window out of the screen `image`, and remove the PII
+/// from the OCR text of those windows.
+///
+/// Returns whether anything was redacted.
+pub fn redact_capture(image: &mut DynamicImage, windows: &mut [WindowOcrResult]) -> bool {
+    let regions: Vec<PiiRegion> = windows
+        .iter()
+        .flat_map(|w| w.pii_redactions.iter().cloned())
+        .collect();
+    if regions.is_empty() {
+        return false;
+    }
+
+    let mut rgba = image.to_rgba8();
+    blur_regions(&mut rgba, &regions);
+    *image = DynamicImage::ImageRgba8(rgba);
+
+    for window in windows.iter_mut().filter(|w| !w.pii_redactions.is_empty()) {
+        window.text = remove_pii(&window.text);
+        window.text_json = remove_pii_from_text_json(&window.text_json);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn block(text: &str, left: f64, top: f64, width: f64, height: f64) -> HashMap<String, String> {
+        HashMap::from([
+            ("text".to_string(), text.to_string()),
+            ("left".to_string(), left.to_string()),
+            ("top".to_string(), top.to_string()),
+            ("width".to_string(), width.to_string()),
+            ("height".to_string(), height.to_string()),
+        ])
+    }
+
+    /// A window covering the 200x100 screen of `striped_image`
+    fn window(text: &str, left: u32, top: u32) -> WindowOcrResult {
+        let block = block(text, left as f64, top as f64, 40.0, 10.0);
+        let placement = WindowPlacement {
+            x: 0,
+            y: 0,
+            width: 200,
+            height: 100,
+            image_width: 200,
+            image_height: 100,
+        };
+        WindowOcrResult {
+            pii_redactions: window_pii_regions(
+                &[block.clone()],
+                BlockCoordinates::Pixels,
+                placement,
+            ),
+            image: DynamicImage::new_rgba8(1, 1),
+            window_name: "terminal".to_string(),
+            app_name: "Terminal".to_string(),
+            text: text.to_string(),
+            text_json: vec![block],
+            focused: true,
+            confidence: 1.0,
+            browser_url: None,
+        }
+    }
+
+    /// Black and white stripes, blurring turns them gray
+    fn striped_image() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(200, 100, |x, _| {
+            if x % 2 == 0 {
+                Rgba([0, 0, 0, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        }))
+    }
+
+    #[test]
+    fn test_redact_capture() {
+        let mut image = striped_image();
+        let original = image.to_rgba8();
+        let mut windows = vec![
+            window(
+                "export OPENAI_API_KEY=sk-proj-abcdefghijklmnopqrstuvwx",
+                20,
+                20,
+            ),
+            window("cargo build --release", 20, 60),
+        ];
+
+        assert!(redact_capture(&mut image, &mut windows));
+
+        assert_eq!(windows[0].pii_redactions.len(), 1);
+        assert!(!windows[0].text.contains("sk-proj-"));
+        assert!(!windows[0].text_json[0]["text"].contains("sk-proj-"));
+        assert!(windows[1].pii_redactions.is_empty());
+        assert_eq!(windows[1].text, "cargo build --release");
+
+        let redacted = image.to_rgba8();
+        // inside the secret
+        assert_ne!(redacted.get_pixel(30, 25), original.get_pixel(30, 25));
+        // the other window's text
+        assert_eq!(redacted.get_pixel(30, 65), original.get_pixel(30, 65));
+    }
+
+    #[test]
+    fn test_window_pii_regions() {
+        // a retina window image (2x) at 100,50 of the screen
+        let placement = WindowPlacement {
+            x: 100,
+            y: 50,
+            width: 400,
+            height: 400,
+            image_width: 800,
+            image_height: 800,
+        };
+        let email = "jane@example.com";
+        let expected = vec![PiiRegion {
+            x: 145,
+            y: 95,
+            width: 110,
+            height: 35,
+            pii_type: "EMAIL".to_string(),
+        }];
+        for (coordinates, block) in [
+            (
+                BlockCoordinates::Pixels,
+                block(email, 100.0, 100.0, 200.0, 50.0),
+            ),
+            (
+                BlockCoordinates::Normalized,
+                block(email, 0.125, 0.125, 0.25, 0.0625),
+            ),
+            (
+                BlockCoordinates::NormalizedBottomLeft,
+                block(email, 0.125, 0.8125, 0.25, 0.0625),
+            ),
+        ] {
+            let regions = window_pii_regions(&[block], coordinates, placement);
+            assert_eq!(regions, expected, "{:?}", coordinates);
+        }
+
+        let blocks = [block("hello world", 100.0, 100.0, 200.0, 50.0)];
+        assert!(window_pii_regions(&blocks, BlockCoordinates::Pixels, placement).is_empty());
+    }
+
+    #[test]
+    fn test_redact_capture_without_pii() {
+        let mut image = striped_image();
+        let mut windows = vec![window("hello world", 20, 20)];
+
+        assert!(!redact_capture(&mut image, &mut windows));
+        assert_eq!(image.to_rgba8(), striped_image().to_rgba8());
+    }
+
+    #[test]
+    fn test_blur_regions_clamps_to_image() {
+        let mut image = striped_image().to_rgba8();
+        blur_regions(
+            &mut image,
+            &[
+                PiiRegion {
+                    x: 190,
+                    y: 90,
+                    width: 50,
+                    height: 50,
+                    pii_type: "EMAIL".to_string(),
+                },
+                PiiRegion {
+                    x: 300,
+                    y: 0,
+                    width: 10,
+                    height: 10,
+                    pii_type: "EMAIL".to_string(),
+                },
+            ],
+        );
+        assert_eq!(image.dimensions(), (200, 100));
+    }
+}
@@ -19,6 +19,8 @@ pub enum OcrEngine {
     WindowsNative,
     AppleNative,
     Custom(CustomOcrConfig),
+    /// PaddleOCR models on ONNX Runtime
+    Onnx,
 }
 
 impl From<OcrEngine> for screenpipe_db::OcrEngine {
@@ -31,6 +33,7 @@ impl From<OcrEngine> for screenpipe_db::OcrEngine {
             OcrEngine::Custom(config) => {
                 screenpipe_db::OcrEngine::Custom(DBCustomOcrConfig::from(config))
             }
+            OcrEngine::Onnx => screenpipe_db::OcrEngine::Onnx,
         }
     }
 }
@@ -43,6 +46,7 @@ impl From<screenpipe_db::OcrEngine> for OcrEngine {
             screenpipe_db::OcrEngine::WindowsNative => OcrEngine::WindowsNative,
             screenpipe_db::OcrEngine::AppleNative => OcrEngine::AppleNative,
             screenpipe_db::OcrEngine::Custom(config) => OcrEngine::Custom(config.into()),
+            screenpipe_db::OcrEngine::Onnx => OcrEngine::Onnx,
         }
     }
 }
@@ -0,0 +1,121 @@
+//! Capture-time PII redaction of a window that doesn't cover the screen
+//!
+//! The OCR results come from the window cache, so no engine runs; the engine only decides
+//! how the cached text blocks are placed on the window image.
+//!
+//! Run with: cargo test -p screenpipe-vision --test pii_redaction_test
+
+use chrono::Utc;
+use image::{DynamicImage, Rgba, RgbaImage};
+use screenpipe_core::pii_removal::PiiRegion;
+use screenpipe_vision::capture_screenshot_by_window::CapturedWindow;
+use screenpipe_vision::core::OcrTaskData;
+use screenpipe_vision::custom_ocr::CustomOcrConfig;
+use screenpipe_vision::ocr_cache::{WindowCacheKey, WindowOcrCache};
+use screenpipe_vision::{process_ocr_task, OcrEngine};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{mpsc, Mutex};
+
+/// Black and white stripes, blurring turns them gray
+fn striped_screen() -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(400, 300, |x, _| {
+        if x % 2 == 0 {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    }))
+}
+
+/// A 100x100 window at 200,100 of the screen, captured at twice its size
+fn window() -> CapturedWindow {
+    CapturedWindow {
+        image: DynamicImage::new_rgba8(200, 200),
+        app_name: "Mail".to_string(),
+        window_name: "inbox".to_string(),
+        process_id: 1234,
+        is_focused: true,
+        browser_url: None,
+        window_x: 200,
+        window_y: 100,
+        window_width: 100,
+        window_height: 100,
+    }
+}
+
+#[tokio::test]
+async fn test_redact_window_pii() {
+    let text = "mail jane@example.com";
+    // the same box, in pixels and in fractions of the window image
+    for (engine, (left, top, width, height)) in [
+        (
+            OcrEngine::Custom(CustomOcrConfig::default()),
+            ("20", "40", "120", "20"),
+        ),
+        (OcrEngine::Onnx, ("0.1", "0.2", "0.6", "0.1")),
+    ] {
+        let window = window();
+        let block = serde_json::json!([{
+            "text": text,
+            "conf": "90",
+            "left": left,
+            "top": top,
+            "width": width,
+            "height": height,
+        }]);
+        let mut cache = WindowOcrCache::default();
+        cache.insert(
+            WindowCacheKey {
+                window_id: WindowOcrCache::make_window_id(&window.app_name, &window.window_name),
+                image_hash: WindowOcrCache::calculate_image_hash(window.image.as_bytes()),
+            },
+            text.to_string(),
+            block.to_string(),
+            0.9,
+        );
+
+        let (result_tx, mut result_rx) = mpsc::channel(1);
+        process_ocr_task(
+            OcrTaskData {
+                image: striped_screen(),
+                window_images: vec![window],
+                frame_number: 1,
+                timestamp: Instant::now(),
+                captured_at: Utc::now(),
+                result_tx,
+                redact_pii: true,
+            },
+            &engine,
+            vec![],
+            Arc::new(Mutex::new(cache)),
+        )
+        .await
+        .unwrap();
+        let result = result_rx.recv().await.unwrap();
+
+        let window = &result.window_ocr_results[0];
+        // 210,120 to 270,130 on the screen, padded by 5
+        assert_eq!(
+            window.pii_redactions,
+            vec![PiiRegion {
+                x: 205,
+                y: 115,
+                width: 70,
+                height: 20,
+                pii_type: "EMAIL".to_string(),
+            }],
+            "{:?}",
+            engine
+        );
+        assert!(!window.text.contains("jane@example.com"));
+
+        let original = striped_screen().to_rgba8();
+        let redacted = result.image.to_rgba8();
+        // inside the email
+        assert_ne!(redacted.get_pixel(240, 125), original.get_pixel(240, 125));
+        // outside the window
+        assert_eq!(redacted.get_pixel(40, 125), original.get_pixel(40, 125));
+        assert_eq!(redacted.get_pixel(240, 25), original.get_pixel(240, 25));
+    }
+}
@@ -51,6 +51,7 @@ mod tests {
                 timestamp,
                 captured_at: Utc::now(),
                 result_tx: tx,
+                redact_pii: false,
             },
             &ocr_engine,
             vec![],
@@ -44,11 +44,20 @@ export interface ScreenpipeClientConfig {
   baseUrl?: string;
   /** Base URL for the Tauri sidecar (notifications, etc.) (default: http://localhost:11435) */
   notificationUrl?: string;
+  /** API token, required when the server runs with --enable-auth (default: $SCREENPIPE_API_TOKEN, set for pipes) */
+  apiToken?: string;
 }
 
 const DEFAULT_BASE_URL = "http://localhost:3030";
 const DEFAULT_NOTIFICATION_URL = "http://localhost:11435";
 
+// pipes get their token in the environment, browsers have no `process`
+function defaultApiToken(): string | undefined {
+  const env = (globalThis as { process?: { env?: Record<string, string | undefined> } })
+    .process?.env;
+  return env?.SCREENPIPE_API_TOKEN;
+}
+
 // ─── Helper: coerce object to Record ─────────────────────────────────────────
 
 // eslint-disable-next-line @typescript-eslint/no-explicit-any
@@ -79,20 +88,26 @@ function buildQueryString(params: Record<string, unknown>): string {
 export class ScreenpipeClient {
   private baseUrl: string;
   private notificationUrl: string;
+  private apiToken?: string;
 
   constructor(config?: ScreenpipeClientConfig) {
     this.baseUrl = (config?.baseUrl ?? DEFAULT_BASE_URL).replace(/\/+$/, "");
     this.notificationUrl = (
       config?.notificationUrl ?? DEFAULT_NOTIFICATION_URL
     ).replace(/\/+$/, "");
+    this.apiToken = config?.apiToken ?? defaultApiToken();
   }
 
   // ── Internal helpers ─────────────────────────────────────────────────────
 
+  private authHeaders(): Record<string, string> {
+    return this.apiToken ? { Authorization: `Bearer ${this.apiToken}` } : {};
+  }
+
   private async get<T>(path: string, query?: Record<string, unknown>): Promise<T> {
     const qs = query ? buildQueryString(query) : "";
     const url = `${this.baseUrl}${path}${qs ? `?${qs}` : ""}`;
-    const res = await fetch(url);
+    const res = await fetch(url, { headers: this.authHeaders() });
     if (!res.ok) {
       const body = await res.text().catch(() => "");
       throw new Error(`GET ${path} failed (${res.status}): ${body}`);
@@ -104,7 +119,7 @@ export class ScreenpipeClient {
     const url = `${this.baseUrl}${path}`;
     const res = await fetch(url, {
       method: "POST",
-      headers: { "Content-Type": "application/json" },
+      headers: { "Content-Type": "application/json", ...this.authHeaders() },
       body: body !== undefined ? JSON.stringify(body) : undefined,
     });
     if (!res.ok) {
@@ -118,7 +133,7 @@ export class ScreenpipeClient {
     const url = `${this.baseUrl}${path}`;
     const res = await fetch(url, {
       method: "DELETE",
-      headers: { "Content-Type": "application/json" },
+      headers: { "Content-Type": "application/json", ...this.authHeaders() },
       body: body !== undefined ? JSON.stringify(body) : undefined,
     });
     if (!res.ok) {
@@ -131,7 +146,7 @@ export class ScreenpipeClient {
   private async getRaw(path: string, query?: Record<string, unknown>): Promise<Response> {
     const qs = query ? buildQueryString(query) : "";
     const url = `${this.baseUrl}${path}${qs ? `?${qs}` : ""}`;
-    const res = await fetch(url);
+    const res = await fetch(url, { headers: this.authHeaders() });
     if (!res.ok) {
       const body = await res.text().catch(() => "");
       throw new Error(`GET ${path} failed (${res.status}): ${body}`);
@@ -481,8 +496,10 @@ export class ScreenpipeClient {
     includeImages: boolean = false
   ): AsyncGenerator<EventStreamResponse, void, unknown> {
     const wsUrl = this.baseUrl.replace(/^http/, "ws");
+    // browsers can't set headers on websockets, the server also reads ?token=
+    const token = this.apiToken ? `&token=${encodeURIComponent(this.apiToken)}` : "";
     const ws = new WebSocket(
-      `${wsUrl}/ws/events?images=${includeImages}`
+      `${wsUrl}/ws/events?images=${includeImages}${token}`
     );
 
     await new Promise<void>((resolve, reject) => {